@@ -0,0 +1,81 @@
+//! Compares the gzipped size of a site rendered with and without canonical attribute ordering.
+//!
+//! Run with `cargo run --release --example payload_sizes`. Compression uses the system's
+//! `gzip` command.
+
+extern crate domafic;
+
+use domafic::{AttributeValue, DomNode, DomNodes, KeyValue};
+use domafic::html_writer::{HtmlWriter, Minifier, MinifyingHtmlWriter};
+use domafic::tags::{a, attributes, div, h2, li, p, ul};
+use domafic::xml_writer::{Canonical, Html, XmlWriter};
+
+use std::io::Write;
+use std::marker::PhantomData;
+use std::process::{Command, Stdio};
+
+const PAGES: usize = 200;
+const ITEMS_PER_PAGE: usize = 50;
+
+// Different components often list the same attributes in different orders
+fn item_attributes(page: usize, item: usize) -> Vec<KeyValue> {
+    let href = ("href", AttributeValue::OwnedStr(format!("/pages/{}/items/{}", page, item)));
+    let class = ("class", AttributeValue::Str(match item % 2 {
+        0 => "item link active",
+        _ => "active item link",
+    }));
+    let title = ("title", AttributeValue::Str("Open item"));
+    match (page + item) % 3 {
+        0 => vec![href, class, title],
+        1 => vec![class, title, href],
+        _ => vec![title, href, class],
+    }
+}
+
+fn page(page: usize) -> impl DomNode<()> {
+    let items: Vec<_> = (0..ITEMS_PER_PAGE).map(|item| li((
+        a((attributes(item_attributes(page, item)), format!("Item {}", item))),
+        p("  A short   description of the item.  "),
+    ))).collect();
+    div((
+        h2(format!("Page {}", page)),
+        ul(items),
+        PhantomData::<()>,
+    ))
+}
+
+fn gzipped_len(bytes: &[u8]) -> usize {
+    let mut gzip = Command::new("gzip")
+        .arg("-c")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run `gzip`");
+    gzip.stdin.take().unwrap().write_all(bytes).unwrap();
+    gzip.wait_with_output().unwrap().stdout.len()
+}
+
+fn main() {
+    let pages: Vec<_> = (0..PAGES).map(page).collect();
+
+    let mut html = Vec::new();
+    let mut canonical = Vec::new();
+    let mut minified = Minifier::new(Vec::new());
+    let mut minified_canonical = Minifier::new(Vec::new()).canonical();
+    for page in &pages {
+        page.process_all::<HtmlWriter<_>>(&mut html).unwrap();
+        page.process_all::<XmlWriter<_, Canonical<Html>>>(&mut canonical).unwrap();
+        page.process_all::<MinifyingHtmlWriter<_>>(&mut minified).unwrap();
+        page.process_all::<MinifyingHtmlWriter<_>>(&mut minified_canonical).unwrap();
+    }
+
+    println!("{:<20} {:>10} {:>10}", "output", "bytes", "gzipped");
+    for &(name, ref bytes) in &[
+        ("html", html),
+        ("canonical", canonical),
+        ("minified", minified.into_inner()),
+        ("minified canonical", minified_canonical.into_inner()),
+    ] {
+        println!("{:<20} {:>10} {:>10}", name, bytes.len(), gzipped_len(bytes));
+    }
+}
@@ -0,0 +1,160 @@
+//! Enable with the `bootstrap` feature.
+//!
+//! A server-rendered page usually needs to hand some data to the client application: the
+//! state the page was rendered from, the signed-in user, or settings which only the server
+//! knows. A `Bootstrap` collects named entries of any serde type, and embeds them in the page
+//! as a `<script type="application/json">` element, which browsers don't run, with the JSON
+//! escaped so that no value can close the element early. When rendering client-side,
+//! `Bootstrap::from_page` reads the entries back, so that the application can start from them
+//! before it's run.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::bootstrap::Bootstrap;
+//! use domafic::document::Document;
+//! use domafic::tags::div;
+//! use std::marker::PhantomData;
+//!
+//! // On the server
+//! let bootstrap = Bootstrap::new()
+//!     .with("user", &("ada", 7)).unwrap()
+//!     .with("todos", &vec!["Write </script>"]).unwrap();
+//! let mut page = Vec::new();
+//! Document::new("Todos").bootstrap(&bootstrap)
+//!     .write(&mut page, &div(PhantomData::<()>))
+//!     .unwrap();
+//! let page = String::from_utf8(page).unwrap();
+//! assert!(page.contains("<script type=\"application/json\" id=\"domafic-bootstrap\">\
+//!     {\"todos\":[\"Write \\u003c/script>\"],\"user\":[\"ada\",7]}</script>"));
+//!
+//! // On the client, `Bootstrap::from_page()` reads the same entries
+//! let bootstrap = Bootstrap::from_json(&bootstrap.to_json()).unwrap();
+//! let todos: Vec<String> = bootstrap.get("todos").unwrap();
+//! assert_eq!(todos, vec!["Write </script>".to_string()]);
+//! assert!(bootstrap.get::<u32>("settings").is_err());
+//! ```
+
+extern crate serde;
+extern crate serde_json;
+
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+use self::serde_json::{Map, Value};
+
+use document::{escape_json, Document, HeadElement};
+
+use std::error;
+use std::fmt;
+
+/// Id of the `<script>` element the entries are embedded in
+pub const SCRIPT_ID: &str = "domafic-bootstrap";
+
+/// Error returned when bootstrap data can't be read
+#[derive(Debug)]
+pub enum Error {
+    /// The data isn't valid JSON, or an entry doesn't have the requested type
+    Json(serde_json::Error),
+    /// The data isn't a JSON object
+    NotAnObject,
+    /// No entry has the given name
+    Missing(String),
+    /// The data wasn't embedded in the page
+    NotEmbedded,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Json(ref err) => write!(f, "bootstrap data was malformed: {}", err),
+            Error::NotAnObject => write!(f, "bootstrap data was not a JSON object"),
+            Error::Missing(ref name) => write!(f, "bootstrap data has no entry `{}`", name),
+            Error::NotEmbedded => write!(f, "bootstrap data was not embedded in the page"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Json(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Named entries of data handed from the server to the client
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bootstrap {
+    entries: Map<String, Value>,
+}
+
+impl Bootstrap {
+    /// An empty set of entries
+    pub fn new() -> Bootstrap {
+        Bootstrap::default()
+    }
+
+    /// Adds the entry `name`, replacing any previous entry of the same name. Returns an error
+    /// if `value` can't be encoded.
+    pub fn with<T: Serialize>(mut self, name: &str, value: &T) -> Result<Bootstrap, serde_json::Error> {
+        self.entries.insert(name.to_string(), serde_json::to_value(value)?);
+        Ok(self)
+    }
+
+    /// Parses entries encoded by `to_json`
+    pub fn from_json(json: &str) -> Result<Bootstrap, Error> {
+        match serde_json::from_str(json).map_err(Error::Json)? {
+            Value::Object(entries) => Ok(Bootstrap { entries }),
+            _ => Err(Error::NotAnObject),
+        }
+    }
+
+    /// Encodes the entries as a JSON object
+    pub fn to_json(&self) -> String {
+        Value::Object(self.entries.clone()).to_string()
+    }
+
+    /// Decodes the entry `name`
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, Error> {
+        let value = self.entries.get(name).ok_or_else(|| Error::Missing(name.to_string()))?;
+        T::deserialize(value).map_err(Error::Json)
+    }
+
+    /// Whether there is an entry `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// The element of the page's `<head>` embedding the entries
+    pub fn head_element(&self) -> HeadElement {
+        HeadElement::Json { id: SCRIPT_ID.to_string(), json: self.to_json() }
+    }
+
+    /// A `<script>` element embedding the entries, for pages which aren't written with a
+    /// `Document`
+    pub fn script_tag(&self) -> String {
+        format!("<script type=\"application/json\" id=\"{}\">{}</script>",
+            SCRIPT_ID, escape_json(&self.to_json()))
+    }
+}
+
+impl Document {
+    /// Embeds `bootstrap` in the page's `<head>`
+    pub fn bootstrap(self, bootstrap: &Bootstrap) -> Document {
+        self.head(bootstrap.head_element())
+    }
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{Bootstrap, Error, SCRIPT_ID};
+    use web_render::element_text;
+
+    impl Bootstrap {
+        /// Reads the entries embedded in the page
+        pub fn from_page() -> Result<Bootstrap, Error> {
+            Bootstrap::from_json(&element_text(SCRIPT_ID).ok_or(Error::NotEmbedded)?)
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! A busy signal for progress indicators, derived from the commands the runtime has in flight.
+//!
+//! When rendering client-side, the runtime counts the HTTP requests issued through the `JsIo`
+//! which haven't been answered yet, including those issued as `Effect`s. Requests issued with
+//! `JsIo::http_tagged` are also counted under their tag, such as `"save"`, so that part of the
+//! page can show that one kind of request is pending. Render functions read the counts with
+//! `current`, which is provided through the `context` module for every render, so a global
+//! progress bar doesn't need the application to count requests in its state.
+//!
+//! Outside of the web runtime, such as when rendering server-side, nothing is in flight unless
+//! a `Busy` is provided with `context::provide`.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::busy::{self, Busy};
+//! use domafic::context;
+//!
+//! fn status() -> &'static str {
+//!     let busy = busy::current();
+//!     if busy.is_busy_with("save") { "Saving..." }
+//!     else if busy.is_busy() { "Loading..." }
+//!     else { "" }
+//! }
+//!
+//! let mut busy = Busy::new();
+//! busy.start(None);
+//! busy.start(Some("save"));
+//! assert_eq!((busy.pending(), busy.pending_with("save")), (2, 1));
+//! assert_eq!(context::provide(busy.clone(), status), "Saving...");
+//!
+//! busy.finish(Some("save"));
+//! assert_eq!(context::provide(busy, status), "Loading...");
+//! assert_eq!(status(), "");
+//! ```
+
+use context;
+
+use std::collections::BTreeMap;
+
+/// The commands in flight, in total and for each tag
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Busy {
+    pending: usize,
+    tagged: BTreeMap<String, usize>,
+}
+
+impl Busy {
+    /// Nothing in flight
+    pub fn new() -> Busy {
+        Busy::default()
+    }
+
+    /// The number of commands in flight
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// The number of commands with `tag` in flight
+    pub fn pending_with(&self, tag: &str) -> usize {
+        self.tagged.get(tag).cloned().unwrap_or(0)
+    }
+
+    /// Whether any command is in flight
+    pub fn is_busy(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// Whether any command with `tag` is in flight
+    pub fn is_busy_with(&self, tag: &str) -> bool {
+        self.pending_with(tag) > 0
+    }
+
+    /// Counts a command which has been issued, with `tag` if it has one
+    pub fn start(&mut self, tag: Option<&str>) {
+        self.pending += 1;
+        if let Some(tag) = tag {
+            *self.tagged.entry(tag.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// Stops counting a command which has finished, which was started with the same `tag`.
+    ///
+    /// Commands which were never started are ignored.
+    pub fn finish(&mut self, tag: Option<&str>) {
+        self.pending = self.pending.saturating_sub(1);
+        if let Some(tag) = tag {
+            let finished = match self.tagged.get_mut(tag) {
+                Some(count) => { *count -= 1; *count == 0 },
+                None => false,
+            };
+            if finished {
+                self.tagged.remove(tag);
+            }
+        }
+    }
+}
+
+/// The commands in flight for the application being rendered, or nothing if no `Busy` has been
+/// provided
+pub fn current() -> Busy {
+    context::get::<Busy>().map(|busy| (*busy).clone()).unwrap_or_default()
+}
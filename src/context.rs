@@ -0,0 +1,66 @@
+//! Context makes values available to every render function called while rendering a part of
+//! the tree, without passing them through each function's arguments.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::context;
+//!
+//! struct Locale(&'static str);
+//!
+//! fn greeting() -> &'static str {
+//!     match context::get::<Locale>() {
+//!         Some(ref locale) if locale.0 == "fr" => "Bonjour",
+//!         _ => "Hello",
+//!     }
+//! }
+//!
+//! assert_eq!(greeting(), "Hello");
+//! assert_eq!(context::provide(Locale("fr"), greeting), "Bonjour");
+//! ```
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+thread_local! {
+    // Values provided by enclosing calls to `provide`, innermost last
+    static CONTEXT: RefCell<Vec<Rc<dyn Any>>> = RefCell::new(Vec::new());
+}
+
+/// Removes a provided value when `provide` returns, or when `f` panics
+struct Provided;
+
+impl Drop for Provided {
+    fn drop(&mut self) {
+        CONTEXT.with(|context| { context.borrow_mut().pop(); });
+    }
+}
+
+/// Calls `f`, with `value` available from `get` until `f` returns.
+///
+/// Values provided by inner calls to `provide` shadow values of the same type provided by
+/// outer calls.
+pub fn provide<T, R, F>(value: T, f: F) -> R
+    where T: 'static, F: FnOnce() -> R
+{
+    provide_rc(Rc::new(value), f)
+}
+
+/// Like `provide`, but shares a value which is already reference-counted
+pub fn provide_rc<T, R, F>(value: Rc<T>, f: F) -> R
+    where T: 'static, F: FnOnce() -> R
+{
+    CONTEXT.with(|context| context.borrow_mut().push(value));
+    let _provided = Provided;
+    f()
+}
+
+/// Returns the innermost value of type `T` provided by an enclosing call to `provide`
+pub fn get<T: 'static>() -> Option<Rc<T>> {
+    CONTEXT.with(|context| {
+        context.borrow().iter().rev()
+            .filter_map(|value| value.clone().downcast::<T>().ok())
+            .next()
+    })
+}
@@ -0,0 +1,98 @@
+//! Content Security Policy nonces for server-side rendering.
+//!
+//! A strict Content Security Policy only runs scripts and applies styles carrying a `nonce`
+//! attribute matching the policy. Provide the nonce generated for the current response with
+//! `provide`, and `script`, `style` and `css::style_tag` add it to the elements they create.
+//! `Document::csp_nonce` adds it to the document's own `<script>` and `<style>` elements, and
+//! emits a `<meta>` tag with the matching policy.
+//!
+//! Inline `style` attributes can't carry a nonce, and the crate writes them itself, such as
+//! for `theme::provide` and `styles::style`, and sets them from the web renderer. `policy`
+//! therefore allows inline style attributes with `style-src-attr 'unsafe-inline'`, while
+//! `<style>` elements still need the nonce. Style attributes can't run scripts, but injected
+//! markup could use them to restyle the page. Browsers which don't support `style-src-attr`
+//! fall back to `style-src`, and block the attributes.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::{AttributeValue, DomNode};
+//! use domafic::csp::{self, CspNonce};
+//!
+//! let render = || csp::script::<(), _>(vec![("src", AttributeValue::Str("/app.js"))], ());
+//!
+//! assert_eq!(render().to_string(), "<script src=\"/app.js\"></script>");
+//! assert_eq!(
+//!     csp::provide(CspNonce::new("r4nd0m"), render).to_string(),
+//!     "<script src=\"/app.js\" nonce=\"r4nd0m\"></script>"
+//! );
+//! ```
+
+use {AttributeValue, DomNodes, KeyValue};
+use context;
+use processors::EmptyListeners;
+use tags::{self, attributes, Tag};
+
+use std::rc::Rc;
+
+/// A nonce allowing a response's scripts and styles to run under a Content Security Policy.
+///
+/// Nonces must be generated randomly for each response, using at least 128 bits from a
+/// cryptographically secure source, and encoded as base64.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    /// Creates a nonce from its base64 encoding
+    pub fn new<S: Into<String>>(nonce: S) -> CspNonce {
+        CspNonce(nonce.into())
+    }
+
+    /// The nonce, as written in `nonce` attributes
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The nonce as a CSP source expression, such as `'nonce-r4nd0m'`
+    pub fn source(&self) -> String {
+        format!("'nonce-{}'", self.0)
+    }
+
+    /// A strict policy only allowing scripts and style elements with this nonce, and inline
+    /// style attributes, as described in the module documentation
+    pub fn policy(&self) -> String {
+        let source = self.source();
+        format!("script-src {} 'strict-dynamic'; style-src {}; style-src-attr 'unsafe-inline'; \
+            object-src 'none'; base-uri 'none'", source, source)
+    }
+
+    /// Returns the nonce provided by the innermost enclosing call to `provide`
+    pub fn current() -> Option<Rc<CspNonce>> {
+        context::get::<CspNonce>()
+    }
+}
+
+/// Calls `f`, adding `nonce` to the scripts and styles it creates
+pub fn provide<R, F: FnOnce() -> R>(nonce: CspNonce, f: F) -> R {
+    context::provide(nonce, f)
+}
+
+/// A `script` or `style` tag with the provided nonce
+pub type Nonced<M, C> = Tag<M, C, Vec<KeyValue>, EmptyListeners>;
+
+/// Creates a `script` tag, adding the provided nonce to `attrs`
+pub fn script<M, C: DomNodes<M>>(attrs: Vec<KeyValue>, children: C) -> Nonced<M, C> {
+    tags::script((attributes(with_nonce(attrs)), children))
+}
+
+/// Creates a `style` tag, adding the provided nonce to `attrs`
+pub fn style<M, C: DomNodes<M>>(attrs: Vec<KeyValue>, children: C) -> Nonced<M, C> {
+    tags::style((attributes(with_nonce(attrs)), children))
+}
+
+fn with_nonce(mut attrs: Vec<KeyValue>) -> Vec<KeyValue> {
+    if let Some(nonce) = CspNonce::current() {
+        attrs.push(("nonce", AttributeValue::OwnedStr(nonce.0.clone())));
+    }
+    attrs
+}
@@ -0,0 +1,343 @@
+//! Scoped CSS for components.
+//!
+//! `styled` attaches a class generated from a hash of some CSS to a `DomNode`, and rewrites
+//! the CSS's selectors so that it only applies to that node and its descendants. Each distinct
+//! block of CSS is collected once per thread, no matter how many nodes use it.
+//!
+//! When rendering server-side, include `style_tag()` in the page's `<head>` after rendering
+//! the application. When rendering client-side, newly collected CSS is injected into
+//! `document.head` after each render.
+//!
+//! Within the CSS, `&` refers to the styled node. Selectors which don't contain `&` apply to
+//! descendants of the styled node. CSS without any rules is applied to the styled node itself.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::css::{self, styled};
+//! use domafic::tags::{div, p};
+//!
+//! let card = |text: &'static str| styled::<(), _>("
+//!     & { padding: 1em; }
+//!     &:hover, p { color: red; }
+//! ", div(p(text)));
+//!
+//! let (first, second) = (card("Hello"), card("World"));
+//! assert_eq!(first.to_string(), "<div class=\"dc-6f1604ad\"><p>Hello</p></div>");
+//! assert_eq!(second.get_attribute(0), first.get_attribute(0));
+//!
+//! // The CSS was only collected once
+//! assert_eq!(
+//!     css::stylesheet(),
+//!     ".dc-6f1604ad { padding: 1em; }\n.dc-6f1604ad:hover, .dc-6f1604ad p { color: red; }"
+//! );
+//! ```
+
+use {AttributeValue, DomNode, DomNodes, DomValue, KeyValue};
+use csp::CspNonce;
+use dom_node::fold_props_hash;
+use processors::DomNodeProcessor;
+use widget::WidgetNode;
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::marker::PhantomData;
+
+struct Collected {
+    hashes: HashSet<u32>,
+    blocks: Vec<String>,
+    // Number of blocks which have already been injected into the page
+    injected: usize,
+}
+
+thread_local! {
+    static COLLECTED: RefCell<Collected> = RefCell::new(Collected {
+        hashes: HashSet::new(),
+        blocks: Vec::new(),
+        injected: 0,
+    });
+}
+
+/// Wrapper for a `DomNode` which adds a scoped CSS class
+pub struct Styled<M, T: DomNode<M>> {
+    node: T,
+    class: KeyValue,
+    // Index of the node's own `class` attribute, which `class` replaces
+    class_index: Option<usize>,
+    _marker: PhantomData<M>,
+}
+
+/// Scopes `css` to `node` by giving it a generated class, collecting the rewritten CSS.
+///
+/// If `node` already has a `class` attribute, the generated class is added to it.
+pub fn styled<M, T: DomNode<M>>(css: &str, node: T) -> Styled<M, T> {
+    let class_name = collect(css);
+    let class_index = node.attributes().position(|attr| attr.0 == "class");
+    let class = match class_index.and_then(|index| node.get_attribute(index)) {
+        Some(existing) => format!("{} {}", existing.1.as_str(), class_name),
+        None => class_name,
+    };
+    Styled {
+        node,
+        class: ("class", AttributeValue::OwnedStr(class)),
+        class_index,
+        _marker: PhantomData,
+    }
+}
+
+impl<M, T: DomNode<M>> DomNodes<M> for Styled<M, T> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M, T: DomNode<M>> DomNode<M> for Styled<M, T> {
+    type Children = T::Children;
+    type Listeners = T::Listeners;
+    type WithoutListeners = Styled<M, T::WithoutListeners>;
+    fn key(&self) -> Option<u32> { self.node.key() }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        match self.class_index {
+            Some(class_index) if index == class_index => Some(&self.class),
+            Some(_) => self.node.get_attribute(index),
+            None => self.node.get_attribute(index).or_else(|| {
+                if index == self.node.attributes().count() { Some(&self.class) } else { None }
+            }),
+        }
+    }
+    fn children(&self) -> &Self::Children {
+        self.node.children()
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        self.node.listeners()
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        self.node.children_and_listeners()
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        let (node, listeners) = self.node.split_listeners();
+        (
+            Styled {
+                node,
+                class: self.class,
+                class_index: self.class_index,
+                _marker: PhantomData,
+            },
+            listeners
+        )
+    }
+    fn value(&self) -> DomValue<'_> { self.node.value() }
+    fn props_hash(&self) -> Option<u64> { fold_props_hash(self.node.props_hash(), &self.class) }
+    fn should_update(&self, old_props_hash: u64) -> bool {
+        match self.props_hash() {
+            Some(props_hash) => props_hash != old_props_hash,
+            None => self.node.should_update(old_props_hash),
+        }
+    }
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.node.widget() }
+}
+
+impl<M, T: DomNode<M>> fmt::Display for Styled<M, T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.displayable().fmt(formatter)
+    }
+}
+
+/// Returns all of the CSS collected on this thread, in the order it was first collected
+pub fn stylesheet() -> String {
+    COLLECTED.with(|collected| collected.borrow().blocks.join("\n"))
+}
+
+/// Returns a `<style>` element containing all of the CSS collected on this thread.
+///
+/// The element has the nonce provided by `csp::provide`, if there is one.
+pub fn style_tag() -> String {
+    style_tag_with_nonce(CspNonce::current().as_ref().map(|nonce| nonce.as_str()))
+}
+
+pub(crate) fn style_tag_with_nonce(nonce: Option<&str>) -> String {
+    let nonce = match nonce {
+        Some(nonce) => format!(" nonce=\"{}\"", nonce.replace('"', "&quot;")),
+        None => String::new(),
+    };
+    // Prevent the CSS from closing the `<style>` element early
+    format!("<style data-domafic-css{}>{}</style>", nonce, stylesheet().replace("</", "<\\/"))
+}
+
+/// Forgets all of the CSS collected on this thread.
+///
+/// Call this before rendering each page server-side so that pages only include the CSS they
+/// use.
+pub fn clear() {
+    COLLECTED.with(|collected| {
+        let mut collected = collected.borrow_mut();
+        collected.hashes.clear();
+        collected.blocks.clear();
+        collected.injected = 0;
+    });
+}
+
+/// Returns the CSS collected since the last call, if there is any
+#[cfg_attr(not(all(feature = "web_render", target_os = "emscripten")), allow(dead_code))]
+pub(crate) fn take_uninjected() -> Option<String> {
+    COLLECTED.with(|collected| {
+        let mut collected = collected.borrow_mut();
+        if collected.injected == collected.blocks.len() { return None; }
+        let css = collected.blocks[collected.injected..].join("\n");
+        collected.injected = collected.blocks.len();
+        Some(css)
+    })
+}
+
+/// Adds `css` scoped to its generated class to the collected CSS, unless it has already been
+/// collected, returning the class name
+pub(crate) fn collect(css: &str) -> String {
+    let hash = fnv1a(css.as_bytes());
+    let class_name = format!("dc-{:08x}", hash);
+    COLLECTED.with(|collected| {
+        let mut collected = collected.borrow_mut();
+        if collected.hashes.insert(hash) {
+            collected.blocks.push(scope(css, &class_name));
+        }
+    });
+    class_name
+}
+
+/// 32-bit FNV-1a hash, used because it's simple and stable across platforms and releases
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193))
+}
+
+/// Rewrites the selectors in `css` to apply within elements with class `class_name`
+fn scope(css: &str, class_name: &str) -> String {
+    let class_selector = format!(".{}", class_name);
+    if !css.contains('{') {
+        return format!("{} {{ {} }}", class_selector, css.trim());
+    }
+    let mut rules = Vec::new();
+    scope_rules(css, &class_selector, &mut rules);
+    rules.join("\n")
+}
+
+fn scope_rules(css: &str, class_selector: &str, rules: &mut Vec<String>) {
+    let mut rest = css;
+    loop {
+        rest = skip_whitespace_and_comments(rest);
+        if rest.is_empty() { break; }
+
+        // Statements such as `@import` end with a semicolon rather than a block
+        let prelude_end = match find_unquoted(rest, &['{', ';']) {
+            Some(index) => index,
+            None => {
+                rules.push(rest.trim().to_string());
+                break;
+            },
+        };
+        let prelude = rest[..prelude_end].trim();
+        if rest.as_bytes()[prelude_end] == b';' {
+            rules.push(format!("{};", prelude));
+            rest = &rest[prelude_end + 1..];
+            continue;
+        }
+
+        let block_start = prelude_end + 1;
+        let block_end = matching_brace(rest, block_start).unwrap_or(rest.len());
+        let block = &rest[block_start..block_end];
+        rest = if block_end < rest.len() { &rest[block_end + 1..] } else { "" };
+
+        if prelude.starts_with('@') {
+            let is_conditional = ["@media", "@supports", "@container", "@layer"].iter()
+                .any(|at_rule| prelude.starts_with(at_rule));
+            if is_conditional {
+                let mut inner = Vec::new();
+                scope_rules(block, class_selector, &mut inner);
+                rules.push(format!("{} {{\n{}\n}}", prelude, inner.join("\n")));
+            } else {
+                // Rules such as `@keyframes` and `@font-face` don't contain selectors
+                rules.push(format!("{} {{{}}}", prelude, block));
+            }
+        } else {
+            let selectors: Vec<String> = split_selectors(prelude).into_iter()
+                .map(|selector| if selector.contains('&') {
+                    selector.replace('&', class_selector)
+                } else {
+                    format!("{} {}", class_selector, selector)
+                })
+                .collect();
+            rules.push(format!("{} {{ {} }}", selectors.join(", "), block.trim()));
+        }
+    }
+}
+
+fn skip_whitespace_and_comments(mut css: &str) -> &str {
+    loop {
+        css = css.trim_start();
+        if !css.starts_with("/*") { return css; }
+        css = match css.find("*/") {
+            Some(end) => &css[end + 2..],
+            None => "",
+        };
+    }
+}
+
+/// Finds the first of `chars` outside of a quoted string
+fn find_unquoted(css: &str, chars: &[char]) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in css.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if chars.contains(&c) => return Some(index),
+            None => {},
+        }
+    }
+    None
+}
+
+/// Finds the `}` closing the block which begins at `start`
+fn matching_brace(css: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut offset = start;
+    while let Some(index) = find_unquoted(&css[offset..], &['{', '}']) {
+        let index = offset + index;
+        if css.as_bytes()[index] == b'{' {
+            depth += 1;
+        } else if depth == 0 {
+            return Some(index);
+        } else {
+            depth -= 1;
+        }
+        offset = index + 1;
+    }
+    None
+}
+
+/// Splits a selector list on commas which aren't inside parentheses, brackets or strings
+fn split_selectors(prelude: &str) -> Vec<&str> {
+    let mut selectors = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut offset = 0;
+    while let Some(index) = find_unquoted(&prelude[offset..], &['(', '[', ')', ']', ',']) {
+        let index = offset + index;
+        match prelude.as_bytes()[index] {
+            b'(' | b'[' => depth += 1,
+            b')' | b']' => depth -= 1,
+            _ if depth == 0 => {
+                selectors.push(prelude[start..index].trim());
+                start = index + 1;
+            },
+            _ => {},
+        }
+        offset = index + 1;
+    }
+    selectors.push(prelude[start..].trim());
+    selectors.retain(|selector| !selector.is_empty());
+    selectors
+}
@@ -0,0 +1,598 @@
+//! Calendar dates, a date picker component, and nodes displaying dates.
+//!
+//! A `DatePicker` is kept in the application state, rendered with `DatePicker::render`, and
+//! passed the `DateMsg`s its nodes produce with `DatePicker::update`, which returns the date
+//! the user picked, if any. The nodes produce `DateMsg`s rather than the application's own
+//! messages, so they're embedded with `DomNode::map_message`.
+//!
+//! When rendering server-side, the picker is a plain `<input type="date">`, which the user can
+//! fill in and submit with a form before the application has started. When rendering
+//! client-side, it also has a button opening a calendar of the focused date's month. The
+//! calendar is navigated with the arrow keys, Page Up and Page Down for the previous and next
+//! month (with Shift, year), and Home and End for the start and end of the week. Enter or
+//! Space picks the focused date, and Escape closes the calendar. Dates before the picker's
+//! minimum or after its maximum can't be focused or picked.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::date_picker::{Date, DateFormat, DateMsg, DatePicker, date_text};
+//! use domafic::tags::div;
+//!
+//! enum Msg { Picker(DateMsg) }
+//!
+//! struct State { picker: DatePicker, departure: Option<Date> }
+//!
+//! fn render(state: &State) -> impl DomNode<Msg> + '_ {
+//!     div((
+//!         state.picker.render().map_message(Msg::Picker),
+//!         state.departure.map(|date| date_text(date, DateFormat::Long)),
+//!     ))
+//! }
+//!
+//! fn update(state: &mut State, msg: Msg) {
+//!     match msg {
+//!         Msg::Picker(msg) => if let Some(date) = state.picker.update(msg) {
+//!             state.departure = Some(date);
+//!         },
+//!     }
+//! }
+//!
+//! let today = Date::new(2024, 3, 5).unwrap();
+//! let mut state = State {
+//!     picker: DatePicker::new("departure", today).min(today),
+//!     departure: None,
+//! };
+//! update(&mut state, Msg::Picker(DateMsg::Select(Date::new(2024, 3, 1).unwrap())));
+//! assert_eq!(state.departure, None);
+//! update(&mut state, Msg::Picker(DateMsg::Select(Date::new(2024, 3, 8).unwrap())));
+//!
+//! assert_eq!(render(&state).displayable().to_string(), "<div>\
+//!     <div class=\"domafic-date-picker\"><input type=\"date\" id=\"departure\" name=\"departure\" \
+//!     value=\"2024-03-08\" min=\"2024-03-05\"></div>\
+//!     <time datetime=\"2024-03-08\">March 8, 2024</time></div>");
+//! ```
+
+use {DomNode, KeyValue};
+use AttributeValue::{Bool, OwnedStr, Str};
+use listener::on;
+use tags::{attributes, button, div, input, table, tbody, td, th, thead, time, tr};
+
+use std::fmt;
+
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+/// Keycodes of the keys the calendar handles
+const KEY_ENTER: u32 = 13;
+const KEY_ESCAPE: u32 = 27;
+const KEY_SPACE: u32 = 32;
+const KEY_PAGE_UP: u32 = 33;
+const KEY_PAGE_DOWN: u32 = 34;
+const KEY_END: u32 = 35;
+const KEY_HOME: u32 = 36;
+const KEY_LEFT: u32 = 37;
+const KEY_UP: u32 = 38;
+const KEY_RIGHT: u32 = 39;
+const KEY_DOWN: u32 = 40;
+
+/// A day of the week
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Weekday {
+    /// Monday
+    Monday,
+    /// Tuesday
+    Tuesday,
+    /// Wednesday
+    Wednesday,
+    /// Thursday
+    Thursday,
+    /// Friday
+    Friday,
+    /// Saturday
+    Saturday,
+    /// Sunday
+    Sunday,
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Monday, Weekday::Tuesday, Weekday::Wednesday, Weekday::Thursday,
+    Weekday::Friday, Weekday::Saturday, Weekday::Sunday,
+];
+
+impl Weekday {
+    /// Number of days after Monday
+    fn index(self) -> i64 {
+        WEEKDAYS.iter().position(|&weekday| weekday == self).unwrap() as i64
+    }
+
+    /// The English name of the day, such as `"Monday"`
+    pub fn name(self) -> &'static str {
+        match self {
+            Weekday::Monday => "Monday",
+            Weekday::Tuesday => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday => "Thursday",
+            Weekday::Friday => "Friday",
+            Weekday::Saturday => "Saturday",
+            Weekday::Sunday => "Sunday",
+        }
+    }
+}
+
+/// A date in the proleptic Gregorian calendar
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+/// Number of days in `month` of `year`, where January is 1
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    /// The date, or `None` if `month`, which starts from 1 for January, or `day` is out of range
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date { year, month, day })
+    }
+
+    /// Parses a date written as `YYYY-MM-DD`, as by `Display` and the value of an
+    /// `<input type="date">`
+    pub fn parse(date: &str) -> Option<Date> {
+        let mut parts = date.splitn(3, '-');
+        let year = parts.next()?;
+        let month = parts.next()?;
+        let day = parts.next()?;
+        if year.len() < 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+
+    /// The year
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    /// The month, starting from 1 for January
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    /// The day of the month, starting from 1
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    /// The day of the week
+    pub fn weekday(&self) -> Weekday {
+        // 1970-01-01 was a Thursday
+        WEEKDAYS[(self.days() + 3).rem_euclid(7) as usize]
+    }
+
+    /// The date `days` days later, or earlier if `days` is negative
+    pub fn add_days(self, days: i64) -> Date {
+        Date::from_days(self.days() + days)
+    }
+
+    /// The same day `months` months later, or earlier if `months` is negative, or the last day
+    /// of that month if it's shorter
+    pub fn add_months(self, months: i32) -> Date {
+        let month_index = self.year * 12 + self.month as i32 - 1 + months;
+        let year = month_index.div_euclid(12);
+        let month = month_index.rem_euclid(12) as u32 + 1;
+        Date { year, month, day: self.day.min(days_in_month(year, month)) }
+    }
+
+    /// The first day of the date's month
+    pub fn first_of_month(self) -> Date {
+        Date { day: 1, ..self }
+    }
+
+    /// The date written in `format`
+    pub fn format(&self, format: DateFormat) -> String {
+        let month_name = MONTH_NAMES[self.month as usize - 1];
+        match format {
+            DateFormat::Iso => self.to_string(),
+            DateFormat::Long => format!("{} {}, {}", month_name, self.day, self.year),
+            DateFormat::Medium => format!("{} {}, {}", &month_name[..3], self.day, self.year),
+            DateFormat::WithWeekday => format!("{}, {} {}, {}",
+                self.weekday().name(), month_name, self.day, self.year),
+        }
+    }
+
+    // Days since 1970-01-01, from Howard Hinnant's `days_from_civil`
+    fn days(&self) -> i64 {
+        let year = i64::from(self.year) - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    // Inverse of `days`
+    fn from_days(days: i64) -> Date {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+            - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year: year as i32, month, day }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// How a date is written by `Date::format` and `date_text`
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum DateFormat {
+    /// `2024-03-05`
+    Iso,
+    /// `March 5, 2024`
+    Long,
+    /// `Mar 5, 2024`
+    Medium,
+    /// `Tuesday, March 5, 2024`
+    WithWeekday,
+}
+
+/// A `<time>` element showing `date` in `format`, with its `datetime` attribute set so that
+/// the date can be read by machines
+pub fn date_text<M>(date: Date, format: DateFormat) -> impl DomNode<M> {
+    time((attributes([("datetime", OwnedStr(date.to_string()))]), date.format(format)))
+}
+
+/// The weeks of the month of `date`, starting on `week_start`. The first and last weeks are
+/// filled out with days of the neighbouring months.
+pub fn month_grid(date: Date, week_start: Weekday) -> Vec<[Date; 7]> {
+    let first = date.first_of_month();
+    let offset = (first.weekday().index() - week_start.index()).rem_euclid(7);
+    let days_shown = offset + i64::from(days_in_month(date.year, date.month));
+    let start = first.add_days(-offset);
+    (0..(days_shown + 6) / 7).map(|week| {
+        let mut days = [start; 7];
+        for (weekday, day) in days.iter_mut().enumerate() {
+            *day = start.add_days(week * 7 + weekday as i64);
+        }
+        days
+    }).collect()
+}
+
+/// A message produced by the nodes of a `DatePicker`
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum DateMsg {
+    /// The value of the input changed, to a date or to nothing
+    Input(Option<Date>),
+    /// The calendar button was clicked
+    Toggle,
+    /// A key was pressed in the calendar
+    Key {
+        /// Keycode of the key, as in `Event::which_keycode`
+        key_code: u32,
+        /// Whether the "shift" key was held
+        shift: bool,
+    },
+    /// Show the previous month
+    PreviousMonth,
+    /// Show the next month
+    NextMonth,
+    /// Pick a date
+    Select(Date),
+    /// Close the calendar
+    Close,
+}
+
+/// A date input with a calendar, constrained to dates between an optional minimum and maximum
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatePicker {
+    id: &'static str,
+    selected: Option<Date>,
+    focused: Date,
+    open: bool,
+    min: Option<Date>,
+    max: Option<Date>,
+    week_start: Weekday,
+}
+
+impl DatePicker {
+    /// A picker with no date picked, whose calendar starts at `today`. `id` is the id and name
+    /// of its input, and prefixes the ids of its other elements.
+    pub fn new(id: &'static str, today: Date) -> DatePicker {
+        DatePicker {
+            id,
+            selected: None,
+            focused: today,
+            open: false,
+            min: None,
+            max: None,
+            week_start: Weekday::Monday,
+        }
+    }
+
+    /// Starts with `date` picked
+    pub fn selected(mut self, date: Date) -> DatePicker {
+        self.selected = Some(date);
+        self.focused = date;
+        self
+    }
+
+    /// Disallows dates before `date`
+    pub fn min(mut self, date: Date) -> DatePicker {
+        self.min = Some(date);
+        self.focused = self.clamp(self.focused);
+        self
+    }
+
+    /// Disallows dates after `date`
+    pub fn max(mut self, date: Date) -> DatePicker {
+        self.max = Some(date);
+        self.focused = self.clamp(self.focused);
+        self
+    }
+
+    /// Starts the weeks of the calendar on `weekday`, rather than Monday
+    pub fn week_starts_on(mut self, weekday: Weekday) -> DatePicker {
+        self.week_start = weekday;
+        self
+    }
+
+    /// The picked date
+    pub fn value(&self) -> Option<Date> {
+        self.selected
+    }
+
+    /// The date focused in the calendar
+    pub fn focused(&self) -> Date {
+        self.focused
+    }
+
+    /// Whether the calendar is open
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Whether `date` is between the minimum and maximum
+    pub fn allows(&self, date: Date) -> bool {
+        self.min.is_none_or(|min| date >= min) && self.max.is_none_or(|max| date <= max)
+    }
+
+    fn clamp(&self, date: Date) -> Date {
+        let date = self.min.map_or(date, |min| date.max(min));
+        self.max.map_or(date, |max| date.min(max))
+    }
+
+    /// Handles a message produced by the picker's nodes, returning the date the user picked,
+    /// if they picked one which is allowed
+    pub fn update(&mut self, msg: DateMsg) -> Option<Date> {
+        match msg {
+            DateMsg::Input(date) => {
+                // The input is cleared with `None`, and dates which aren't allowed are ignored
+                match date {
+                    Some(date) if !self.allows(date) => None,
+                    Some(date) => {
+                        self.selected = Some(date);
+                        self.focused = date;
+                        Some(date)
+                    },
+                    None => {
+                        self.selected = None;
+                        None
+                    },
+                }
+            },
+            DateMsg::Toggle => {
+                self.open = !self.open;
+                if let Some(selected) = self.selected {
+                    self.focused = selected;
+                }
+                None
+            },
+            DateMsg::PreviousMonth => self.focus(self.focused.add_months(-1)),
+            DateMsg::NextMonth => self.focus(self.focused.add_months(1)),
+            DateMsg::Select(date) => self.pick(date),
+            DateMsg::Close => {
+                self.open = false;
+                None
+            },
+            DateMsg::Key { key_code, shift } => {
+                let focused = self.focused;
+                let year_or_month = if shift { 12 } else { 1 };
+                let weekday = (focused.weekday().index() - self.week_start.index()).rem_euclid(7);
+                match key_code {
+                    KEY_LEFT => self.focus(focused.add_days(-1)),
+                    KEY_RIGHT => self.focus(focused.add_days(1)),
+                    KEY_UP => self.focus(focused.add_days(-7)),
+                    KEY_DOWN => self.focus(focused.add_days(7)),
+                    KEY_PAGE_UP => self.focus(focused.add_months(-year_or_month)),
+                    KEY_PAGE_DOWN => self.focus(focused.add_months(year_or_month)),
+                    KEY_HOME => self.focus(focused.add_days(-weekday)),
+                    KEY_END => self.focus(focused.add_days(6 - weekday)),
+                    KEY_ENTER | KEY_SPACE => self.pick(focused),
+                    KEY_ESCAPE => {
+                        self.open = false;
+                        None
+                    },
+                    _ => None,
+                }
+            },
+        }
+    }
+
+    fn focus(&mut self, date: Date) -> Option<Date> {
+        self.focused = self.clamp(date);
+        None
+    }
+
+    fn pick(&mut self, date: Date) -> Option<Date> {
+        if !self.allows(date) {
+            return None;
+        }
+        self.selected = Some(date);
+        self.focused = date;
+        self.open = false;
+        Some(date)
+    }
+
+    /// Renders the picker's input, and when rendering client-side, its calendar button, and
+    /// calendar while it's open
+    pub fn render(&self) -> impl DomNode<DateMsg> + '_ {
+        let client_side = cfg!(all(feature = "web_render", target_os = "emscripten"));
+        let mut input_attributes = vec![
+            ("type", Str("date")),
+            ("id", Str(self.id)),
+            ("name", Str(self.id)),
+            ("value", OwnedStr(self.selected.map(|date| date.to_string()).unwrap_or_default())),
+        ];
+        if let Some(min) = self.min {
+            input_attributes.push(("min", OwnedStr(min.to_string())));
+        }
+        if let Some(max) = self.max {
+            input_attributes.push(("max", OwnedStr(max.to_string())));
+        }
+        let field = input((
+            attributes(input_attributes),
+            on("change", |event| DateMsg::Input(event.target_value.and_then(Date::parse))),
+        ));
+
+        let enhancements = if client_side {
+            let toggle: [KeyValue; 4] = [
+                ("type", Str("button")),
+                ("aria-haspopup", Str("dialog")),
+                ("aria-expanded", Bool(self.open)),
+                ("aria-label", Str("Choose date")),
+            ];
+            Some((
+                button((attributes(toggle), on("click", |_| DateMsg::Toggle), "\u{1f4c5}")),
+                if self.open { Some(self.render_calendar()) } else { None },
+            ))
+        } else {
+            None
+        };
+
+        div((attributes([("class", Str("domafic-date-picker"))]), (field, enhancements)))
+    }
+
+    /// Renders the calendar of the focused date's month, whether or not it's open
+    pub fn render_calendar(&self) -> impl DomNode<DateMsg> + '_ {
+        let month = self.focused.first_of_month();
+        let previous_allowed = self.min.is_none_or(|min| month > min);
+        let next_allowed = self.max.is_none_or(|max| month.add_months(1) <= max);
+        let caption_id = format!("{}-caption", self.id);
+
+        let header = div((
+            attributes([("class", Str("domafic-date-picker-header"))]),
+            (
+                button((
+                    attributes([
+                        ("type", Str("button")),
+                        ("aria-label", Str("Previous month")),
+                        ("disabled", Bool(!previous_allowed)),
+                    ]),
+                    on("click", |_| DateMsg::PreviousMonth),
+                    "\u{2039}",
+                )),
+                div((
+                    attributes([("id", OwnedStr(caption_id.clone())), ("aria-live", Str("polite"))]),
+                    format!("{} {}", MONTH_NAMES[month.month as usize - 1], month.year),
+                )),
+                button((
+                    attributes([
+                        ("type", Str("button")),
+                        ("aria-label", Str("Next month")),
+                        ("disabled", Bool(!next_allowed)),
+                    ]),
+                    on("click", |_| DateMsg::NextMonth),
+                    "\u{203a}",
+                )),
+            ),
+        ));
+
+        let weekdays: Vec<_> = (0..7).map(|offset| {
+            let weekday = WEEKDAYS[((self.week_start.index() + offset) % 7) as usize];
+            th((
+                attributes([("scope", Str("col")), ("abbr", Str(weekday.name()))]),
+                &weekday.name()[..2],
+            ))
+        }).collect();
+
+        let weeks: Vec<_> = month_grid(self.focused, self.week_start).into_iter().map(|week| {
+            let days: Vec<_> = week.iter().map(|&date| self.render_day(date)).collect();
+            tr(days)
+        }).collect();
+
+        let grid_attributes = vec![
+            ("role", Str("grid")),
+            ("tabindex", Str("0")),
+            ("aria-labelledby", OwnedStr(caption_id)),
+            ("aria-activedescendant", OwnedStr(self.day_id(self.focused))),
+        ];
+        let grid = table((
+            attributes(grid_attributes),
+            on("keydown", |event| DateMsg::Key {
+                key_code: event.which_keycode as u32,
+                shift: event.shift_key,
+            }),
+            (thead(tr(weekdays)), tbody(weeks)),
+        ));
+
+        div((
+            attributes([
+                ("class", Str("domafic-date-picker-calendar")),
+                ("role", Str("dialog")),
+                ("aria-label", Str("Choose date")),
+            ]),
+            (header, grid),
+        ))
+    }
+
+    fn day_id(&self, date: Date) -> String {
+        format!("{}-{}", self.id, date)
+    }
+
+    fn render_day(&self, date: Date) -> impl DomNode<DateMsg> {
+        let mut classes = Vec::new();
+        if date.month != self.focused.month {
+            classes.push("outside");
+        }
+        if date == self.focused {
+            classes.push("focused");
+        }
+        let day_attributes = vec![
+            ("id", OwnedStr(self.day_id(date))),
+            ("class", OwnedStr(classes.join(" "))),
+            ("aria-selected", Bool(self.selected == Some(date))),
+            ("aria-disabled", Bool(!self.allows(date))),
+        ];
+        td((
+            attributes(day_attributes),
+            on("click", move |_| DateMsg::Select(date)),
+            date.day.to_string(),
+        ))
+    }
+}
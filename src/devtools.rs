@@ -0,0 +1,301 @@
+//! The protocol used to describe a running application to browser devtools extensions.
+//!
+//! In debug builds, after every render, the web renderer checks for a JS global named
+//! `__DOMAFIC_DEVTOOLS_HOOK__`. If an extension has defined it, the renderer calls its `update`
+//! method with a JSON description of the application, and posts the same description to the
+//! page's window as a message of the form `{ source: "domafic-devtools", payload: "..." }`.
+//!
+//! The description is an object with the fields:
+//!
+//! - `version`: `PROTOCOL_VERSION`
+//! - `tree`: the rendered nodes, each an object with a `tag`, `text`, `comment` or `doctype`
+//!   field, along with `keys` (the node's key stack), `attributes`, `listeners` (the number of
+//!   listeners) and `children`
+//! - `messages`: the most recently delivered messages, oldest first, each with a `sequence`
+//!   number, the message's `type`, and the `keys` of the node which produced it
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::devtools::{to_json, MessageRecord, MessageRing, NodeSnapshot};
+//! use domafic::tags::{button, div};
+//! use domafic::listener::on;
+//!
+//! let tree = div(button((on("click", |_| ()), "+")));
+//! let mut messages = MessageRing::new(2);
+//! for sequence in 0..3 {
+//!     messages.push(MessageRecord { sequence, message_type: "()", keys: vec![] });
+//! }
+//!
+//! assert_eq!(
+//!     to_json(&NodeSnapshot::of(&tree), &messages),
+//!     "{\"version\":1,\"tree\":[\
+//!     {\"tag\":\"div\",\"keys\":[],\"attributes\":{},\"listeners\":0,\"children\":[\
+//!     {\"tag\":\"button\",\"keys\":[],\"attributes\":{},\"listeners\":1,\"children\":[\
+//!     {\"text\":\"+\",\"keys\":[],\"attributes\":{},\"listeners\":0,\"children\":[]}]}]}],\
+//!     \"messages\":[\
+//!     {\"sequence\":1,\"type\":\"()\",\"keys\":[]},\
+//!     {\"sequence\":2,\"type\":\"()\",\"keys\":[]}]}"
+//! );
+//! ```
+
+use {DomNode, DomNodes, DomValue, KeyValue, Listener};
+use processors::{DomNodeProcessor, EmptyListeners, Listeners, ListenerProcessor};
+
+use std::collections::VecDeque;
+use std::collections::vec_deque;
+use std::fmt::Write;
+
+/// Version of the JSON description, incremented when its format changes incompatibly
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A fixed-capacity buffer which keeps only the most recently pushed values
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageRing<T> {
+    capacity: usize,
+    entries: VecDeque<T>,
+}
+
+impl<T> MessageRing<T> {
+    /// Creates an empty buffer which holds at most `capacity` values
+    pub fn new(capacity: usize) -> MessageRing<T> {
+        MessageRing { capacity, entries: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Adds a value, dropping the oldest value if the buffer is full
+    pub fn push(&mut self, value: T) {
+        if self.capacity == 0 { return; }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(value);
+    }
+
+    /// The number of values in the buffer
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the values in the buffer, oldest first
+    pub fn iter(&self) -> vec_deque::Iter<'_, T> {
+        self.entries.iter()
+    }
+}
+
+/// A message delivered to the application's updater
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct MessageRecord {
+    /// Number of messages delivered before this one
+    pub sequence: u64,
+    /// Name of the message's type
+    pub message_type: &'static str,
+    /// Key stack of the node whose listener produced the message
+    pub keys: Vec<u32>,
+}
+
+/// The contents of a rendered node
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum SnapshotValue {
+    /// An element with the given tag
+    Tag(&'static str),
+    /// A text node
+    Text(String),
+    /// A comment
+    Comment(String),
+    /// A document type declaration with the given name
+    Doctype(String),
+}
+
+/// A description of a rendered node and its descendants.
+///
+/// Snapshots own their contents and are `Send`, and are themselves `DomNode`s without
+/// listeners, which render the same as the nodes they describe.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct NodeSnapshot {
+    /// The node's tag or text
+    pub value: SnapshotValue,
+    /// The node's own key
+    pub key: Option<u32>,
+    /// Keys of the node and its ancestors, outermost first
+    pub keys: Vec<u32>,
+    /// The node's attributes
+    pub attributes: Vec<KeyValue>,
+    /// Number of listeners attached to the node
+    pub listeners: usize,
+    /// Descriptions of the node's children
+    pub children: Vec<NodeSnapshot>,
+}
+
+impl NodeSnapshot {
+    /// Describes `nodes` and their descendants
+    pub fn of<M, T: DomNodes<M>>(nodes: &T) -> Vec<NodeSnapshot> {
+        let mut acc = (Vec::new(), Vec::new());
+        nodes.process_all::<Snapshotter>(&mut acc).unwrap();
+        acc.1
+    }
+
+    fn write_json(&self, json: &mut String) {
+        json.push('{');
+        match self.value {
+            SnapshotValue::Tag(tag) => { json.push_str("\"tag\":"); write_string(json, tag); },
+            SnapshotValue::Text(ref text) => { json.push_str("\"text\":"); write_string(json, text); },
+            SnapshotValue::Comment(ref text) => { json.push_str("\"comment\":"); write_string(json, text); },
+            SnapshotValue::Doctype(ref name) => { json.push_str("\"doctype\":"); write_string(json, name); },
+        }
+        json.push_str(",\"keys\":");
+        write_keys(json, &self.keys);
+        json.push_str(",\"attributes\":{");
+        for (i, &(key, ref value)) in self.attributes.iter().enumerate() {
+            if i > 0 { json.push(','); }
+            write_string(json, key);
+            json.push(':');
+            write_string(json, value.as_str());
+        }
+        write!(json, "}},\"listeners\":{},\"children\":[", self.listeners).unwrap();
+        for (i, child) in self.children.iter().enumerate() {
+            if i > 0 { json.push(','); }
+            child.write_json(json);
+        }
+        json.push_str("]}");
+    }
+}
+
+impl<M> DomNodes<M> for NodeSnapshot {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+static EMPTY_LISTN_REF: &EmptyListeners = &EmptyListeners;
+
+impl<M> DomNode<M> for NodeSnapshot {
+    type Children = Vec<NodeSnapshot>;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = NodeSnapshot;
+    fn key(&self) -> Option<u32> { self.key }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.attributes.get(index)
+    }
+    fn children(&self) -> &Self::Children {
+        &self.children
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (&self.children, EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> {
+        match self.value {
+            SnapshotValue::Tag(tag) => DomValue::Element { tag },
+            SnapshotValue::Text(ref text) => DomValue::Text(text),
+            SnapshotValue::Comment(ref text) => DomValue::Comment(text),
+            SnapshotValue::Doctype(ref name) => DomValue::Doctype(name),
+        }
+    }
+}
+
+/// Encodes the description of an application sent to devtools extensions
+pub fn to_json(tree: &[NodeSnapshot], messages: &MessageRing<MessageRecord>) -> String {
+    let mut json = format!("{{\"version\":{},\"tree\":[", PROTOCOL_VERSION);
+    for (i, node) in tree.iter().enumerate() {
+        if i > 0 { json.push(','); }
+        node.write_json(&mut json);
+    }
+    json.push_str("],\"messages\":[");
+    for (i, message) in messages.iter().enumerate() {
+        if i > 0 { json.push(','); }
+        write!(json, "{{\"sequence\":{},\"type\":", message.sequence).unwrap();
+        write_string(&mut json, message.message_type);
+        json.push_str(",\"keys\":");
+        write_keys(&mut json, &message.keys);
+        json.push('}');
+    }
+    json.push_str("]}");
+    json
+}
+
+fn write_keys(json: &mut String, keys: &[u32]) {
+    json.push('[');
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 { json.push(','); }
+        write!(json, "{}", key).unwrap();
+    }
+    json.push(']');
+}
+
+pub(crate) fn write_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Processor building `NodeSnapshot`s, accumulating the key stack of the current node's
+/// parent and the snapshots of its siblings
+struct Snapshotter;
+impl<'a, M> DomNodeProcessor<'a, M> for Snapshotter {
+    type Acc = (Vec<u32>, Vec<NodeSnapshot>);
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(acc: &mut (Vec<u32>, Vec<NodeSnapshot>), node: &T) -> Result<(), ()> {
+            let mut keys = acc.0.clone();
+            keys.extend(node.key());
+
+            let mut listeners = 0;
+            node.listeners().process_all::<ListenerCounter>(&mut listeners)?;
+
+            let mut children = (keys.clone(), Vec::new());
+            node.children().process_all::<Snapshotter>(&mut children)?;
+
+            acc.1.push(NodeSnapshot {
+                value: match node.value() {
+                    DomValue::Element { tag } => SnapshotValue::Tag(tag),
+                    DomValue::Comment(text) => SnapshotValue::Comment(text.to_string()),
+                    DomValue::Doctype(name) => SnapshotValue::Doctype(name.to_string()),
+                    text => SnapshotValue::Text(text.into_text().unwrap()),
+                },
+                key: node.key(),
+                keys,
+                attributes: node.merged_attributes().map(|attr| attr.into_owned()).collect(),
+                listeners,
+                children: children.1,
+            });
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
+
+/// Processor counting listeners
+pub(crate) struct ListenerCounter;
+impl<'a, M> ListenerProcessor<'a, M> for ListenerCounter {
+    type Acc = usize;
+    type Error = ();
+
+    fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn count<L>(count: &mut usize, _listener: &L) -> Result<(), ()> {
+            *count += 1;
+            Ok(())
+        }
+        count::<L>
+    }
+}
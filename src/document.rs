@@ -0,0 +1,325 @@
+//! A builder for complete HTML pages.
+//!
+//! A `Document` holds everything outside of the application's own `DomNode`: the page's
+//! language, title, and the elements of its `<head>`. `Document::write` writes the full page
+//! with the `DomNode` as the contents of `<body>`, including any CSS collected by the `css`
+//! module while building the node.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::document::Document;
+//! use domafic::tags::h1;
+//! use std::marker::PhantomData;
+//!
+//! let mut page = Vec::new();
+//! Document::new("Home")
+//!     .lang("en")
+//!     .meta("description", "Welcome")
+//!     .stylesheet("/style.css")
+//!     .write(&mut page, &h1(("Hello", PhantomData::<()>)))
+//!     .unwrap();
+//!
+//! assert_eq!(String::from_utf8(page).unwrap(), "\
+//!     <!DOCTYPE html><html lang=\"en\"><head><meta charset=\"utf-8\">\
+//!     <title>Home</title>\
+//!     <meta name=\"description\" content=\"Welcome\">\
+//!     <link rel=\"stylesheet\" href=\"/style.css\">\
+//!     </head><body><h1>Hello</h1></body></html>");
+//! ```
+
+#[cfg(all(feature = "logging", debug_assertions))]
+extern crate log;
+
+use DomNode;
+use csp::CspNonce;
+use css;
+use ids;
+use nesting;
+use router::RouteTitle;
+use sri::Integrity;
+use xml_writer::{write_attribute, write_escaped};
+
+use std::fmt;
+use std::io;
+
+/// An element of a document's `<head>`
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum HeadElement {
+    /// `<meta name="..." content="...">`
+    Meta {
+        /// Name of the metadata, such as `description`
+        name: String,
+        /// Value of the metadata
+        content: String,
+    },
+    /// `<meta property="..." content="...">`, as used by Open Graph
+    Property {
+        /// Name of the property, such as `og:title`
+        property: String,
+        /// Value of the property
+        content: String,
+    },
+    /// `<link rel="..." href="...">`
+    Link {
+        /// Relationship of the linked resource, such as `stylesheet` or `canonical`
+        rel: String,
+        /// URL of the linked resource
+        href: String,
+        /// Hash the linked resource must match
+        integrity: Option<Integrity>,
+    },
+    /// `<script src="..."></script>`
+    Script {
+        /// URL of the script
+        src: String,
+        /// Hash the script must match
+        integrity: Option<Integrity>,
+    },
+    /// `<script type="application/json" id="...">...</script>`, embedding data for the
+    /// client, which browsers don't run
+    Json {
+        /// Id of the element, which the client finds the data by
+        id: String,
+        /// The data, encoded as JSON
+        json: String,
+    },
+}
+
+/// Problems with a page's body which browsers work around silently, as found by
+/// `Document::validate`
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Validation {
+    /// Nodes which browsers would move while parsing the page, as described in the `nesting`
+    /// module
+    pub nesting: Vec<nesting::Violation>,
+    /// Ids given to more than one element, as described in the `ids` module
+    pub duplicate_ids: Vec<ids::DuplicateId>,
+}
+
+impl Validation {
+    /// Whether the body has no problems
+    pub fn is_empty(&self) -> bool {
+        self.nesting.is_empty() && self.duplicate_ids.is_empty()
+    }
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut separator = "";
+        for violation in &self.nesting {
+            write!(f, "{}invalid nesting: {}", separator, violation)?;
+            separator = "; ";
+        }
+        for duplicate in &self.duplicate_ids {
+            write!(f, "{}duplicate id: {}", separator, duplicate)?;
+            separator = "; ";
+        }
+        Ok(())
+    }
+}
+
+/// Builder for a complete HTML page
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Document {
+    /// Title of the page
+    pub title: String,
+    /// Language of the page, such as `en`
+    pub lang: Option<String>,
+    /// Elements of the page's `<head>`, after its `<title>`
+    pub head: Vec<HeadElement>,
+    /// Nonce for the page's Content Security Policy
+    pub csp_nonce: Option<CspNonce>,
+}
+
+impl Document {
+    /// Creates a document with the given title
+    pub fn new<T: Into<String>>(title: T) -> Document {
+        Document { title: title.into(), lang: None, head: Vec::new(), csp_nonce: None }
+    }
+
+    /// Creates a document titled with the title of `route`, as `router::with_title` titles the
+    /// page when rendering client-side
+    pub fn for_route<R: RouteTitle<S>, S>(route: &R, state: &S) -> Document {
+        Document::new(route.title(state))
+    }
+
+    /// Sets the language of the page
+    pub fn lang<L: Into<String>>(mut self, lang: L) -> Document {
+        self.lang = Some(lang.into());
+        self
+    }
+
+    /// Adds an element to the page's `<head>`
+    pub fn head(mut self, element: HeadElement) -> Document {
+        self.head.push(element);
+        self
+    }
+
+    /// Adds a `<meta name="..." content="...">` element
+    pub fn meta<N: Into<String>, C: Into<String>>(self, name: N, content: C) -> Document {
+        self.head(HeadElement::Meta { name: name.into(), content: content.into() })
+    }
+
+    /// Adds a `<meta property="..." content="...">` element
+    pub fn property<P: Into<String>, C: Into<String>>(self, property: P, content: C) -> Document {
+        self.head(HeadElement::Property { property: property.into(), content: content.into() })
+    }
+
+    /// Adds a `<link rel="..." href="...">` element
+    pub fn link<R: Into<String>, H: Into<String>>(self, rel: R, href: H) -> Document {
+        self.head(HeadElement::Link { rel: rel.into(), href: href.into(), integrity: None })
+    }
+
+    /// Adds a stylesheet
+    pub fn stylesheet<H: Into<String>>(self, href: H) -> Document {
+        self.link("stylesheet", href)
+    }
+
+    /// Adds a stylesheet which the browser only applies if its contents match `integrity`
+    pub fn stylesheet_with_integrity<H: Into<String>>(self, href: H, integrity: Integrity) -> Document {
+        self.head(HeadElement::Link {
+            rel: "stylesheet".to_string(),
+            href: href.into(),
+            integrity: Some(integrity),
+        })
+    }
+
+    /// Adds a script
+    pub fn script<S: Into<String>>(self, src: S) -> Document {
+        self.head(HeadElement::Script { src: src.into(), integrity: None })
+    }
+
+    /// Adds a script which the browser only runs if its contents match `integrity`
+    pub fn script_with_integrity<S: Into<String>>(self, src: S, integrity: Integrity) -> Document {
+        self.head(HeadElement::Script { src: src.into(), integrity: Some(integrity) })
+    }
+
+    /// Emits a `<meta>` tag with a strict Content Security Policy allowing only scripts and
+    /// styles with `nonce`, and adds `nonce` to the document's `<script>` and `<style>` elements.
+    ///
+    /// The body's scripts and styles should be created with `csp::provide` and the same nonce.
+    pub fn csp_nonce(mut self, nonce: CspNonce) -> Document {
+        self.csp_nonce = Some(nonce);
+        self
+    }
+
+    /// Finds the problems with `body` which browsers would work around silently, such as by
+    /// moving elements while parsing the page or only finding the first element with an id, so that tests can assert that there are none.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use domafic::document::Document;
+    /// use domafic::tags::{div, p};
+    /// use std::marker::PhantomData;
+    ///
+    /// let body = p(div(("Block", PhantomData::<()>)));
+    /// let validation = Document::new("Page").validate(&body);
+    /// assert_eq!(validation.to_string(),
+    ///     "invalid nesting: `div` at 0.0 inside `p` closes the enclosing `p`");
+    /// ```
+    pub fn validate<M, T: DomNode<M>>(&self, body: &T) -> Validation {
+        Validation { nesting: nesting::check(body), duplicate_ids: ids::check(body) }
+    }
+
+    /// Writes the page with `body` as the contents of its `<body>`.
+    ///
+    /// CSS collected by the `css` module on this thread is included in a `<style>` element at
+    /// the end of the `<head>`. If the document has no nonce of its own, its `<script>` and
+    /// `<style>` elements have the nonce provided by `csp::provide`, if there is one.
+    ///
+    /// In debug builds with the `logging` feature, the problems found by `validate` are logged
+    /// as warnings. `body` is written as it is either way, since it may come from the page's
+    /// users. Only IO errors are returned.
+    pub fn write<W, M, T>(&self, w: &mut W, body: &T) -> io::Result<()>
+        where W: io::Write, T: DomNode<M>
+    {
+        #[cfg(all(feature = "logging", debug_assertions))]
+        {
+            let validation = self.validate(body);
+            for violation in validation.nesting {
+                log::warn!("invalid nesting in page body: {}", violation);
+            }
+            for duplicate in validation.duplicate_ids {
+                log::warn!("duplicate id in page body: {}", duplicate);
+            }
+        }
+        write!(w, "<!DOCTYPE html><html")?;
+        if let Some(ref lang) = self.lang {
+            write_attribute(w, "lang", lang)?;
+        }
+        write!(w, "><head><meta charset=\"utf-8\">")?;
+        let provided = CspNonce::current();
+        if let Some(ref nonce) = self.csp_nonce {
+            write!(w, "<meta http-equiv=\"Content-Security-Policy\"")?;
+            write_attribute(w, "content", &nonce.policy())?;
+            write!(w, ">")?;
+        }
+        let nonce = self.csp_nonce.as_ref().or(provided.as_deref())
+            .map(|nonce| nonce.as_str());
+        write!(w, "<title>")?;
+        write_escaped(w, &self.title)?;
+        write!(w, "</title>")?;
+        for element in &self.head {
+            match *element {
+                HeadElement::Meta { ref name, ref content } => {
+                    write!(w, "<meta")?;
+                    write_attribute(w, "name", name)?;
+                    write_attribute(w, "content", content)?;
+                    write!(w, ">")?;
+                },
+                HeadElement::Property { ref property, ref content } => {
+                    write!(w, "<meta")?;
+                    write_attribute(w, "property", property)?;
+                    write_attribute(w, "content", content)?;
+                    write!(w, ">")?;
+                },
+                HeadElement::Link { ref rel, ref href, ref integrity } => {
+                    write!(w, "<link")?;
+                    write_attribute(w, "rel", rel)?;
+                    write_attribute(w, "href", href)?;
+                    write_integrity(w, integrity)?;
+                    write!(w, ">")?;
+                },
+                HeadElement::Script { ref src, ref integrity } => {
+                    write!(w, "<script")?;
+                    write_attribute(w, "src", src)?;
+                    write_integrity(w, integrity)?;
+                    if let Some(nonce) = nonce {
+                        write_attribute(w, "nonce", nonce)?;
+                    }
+                    write!(w, "></script>")?;
+                },
+                HeadElement::Json { ref id, ref json } => {
+                    write!(w, "<script")?;
+                    write_attribute(w, "type", "application/json")?;
+                    write_attribute(w, "id", id)?;
+                    write!(w, ">{}</script>", escape_json(json))?;
+                },
+            }
+        }
+        if !css::stylesheet().is_empty() {
+            write!(w, "{}", css::style_tag_with_nonce(nonce))?;
+        }
+        write!(w, "</head><body>")?;
+        body.write_html(w)?;
+        write!(w, "</body></html>")
+    }
+}
+
+/// Writes an `integrity` attribute, along with the `crossorigin` attribute browsers require to
+/// check the integrity of resources from other origins
+fn write_integrity<W: io::Write>(w: &mut W, integrity: &Option<Integrity>) -> io::Result<()> {
+    if let Some(ref integrity) = *integrity {
+        write_attribute(w, "integrity", integrity.as_str())?;
+        write_attribute(w, "crossorigin", "anonymous")?;
+    }
+    Ok(())
+}
+
+/// Escapes JSON embedded in a `<script>` element, so that it can't close the element early or
+/// start a comment. `<` is only valid within JSON strings, where it can be written as `\u003c`.
+pub(crate) fn escape_json(json: &str) -> String {
+    json.replace('<', "\\u003c")
+}
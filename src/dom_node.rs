@@ -1,7 +1,13 @@
-use processors::{DomNodes, DomNodeProcessor, Listeners, EmptyListeners};
-use KeyValue;
+use listener::{Event, Listener};
+use processors::{DomNodes, DomNodeProcessor, Listeners, ListenerProcessor, EmptyListeners};
+use {AttributeValue, KeyValue};
+#[cfg(any(feature = "use_std", test))]
+use static_node::Precompiled;
+#[cfg(any(feature = "use_std", test))]
+use widget::WidgetNode;
 
 use opt_std::marker::PhantomData;
+use opt_std::{mem, ptr};
 
 /// A `DomNode` specifies the HTML DOM (Document Object Model) representation of a type.
 ///
@@ -96,6 +102,39 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
         AttributeIter { node: self, index: 0, _marker: PhantomData }
     }
 
+    /// Returns an iterator over a `DomNode`'s attributes as they're written to the page, with
+    /// repeated `class` and `style` attributes merged into the first of them.
+    ///
+    /// Layers of attributes added with `with_attributes` or attribute groups can each give a
+    /// `class` or `style`. Classes are joined with spaces, and style declarations with
+    /// semicolons, in the order the attributes appear, so later declarations take precedence.
+    /// Other repeated attributes are returned unchanged.
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::tags::div;
+    /// use domafic::AttributeValue::Str;
+    /// use std::marker::PhantomData;
+    ///
+    /// let node = div(PhantomData::<()>)
+    ///     .with_attributes([("class", Str("card")), ("style", Str("color: red;"))])
+    ///     .with_attributes([("class", Str("selected")), ("style", Str("margin: 0"))]);
+    ///
+    /// let attributes: Vec<_> = node.merged_attributes()
+    ///     .map(|attr| (attr.0, attr.1.as_str().to_string()))
+    ///     .collect();
+    /// assert_eq!(attributes, vec![
+    ///     ("class", "selected card".to_string()),
+    ///     ("style", "margin: 0; color: red".to_string()),
+    /// ]);
+    ///```
+    #[cfg(any(feature = "use_std", test))]
+    fn merged_attributes(&self) -> MergedAttributes<'_, Message, Self> {
+        MergedAttributes { node: self, index: 0, _marker: PhantomData }
+    }
+
     /// Wrap the `DomNode` in an additional set of attributes.
     ///
     /// Example:
@@ -119,6 +158,57 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
         WithAttributes { node: self, attributes: attributes, _marker: PhantomData }
     }
 
+    /// Wrap the `DomNode` in an `id` attribute, which takes the place of any id the node
+    /// already has.
+    ///
+    /// Ids should be unique within the page. The `ids` module can check a tree for ids used
+    /// more than once.
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::tags::{div, h2};
+    /// use domafic::AttributeValue::Str;
+    /// use std::marker::PhantomData;
+    ///
+    /// let heading = h2(("Intro", PhantomData::<()>)).with_id("intro");
+    /// assert_eq!(heading.get_attribute(0), Some(&("id", Str("intro"))));
+    ///
+    /// let section = div(PhantomData::<()>).with_id(format!("section-{}", 2));
+    /// assert_eq!(section.get_attribute(0).unwrap().1.as_str(), "section-2");
+    ///```
+    fn with_id<I: Into<AttributeValue>>(self, id: I) -> WithAttributes<Message, Self, [KeyValue; 1]> {
+        self.with_attributes([("id", id.into())])
+    }
+
+    /// Wrap the `DomNode` in an attribute giving `class`, which the web renderer adds to the
+    /// element's classes while the pointer is over it, as described in the `interaction`
+    /// module.
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::interaction::HOVER_CLASS_ATTRIBUTE;
+    /// use domafic::tags::li;
+    /// use domafic::AttributeValue::Str;
+    /// use std::marker::PhantomData;
+    ///
+    /// let row = li(PhantomData::<()>).with_hover_class("hovered");
+    /// assert_eq!(row.get_attribute(0), Some(&(HOVER_CLASS_ATTRIBUTE, Str("hovered"))));
+    ///```
+    fn with_hover_class(self, class: &'static str) -> WithAttributes<Message, Self, [KeyValue; 1]> {
+        self.with_attributes([(::interaction::HOVER_CLASS_ATTRIBUTE, AttributeValue::Str(class))])
+    }
+
+    /// Wrap the `DomNode` in an attribute giving `class`, which the web renderer adds to the
+    /// element's classes while it or one of its descendants has focus, as described in the
+    /// `interaction` module.
+    fn with_focus_within_class(self, class: &'static str) -> WithAttributes<Message, Self, [KeyValue; 1]> {
+        self.with_attributes([(::interaction::FOCUS_WITHIN_CLASS_ATTRIBUTE, AttributeValue::Str(class))])
+    }
+
     /// Wrap the `DomNode` in an additional set of liseners.
     ///
     /// Example:
@@ -145,9 +235,102 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
         }
     }
 
-    // TODO once type ATCs land
-    // type Mapped<Mapper: Map<In=Self::Message>>: DomNode<Message=Mapper::Out>
-    // fn map_listeners<Mapper: Map<In=Self::Message>>(self) -> Mapped<Mapper>
+    /// Wrap the `DomNode` so that the web renderer only updates it, along with its descendants,
+    /// when `should_update` returns `true`.
+    ///
+    /// `should_update` is called with the hashes of the `props` the node was last rendered with
+    /// and of the new `props`. When it returns `false`, the node's attributes and children are
+    /// left as they were in the DOM, which is useful for regions of the page managed by other
+    /// libraries. Skipped subtrees must have the same structure as when they were last
+    /// updated, so that their listeners can still be found.
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::tags::div;
+    /// use std::marker::PhantomData;
+    ///
+    /// let points = vec![(0, 1), (1, 3), (2, 2)];
+    ///
+    /// // Only redraw the chart when the points change
+    /// let chart = div(PhantomData::<()>).with_should_update(&points, |old, new| old != new);
+    ///
+    /// assert!(!chart.should_update(chart.props_hash().unwrap()));
+    ///```
+    #[cfg(any(feature = "use_std", test))]
+    fn with_should_update<P, F>(self, props: &P, should_update: F) -> WithShouldUpdate<Message, Self, F>
+        where P: ::std::hash::Hash, F: Fn(u64, u64) -> bool
+    {
+        use std::hash::Hasher;
+        let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+        props.hash(&mut hasher);
+        WithShouldUpdate {
+            node: self,
+            props_hash: hasher.finish(),
+            should_update,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Hash of the props the node was rendered from, if it was created with
+    /// `with_should_update`.
+    ///
+    /// Wrappers which add to the node's output, such as `WithAttributes`, fold what they add
+    /// into the hash, and are updated whenever the result changes.
+    fn props_hash(&self) -> Option<u64> { None }
+
+    /// Whether the web renderer should update the node and its descendants, given the hash of
+    /// the props it was last rendered with.
+    ///
+    /// Nodes without a props hash are passed `0`, so that those which are never updated, such
+    /// as `uncontrolled` regions, can decline without claiming that their output is unchanged.
+    fn should_update(&self, _old_props_hash: u64) -> bool { true }
+
+    /// The widget managing the node's element, if it was created with `widget::widget`
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { None }
+
+    /// The node's precompiled HTML, if it's a `static_node::StaticNode`.
+    ///
+    /// Wrappers which change the node's HTML, such as `WithAttributes`, return `None`.
+    #[cfg(any(feature = "use_std", test))]
+    fn precompiled(&self) -> Option<&Precompiled> { None }
+
+    /// Wrap the `DomNode` so that the messages produced by its listeners, and those of its
+    /// descendants, are passed through `f`.
+    ///
+    /// This lets a component with its own message type be used in the view of a parent with
+    /// another. `f` can't capture any state, which is checked at compile time, so that the
+    /// wrapped nodes are views of the originals rather than copies: an enum variant or a
+    /// closure without captures works. Keys can be used to tell apart messages from several
+    /// copies of the same component.
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::listener::on;
+    /// use domafic::tags::{button, div};
+    ///
+    /// enum CounterMsg { Increment }
+    /// enum ParentMsg { Counter(CounterMsg), Reset }
+    ///
+    /// fn counter(count: u32) -> impl DomNode<CounterMsg> {
+    ///     button(count.to_string()).with_listeners(on("click", |_| CounterMsg::Increment))
+    /// }
+    ///
+    /// let _view = div((
+    ///     counter(3).map_message(ParentMsg::Counter),
+    ///     button("Reset").with_listeners(on("click", |_| ParentMsg::Reset)),
+    /// ));
+    ///```
+    fn map_message<Mapped, F>(self, _f: F) -> MessageMap<Mapped, Message, Self, F>
+        where F: Fn(Message) -> Mapped + Copy
+    {
+        let () = Stateless::<F>::CHECK;
+        MessageMap(self, PhantomData)
+    }
 
     /// Returns a reference to the children of this `DomNode`
     fn children(&self) -> &Self::Children;
@@ -173,6 +356,30 @@ pub trait DomNode<Message>: DomNodes<Message> + Sized {
         use html_writer::HtmlWriter;
         self.process_all::<HtmlWriter<W>>(writer)
     }
+
+    /// Returns an owned copy of the node and its descendants, without their listeners.
+    ///
+    /// The copy is `Send`, so it can be rendered to HTML on another thread, cached, or compared
+    /// with an earlier render, and is itself a `DomNode`, whose HTML is the same as this node's.
+    ///
+    /// Example:
+    ///
+    ///```rust
+    /// use domafic::DomNode;
+    /// use domafic::listener::on;
+    /// use domafic::tags::{button, div};
+    /// use std::thread;
+    ///
+    /// let page = div(button((on("click", |_| ()), "Save")));
+    /// let snapshot = page.snapshot();
+    ///
+    /// let html = thread::spawn(move || DomNode::<()>::displayable(&snapshot).to_string());
+    /// assert_eq!(html.join().unwrap(), "<div><button>Save</button></div>");
+    ///```
+    #[cfg(any(feature = "use_std", test))]
+    fn snapshot(&self) -> ::devtools::NodeSnapshot {
+        ::devtools::NodeSnapshot::of(self).remove(0)
+    }
 }
 
 /// "Value" of a `DomNode`: either an element's tag name (e.g. "div"/"h1"/"body") or the text
@@ -186,6 +393,41 @@ pub enum DomValue<'a> {
 
     /// A text node
     Text(&'a str),
+
+    /// A text node whose text is created when the value is requested, such as a formatted
+    /// number. Processors which need an owned `String` can take it with `into_text`.
+    #[cfg(any(feature = "use_std", test))]
+    OwnedText(String),
+
+    /// A comment, written to HTML as `<!--...-->`
+    Comment(&'a str),
+
+    /// A document type declaration with the given name, written to HTML as `<!DOCTYPE ...>`.
+    /// The web renderer creates an empty comment in its place.
+    Doctype(&'a str),
+}
+
+impl<'a> DomValue<'a> {
+    /// Returns the text of a text node, or `None` for other nodes.
+    pub fn text(&self) -> Option<&str> {
+        match *self {
+            DomValue::Element { .. } | DomValue::Comment(_) | DomValue::Doctype(_) => None,
+            DomValue::Text(text) => Some(text),
+            #[cfg(any(feature = "use_std", test))]
+            DomValue::OwnedText(ref text) => Some(text),
+        }
+    }
+
+    /// Returns the text of a text node as a `String`, copying it only if it's borrowed, or
+    /// `None` for other nodes.
+    #[cfg(any(feature = "use_std", test))]
+    pub fn into_text(self) -> Option<String> {
+        match self {
+            DomValue::Element { .. } | DomValue::Comment(_) | DomValue::Doctype(_) => None,
+            DomValue::Text(text) => Some(text.to_string()),
+            DomValue::OwnedText(text) => Some(text),
+        }
+    }
 }
 
 /// A `DomNode` with a key
@@ -218,6 +460,48 @@ impl<M, T: DomNode<M>> DomNode<M> for WithKey<M, T> {
         (WithKey(node, self.1, PhantomData), listeners)
     }
     fn value(&self) -> DomValue { self.0.value() }
+    fn props_hash(&self) -> Option<u64> { self.0.props_hash() }
+    fn should_update(&self, old_props_hash: u64) -> bool {
+        self.0.should_update(old_props_hash)
+    }
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.0.widget() }
+    #[cfg(any(feature = "use_std", test))]
+    fn precompiled(&self) -> Option<&Precompiled> { self.0.precompiled() }
+}
+
+/// Folds `added`, which a wrapper writes in addition to its node, into the node's props hash,
+/// so that the wrapper is only left as it was when both are unchanged
+#[cfg(any(feature = "use_std", test))]
+pub(crate) fn fold_props_hash<H: ::std::hash::Hash + ?Sized>(props_hash: Option<u64>, added: &H) -> Option<u64> {
+    use std::hash::{Hash, Hasher};
+    props_hash.map(|props_hash| {
+        let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+        props_hash.hash(&mut hasher);
+        added.hash(&mut hasher);
+        hasher.finish()
+    })
+}
+
+/// Listener processor hashing the event types handled by listeners
+#[cfg(any(feature = "use_std", test))]
+struct EventTypeHasher;
+#[cfg(any(feature = "use_std", test))]
+impl<'a, M> ListenerProcessor<'a, M> for EventTypeHasher {
+    type Acc = ::std::collections::hash_map::DefaultHasher;
+    type Error = ();
+
+    fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn add_listener<M, L: Listener<M>>(
+            hasher: &mut ::std::collections::hash_map::DefaultHasher,
+            listener: &L) -> Result<(), ()>
+        {
+            use std::hash::Hash;
+            listener.event_type_handled().hash(hasher);
+            Ok(())
+        }
+        add_listener::<M, L>
+    }
 }
 
 /// Wrapper for `DomNode`s that adds attributes.
@@ -263,6 +547,18 @@ impl<M, T, A> DomNode<M> for WithAttributes<M, T, A> where T: DomNode<M>, A: AsR
         )
     }
     fn value(&self) -> DomValue { self.node.value() }
+    #[cfg(any(feature = "use_std", test))]
+    fn props_hash(&self) -> Option<u64> {
+        fold_props_hash(self.node.props_hash(), self.attributes.as_ref())
+    }
+    fn should_update(&self, old_props_hash: u64) -> bool {
+        match self.props_hash() {
+            Some(props_hash) => props_hash != old_props_hash,
+            None => self.node.should_update(old_props_hash),
+        }
+    }
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.node.widget() }
 }
 
 /// Wrapper for `DomNode`s that adds listeners.
@@ -299,6 +595,365 @@ impl<M, T, L> DomNode<M> for WithListeners<M, T, L>
         (self.node, self.listeners)
     }
     fn value(&self) -> DomValue { self.node.value() }
+    #[cfg(any(feature = "use_std", test))]
+    fn props_hash(&self) -> Option<u64> {
+        use std::hash::Hasher;
+        let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+        let _ = self.listeners.process_all::<EventTypeHasher>(&mut hasher);
+        fold_props_hash(self.node.props_hash(), &hasher.finish())
+    }
+    fn should_update(&self, old_props_hash: u64) -> bool {
+        match self.props_hash() {
+            Some(props_hash) => props_hash != old_props_hash,
+            None => self.node.should_update(old_props_hash),
+        }
+    }
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.node.widget() }
+    #[cfg(any(feature = "use_std", test))]
+    fn precompiled(&self) -> Option<&Precompiled> { self.node.precompiled() }
+}
+
+/// Renders each of `items` with `render`, keyed with `key`, such as an id from the item
+/// rather than its position.
+///
+/// Keys which stay with their items, rather than with the positions the items are rendered
+/// at, let the web renderer tell which element belongs to which item when the list is
+/// reordered, or items are inserted or removed. Keys should be unique among siblings.
+///
+/// Example:
+///
+///```rust
+/// use domafic::{keyed_by, DomNode};
+/// use domafic::tags::{li, ul};
+///
+/// struct Todo { id: usize, title: &'static str }
+/// let todos = vec![Todo { id: 7, title: "Walk" }, Todo { id: 3, title: "Read" }];
+///
+/// let list = ul(keyed_by(&todos, |todo| todo.id, |todo| li::<(), _, _, _, _>(todo.title)));
+/// assert_eq!(list.children()[0].key(), Some(7));
+/// assert_eq!(list.to_string(), "<ul><li>Walk</li><li>Read</li></ul>");
+///```
+#[cfg(any(feature = "use_std", test))]
+pub fn keyed_by<M, I, T, K, R>(items: I, key: K, render: R) -> Vec<WithKey<M, T>>
+    where
+    I: IntoIterator,
+    T: DomNode<M>,
+    K: Fn(&I::Item) -> usize,
+    R: Fn(I::Item) -> T,
+{
+    items.into_iter().map(|item| {
+        let item_key = key(&item);
+        render(item).with_key(item_key)
+    }).collect()
+}
+
+/// Wraps a region of the page for the web renderer to create once and then never update, such
+/// as the container of a map or editor managed by another JS library.
+///
+/// On later renders, the region's attributes and children are left as they are in the DOM, so
+/// other libraries can modify them freely. Only the listeners of the region's elements are kept
+/// in sync with the latest render, so that events don't refer to nodes which no longer exist.
+///
+/// Example:
+///
+///```rust
+/// use domafic::{DomNode, uncontrolled};
+/// use domafic::tags::div;
+/// use std::marker::PhantomData;
+///
+/// let map = uncontrolled(div(PhantomData::<()>));
+///
+/// assert_eq!(map.props_hash(), None);
+/// assert!(!map.should_update(0));
+///```
+pub fn uncontrolled<M, T: DomNode<M>>(initial_node: T) -> Uncontrolled<M, T> {
+    Uncontrolled(initial_node, PhantomData)
+}
+
+/// A `DomNode` which the web renderer doesn't update after creating it
+pub struct Uncontrolled<M, T: DomNode<M>>(T, PhantomData<M>);
+impl<M, T: DomNode<M>> DomNodes<M> for Uncontrolled<M, T> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M, T: DomNode<M>> DomNode<M> for Uncontrolled<M, T> {
+    type Children = T::Children;
+    type Listeners = T::Listeners;
+    type WithoutListeners = Uncontrolled<M, T::WithoutListeners>;
+
+    fn key(&self) -> Option<u32> { self.0.key() }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.0.get_attribute(index)
+    }
+    fn children(&self) -> &Self::Children {
+        self.0.children()
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        self.0.listeners()
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        self.0.children_and_listeners()
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        let (node, listeners) = self.0.split_listeners();
+        (Uncontrolled(node, PhantomData), listeners)
+    }
+    fn value(&self) -> DomValue<'_> { self.0.value() }
+    fn should_update(&self, _old_props_hash: u64) -> bool { false }
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.0.widget() }
+    #[cfg(any(feature = "use_std", test))]
+    fn precompiled(&self) -> Option<&Precompiled> { self.0.precompiled() }
+}
+
+/// Wrapper for `DomNode`s that decides whether the web renderer updates them.
+pub struct WithShouldUpdate<M, T: DomNode<M>, F: Fn(u64, u64) -> bool> {
+    node: T,
+    props_hash: u64,
+    should_update: F,
+    _marker: PhantomData<M>,
+}
+impl<M, T: DomNode<M>, F: Fn(u64, u64) -> bool> DomNodes<M> for WithShouldUpdate<M, T, F> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M, T, F> DomNode<M> for WithShouldUpdate<M, T, F> where T: DomNode<M>, F: Fn(u64, u64) -> bool {
+    type Children = T::Children;
+    type Listeners = T::Listeners;
+    type WithoutListeners = WithShouldUpdate<M, T::WithoutListeners, F>;
+    fn key(&self) -> Option<u32> { self.node.key() }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.node.get_attribute(index)
+    }
+    fn children(&self) -> &Self::Children {
+        self.node.children()
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        self.node.listeners()
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        self.node.children_and_listeners()
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        let (node, listeners) = self.node.split_listeners();
+        (
+            WithShouldUpdate {
+                node,
+                props_hash: self.props_hash,
+                should_update: self.should_update,
+                _marker: PhantomData,
+            },
+            listeners
+        )
+    }
+    fn value(&self) -> DomValue<'_> { self.node.value() }
+    fn props_hash(&self) -> Option<u64> { Some(self.props_hash) }
+    fn should_update(&self, old_props_hash: u64) -> bool {
+        (self.should_update)(old_props_hash, self.props_hash)
+    }
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.node.widget() }
+    #[cfg(any(feature = "use_std", test))]
+    fn precompiled(&self) -> Option<&Precompiled> { self.node.precompiled() }
+}
+
+/// Wrapper for `DomNode`s that maps the messages of their listeners, and those of their
+/// descendants, into another type.
+///
+/// Created by `DomNode::map_message`. The wrapper has the same layout as the node, so that
+/// its children and listeners can be visited as mapped views without copying them.
+#[repr(transparent)]
+pub struct MessageMap<PM, CM, T: DomNode<CM>, F: Fn(CM) -> PM + Copy>(T, Mapping<PM, CM, F>);
+impl<PM, CM, T: DomNode<CM>, F: Fn(CM) -> PM + Copy> MessageMap<PM, CM, T, F> {
+    fn from_ref(node: &T) -> &Self {
+        // Safe because `MessageMap` is a transparent wrapper around `T`
+        unsafe { &*(node as *const T as *const Self) }
+    }
+}
+impl<PM, CM, T: DomNode<CM>, F: Fn(CM) -> PM + Copy> DomNodes<PM> for MessageMap<PM, CM, T, F> {
+    fn process_all<'a, P: DomNodeProcessor<'a, PM>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<PM, CM, T, F> DomNode<PM> for MessageMap<PM, CM, T, F> where T: DomNode<CM>, F: Fn(CM) -> PM + Copy {
+    type Children = MapNodes<PM, CM, T::Children, F>;
+    type Listeners = MapListeners<PM, CM, T::Listeners, F>;
+    type WithoutListeners = MessageMapWithoutListeners<PM, CM, T::WithoutListeners, F>;
+    fn key(&self) -> Option<u32> { self.0.key() }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.0.get_attribute(index)
+    }
+    fn children(&self) -> &Self::Children {
+        MapNodes::from_ref(self.0.children())
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        MapListeners::from_ref(self.0.listeners())
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        let (children, listeners) = self.0.children_and_listeners();
+        (MapNodes::from_ref(children), MapListeners::from_ref(listeners))
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        let (node, listeners) = self.0.split_listeners();
+        (MessageMapWithoutListeners(node, PhantomData), MapListeners(listeners, PhantomData))
+    }
+    fn value(&self) -> DomValue<'_> { self.0.value() }
+    fn props_hash(&self) -> Option<u64> { self.0.props_hash() }
+    fn should_update(&self, old_props_hash: u64) -> bool {
+        self.0.should_update(old_props_hash)
+    }
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.0.widget() }
+    #[cfg(any(feature = "use_std", test))]
+    fn precompiled(&self) -> Option<&Precompiled> { self.0.precompiled() }
+}
+
+/// A `MessageMap` whose listeners have been split off
+pub struct MessageMapWithoutListeners<PM, CM, T, F>(T, Mapping<PM, CM, F>)
+    where T: DomNode<CM, Listeners=EmptyListeners>, F: Fn(CM) -> PM + Copy;
+impl<PM, CM, T, F> DomNodes<PM> for MessageMapWithoutListeners<PM, CM, T, F>
+    where T: DomNode<CM, Listeners=EmptyListeners>, F: Fn(CM) -> PM + Copy
+{
+    fn process_all<'a, P: DomNodeProcessor<'a, PM>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<PM, CM, T, F> DomNode<PM> for MessageMapWithoutListeners<PM, CM, T, F>
+    where T: DomNode<CM, Listeners=EmptyListeners>, F: Fn(CM) -> PM + Copy
+{
+    type Children = MapNodes<PM, CM, T::Children, F>;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { self.0.key() }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.0.get_attribute(index)
+    }
+    fn children(&self) -> &Self::Children {
+        MapNodes::from_ref(self.0.children())
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        self.0.listeners()
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        let (children, listeners) = self.0.children_and_listeners();
+        (MapNodes::from_ref(children), listeners)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { self.0.value() }
+    fn props_hash(&self) -> Option<u64> { self.0.props_hash() }
+    fn should_update(&self, old_props_hash: u64) -> bool {
+        self.0.should_update(old_props_hash)
+    }
+    #[cfg(any(feature = "use_std", test))]
+    fn widget(&self) -> Option<&dyn WidgetNode> { self.0.widget() }
+    #[cfg(any(feature = "use_std", test))]
+    fn precompiled(&self) -> Option<&Precompiled> { self.0.precompiled() }
+}
+
+/// The children of a `MessageMap`, each visited as a `MessageMap`
+#[repr(transparent)]
+pub struct MapNodes<PM, CM, C: DomNodes<CM>, F: Fn(CM) -> PM + Copy>(C, Mapping<PM, CM, F>);
+impl<PM, CM, C: DomNodes<CM>, F: Fn(CM) -> PM + Copy> MapNodes<PM, CM, C, F> {
+    fn from_ref(nodes: &C) -> &Self {
+        // Safe because `MapNodes` is a transparent wrapper around `C`
+        unsafe { &*(nodes as *const C as *const Self) }
+    }
+}
+impl<PM, CM, C: DomNodes<CM>, F: Fn(CM) -> PM + Copy> DomNodes<PM> for MapNodes<PM, CM, C, F> {
+    fn process_all<'a, P: DomNodeProcessor<'a, PM>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        self.0.process_all::<MapNodeProcessor<PM, CM, F, P>>(acc)
+    }
+}
+
+/// Forwards each child of a `MapNodes` to `P` as a `MessageMap`
+struct MapNodeProcessor<PM, CM, F, P>(PhantomData<(Mapping<PM, CM, F>, P)>);
+impl<'a, PM, CM, F, P> DomNodeProcessor<'a, CM> for MapNodeProcessor<PM, CM, F, P>
+    where PM: 'a, CM: 'a, F: Fn(CM) -> PM + Copy + 'a, P: DomNodeProcessor<'a, PM>
+{
+    type Acc = P::Acc;
+    type Error = P::Error;
+
+    fn get_processor<T: DomNode<CM>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn process_mapped<'a, PM, CM, F, P, T>(acc: &mut P::Acc, node: &'a T) -> Result<(), P::Error>
+            where PM: 'a, CM: 'a, F: Fn(CM) -> PM + Copy + 'a, P: DomNodeProcessor<'a, PM>, T: DomNode<CM>
+        {
+            P::get_processor::<MessageMap<PM, CM, T, F>>()(acc, MessageMap::from_ref(node))
+        }
+        process_mapped::<PM, CM, F, P, T>
+    }
+
+    fn is_stopped(acc: &Self::Acc) -> bool {
+        P::is_stopped(acc)
+    }
+}
+
+/// The listeners of a `MessageMap`, each visited as a `MapListener`
+#[repr(transparent)]
+pub struct MapListeners<PM, CM, L: Listeners<CM>, F: Fn(CM) -> PM + Copy>(L, Mapping<PM, CM, F>);
+impl<PM, CM, L: Listeners<CM>, F: Fn(CM) -> PM + Copy> MapListeners<PM, CM, L, F> {
+    fn from_ref(listeners: &L) -> &Self {
+        // Safe because `MapListeners` is a transparent wrapper around `L`
+        unsafe { &*(listeners as *const L as *const Self) }
+    }
+}
+impl<PM, CM, L: Listeners<CM>, F: Fn(CM) -> PM + Copy> Listeners<PM> for MapListeners<PM, CM, L, F> {
+    fn process_all<'a, P: ListenerProcessor<'a, PM>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        self.0.process_all::<MapListenerProcessor<PM, CM, F, P>>(acc)
+    }
+}
+
+/// Forwards each listener of a `MapListeners` to `P` as a `MapListener`
+struct MapListenerProcessor<PM, CM, F, P>(PhantomData<(Mapping<PM, CM, F>, P)>);
+impl<'a, PM, CM, F, P> ListenerProcessor<'a, CM> for MapListenerProcessor<PM, CM, F, P>
+    where PM: 'a, CM: 'a, F: Fn(CM) -> PM + Copy + 'a, P: ListenerProcessor<'a, PM>
+{
+    type Acc = P::Acc;
+    type Error = P::Error;
+
+    fn get_processor<L: Listener<CM>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn process_mapped<'a, PM, CM, F, P, L>(acc: &mut P::Acc, listener: &'a L) -> Result<(), P::Error>
+            where PM: 'a, CM: 'a, F: Fn(CM) -> PM + Copy + 'a, P: ListenerProcessor<'a, PM>, L: Listener<CM>
+        {
+            // Safe because `MapListener` is a transparent wrapper around `L`
+            let mapped = unsafe { &*(listener as *const L as *const MapListener<PM, CM, L, F>) };
+            P::get_processor::<MapListener<PM, CM, L, F>>()(acc, mapped)
+        }
+        process_mapped::<PM, CM, F, P, L>
+    }
+}
+
+/// A listener of a `MessageMap`, whose messages are passed through the mapping function
+#[repr(transparent)]
+pub struct MapListener<PM, CM, L: Listener<CM>, F: Fn(CM) -> PM + Copy>(L, Mapping<PM, CM, F>);
+impl<PM, CM, L: Listener<CM>, F: Fn(CM) -> PM + Copy> Listener<PM> for MapListener<PM, CM, L, F> {
+    fn event_type_handled(&self) -> &'static str {
+        self.0.event_type_handled()
+    }
+
+    fn handle_event(&self, event: Event) -> PM {
+        Stateless::<F>::get()(self.0.handle_event(event))
+    }
+}
+
+/// Marker for the types of a mapping function and its messages
+type Mapping<PM, CM, F> = PhantomData<(fn(CM) -> PM, F)>;
+
+/// A function which captures nothing, and so can be created wherever it's needed
+struct Stateless<F>(PhantomData<F>);
+impl<F: Copy> Stateless<F> {
+    const CHECK: () = assert!(mem::size_of::<F>() == 0,
+        "map_message requires a function which captures nothing, such as an enum variant");
+
+    fn get() -> F {
+        let () = Stateless::<F>::CHECK;
+        // Safe because `F` is zero-sized, and can't implement `Drop` since it's `Copy`
+        unsafe { ptr::NonNull::<F>::dangling().as_ptr().read() }
+    }
 }
 
 /// Iterator over the attributes of a `DomNode`
@@ -317,6 +972,50 @@ impl<'a, M, T: DomNode<M>> Iterator for AttributeIter<'a, M, T> {
     }
 }
 
+/// Iterator over the attributes of a `DomNode`, with repeated `class` and `style` attributes
+/// merged. Created by `DomNode::merged_attributes`.
+#[cfg(any(feature = "use_std", test))]
+pub struct MergedAttributes<'a, M, T: DomNode<M> + 'a> {
+    node: &'a T,
+    index: usize,
+    _marker: PhantomData<M>,
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl<'a, M, T: DomNode<M>> Iterator for MergedAttributes<'a, M, T> {
+    type Item = ::std::borrow::Cow<'a, KeyValue>;
+    fn next(&mut self) -> Option<Self::Item> {
+        use std::borrow::Cow;
+
+        loop {
+            let attr = self.node.get_attribute(self.index)?;
+            self.index += 1;
+            let separator = match attr.0 {
+                "class" => " ",
+                "style" => "; ",
+                _ => return Some(Cow::Borrowed(attr)),
+            };
+
+            // Repeated attributes were merged into the first
+            if self.node.attributes().take(self.index - 1).any(|earlier| earlier.0 == attr.0) {
+                continue;
+            }
+            let mut repeated = self.node.attributes().skip(self.index)
+                .filter(|later| later.0 == attr.0)
+                .peekable();
+            if repeated.peek().is_none() {
+                return Some(Cow::Borrowed(attr));
+            }
+            let values: Vec<&str> = Some(attr).into_iter().chain(repeated)
+                .map(|attr| attr.1.as_str().trim().trim_end_matches(';').trim_end())
+                .filter(|value| !value.is_empty())
+                .collect();
+            let merged = values.join(separator);
+            return Some(Cow::Owned((attr.0, AttributeValue::OwnedStr(merged))));
+        }
+    }
+}
+
 static EMPTY_NODES_REF: &'static () = &();
 static EMPTY_LISTN_REF: &'static EmptyListeners = &EmptyListeners;
 
@@ -375,3 +1074,83 @@ impl<'a, M> DomNode<M> for &'a str {
     }
     fn value(&self) -> DomValue { DomValue::Text(self) }
 }
+
+/// Creates a comment, such as a marker read by scripts or a conditional comment.
+///
+/// Writers break up sequences in `text` which would end the comment early, such as `-->`.
+///
+/// Example:
+///
+///```rust
+/// use domafic::{comment, DomNode};
+/// use domafic::tags::div;
+///
+/// let marker = div((comment("island: cart"), "Loading", comment::<(), _>("/island")));
+/// assert_eq!(marker.to_string(), "<div><!--island: cart-->Loading<!--/island--></div>");
+///```
+pub fn comment<M, S: AsRef<str>>(text: S) -> Comment<M, S> {
+    Comment(text, PhantomData)
+}
+
+/// A comment `DomNode`
+pub struct Comment<M, S: AsRef<str>>(S, PhantomData<M>);
+impl<M, S: AsRef<str>> DomNodes<M> for Comment<M, S> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M, S: AsRef<str>> DomNode<M> for Comment<M, S> {
+    type Children = ();
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { None }
+    fn get_attribute(&self, _index: usize) -> Option<&KeyValue> { None }
+    fn children(&self) -> &Self::Children {
+        EMPTY_NODES_REF
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (EMPTY_NODES_REF, EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { DomValue::Comment(self.0.as_ref()) }
+}
+
+/// Creates a document type declaration, such as `doctype("html")` for `<!DOCTYPE html>`.
+///
+/// `Document` writes the HTML doctype itself, so this is for pages written without one.
+pub fn doctype<M>(name: &'static str) -> Doctype<M> {
+    Doctype(name, PhantomData)
+}
+
+/// A document type declaration `DomNode`
+pub struct Doctype<M>(&'static str, PhantomData<M>);
+impl<M> DomNodes<M> for Doctype<M> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+impl<M> DomNode<M> for Doctype<M> {
+    type Children = ();
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { None }
+    fn get_attribute(&self, _index: usize) -> Option<&KeyValue> { None }
+    fn children(&self) -> &Self::Children {
+        EMPTY_NODES_REF
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (EMPTY_NODES_REF, EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { DomValue::Doctype(self.0) }
+}
@@ -0,0 +1,769 @@
+//! Effects described as values, so that updates which issue them can be tested natively.
+//!
+//! Rather than calling the `JsIo` directly, an update can issue each effect, such as an HTTP
+//! request, a write to storage or a timer, as an `Effect` passed to an `Executor`. When
+//! rendering client-side, the `JsIo` is an `Executor` which runs the effects in the browser.
+//! In tests, a `MockExecutor` records the effects instead, and responses to them can be fed
+//! back to the update as messages, so that updates with side effects run deterministically.
+//!
+//! An HTTP request which may fail transiently can be issued with `Effect::with_retry`, which
+//! attempts it again after an exponential backoff, as described by a `Retry` policy.
+//!
+//! A numeric value shown by the UI, such as a progress bar's width or a counter, can be moved
+//! smoothly to a new value with `Effect::animate`, which delivers a message with the
+//! interpolated value on each animation frame, ending with the new value itself. A value with
+//! momentum, such as a card released after being dragged, can be moved by a spring instead with
+//! `Effect::spring`.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::effects::{Effect, Executor, MockExecutor, Request, Response};
+//!
+//! enum Msg {
+//!     Refresh,
+//!     Loaded(Result<Response, domafic::effects::HttpError>),
+//! }
+//!
+//! fn update(state: &mut Option<String>, msg: Msg, executor: &dyn Executor<Msg>) {
+//!     match msg {
+//!         Msg::Refresh => executor.execute(Effect::http(Request::get("/status"), Msg::Loaded)),
+//!         Msg::Loaded(response) => *state = response.ok().map(|response| response.body),
+//!     }
+//! }
+//!
+//! let executor = MockExecutor::new();
+//! let mut state = None;
+//!
+//! update(&mut state, Msg::Refresh, &executor);
+//! let msg = executor.respond(Ok(Response::new(200, "online")));
+//! update(&mut state, msg, &executor);
+//!
+//! assert_eq!(state, Some("online".to_string()));
+//! assert!(executor.is_empty());
+//! ```
+
+use spring::{Spring, SpringState};
+
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::rc::Rc;
+
+/// HTTP request error indicating either a network connection error or a timeout
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum HttpError {
+    /// The request could not be completed due to a network error
+    NetworkError,
+    /// The request did not complete within its timeout
+    Timeout,
+}
+
+/// An HTTP request issued as an `Effect`
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Request {
+    /// HTTP Method ("GET", "POST", etc.)
+    pub method: String,
+    /// A list of HTTP header (key, value) pairs
+    pub headers: Vec<(String, String)>,
+    /// Request URL
+    pub url: String,
+    /// Request body
+    pub body: String,
+    /// Optional request timeout in milliseconds
+    pub timeout_millis: Option<u32>,
+}
+
+impl Request {
+    /// A "GET" request for `url` with no headers or body
+    pub fn get<S: Into<String>>(url: S) -> Request {
+        Request { method: "GET".to_string(), url: url.into(), ..Request::default() }
+    }
+
+    /// A "POST" request sending `body` to `url`
+    pub fn post<S: Into<String>, B: Into<String>>(url: S, body: B) -> Request {
+        Request { method: "POST".to_string(), url: url.into(), body: body.into(), ..Request::default() }
+    }
+}
+
+/// The response to an HTTP request issued as an `Effect`
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Response {
+    /// HTTP status
+    pub status_code: u16,
+    /// HTTP status text
+    pub status_text: String,
+    /// A list of HTTP response header (key, value) pairs
+    pub headers: Vec<(String, String)>,
+    /// The body of the HTTP response
+    pub body: String,
+}
+
+impl Response {
+    /// A response with the given status code and body, and no headers
+    pub fn new<S: Into<String>>(status_code: u16, body: S) -> Response {
+        Response { status_code, body: body.into(), ..Response::default() }
+    }
+}
+
+/// Shortest interval between the progress messages delivered for an HTTP request, except for
+/// the last one
+pub const PROGRESS_INTERVAL_MILLIS: u32 = 100;
+
+/// Which way the bytes of an HTTP transfer are moving
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Direction {
+    /// The request body is being sent
+    Upload,
+    /// The response body is being received
+    Download,
+}
+
+/// How much of an HTTP transfer has completed, as delivered by `JsIo::http_with_progress`
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Progress {
+    /// Whether the request is being sent or the response received
+    pub direction: Direction,
+    /// Bytes transferred so far
+    pub loaded: u64,
+    /// Bytes to transfer in total, if the size of the body is known
+    pub total: Option<u64>,
+    /// Whether the request has finished, so that no further progress will be delivered. The
+    /// last progress is delivered just before the response.
+    pub done: bool,
+}
+
+impl Progress {
+    /// The fraction of the transfer which has completed, from 0 to 1, if its size is known
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.loaded as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+
+    /// Parses progress passed from JavaScript as the direction (1 for uploads), the bytes
+    /// loaded, and the total bytes or -1, separated by spaces
+    #[doc(hidden)]
+    pub fn from_js(text: &str, done: bool) -> Option<Progress> {
+        let mut fields = text.split(' ');
+        let direction = match fields.next() {
+            Some("1") => Direction::Upload,
+            Some("0") => Direction::Download,
+            _ => return None,
+        };
+        let loaded = fields.next()?.parse().ok()?;
+        let total = fields.next()?.parse::<i64>().ok()?;
+        Some(Progress { direction, loaded, total: if total < 0 { None } else { Some(total as u64) }, done })
+    }
+}
+
+/// Which of the browser's key-value storages an `Effect` writes to
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum StorageArea {
+    /// `window.localStorage`, which persists across browser sessions
+    Local,
+    /// `window.sessionStorage`, which is cleared when the page session ends
+    Session,
+}
+
+/// A side effect issued by an update, which produces messages of type `M`
+pub enum Effect<M> {
+    /// An HTTP request, whose result is converted to a message by `on_response`
+    Http {
+        /// The request to issue
+        request: Request,
+        /// Converts the result of the request to a message
+        on_response: Box<dyn Fn(Result<Response, HttpError>) -> M>,
+    },
+    /// Stores `value` under `key`, replacing any previous value
+    SetStorage {
+        /// The storage to write to
+        area: StorageArea,
+        /// The key to store the value under
+        key: String,
+        /// The value to store
+        value: String,
+    },
+    /// Removes the value stored under `key`, if any
+    RemoveStorage {
+        /// The storage to remove the value from
+        area: StorageArea,
+        /// The key of the value to remove
+        key: String,
+    },
+    /// Delivers `message` once `millis` milliseconds have passed
+    Delay {
+        /// Milliseconds to wait before delivering the message
+        millis: u32,
+        /// The message to deliver
+        message: M,
+    },
+    /// Delivers the values of `animation` on each animation frame until it ends
+    Animate {
+        /// The value to animate
+        animation: Animation<M>,
+    },
+    /// Delivers the values of `animation` on each animation frame until its spring comes to
+    /// rest
+    Spring {
+        /// The value to animate
+        animation: SpringAnimation<M>,
+    },
+}
+
+impl<M> Effect<M> {
+    /// Issues `request`, converting its result to a message with `on_response`
+    pub fn http<F>(request: Request, on_response: F) -> Effect<M>
+        where F: Fn(Result<Response, HttpError>) -> M + 'static
+    {
+        Effect::Http { request, on_response: Box::new(on_response) }
+    }
+
+    /// Stores `value` under `key` in `area`
+    pub fn set_storage<K: Into<String>, V: Into<String>>(area: StorageArea, key: K, value: V) -> Effect<M> {
+        Effect::SetStorage { area, key: key.into(), value: value.into() }
+    }
+
+    /// Removes the value stored under `key` in `area`
+    pub fn remove_storage<K: Into<String>>(area: StorageArea, key: K) -> Effect<M> {
+        Effect::RemoveStorage { area, key: key.into() }
+    }
+
+    /// Delivers `message` after `millis` milliseconds
+    pub fn delay(millis: u32, message: M) -> Effect<M> {
+        Effect::Delay { millis, message }
+    }
+
+    /// Moves a value from `from` to `to` over `duration_millis` milliseconds, delivering the
+    /// message `on_frame` makes from the current value on each animation frame. The last
+    /// message, delivered once the duration has passed, is made from `to`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use domafic::effects::{Easing, Effect, Executor, MockExecutor};
+    ///
+    /// enum Msg { Progress(f64) }
+    ///
+    /// let executor = MockExecutor::new();
+    /// executor.execute(Effect::animate(0.0, 100.0, 300, Easing::Linear, Msg::Progress));
+    ///
+    /// let values: Vec<f64> = executor.fire_animations(100).into_iter()
+    ///     .map(|Msg::Progress(value)| value.round())
+    ///     .collect();
+    /// assert_eq!(values, vec![33.0, 67.0, 100.0]);
+    /// ```
+    pub fn animate<F>(from: f64, to: f64, duration_millis: u32, easing: Easing, on_frame: F) -> Effect<M>
+        where F: Fn(f64) -> M + 'static
+    {
+        Effect::Animate {
+            animation: Animation { from, to, duration_millis, easing, on_frame: Box::new(on_frame) },
+        }
+    }
+
+    /// Moves a value from `from`, at `velocity` per second, to `to` with `spring`, delivering
+    /// the message `on_frame` makes from the current value on each animation frame. The last
+    /// message, delivered once the spring comes to rest, is made from `to`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use domafic::effects::{Effect, Executor, MockExecutor};
+    /// use domafic::spring::spring;
+    ///
+    /// enum Msg { Moved(f64) }
+    ///
+    /// // A card released while moving to the right springs back to its place
+    /// let executor = MockExecutor::new();
+    /// executor.execute(Effect::spring(spring(170.0, 26.0), 40.0, 0.0, 600.0, Msg::Moved));
+    ///
+    /// let positions: Vec<f64> = executor.fire_animations(16).into_iter()
+    ///     .map(|Msg::Moved(position)| position)
+    ///     .collect();
+    /// assert!(positions[0] > 40.0);
+    /// assert_eq!(positions.last(), Some(&0.0));
+    /// ```
+    pub fn spring<F>(spring: Spring, from: f64, to: f64, velocity: f64, on_frame: F) -> Effect<M>
+        where F: Fn(f64) -> M + 'static
+    {
+        Effect::Spring {
+            animation: SpringAnimation {
+                spring,
+                state: SpringState { value: from, velocity },
+                to,
+                on_frame: Box::new(on_frame),
+            },
+        }
+    }
+}
+
+/// How an animated value moves between its start and its end
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Easing {
+    /// At a constant speed
+    Linear,
+    /// Starting slowly and speeding up
+    EaseIn,
+    /// Starting quickly and slowing down
+    EaseOut,
+    /// Starting and ending slowly
+    EaseInOut,
+}
+
+impl Easing {
+    /// The fraction of the distance covered once the fraction `progress` of the duration has
+    /// passed, following a cubic curve for the eased variants
+    pub fn apply(&self, progress: f64) -> f64 {
+        let t = progress.clamp(0.0, 1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0,
+        }
+    }
+}
+
+/// A value animated by `Effect::animate`
+pub struct Animation<M> {
+    /// The value when the animation starts
+    pub from: f64,
+    /// The value when the animation ends
+    pub to: f64,
+    /// Length of the animation
+    pub duration_millis: u32,
+    /// How the value moves between `from` and `to`
+    pub easing: Easing,
+    /// Makes the message delivered for each value
+    pub on_frame: Box<dyn Fn(f64) -> M>,
+}
+
+impl<M> Animation<M> {
+    /// The value once `elapsed_millis` have passed since the animation started
+    pub fn value_at(&self, elapsed_millis: f64) -> f64 {
+        if self.is_done_at(elapsed_millis) {
+            return self.to;
+        }
+        let progress = elapsed_millis / f64::from(self.duration_millis);
+        self.from + (self.to - self.from) * self.easing.apply(progress)
+    }
+
+    /// Whether the animation has ended once `elapsed_millis` have passed
+    pub fn is_done_at(&self, elapsed_millis: f64) -> bool {
+        elapsed_millis >= f64::from(self.duration_millis)
+    }
+
+    /// The message delivered once `elapsed_millis` have passed
+    pub fn message_at(&self, elapsed_millis: f64) -> M {
+        (self.on_frame)(self.value_at(elapsed_millis))
+    }
+}
+
+/// A value animated by `Effect::spring`
+pub struct SpringAnimation<M> {
+    /// The spring moving the value
+    pub spring: Spring,
+    /// The value's position and velocity
+    pub state: SpringState,
+    /// The value the spring comes to rest at
+    pub to: f64,
+    /// Makes the message delivered for each value
+    pub on_frame: Box<dyn Fn(f64) -> M>,
+}
+
+impl<M> SpringAnimation<M> {
+    /// Moves the value for `millis` milliseconds, returning the message for its new position.
+    ///
+    /// Once the spring has come to rest, the value is moved to exactly `to`.
+    pub fn advance(&mut self, millis: f64) -> M {
+        self.state = self.spring.step(self.state, self.to, millis);
+        if self.is_done() {
+            self.state = SpringState { value: self.to, velocity: 0.0 };
+        }
+        (self.on_frame)(self.state.value)
+    }
+
+    /// Whether the spring has come to rest
+    pub fn is_done(&self) -> bool {
+        self.spring.is_at_rest(&self.state, self.to)
+    }
+}
+
+impl<M> fmt::Debug for SpringAnimation<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SpringAnimation").field("spring", &self.spring).field("state", &self.state)
+            .field("to", &self.to).finish()
+    }
+}
+
+impl<M> fmt::Debug for Animation<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Animation").field("from", &self.from).field("to", &self.to)
+            .field("duration_millis", &self.duration_millis).field("easing", &self.easing)
+            .finish()
+    }
+}
+
+impl<M: 'static> Effect<M> {
+    /// Attempts an HTTP request again when it fails in a way `policy` allows, after a backoff.
+    ///
+    /// Rather than the outcome, a failed attempt which will be retried produces the message
+    /// `wrap` makes from a `Retrying`, which the update passes to `Retrying::resume` to wait
+    /// for the backoff and then issue the next attempt. The update can also show that the
+    /// request is being retried, or stop retrying by dropping the `Retrying`. The outcome
+    /// message is produced by the original `on_response` once an attempt succeeds, fails in a
+    /// way which isn't retried, or is the last one allowed.
+    ///
+    /// Other effects can't fail, so they're returned unchanged.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use domafic::effects::{Effect, Executor, MockExecutor, Request, Response, Retry, Retrying};
+    ///
+    /// enum Msg {
+    ///     Loaded(u16),
+    ///     Retry(Retrying<Msg>),
+    /// }
+    ///
+    /// let executor = MockExecutor::new();
+    /// let policy = Retry::new(3).backoff(200, 5_000).without_jitter();
+    /// executor.execute(Effect::http(Request::get("/status"), |result| {
+    ///     Msg::Loaded(result.map(|response| response.status_code).unwrap_or(0))
+    /// }).with_retry(policy, Msg::Retry));
+    ///
+    /// // The server is briefly unavailable, so the request is attempted again after 200ms
+    /// let retrying = match executor.respond(Ok(Response::new(503, ""))) {
+    ///     Msg::Retry(retrying) => retrying,
+    ///     Msg::Loaded(_) => unreachable!(),
+    /// };
+    /// assert_eq!((retrying.attempt(), retrying.delay_millis()), (1, 200));
+    /// retrying.resume(&executor);
+    /// for msg in executor.fire_delays() {
+    ///     if let Msg::Retry(retrying) = msg { retrying.resume(&executor); }
+    /// }
+    ///
+    /// match executor.respond(Ok(Response::new(200, "online"))) {
+    ///     Msg::Loaded(status) => assert_eq!(status, 200),
+    ///     Msg::Retry(_) => unreachable!(),
+    /// }
+    /// ```
+    pub fn with_retry<W>(self, policy: Retry, wrap: W) -> Effect<M>
+        where W: Fn(Retrying<M>) -> M + 'static
+    {
+        match self {
+            Effect::Http { request, on_response } => {
+                let state = Rc::new(RetryState { request, policy, on_response, wrap: Box::new(wrap) });
+                attempt(state, 1)
+            },
+            effect => effect,
+        }
+    }
+}
+
+/// When an HTTP request issued with `Effect::with_retry` is attempted again
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Retry {
+    /// Number of attempts, including the first, before a failure is reported
+    pub max_attempts: u32,
+    /// Backoff before the second attempt, which doubles with each further attempt
+    pub base_delay_millis: u32,
+    /// Longest backoff between attempts
+    pub max_delay_millis: u32,
+    /// Whether each backoff is chosen at random between half and all of its length, so that
+    /// clients which failed together don't all retry at once
+    pub jitter: bool,
+    /// Response statuses which are retried, such as 503 Service Unavailable
+    pub statuses: Vec<u16>,
+    /// Whether requests which time out are retried. Network errors are always retried.
+    pub timeouts: bool,
+}
+
+impl Retry {
+    /// Attempts a request up to `max_attempts` times in total, after a backoff of 500ms
+    /// doubling up to 30s, with jitter. Network errors and the statuses 408, 429, 502, 503
+    /// and 504 are retried, but timeouts and other errors aren't, since the server may have
+    /// acted on the request.
+    pub fn new(max_attempts: u32) -> Retry {
+        Retry {
+            max_attempts,
+            base_delay_millis: 500,
+            max_delay_millis: 30_000,
+            jitter: true,
+            statuses: vec![408, 429, 502, 503, 504],
+            timeouts: false,
+        }
+    }
+
+    /// Sets the backoff before the second attempt, and the longest backoff
+    pub fn backoff(mut self, base_delay_millis: u32, max_delay_millis: u32) -> Retry {
+        self.base_delay_millis = base_delay_millis;
+        self.max_delay_millis = max_delay_millis;
+        self
+    }
+
+    /// Waits for the whole backoff rather than a random part of it
+    pub fn without_jitter(mut self) -> Retry {
+        self.jitter = false;
+        self
+    }
+
+    /// Also retries responses with `status`
+    pub fn retry_status(mut self, status: u16) -> Retry {
+        self.statuses.push(status);
+        self
+    }
+
+    /// Sets whether requests which time out are retried
+    pub fn retry_timeouts(mut self, timeouts: bool) -> Retry {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Whether an attempt with `result` is retried, if the policy allows more attempts
+    pub fn is_retryable(&self, result: &Result<Response, HttpError>) -> bool {
+        match *result {
+            Ok(ref response) => self.statuses.contains(&response.status_code),
+            Err(HttpError::NetworkError) => true,
+            Err(HttpError::Timeout) => self.timeouts,
+        }
+    }
+
+    /// The longest backoff after the failure of `attempt`, the first of which is 1
+    pub fn max_delay_after(&self, attempt: u32) -> u32 {
+        self.base_delay_millis.saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_delay_millis)
+    }
+
+    fn delay_after(&self, attempt: u32) -> u32 {
+        let delay = self.max_delay_after(attempt);
+        if !self.jitter || delay < 2 {
+            return delay;
+        }
+        // The hasher of a new `RandomState` is randomly keyed
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempt);
+        delay / 2 + (hasher.finish() % (u64::from(delay - delay / 2) + 1)) as u32
+    }
+}
+
+struct RetryState<M> {
+    request: Request,
+    policy: Retry,
+    on_response: Box<dyn Fn(Result<Response, HttpError>) -> M>,
+    wrap: Box<dyn Fn(Retrying<M>) -> M>,
+}
+
+/// Message produced by an HTTP request issued with `Effect::with_retry` when an attempt has
+/// failed and it will be retried, or once the backoff has passed. Pass it to `resume`.
+pub struct Retrying<M> {
+    state: Rc<RetryState<M>>,
+    attempt: u32,
+    failure: Option<Result<Response, HttpError>>,
+    delay_millis: u32,
+}
+
+impl<M: 'static> Retrying<M> {
+    /// Waits for the backoff after a failed attempt, or issues the next attempt once it has
+    /// passed
+    pub fn resume(self, executor: &dyn Executor<M>) {
+        if self.failure.is_some() {
+            let delay_millis = self.delay_millis;
+            let state = self.state.clone();
+            let message = (state.wrap)(Retrying { failure: None, ..self });
+            executor.execute(Effect::delay(delay_millis, message));
+        } else {
+            executor.execute(attempt(self.state, self.attempt + 1));
+        }
+    }
+}
+
+impl<M> Retrying<M> {
+    /// The number of the last attempt, the first of which is 1
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// The request being retried
+    pub fn request(&self) -> &Request {
+        &self.state.request
+    }
+
+    /// The result of the failed attempt, or `None` once the backoff has passed
+    pub fn failure(&self) -> Option<&Result<Response, HttpError>> {
+        self.failure.as_ref()
+    }
+
+    /// The backoff before the next attempt
+    pub fn delay_millis(&self) -> u32 {
+        self.delay_millis
+    }
+}
+
+impl<M> fmt::Debug for Retrying<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Retrying").field("request", &self.state.request)
+            .field("attempt", &self.attempt).field("failure", &self.failure)
+            .field("delay_millis", &self.delay_millis).finish()
+    }
+}
+
+/// Issues attempt number `attempt` of a request issued with `Effect::with_retry`
+fn attempt<M: 'static>(state: Rc<RetryState<M>>, attempt: u32) -> Effect<M> {
+    let request = state.request.clone();
+    Effect::http(request, move |result| {
+        let policy = &state.policy;
+        if attempt >= policy.max_attempts || !policy.is_retryable(&result) {
+            return (state.on_response)(result);
+        }
+        (state.wrap)(Retrying {
+            state: state.clone(),
+            attempt,
+            failure: Some(result),
+            delay_millis: policy.delay_after(attempt),
+        })
+    })
+}
+
+impl<M: fmt::Debug> fmt::Debug for Effect<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Effect::Http { ref request, .. } =>
+                f.debug_struct("Http").field("request", request).finish(),
+            Effect::SetStorage { ref area, ref key, ref value } =>
+                f.debug_struct("SetStorage").field("area", area).field("key", key)
+                    .field("value", value).finish(),
+            Effect::RemoveStorage { ref area, ref key } =>
+                f.debug_struct("RemoveStorage").field("area", area).field("key", key).finish(),
+            Effect::Delay { ref millis, ref message } =>
+                f.debug_struct("Delay").field("millis", millis).field("message", message).finish(),
+            Effect::Animate { ref animation } =>
+                f.debug_struct("Animate").field("animation", animation).finish(),
+            Effect::Spring { ref animation } =>
+                f.debug_struct("Spring").field("animation", animation).finish(),
+        }
+    }
+}
+
+/// Runs the effects issued by updates
+pub trait Executor<M> {
+    /// Runs `effect`, delivering any messages it produces to the application
+    fn execute(&self, effect: Effect<M>);
+}
+
+/// An `Executor` which records effects instead of running them, for testing updates natively.
+///
+/// Tests inspect the recorded effects with `take`, or resolve them with `respond` and
+/// `fire_delays`, passing the resulting messages back to the update.
+pub struct MockExecutor<M> {
+    issued: RefCell<Vec<Effect<M>>>,
+}
+
+impl<M> MockExecutor<M> {
+    /// Creates a `MockExecutor` with no recorded effects
+    pub fn new() -> MockExecutor<M> {
+        MockExecutor { issued: RefCell::new(Vec::new()) }
+    }
+
+    /// The number of recorded effects which haven't been taken or resolved
+    pub fn len(&self) -> usize {
+        self.issued.borrow().len()
+    }
+
+    /// Whether every recorded effect has been taken or resolved
+    pub fn is_empty(&self) -> bool {
+        self.issued.borrow().is_empty()
+    }
+
+    /// Removes and returns the recorded effects, in the order they were issued
+    pub fn take(&self) -> Vec<Effect<M>> {
+        ::std::mem::take(&mut *self.issued.borrow_mut())
+    }
+
+    /// Resolves the oldest recorded HTTP request with `response`, returning the message
+    /// produced by its `on_response`.
+    ///
+    /// Panics if no HTTP request has been recorded.
+    pub fn respond(&self, response: Result<Response, HttpError>) -> M {
+        let mut issued = self.issued.borrow_mut();
+        let index = issued.iter().position(|effect| matches!(*effect, Effect::Http { .. }))
+            .expect("no HTTP request has been issued");
+        match issued.remove(index) {
+            Effect::Http { on_response, .. } => on_response(response),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Resolves every recorded delay, returning their messages in the order they would be
+    /// delivered
+    pub fn fire_delays(&self) -> Vec<M> {
+        let mut delays = Vec::new();
+        let issued = self.take();
+        for effect in issued {
+            match effect {
+                Effect::Delay { millis, message } => delays.push((millis, message)),
+                effect => self.issued.borrow_mut().push(effect),
+            }
+        }
+        // Sorting is stable, so delays of the same length are delivered in the order issued
+        delays.sort_by_key(|delay| delay.0);
+        delays.into_iter().map(|delay| delay.1).collect()
+    }
+
+    /// Resolves every recorded animation, including springs, returning the messages they would
+    /// deliver if frames were drawn every `frame_millis` milliseconds, one animation after
+    /// another in the order they were issued.
+    ///
+    /// Panics if `frame_millis` is 0.
+    pub fn fire_animations(&self, frame_millis: u32) -> Vec<M> {
+        assert!(frame_millis > 0, "frames must be drawn at an interval");
+        let mut messages = Vec::new();
+        let issued = self.take();
+        for effect in issued {
+            match effect {
+                Effect::Animate { animation } => {
+                    let mut elapsed = 0.0;
+                    loop {
+                        elapsed += f64::from(frame_millis);
+                        messages.push(animation.message_at(elapsed));
+                        if animation.is_done_at(elapsed) { break; }
+                    }
+                },
+                Effect::Spring { mut animation } => loop {
+                    messages.push(animation.advance(f64::from(frame_millis)));
+                    if animation.is_done() { break; }
+                },
+                effect => self.issued.borrow_mut().push(effect),
+            }
+        }
+        messages
+    }
+}
+
+impl<M> Default for MockExecutor<M> {
+    fn default() -> MockExecutor<M> {
+        MockExecutor::new()
+    }
+}
+
+impl<M> Executor<M> for MockExecutor<M> {
+    fn execute(&self, effect: Effect<M>) {
+        self.issued.borrow_mut().push(effect);
+    }
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{Effect, Executor};
+    use web_render::JsIo;
+
+    impl<'a, M> Executor<M> for dyn JsIo<M> + 'a {
+        fn execute(&self, effect: Effect<M>) {
+            self.executor().execute(effect)
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use {AttributeValue, KeyValue};
+
+/// Attribute which marks an element as the target of an `ElementRef`
+pub const REF_ATTRIBUTE: &str = "data-domafic-ref";
+
+/// A name identifying a rendered element, used to target the element with browser commands
+/// such as `JsIo::request_fullscreen`.
+///
+/// Mark the element by adding the ref's `attribute` to it.
+///
+/// Example:
+///
+///```rust
+/// use domafic::{DomNode, ElementRef};
+/// use domafic::tags::canvas;
+/// use std::marker::PhantomData;
+///
+/// const GAME_CANVAS: ElementRef = ElementRef("game-canvas");
+///
+/// let game = canvas(PhantomData::<()>).with_attributes([GAME_CANVAS.attribute()]);
+/// assert_eq!(game.get_attribute(0), Some(&GAME_CANVAS.attribute()));
+///```
+///
+/// If more than one element is marked with the same ref, the first one in the document is
+/// targeted.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct ElementRef(pub &'static str);
+
+impl ElementRef {
+    /// The name of the ref
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    /// The attribute which marks an element as the target of this ref
+    pub fn attribute(&self) -> KeyValue {
+        (REF_ATTRIBUTE, AttributeValue::Str(self.0))
+    }
+}
@@ -0,0 +1,226 @@
+//! The error type shared by the runtime and the writers.
+//!
+//! Failures which an application can handle, such as mounting on a page which has no element
+//! matching the selector, are returned as an `Error` by the `try_` variants of the runtime's
+//! functions. Failures while patching the page after an update have no caller to return to, so
+//! the runtime panics with the `Error`'s description, which the crash overlay shows in debug
+//! builds. HTML and XML writers report `io::Error`s, JSON encoding reports
+//! `serde_json::Error`s, and the IO modules, such as `persist` and `graphql`, report their own
+//! error types. All of them convert to `Error` with `?`, and `source` returns the original
+//! error.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::{DomNode, Error};
+//! use domafic::tags::p;
+//! use std::marker::PhantomData;
+//!
+//! fn page() -> Result<Vec<u8>, Error> {
+//!     let mut html = Vec::new();
+//!     p(("Hello", PhantomData::<()>)).write_html(&mut html)?;
+//!     Ok(html)
+//! }
+//!
+//! assert_eq!(page().unwrap(), b"<p>Hello</p>");
+//! assert_eq!(Error::InvalidSelector("#".to_string()).to_string(), "invalid CSS selector `#`");
+//! ```
+
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+
+#[cfg(feature = "bootstrap")]
+use bootstrap;
+#[cfg(feature = "feature_flags")]
+use feature_flags;
+#[cfg(feature = "graphql")]
+use graphql;
+#[cfg(feature = "indexed_db")]
+use indexed_db::IdbError;
+#[cfg(feature = "live")]
+use live::LiveError;
+#[cfg(feature = "persist")]
+use persist::RestoreError;
+#[cfg(feature = "replay")]
+use replay::ReplayError;
+use social_meta;
+use web_manifest;
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// An error raised by the runtime, a writer, or one of the IO modules
+#[derive(Debug)]
+pub enum Error {
+    /// A call into JavaScript failed, such as creating an element, given as the name of the
+    /// operation
+    Ffi(&'static str),
+    /// A CSS selector couldn't be parsed by the browser
+    InvalidSelector(String),
+    /// No element matches a CSS selector
+    MissingElement(String),
+    /// The pool of JavaScript objects referenced from Rust has no room for another object
+    PoolExhausted,
+    /// Writing HTML or XML failed
+    Io(io::Error),
+    /// A value couldn't be encoded to, or decoded from, JSON, with the encoder's message
+    Serialization(String),
+    /// The page being hydrated doesn't have the node the application rendered, given as a
+    /// description of the node, such as `<button>`
+    HydrationMismatch(String),
+    /// Bootstrap data couldn't be read
+    #[cfg(feature = "bootstrap")]
+    Bootstrap(bootstrap::Error),
+    /// Feature flags couldn't be loaded
+    #[cfg(feature = "feature_flags")]
+    FeatureFlags(feature_flags::Error),
+    /// A GraphQL response couldn't be decoded
+    #[cfg(feature = "graphql")]
+    GraphQl(graphql::Error),
+    /// An IndexedDB request failed
+    #[cfg(feature = "indexed_db")]
+    IndexedDb(IdbError),
+    /// An event forwarded by a live view client couldn't be handled
+    #[cfg(feature = "live")]
+    Live(LiveError),
+    /// Persisted state couldn't be restored
+    #[cfg(feature = "persist")]
+    Restore(RestoreError),
+    /// A recording couldn't be replayed
+    #[cfg(feature = "replay")]
+    Replay(ReplayError),
+    /// Social metadata would prevent a correct link preview
+    SocialMeta(social_meta::Error),
+    /// A web app manifest would prevent browsers from installing the app
+    WebManifest(web_manifest::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Ffi(operation) => write!(f, "JavaScript call failed: {}", operation),
+            Error::InvalidSelector(ref selector) => write!(f, "invalid CSS selector `{}`", selector),
+            Error::MissingElement(ref selector) => write!(f, "no element matches `{}`", selector),
+            Error::PoolExhausted => write!(f, "the pool of JavaScript objects is full"),
+            Error::Io(ref err) => write!(f, "write failed: {}", err),
+            Error::Serialization(ref message) => write!(f, "serialization failed: {}", message),
+            Error::HydrationMismatch(ref node) => write!(f, "the page has no {} to hydrate", node),
+            #[cfg(feature = "bootstrap")]
+            Error::Bootstrap(ref err) => err.fmt(f),
+            #[cfg(feature = "feature_flags")]
+            Error::FeatureFlags(ref err) => err.fmt(f),
+            #[cfg(feature = "graphql")]
+            Error::GraphQl(ref err) => err.fmt(f),
+            #[cfg(feature = "indexed_db")]
+            Error::IndexedDb(ref err) => err.fmt(f),
+            #[cfg(feature = "live")]
+            Error::Live(ref err) => err.fmt(f),
+            #[cfg(feature = "persist")]
+            Error::Restore(ref err) => err.fmt(f),
+            #[cfg(feature = "replay")]
+            Error::Replay(ref err) => err.fmt(f),
+            Error::SocialMeta(ref err) => err.fmt(f),
+            Error::WebManifest(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            #[cfg(feature = "bootstrap")]
+            Error::Bootstrap(ref err) => Some(err),
+            #[cfg(feature = "feature_flags")]
+            Error::FeatureFlags(ref err) => Some(err),
+            #[cfg(feature = "graphql")]
+            Error::GraphQl(ref err) => Some(err),
+            #[cfg(feature = "indexed_db")]
+            Error::IndexedDb(ref err) => Some(err),
+            #[cfg(feature = "live")]
+            Error::Live(ref err) => Some(err),
+            #[cfg(feature = "persist")]
+            Error::Restore(ref err) => Some(err),
+            #[cfg(feature = "replay")]
+            Error::Replay(ref err) => Some(err),
+            Error::SocialMeta(ref err) => Some(err),
+            Error::WebManifest(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Serialization(err.to_string())
+    }
+}
+
+#[cfg(feature = "bootstrap")]
+impl From<bootstrap::Error> for Error {
+    fn from(err: bootstrap::Error) -> Error {
+        Error::Bootstrap(err)
+    }
+}
+
+#[cfg(feature = "feature_flags")]
+impl From<feature_flags::Error> for Error {
+    fn from(err: feature_flags::Error) -> Error {
+        Error::FeatureFlags(err)
+    }
+}
+
+#[cfg(feature = "graphql")]
+impl From<graphql::Error> for Error {
+    fn from(err: graphql::Error) -> Error {
+        Error::GraphQl(err)
+    }
+}
+
+#[cfg(feature = "indexed_db")]
+impl From<IdbError> for Error {
+    fn from(err: IdbError) -> Error {
+        Error::IndexedDb(err)
+    }
+}
+
+#[cfg(feature = "live")]
+impl From<LiveError> for Error {
+    fn from(err: LiveError) -> Error {
+        Error::Live(err)
+    }
+}
+
+#[cfg(feature = "persist")]
+impl From<RestoreError> for Error {
+    fn from(err: RestoreError) -> Error {
+        Error::Restore(err)
+    }
+}
+
+#[cfg(feature = "replay")]
+impl From<ReplayError> for Error {
+    fn from(err: ReplayError) -> Error {
+        Error::Replay(err)
+    }
+}
+
+impl From<social_meta::Error> for Error {
+    fn from(err: social_meta::Error) -> Error {
+        Error::SocialMeta(err)
+    }
+}
+
+impl From<web_manifest::Error> for Error {
+    fn from(err: web_manifest::Error) -> Error {
+        Error::WebManifest(err)
+    }
+}
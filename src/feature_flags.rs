@@ -0,0 +1,378 @@
+//! Enable with the `feature_flags` feature.
+//!
+//! A `FeatureFlags` is a set of named flags, each a boolean, a number or a variant name, which
+//! decide which features a page shows. The server usually decides the flags for each user: when
+//! rendering server-side, `FeatureFlags::script_tag` embeds them in the page as JSON, and when
+//! rendering client-side, `FeatureFlags::from_page` reads them back. Flags can also be loaded
+//! from an HTTP endpoint with `fetch`.
+//!
+//! Render functions and updates look flags up through the context API: `provide` makes a set
+//! of flags available from `current` and `is_enabled`, and `FeatureFlags::provide_to` wraps an
+//! `Updater` or `Renderer` so that every update and render has them.
+//!
+//! For testing a feature before it's rolled out, the flags loaded client-side can be overridden
+//! with the `flags` query parameter, a comma-separated list where `name` enables a flag,
+//! `-name` disables it, and `name:value` sets it to a number or variant, as in
+//! `?flags=new-checkout,-banner,layout:grid`.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::feature_flags::{self, FeatureFlags, FlagValue};
+//!
+//! let flags = FeatureFlags::from_json(r#"{"new-checkout": false, "layout": "list"}"#).unwrap()
+//!     .with_overrides("/cart?flags=new-checkout,layout:grid,max-items:20");
+//! assert_eq!(flags.get("max-items"), Some(&FlagValue::Number(20.0)));
+//!
+//! let checkout = || if feature_flags::is_enabled("new-checkout") { "new" } else { "old" };
+//! assert_eq!(checkout(), "old");
+//! assert_eq!(feature_flags::provide(flags.clone(), checkout), "new");
+//!
+//! assert_eq!(flags.variant("layout"), Some("grid"));
+//! assert_eq!(flags.script_tag(), "<script type=\"application/json\" \
+//!     id=\"domafic-feature-flags\">{\"layout\":\"grid\",\"max-items\":20.0,\
+//!     \"new-checkout\":true}</script>");
+//! ```
+
+extern crate serde_json;
+
+use self::serde_json::{Map, Number, Value};
+
+use context;
+use csp::CspNonce;
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+
+/// Id of the `<script>` element the flags are embedded in by `FeatureFlags::script_tag`
+pub const SCRIPT_ID: &str = "domafic-feature-flags";
+
+/// Query parameter which overrides flags loaded client-side
+pub const OVERRIDE_PARAM: &str = "flags";
+
+/// The value of a flag
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlagValue {
+    /// Whether a feature is enabled
+    Bool(bool),
+    /// A numeric setting, such as a limit
+    Number(f64),
+    /// The name of a variant, such as in an A/B test
+    Variant(String),
+}
+
+impl From<bool> for FlagValue {
+    fn from(value: bool) -> FlagValue {
+        FlagValue::Bool(value)
+    }
+}
+
+impl From<f64> for FlagValue {
+    fn from(value: f64) -> FlagValue {
+        FlagValue::Number(value)
+    }
+}
+
+impl<'a> From<&'a str> for FlagValue {
+    fn from(value: &'a str) -> FlagValue {
+        FlagValue::Variant(value.to_string())
+    }
+}
+
+impl From<String> for FlagValue {
+    fn from(value: String) -> FlagValue {
+        FlagValue::Variant(value)
+    }
+}
+
+/// Error returned when a set of flags can't be loaded
+#[derive(Debug)]
+pub enum Error {
+    /// The flags aren't valid JSON
+    Json(serde_json::Error),
+    /// The flags aren't a JSON object
+    NotAnObject,
+    /// The named flag isn't a boolean, number or string
+    InvalidValue(String),
+    /// The flags weren't embedded in the page
+    Missing,
+    /// The request for the flags failed due to a network error or timeout
+    Network,
+    /// The server responded to the request for the flags with an unsuccessful HTTP status
+    Status(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Json(ref err) => write!(f, "feature flags were malformed: {}", err),
+            Error::NotAnObject => write!(f, "feature flags were not a JSON object"),
+            Error::InvalidValue(ref name) =>
+                write!(f, "feature flag `{}` was not a boolean, number or string", name),
+            Error::Missing => write!(f, "feature flags were not embedded in the page"),
+            Error::Network => write!(f, "feature flags request failed"),
+            Error::Status(status) =>
+                write!(f, "feature flags request failed with HTTP status {}", status),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Json(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A set of named feature flags
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeatureFlags {
+    flags: BTreeMap<String, FlagValue>,
+}
+
+impl FeatureFlags {
+    /// An empty set of flags, in which every feature is disabled
+    pub fn new() -> FeatureFlags {
+        FeatureFlags::default()
+    }
+
+    /// Sets the flag `name` to `value`
+    pub fn with<N: Into<String>, V: Into<FlagValue>>(mut self, name: N, value: V) -> FeatureFlags {
+        self.set(name, value);
+        self
+    }
+
+    /// Sets the flag `name` to `value`, replacing any previous value
+    pub fn set<N: Into<String>, V: Into<FlagValue>>(&mut self, name: N, value: V) {
+        self.flags.insert(name.into(), value.into());
+    }
+
+    /// Parses flags from a JSON object mapping their names to booleans, numbers or strings
+    pub fn from_json(json: &str) -> Result<FeatureFlags, Error> {
+        let object = match serde_json::from_str(json).map_err(Error::Json)? {
+            Value::Object(object) => object,
+            _ => return Err(Error::NotAnObject),
+        };
+        let mut flags = FeatureFlags::new();
+        for (name, value) in object {
+            let value = match value {
+                Value::Bool(value) => FlagValue::Bool(value),
+                Value::Number(ref value) if value.as_f64().is_some() =>
+                    FlagValue::Number(value.as_f64().unwrap()),
+                Value::String(value) => FlagValue::Variant(value),
+                _ => return Err(Error::InvalidValue(name)),
+            };
+            flags.flags.insert(name, value);
+        }
+        Ok(flags)
+    }
+
+    /// Encodes the flags as a JSON object
+    pub fn to_json(&self) -> String {
+        let object: Map<String, Value> = self.flags.iter().map(|(name, value)| {
+            let value = match *value {
+                FlagValue::Bool(value) => Value::Bool(value),
+                FlagValue::Number(value) => Number::from_f64(value).map_or(Value::Null, Value::Number),
+                FlagValue::Variant(ref value) => Value::String(value.clone()),
+            };
+            (name.clone(), value)
+        }).collect();
+        Value::Object(object).to_string()
+    }
+
+    /// Applies the overrides in the `flags` query parameter of `url`, which may be a whole URL
+    /// or only its path and query string, as described in the module documentation
+    pub fn with_overrides(mut self, url: &str) -> FeatureFlags {
+        let query = match url.find('?') {
+            Some(start) => &url[start + 1..],
+            None => return self,
+        };
+        let query = query.split('#').next().unwrap_or("");
+        let overrides = query.split('&')
+            .filter_map(|pair| {
+                let mut parts = pair.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(OVERRIDE_PARAM), Some(value)) => Some(decode_component(value)),
+                    _ => None,
+                }
+            });
+        for overrides in overrides {
+            for entry in overrides.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+                if let Some(name) = entry.strip_prefix('-') {
+                    self.set(name, false);
+                } else if let Some(colon) = entry.find(':') {
+                    let (name, value) = (&entry[..colon], &entry[colon + 1..]);
+                    let value = match value {
+                        "true" => FlagValue::Bool(true),
+                        "false" => FlagValue::Bool(false),
+                        _ => value.parse().map(FlagValue::Number)
+                            .unwrap_or_else(|_| FlagValue::Variant(value.to_string())),
+                    };
+                    self.set(name, value);
+                } else {
+                    self.set(entry, true);
+                }
+            }
+        }
+        self
+    }
+
+    /// The value of the flag `name`, if it's set
+    pub fn get(&self, name: &str) -> Option<&FlagValue> {
+        self.flags.get(name)
+    }
+
+    /// Whether the flag `name` is `true`. Flags which aren't set, or aren't booleans, are
+    /// disabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.get(name) == Some(&FlagValue::Bool(true))
+    }
+
+    /// The number the flag `name` is set to, if it's a number
+    pub fn number(&self, name: &str) -> Option<f64> {
+        match self.get(name) {
+            Some(&FlagValue::Number(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The variant the flag `name` is set to, if it's a string
+    pub fn variant(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(FlagValue::Variant(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Names and values of the flags, in order of their names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &FlagValue)> {
+        self.flags.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// A `<script>` element embedding the flags in a server-rendered page, for `from_page` to
+    /// read. It has the nonce provided by `csp::provide`, if there is one.
+    pub fn script_tag(&self) -> String {
+        let nonce = match CspNonce::current() {
+            Some(nonce) => format!(" nonce=\"{}\"", nonce.as_str().replace('"', "&quot;")),
+            None => String::new(),
+        };
+        // Prevent a flag from closing the `<script>` element early
+        format!("<script type=\"application/json\" id=\"{}\"{}>{}</script>",
+            SCRIPT_ID, nonce, self.to_json().replace("</", "<\\/"))
+    }
+
+    /// Wraps an `Updater` or `Renderer` so that the flags are provided to every update or
+    /// render
+    #[cfg(all(feature = "web_render", target_os = "emscripten"))]
+    pub fn provide_to<T>(self, inner: T) -> WithFlags<T> {
+        WithFlags { flags: Rc::new(self), inner }
+    }
+}
+
+/// Decodes a percent-encoded query string component
+fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = |b: u8| (b as char).to_digit(16);
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() && hex(bytes[i + 1]).is_some() && hex(bytes[i + 2]).is_some() => {
+                decoded.push((hex(bytes[i + 1]).unwrap() * 16 + hex(bytes[i + 2]).unwrap()) as u8);
+                i += 2;
+            },
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Calls `f`, with `flags` available from `current` until it returns
+pub fn provide<R, F: FnOnce() -> R>(flags: FeatureFlags, f: F) -> R {
+    context::provide(flags, f)
+}
+
+/// Returns the flags provided by the innermost enclosing call to `provide`
+pub fn current() -> Option<Rc<FeatureFlags>> {
+    context::get::<FeatureFlags>()
+}
+
+/// Whether the flag `name` is enabled in the provided flags. Every flag is disabled if no
+/// flags are provided.
+pub fn is_enabled(name: &str) -> bool {
+    current().is_some_and(|flags| flags.is_enabled(name))
+}
+
+/// An `Updater` or `Renderer` which provides feature flags to every update or render.
+///
+/// Created by `FeatureFlags::provide_to`.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub struct WithFlags<T> {
+    flags: Rc<FeatureFlags>,
+    inner: T,
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub use self::web::fetch;
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{Error, FeatureFlags, WithFlags};
+    use context;
+    use keys::KeyIter;
+    use web_render::{element_text, location_path, HttpError, HttpRequest, HttpResult, JsIo, Renderer,
+        Updater};
+
+    impl FeatureFlags {
+        /// Reads the flags embedded in the page by `script_tag`, applying any overrides in the
+        /// page's URL
+        pub fn from_page() -> Result<FeatureFlags, Error> {
+            let json = element_text(super::SCRIPT_ID).ok_or(Error::Missing)?;
+            Ok(FeatureFlags::from_json(&json)?.with_overrides(&location_path()))
+        }
+    }
+
+    /// Loads the flags from `url`, applying any overrides in the page's URL, and delivers them
+    /// as the message produced by `f`
+    pub fn fetch<M, F>(js_io: &dyn JsIo<M>, url: &str, f: F)
+        where F: Fn(Result<FeatureFlags, Error>) -> M + 'static
+    {
+        js_io.http(HttpRequest {
+            method: "GET",
+            headers: &[("Accept", "application/json")],
+            url,
+            body: "",
+            timeout_millis: None,
+        }, Box::new(move |result: HttpResult| f(match result {
+            Ok(ref response) if response.status_code >= 200 && response.status_code < 300 =>
+                FeatureFlags::from_json(response.body)
+                    .map(|flags| flags.with_overrides(&location_path())),
+            Ok(response) => Err(Error::Status(response.status_code)),
+            Err(HttpError::NetworkError) | Err(HttpError::Timeout) => Err(Error::Network),
+        })));
+    }
+
+    impl<S, M, U> Updater<S, M> for WithFlags<U>
+        where U: Updater<S, M>
+    {
+        fn update(&self, state: &mut S, msg: M, keys: KeyIter, js_io: &dyn JsIo<M>) {
+            context::provide_rc(self.flags.clone(), || self.inner.update(state, msg, keys, js_io));
+        }
+    }
+
+    impl<S, M, R> Renderer<S, M> for WithFlags<R>
+        where R: Renderer<S, M>
+    {
+        type Rendered<'a> = R::Rendered<'a> where S: 'a;
+
+        fn render<'a>(&self, state: &'a S) -> R::Rendered<'a> {
+            context::provide_rc(self.flags.clone(), || self.inner.render(state))
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Generating `sitemap.xml` files and RSS or Atom feeds from a list of routes.
+//!
+//! Each generator takes the site's routes, of any type, along with a callback producing the
+//! metadata for a route. Routes for which the callback returns `None` are left out.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::feeds::{write_sitemap, ChangeFrequency, DateTime, SitemapEntry};
+//!
+//! enum Route { Home, Post(&'static str), Admin }
+//!
+//! let routes = [Route::Home, Route::Post("hello-world"), Route::Admin];
+//! let mut sitemap = Vec::new();
+//! write_sitemap(&mut sitemap, "https://example.com", &routes, |route| match *route {
+//!     Route::Home => Some(SitemapEntry::new("/").change_frequency(ChangeFrequency::Daily)),
+//!     Route::Post(slug) => Some(SitemapEntry::new(format!("/posts/{}", slug))
+//!         .last_modified(DateTime::date(2017, 3, 1))),
+//!     Route::Admin => None,
+//! }).unwrap();
+//!
+//! assert_eq!(String::from_utf8(sitemap).unwrap(), "\
+//!     <?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+//!     <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\
+//!     <url><loc>https://example.com/</loc><changefreq>daily</changefreq></url>\
+//!     <url><loc>https://example.com/posts/hello-world</loc>\
+//!     <lastmod>2017-03-01T00:00:00Z</lastmod></url>\
+//!     </urlset>");
+//! ```
+
+use xml_writer::write_escaped;
+
+use std::fmt;
+use std::io;
+
+/// A UTC date and time, as used in sitemaps and feeds
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct DateTime {
+    /// Year, such as 2017
+    pub year: u16,
+    /// Month, from 1 to 12
+    pub month: u8,
+    /// Day of the month, from 1 to 31
+    pub day: u8,
+    /// Hour, from 0 to 23
+    pub hour: u8,
+    /// Minute, from 0 to 59
+    pub minute: u8,
+    /// Second, from 0 to 59
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Midnight UTC at the start of the given day
+    pub fn date(year: u16, month: u8, day: u8) -> DateTime {
+        DateTime { year, month, day, hour: 0, minute: 0, second: 0 }
+    }
+
+    /// Formats the time as RFC 3339, as used by sitemaps and Atom, such as
+    /// `2017-03-01T12:30:00Z`
+    pub fn to_rfc3339(&self) -> String {
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+
+    /// Formats the time as RFC 822, as used by RSS, such as `Wed, 01 Mar 2017 12:30:00 GMT`
+    pub fn to_rfc822(&self) -> String {
+        const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+        const MONTHS: [&str; 12] =
+            ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        format!("{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            DAYS[self.weekday()], self.day, MONTHS[(self.month as usize + 11) % 12], self.year,
+            self.hour, self.minute, self.second)
+    }
+
+    /// Day of the week, with Sunday as 0, using Sakamoto's method
+    fn weekday(&self) -> usize {
+        const OFFSETS: [usize; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+        let month = (self.month as usize).clamp(1, 12);
+        let year = if month < 3 { self.year as usize - 1 } else { self.year as usize };
+        (year + year / 4 - year / 100 + year / 400 + OFFSETS[month - 1] + self.day as usize) % 7
+    }
+}
+
+/// How often the content of a page is expected to change
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum ChangeFrequency {
+    /// Changes every time it's accessed
+    Always,
+    /// Changes hourly
+    Hourly,
+    /// Changes daily
+    Daily,
+    /// Changes weekly
+    Weekly,
+    /// Changes monthly
+    Monthly,
+    /// Changes yearly
+    Yearly,
+    /// Archived content which never changes
+    Never,
+}
+
+impl fmt::Display for ChangeFrequency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ChangeFrequency::Always => "always",
+            ChangeFrequency::Hourly => "hourly",
+            ChangeFrequency::Daily => "daily",
+            ChangeFrequency::Weekly => "weekly",
+            ChangeFrequency::Monthly => "monthly",
+            ChangeFrequency::Yearly => "yearly",
+            ChangeFrequency::Never => "never",
+        })
+    }
+}
+
+/// Sitemap metadata for a route
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapEntry {
+    /// Path of the page, relative to the site's base URL, such as `/posts/hello-world`
+    pub path: String,
+    /// When the page was last modified
+    pub last_modified: Option<DateTime>,
+    /// How often the page is expected to change
+    pub change_frequency: Option<ChangeFrequency>,
+    /// Priority of the page relative to the site's other pages, from 0.0 to 1.0
+    pub priority: Option<f32>,
+}
+
+impl SitemapEntry {
+    /// Creates an entry for the page at `path` with no other metadata
+    pub fn new<P: Into<String>>(path: P) -> SitemapEntry {
+        SitemapEntry { path: path.into(), last_modified: None, change_frequency: None, priority: None }
+    }
+
+    /// Sets when the page was last modified
+    pub fn last_modified(mut self, last_modified: DateTime) -> SitemapEntry {
+        self.last_modified = Some(last_modified);
+        self
+    }
+
+    /// Sets how often the page is expected to change
+    pub fn change_frequency(mut self, change_frequency: ChangeFrequency) -> SitemapEntry {
+        self.change_frequency = Some(change_frequency);
+        self
+    }
+
+    /// Sets the priority of the page, from 0.0 to 1.0
+    pub fn priority(mut self, priority: f32) -> SitemapEntry {
+        self.priority = Some(priority);
+        self
+    }
+}
+
+/// Information about a feed as a whole
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Feed<'a> {
+    /// Title of the feed
+    pub title: &'a str,
+    /// Path of the page the feed describes, relative to the site's base URL
+    pub path: &'a str,
+    /// Path of the feed itself, relative to the site's base URL
+    pub feed_path: &'a str,
+    /// Description of the feed
+    pub description: &'a str,
+    /// When the feed's content was last updated
+    pub updated: DateTime,
+}
+
+/// Feed metadata for a route
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedItem {
+    /// Title of the item
+    pub title: String,
+    /// Path of the item's page, relative to the site's base URL
+    pub path: String,
+    /// Short summary of the item
+    pub summary: Option<String>,
+    /// Name of the item's author
+    pub author: Option<String>,
+    /// When the item was published
+    pub published: DateTime,
+}
+
+fn write_element<W: io::Write>(w: &mut W, tag: &str, text: &str) -> io::Result<()> {
+    write!(w, "<{}>", tag)?;
+    write_escaped(w, text)?;
+    write!(w, "</{}>", tag)
+}
+
+fn url(base_url: &str, path: &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+/// Writes a `sitemap.xml` listing the routes for which `metadata` returns an entry.
+///
+/// Paths are joined to `base_url`, such as `https://example.com`, to form absolute URLs.
+pub fn write_sitemap<W, R, F>(w: &mut W, base_url: &str, routes: &[R], metadata: F) -> io::Result<()>
+    where W: io::Write, F: Fn(&R) -> Option<SitemapEntry>
+{
+    write!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    write!(w, "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">")?;
+    for entry in routes.iter().filter_map(metadata) {
+        write!(w, "<url>")?;
+        write_element(w, "loc", &url(base_url, &entry.path))?;
+        if let Some(last_modified) = entry.last_modified {
+            write_element(w, "lastmod", &last_modified.to_rfc3339())?;
+        }
+        if let Some(change_frequency) = entry.change_frequency {
+            write_element(w, "changefreq", &change_frequency.to_string())?;
+        }
+        if let Some(priority) = entry.priority {
+            write_element(w, "priority", &format!("{:.1}", priority.clamp(0.0, 1.0)))?;
+        }
+        write!(w, "</url>")?;
+    }
+    write!(w, "</urlset>")
+}
+
+/// Writes an RSS 2.0 feed of the routes for which `metadata` returns an item.
+pub fn write_rss<W, R, F>(
+    w: &mut W,
+    base_url: &str,
+    feed: &Feed,
+    routes: &[R],
+    metadata: F,
+) -> io::Result<()>
+    where W: io::Write, F: Fn(&R) -> Option<FeedItem>
+{
+    write!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    write!(w, "<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+        xmlns:dc=\"http://purl.org/dc/elements/1.1/\"><channel>")?;
+    write_element(w, "title", feed.title)?;
+    write_element(w, "link", &url(base_url, feed.path))?;
+    write_element(w, "description", feed.description)?;
+    write_element(w, "lastBuildDate", &feed.updated.to_rfc822())?;
+    write!(w, "<atom:link href=\"")?;
+    write_escaped(w, &url(base_url, feed.feed_path))?;
+    write!(w, "\" rel=\"self\" type=\"application/rss+xml\"/>")?;
+    for item in routes.iter().filter_map(metadata) {
+        let link = url(base_url, &item.path);
+        write!(w, "<item>")?;
+        write_element(w, "title", &item.title)?;
+        write_element(w, "link", &link)?;
+        write_element(w, "guid", &link)?;
+        if let Some(ref summary) = item.summary {
+            write_element(w, "description", summary)?;
+        }
+        if let Some(ref author) = item.author {
+            write_element(w, "dc:creator", author)?;
+        }
+        write_element(w, "pubDate", &item.published.to_rfc822())?;
+        write!(w, "</item>")?;
+    }
+    write!(w, "</channel></rss>")
+}
+
+/// Writes an Atom feed of the routes for which `metadata` returns an item.
+pub fn write_atom<W, R, F>(
+    w: &mut W,
+    base_url: &str,
+    feed: &Feed,
+    routes: &[R],
+    metadata: F,
+) -> io::Result<()>
+    where W: io::Write, F: Fn(&R) -> Option<FeedItem>
+{
+    write!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    write!(w, "<feed xmlns=\"http://www.w3.org/2005/Atom\">")?;
+    write_element(w, "title", feed.title)?;
+    write_element(w, "subtitle", feed.description)?;
+    write_element(w, "id", &url(base_url, feed.feed_path))?;
+    write!(w, "<link href=\"")?;
+    write_escaped(w, &url(base_url, feed.path))?;
+    write!(w, "\"/><link href=\"")?;
+    write_escaped(w, &url(base_url, feed.feed_path))?;
+    write!(w, "\" rel=\"self\"/>")?;
+    write_element(w, "updated", &feed.updated.to_rfc3339())?;
+    for item in routes.iter().filter_map(metadata) {
+        let link = url(base_url, &item.path);
+        write!(w, "<entry>")?;
+        write_element(w, "title", &item.title)?;
+        write!(w, "<link href=\"")?;
+        write_escaped(w, &link)?;
+        write!(w, "\"/>")?;
+        write_element(w, "id", &link)?;
+        write_element(w, "updated", &item.published.to_rfc3339())?;
+        if let Some(ref summary) = item.summary {
+            write_element(w, "summary", summary)?;
+        }
+        if let Some(ref author) = item.author {
+            write!(w, "<author>")?;
+            write_element(w, "name", author)?;
+            write!(w, "</author>")?;
+        }
+        write!(w, "</entry>")?;
+    }
+    write!(w, "</feed>")
+}
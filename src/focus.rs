@@ -0,0 +1,53 @@
+//! Confining keyboard focus to a modal dialog while it's open.
+//!
+//! While a modal is open, the rest of the page can't be used, so keyboard users shouldn't be
+//! able to Tab out of it into elements hidden behind it. `focus_trap` marks a node whose
+//! subtree keeps focus while it's rendered. When rendering client-side, the runtime moves focus
+//! into the trap once it's added to the page, to its first element with an `autofocus`
+//! attribute or else its first focusable element. Tab and Shift+Tab then cycle through the
+//! trap's focusable elements, and focus which escapes it, such as by clicking outside of it, is
+//! brought back. Once the trap is removed, focus returns to the element which had it before the
+//! trap was added, such as the button which opened the modal.
+//!
+//! When traps are nested, only the innermost one confines focus.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::{DomNode, ElementRef};
+//! use domafic::AttributeValue::Str;
+//! use domafic::focus::focus_trap;
+//! use domafic::tags::{attributes, button, div, h2};
+//! use std::marker::PhantomData;
+//!
+//! const CONFIRM: ElementRef = ElementRef("confirm");
+//!
+//! let modal = focus_trap(div((
+//!     attributes([("role", Str("dialog")), ("aria-modal", Str("true"))]),
+//!     (
+//!         h2("Delete this post?"),
+//!         button("Cancel"),
+//!         button(("Delete", PhantomData::<()>)).with_attributes([CONFIRM.attribute()]),
+//!     ),
+//! )));
+//!
+//! assert_eq!(modal.displayable().to_string(), "\
+//!     <div data-domafic-focus-trap=\"\" role=\"dialog\" aria-modal=\"true\">\
+//!     <h2>Delete this post?</h2><button>Cancel</button>\
+//!     <button data-domafic-ref=\"confirm\">Delete</button></div>");
+//! ```
+//!
+//! Focus can also be moved within a trap, or anywhere else, with `JsIo::focus`.
+
+use {DomNode, KeyValue};
+use AttributeValue::Str;
+use dom_node::WithAttributes;
+
+/// Attribute marking the root of a `focus_trap`
+pub const FOCUS_TRAP_ATTRIBUTE: &str = "data-domafic-focus-trap";
+
+/// Confines keyboard focus to `node` and its descendants while it's rendered, returning
+/// focus to the previously focused element once it's removed
+pub fn focus_trap<M, N: DomNode<M>>(node: N) -> WithAttributes<M, N, [KeyValue; 1]> {
+    node.with_attributes([(FOCUS_TRAP_ATTRIBUTE, Str(""))])
+}
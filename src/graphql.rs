@@ -0,0 +1,229 @@
+//! Enable with the `graphql` feature.
+//!
+//! A `Query` holds a GraphQL document along with its variables, which may be any type
+//! implementing `serde::Serialize`. Responses are decoded with `parse_response` into a
+//! `Response` containing the typed `data` and any `errors` reported by the server.
+//!
+//! When rendering client-side, `send` posts a `Query` through the `JsIo` HTTP interface and
+//! delivers the decoded response as a message.
+//!
+//! Example:
+//!
+//! ```rust
+//! extern crate domafic;
+//! extern crate serde_json;
+//!
+//! use domafic::graphql::{parse_response, Query};
+//! use std::collections::HashMap;
+//!
+//! fn main() {
+//!     let mut variables = HashMap::new();
+//!     variables.insert("id", 5);
+//!     let query = Query::new("query User($id: Int!) { user(id: $id) { name } }", variables);
+//!     assert!(query.to_body().unwrap().contains(r#""variables":{"id":5}"#));
+//!
+//!     type UserData = HashMap<String, HashMap<String, String>>;
+//!     let response = parse_response::<UserData>(200, r#"{"data":{"user":{"name":"Taylor"}}}"#)
+//!         .unwrap();
+//!     assert_eq!(response.data.unwrap()["user"]["name"], "Taylor");
+//!     assert!(response.errors.is_empty());
+//! }
+//! ```
+
+extern crate serde;
+extern crate serde_json;
+
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+use self::serde_json::Value;
+
+use std::error;
+use std::fmt;
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+use web_render::{HttpError, HttpRequest, HttpResult, JsIo};
+
+/// A GraphQL query or mutation along with its variables
+#[derive(Debug, Clone)]
+pub struct Query<'a, V> {
+    query: &'a str,
+    variables: V,
+    operation_name: Option<&'a str>,
+}
+
+impl<'a, V: Serialize> Query<'a, V> {
+    /// Creates a `Query` for the GraphQL document `query` with the given variables.
+    ///
+    /// Use `()` for queries without variables.
+    pub fn new(query: &'a str, variables: V) -> Query<'a, V> {
+        Query { query, variables, operation_name: None }
+    }
+
+    /// Selects which operation in the document to run
+    pub fn with_operation_name(mut self, operation_name: &'a str) -> Query<'a, V> {
+        self.operation_name = Some(operation_name);
+        self
+    }
+
+    /// Encodes the query as a JSON request body
+    pub fn to_body(&self) -> Result<String, serde_json::Error> {
+        let mut body = serde_json::Map::new();
+        body.insert("query".to_string(), Value::from(self.query));
+        body.insert("variables".to_string(), serde_json::to_value(&self.variables)?);
+        if let Some(operation_name) = self.operation_name {
+            body.insert("operationName".to_string(), Value::from(operation_name));
+        }
+        serde_json::to_string(&Value::Object(body))
+    }
+}
+
+/// A decoded GraphQL response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response<T> {
+    /// The data returned by the server, if any.
+    ///
+    /// This may be present even when `errors` is not empty, in which case the data is partial.
+    pub data: Option<T>,
+    /// Errors reported by the server
+    pub errors: Vec<ResponseError>,
+}
+
+/// An error reported in the `errors` list of a GraphQL response
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseError {
+    /// Description of the error
+    pub message: String,
+    /// (line, column) locations in the query document associated with the error
+    pub locations: Vec<(u64, u64)>,
+    /// Path to the response field which experienced the error. List indices are included as
+    /// strings.
+    pub path: Vec<String>,
+}
+
+/// Error preventing a GraphQL response from being decoded
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be completed due to a network error
+    Network,
+    /// The request did not complete within its timeout
+    Timeout,
+    /// The server responded with an unsuccessful HTTP status and no GraphQL response
+    Status(u16),
+    /// The response body could not be decoded
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Network => write!(f, "GraphQL request failed due to a network error"),
+            Error::Timeout => write!(f, "GraphQL request timed out"),
+            Error::Status(status) => write!(f, "GraphQL request failed with HTTP status {}", status),
+            Error::Json(ref err) => write!(f, "GraphQL response was malformed: {}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Json(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Result of a GraphQL request
+pub type GraphQlResult<T> = Result<Response<T>, Error>;
+
+/// Decodes the body of a GraphQL response received with HTTP status `status`.
+///
+/// Servers often report GraphQL errors with unsuccessful statuses, so the body is decoded
+/// regardless of the status. `Error::Status` is only returned if an unsuccessful response
+/// doesn't contain a GraphQL response.
+pub fn parse_response<T: DeserializeOwned>(status: u16, body: &str) -> GraphQlResult<T> {
+    let successful = (200..300).contains(&status);
+    let mut value: Value = match serde_json::from_str(body) {
+        Ok(Value::Object(value)) => Value::Object(value),
+        Ok(_) if !successful => return Err(Error::Status(status)),
+        Ok(_) => return Err(Error::Json(malformed("expected a JSON object"))),
+        Err(_) if !successful => return Err(Error::Status(status)),
+        Err(err) => return Err(Error::Json(err)),
+    };
+
+    let data = match value.get_mut("data").map(Value::take) {
+        None | Some(Value::Null) => None,
+        Some(data) => Some(serde_json::from_value(data).map_err(Error::Json)?),
+    };
+
+    let errors = match value.get_mut("errors").map(Value::take) {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(errors)) =>
+            errors.iter().map(parse_error).collect::<Result<Vec<_>, _>>().map_err(Error::Json)?,
+        Some(_) => return Err(Error::Json(malformed("expected `errors` to be a list"))),
+    };
+
+    if data.is_none() && errors.is_empty() && !successful {
+        return Err(Error::Status(status));
+    }
+
+    Ok(Response { data, errors })
+}
+
+fn malformed(msg: &str) -> serde_json::Error {
+    <serde_json::Error as serde::de::Error>::custom(msg)
+}
+
+fn parse_error(error: &Value) -> Result<ResponseError, serde_json::Error> {
+    let message = error.get("message")
+        .and_then(Value::as_str)
+        .ok_or_else(|| malformed("expected errors to have a `message`"))?
+        .to_string();
+
+    let locations = error.get("locations")
+        .and_then(Value::as_array)
+        .map(|locations| locations.iter().filter_map(|location| {
+            match (location.get("line").and_then(Value::as_u64),
+                   location.get("column").and_then(Value::as_u64)) {
+                (Some(line), Some(column)) => Some((line, column)),
+                _ => None,
+            }
+        }).collect())
+        .unwrap_or_default();
+
+    let path = error.get("path")
+        .and_then(Value::as_array)
+        .map(|path| path.iter().map(|segment| match *segment {
+            Value::String(ref segment) => segment.clone(),
+            ref segment => segment.to_string(),
+        }).collect())
+        .unwrap_or_default();
+
+    Ok(ResponseError { message, locations, path })
+}
+
+/// Posts `query` to `url`, delivering the decoded response as the message produced by `f`.
+///
+/// Returns an error if the query's variables could not be encoded.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub fn send<M, T, V, F>(js_io: &dyn JsIo<M>, url: &str, query: &Query<V>, f: F)
+    -> Result<(), serde_json::Error>
+    where
+    V: Serialize,
+    T: DeserializeOwned,
+    F: Fn(GraphQlResult<T>) -> M + 'static,
+{
+    let body = query.to_body()?;
+    js_io.http(HttpRequest {
+        method: "POST",
+        headers: &[("Content-Type", "application/json"), ("Accept", "application/json")],
+        url,
+        body: &body,
+        timeout_millis: None,
+    }, Box::new(move |result: HttpResult| f(match result {
+        Ok(response) => parse_response(response.status_code, response.body),
+        Err(HttpError::NetworkError) => Err(Error::Network),
+        Err(HttpError::Timeout) => Err(Error::Timeout),
+    })));
+    Ok(())
+}
@@ -1,8 +1,10 @@
-extern crate marksman_escape;
-use self::marksman_escape::Escape;
-
-use {DomNode, DomNodes, DomValue};
-use processors::DomNodeProcessor;
+use {DomNode, DomNodes, DomValue, ElementRef, Listener, REF_ATTRIBUTE};
+use processors::{DomNodeProcessor, ListenerProcessor, Listeners};
+pub use xml_writer::{Html, VOID_ELEMENTS};
+use xml_writer::{
+    canonical_attributes, has_children, html_attribute, write_attribute, write_comment,
+    write_escaped, write_escaped_attribute, write_html_attribute, XmlWriter,
+};
 
 // This module as a whole is "use_std"-only, so these don't need to be cfg'd
 use std::marker::PhantomData;
@@ -13,36 +15,7 @@ use std::io;
 ///
 /// This type should not ever need to be instantiated. Instead, simply
 /// name the type in calls to `DomNodes::process_all::<HtmlWriter<...>>(...)`.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct HtmlWriter<W: io::Write>(PhantomData<W>);
-impl<'a, M, W: io::Write> DomNodeProcessor<'a, M> for HtmlWriter<W> {
-    type Acc = W;
-    type Error = io::Error;
-
-    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
-        fn add_node<M, W, T>(w: &mut W, node: &T) -> Result<(), io::Error>
-                where W: io::Write, T: DomNode<M> {
-            match node.value() {
-                DomValue::Element { tag: tagname } => {
-                    write!(w, "<{}", tagname)?;
-                    for attr in node.attributes() {
-                        write!(w, " {}=\"{}\"", attr.0, attr.1)?;
-                    }
-                    write!(w, ">")?;
-                    node.children().process_all::<HtmlWriter<W>>(w)?;
-                    write!(w, "</{}>", tagname)
-                }
-                DomValue::Text(text) => {
-                    for escaped_u8 in Escape::new(text.bytes()) {
-                        w.write(&[escaped_u8])?;
-                    }
-                    Ok(())
-                }
-            }
-        }
-        add_node
-    }
-}
+pub type HtmlWriter<W> = XmlWriter<W, Html>;
 
 /// Wrapper struct to allow `DomNode`s to implement `Display` as html
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -59,3 +32,462 @@ impl<'a, M, T: DomNode<M>> fmt::Display for HtmlDisplayable<'a, M, T> {
         formatter.write_str(&string)
     }
 }
+
+/// Elements whose text is written exactly as given when minifying
+pub const PRESERVED_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Elements which browsers lay out as blocks, so that whitespace between them, or at the start
+/// or end of their contents, isn't shown
+pub const BLOCK_LEVEL_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "base", "blockquote", "body", "caption", "col", "colgroup",
+    "dd", "details", "dialog", "div", "dl", "dt", "fieldset", "figcaption", "figure", "footer",
+    "form", "h1", "h2", "h3", "h4", "h5", "h6", "head", "header", "hgroup", "hr", "html", "legend",
+    "li", "link", "main", "menu", "meta", "nav", "ol", "optgroup", "option", "p", "pre",
+    "section", "summary", "table", "tbody", "td", "tfoot", "th", "thead", "title", "tr", "ul",
+];
+
+/// What came before the node being written, among its siblings
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Preceding {
+    // Nothing, and the parent is one of `BLOCK_LEVEL_ELEMENTS`, or there is no parent
+    BlockStart,
+    // One of `BLOCK_LEVEL_ELEMENTS`
+    Block,
+    // Text or an inline element, or nothing, and the parent is inline
+    Inline,
+}
+
+/// Writer state used by `MinifyingHtmlWriter`
+#[derive(Debug)]
+pub struct Minifier<W: io::Write> {
+    writer: W,
+    // Number of `PRESERVED_ELEMENTS` containing the node being written
+    preserving: usize,
+    canonical: bool,
+    keep_comments: bool,
+    preceding: Preceding,
+    // Whitespace-only text which has been skipped, and is written as a space unless the next
+    // sibling is also one of `BLOCK_LEVEL_ELEMENTS`, given as whether it followed one
+    pending_space: Option<bool>,
+    // Whether the last text written ended with a space
+    ends_with_space: bool,
+}
+
+impl<W: io::Write> Minifier<W> {
+    /// Creates a `Minifier` writing to `writer`
+    pub fn new(writer: W) -> Minifier<W> {
+        Minifier {
+            writer,
+            preserving: 0,
+            canonical: false,
+            keep_comments: false,
+            preceding: Preceding::BlockStart,
+            pending_space: None,
+            ends_with_space: false,
+        }
+    }
+
+    /// Writes comments, which are otherwise left out, such as for scripts which read them as
+    /// markers
+    pub fn keep_comments(mut self) -> Minifier<W> {
+        self.keep_comments = true;
+        self
+    }
+
+    /// Writes attributes in the canonical order used by `xml_writer::Canonical`, which improves
+    /// how well pages compress
+    pub fn canonical(mut self) -> Minifier<W> {
+        self.canonical = true;
+        self
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Type to use for processing a `DomNode` tree and writing it to minified HTML.
+///
+/// Runs of whitespace in text are collapsed to a single space, except within
+/// `PRESERVED_ELEMENTS`. Text consisting only of whitespace is dropped where browsers wouldn't
+/// show it: between two `BLOCK_LEVEL_ELEMENTS`, and at the start or end of one. Elsewhere, such
+/// as between two inline elements, it's written as a single space. Attribute values are only
+/// quoted when HTML requires it, and empty values are left out. Comments are left out, unless
+/// the `Minifier` keeps them with `Minifier::keep_comments`.
+///
+/// Use `write_minified` rather than naming this type directly.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MinifyingHtmlWriter<W: io::Write>(PhantomData<W>);
+impl<'a, M, W: io::Write> DomNodeProcessor<'a, M> for MinifyingHtmlWriter<W> {
+    type Acc = Minifier<W>;
+    type Error = io::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, W, T>(m: &mut Minifier<W>, node: &T) -> Result<(), io::Error>
+                where W: io::Write, T: DomNode<M> {
+            match node.value() {
+                DomValue::Element { tag } => {
+                    let block = BLOCK_LEVEL_ELEMENTS.contains(&tag);
+                    if let Some(after_block) = m.pending_space.take() {
+                        if !(block && after_block) {
+                            write!(m.writer, " ")?;
+                        }
+                    }
+                    m.ends_with_space = false;
+                    write!(m.writer, "<{}", tag)?;
+                    if m.canonical {
+                        let merged: Vec<_> = node.merged_attributes().collect();
+                        let attributes = merged.iter()
+                            .filter_map(|attr| html_attribute(attr.0, &attr.1).map(|value| (attr.0, value)));
+                        for (key, value) in canonical_attributes(attributes) {
+                            write_minified_attribute(&mut m.writer, key, value.as_deref().unwrap_or(""))?;
+                        }
+                    } else {
+                        for attr in node.merged_attributes() {
+                            if let Some(value) = html_attribute(attr.0, &attr.1) {
+                                write_minified_attribute(&mut m.writer, attr.0, value.unwrap_or(""))?;
+                            }
+                        }
+                    }
+                    write!(m.writer, ">")?;
+                    if VOID_ELEMENTS.contains(&tag) {
+                        m.preceding = if block { Preceding::Block } else { Preceding::Inline };
+                        return Ok(());
+                    }
+
+                    let preserved = PRESERVED_ELEMENTS.contains(&tag);
+                    if preserved { m.preserving += 1; }
+                    m.preceding = if block { Preceding::BlockStart } else { Preceding::Inline };
+                    let result = node.children().process_all::<MinifyingHtmlWriter<W>>(m);
+                    if preserved { m.preserving -= 1; }
+                    result?;
+
+                    // Whitespace at the end of a block isn't shown
+                    if m.pending_space.take().is_some() && !block {
+                        write!(m.writer, " ")?;
+                        m.ends_with_space = true;
+                    }
+                    m.preceding = if block { Preceding::Block } else { Preceding::Inline };
+                    if block {
+                        m.ends_with_space = false;
+                    }
+                    write!(m.writer, "</{}>", tag)
+                },
+                DomValue::Text(text) => write_minified_text(m, text),
+                DomValue::OwnedText(text) => write_minified_text(m, &text),
+                DomValue::Comment(text) if m.keep_comments => write_comment(&mut m.writer, text),
+                DomValue::Comment(_) => Ok(()),
+                DomValue::Doctype(name) => {
+                    m.pending_space = None;
+                    m.preceding = Preceding::Block;
+                    write!(m.writer, "<!DOCTYPE {}>", name)
+                },
+            }
+        }
+        add_node::<M, W, T>
+    }
+}
+
+/// Writes `node` to `writer` as minified HTML, as described by `MinifyingHtmlWriter`.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::AttributeValue::Str;
+/// use domafic::html_writer::write_minified;
+/// use domafic::tags::{attributes, div, input, pre};
+/// use std::marker::PhantomData;
+///
+/// let node = div((
+///     "\n    Hello,\n    world  ",
+///     input(attributes([("type", Str("text")), ("disabled", Str(""))])),
+///     "\n    ",
+///     pre("  keep\n  this"),
+///     PhantomData::<()>,
+/// ));
+///
+/// let mut html = Vec::new();
+/// write_minified(&node, &mut html).unwrap();
+/// assert_eq!(
+///     String::from_utf8(html).unwrap(),
+///     "<div> Hello, world <input type=text disabled> <pre>  keep\n  this</pre></div>"
+/// );
+/// ```
+pub fn write_minified<M, T: DomNode<M>, W: io::Write>(node: &T, writer: W) -> io::Result<()> {
+    node.process_all::<MinifyingHtmlWriter<W>>(&mut Minifier::new(writer))
+}
+
+fn write_minified_attribute<W: io::Write>(w: &mut W, key: &str, value: &str) -> io::Result<()> {
+    write!(w, " {}", key)?;
+    if value.is_empty() {
+        return Ok(());
+    }
+    let needs_quotes = value.contains(|c: char| {
+        c.is_ascii_whitespace() || ['"', '\'', '=', '<', '>', '`'].contains(&c)
+    });
+    if needs_quotes {
+        write!(w, "=\"")?;
+        write_escaped_attribute(w, value)?;
+        write!(w, "\"")
+    } else {
+        write!(w, "=")?;
+        write_escaped_attribute(w, value)
+    }
+}
+
+fn write_minified_text<W: io::Write>(m: &mut Minifier<W>, text: &str) -> io::Result<()> {
+    if m.preserving > 0 {
+        return write_escaped(&mut m.writer, text);
+    }
+    let collapsed = collapse_whitespace(text);
+    if collapsed.is_empty() {
+        return Ok(());
+    }
+    if collapsed == " " {
+        // Whitespace at the start of a block isn't shown, and whether whitespace after a block
+        // is depends on the next sibling
+        if m.preceding != Preceding::BlockStart && !m.ends_with_space && m.pending_space.is_none() {
+            m.pending_space = Some(m.preceding == Preceding::Block);
+        }
+        return Ok(());
+    }
+    if m.pending_space.take().is_some() && !collapsed.starts_with(' ') {
+        write!(m.writer, " ")?;
+    }
+    m.preceding = Preceding::Inline;
+    m.ends_with_space = collapsed.ends_with(' ');
+    write_escaped(&mut m.writer, &collapsed)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !in_whitespace { collapsed.push(' '); }
+            in_whitespace = true;
+        } else {
+            collapsed.push(c);
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+/// Attribute numbering the elements with listeners in HTML written by `write_hydratable`, in
+/// the order they're written
+pub const HYDRATION_ID_ATTRIBUTE: &str = "data-domafic-id";
+
+/// Writer state used by `HydratableHtmlWriter`
+#[derive(Debug)]
+pub struct Hydratable<W: io::Write> {
+    writer: W,
+    next_id: usize,
+}
+
+impl<W: io::Write> Hydratable<W> {
+    /// Creates a `Hydratable` writing to `writer`
+    pub fn new(writer: W) -> Hydratable<W> {
+        Hydratable { writer, next_id: 0 }
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Type to use for processing a `DomNode` tree and writing it to HTML which the web renderer
+/// can hydrate.
+///
+/// The HTML is the same as `HtmlWriter`'s, except that each element with listeners is given a
+/// `HYDRATION_ID_ATTRIBUTE`. When the page is started with `web_render::hydrate`, the runtime
+/// renders the same tree from the same state and, rather than rebuilding the page, adopts the
+/// elements the server wrote and attaches their listeners, using the ids to check that each
+/// element is the one it expects.
+///
+/// Use `write_hydratable` rather than naming this type directly.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct HydratableHtmlWriter<W: io::Write>(PhantomData<W>);
+impl<'a, M, W: io::Write> DomNodeProcessor<'a, M> for HydratableHtmlWriter<W> {
+    type Acc = Hydratable<W>;
+    type Error = io::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, W, T>(h: &mut Hydratable<W>, node: &T) -> Result<(), io::Error>
+                where W: io::Write, T: DomNode<M> {
+            if let Some(precompiled) = node.precompiled() {
+                return h.writer.write_all(precompiled.html().as_bytes());
+            }
+            match node.value() {
+                DomValue::Element { tag } => {
+                    write!(h.writer, "<{}", tag)?;
+                    for attr in node.merged_attributes() {
+                        write_html_attribute(&mut h.writer, attr.0, &attr.1)?;
+                    }
+                    if has_listeners::<M, _>(node.listeners()) {
+                        write_attribute(&mut h.writer, HYDRATION_ID_ATTRIBUTE, &h.next_id.to_string())?;
+                        h.next_id += 1;
+                    }
+                    write!(h.writer, ">")?;
+                    if VOID_ELEMENTS.contains(&tag) && !has_children::<M, _>(node.children()) {
+                        return Ok(());
+                    }
+                    node.children().process_all::<HydratableHtmlWriter<W>>(h)?;
+                    write!(h.writer, "</{}>", tag)
+                },
+                DomValue::Text(text) => write_escaped(&mut h.writer, text),
+                DomValue::OwnedText(text) => write_escaped(&mut h.writer, &text),
+                DomValue::Comment(text) => write_comment(&mut h.writer, text),
+                DomValue::Doctype(name) => write!(h.writer, "<!DOCTYPE {}>", name),
+            }
+        }
+        add_node::<M, W, T>
+    }
+}
+
+/// Writes `node` to `writer` as HTML which the web renderer can hydrate, as described by
+/// `HydratableHtmlWriter`.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::html_writer::write_hydratable;
+/// use domafic::listener::on;
+/// use domafic::tags::{button, div, p};
+///
+/// struct Clicked;
+///
+/// let node = div((
+///     p("Count: 0"),
+///     button((on("click", |_| Clicked), "Add")),
+///     button((on("click", |_| Clicked), "Remove")),
+/// ));
+///
+/// let mut html = Vec::new();
+/// write_hydratable(&node, &mut html).unwrap();
+/// assert_eq!(String::from_utf8(html).unwrap(), "<div><p>Count: 0</p>\
+///     <button data-domafic-id=\"0\">Add</button><button data-domafic-id=\"1\">Remove</button></div>");
+/// ```
+pub fn write_hydratable<M, T: DomNode<M>, W: io::Write>(node: &T, writer: W) -> io::Result<()> {
+    node.process_all::<HydratableHtmlWriter<W>>(&mut Hydratable::new(writer))
+}
+
+/// Whether `listeners` contains any listeners
+fn has_listeners<M, L: Listeners<M>>(listeners: &L) -> bool {
+    struct AnyListener;
+    impl<'a, M> ListenerProcessor<'a, M> for AnyListener {
+        type Acc = bool;
+        type Error = ();
+
+        fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+            fn found<L>(acc: &mut bool, _listener: &L) -> Result<(), ()> {
+                *acc = true;
+                Ok(())
+            }
+            found::<L>
+        }
+    }
+
+    let mut found = false;
+    let _ = listeners.process_all::<AnyListener>(&mut found);
+    found
+}
+
+/// A node within a rendered tree, to be written by `subtree_html`
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Subtree<'a> {
+    /// The first element marked with the ref
+    Ref(ElementRef),
+    /// The keyed node reached through nodes with these keys, from the outermost down, as
+    /// yielded by the `KeyIter` passed to the updater along with a message
+    KeyPath(&'a [usize]),
+}
+
+/// Writes the node identified by `subtree`, and its descendants, to a string of HTML, or
+/// returns `None` if `nodes` has no such node.
+///
+/// When rendering client-side, `JsIo::export_html` writes a subtree of the rendered page, such
+/// as for a "copy as HTML" button or a preview of an email template.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::{DomNode, ElementRef};
+/// use domafic::html_writer::{subtree_html, Subtree};
+/// use domafic::tags::{div, li, p, ul};
+/// use std::marker::PhantomData;
+///
+/// const PREVIEW: ElementRef = ElementRef("preview");
+///
+/// let page = div((
+///     p("Hi <name>,").with_attributes([PREVIEW.attribute()]),
+///     ul([li("first").with_key(1), li("second").with_key(2)]).with_key(0),
+///     PhantomData::<()>,
+/// ));
+///
+/// assert_eq!(
+///     subtree_html(&page, Subtree::Ref(PREVIEW)).unwrap(),
+///     "<p data-domafic-ref=\"preview\">Hi &lt;name&gt;,</p>"
+/// );
+/// assert_eq!(subtree_html(&page, Subtree::KeyPath(&[0, 2])).unwrap(), "<li>second</li>");
+/// assert_eq!(subtree_html(&page, Subtree::KeyPath(&[2])), None);
+/// ```
+pub fn subtree_html<M, T: DomNodes<M>>(nodes: &T, subtree: Subtree) -> Option<String> {
+    let target = match subtree {
+        Subtree::Ref(element) => SubtreeTarget::Ref(element.name()),
+        Subtree::KeyPath(path) => SubtreeTarget::KeyPath(path.to_vec()),
+    };
+    let mut acc = SubtreeAcc { target, keys: Vec::new(), html: None };
+    nodes.process_all::<SubtreeWriter>(&mut acc).ok()?;
+    acc.html
+}
+
+enum SubtreeTarget {
+    Ref(&'static str),
+    KeyPath(Vec<usize>),
+}
+
+struct SubtreeAcc {
+    target: SubtreeTarget,
+    // Keys of the current node and its keyed ancestors
+    keys: Vec<usize>,
+    html: Option<String>,
+}
+
+/// Processor searching for a subtree and writing it once it's found
+struct SubtreeWriter;
+impl<'a, M> DomNodeProcessor<'a, M> for SubtreeWriter {
+    type Acc = SubtreeAcc;
+    type Error = io::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(acc: &mut SubtreeAcc, node: &T) -> Result<(), io::Error> {
+            if acc.html.is_some() {
+                return Ok(());
+            }
+            if let Some(key) = node.key() {
+                acc.keys.push(key as usize);
+            }
+            let is_target = match acc.target {
+                SubtreeTarget::Ref(name) => node.attributes()
+                    .any(|attr| attr.0 == REF_ATTRIBUTE && attr.1.as_str() == name),
+                SubtreeTarget::KeyPath(ref path) => node.key().is_some() && acc.keys == *path,
+            };
+            if is_target {
+                let mut html = Vec::new();
+                node.write_html(&mut html)?;
+                acc.html = Some(String::from_utf8(html)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?);
+            } else if let DomValue::Element { .. } = node.value() {
+                node.children().process_all::<SubtreeWriter>(acc)?;
+            }
+            if node.key().is_some() {
+                acc.keys.pop();
+            }
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
@@ -0,0 +1,112 @@
+//! Checks for element ids used more than once in a rendered tree.
+//!
+//! An id should identify a single element on the page. When two elements share one, a
+//! `label` whose `for` names it, an anchor link to `#id` and `document.getElementById` all
+//! silently pick the first, so the second element can't be labelled or linked to.
+//!
+//! `check` lists every id which appears again after its first element, with the positions of
+//! both. `Document::validate` includes them, `Document::write` logs them as warnings in debug
+//! builds with the `logging` feature, and the web runtime warns in the console after rendering a tree which has some.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::ids::check;
+//! use domafic::tags::{div, input, label};
+//! use std::marker::PhantomData;
+//!
+//! let form = div((
+//!     label(("Email", PhantomData::<()>)).with_id("email"),
+//!     input(()).with_id("email"),
+//! ));
+//!
+//! let duplicates = check(&form);
+//! assert_eq!(duplicates.len(), 1);
+//! assert_eq!(duplicates[0].first, vec![0, 0]);
+//! assert_eq!(duplicates[0].to_string(), "id `email` is used at 0.0 and again at 0.1");
+//! ```
+
+use {DomNode, DomNodes, DomValue};
+use processors::DomNodeProcessor;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// An id given to more than one element
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct DuplicateId {
+    /// The repeated id
+    pub id: String,
+    /// Position of the first element with the id, as the indices of it and its ancestors among
+    /// their siblings
+    pub first: Vec<usize>,
+    /// Position of the later element with the id
+    pub second: Vec<usize>,
+}
+
+fn write_path(f: &mut fmt::Formatter, path: &[usize]) -> fmt::Result {
+    let path: Vec<String> = path.iter().map(|index| index.to_string()).collect();
+    write!(f, "{}", path.join("."))
+}
+
+impl fmt::Display for DuplicateId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "id `{}` is used at ", self.id)?;
+        write_path(f, &self.first)?;
+        write!(f, " and again at ")?;
+        write_path(f, &self.second)
+    }
+}
+
+/// Lists the elements in `nodes` whose id was already given to an earlier element, in the
+/// order they appear.
+///
+/// An element used a third time appears again, paired with the first element.
+pub fn check<M, T: DomNodes<M>>(nodes: &T) -> Vec<DuplicateId> {
+    let mut acc = IdRegistryAcc { ids: HashMap::new(), path: vec![0], duplicates: Vec::new() };
+    nodes.process_all::<IdRegistry>(&mut acc).unwrap();
+    acc.duplicates
+}
+
+struct IdRegistryAcc {
+    // Position of the first element with each id
+    ids: HashMap<String, Vec<usize>>,
+    // Position of the current node
+    path: Vec<usize>,
+    duplicates: Vec<DuplicateId>,
+}
+
+/// Processor registering the id of each element
+struct IdRegistry;
+impl<'a, M> DomNodeProcessor<'a, M> for IdRegistry {
+    type Acc = IdRegistryAcc;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(acc: &mut IdRegistryAcc, node: &T) -> Result<(), ()> {
+            if let DomValue::Element { .. } = node.value() {
+                // Browsers ignore every `id` attribute after the first
+                let id = node.attributes().find(|attr| attr.0 == "id");
+                if let Some(id) = id.map(|attr| attr.1.as_str()).filter(|id| !id.is_empty()) {
+                    if let Some(first) = acc.ids.get(id) {
+                        acc.duplicates.push(DuplicateId {
+                            id: id.to_string(),
+                            first: first.clone(),
+                            second: acc.path.clone(),
+                        });
+                    } else {
+                        acc.ids.insert(id.to_string(), acc.path.clone());
+                    }
+                }
+
+                acc.path.push(0);
+                node.children().process_all::<IdRegistry>(acc)?;
+                acc.path.pop();
+            }
+            *acc.path.last_mut().unwrap() += 1;
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
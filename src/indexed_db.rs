@@ -0,0 +1,177 @@
+//! Enable with the `indexed_db` feature.
+//!
+//! `localStorage` holds a few megabytes of strings, which isn't enough for an application that
+//! keeps its data offline. IndexedDB stores far more, but only asynchronously. When rendering
+//! client-side, the `JsIo` reads and writes the entries of an `IdbStore`: an object store
+//! within a database, created the first time it's used. Each request delivers its result as a
+//! message, and `scan` delivers every entry of a store in a series of batches, so a large store
+//! isn't read into memory at once.
+//!
+//! Values are encoded as JSON with serde, and stored as structured objects, so they can be
+//! inspected with the browser's developer tools. The functions in this module encode and decode
+//! values of any serde type, on top of the `JsIo` methods, which take and return JSON.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::indexed_db::{decode, encode, parse_batch, IdbStore};
+//!
+//! const NOTES: IdbStore = IdbStore { database: "notes-app", name: "notes" };
+//!
+//! let note = ("Groceries".to_string(), true);
+//! let json = encode(&note).unwrap();
+//! assert_eq!(json, r#"["Groceries",true]"#);
+//! assert_eq!(decode::<(String, bool)>(&json).unwrap(), note);
+//! assert!(decode::<(String, bool)>("{}").is_err());
+//!
+//! // In an update, when rendering client-side:
+//! // indexed_db::put(js_io, NOTES, "groceries", &note, Msg::Saved).unwrap();
+//! // indexed_db::scan(js_io, NOTES, DEFAULT_BATCH_SIZE, Msg::NotesLoaded);
+//! # let _ = NOTES;
+//! # let batch = parse_batch(r#"[["groceries",["Groceries",true]]]"#, true).unwrap();
+//! # assert_eq!(batch.decode::<(String, bool)>().unwrap().entries,
+//! #     vec![("groceries".to_string(), note)]);
+//! ```
+
+extern crate serde;
+extern crate serde_json;
+
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+use self::serde_json::Value;
+
+use std::error;
+use std::fmt;
+
+/// Number of entries delivered in each batch by a `scan` unless another size is given
+pub const DEFAULT_BATCH_SIZE: u32 = 100;
+
+/// An object store within an IndexedDB database, which holds values under string keys.
+///
+/// The database and the store are created the first time they're used.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct IdbStore {
+    /// Name of the database, which is shared by every page of the site
+    pub database: &'static str,
+    /// Name of the object store within the database
+    pub name: &'static str,
+}
+
+impl IdbStore {
+    /// The store named `name` in the database named `database`
+    pub fn new(database: &'static str, name: &'static str) -> IdbStore {
+        IdbStore { database, name }
+    }
+}
+
+/// Error returned by an IndexedDB request
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum IdbError {
+    /// The browser doesn't support IndexedDB, or doesn't allow the page to use it, such as in
+    /// some private browsing modes
+    Unsupported,
+    /// The request failed, such as by exceeding the storage quota, with the browser's message
+    Failed(String),
+    /// A value couldn't be encoded to, or decoded from, JSON
+    Malformed(String),
+}
+
+impl fmt::Display for IdbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IdbError::Unsupported => write!(f, "IndexedDB is not available"),
+            IdbError::Failed(ref message) => write!(f, "IndexedDB request failed: {}", message),
+            IdbError::Malformed(ref message) => write!(f, "malformed IndexedDB value: {}", message),
+        }
+    }
+}
+
+impl error::Error for IdbError {}
+
+/// Entries of a store read by a scan, in the order of their keys
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct IdbBatch<T> {
+    /// Keys and values of the entries
+    pub entries: Vec<(String, T)>,
+    /// Whether this is the last batch of the scan
+    pub done: bool,
+}
+
+impl IdbBatch<String> {
+    /// Decodes the JSON of each value in the batch
+    pub fn decode<T: DeserializeOwned>(self) -> Result<IdbBatch<T>, IdbError> {
+        let entries = self.entries.into_iter()
+            .map(|(key, json)| decode(&json).map(|value| (key, value)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(IdbBatch { entries, done: self.done })
+    }
+}
+
+/// Encodes `value` as the JSON of an IndexedDB value
+pub fn encode<T: Serialize>(value: &T) -> Result<String, IdbError> {
+    serde_json::to_string(value).map_err(|err| IdbError::Malformed(err.to_string()))
+}
+
+/// Decodes the JSON of an IndexedDB value
+pub fn decode<T: DeserializeOwned>(json: &str) -> Result<T, IdbError> {
+    serde_json::from_str(json).map_err(|err| IdbError::Malformed(err.to_string()))
+}
+
+/// Parses a batch of entries passed from JavaScript as a JSON array of `[key, value]` pairs
+#[doc(hidden)]
+pub fn parse_batch(json: &str, done: bool) -> Result<IdbBatch<String>, IdbError> {
+    let entries: Vec<(String, Value)> = decode(json)?;
+    let entries = entries.into_iter().map(|(key, value)| (key, value.to_string())).collect();
+    Ok(IdbBatch { entries, done })
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub use self::web::{delete, get, put, scan};
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{decode, encode, IdbBatch, IdbError, IdbStore};
+    use super::serde::Serialize;
+    use super::serde::de::DeserializeOwned;
+    use web_render::JsIo;
+
+    /// Reads the value stored under `key`, delivering the message produced by `f` from it, or
+    /// from `None` if there is none
+    pub fn get<T, M, F>(js_io: &dyn JsIo<M>, store: IdbStore, key: &str, f: F)
+        where T: DeserializeOwned, F: Fn(Result<Option<T>, IdbError>) -> M + 'static
+    {
+        js_io.idb_get(store, key, Box::new(move |result| {
+            f(result.and_then(|json| json.map(|json| decode(&json)).transpose()))
+        }));
+    }
+
+    /// Stores `value` under `key`, replacing any previous value, and delivers the message
+    /// produced by `f` once it's been written.
+    ///
+    /// Returns an error without issuing the request if `value` can't be encoded.
+    pub fn put<T, M, F>(js_io: &dyn JsIo<M>, store: IdbStore, key: &str, value: &T, f: F)
+        -> Result<(), IdbError>
+        where T: Serialize, F: Fn(Result<(), IdbError>) -> M + 'static
+    {
+        let json = encode(value)?;
+        js_io.idb_put(store, key, &json, Box::new(f));
+        Ok(())
+    }
+
+    /// Removes the value stored under `key`, if any, and delivers the message produced by `f`
+    /// once it's been removed
+    pub fn delete<M, F>(js_io: &dyn JsIo<M>, store: IdbStore, key: &str, f: F)
+        where F: Fn(Result<(), IdbError>) -> M + 'static
+    {
+        js_io.idb_delete(store, key, Box::new(f));
+    }
+
+    /// Reads every entry of `store` in the order of their keys, delivering a message produced
+    /// by `f` for each batch of up to `batch_size` entries. The last batch is `done`, and no
+    /// further batches are delivered after an error.
+    pub fn scan<T, M, F>(js_io: &dyn JsIo<M>, store: IdbStore, batch_size: u32, f: F)
+        where T: DeserializeOwned, F: Fn(Result<IdbBatch<T>, IdbError>) -> M + 'static
+    {
+        js_io.idb_scan(store, batch_size, Box::new(move |batch| f(batch.and_then(IdbBatch::decode))));
+    }
+}
@@ -0,0 +1,135 @@
+//! Loading pages of a list as the user scrolls to its end.
+//!
+//! `infinite_scroll` renders a sentinel node to be placed after the last item of a list. When
+//! rendering client-side, the runtime watches sentinels with an `IntersectionObserver`, and
+//! when one comes within 200 pixels of the viewport, its listener produces a message asking for
+//! the next page. A `Paging` in the application state keeps track of which page is next, and
+//! disarms the sentinel while a page is loading, or once there are no more pages, so that a
+//! page is only asked for once. If the sentinel is still in view when a page has loaded, such
+//! as when the page was too short to fill the viewport, the next page is asked for straight
+//! away.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::infinite_scroll::{infinite_scroll, Paging};
+//! use domafic::tags::{div, li, ul};
+//!
+//! struct State { items: Vec<String>, paging: Paging }
+//! enum Msg { LoadMore(u32), Loaded(Vec<String>) }
+//!
+//! fn render(state: &State) -> impl DomNode<Msg> + '_ {
+//!     div((
+//!         ul(state.items.iter().map(|item| li(item.as_str())).collect::<Vec<_>>()),
+//!         infinite_scroll(0, &state.paging, Msg::LoadMore),
+//!     ))
+//! }
+//!
+//! fn update(state: &mut State, msg: Msg) {
+//!     match msg {
+//!         // Ignore requests for a page which is already loading
+//!         Msg::LoadMore(page) => if state.paging.start(page) {
+//!             // Fetch `page`, then deliver `Msg::Loaded`
+//!         },
+//!         Msg::Loaded(items) => {
+//!             state.paging.loaded(!items.is_empty());
+//!             state.items.extend(items);
+//!         },
+//!     }
+//! }
+//!
+//! let mut state = State { items: Vec::new(), paging: Paging::new(1) };
+//! update(&mut state, Msg::LoadMore(1));
+//! assert!(state.paging.is_loading());
+//! assert_eq!(render(&state).displayable().to_string(),
+//!     "<div><ul></ul><div data-domafic-sentinel=\"false\"></div></div>");
+//!
+//! update(&mut state, Msg::Loaded(vec!["First".to_string()]));
+//! assert_eq!(state.paging.next_page(), Some(2));
+//! ```
+
+use {DomNode, KeyValue};
+use AttributeValue::Bool;
+use listener::on_custom;
+use tags::{attributes, div};
+
+/// Attribute marking a sentinel, whose value is whether it asks for the next page when it
+/// comes into view
+pub const SENTINEL_ATTRIBUTE: &str = "data-domafic-sentinel";
+
+/// Custom event dispatched on a sentinel when it comes into view while it's armed
+pub const VISIBLE_EVENT: &str = "domafic:visible";
+
+/// Which page of an infinitely scrolling list is next, and whether it's loading
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Paging {
+    next_page: u32,
+    loading: bool,
+    exhausted: bool,
+}
+
+impl Paging {
+    /// Paging which starts at `first_page`
+    pub fn new(first_page: u32) -> Paging {
+        Paging { next_page: first_page, loading: false, exhausted: false }
+    }
+
+    /// The page to ask for next, or `None` while a page is loading or once there are no more
+    /// pages
+    pub fn next_page(&self) -> Option<u32> {
+        if self.loading || self.exhausted { None } else { Some(self.next_page) }
+    }
+
+    /// Whether a page is loading
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    /// Whether the last page has been loaded
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Starts loading `page`, returning whether it should be fetched.
+    ///
+    /// Returns `false`, and changes nothing, if `page` isn't the next page, such as when a
+    /// sentinel from an earlier render asked for it again.
+    pub fn start(&mut self, page: u32) -> bool {
+        if self.next_page() != Some(page) {
+            return false;
+        }
+        self.loading = true;
+        true
+    }
+
+    /// Finishes loading the current page, moving on to the next one, or stopping if `has_more`
+    /// is `false`
+    pub fn loaded(&mut self, has_more: bool) {
+        if self.loading {
+            self.loading = false;
+            self.next_page += 1;
+            self.exhausted = !has_more;
+        }
+    }
+
+    /// Gives up loading the current page, so that it's asked for again the next time the
+    /// sentinel comes into view
+    pub fn failed(&mut self) {
+        self.loading = false;
+    }
+}
+
+/// A sentinel which produces `f(page)` when it comes into view, while `paging` has a next page.
+///
+/// The sentinel is keyed with `sentinel_key`, so that it can be told apart from the list's
+/// items, and renders as an empty `<div>` which can be styled to show that more items are
+/// loading.
+pub fn infinite_scroll<M, F>(sentinel_key: usize, paging: &Paging, f: F) -> impl DomNode<M>
+    where F: Fn(u32) -> M
+{
+    let armed = paging.next_page().is_some();
+    let page = paging.next_page;
+    let sentinel: [KeyValue; 1] = [(SENTINEL_ATTRIBUTE, Bool(armed))];
+    div((attributes(sentinel), on_custom(VISIBLE_EVENT, move |_| f(page)))).with_key(sentinel_key)
+}
@@ -0,0 +1,33 @@
+//! Classes applied to elements while they're hovered or contain focus.
+//!
+//! Styling an element differently while the pointer is over it, or while one of its
+//! descendants has focus, doesn't need to go through the application's state.
+//! `DomNode::with_hover_class` and `DomNode::with_focus_within_class` mark a node with the
+//! classes to apply, and when rendering client-side, the runtime adds them to the element while
+//! it's hovered or contains focus, and removes them afterwards. The classes are kept when the
+//! element is patched by a later render.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::tags::{input, label};
+//! use std::marker::PhantomData;
+//!
+//! let field = label(input(PhantomData::<()>))
+//!     .with_hover_class("highlighted")
+//!     .with_focus_within_class("active editing");
+//!
+//! assert_eq!(field.displayable().to_string(), "\
+//!     <label data-domafic-focus-within-class=\"active editing\" \
+//!     data-domafic-hover-class=\"highlighted\"><input></label>");
+//! ```
+//!
+//! Each node has one set of classes of each kind. To apply several classes, separate them
+//! with spaces.
+
+/// Attribute giving the classes applied to an element while it's hovered
+pub const HOVER_CLASS_ATTRIBUTE: &str = "data-domafic-hover-class";
+
+/// Attribute giving the classes applied to an element while it or a descendant has focus
+pub const FOCUS_WITHIN_CLASS_ATTRIBUTE: &str = "data-domafic-focus-within-class";
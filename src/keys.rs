@@ -30,8 +30,17 @@ impl Keys {
 }
 
 /// An iterator over keys into a `DomNode` tree.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct KeyIter(Keys, u32);
 
+impl KeyIter {
+    /// An iterator over no keys, for passing messages which didn't come from a listener
+    /// directly to an updater
+    pub fn empty() -> KeyIter {
+        Keys::new().into_iter()
+    }
+}
+
 impl Iterator for KeyIter {
     type Item = usize;
 
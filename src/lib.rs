@@ -128,25 +128,235 @@
 #![allow(unused_unsafe)]
 #![deny(missing_docs)]
 
+/// Data embedded in server-rendered pages for the client application to start from
+#[cfg(feature = "bootstrap")]
+pub mod bootstrap;
+
+/// A busy signal derived from the commands in flight, for progress indicators
+#[cfg(any(feature = "use_std", test))]
+pub mod busy;
+
+/// Values available to render functions without passing them through arguments
+#[cfg(any(feature = "use_std", test))]
+pub mod context;
+
+/// Content Security Policy nonces for server-side rendering
+#[cfg(any(feature = "use_std", test))]
+pub mod csp;
+
+/// Scoped CSS for components, collected into a deduplicated stylesheet
+#[cfg(any(feature = "use_std", test))]
+pub mod css;
+
+/// Calendar dates, a date picker component, and nodes displaying dates
+#[cfg(any(feature = "use_std", test))]
+pub mod date_picker;
+
+/// A builder for complete HTML pages
+#[cfg(any(feature = "use_std", test))]
+pub mod document;
+
+/// The protocol used to describe a running application to browser devtools extensions
+#[cfg(any(feature = "use_std", test))]
+pub mod devtools;
+
 /// Trait for elements that can be drawn as to HTML DOM nodes
 pub mod dom_node;
-pub use dom_node::{DomNode, DomValue};
+pub use dom_node::{DomNode, DomValue, comment, doctype, uncontrolled};
+#[cfg(any(feature = "use_std", test))]
+pub use dom_node::keyed_by;
+
+/// Side effects described as values, which can be recorded by a mock executor in tests
+#[cfg(any(feature = "use_std", test))]
+pub mod effects;
+
+/// Names identifying rendered elements for browser commands
+mod element_ref;
+pub use element_ref::{ElementRef, REF_ATTRIBUTE};
+
+/// The error type shared by the runtime, the writers and the IO modules
+#[cfg(any(feature = "use_std", test))]
+pub mod error;
+#[cfg(any(feature = "use_std", test))]
+pub use error::Error;
+
+/// Feature flags embedded in server-rendered pages or loaded over HTTP, provided as context
+#[cfg(feature = "feature_flags")]
+pub mod feature_flags;
+
+/// Generating sitemaps and RSS or Atom feeds from a list of routes
+#[cfg(any(feature = "use_std", test))]
+pub mod feeds;
+
+/// Confining keyboard focus to modal dialogs
+#[cfg(any(feature = "use_std", test))]
+pub mod focus;
+
+/// Building GraphQL requests and decoding their responses
+#[cfg(feature = "graphql")]
+pub mod graphql;
 
 /// Types, traits and functions for writing a `DomNode` to HTML
 #[cfg(any(feature = "use_std", test))]
 pub mod html_writer;
 
+/// Checks for element ids used more than once in a rendered tree
+#[cfg(any(feature = "use_std", test))]
+pub mod ids;
+
+/// IndexedDB stores for large client-side data, with values encoded by serde
+#[cfg(feature = "indexed_db")]
+pub mod indexed_db;
+
+/// Loading pages of a list as the user scrolls to its end
+pub mod infinite_scroll;
+
+/// Classes applied to elements while they're hovered or contain focus
+pub mod interaction;
+
 mod keys;
 pub use keys::KeyIter;
+
 /// Types, traits, and functions for creating event handlers
 pub mod listener;
-pub use listener::{Listener, Event, on};
+pub use listener::{Listener, Event, on, on_custom};
+#[cfg(any(feature = "use_std", test))]
+pub use listener::{OwnedEvent, on_owned};
+
+/// Experimental live views, running update and render on a server and patching the page's DOM
+#[cfg(feature = "live")]
+pub mod live;
+
+/// A `log` backend which writes to the browser console or to stderr
+#[cfg(feature = "logging")]
+pub mod logging;
+
+/// Size and depth of rendered trees, for keeping pages within a budget
+#[cfg(any(feature = "use_std", test))]
+pub mod metrics;
+
+/// Encoding forms with file uploads as `multipart/form-data`
+#[cfg(any(feature = "use_std", test))]
+pub mod multipart;
+
+/// Checks for nesting of elements which browsers change while parsing HTML
+#[cfg(any(feature = "use_std", test))]
+pub mod nesting;
+
+/// Retrying HTTP requests which fail while offline once the device reconnects
+#[cfg(any(feature = "use_std", test))]
+pub mod offline;
+
+/// Rolling back optimistic state updates when the command they anticipated fails
+#[cfg(any(feature = "use_std", test))]
+pub mod optimistic;
+
+/// Saving application state to storage and restoring it through versioned migrations
+#[cfg(feature = "persist")]
+pub mod persist;
+
+/// Moving averages of render and patch timings, for finding the causes of jank
+#[cfg(any(feature = "use_std", test))]
+pub mod perf;
+
 /// Traits for processing collections of `DomNode`s or `Listener`s
 pub mod processors;
 pub use processors::{DomNodes, Listeners};
+
+/// Limiting how often messages from high-frequency events are handled, by dropping or
+/// coalescing the excess
+#[cfg(any(feature = "use_std", test))]
+pub mod rate_limit;
+
+/// Recording the messages of a session and replaying them to reproduce its state
+#[cfg(feature = "replay")]
+pub mod replay;
+
+/// Typed routes, and links which navigate between them without reloading the page
+#[cfg(any(feature = "use_std", test))]
+#[macro_use]
+pub mod router;
+
+/// Re-rendering similar pages to HTML by reusing the unchanged parts of previous renders
+#[cfg(any(feature = "use_std", test))]
+pub mod render_cache;
+
+/// Scheduling re-renders, timeouts and animation frames, with a virtual clock for tests
+#[cfg(any(feature = "use_std", test))]
+pub mod scheduler;
+
+/// Service worker lifecycle messages, and a prompt to reload into a new version of the app
+#[cfg(any(feature = "use_std", test))]
+pub mod service_worker;
+
+/// Keyboard shortcuts mapped to messages in global or named scopes
+#[cfg(any(feature = "use_std", test))]
+pub mod shortcuts;
+
+/// Open Graph and Twitter card metadata for link previews
+#[cfg(any(feature = "use_std", test))]
+pub mod social_meta;
+
+/// Generating a static site by rendering each route to a file
+#[cfg(any(feature = "use_std", test))]
+pub mod ssg;
+
+/// Spring physics for animating values with momentum
+#[cfg(any(feature = "use_std", test))]
+pub mod spring;
+
+/// Subresource integrity hashes for external scripts and stylesheets
+#[cfg(any(feature = "use_std", test))]
+pub mod sri;
+
+/// Key-value string storage, such as the browser's `localStorage`
+#[cfg(any(feature = "use_std", test))]
+pub mod storage;
+
+/// Independent stores of application state, combined into a single state for rendering
+#[cfg(any(feature = "use_std", test))]
+#[macro_use]
+pub mod stores;
+
+/// Typed inline `style` attributes
+#[cfg(any(feature = "use_std", test))]
+pub mod styles;
+
+/// A typed builder for CSS stylesheets
+#[cfg(any(feature = "use_std", test))]
+pub mod stylesheet;
+
+/// Subtrees precompiled to HTML, for parts of a page which never change
+#[cfg(any(feature = "use_std", test))]
+pub mod static_node;
+
+/// Subscriptions to page-level events such as visibility changes and unloading
+#[cfg(any(feature = "use_std", test))]
+pub mod subscriptions;
+
+/// Generators of arbitrary `DomNode` trees for property-based testing with `quickcheck`
+#[cfg(feature = "testing")]
+pub mod testing;
+
+/// Themes of colors and spacing values set as CSS custom properties
+#[cfg(any(feature = "use_std", test))]
+pub mod theme;
+
 /// Types and functions for creating tag elements such as `div`s or `span`s
 pub mod tags;
 
+/// Web app manifests, and the meta tags which let browsers install a site as an app
+#[cfg(any(feature = "use_std", test))]
+pub mod web_manifest;
+
+/// Adapters for embedding widgets from JS libraries, such as maps or code editors
+#[cfg(any(feature = "use_std", test))]
+pub mod widget;
+
+/// Types, traits and functions for writing a `DomNode` to HTML, XHTML or XML
+#[cfg(any(feature = "use_std", test))]
+pub mod xml_writer;
+
 /// Functions for interacting with a webpage when rendering client-side using asmjs or emscripten
 #[cfg(all(feature = "web_render", target_os = "emscripten"))]
 pub mod web_render;
@@ -184,6 +394,26 @@ impl AttributeValue {
             AttributeValue::Bool(false) => "false",
         }
     }
+
+    /// Returns whether an attribute named `key` is set, if it's an HTML boolean attribute.
+    ///
+    /// `Bool` values are given to boolean attributes, such as `disabled`, which are set by
+    /// being present and unset by being absent, whatever their text. They're written by name
+    /// alone when `true` and left out when `false`. Attributes whose text is the keyword `true`
+    /// or `false`, such as `aria-expanded`, `data-` attributes and `draggable`, aren't boolean
+    /// attributes, so this returns `None` for them, as it does for values other than `Bool`s.
+    pub fn as_bool_attribute(&self, key: &str) -> Option<bool> {
+        match *self {
+            AttributeValue::Bool(value) if !has_keyword_values(key) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+// Attributes whose values are `true` or `false` as text, rather than being set by their presence
+fn has_keyword_values(key: &str) -> bool {
+    key.starts_with("aria-") || key.starts_with("data-") ||
+        ["contenteditable", "draggable", "spellcheck"].contains(&key)
 }
 
 #[cfg(any(feature = "use_std", test))]
@@ -193,6 +423,25 @@ impl std::fmt::Display for AttributeValue {
     }
 }
 
+impl From<&'static str> for AttributeValue {
+    fn from(value: &'static str) -> AttributeValue {
+        AttributeValue::Str(value)
+    }
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl From<String> for AttributeValue {
+    fn from(value: String) -> AttributeValue {
+        AttributeValue::OwnedStr(value)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> AttributeValue {
+        AttributeValue::Bool(value)
+    }
+}
+
 mod opt_std {
     #[cfg(not(any(feature = "use_std", test)))]
     pub extern crate core as std;
@@ -205,7 +454,7 @@ mod opt_std {
 
 #[cfg(test)]
 mod tests {
-    use super::{DomNode, DomNodes, DomValue, KeyValue};
+    use super::{DomNode, DomNodes, DomValue, Event, KeyValue, Listener, OwnedEvent, on_custom, on_owned};
     use super::AttributeValue::Str;
     use super::tags::*;
     use super::processors::{DomNodeProcessor, EmptyListeners};
@@ -215,6 +464,12 @@ mod tests {
     #[cfg(feature = "use_either_n")]
     use self::either_n::*;
 
+    #[cfg(any(feature = "persist", feature = "replay"))]
+    extern crate serde_json;
+
+    #[cfg(feature = "testing")]
+    extern crate quickcheck;
+
     use std::marker::PhantomData;
 
     static EMPTY_NODES_REF: &'static () = &();
@@ -276,6 +531,34 @@ mod tests {
         }
     }
 
+    struct Count(u32);
+    impl<M> DomNodes<M> for Count {
+        fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+            P::get_processor()(acc, self)
+        }
+    }
+    impl<M> DomNode<M> for Count {
+        type Children = ();
+        type Listeners = EmptyListeners;
+        type WithoutListeners = Count;
+
+        fn key(&self) -> Option<u32> { None }
+        fn get_attribute(&self, _index: usize) -> Option<&KeyValue> { None }
+
+        fn children(&self) -> &Self::Children { EMPTY_NODES_REF }
+        fn listeners(&self) -> &Self::Listeners { EMPTY_LISTN_REF }
+        fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+            (EMPTY_NODES_REF, EMPTY_LISTN_REF)
+        }
+        fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+            (self, EmptyListeners)
+        }
+
+        fn value(&self) -> DomValue<'_> {
+            DomValue::OwnedText(format!("{} < {}", self.0, self.0 + 1))
+        }
+    }
+
     struct ChildCounter<M=Never>(PhantomData<M>);
     impl<'a, M> DomNodeProcessor<'a, M> for ChildCounter<M> {
         type Acc = usize;
@@ -453,4 +736,2444 @@ mod tests {
         )).with_attributes([("attr1", Str("val1"))]);
         check_attribute_list(div2);
     }
+
+    #[test]
+    fn owned_events() {
+        let event = Event {
+            type_str: Some("input"),
+            target_value: Some("typed text"),
+            client_x: 1,
+            client_y: 2,
+            offset_x: 3,
+            offset_y: 4,
+            which_keycode: 13,
+            shift_key: true,
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: true,
+            detail: Some("{\"count\":2}"),
+        };
+
+        let owned = event.to_owned();
+        assert_eq!(owned.target_value, Some("typed text".to_string()));
+        assert_eq!(owned.as_event(), event);
+
+        let listener = on_owned("input", |event: OwnedEvent| event);
+        assert_eq!(listener.event_type_handled(), "input");
+        assert_eq!(listener.handle_event(event), owned);
+
+        let listener = on_custom("cart:updated", |detail: Option<&str>| detail.map(String::from));
+        assert_eq!(listener.event_type_handled(), "cart:updated");
+        assert_eq!(listener.handle_event(event), Some("{\"count\":2}".to_string()));
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn persists_state() {
+        use super::persist::{Persist, RestoreError};
+        use super::storage::{MemoryStorage, Storage};
+
+        let storage = MemoryStorage::new();
+        let persist = Persist::<(u32, String)>::new("state", 1);
+        match persist.restore(&storage) {
+            Err(RestoreError::Missing) => {},
+            other => panic!("Expected missing state, found {:?}", other),
+        }
+
+        let state = (5, "five".to_string());
+        persist.save(&storage, &state).unwrap();
+        assert_eq!(persist.restore(&storage).unwrap(), state);
+
+        // State saved by a newer version can't be restored
+        storage.set("state", r#"{"version":2,"state":[5,"five"]}"#);
+        match persist.restore(&storage) {
+            Err(RestoreError::UnknownVersion(2)) => {},
+            other => panic!("Expected unknown version, found {:?}", other),
+        }
+
+        // State from an older version requires a migration
+        storage.set("state", r#"{"version":0,"state":5}"#);
+        match persist.restore(&storage) {
+            Err(RestoreError::MissingMigration(0)) => {},
+            other => panic!("Expected missing migration, found {:?}", other),
+        }
+        let persist = persist.with_migration(0, |count|
+            serde_json::Value::Array(vec![count, "five".into()]));
+        assert_eq!(persist.restore(&storage).unwrap(), state);
+    }
+
+    #[test]
+    fn settles_optimistic_updates() {
+        use super::optimistic::*;
+
+        struct State {
+            count: i32,
+            pending: Pending<State, Msg>,
+        }
+        impl Optimistic<Msg> for State {
+            fn pending(&mut self) -> &mut Pending<State, Msg> { &mut self.pending }
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum Msg {
+            Done(CommandId, bool),
+            Failed,
+        }
+        impl ReportsCommand for Msg {
+            fn command_result(&self) -> Option<CommandResult> {
+                match *self {
+                    Msg::Done(id, true) => Some(CommandResult::Succeeded(id)),
+                    Msg::Done(id, false) => Some(CommandResult::Failed(id)),
+                    Msg::Failed => None,
+                }
+            }
+        }
+
+        let mut state = State { count: 0, pending: Pending::new() };
+        let first = state.pending.begin(Rollback::new(|state: &mut State| state.count -= 1), Msg::Failed);
+        state.count += 1;
+        let second = state.pending.begin(Rollback::new(|state: &mut State| state.count -= 10), Msg::Failed);
+        state.count += 10;
+        assert_eq!(state.pending.len(), 2);
+
+        assert_eq!(settle(&mut state, &Msg::Done(second, false)), Some(Msg::Failed));
+        assert_eq!(state.count, 1);
+
+        assert_eq!(settle(&mut state, &Msg::Done(first, true)), None);
+        assert_eq!(state.count, 1);
+        assert!(state.pending.is_empty());
+
+        // Settling an unknown or already-settled command does nothing
+        assert_eq!(settle(&mut state, &Msg::Done(second, false)), None);
+        assert_eq!(state.count, 1);
+    }
+
+    #[cfg(feature = "graphql")]
+    #[test]
+    fn parses_graphql_responses() {
+        use super::graphql::{parse_response, Error, Response, ResponseError};
+
+        let response = parse_response::<Vec<u32>>(200, r#"{
+            "data": null,
+            "errors": [{
+                "message": "Not allowed",
+                "locations": [{"line": 2, "column": 3}],
+                "path": ["items", 1, "secret"]
+            }]
+        }"#).unwrap();
+        assert_eq!(response, Response {
+            data: None,
+            errors: vec![ResponseError {
+                message: "Not allowed".to_string(),
+                locations: vec![(2, 3)],
+                path: vec!["items".to_string(), "1".to_string(), "secret".to_string()],
+            }],
+        });
+
+        let response = parse_response::<Vec<u32>>(400, r#"{"errors":[{"message":"Bad"}]}"#).unwrap();
+        assert_eq!(response.errors[0].message, "Bad");
+
+        match parse_response::<Vec<u32>>(502, "Bad Gateway") {
+            Err(Error::Status(502)) => {},
+            other => panic!("Expected status error, found {:?}", other),
+        }
+        match parse_response::<Vec<u32>>(200, r#"{"data":{"not":"a list"}}"#) {
+            Err(Error::Json(_)) => {},
+            other => panic!("Expected JSON error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn subscription_sources() {
+        use super::subscriptions::{
+            on_before_unload, on_device_orientation, on_page_hide, DeviceOrientation,
+            SubscriptionSource,
+        };
+
+        let page_hide = on_page_hide(|persisted| persisted);
+        assert_eq!(page_hide.source(), SubscriptionSource::PageHide);
+        assert_eq!(page_hide.source().event_type(), "pagehide");
+        assert_eq!(page_hide.handle(&[1.0]), Some(true));
+        assert_eq!(page_hide.handle(&[0.0]), Some(false));
+
+        let unsaved_changes = true;
+        let before_unload = on_before_unload(unsaved_changes, || "flush");
+        assert_eq!(before_unload.source(), SubscriptionSource::BeforeUnload { prompt: true });
+        assert_eq!(before_unload.handle(&[]), Some("flush"));
+
+        let orientation = on_device_orientation(|orientation| orientation);
+        assert_eq!(orientation.handle(&[f64::NAN, 45.0, -10.0, 0.0]), Some(DeviceOrientation {
+            alpha: None,
+            beta: Some(45.0),
+            gamma: Some(-10.0),
+            absolute: false,
+        }));
+    }
+
+    #[test]
+    fn provides_context() {
+        use super::context;
+        use super::theme::{self, Color, Theme};
+
+        assert!(context::get::<u32>().is_none());
+        context::provide(1u32, || {
+            context::provide(2u32, || assert_eq!(*context::get::<u32>().unwrap(), 2));
+            assert_eq!(*context::get::<u32>().unwrap(), 1);
+        });
+        assert!(context::get::<u32>().is_none());
+
+        let dark = Theme::new("dark").with_color("text", Color::Rgba(255, 255, 255, 0.5));
+        let themed = theme::provide(&dark, || {
+            let current = theme::current().unwrap();
+            assert_eq!(current.name(), "dark");
+            assert_eq!(current.color("text"), Some(Color::Rgba(255, 255, 255, 0.5)));
+            div(PhantomData::<()>)
+        });
+        assert_eq!(
+            themed.get_attribute(1).unwrap().1.as_str(),
+            "--color-text: rgba(255, 255, 255, 0.5);"
+        );
+        assert!(theme::current().is_none());
+    }
+
+    #[test]
+    fn scopes_css() {
+        use super::css::{self, styled};
+        use super::tags::attributes;
+
+        css::clear();
+        let node = styled(
+            "a[title='x, y'], & > p { margin: 0 }\n\
+             @media (max-width: 600px) { & { display: none } }\n\
+             @keyframes spin { from { opacity: 0 } }",
+            div((attributes([("class", Str("card"))]), PhantomData::<()>)));
+        let class = node.get_attribute(0).unwrap().1.as_str().to_string();
+        assert!(class.starts_with("card dc-"));
+        assert!(node.get_attribute(1).is_none());
+
+        let scoped = &class["card ".len()..];
+        assert_eq!(css::stylesheet(), format!(
+            ".{0} a[title='x, y'], .{0} > p {{ margin: 0 }}\n\
+             @media (max-width: 600px) {{\n.{0} {{ display: none }}\n}}\n\
+             @keyframes spin {{ from {{ opacity: 0 }} }}",
+            scoped));
+
+        styled("color: red", div(PhantomData::<()>));
+        assert!(css::style_tag().ends_with(" { color: red }</style>"));
+    }
+
+    #[test]
+    fn scopes_typed_stylesheets() {
+        use super::css;
+        use super::stylesheet::{scoped, Rule, Selector, Stylesheet};
+        use super::theme::Length;
+
+        const HOVER: Selector = Selector::PseudoClass(&Selector::Scope, "hover");
+        let sheet = Stylesheet::new()
+            .with_rule(Rule::new(vec![HOVER, Selector::Class("title")]).with("opacity", 0.5))
+            .with_media("print", vec![Rule::new(vec![Selector::Scope]).with("margin", Length::Px(0.0))]);
+
+        css::clear();
+        let node = scoped(&sheet, div(PhantomData::<()>));
+        let class = node.get_attribute(0).unwrap().1.as_str().to_string();
+        assert_eq!(css::stylesheet(), format!(
+            ".{0}:hover, .{0} .title {{ opacity: 0.5; }}\n@media print {{\n.{0} {{ margin: 0px; }}\n}}",
+            class));
+    }
+
+    #[test]
+    fn writes_feeds() {
+        use super::feeds::{write_atom, write_rss, DateTime, Feed, FeedItem};
+
+        let published = DateTime { year: 2017, month: 3, day: 1, hour: 12, minute: 30, second: 0 };
+        assert_eq!(published.to_rfc822(), "Wed, 01 Mar 2017 12:30:00 GMT");
+        assert_eq!(published.to_rfc3339(), "2017-03-01T12:30:00Z");
+
+        let feed = Feed {
+            title: "Posts & News",
+            path: "/",
+            feed_path: "/feed.xml",
+            description: "Everything",
+            updated: published,
+        };
+        let posts = ["<first>", "second"];
+        let metadata = |post: &&str| if *post == "second" { None } else {
+            Some(FeedItem {
+                title: post.to_string(),
+                path: "/posts/1".to_string(),
+                summary: None,
+                author: Some("Taylor".to_string()),
+                published,
+            })
+        };
+
+        let mut rss = Vec::new();
+        write_rss(&mut rss, "https://example.com/", &feed, &posts, metadata).unwrap();
+        let rss = String::from_utf8(rss).unwrap();
+        assert!(rss.contains("<title>Posts &amp; News</title>"));
+        assert!(rss.contains("<item><title>&lt;first&gt;</title>\
+            <link>https://example.com/posts/1</link>"));
+        assert_eq!(rss.matches("<item>").count(), 1);
+
+        let mut atom = Vec::new();
+        write_atom(&mut atom, "https://example.com", &feed, &posts, metadata).unwrap();
+        let atom = String::from_utf8(atom).unwrap();
+        assert!(atom.contains("<author><name>Taylor</name></author>"));
+        assert!(atom.contains("<updated>2017-03-01T12:30:00Z</updated>"));
+    }
+
+    #[test]
+    fn writes_xml_dialects() {
+        use super::xml_writer::{Html, Xhtml, Xml, XmlDocument, XmlWriter};
+
+        let node = div((
+            attributes([("title", Str("\"a\" & <b>"))]),
+            (img(PhantomData::<()>), p(()), "1 < 2"),
+        ));
+
+        let mut html = Vec::new();
+        node.process_all::<XmlWriter<_, Html>>(&mut html).unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(),
+            "<div title=\"&quot;a&quot; &amp; &lt;b>\"><img><p></p>1 &lt; 2</div>");
+
+        let mut xhtml = Vec::new();
+        node.process_all::<XmlWriter<_, Xhtml>>(&mut xhtml).unwrap();
+        assert!(String::from_utf8(xhtml).unwrap().contains("<img/><p></p>"));
+
+        let mut xml = Vec::new();
+        XmlDocument::new()
+            .namespace(Some("amp"), "https://amp.dev")
+            .write::<_, _, _, Xml>(&mut xml, &node)
+            .unwrap();
+        let xml = String::from_utf8(xml).unwrap();
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?><div title="));
+        assert!(xml.contains("xmlns:amp=\"https://amp.dev\"><img/><p/>"));
+    }
+
+    #[test]
+    fn generates_static_sites() {
+        use super::css::styled;
+        use super::document::Document;
+        use super::ssg::{AssetManifest, Generator};
+        use std::fs;
+
+        let root = ::std::env::temp_dir().join("domafic-generates-static-sites");
+        let _ = fs::remove_dir_all(&root);
+        let (assets, out_dir) = (root.join("assets"), root.join("out"));
+        fs::create_dir_all(assets.join("images")).unwrap();
+        fs::write(assets.join("images/logo.png"), "png").unwrap();
+
+        let routes = ["/", "/posts/1", "/404.html"];
+        let report = Generator::new(&out_dir)
+            .with_assets(AssetManifest::scan(&assets).unwrap())
+            .generate(&routes, |route| route.to_string(), |route| *route, |route: &&str| {
+                let body = styled("color: red;", div((
+                    a((attributes([("href", Str("/posts/1/"))]), *route)),
+                    a((attributes([("href", Str("/index.html#top"))]), "Home")),
+                    a((attributes([("href", Str("https://example.com/missing"))]), "External")),
+                    a((attributes([("href", Str("/posts/2?ref=home"))]), "Missing")),
+                    img(attributes([("src", Str("/images/logo.png"))])),
+                    PhantomData::<()>,
+                )));
+                (Document::new(*route), body)
+            })
+            .unwrap();
+
+        assert_eq!(report.assets, vec![out_dir.join("images/logo.png")]);
+        assert_eq!(fs::read_to_string(&report.assets[0]).unwrap(), "png");
+        assert_eq!(report.pages, vec![
+            out_dir.join("index.html"),
+            out_dir.join("posts/1/index.html"),
+            out_dir.join("404.html"),
+        ]);
+        let page = fs::read_to_string(&report.pages[1]).unwrap();
+        assert!(page.starts_with("<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+            <title>/posts/1</title><style data-domafic-css>"));
+        assert!(page.ends_with("<img src=\"/images/logo.png\"></div></body></html>"));
+
+        let broken: Vec<_> = report.broken_links.iter()
+            .map(|link| (link.page.as_str(), link.href.as_str()))
+            .collect();
+        assert_eq!(broken, vec![
+            ("/", "/posts/2?ref=home"),
+            ("/posts/1", "/posts/2?ref=home"),
+            ("/404.html", "/posts/2?ref=home"),
+        ]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn writes_social_meta() {
+        use super::document::Document;
+        use super::social_meta::{Card, Error, SocialMeta};
+
+        let meta = SocialMeta::new("Post", "A \"quoted\" post")
+            .kind("article")
+            .site_name("Blog")
+            .twitter_site("@blog")
+            .card(Card::SummaryLargeImage);
+        assert_eq!(meta.validate(), Err(Error::Missing("og:image")));
+        assert_eq!(SocialMeta::new(" ", "Empty title").validate(), Err(Error::Missing("og:title")));
+        assert_eq!(meta.clone().image("https://example.com/a.png").url("/post").validate(),
+            Err(Error::RelativeUrl("og:url")));
+
+        let meta = meta.image("https://example.com/a.png").image_alt("A picture");
+        let mut html = Vec::new();
+        Document::new("Post").social_meta(&meta).unwrap().write(&mut html, &p(PhantomData::<()>)).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<title>Post</title>\
+            <meta property=\"og:title\" content=\"Post\">\
+            <meta property=\"og:description\" content=\"A &quot;quoted&quot; post\">\
+            <meta property=\"og:type\" content=\"article\">\
+            <meta property=\"og:site_name\" content=\"Blog\">\
+            <meta property=\"og:image\" content=\"https://example.com/a.png\">\
+            <meta property=\"og:image:alt\" content=\"A picture\">\
+            <meta name=\"twitter:card\" content=\"summary_large_image\">\
+            <meta name=\"twitter:site\" content=\"@blog\">"));
+    }
+
+    #[test]
+    fn writes_minified_html() {
+        use super::html_writer::write_minified;
+
+        let node = div((
+            attributes([("class", Str("a b")), ("id", Str("x\"y")), ("data-n", Str("1&2"))]),
+            (
+                ("  \n  ", p(("Some   ", b("bold"), "\ttext")), "\n  "),
+                (textarea("  a\n  b"), script("if (a  <  b) {}"), br(()), PhantomData::<()>),
+            ),
+        ));
+
+        let mut html = Vec::new();
+        write_minified(&node, &mut html).unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(),
+            "<div class=\"a b\" id=\"x&quot;y\" data-n=1&amp;2>\
+            <p>Some <b>bold</b> text</p> \
+            <textarea>  a\n  b</textarea><script>if &#40;a  &lt;  b&#41; &#123;&#125;</script><br></div>");
+    }
+
+    #[test]
+    fn writes_canonical_attributes() {
+        use super::html_writer::{Minifier, MinifyingHtmlWriter};
+        use super::xml_writer::{Canonical, Html, XmlWriter};
+
+        let node = div((
+            attributes([("id", Str("a")), ("class", Str("b  a b")), ("data-x", Str("1"))]),
+            (span(attributes([("title", Str("t")), ("class", Str("z y"))])), PhantomData::<()>),
+        ));
+
+        let mut html = Vec::new();
+        node.process_all::<XmlWriter<_, Canonical<Html>>>(&mut html).unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), "<div class=\"a b\" data-x=\"1\" id=\"a\">\
+            <span class=\"y z\" title=\"t\"></span></div>");
+
+        let mut minifier = Minifier::new(Vec::new()).canonical();
+        node.process_all::<MinifyingHtmlWriter<_>>(&mut minifier).unwrap();
+        assert_eq!(String::from_utf8(minifier.into_inner()).unwrap(),
+            "<div class=\"a b\" data-x=1 id=a><span class=\"y z\" title=t></span></div>");
+    }
+
+    #[test]
+    fn propagates_csp_nonces() {
+        use super::csp::{self, CspNonce};
+        use super::css::{self, styled};
+        use super::document::Document;
+
+        let nonce = CspNonce::new("abc");
+        assert_eq!(nonce.policy(), "script-src 'nonce-abc' 'strict-dynamic'; \
+            style-src 'nonce-abc'; style-src-attr 'unsafe-inline'; object-src 'none'; base-uri 'none'");
+
+        let html = csp::provide(nonce.clone(), || {
+            let body = styled("color: red;", div((
+                csp::style(vec![], "p { margin: 0; }"),
+                PhantomData::<()>,
+            )));
+            assert!(css::style_tag().starts_with("<style data-domafic-css nonce=\"abc\">"));
+            let mut html = Vec::new();
+            Document::new("Page")
+                .script("/app.js")
+                .csp_nonce(nonce)
+                .write(&mut html, &body)
+                .unwrap();
+            String::from_utf8(html).unwrap()
+        });
+        css::clear();
+
+        assert!(html.contains("<meta http-equiv=\"Content-Security-Policy\" \
+            content=\"script-src 'nonce-abc' 'strict-dynamic';"));
+        assert!(html.contains("<script src=\"/app.js\" nonce=\"abc\"></script>\
+            <style data-domafic-css nonce=\"abc\">"));
+        assert!(html.contains("<style nonce=\"abc\">"));
+        assert_eq!(csp::script::<(), _>(vec![], ()).to_string(), "<script></script>");
+    }
+
+    #[test]
+    fn computes_subresource_integrity() {
+        use super::document::Document;
+        use super::sri::Integrity;
+
+        let expected = [
+            (&b""[..], "OLBgp1GsljhM2TJ+sbHjaiH9txEUvgdDTAzHv2P24donTt6/529l+9Ua0vFImLlb"),
+            (&b"ab"[..], "x74DulvKo4RycHbbABjpkkjhpui9G571ip7J3U7uuz9IuDYgEiEXW++nTdw9Na/d"),
+            (&[b'a'; 200][..], "BpG26XhhS2fWBVeyos3dU0BlCFIu+iHGJNu/qKtucm1cWGtInHwJ8kEJpkwQIR1I"),
+        ];
+        for &(contents, hash) in &expected {
+            assert_eq!(Integrity::sha384(contents).as_str(), format!("sha384-{}", hash));
+        }
+
+        let mut html = Vec::new();
+        Document::new("Page")
+            .stylesheet_with_integrity("/style.css", Integrity::new("sha256-precomputed"))
+            .script("/app.js")
+            .write(&mut html, &p(PhantomData::<()>))
+            .unwrap();
+        assert!(String::from_utf8(html).unwrap().contains("\
+            <link rel=\"stylesheet\" href=\"/style.css\" integrity=\"sha256-precomputed\" \
+            crossorigin=\"anonymous\"><script src=\"/app.js\"></script>"));
+    }
+
+    #[test]
+    fn snapshots_for_devtools() {
+        use super::on;
+        use super::devtools::{to_json, MessageRing, NodeSnapshot, SnapshotValue};
+
+        let tree = div((
+            attributes([("title", Str("\"quoted\"\n"))]),
+            (
+                div(((on("click", |_| ()), on("keydown", |_| ())), "a\\b")).with_key(3),
+                PhantomData::<()>,
+            ),
+        )).with_key(1);
+        let snapshot = NodeSnapshot::of(&tree);
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].keys, vec![1]);
+        let inner = &snapshot[0].children[0];
+        assert_eq!((inner.keys.clone(), inner.listeners), (vec![1, 3], 2));
+        assert_eq!(inner.children[0].value, SnapshotValue::Text("a\\b".to_string()));
+
+        let json = to_json(&snapshot, &MessageRing::new(0));
+        assert!(json.contains("\"attributes\":{\"title\":\"\\\"quoted\\\"\\n\"}"));
+        assert!(json.contains("{\"text\":\"a\\\\b\",\"keys\":[1,3]"));
+        assert!(json.ends_with("\"messages\":[]}"));
+    }
+
+    #[test]
+    fn forwards_should_update() {
+        use super::css::styled;
+
+        let node = |count: u32| styled("color: red;", div((count.to_string(), PhantomData::<()>))
+            .with_should_update(&count, |old, new| old != new)
+            .with_attributes([("title", Str("count"))])
+            .with_key(7));
+
+        let first = node(1);
+        let old_props_hash = first.props_hash().unwrap();
+        assert!(!node(1).should_update(old_props_hash));
+        assert!(node(2).should_update(old_props_hash));
+        assert_eq!(first.key(), Some(7));
+
+        let plain = div(PhantomData::<()>);
+        assert_eq!(plain.props_hash(), None);
+        assert!(plain.should_update(old_props_hash));
+    }
+
+    #[test]
+    fn renders_uncontrolled_regions() {
+        use super::uncontrolled;
+
+        let editor = uncontrolled(div((
+            attributes([("id", Str("editor"))]),
+            ("Loading", PhantomData::<()>),
+        )).with_key(2));
+        assert_eq!(editor.key(), Some(2));
+        assert_eq!(editor.get_attribute(0), Some(&("id", Str("editor"))));
+        assert_eq!(editor.displayable().to_string(), "<div id=\"editor\">Loading</div>");
+        assert_eq!(editor.props_hash(), None);
+        assert!(!editor.should_update(0));
+    }
+
+    #[test]
+    fn adapts_js_widgets() {
+        use super::ElementRef;
+        use super::widget::{widget, JsWidget};
+        use std::cell::RefCell;
+
+        thread_local!(static CALLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) });
+        fn call(description: String) {
+            CALLS.with(|calls| calls.borrow_mut().push(description));
+        }
+
+        struct Editor;
+        impl JsWidget for Editor {
+            type Props = &'static str;
+            fn init(element: ElementRef, props: &&'static str) -> Editor {
+                call(format!("init {} {}", element.name(), props));
+                Editor
+            }
+            fn update(&mut self, props: &&'static str) { call(format!("update {}", props)); }
+            fn destroy(&mut self) { call("destroy".to_string()); }
+        }
+        struct Chart;
+        impl JsWidget for Chart {
+            type Props = ();
+            fn init(_element: ElementRef, _props: &()) -> Chart { Chart }
+            fn update(&mut self, _props: &()) {}
+            fn destroy(&mut self) {}
+        }
+
+        const EDITOR: ElementRef = ElementRef("editor");
+        let first = widget::<(), Editor>(EDITOR, "fn main() {}");
+        let second = widget::<(), Editor>(EDITOR, "fn main() { run() }");
+        assert_eq!(first.displayable().to_string(), "<div data-domafic-ref=\"editor\"></div>");
+        assert!(!first.should_update(first.props_hash().unwrap()));
+        assert!(second.should_update(first.props_hash().unwrap()));
+
+        let mut mounted = first.widget().unwrap().mount()();
+        assert!(second.widget().unwrap().update(&mut *mounted));
+        assert!(!widget::<(), Chart>(EDITOR, ()).widget().unwrap().update(&mut *mounted));
+        mounted.destroy();
+        assert!(div(PhantomData::<()>).widget().is_none());
+
+        CALLS.with(|calls| assert_eq!(*calls.borrow(), vec![
+            "init editor fn main() {}",
+            "update fn main() { run() }",
+            "destroy",
+        ]));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn holds_invariants_for_arbitrary_trees() {
+        use self::quickcheck::{Arbitrary, QuickCheck};
+        use super::testing::{html_round_trips, normalize, parse_html, snapshot_matches, TestNode};
+
+        fn round_trips(node: TestNode) -> bool { html_round_trips(&node) }
+        fn snapshots(node: TestNode) -> bool { snapshot_matches(&node) }
+        QuickCheck::new().quickcheck(round_trips as fn(TestNode) -> bool);
+        QuickCheck::new().quickcheck(snapshots as fn(TestNode) -> bool);
+
+        let mut list = TestNode::tag("ul");
+        list.attributes.push(("class", Str("a \"b\" & <c>")));
+        list.children = vec![TestNode::text("x < y"), TestNode::text(""), TestNode::text(" & z")];
+        assert_eq!(parse_html(&DomNode::<()>::displayable(&list).to_string()), Some(normalize(&[list.clone()])));
+        assert_eq!(normalize(&[list.clone()])[0].children, vec![TestNode::text("x < y & z")]);
+        assert!(list.shrink().any(|smaller| smaller.children.len() == 2));
+    }
+
+    #[test]
+    fn copies_precompiled_html() {
+        use super::html_writer::MinifyingHtmlWriter;
+        use super::html_writer::Minifier;
+        use super::static_node::StaticNode;
+        use super::xml_writer::{Xhtml, XmlWriter};
+
+        let nav = StaticNode::new(nav((
+            attributes([("class", Str("site  nav"))]),
+            (a((attributes([("href", Str("/"))]), "Home & away")), br(PhantomData::<()>)),
+        )));
+        assert_eq!(nav.html(), "<nav class=\"site  nav\"><a href=\"/\">Home &amp; away</a><br></nav>");
+        assert_eq!(nav.clone().with_key(1).displayable().to_string(), nav.html());
+        assert_eq!(nav.precompiled().map(|p| p.hash()), nav.props_hash());
+
+        // Extra attributes and other dialects write the tree as usual
+        assert_eq!(nav.clone().with_attributes([("id", Str("top"))]).precompiled(), None);
+        let mut xhtml = Vec::new();
+        nav.process_all::<XmlWriter<_, Xhtml>>(&mut xhtml).unwrap();
+        assert!(String::from_utf8(xhtml).unwrap().contains("<br/>"));
+        let mut minified = Minifier::new(Vec::new());
+        nav.process_all::<MinifyingHtmlWriter<_>>(&mut minified).unwrap();
+        assert!(String::from_utf8(minified.into_inner()).unwrap().contains("<a href=/>"));
+    }
+
+    #[test]
+    fn reuses_cached_renders() {
+        use super::render_cache::RenderCache;
+        use super::static_node::StaticNode;
+
+        let footer_node = StaticNode::new(footer(("Contact", PhantomData::<()>)));
+        let page = |user: &str, items: &[&'static str]| div((
+            header(format!("Hi {}", user)),
+            (
+                section(items.iter().map(|item|
+                    p(*item).with_should_update(item, |old, new| old != new)
+                ).collect::<Vec<_>>()).with_should_update(&items, |old, new| old != new),
+                (img(attributes([("src", Str("/a&b.png"))])), footer_node.clone()),
+            ),
+        ));
+
+        let mut cache = RenderCache::new();
+        let first = cache.render("page", &page("Ada", &["a", "b"])).to_vec();
+        assert_eq!(first, page("Ada", &["a", "b"]).to_string().into_bytes());
+        assert_eq!(cache.reused("page"), 0);
+
+        // The section is reused along with the paragraphs inside it, as is the footer
+        let second = cache.render("page", &page("Grace", &["a", "b"])).to_vec();
+        assert_eq!(second, page("Grace", &["a", "b"]).to_string().into_bytes());
+        assert_eq!(cache.reused("page"), 2);
+
+        // Only the unchanged paragraph is reused, at its offset in the previous render
+        let third = cache.render("page", &page("Ada", &["a", "c"])).to_vec();
+        assert_eq!(third, page("Ada", &["a", "c"]).to_string().into_bytes());
+        assert_eq!(cache.reused("page"), 2);
+
+        assert_eq!(cache.reused("other"), 0);
+        cache.clear();
+        cache.render("page", &page("Ada", &["a", "c"]));
+        assert_eq!(cache.reused("page"), 0);
+    }
+
+    #[test]
+    fn renders_owned_text() {
+        use devtools::{NodeSnapshot, SnapshotValue};
+        use html_writer::write_minified;
+
+        let node = div((Count(1), pre(Count(2)), PhantomData::<()>));
+        assert_eq!(node.to_string(), "<div>1 &lt; 2<pre>2 &lt; 3</pre></div>");
+
+        let mut minified = Vec::new();
+        write_minified::<(), _, _>(&node, &mut minified).unwrap();
+        assert_eq!(minified, node.to_string().into_bytes());
+
+        let snapshot = NodeSnapshot::of::<(), _>(&Count(1));
+        assert_eq!(snapshot[0].value, SnapshotValue::Text("1 < 2".to_string()));
+
+        assert_eq!(DomNode::<()>::value(&Count(1)).text(), Some("1 < 2"));
+        assert_eq!(DomNode::<()>::value(&"text").into_text(), Some("text".to_string()));
+        assert_eq!(DomNode::<()>::value(&div(())).text(), None);
+    }
+
+    #[test]
+    fn renders_borrowed_slices() {
+        use std::borrow::Cow;
+
+        let names = vec!["Ada".to_string(), "Grace".to_string()];
+
+        let items: Vec<_> = names.iter().map(|name| li((name.as_str(), PhantomData::<()>))).collect();
+        assert_eq!(ul(&items[..]).to_string(), "<ul><li>Ada</li><li>Grace</li></ul>");
+
+        assert_eq!(p((&names[..], PhantomData::<()>)).to_string(), "<p>AdaGrace</p>");
+        assert_eq!(p((names.clone().into_boxed_slice(), PhantomData::<()>)).to_string(), "<p>AdaGrace</p>");
+        assert_eq!(p((Cow::Borrowed(&names[..]), PhantomData::<()>)).to_string(), "<p>AdaGrace</p>");
+    }
+
+    #[test]
+    fn renders_large_tuples() {
+        let row = tr((
+            td("0"), td("1"), td("2"), td("3"), td("4"), td("5"), td("6"), td("7"),
+            td("8"), td("9"), td("10"), td("11"), td("12"), td("13"), td("14"), td("15"),
+            td("16"), td("17"), td("18"), td("19"), td("20"), td("21"), td("22"), td("23"),
+            td("24"), td("25"), td("26"), td("27"), td("28"), td("29"), td("30"), PhantomData::<()>,
+        ));
+        let cells: String = (0..31).map(|i| format!("<td>{}</td>", i)).collect();
+        assert_eq!(row.to_string(), format!("<tr>{}</tr>", cells));
+    }
+
+    #[test]
+    fn merges_attribute_groups() {
+        use listener::on;
+
+        let shared = || attributes([("class", Str("field"))]);
+        let with_children = label((shared(), attributes([("for", Str("name"))]), ("Name", PhantomData::<()>)));
+        assert_eq!(with_children.to_string(), "<label class=\"field\" for=\"name\">Name</label>");
+
+        let with_listeners = input((shared(), attributes([("id", Str("name"))]), on("input", |_| ())));
+        assert_eq!(with_listeners.get_attribute(1), Some(&("id", Str("name"))));
+        assert_eq!(with_listeners.get_attribute(2), None);
+
+        let both = button((
+            shared(), attributes(vec![("type", Str("submit"))]), on("click", |_| ()), "Go",
+        ));
+        assert_eq!(both.to_string(), "<button class=\"field\" type=\"submit\">Go</button>");
+    }
+
+    #[test]
+    fn merges_class_and_style_layers() {
+        let node = div(("Hi", PhantomData::<()>))
+            .with_attributes([("class", Str("card")), ("style", Str(" color: red; "))])
+            .with_attributes([("class", Str("selected")), ("style", Str("")), ("id", Str("main"))]);
+        assert_eq!(
+            node.displayable().to_string(),
+            "<div class=\"selected card\" style=\"color: red\" id=\"main\">Hi</div>"
+        );
+
+        let single = div(PhantomData::<()>).with_attributes([("class", Str("card"))]);
+        assert_eq!(single.displayable().to_string(), "<div class=\"card\"></div>");
+    }
+
+    #[cfg(feature = "strict_attrs")]
+    #[test]
+    fn sets_strict_attributes() {
+        use AttributeValue;
+        use listener::on;
+        use tags::strict;
+
+        let field = strict::label::<()>().for_("email").children((
+            "Email",
+            strict::input().type_("email").id("email").required(true).disabled(false)
+                .data("data-field", "email").aria("aria-describedby", "hint"),
+        ));
+        assert_eq!(
+            field.to_string(),
+            "<label for=\"email\">Email<input type=\"email\" id=\"email\" required=\"\" \
+            data-field=\"email\" aria-describedby=\"hint\"></label>"
+        );
+
+        let link = strict::a::<()>().href(format!("/users/{}", 7)).listeners(on("click", |_| ()));
+        assert_eq!(link.get_attribute(0), Some(&("href", AttributeValue::OwnedStr("/users/7".to_string()))));
+        assert_eq!(link.to_string(), "<a href=\"/users/7\"></a>");
+    }
+
+    #[test]
+    fn checks_nesting() {
+        use nesting::{check, Problem};
+
+        let valid = div((
+            p(("Text ", em("emphasis"), button(div(())))),
+            ul(li("item")),
+            table((thead(tr(th("a"))), tbody(tr(td(div("cell")))))),
+            select(option("x")),
+            dl(div((dt("term"), dd("definition")))),
+            PhantomData::<()>,
+        ));
+        assert_eq!(check(&valid), vec![]);
+
+        let invalid = div((
+            li("orphan"),
+            a(a("link")),
+            table(("text", tbody(tr(span(()))))),
+            h1(h2("heading")),
+            img(("child", PhantomData::<()>)),
+        ));
+        let problems: Vec<_> = check(&invalid).into_iter().map(|v| (v.tag, v.problem)).collect();
+        assert_eq!(problems, vec![
+            ("li", Problem::Misplaced(&["ul", "ol", "menu"])),
+            ("a", Problem::Nested),
+            ("#text", Problem::Disallowed),
+            ("span", Problem::Disallowed),
+            ("h2", Problem::Nested),
+            ("img", Problem::VoidWithChildren),
+        ]);
+    }
+
+    #[test]
+    fn document_reports_invalid_nesting() {
+        use document::Document;
+
+        let body = p(div(("Block", PhantomData::<()>)));
+        let document = Document::new("Page");
+        let validation = document.validate(&body);
+        assert_eq!(validation.nesting.len(), 1);
+        assert_eq!(validation.to_string(), "invalid nesting: `div` at 0.0 inside `p` closes the enclosing `p`");
+        assert!(document.validate(&div(p(("Inline", PhantomData::<()>)))).is_empty());
+
+        // Written as it is, rather than panicking
+        let mut html = Vec::new();
+        document.write(&mut html, &body).unwrap();
+        assert!(String::from_utf8(html).unwrap().contains("<body><p><div>Block</div></p></body>"));
+    }
+
+    #[test]
+    fn renders_comments() {
+        use devtools::{NodeSnapshot, SnapshotValue};
+        use html_writer::write_minified;
+        use nesting;
+        use {comment, doctype};
+
+        let node = div((comment("a-->b"), "Text", comment("-"), PhantomData::<()>));
+        let expected = "<div><!--a- ->b-->Text<!--- --></div>";
+        assert_eq!(node.to_string(), expected);
+        assert_eq!(comment::<(), _>(">b").displayable().to_string(), "<!-- >b-->");
+        assert_eq!(comment::<(), _>("->b").displayable().to_string(), "<!-- ->b-->");
+        assert_eq!(comment::<(), _>("a-").displayable().to_string(), "<!--a- -->");
+        assert_eq!(comment::<(), _>("<!-").displayable().to_string(), "<!--<!- -->");
+
+        let mut minified = Vec::new();
+        write_minified::<(), _, _>(&node, &mut minified).unwrap();
+        assert_eq!(String::from_utf8(minified).unwrap(), "<div>Text</div>");
+
+        let snapshot = NodeSnapshot::of::<(), _>(&node);
+        assert_eq!(snapshot[0].children[0].value, SnapshotValue::Comment("a-->b".to_string()));
+        assert!(nesting::check(&table(comment::<(), _>("Empty"))).is_empty());
+
+        let doctype = doctype::<()>("html");
+        assert_eq!(doctype.displayable().to_string(), "<!DOCTYPE html>");
+        assert_eq!(NodeSnapshot::of::<(), _>(&doctype)[0].value, SnapshotValue::Doctype("html".to_string()));
+    }
+
+    #[test]
+    fn combines_stores() {
+        use stores::{AnyMessage, Store};
+        use KeyIter;
+
+        struct Log(Vec<&'static str>);
+        impl<Io: ?Sized> Store<Io> for Log {
+            type Message = &'static str;
+            fn update(&mut self, msg: &'static str, _keys: KeyIter, _io: &Io) { self.0.push(msg); }
+        }
+
+        struct Total(u32);
+        impl Store<u32> for Total {
+            type Message = u32;
+            fn update(&mut self, msg: u32, _keys: KeyIter, io: &u32) { self.0 += msg * io; }
+        }
+
+        combine_stores! {
+            struct App {
+                log: Log,
+                total: Total,
+            }
+        }
+
+        let mut app = App { log: Log(Vec::new()), total: Total(0) };
+        app.update(AnyMessage::new("started"), KeyIter::empty(), &10);
+        app.update(AnyMessage::new(2u32), KeyIter::empty(), &10);
+        assert_eq!(app.log.0, vec!["started"]);
+        assert_eq!(app.total.0, 20);
+
+        let unhandled = app.route(AnyMessage::new(1i64), KeyIter::empty(), &10).unwrap_err();
+        assert!(unhandled.is::<i64>());
+        assert_eq!(unhandled.downcast::<i64>().unwrap(), 1);
+    }
+
+    #[test]
+    fn records_effects() {
+        use effects::{Effect, Executor, HttpError, MockExecutor, Request, StorageArea};
+
+        let executor = MockExecutor::new();
+        executor.execute(Effect::delay(500, "late"));
+        executor.execute(Effect::http(Request::post("/save", "{}"), |result| {
+            if result.is_ok() { "saved" } else { "failed" }
+        }));
+        executor.execute(Effect::set_storage(StorageArea::Local, "draft", "{}"));
+        executor.execute(Effect::delay(100, "early"));
+        assert_eq!(executor.len(), 4);
+
+        assert_eq!(executor.fire_delays(), vec!["early", "late"]);
+        assert_eq!(executor.respond(Err(HttpError::Timeout)), "failed");
+
+        let remaining = executor.take();
+        assert_eq!(format!("{:?}", remaining),
+            r#"[SetStorage { area: Local, key: "draft", value: "{}" }]"#);
+        assert!(executor.is_empty());
+    }
+
+    #[cfg(feature = "replay")]
+    #[test]
+    fn replays_recorded_messages() {
+        use super::replay::{replay, Recorder, ReplayError};
+        use keys::Keys;
+        use KeyIter;
+
+        let recorder = Recorder::new();
+        recorder.record(&("add", 3), Keys::new().push(2).push(7).into_iter()).unwrap();
+        recorder.record(&("remove", 1), KeyIter::empty()).unwrap();
+
+        let update = |items: &mut Vec<(usize, i32)>, (op, count): (String, i32), keys: KeyIter| {
+            let key = keys.last().unwrap_or(0);
+            items.push((key, if op == "add" { count } else { -count }));
+        };
+
+        let restored = Recorder::from_json(&recorder.to_json()).unwrap();
+        assert_eq!(restored.messages(), recorder.messages());
+        assert_eq!(restored.messages()[0].keys, vec![2, 7]);
+        assert_eq!(replay(&restored.messages(), update, Vec::new()).unwrap(), vec![(7, 3), (0, -1)]);
+
+        match replay(&restored.messages(), |_: &mut (), _: u32, _| {}, ()) {
+            Err(ReplayError::Message(0, _)) => {},
+            other => panic!("Expected an undecodable message, found {:?}", other.map(|_| ())),
+        }
+        assert!(Recorder::from_json("{}").is_err());
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn patches_live_views() {
+        use super::live::{encode, LiveError, LiveNode, LiveSession, Patch};
+        use {on, KeyIter};
+
+        let render = |items: &Vec<String>| ul(items.iter().enumerate().map(|(i, item)| {
+            li((
+                attributes([("class", Str(if i == 0 { "first" } else { "rest" }))]),
+                on("click", |event| event.target_value.unwrap_or("").to_string()),
+                item.clone(),
+            )).with_key(i)
+        }).collect::<Vec<_>>());
+        let update = |items: &mut Vec<String>, msg: String, mut keys: KeyIter| {
+            let key = keys.next().unwrap();
+            if msg.is_empty() { items.remove(key); } else { items[key] = msg; }
+        };
+        let mut session = LiveSession::new(vec!["a".to_string(), "b".to_string()], update, render);
+
+        let mounted = session.mount();
+        assert_eq!(mounted[0], Patch::Truncate { path: vec![], len: 0 });
+        match mounted[1] {
+            Patch::Append { node: LiveNode::Element { tag: "ul", ref children, .. }, .. } => {
+                assert_eq!(children[1], LiveNode::Element {
+                    tag: "li",
+                    attributes: vec![("class", "rest".to_string())],
+                    events: vec!["click"],
+                    children: vec![LiveNode::Text("b".to_string())],
+                });
+            },
+            ref other => panic!("Expected the list to be appended, found {:?}", other),
+        }
+
+        let patches = session.handle_event(r#"{"path":[0,1],"event":"click","value":"c"}"#).unwrap();
+        assert_eq!(patches, vec![Patch::SetText { path: vec![0, 1, 0], text: "c".to_string() }]);
+        assert_eq!(*session.state(), vec!["a".to_string(), "c".to_string()]);
+
+        let patches = session.handle_event(r#"{"path":[0,0],"event":"click"}"#).unwrap();
+        assert_eq!(patches, vec![
+            Patch::SetText { path: vec![0, 0, 0], text: "c".to_string() },
+            Patch::Truncate { path: vec![0], len: 1 },
+        ]);
+        assert_eq!(encode(&patches[1..]), r#"[{"len":1,"op":"truncate","path":[0]}]"#);
+
+        // Events for nodes which no longer exist, or which have no listener, are ignored
+        assert_eq!(session.handle_event(r#"{"path":[0,4],"event":"click"}"#).unwrap(), vec![]);
+        assert_eq!(session.handle_event(r#"{"path":[0,0],"event":"input"}"#).unwrap(), vec![]);
+        assert_eq!(*session.state(), vec!["c".to_string()]);
+
+        match session.handle_event(r#"{"event":"click"}"#) {
+            Err(LiveError::MissingField("path")) => {},
+            other => panic!("Expected a missing path, found {:?}", other),
+        }
+        assert!(session.handle_event("click").is_err());
+    }
+
+    #[test]
+    fn schedules_test_callbacks() {
+        use super::scheduler::{Scheduler, TestScheduler};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let scheduler = Rc::new(TestScheduler::new());
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        // A timeout which sets another, and requests a frame
+        let (inner_scheduler, inner_log) = (scheduler.clone(), log.clone());
+        scheduler.set_timeout(50, Box::new(move || {
+            inner_log.borrow_mut().push(format!("first at {}", inner_scheduler.now()));
+            let timeout_log = inner_log.clone();
+            inner_scheduler.set_timeout(10, Box::new(move || timeout_log.borrow_mut().push("second".to_string())));
+            let frame_log = inner_log.clone();
+            inner_scheduler.request_frame(Box::new(move || frame_log.borrow_mut().push("frame".to_string())));
+        }));
+        let timeout_log = log.clone();
+        scheduler.set_timeout(50, Box::new(move || timeout_log.borrow_mut().push("tied".to_string())));
+
+        scheduler.advance(70);
+        assert_eq!(*log.borrow(), vec!["first at 50", "tied", "second"]);
+        assert_eq!(scheduler.now(), 70);
+        assert_eq!(scheduler.pending_frames(), 1);
+
+        // Renders are coalesced, and run in the position of the first request
+        for &name in &["stale render", "render"] {
+            let render_log = log.clone();
+            scheduler.schedule_render(Box::new(move || render_log.borrow_mut().push(name.to_string())));
+        }
+        assert!(scheduler.is_render_pending());
+        let frame_log = log.clone();
+        scheduler.request_frame(Box::new(move || frame_log.borrow_mut().push("late frame".to_string())));
+        let timeout_log = log.clone();
+        scheduler.set_timeout(1000, Box::new(move || timeout_log.borrow_mut().push("idle".to_string())));
+
+        scheduler.run_main_loop();
+        assert_eq!(log.borrow()[3..].to_vec(), vec!["frame", "render", "late frame", "idle"]);
+        assert_eq!(scheduler.now(), 1070);
+        assert_eq!((scheduler.pending_frames(), scheduler.pending_timeouts()), (0, 0));
+        assert!(!scheduler.is_render_pending());
+    }
+
+    #[test]
+    fn averages_render_timings() {
+        use super::perf::{PerfMonitor, FRAME_BUDGET_MILLIS};
+
+        let mut monitor = PerfMonitor::with_smoothing(0.5);
+        assert_eq!(monitor.record(4.0, 2.0), 0);
+        assert_eq!(monitor.record(8.0, 4.0), 0);
+        assert_eq!(monitor.record(50.0, 0.0), 3);
+
+        let stats = monitor.stats();
+        assert_eq!((stats.render_millis, stats.patch_millis), (28.0, 1.5));
+        assert_eq!((stats.updates, stats.dropped_frames, stats.slowest_millis), (3, 3, 50.0));
+        assert_eq!(stats.sustainable_fps(), 1000.0 / 29.5);
+        assert_eq!(stats.to_string(),
+            "render 28.0ms, patch 1.5ms, 34 fps, slowest 50.0ms, 3 dropped frames in 3 updates");
+
+        monitor.reset();
+        monitor.record(1.0, 1.0);
+        assert_eq!(monitor.stats().sustainable_fps(), 1000.0 / FRAME_BUDGET_MILLIS);
+    }
+
+    #[test]
+    fn finds_duplicate_ids() {
+        use ids::{check, DuplicateId};
+
+        let page = div((
+            label(("Name", PhantomData::<()>)).with_id("name"),
+            "text",
+            div((
+                input(()).with_id("ignored").with_id("name"),
+                span(()).with_id(""),
+                span(()).with_attributes([("id", Str("other"))]),
+            )),
+            a(()).with_id(String::from("name")),
+            span(()).with_id("ignored"),
+        ));
+        assert_eq!(check(&page), vec![
+            DuplicateId { id: "name".to_string(), first: vec![0, 0], second: vec![0, 2, 0] },
+            DuplicateId { id: "name".to_string(), first: vec![0, 0], second: vec![0, 3] },
+        ]);
+        assert_eq!(check(&div(span(PhantomData::<()>).with_id("other"))), vec![]);
+    }
+
+    #[test]
+    fn document_reports_duplicate_ids() {
+        use document::Document;
+
+        let body = div((p(()).with_id("main"), p(("Again", PhantomData::<()>)).with_id("main")));
+        let document = Document::new("Page");
+        let validation = document.validate(&body);
+        assert!(validation.nesting.is_empty());
+        assert_eq!(validation.duplicate_ids.len(), 1);
+        assert_eq!(validation.to_string(), "duplicate id: id `main` is used at 0.0 and again at 0.1");
+
+        // Written as it is, rather than panicking
+        let mut html = Vec::new();
+        document.write(&mut html, &body).unwrap();
+        assert!(String::from_utf8(html).unwrap().contains("<p id=\"main\">Again</p>"));
+    }
+
+    #[test]
+    fn links_to_routes() {
+        use router::{link, on_route_change, Route, RouteOutcome, LINK_ATTRIBUTE};
+        use subscriptions::SubscriptionSource;
+        use {Event, Listener};
+
+        #[derive(Debug, Clone, PartialEq)]
+        enum Page { Home, Search(String) }
+
+        impl Route for Page {
+            fn path(&self) -> String {
+                match *self {
+                    Page::Home => "/".to_string(),
+                    Page::Search(ref query) => format!("/search?q={}", query),
+                }
+            }
+
+            fn from_path(path: &str) -> Option<Page> {
+                match path {
+                    "/" => Some(Page::Home),
+                    _ => path.strip_prefix("/search?q=").map(|query| Page::Search(query.to_string())),
+                }
+            }
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum Msg { Navigate(Page), Unknown }
+
+        impl From<Page> for Msg {
+            fn from(page: Page) -> Msg { Msg::Navigate(page) }
+        }
+
+        let search = link(Page::Search("a&b".to_string()), (strong("Search"), PhantomData::<Msg>));
+        assert_eq!(
+            search.to_string(),
+            "<a href=\"/search?q=a&amp;b\" data-domafic-link=\"\"><strong>Search</strong></a>"
+        );
+        assert_eq!(search.get_attribute(1), Some(&(LINK_ATTRIBUTE, Str(""))));
+
+        let listener = search.listeners();
+        assert_eq!(listener.event_type_handled(), "click");
+        let click = Event {
+            type_str: Some("click"), target_value: None, client_x: 0, client_y: 0, offset_x: 0,
+            offset_y: 0, which_keycode: 1, shift_key: false, alt_key: false, ctrl_key: false,
+            meta_key: false, detail: None,
+        };
+        assert_eq!(listener.handle_event(click), Msg::Navigate(Page::Search("a&b".to_string())));
+
+        let subscription = on_route_change(|outcome: RouteOutcome<Page>| match outcome {
+            RouteOutcome::NotFound(_) => Msg::Unknown,
+            RouteOutcome::Matched(page) | RouteOutcome::Redirect(page) => Msg::Navigate(page),
+        });
+        assert_eq!(subscription.source(), SubscriptionSource::PopState);
+        assert_eq!(subscription.source().event_type(), "popstate");
+    }
+
+    #[test]
+    fn parses_route_parameters() {
+        use router::{Route, RouteParam, RoutePattern};
+
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        enum Tab { Posts, Likes }
+
+        impl RouteParam for Tab {
+            fn from_param(value: &str) -> Option<Tab> {
+                match value {
+                    "posts" => Some(Tab::Posts),
+                    "likes" => Some(Tab::Likes),
+                    _ => None,
+                }
+            }
+            fn to_param(&self) -> String {
+                match *self { Tab::Posts => "posts", Tab::Likes => "likes" }.to_string()
+            }
+        }
+
+        routes! {
+            #[derive(Debug, Clone, PartialEq)]
+            enum Page {
+                Home => "/",
+                User { id: u64, tab: Tab } => "/users/{id}?tab={tab}",
+                NewUser => "/users/new",
+                Search { query: String, page: Option<u32> } => "/search/{query}?p={page}",
+            }
+        }
+
+        let user = Page::User { id: 7, tab: Tab::Likes };
+        assert_eq!(user.path(), "/users/7?tab=likes");
+        assert_eq!(Page::from_path("/users/7/?ref=feed&tab=likes#bio"), Some(user));
+        assert_eq!(Page::from_path("/users/7"), None);
+        assert_eq!(Page::from_path("/users/new"), Some(Page::NewUser));
+        assert_eq!(Page::Home.path(), "/");
+        assert_eq!(Page::from_path(""), Some(Page::Home));
+
+        let search = Page::Search { query: "rust & wasm/é".to_string(), page: None };
+        assert_eq!(search.path(), "/search/rust%20%26%20wasm%2F%C3%A9");
+        assert_eq!(Page::from_path(&search.path()), Some(search));
+        assert_eq!(
+            Page::from_path("/search/a+b?p=2&p=3"),
+            Some(Page::Search { query: "a+b".to_string(), page: Some(2) })
+        );
+        assert_eq!(Page::from_path("/search/a?p=two"), None);
+        assert_eq!(Page::from_path("/search/%ZZ"), None);
+
+        let pattern = RoutePattern::new("/files/{name}?q={q}");
+        let params = pattern.matches("/files/a%2Fb?q=x+y").unwrap();
+        assert_eq!(params.get("name"), Some("a/b"));
+        assert_eq!(params.get("q"), Some("x y"));
+        assert_eq!(params.parse::<u8>("name"), None);
+    }
+
+    #[test]
+    fn resolves_redirects_and_missing_routes() {
+        use router::{link, resolve, Route, RouteOutcome};
+
+        routes! {
+            #[derive(Debug, Clone, PartialEq)]
+            enum Page {
+                Docs { section: String } => "/docs/{section}",
+                Guide { section: String } => "/guide/{section}" => Page::Docs { section: section.clone() },
+                Manual => "/manual" => Page::Guide { section: "intro".to_string() },
+            }
+        }
+
+        impl From<Page> for String {
+            fn from(page: Page) -> String { page.path() }
+        }
+
+        let docs = Page::Docs { section: "intro".to_string() };
+        assert_eq!(resolve::<Page>("/docs/intro"), RouteOutcome::Matched(docs.clone()));
+        assert_eq!(resolve::<Page>("/manual"), RouteOutcome::Redirect(docs.clone()));
+        assert_eq!(Page::Manual.redirect(), Some(Page::Guide { section: "intro".to_string() }));
+
+        let redirect = resolve::<Page>("/guide/intro");
+        assert_eq!(redirect.route(), Some(&docs));
+        assert_eq!(redirect.status_code(), 301);
+        assert_eq!(redirect.location(), Some("/docs/intro".to_string()));
+
+        let missing = resolve::<Page>("/docs");
+        assert_eq!(missing, RouteOutcome::NotFound("/docs".to_string()));
+        assert_eq!((missing.route(), missing.status_code(), missing.location()), (None, 404, None));
+
+        let manual = link::<String, _, _>(Page::Manual, "Manual");
+        assert_eq!(manual.to_string(), "<a href=\"/docs/intro\" data-domafic-link=\"\">Manual</a>");
+    }
+
+    #[test]
+    fn nests_child_routes() {
+        use router::{outlet, resolve, Route, RouteOutcome, RoutePattern};
+
+        routes! {
+            #[derive(Debug, Clone, PartialEq)]
+            enum Settings {
+                Profile => "/",
+                Keys { filter: Option<String> } => "/keys?filter={filter}",
+            }
+        }
+
+        routes! {
+            #[derive(Debug, Clone, PartialEq)]
+            enum Page {
+                Home => "/",
+                Account { user: String, settings: Settings, tab: Option<u8> } =>
+                    "/users/{user}/settings/{settings..}?tab={tab}",
+            }
+        }
+
+        let keys = Page::Account {
+            user: "ada l".to_string(),
+            settings: Settings::Keys { filter: Some("ssh".to_string()) },
+            tab: Some(2),
+        };
+        assert_eq!(keys.path(), "/users/ada%20l/settings/keys?filter=ssh&tab=2");
+        assert_eq!(Page::from_path("/users/ada%20l/settings/keys?tab=2&filter=ssh"), Some(keys));
+
+        let profile = Page::Account { user: "ada".to_string(), settings: Settings::Profile, tab: None };
+        assert_eq!(profile.path(), "/users/ada/settings");
+        assert_eq!(Page::from_path("/users/ada/settings/"), Some(profile.clone()));
+        assert_eq!(resolve::<Page>("/users/ada/settings/passwords"),
+            RouteOutcome::NotFound("/users/ada/settings/passwords".to_string()));
+
+        let params = RoutePattern::new("/files/{path..}").matches("/files/a/b%20c?raw").unwrap();
+        assert_eq!(params.get("path"), Some("/a/b%20c?raw"));
+
+        if let Page::Account { ref user, ref settings, .. } = profile {
+            let chrome = div((h1(user.clone()), outlet(settings, |settings| match *settings {
+                Settings::Profile => p(("Profile", PhantomData::<()>)),
+                Settings::Keys { .. } => p(("Keys", PhantomData::<()>)),
+            })));
+            assert_eq!(chrome.to_string(), "<div><h1>ada</h1><p>Profile</p></div>");
+        }
+    }
+
+    #[test]
+    fn marks_active_links() {
+        use router::{ancestors, breadcrumbs, is_active, nav_link};
+
+        routes! {
+            #[derive(Debug, Clone, PartialEq)]
+            enum Page {
+                Home => "/",
+                Users => "/users",
+                User { name: String, tab: Option<u8> } => "/users/{name}?tab={tab}",
+                Old => "/u" => Page::Users,
+            }
+        }
+
+        impl From<Page> for () {
+            fn from(_page: Page) {}
+        }
+
+        let ada = Page::User { name: "ada".to_string(), tab: Some(1) };
+        assert!(is_active(&Page::Users, &ada));
+        assert!(is_active(&Page::User { name: "ada".to_string(), tab: None }, &ada));
+        assert!(!is_active(&Page::User { name: "ad".to_string(), tab: None }, &ada));
+        assert!(!is_active(&Page::Home, &ada));
+        assert!(is_active(&Page::Home, &Page::Home));
+
+        let users = nav_link::<(), _, _>(Page::Old, &ada, "Users")
+            .with_attributes([("class", Str("nav"))]);
+        assert_eq!(
+            users.displayable().to_string(),
+            "<a class=\"nav active\" href=\"/users\" data-domafic-link=\"\">Users</a>"
+        );
+        let home = nav_link::<(), _, _>(Page::Home, &Page::Home, "Home");
+        assert_eq!(home.get_attribute(2), Some(&("aria-current", Str("page"))));
+
+        assert_eq!(ancestors(&ada), vec![Page::Home, Page::Users, ada.clone()]);
+        assert_eq!(ancestors(&Page::Home), vec![Page::Home]);
+        let crumbs = breadcrumbs::<(), _, _, _>(&ada, |page| match *page {
+            Page::User { ref name, .. } => name.clone(),
+            _ => "Up".to_string(),
+        });
+        assert_eq!(crumbs.len(), 3);
+        assert_eq!(
+            crumbs[2].to_string(),
+            "<li><a href=\"/users/ada?tab=1\" data-domafic-link=\"\" aria-current=\"page\" \
+                class=\"active\">ada</a></li>"
+        );
+    }
+
+    #[test]
+    fn titles_routes() {
+        use document::Document;
+        use router::RouteTitle;
+
+        routes! {
+            enum Page {
+                Home => "/",
+                Post { id: usize } => "/posts/{id}",
+            }
+        }
+
+        impl RouteTitle<Vec<&'static str>> for Page {
+            fn title(&self, posts: &Vec<&'static str>) -> String {
+                match *self {
+                    Page::Home => "Home".to_string(),
+                    Page::Post { id } => format!("{} - Blog", posts.get(id).unwrap_or(&"Missing post")),
+                }
+            }
+        }
+
+        let posts = vec!["Hello <world>"];
+        let mut html = Vec::new();
+        Document::for_route(&Page::Post { id: 0 }, &posts).write(&mut html, &p(PhantomData::<()>)).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<title>Hello &lt;world&gt; - Blog</title>"), "{}", html);
+        assert_eq!(Document::for_route(&Page::Post { id: 1 }, &posts).title, "Missing post - Blog");
+        assert_eq!(Document::for_route(&Page::Home, &posts).title, "Home");
+    }
+
+    #[test]
+    fn prefetches_route_data() {
+        use router::{prefetch_link, Prefetch, RouteData};
+
+        routes! {
+            #[derive(Debug, Clone, PartialEq)]
+            enum Page {
+                About => "/about",
+                Post { id: u32 } => "/posts/{id}",
+                OldPost { id: u32 } => "/p/{id}" => Page::Post { id: *id },
+            }
+        }
+
+        impl RouteData for Page {
+            fn data_url(&self) -> Option<String> {
+                match *self {
+                    Page::Post { id } => Some(format!("/api/posts/{}.json", id)),
+                    _ => None,
+                }
+            }
+        }
+
+        impl From<Page> for () {
+            fn from(_page: Page) {}
+        }
+
+        let post = prefetch_link::<(), _, _>(Page::OldPost { id: 3 }, Prefetch::Hover, "Post");
+        assert_eq!(
+            post.displayable().to_string(),
+            "<a href=\"/posts/3\" data-domafic-link=\"\" \
+                data-domafic-prefetch=\"/api/posts/3.json\" data-domafic-prefetch-on=\"hover\">Post</a>"
+        );
+        let about = prefetch_link::<(), _, _>(Page::About, Prefetch::Visible, "About");
+        assert_eq!(about.displayable().to_string(), "<a href=\"/about\" data-domafic-link=\"\">About</a>");
+    }
+
+    #[test]
+    fn counts_busy_commands() {
+        use busy::{self, Busy};
+        use context;
+
+        let mut in_flight = Busy::new();
+        in_flight.start(Some("save"));
+        in_flight.start(Some("save"));
+        in_flight.start(None);
+        in_flight.finish(Some("save"));
+        assert_eq!((in_flight.pending(), in_flight.pending_with("save")), (2, 1));
+
+        in_flight.finish(Some("save"));
+        in_flight.finish(Some("load"));
+        assert!(!in_flight.is_busy());
+        assert_eq!(in_flight, Busy::new());
+        in_flight.finish(None);
+        assert_eq!(in_flight.pending(), 0);
+
+        in_flight.start(Some("load"));
+        let nested = context::provide(in_flight, || context::provide(Busy::new(), busy::current));
+        assert!(!nested.is_busy());
+        assert!(!busy::current().is_busy());
+    }
+
+    #[test]
+    fn maps_keyboard_shortcuts() {
+        use shortcuts::{KeyCombo, Scope, Shortcuts};
+
+        let shift_f2 = KeyCombo::parse("shift + F2").unwrap();
+        assert_eq!((shift_f2.key_code, shift_f2.shift), (113, true));
+        assert_eq!(shift_f2.to_string(), "Shift+F2");
+        assert_eq!(KeyCombo::parse("ctrl+alt+delete").unwrap().to_string(), "Ctrl+Alt+Delete");
+        assert_eq!(KeyCombo::parse("esc"), KeyCombo::parse("Escape"));
+        assert_eq!(KeyCombo::parse("hyper+k"), None);
+        assert_eq!(KeyCombo::parse("ctrl+"), None);
+
+        let shortcuts = Shortcuts::new()
+            .global("escape", "close")
+            .global("j", "next")
+            .scoped("list", "j", "next item")
+            .scoped("list", "ctrl+a", "select all")
+            .scoped("list", "ctrl+a", "select page")
+            .scoped("editor", "ctrl+a", "select text")
+            .enabled(Scope::Named("editor"), false);
+        let j = KeyCombo::parse("j").unwrap();
+        assert_eq!(shortcuts.find(j, false), Some(&"next item"));
+        assert_eq!(shortcuts.find(j, true), None);
+        assert_eq!(shortcuts.find(KeyCombo::parse("escape").unwrap(), true), Some(&"close"));
+
+        let conflicts = shortcuts.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].to_string(), "Ctrl+A is bound twice in scope `list`");
+
+        let shortcuts = shortcuts.enabled(Scope::Named("list"), false).enabled(Scope::Named("editor"), true);
+        assert!(shortcuts.conflicts().is_empty());
+        assert_eq!(shortcuts.find(j, false), Some(&"next"));
+
+        let subscription = shortcuts.subscription();
+        // Keycode, shift, alt, ctrl, meta, editing, repeat
+        assert_eq!(subscription.handle(&[65.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0]), Some("select text"));
+        assert_eq!(subscription.handle(&[65.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn marks_focus_traps() {
+        use focus::{focus_trap, FOCUS_TRAP_ATTRIBUTE};
+
+        let modal = focus_trap(div((attributes([("class", Str("modal"))]), PhantomData::<()>)))
+            .with_attributes([("class", Str("open"))]);
+        assert_eq!(
+            modal.displayable().to_string(),
+            "<div class=\"open modal\" data-domafic-focus-trap=\"\"></div>"
+        );
+        assert_eq!(focus_trap(modal).get_attribute(0), Some(&(FOCUS_TRAP_ATTRIBUTE, Str(""))));
+    }
+
+    #[test]
+    fn exports_subtrees() {
+        use html_writer::{subtree_html, Subtree};
+        use ElementRef;
+
+        const ROW: ElementRef = ElementRef("row");
+
+        let table = div((
+            p("unkeyed").with_attributes([ROW.attribute()]),
+            div([span("a").with_key(3), span("b").with_key(4)]).with_key(3),
+            span("c").with_attributes([ROW.attribute()]),
+            PhantomData::<()>,
+        ));
+        assert_eq!(subtree_html(&table, Subtree::Ref(ROW)).unwrap(), "<p data-domafic-ref=\"row\">unkeyed</p>");
+        assert_eq!(subtree_html(&table, Subtree::KeyPath(&[3, 3])).unwrap(), "<span>a</span>");
+        assert_eq!(
+            subtree_html(&table, Subtree::KeyPath(&[3])).unwrap(),
+            "<div><span>a</span><span>b</span></div>"
+        );
+        assert_eq!(subtree_html(&table, Subtree::KeyPath(&[])), None);
+        assert_eq!(subtree_html(&table, Subtree::Ref(ElementRef("missing"))), None);
+    }
+
+    #[cfg(feature = "indexed_db")]
+    #[test]
+    fn decodes_idb_batches() {
+        use super::indexed_db::{encode, parse_batch, IdbBatch, IdbError};
+
+        let batch = parse_batch(r#"[["a",[1,"one"]],["b",[2,"two"]]]"#, false).unwrap();
+        assert_eq!(batch.entries[1], ("b".to_string(), encode(&(2, "two")).unwrap()));
+        assert_eq!(batch.decode::<(u32, String)>().unwrap(), IdbBatch {
+            entries: vec![("a".to_string(), (1, "one".to_string())), ("b".to_string(), (2, "two".to_string()))],
+            done: false,
+        });
+
+        let last = parse_batch("[]", true).unwrap();
+        assert!(last.entries.is_empty() && last.done);
+
+        match parse_batch(r#"[["a",1]]"#, true).unwrap().decode::<String>() {
+            Err(IdbError::Malformed(_)) => {},
+            other => panic!("Expected a malformed value, found {:?}", other),
+        }
+        match parse_batch("{}", true) {
+            Err(IdbError::Malformed(_)) => {},
+            other => panic!("Expected a malformed batch, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prompts_reload_on_update() {
+        use service_worker::{ReloadPrompt, ServiceWorkerEvent};
+
+        let mut prompt = ReloadPrompt::new();
+        for name in &["registered", "installed", "controllerchange"] {
+            prompt.observe(&ServiceWorkerEvent::from_js(name).unwrap());
+        }
+        assert!(!prompt.is_shown());
+        assert_eq!(ServiceWorkerEvent::from_js("redundant"), None);
+
+        prompt.observe(&ServiceWorkerEvent::Updated);
+        let rendered = prompt.render("Update ready", "Reload", "Later", 1, 2).unwrap();
+        assert_eq!(rendered.get_attribute(1), Some(&("role", Str("status"))));
+        prompt.dismiss();
+        assert!(prompt.render("Update ready", "Reload", "Later", 1, 2).is_none());
+
+        // A later update is shown even though the earlier one was dismissed
+        prompt.observe(&ServiceWorkerEvent::Updated);
+        assert!(prompt.is_shown());
+        prompt.observe(&ServiceWorkerEvent::ControllerChange);
+        assert!(!prompt.is_shown());
+    }
+
+    #[test]
+    fn writes_web_manifests() {
+        use super::document::Document;
+        use super::ssg::Generator;
+        use super::web_manifest::{Error, Icon, WebManifest};
+        use std::fs;
+
+        assert_eq!(WebManifest::new("").validate(), Err(Error::Missing("name")));
+        assert_eq!(WebManifest::new("App").validate(), Err(Error::MissingIcon("192x192")));
+
+        let manifest = WebManifest::new("Say \"hi\"")
+            .background_color("white")
+            .icon(Icon::new("/mask.png", "192x192 512x512").purpose("maskable"))
+            .icon(Icon::new("/icon.svg", "any 192x192 512x512"));
+        assert!(manifest.to_json().starts_with("{\"name\":\"Say \\\"hi\\\"\",\"start_url\":\"/\",\
+            \"background_color\":\"white\",\"icons\":[{\"src\":\"/mask.png\",\"sizes\":\"192x192 512x512\",\
+            \"purpose\":\"maskable\"}"));
+
+        let out_dir = ::std::env::temp_dir().join("domafic-writes-web-manifests");
+        let _ = fs::remove_dir_all(&out_dir);
+        let report = Generator::new(&out_dir)
+            .with_web_manifest("/app.webmanifest", manifest.clone())
+            .generate(&["/"], |route| route.to_string(), |_| (), |_: &()| {
+                (Document::new("Home"), a((attributes([("href", Str("/app.webmanifest"))]), PhantomData::<()>)))
+            })
+            .unwrap();
+        assert_eq!(report.broken_links, vec![]);
+        assert_eq!(fs::read_to_string(out_dir.join("app.webmanifest")).unwrap(), manifest.to_json());
+        let page = fs::read_to_string(&report.pages[0]).unwrap();
+        assert!(page.contains("<title>Home</title><link rel=\"manifest\" href=\"/app.webmanifest\">\
+            <meta name=\"apple-mobile-web-app-title\" content=\"Say &quot;hi&quot;\">\
+            <link rel=\"apple-touch-icon\" href=\"/icon.svg\"></head>"));
+
+        let invalid = Generator::new(&out_dir).with_web_manifest("/app.webmanifest", WebManifest::new("App"))
+            .generate(&["/"], |route| route.to_string(), |_| (), |_: &()| (Document::new("Home"), p(PhantomData::<()>)));
+        assert_eq!(invalid.unwrap_err().kind(), ::std::io::ErrorKind::InvalidData);
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn retries_requests_offline() {
+        use super::effects::{Effect, HttpError, MockExecutor, Request, Response};
+        use super::offline::{Attempt, RetryPolicy, RetryQueue, RETRY_DELAY_MILLIS};
+
+        #[derive(Debug)]
+        enum Msg { Retry(Attempt), Done(u16) }
+
+        fn status(result: Result<Response, HttpError>) -> Msg {
+            Msg::Done(result.map(|response| response.status_code).unwrap_or(0))
+        }
+        fn handle(queue: &mut RetryQueue<Msg>, executor: &MockExecutor<Msg>, msg: Msg) -> Option<u16> {
+            match msg {
+                Msg::Retry(attempt) => queue.handle(executor, attempt).and_then(|msg| handle(queue, executor, msg)),
+                Msg::Done(status) => Some(status),
+            }
+        }
+
+        assert_eq!(RetryPolicy::for_method("GET"), RetryPolicy::Never);
+        let executor = MockExecutor::new();
+        let mut queue = RetryQueue::new(true, Msg::Retry);
+
+        // Online failures are retried after a backoff, until the policy runs out of attempts
+        let id = queue.send(&executor, Request::post("/a", ""), RetryPolicy::WhenOnline { max_attempts: 2 }, status);
+        let msg = executor.respond(Err(HttpError::NetworkError));
+        assert_eq!(handle(&mut queue, &executor, msg), None);
+        match executor.take().pop() {
+            Some(Effect::Delay { millis, message }) => {
+                assert_eq!(millis, RETRY_DELAY_MILLIS);
+                assert_eq!(handle(&mut queue, &executor, message), None);
+            },
+            other => panic!("Expected a delay, found {:?}", other),
+        }
+        assert_eq!(queue.attempts(id), Some(2));
+        let msg = executor.respond(Err(HttpError::NetworkError));
+        assert_eq!(handle(&mut queue, &executor, msg), Some(0));
+        assert!(queue.is_empty() && executor.is_empty());
+
+        // Requests sent while offline aren't attempted until the device reconnects, unless
+        // they're never retried
+        queue.set_online(&executor, false);
+        queue.send(&executor, Request::post("/b", ""), RetryPolicy::for_method("POST"), status);
+        queue.send(&executor, Request::get("/c"), RetryPolicy::Never, status);
+        assert_eq!((queue.queued(), queue.len(), executor.len()), (1, 2, 1));
+        let msg = executor.respond(Err(HttpError::NetworkError));
+        assert_eq!(handle(&mut queue, &executor, msg), Some(0));
+
+        queue.set_online(&executor, true);
+        let msg = executor.respond(Ok(Response::new(201, "")));
+        assert_eq!(handle(&mut queue, &executor, msg), Some(201));
+        assert!(queue.is_empty() && executor.is_empty());
+    }
+
+    #[test]
+    fn encodes_multipart_forms() {
+        use super::multipart::Multipart;
+
+        let form = Multipart::new()
+            .field("say \"hi\"\r\n", "hello")
+            .file("upload", "a.txt", "text/plain", b"line\r\n".to_vec());
+        let boundary = form.boundary();
+        assert_eq!(boundary, form.clone().boundary());
+        let encoded = String::from_utf8(form.to_bytes()).unwrap();
+        assert_eq!(encoded, format!("--{b}\r\nContent-Disposition: form-data; name=\"say %22hi%22%0D%0A\"\r\n\r\n\
+            hello\r\n--{b}\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\nline\r\n\r\n--{b}--\r\n", b = boundary));
+
+        assert!(!encoded.replace(&format!("--{}", boundary), "").contains(&boundary));
+        assert_eq!(Multipart::new().to_bytes(), format!("--{}--\r\n", Multipart::new().boundary()).into_bytes());
+    }
+
+    #[test]
+    fn reports_http_progress() {
+        use super::effects::{Direction, Progress};
+
+        let upload = Progress::from_js("1 256 1024", false).unwrap();
+        assert_eq!(upload, Progress { direction: Direction::Upload, loaded: 256, total: Some(1024), done: false });
+        assert_eq!(upload.fraction(), Some(0.25));
+
+        let download = Progress::from_js("0 4096 -1", true).unwrap();
+        assert_eq!((download.direction, download.total, download.done), (Direction::Download, None, true));
+        assert_eq!(download.fraction(), None);
+        assert_eq!(Progress::from_js("0 0 0", true).unwrap().fraction(), Some(1.0));
+        assert_eq!(Progress::from_js("2 0 0", true), None);
+    }
+
+    #[test]
+    fn retries_effects_with_backoff() {
+        use super::effects::{Effect, Executor, HttpError, MockExecutor, Request, Response, Retry, Retrying};
+
+        #[derive(Debug)]
+        enum Msg { Retry(Retrying<Msg>), Done(Result<u16, HttpError>) }
+
+        let policy = Retry::new(3).backoff(100, 150);
+        assert_eq!((policy.max_delay_after(1), policy.max_delay_after(2), policy.max_delay_after(9)), (100, 150, 150));
+        assert!(policy.is_retryable(&Err(HttpError::NetworkError)));
+        assert!(!policy.is_retryable(&Err(HttpError::Timeout)));
+        assert!(!policy.is_retryable(&Ok(Response::new(500, ""))));
+        assert!(policy.clone().retry_status(500).is_retryable(&Ok(Response::new(500, ""))));
+
+        let executor = MockExecutor::new();
+        executor.execute(Effect::http(Request::get("/a"), |result| {
+            Msg::Done(result.map(|response| response.status_code))
+        }).with_retry(policy, Msg::Retry));
+
+        // Each retried failure is reported with its jittered backoff, until the last attempt
+        for attempt in 1..3 {
+            let retrying = match executor.respond(Err(HttpError::NetworkError)) {
+                Msg::Retry(retrying) => retrying,
+                other => panic!("Expected a retry, found {:?}", other),
+            };
+            assert_eq!((retrying.attempt(), retrying.failure()), (attempt, Some(&Err(HttpError::NetworkError))));
+            let max = if attempt == 1 { 100 } else { 150 };
+            assert!(retrying.delay_millis() >= max / 2 && retrying.delay_millis() <= max);
+            retrying.resume(&executor);
+            match executor.take().pop() {
+                Some(Effect::Delay { millis, message: Msg::Retry(waited) }) => {
+                    assert!(millis <= max && waited.failure().is_none());
+                    waited.resume(&executor);
+                },
+                other => panic!("Expected a delay, found {:?}", other),
+            }
+        }
+        match executor.respond(Err(HttpError::NetworkError)) {
+            Msg::Done(result) => assert_eq!(result, Err(HttpError::NetworkError)),
+            other => panic!("Expected the outcome, found {:?}", other),
+        }
+
+        // Failures which aren't retryable are reported right away, and other effects are
+        // unaffected
+        executor.execute(Effect::http(Request::get("/b"), |result| {
+            Msg::Done(result.map(|response| response.status_code))
+        }).with_retry(Retry::new(3), Msg::Retry));
+        match executor.respond(Ok(Response::new(404, ""))) {
+            Msg::Done(result) => assert_eq!(result, Ok(404)),
+            other => panic!("Expected the outcome, found {:?}", other),
+        }
+        match Effect::delay(5, Msg::Done(Ok(0))).with_retry(Retry::new(3), Msg::Retry) {
+            Effect::Delay { millis, .. } => assert_eq!(millis, 5),
+            other => panic!("Expected a delay, found {:?}", other),
+        }
+        assert!(executor.is_empty());
+    }
+
+    #[test]
+    fn rate_limits_messages() {
+        use super::keys::Keys;
+        use KeyIter;
+        use super::rate_limit::{Admission, Limit, RateLimiter};
+
+        let mut limiter = RateLimiter::new(|msg: &u32| match *msg {
+            0..=9 => Some(Limit::coalesce("low", 100)),
+            10..=19 => Some(Limit::drop("high", 100)),
+            _ => None,
+        });
+        let keys = Keys::new().push(3).into_iter();
+
+        assert!(limiter.admit(0, 1, KeyIter::empty()).is_handled());
+        assert_eq!(limiter.admit(30, 2, keys.clone()), Admission::Held { class: "low", flush_after_millis: Some(70) });
+        assert!(limiter.is_waiting("low") && !limiter.is_waiting("high"));
+
+        // A message arriving after a late flush was due still waits for it, and the flush
+        // starts the next interval
+        assert_eq!(limiter.admit(120, 3, keys.clone()), Admission::Held { class: "low", flush_after_millis: None });
+        assert_eq!(limiter.flush(130, "low"), Some((3, keys)));
+        assert_eq!(limiter.flush(130, "low"), None);
+        assert_eq!(limiter.admit(200, 4, KeyIter::empty()), Admission::Held { class: "low", flush_after_millis: Some(30) });
+
+        // Dropped messages don't extend the interval
+        assert!(limiter.admit(0, 10, KeyIter::empty()).is_handled());
+        assert_eq!(limiter.admit(99, 11, KeyIter::empty()), Admission::Dropped);
+        assert_eq!(limiter.admit(100, 12, KeyIter::empty()), Admission::Handle(12, KeyIter::empty()));
+        assert!(limiter.admit(100, 20, KeyIter::empty()).is_handled());
+    }
+
+    #[cfg(feature = "feature_flags")]
+    #[test]
+    fn overrides_feature_flags() {
+        use super::csp::{self, CspNonce};
+        use super::feature_flags::{self, Error, FeatureFlags, FlagValue};
+
+        let flags = FeatureFlags::new().with("beta", true).with("limit", 5.0).with("theme", "light");
+        assert_eq!(FeatureFlags::from_json(&flags.to_json()).unwrap(), flags);
+        assert!(matches!(FeatureFlags::from_json("[]"), Err(Error::NotAnObject)));
+        match FeatureFlags::from_json(r#"{"beta": null}"#) {
+            Err(Error::InvalidValue(name)) => assert_eq!(name, "beta"),
+            other => panic!("Expected an invalid value, found {:?}", other),
+        }
+
+        // Only the `flags` parameter overrides flags, and its values are percent-decoded
+        let overridden = flags.clone()
+            .with_overrides("https://example.com/?theme=x&flags=-beta,theme:high%20contrast,limit:false,new#flags=x");
+        assert!(!overridden.is_enabled("beta") && overridden.is_enabled("new"));
+        assert_eq!(overridden.variant("theme"), Some("high contrast"));
+        assert_eq!(overridden.get("limit"), Some(&FlagValue::Bool(false)));
+        assert_eq!(overridden.number("limit"), None);
+        assert_eq!(flags.clone().with_overrides("/page"), flags);
+
+        assert!(!feature_flags::is_enabled("beta"));
+        assert!(feature_flags::provide(flags.clone(), || feature_flags::is_enabled("beta")));
+        let tag = csp::provide(CspNonce::new("n"), || FeatureFlags::new().with("a</script>", true).script_tag());
+        assert_eq!(tag, "<script type=\"application/json\" id=\"domafic-feature-flags\" nonce=\"n\">\
+            {\"a<\\/script>\":true}</script>");
+    }
+
+    #[cfg(feature = "bootstrap")]
+    #[test]
+    fn embeds_bootstrap_data() {
+        use super::bootstrap::{Bootstrap, Error};
+        use super::document::{Document, HeadElement};
+
+        let bootstrap = Bootstrap::new()
+            .with("state", &(1, "<!-- </SCRIPT>")).unwrap()
+            .with("state", &(2, "<!-- </SCRIPT>")).unwrap();
+        assert_eq!(bootstrap.script_tag(), "<script type=\"application/json\" id=\"domafic-bootstrap\">\
+            {\"state\":[2,\"\\u003c!-- \\u003c/SCRIPT>\"]}</script>");
+        let document = Document::new("").bootstrap(&bootstrap);
+        assert_eq!(document.head, vec![HeadElement::Json { id: "domafic-bootstrap".to_string(), json: bootstrap.to_json() }]);
+
+        let decoded = Bootstrap::from_json(&bootstrap.to_json()).unwrap();
+        assert!(decoded.contains("state") && !decoded.contains("user"));
+        assert_eq!(decoded.get::<(u32, String)>("state").unwrap(), (2, "<!-- </SCRIPT>".to_string()));
+        assert!(matches!(decoded.get::<bool>("state"), Err(Error::Json(_))));
+        assert!(matches!(decoded.get::<bool>("user"), Err(Error::Missing(ref name)) if name == "user"));
+        assert!(matches!(Bootstrap::from_json("[]"), Err(Error::NotAnObject)));
+    }
+
+    #[test]
+    fn converts_errors_to_crate_error() {
+        use super::Error;
+        use std::error::Error as StdError;
+        use std::io;
+
+        assert_eq!(Error::MissingElement("#app".to_string()).to_string(), "no element matches `#app`");
+        assert_eq!(Error::Ffi("createElement").to_string(), "JavaScript call failed: createElement");
+        assert!(Error::PoolExhausted.source().is_none());
+
+        fn write() -> Result<(), Error> {
+            Err(io::Error::new(io::ErrorKind::WriteZero, "closed"))?;
+            Ok(())
+        }
+        let err = write().unwrap_err();
+        assert!(matches!(err, Error::Io(ref err) if err.kind() == io::ErrorKind::WriteZero));
+        assert_eq!(err.to_string(), "write failed: closed");
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn subscribes_to_root_and_body_events() {
+        use super::subscriptions::{on_body_event, on_key_down, on_root_event, SubscriptionSource};
+
+        let backdrop = on_root_event("click", |event| if event.on_element { Some("backdrop") } else { None });
+        assert_eq!(backdrop.source(), SubscriptionSource::RootEvent("click"));
+        assert_eq!(backdrop.handle(&[1.0, 1.0]), Some("backdrop"));
+        assert_eq!(backdrop.handle(&[0.0, 1.0]), None);
+
+        let outside = on_body_event("click", |event| if event.inside_app { None } else { Some("outside") });
+        assert_eq!(outside.source().event_type(), "click");
+        assert_eq!(outside.handle(&[0.0, 0.0]), Some("outside"));
+        assert_eq!(outside.handle(&[0.0, 1.0]), None);
+
+        assert!(outside.source().is_element_event());
+        assert!(!on_key_down(|_| Some("key")).source().is_element_event());
+    }
+
+    #[test]
+    fn subscribes_to_clicks_outside_of_refs() {
+        use super::ElementRef;
+        use super::subscriptions::{on_click_outside, SubscriptionSource};
+
+        let close = on_click_outside(ElementRef("menu"), || "close");
+        assert_eq!(close.source(), SubscriptionSource::ClickOutside(ElementRef("menu")));
+        assert_eq!(close.source().event_type(), "click");
+        assert!(close.source().is_element_event());
+        assert_eq!(close.handle(&[]), Some("close"));
+    }
+
+    #[test]
+    fn marks_interaction_classes() {
+        use interaction::{FOCUS_WITHIN_CLASS_ATTRIBUTE, HOVER_CLASS_ATTRIBUTE};
+
+        let card = div((attributes([("class", Str("card"))]), PhantomData::<()>))
+            .with_hover_class("raised")
+            .with_focus_within_class("outlined");
+        assert_eq!(card.get_attribute(0), Some(&(FOCUS_WITHIN_CLASS_ATTRIBUTE, Str("outlined"))));
+        assert_eq!(card.get_attribute(1), Some(&(HOVER_CLASS_ATTRIBUTE, Str("raised"))));
+        assert_eq!(card.displayable().to_string(), "<div data-domafic-focus-within-class=\"outlined\" \
+            data-domafic-hover-class=\"raised\" class=\"card\"></div>");
+    }
+
+    #[test]
+    fn animates_values_with_easing() {
+        use effects::{Easing, Effect, Executor, MockExecutor};
+
+        for &easing in &[Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!((easing.apply(-1.0), easing.apply(0.0), easing.apply(1.0), easing.apply(2.0)), (0.0, 0.0, 1.0, 1.0));
+        }
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseIn.apply(0.5) < 0.5 && Easing::EaseOut.apply(0.5) > 0.5);
+
+        let executor = MockExecutor::new();
+        executor.execute(Effect::animate(10.0, 0.0, 50, Easing::EaseInOut, |value| value));
+        executor.execute(Effect::delay(10, -1.0));
+        executor.execute(Effect::animate(0.0, 1.0, 0, Easing::Linear, |value| value));
+        // Frames which don't divide the duration still end on the final value
+        assert_eq!(executor.fire_animations(20), vec![10.0 - 10.0 * Easing::EaseInOut.apply(0.4), 10.0 - 10.0 * Easing::EaseInOut.apply(0.8), 0.0, 1.0]);
+        assert_eq!(executor.len(), 1);
+    }
+
+    #[test]
+    fn settles_springs() {
+        use effects::{Effect, Executor, MockExecutor};
+        use spring::{spring, SpringState};
+
+        // Critically damped springs approach the target without overshooting
+        let smooth = spring(100.0, 20.0);
+        let mut state = SpringState { value: 0.0, velocity: 0.0 };
+        for _ in 0..100 {
+            let next = smooth.step(state, 1.0, 16.0);
+            assert!(next.value >= state.value && next.value <= 1.0);
+            state = next;
+        }
+        assert!(smooth.is_at_rest(&state, 1.0));
+        // Steps are split, so one long step gives the same result as many short ones
+        let start = SpringState { value: 5.0, velocity: -20.0 };
+        assert_eq!(smooth.step(start, 0.0, 10.0), (0..10).fold(start, |state, _| smooth.step(state, 0.0, 1.0)));
+
+        let heavy = spring(100.0, 20.0).with_mass(4.0);
+        assert!(heavy.step(start, 0.0, 100.0).value > smooth.step(start, 0.0, 100.0).value);
+
+        let executor = MockExecutor::new();
+        executor.execute(Effect::spring(spring(300.0, 5.0), 0.0, 10.0, 0.0, |value| value));
+        let values = executor.fire_animations(16);
+        assert!(values.iter().any(|&value| value > 10.0));
+        assert_eq!(values.last(), Some(&10.0));
+        assert!(executor.is_empty());
+    }
+
+    #[test]
+    fn maps_messages() {
+        use listener::on;
+        use processors::{ListenerProcessor, Listeners};
+
+        #[derive(Debug, PartialEq)]
+        enum Child { Clicked(u32) }
+        #[derive(Debug, PartialEq)]
+        enum Parent { Child(Child), Closed }
+
+        // Collects the messages of every listener in a tree, as if each were clicked
+        struct Clicks;
+        impl<'a> ListenerProcessor<'a, Parent> for Clicks {
+            type Acc = Vec<Parent>;
+            type Error = ();
+            fn get_processor<L: Listener<Parent>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), ()> {
+                fn click<L: Listener<Parent>>(acc: &mut Vec<Parent>, listener: &L) -> Result<(), ()> {
+                    acc.push(listener.handle_event(Event {
+                        type_str: Some("click"), target_value: None, client_x: 0, client_y: 0,
+                        offset_x: 0, offset_y: 0, which_keycode: 1, shift_key: false,
+                        alt_key: false, ctrl_key: false, meta_key: false, detail: None,
+                    }));
+                    Ok(())
+                }
+                click::<L>
+            }
+        }
+        struct Walk;
+        impl<'a> DomNodeProcessor<'a, Parent> for Walk {
+            type Acc = Vec<Parent>;
+            type Error = ();
+            fn get_processor<T: DomNode<Parent>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), ()> {
+                fn walk<T: DomNode<Parent>>(acc: &mut Vec<Parent>, node: &T) -> Result<(), ()> {
+                    node.listeners().process_all::<Clicks>(acc)?;
+                    node.children().process_all::<Walk>(acc)
+                }
+                walk::<T>
+            }
+        }
+
+        let child = div((
+            button("One").with_listeners(on("click", |_| Child::Clicked(1))),
+            button("Two").with_listeners(on("click", |_| Child::Clicked(2))).with_key(2),
+        )).with_listeners(on("click", |_| Child::Clicked(0)));
+        let html = child.displayable().to_string();
+        let view = div((
+            child.map_message(Parent::Child),
+            button("Close").with_listeners(on("click", |_| Parent::Closed)),
+        ));
+        assert_eq!(view.displayable().to_string(), format!("<div>{}<button>Close</button></div>", html));
+
+        let mut messages = Vec::new();
+        view.process_all::<Walk>(&mut messages).unwrap();
+        assert_eq!(messages, vec![
+            Parent::Child(Child::Clicked(0)),
+            Parent::Child(Child::Clicked(1)),
+            Parent::Child(Child::Clicked(2)),
+            Parent::Closed,
+        ]);
+        // Splitting off the listeners still maps them
+        let (_, listeners) = div(()).with_listeners(on("click", |_| 7)).map_message(|n| Parent::Child(Child::Clicked(n))).split_listeners();
+        let mut messages = Vec::new();
+        listeners.process_all::<Clicks>(&mut messages).unwrap();
+        assert_eq!(messages, vec![Parent::Child(Child::Clicked(7))]);
+    }
+
+    #[test]
+    fn subscribes_to_timers_and_resizes() {
+        use super::subscriptions::{every, on_animation_frame, on_resize, SubscriptionSource, Viewport};
+
+        let tick = every(1000, |now| now as u64);
+        assert_eq!(tick.source(), SubscriptionSource::Interval(1000));
+        assert_ne!(tick.source(), every(500, |now| now as u64).source());
+        assert_eq!(tick.handle(&[1500000000000.0]), Some(1500000000000));
+
+        let frame = on_animation_frame(|elapsed| elapsed);
+        assert_eq!(frame.source(), SubscriptionSource::AnimationFrame);
+        assert_eq!(frame.handle(&[16.5]), Some(16.5));
+
+        let resize = on_resize(|viewport| viewport);
+        assert_eq!(resize.source().event_type(), "resize");
+        assert!(!resize.source().is_element_event());
+        assert_eq!(resize.handle(&[1280.0, 720.0]), Some(Viewport { width: 1280, height: 720 }));
+    }
+
+    #[test]
+    fn pages_infinite_scroll() {
+        use infinite_scroll::{infinite_scroll, Paging};
+
+        let mut paging = Paging::new(0);
+        assert_eq!(paging.next_page(), Some(0));
+        assert!(!paging.start(1));
+        assert!(paging.start(0));
+        // A sentinel rendered before the page started loading asks for it again
+        assert!(!paging.start(0));
+        assert_eq!(infinite_scroll(7, &paging, |page| page).key(), Some(7));
+        assert_eq!(infinite_scroll(7, &paging, |page| page).displayable().to_string(),
+            "<div data-domafic-sentinel=\"false\"></div>");
+
+        paging.failed();
+        assert!(paging.start(0));
+        paging.loaded(true);
+        assert_eq!(paging.next_page(), Some(1));
+        assert_eq!(infinite_scroll(7, &paging, |page| page).displayable().to_string(),
+            "<div data-domafic-sentinel=\"true\"></div>");
+
+        assert!(paging.start(1));
+        paging.loaded(false);
+        assert!(paging.is_exhausted());
+        assert_eq!(paging.next_page(), None);
+        // Finishing a page which wasn't loading changes nothing
+        paging.loaded(true);
+        assert_eq!(paging.next_page(), None);
+    }
+
+    #[test]
+    fn writes_hydratable_html() {
+        use super::Error;
+        use super::html_writer::{write_hydratable, HYDRATION_ID_ATTRIBUTE};
+        use listener::on;
+
+        // Elements are numbered in document order, parents before their children
+        let node = div((
+            attributes([("class", Str("list"))]),
+            on("click", |_| ()),
+            (ul((
+                li((on("click", |_| ()), "One")),
+                li("Two"),
+                li((on("click", |_| ()), "Three")),
+            )),
+            input(on("input", |_| ()))),
+        ));
+        let mut html = Vec::new();
+        write_hydratable(&node, &mut html).unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), format!(
+            "<div class=\"list\" {0}=\"0\"><ul><li {0}=\"1\">One</li><li>Two</li>\
+            <li {0}=\"2\">Three</li></ul><input {0}=\"3\"></div>",
+            HYDRATION_ID_ATTRIBUTE));
+
+        assert_eq!(Error::HydrationMismatch("<li>".to_string()).to_string(),
+            "the page has no <li> to hydrate");
+    }
+
+    #[test]
+    fn merges_typed_styles() {
+        use super::styles::{style, to_css};
+        use super::theme::{Color, Length};
+
+        assert_eq!(to_css([("margin", Length::Px(0.0)), ("padding", Length::Rem(1.5))]),
+            "margin: 0px; padding: 1.5rem");
+        assert_eq!(to_css(vec![("opacity", 0.5)]), "opacity: 0.5");
+        assert_eq!(to_css(Vec::<(&'static str, f32)>::new()), "");
+
+        let node = span((
+            style((("color", Color::Rgb(255, 0, 0)), ("display", "block"))),
+            style([("width", Length::Percent(50.0))]),
+            ("Hi", PhantomData::<()>),
+        )).with_attributes([("style", Str("height: 2em;"))]);
+        assert_eq!(node.displayable().to_string(),
+            "<span style=\"height: 2em; color: #ff0000; display: block; width: 50%\">Hi</span>");
+    }
+
+    #[test]
+    fn picks_dates_from_the_calendar() {
+        use super::date_picker::{month_grid, Date, DateFormat, DateMsg, DatePicker, Weekday};
+
+        let date = |year, month, day| Date::new(year, month, day).unwrap();
+        assert_eq!(Date::new(2023, 2, 29), None);
+        assert_eq!(Date::parse("2024-02-29"), Some(date(2024, 2, 29)));
+        assert_eq!(Date::parse("2024-2-29"), None);
+        assert_eq!(date(2000, 1, 1).weekday(), Weekday::Saturday);
+        assert_eq!(date(2024, 12, 31).add_days(1), date(2025, 1, 1));
+        assert_eq!(date(2024, 1, 31).add_months(1), date(2024, 2, 29));
+        assert_eq!(date(2024, 1, 15).add_months(-13), date(2022, 12, 15));
+        assert_eq!(date(1969, 12, 31).format(DateFormat::WithWeekday), "Wednesday, December 31, 1969");
+
+        // September 2024 starts on a Sunday, and ends on a Monday
+        let weeks = month_grid(date(2024, 9, 10), Weekday::Monday);
+        assert_eq!(weeks.len(), 6);
+        assert_eq!(weeks[0][0], date(2024, 8, 26));
+        assert_eq!(weeks[5][6], date(2024, 10, 6));
+        assert_eq!(month_grid(date(2024, 9, 10), Weekday::Sunday)[0][0], date(2024, 9, 1));
+
+        let mut picker = DatePicker::new("due", date(2024, 3, 29))
+            .min(date(2024, 3, 4))
+            .max(date(2024, 4, 2));
+        picker.update(DateMsg::Toggle);
+        assert!(picker.is_open());
+        let key = |key_code| DateMsg::Key { key_code, shift: false };
+        assert_eq!(picker.update(key(40)), None);
+        // Moving past the maximum stops at it
+        assert_eq!(picker.focused(), date(2024, 4, 2));
+        picker.update(key(36));
+        assert_eq!(picker.focused(), date(2024, 4, 1));
+        picker.update(DateMsg::Key { key_code: 33, shift: true });
+        assert_eq!(picker.focused(), date(2024, 3, 4));
+        picker.update(key(39));
+        assert_eq!(picker.update(DateMsg::Select(date(2024, 3, 3))), None);
+        assert_eq!(picker.update(key(13)), Some(date(2024, 3, 5)));
+        assert!(!picker.is_open());
+        assert_eq!(picker.update(DateMsg::Input(Some(date(2024, 5, 1)))), None);
+        assert_eq!(picker.value(), Some(date(2024, 3, 5)));
+        assert_eq!(picker.update(DateMsg::Input(None)), None);
+        assert_eq!(picker.value(), None);
+
+        let calendar = picker.render_calendar().displayable().to_string();
+        assert!(calendar.contains("aria-activedescendant=\"due-2024-03-05\""));
+        assert!(calendar.contains("<td id=\"due-2024-03-03\" class=\"\" aria-selected=\"false\" \
+            aria-disabled=\"true\">3</td>"));
+        assert!(calendar.contains("<td id=\"due-2024-02-26\" class=\"outside\""));
+        assert!(calendar.contains("aria-label=\"Previous month\" disabled>"));
+    }
+
+    #[test]
+    fn keys_children_from_maps_and_items() {
+        use super::keyed_by;
+        use std::collections::BTreeMap;
+
+        let mut rows = BTreeMap::new();
+        rows.insert("b", li("Second"));
+        rows.insert("a", li("First"));
+        assert_eq!(ul::<(), _, _, _, _>(rows).to_string(), "<ul><li>First</li><li>Second</li></ul>");
+
+        let ids = [4, 9, 2];
+        let list = div::<(), _, _, _, _>(keyed_by(ids.iter(), |&&id| id * 10, |id| p(id.to_string())));
+        let keys: Vec<_> = list.children().iter().map(|child| child.key()).collect();
+        assert_eq!(keys, vec![Some(40), Some(90), Some(20)]);
+        assert_eq!(list.to_string(), "<div><p>4</p><p>9</p><p>2</p></div>");
+    }
+
+    #[test]
+    fn stops_visiting_early() {
+        use processors::{visit, Flow, Visitor};
+
+        // Records the tags it visits, skipping lists and stopping at the first heading
+        struct Tags(Vec<&'static str>);
+        impl Visitor<()> for Tags {
+            fn visit<T: DomNode<()>>(&mut self, node: &T) -> Flow {
+                match node.value() {
+                    DomValue::Element { tag } => {
+                        self.0.push(tag);
+                        match tag {
+                            "ul" => Flow::SkipChildren,
+                            "h2" => Flow::Stop,
+                            _ => Flow::Continue,
+                        }
+                    },
+                    _ => Flow::Continue,
+                }
+            }
+        }
+
+        let page = div((
+            ul((li("One"), li("Two"))),
+            vec![p("Intro"), p("More")],
+            section((h2("Heading"), p("After"))),
+            footer("Footer"),
+        ));
+        assert_eq!(visit(&page, Tags(Vec::new())).0, vec!["div", "ul", "p", "p", "section", "h2"]);
+    }
+
+    #[test]
+    fn writes_boolean_attributes() {
+        use super::html_writer::write_minified;
+        use super::xml_writer::{Canonical, Html, Xhtml, XmlWriter};
+        use AttributeValue::Bool;
+
+        let node = input::<(), _, _, _, _>(attributes([
+            ("disabled", Bool(true)),
+            ("required", Bool(false)),
+            ("aria-invalid", Bool(false)),
+            ("draggable", Bool(true)),
+        ]));
+        assert_eq!(node.to_string(), "<input disabled aria-invalid=\"false\" draggable=\"true\">");
+
+        let mut xhtml = Vec::new();
+        node.process_all::<XmlWriter<_, Xhtml>>(&mut xhtml).unwrap();
+        assert_eq!(String::from_utf8(xhtml).unwrap(),
+            "<input disabled=\"disabled\" aria-invalid=\"false\" draggable=\"true\"/>");
+
+        let mut canonical = Vec::new();
+        node.process_all::<XmlWriter<_, Canonical<Html>>>(&mut canonical).unwrap();
+        assert_eq!(String::from_utf8(canonical).unwrap(),
+            "<input aria-invalid=\"false\" disabled draggable=\"true\">");
+
+        let mut minified = Vec::new();
+        write_minified(&node, &mut minified).unwrap();
+        assert_eq!(String::from_utf8(minified).unwrap(), "<input disabled aria-invalid=false draggable=true>");
+
+        assert_eq!(Bool(false).as_bool_attribute("hidden"), Some(false));
+        assert_eq!(Bool(true).as_bool_attribute("data-open"), None);
+        assert_eq!(Str("").as_bool_attribute("hidden"), None);
+    }
+
+    #[test]
+    fn snapshots_render_like_their_nodes() {
+        use super::{comment, on};
+        use super::devtools::NodeSnapshot;
+        use AttributeValue::Bool;
+
+        fn assert_send<T: Send>(_: &T) {}
+
+        let page = |label| div((
+            attributes([("hidden", Bool(false)), ("class", Str("page"))]),
+            (
+                button((attributes([("disabled", Bool(true))]), on("click", |_| ()), label)).with_key(2),
+                comment("end"),
+            ),
+        ));
+        let snapshot: NodeSnapshot = page("Save").snapshot();
+        assert_send(&snapshot);
+        assert_eq!(snapshot.children[0].key, Some(2));
+        assert_eq!(snapshot.children[0].listeners, 1);
+        assert_eq!(DomNode::<()>::displayable(&snapshot).to_string(), page("Save").to_string());
+        assert_eq!(DomNode::<()>::displayable(&snapshot).to_string(),
+            "<div class=\"page\"><button disabled>Save</button><!--end--></div>");
+
+        assert_eq!(page("Save").snapshot(), snapshot);
+        assert_ne!(page("Saved").snapshot(), snapshot);
+    }
+
+    #[test]
+    fn measures_trees() {
+        use super::on;
+        use super::metrics::{measure, Metrics};
+
+        let items: Vec<_> = (0..3).map(|i| li(i.to_string())).collect();
+        let page = div((
+            attributes([("class", Str("a")), ("class", Str("b")), ("id", Str("page"))]),
+            (
+                ul(items),
+                button(((on("click", |_| ()), on("keydown", |_| ())), "Más")),
+            ),
+        ));
+        let metrics = measure(&page);
+        assert_eq!(metrics, Metrics { nodes: 10, max_depth: 4, attributes: 2, listeners: 2, text_bytes: 7 });
+        assert_eq!(measure::<(), _>(&()), Metrics::default());
+
+        let budget = Metrics { max_depth: 3, listeners: 1, ..Metrics::unlimited() };
+        assert_eq!(metrics.exceeds(&budget), vec!["max_depth", "listeners"]);
+        assert!(metrics.exceeds(&metrics).is_empty());
+    }
+
+    #[test]
+    fn minifies_whitespace_between_inline_elements() {
+        use super::comment;
+        use super::html_writer::{write_minified, Minifier, MinifyingHtmlWriter};
+
+        let minify = |node: &dyn Fn(&mut Vec<u8>)| {
+            let mut html = Vec::new();
+            node(&mut html);
+            String::from_utf8(html).unwrap()
+        };
+
+        let paragraph = p(("Hello ", b("bold"), " ", i("it"), PhantomData::<()>));
+        assert_eq!(minify(&|html| write_minified(&paragraph, html).unwrap()),
+            "<p>Hello <b>bold</b> <i>it</i></p>");
+
+        let list = div((
+            "\n  ",
+            ul(("\n    ", li("One"), "\n    ", li(("Two", b(" "), "  ", i("!"))), "\n  ")),
+            "\n  ",
+            comment("end"),
+            span("Inline"),
+            "\n",
+            PhantomData::<()>,
+        ));
+        assert_eq!(minify(&|html| write_minified(&list, html).unwrap()),
+            "<div><ul><li>One</li><li>Two<b> </b><i>&#33;</i></li></ul> <span>Inline</span></div>");
+
+        let kept = minify(&|html| {
+            let mut minifier = Minifier::new(html).keep_comments();
+            list.process_all::<MinifyingHtmlWriter<_>>(&mut minifier).unwrap();
+        });
+        assert!(kept.contains("</ul><!--end--> <span>"));
+    }
+
+    #[test]
+    fn csp_policy_allows_themed_pages() {
+        use super::csp::{self, CspNonce};
+        use super::document::Document;
+        use super::theme::{self, Color, Theme};
+
+        // Whether `policy` applies style attributes, which can't carry a nonce. Sources other
+        // than keywords, such as nonces, make browsers ignore `'unsafe-inline'`.
+        fn allows_style_attributes(policy: &str) -> bool {
+            let directives: Vec<Vec<&str>> = policy.split(';')
+                .map(|directive| directive.split_whitespace().collect())
+                .collect();
+            let directive = ["style-src-attr", "style-src", "default-src"].iter()
+                .filter_map(|name| directives.iter().find(|sources| sources.first() == Some(name)))
+                .next();
+            directive.is_none_or(|sources| sources.contains(&"'unsafe-inline'") &&
+                !sources.iter().any(|source| source.starts_with("'nonce-") || source.starts_with("'sha")))
+        }
+
+        let nonce = CspNonce::new("abc");
+        let page = Theme::new("dark").with_color("text", Color::Rgb(255, 255, 255));
+        let html = csp::provide(nonce.clone(), || {
+            let body = theme::provide(&page, || p(("Themed", PhantomData::<()>)));
+            let mut html = Vec::new();
+            Document::new("Page").csp_nonce(nonce.clone()).write(&mut html, &body).unwrap();
+            String::from_utf8(html).unwrap()
+        });
+
+        assert!(html.contains("style=\"--color-text: #ffffff"));
+        assert!(html.contains(&format!("content=\"{}\"", nonce.policy())));
+        assert!(allows_style_attributes(&nonce.policy()));
+        assert!(!allows_style_attributes("style-src 'nonce-abc' 'unsafe-inline'"));
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn chains_module_errors_through_crate_error() {
+        use super::Error;
+        use super::persist::RestoreError;
+        use super::social_meta;
+        use std::error::Error as StdError;
+
+        fn restore() -> Result<u32, Error> {
+            let err = serde_json::from_str::<u32>("[").unwrap_err();
+            Err(RestoreError::Malformed(err))?
+        }
+        let err = restore().unwrap_err();
+        assert!(matches!(err, Error::Restore(RestoreError::Malformed(_))));
+        assert!(err.to_string().starts_with("persisted state was malformed: "));
+        let restore_err = err.source().unwrap();
+        assert!(restore_err.is::<RestoreError>());
+        assert!(restore_err.source().unwrap().is::<serde_json::Error>());
+
+        let err = Error::from(social_meta::Error::Missing("og:title"));
+        assert_eq!(err.to_string(), "social metadata is missing `og:title`");
+        assert!(err.source().unwrap().source().is_none());
+    }
+
+    #[test]
+    fn folds_wrappers_into_props_hashes() {
+        use super::on;
+
+        let with_class = |cls: &'static str| p(("x", PhantomData::<()>))
+            .with_should_update(&(), |old, new| old != new)
+            .with_attributes([("class", Str(cls))]);
+        let old_props_hash = with_class("a").props_hash().unwrap();
+        assert!(!with_class("a").should_update(old_props_hash));
+        assert!(with_class("b").should_update(old_props_hash));
+
+        let with_listener = |event_type: &'static str| p(("x", PhantomData::<()>))
+            .with_should_update(&(), |old, new| old != new)
+            .with_listeners(on(event_type, |_| ()));
+        let old_props_hash = with_listener("click").props_hash().unwrap();
+        assert!(!with_listener("click").should_update(old_props_hash));
+        assert!(with_listener("input").should_update(old_props_hash));
+
+        assert_eq!(div(PhantomData::<()>).with_attributes([("id", Str("a"))]).props_hash(), None);
+    }
+
+    #[test]
+    fn rewrites_cached_regions_whose_output_changed() {
+        use super::render_cache::RenderCache;
+        use super::uncontrolled;
+
+        let greeting = |user: &'static str| div((uncontrolled(p((user, PhantomData::<()>))),));
+        let mut cache = RenderCache::new();
+        cache.render("greeting", &greeting("Ada"));
+        assert_eq!(cache.render("greeting", &greeting("Grace")), &b"<div><p>Grace</p></div>"[..]);
+        assert_eq!(cache.reused("greeting"), 0);
+
+        let styled = |cls: &'static str| p(("x", PhantomData::<()>))
+            .with_should_update(&(), |old, new| old != new)
+            .with_attributes([("class", Str(cls))]);
+        cache.render("styled", &styled("a"));
+        assert_eq!(cache.render("styled", &styled("b")), &b"<p class=\"b\">x</p>"[..]);
+        assert_eq!(cache.reused("styled"), 0);
+        cache.render("styled", &styled("b"));
+        assert_eq!(cache.reused("styled"), 1);
+    }
+
+    #[test]
+    fn links_to_routes_whose_redirects_loop() {
+        use router::{link, nav_link, Route};
+
+        routes! {
+            #[derive(Debug, Clone, PartialEq)]
+            enum Loop {
+                Ping => "/ping" => Loop::Pong,
+                Pong => "/pong" => Loop::Ping,
+            }
+        }
+
+        impl From<Loop> for String {
+            fn from(route: Loop) -> String { route.path() }
+        }
+
+        let ping = link::<String, _, _>(Loop::Ping, "Ping");
+        assert_eq!(ping.to_string(), "<a href=\"/ping\" data-domafic-link=\"\">Ping</a>");
+        let pong = nav_link::<String, _, _>(Loop::Pong, &Loop::Ping, "Pong");
+        assert!(pong.to_string().starts_with("<a href=\"/pong\""));
+    }
 }
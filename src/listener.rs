@@ -26,6 +26,84 @@ pub struct Event<'a> {
     pub ctrl_key: bool,
     /// Whether or not the "meta" key was pressed at the time of the event
     pub meta_key: bool,
+    /// JSON of the `detail` of a `CustomEvent`, if it has one which can be serialized
+    pub detail: Option<&'a str>,
+}
+
+impl<'a> Event<'a> {
+    /// Copies the strings borrowed by this `Event`, producing an `OwnedEvent` which can be
+    /// stored in application state or handled after the listener has returned.
+    #[cfg(any(feature = "use_std", test))]
+    pub fn to_owned(&self) -> OwnedEvent {
+        OwnedEvent {
+            type_str: self.type_str.map(String::from),
+            target_value: self.target_value.map(String::from),
+            client_x: self.client_x,
+            client_y: self.client_y,
+            offset_x: self.offset_x,
+            offset_y: self.offset_y,
+            which_keycode: self.which_keycode,
+            shift_key: self.shift_key,
+            alt_key: self.alt_key,
+            ctrl_key: self.ctrl_key,
+            meta_key: self.meta_key,
+            detail: self.detail.map(String::from),
+        }
+    }
+}
+
+/// An `Event` whose string data is owned rather than borrowed from the browser.
+///
+/// `Event`s only live for the duration of a listener call. Use `Event::to_owned` or the
+/// `on_owned` listener to keep event data around for later.
+#[cfg(any(feature = "use_std", test))]
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct OwnedEvent {
+    /// Type of event
+    pub type_str: Option<String>,
+    /// Value of the node from which the event originated
+    pub target_value: Option<String>,
+    /// Horizontal component at which the event occurred relative to the client area
+    pub client_x: i32,
+    /// Vertical component at which the event occurred relative to the client area
+    pub client_y: i32,
+    /// Horizontal component at which the event occurred relative to the target node
+    pub offset_x: i32,
+    /// Vertical component at which the event occurred relative to the target node
+    pub offset_y: i32,
+    /// Keycode of the keyboard key or mouse button that caused the event
+    pub which_keycode: i32,
+    /// Whether or not the "shift" key was pressed at the time of the event
+    pub shift_key: bool,
+    /// Whether or not the "alt" key was pressed at the time of the event
+    pub alt_key: bool,
+    /// Whether or not the "ctrl" key was pressed at the time of the event
+    pub ctrl_key: bool,
+    /// Whether or not the "meta" key was pressed at the time of the event
+    pub meta_key: bool,
+    /// JSON of the `detail` of a `CustomEvent`, if it has one which can be serialized
+    pub detail: Option<String>,
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl OwnedEvent {
+    /// Borrows the `OwnedEvent` as an `Event`
+    pub fn as_event(&self) -> Event<'_> {
+        Event {
+            type_str: self.type_str.as_deref(),
+            target_value: self.target_value.as_deref(),
+            client_x: self.client_x,
+            client_y: self.client_y,
+            offset_x: self.offset_x,
+            offset_y: self.offset_y,
+            which_keycode: self.which_keycode,
+            shift_key: self.shift_key,
+            alt_key: self.alt_key,
+            ctrl_key: self.ctrl_key,
+            meta_key: self.meta_key,
+            detail: self.detail.as_deref(),
+        }
+    }
 }
 
 /// `Listener`s listen to events and convert them into a message
@@ -64,3 +142,98 @@ pub fn on<M, F: Fn(Event) -> M>(event_type: &'static str, f: F) -> FnListener<M,
 {
     FnListener { event_type_handled: event_type, f: f }
 }
+
+/// A listener that consists of an event type and a function from an event's `detail` to message
+pub struct CustomListener<M, F: Fn(Option<&str>) -> M> {
+    event_type_handled: &'static str,
+    f: F,
+}
+
+impl<M, F: Fn(Option<&str>) -> M> Listeners<M> for CustomListener<M, F> {
+    fn process_all<'a, P: ListenerProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M, F: Fn(Option<&str>) -> M> Listener<M> for CustomListener<M, F> {
+    fn event_type_handled(&self) -> &'static str {
+        self.event_type_handled
+    }
+    fn handle_event(&self, event: Event) -> M {
+        (self.f)(event.detail)
+    }
+}
+
+/// Create a `CustomListener` that handles `CustomEvent`s named `event_type`, such as those
+/// dispatched by web components and other scripts on the page, using function `f`.
+///
+/// `f` receives the JSON of the event's `detail`, or `None` if the event has no `detail` or it
+/// can't be serialized. Any event name can be listened to, including names containing dashes or
+/// colons.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::listener::on_custom;
+///
+/// enum Msg {
+///     ItemAdded(String),
+///     Cleared,
+/// }
+///
+/// let _listener = on_custom("cart:item-added", |detail| match detail {
+///     Some(json) => Msg::ItemAdded(json.to_string()),
+///     None => Msg::Cleared,
+/// });
+/// ```
+pub fn on_custom<M, F: Fn(Option<&str>) -> M>(event_type: &'static str, f: F) -> CustomListener<M, F>
+{
+    CustomListener { event_type_handled: event_type, f }
+}
+
+/// A listener that consists of an event type and a function from `OwnedEvent` to message
+#[cfg(any(feature = "use_std", test))]
+pub struct OwnedFnListener<M, F: Fn(OwnedEvent) -> M> {
+    event_type_handled: &'static str,
+    f: F,
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl<M, F: Fn(OwnedEvent) -> M> Listeners<M> for OwnedFnListener<M, F> {
+    fn process_all<'a, P: ListenerProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl<M, F: Fn(OwnedEvent) -> M> Listener<M> for OwnedFnListener<M, F> {
+    fn event_type_handled(&self) -> &'static str {
+        self.event_type_handled
+    }
+    fn handle_event(&self, event: Event) -> M {
+        (self.f)(event.to_owned())
+    }
+}
+
+/// Create an `OwnedFnListener` that handles events of type `event_type` using function `f`.
+///
+/// Unlike `on`, `f` receives an `OwnedEvent`, so the event data can be moved into the
+/// resulting message.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::OwnedEvent;
+/// use domafic::listener::on_owned;
+///
+/// enum Msg {
+///     Input(OwnedEvent),
+/// }
+///
+/// let _listener = on_owned("input", Msg::Input);
+/// ```
+#[cfg(any(feature = "use_std", test))]
+pub fn on_owned<M, F: Fn(OwnedEvent) -> M>(event_type: &'static str, f: F) -> OwnedFnListener<M, F>
+{
+    OwnedFnListener { event_type_handled: event_type, f }
+}
@@ -0,0 +1,570 @@
+//! Enable with the `live` feature. This mode is experimental, and its protocol may change.
+//!
+//! In a live view, the application's update and render functions run on the server. The page
+//! only runs `CLIENT_SCRIPT`, a small script which forwards events over a WebSocket and applies
+//! the patches it receives in response, so the application's code is never shipped to the
+//! browser.
+//!
+//! A `LiveSession` holds the state of one connected page. `mount` returns the patches which
+//! build the initial page, and `handle_event` passes an event forwarded by the client to the
+//! listeners of the targeted node, updates the state with the resulting messages, and returns
+//! the patches from the previous render to the new one. Patches are encoded for the client with
+//! `encode`. The session is independent of any WebSocket library: the server passes the text
+//! of each received message to the session, and sends back the encoded patches.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::KeyIter;
+//! use domafic::live::{encode, LiveSession, Patch};
+//! use domafic::listener::on;
+//! use domafic::tags::button;
+//!
+//! let render = |count: &u32| button((on("click", |_| ()), count.to_string()));
+//! let update = |count: &mut u32, _: (), _: KeyIter| *count += 1;
+//!
+//! let mut session = LiveSession::new(0, update, render);
+//! let initial = encode(&session.mount());
+//! assert!(initial.contains(r#""tag":"button""#));
+//!
+//! // The client reports a click on the button, the first node in the page
+//! let patches = session.handle_event(r#"{"path":[0],"event":"click"}"#).unwrap();
+//! assert_eq!(patches, vec![Patch::SetText { path: vec![0, 0], text: "1".to_string() }]);
+//! ```
+//!
+//! On the page, the script connects to the server and renders into the given element:
+//!
+//! ```html
+//! <div id="app"></div>
+//! <script>/* CLIENT_SCRIPT */</script>
+//! <script>domaficLive("wss://example.com/live", "#app");</script>
+//! ```
+//!
+//! Patches are found by comparing nodes in the same position, without matching keys, so nodes
+//! which move are replaced rather than moved. Listeners are identified by their node's position
+//! and event type, and all listeners of the node for the event's type are called.
+
+extern crate serde_json;
+
+use self::serde_json::Value;
+
+use {DomNode, DomNodes, DomValue, Event, Listener, Listeners};
+use keys::{KeyIter, Keys};
+use processors::{DomNodeProcessor, ListenerProcessor};
+
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A node of a rendered page, as sent to live view clients
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum LiveNode {
+    /// An element
+    Element {
+        /// The element's tag
+        tag: &'static str,
+        /// The element's attributes
+        attributes: Vec<(&'static str, String)>,
+        /// Types of the events the element's listeners handle, which the client forwards
+        events: Vec<&'static str>,
+        /// The element's children
+        children: Vec<LiveNode>,
+    },
+    /// A text node
+    Text(String),
+    /// A comment. Doctypes are sent as empty comments.
+    Comment(String),
+}
+
+impl LiveNode {
+    /// Describes `nodes` and their descendants
+    pub fn of<M, T: DomNodes<M>>(nodes: &T) -> Vec<LiveNode> {
+        let mut acc = Vec::new();
+        nodes.process_all::<LiveNodeBuilder>(&mut acc).unwrap();
+        acc
+    }
+
+    fn to_value(&self) -> Value {
+        let mut object = serde_json::Map::new();
+        match *self {
+            LiveNode::Element { tag, ref attributes, ref events, ref children } => {
+                object.insert("tag".to_string(), Value::from(tag));
+                object.insert("attributes".to_string(), Value::Array(attributes.iter()
+                    .map(|&(key, ref value)| Value::Array(vec![Value::from(key), Value::from(value.as_str())]))
+                    .collect()));
+                object.insert("events".to_string(), Value::from(events.clone()));
+                object.insert("children".to_string(),
+                    Value::Array(children.iter().map(LiveNode::to_value).collect()));
+            },
+            LiveNode::Text(ref text) => { object.insert("text".to_string(), Value::from(text.as_str())); },
+            LiveNode::Comment(ref text) => { object.insert("comment".to_string(), Value::from(text.as_str())); },
+        }
+        Value::Object(object)
+    }
+}
+
+/// A change to a live view client's page.
+///
+/// Paths are the indices of a node and its ancestors among their siblings, counted from the
+/// children of the element the client renders into. The empty path refers to that element.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum Patch {
+    /// Replaces the node at `path` with `node`
+    Replace {
+        /// Position of the node to replace
+        path: Vec<usize>,
+        /// The new node
+        node: LiveNode,
+    },
+    /// Replaces the text of the text or comment node at `path`
+    SetText {
+        /// Position of the text or comment node
+        path: Vec<usize>,
+        /// The new text
+        text: String,
+    },
+    /// Sets an attribute of the element at `path`
+    SetAttribute {
+        /// Position of the element
+        path: Vec<usize>,
+        /// The attribute's key
+        key: &'static str,
+        /// The attribute's new value
+        value: String,
+    },
+    /// Removes an attribute of the element at `path`
+    RemoveAttribute {
+        /// Position of the element
+        path: Vec<usize>,
+        /// The attribute's key
+        key: &'static str,
+    },
+    /// Replaces the types of events forwarded from the element at `path`
+    SetEvents {
+        /// Position of the element
+        path: Vec<usize>,
+        /// The types of events to forward
+        events: Vec<&'static str>,
+    },
+    /// Appends `node` to the children of the element at `path`
+    Append {
+        /// Position of the parent element
+        path: Vec<usize>,
+        /// The new child
+        node: LiveNode,
+    },
+    /// Removes all but the first `len` children of the element at `path`
+    Truncate {
+        /// Position of the parent element
+        path: Vec<usize>,
+        /// The number of children to keep
+        len: usize,
+    },
+}
+
+impl Patch {
+    fn to_value(&self) -> Value {
+        let mut object = serde_json::Map::new();
+        let (op, path) = match *self {
+            Patch::Replace { ref path, ref node } => {
+                object.insert("node".to_string(), node.to_value());
+                ("replace", path)
+            },
+            Patch::SetText { ref path, ref text } => {
+                object.insert("text".to_string(), Value::from(text.as_str()));
+                ("text", path)
+            },
+            Patch::SetAttribute { ref path, key, ref value } => {
+                object.insert("key".to_string(), Value::from(key));
+                object.insert("value".to_string(), Value::from(value.as_str()));
+                ("attr", path)
+            },
+            Patch::RemoveAttribute { ref path, key } => {
+                object.insert("key".to_string(), Value::from(key));
+                ("remove_attr", path)
+            },
+            Patch::SetEvents { ref path, ref events } => {
+                object.insert("events".to_string(), Value::from(events.clone()));
+                ("events", path)
+            },
+            Patch::Append { ref path, ref node } => {
+                object.insert("node".to_string(), node.to_value());
+                ("append", path)
+            },
+            Patch::Truncate { ref path, len } => {
+                object.insert("len".to_string(), Value::from(len));
+                ("truncate", path)
+            },
+        };
+        object.insert("op".to_string(), Value::from(op));
+        object.insert("path".to_string(), Value::from(path.clone()));
+        Value::Object(object)
+    }
+}
+
+/// Encodes `patches` as the JSON message sent to a live view client
+pub fn encode(patches: &[Patch]) -> String {
+    Value::Array(patches.iter().map(Patch::to_value).collect()).to_string()
+}
+
+/// Lists the patches which change the page `old` into the page `new`
+pub fn diff(old: &[LiveNode], new: &[LiveNode]) -> Vec<Patch> {
+    let mut patches = Vec::new();
+    diff_children(old, new, &mut Vec::new(), &mut patches);
+    patches
+}
+
+fn diff_children(old: &[LiveNode], new: &[LiveNode], path: &mut Vec<usize>, patches: &mut Vec<Patch>) {
+    for (i, (old, new)) in old.iter().zip(new).enumerate() {
+        path.push(i);
+        diff_node(old, new, path, patches);
+        path.pop();
+    }
+    if new.len() < old.len() {
+        patches.push(Patch::Truncate { path: path.clone(), len: new.len() });
+    }
+    for node in new.iter().skip(old.len()) {
+        patches.push(Patch::Append { path: path.clone(), node: node.clone() });
+    }
+}
+
+fn diff_node(old: &LiveNode, new: &LiveNode, path: &mut Vec<usize>, patches: &mut Vec<Patch>) {
+    match (old, new) {
+        (&LiveNode::Text(ref old), &LiveNode::Text(ref new)) |
+        (&LiveNode::Comment(ref old), &LiveNode::Comment(ref new)) => {
+            if old != new {
+                patches.push(Patch::SetText { path: path.clone(), text: new.clone() });
+            }
+        },
+        (&LiveNode::Element { tag: old_tag, attributes: ref old_attributes, events: ref old_events,
+                              children: ref old_children },
+         &LiveNode::Element { tag, ref attributes, ref events, ref children })
+            if old_tag == tag =>
+        {
+            for &(key, ref value) in attributes {
+                if !old_attributes.iter().any(|old| old.0 == key && old.1 == *value) {
+                    patches.push(Patch::SetAttribute { path: path.clone(), key, value: value.clone() });
+                }
+            }
+            for &(key, _) in old_attributes {
+                if !attributes.iter().any(|attr| attr.0 == key) {
+                    patches.push(Patch::RemoveAttribute { path: path.clone(), key });
+                }
+            }
+            if old_events != events {
+                patches.push(Patch::SetEvents { path: path.clone(), events: events.clone() });
+            }
+            diff_children(old_children, children, path, patches);
+        },
+        _ => patches.push(Patch::Replace { path: path.clone(), node: new.clone() }),
+    }
+}
+
+/// Error returned when an event forwarded by a live view client can't be handled
+#[derive(Debug)]
+pub enum LiveError {
+    /// The client's message wasn't valid JSON
+    Malformed(serde_json::Error),
+    /// The client's message was missing the event's path or type
+    MissingField(&'static str),
+}
+
+impl fmt::Display for LiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LiveError::Malformed(ref err) => write!(f, "live view event was malformed: {}", err),
+            LiveError::MissingField(field) => write!(f, "live view event is missing `{}`", field),
+        }
+    }
+}
+
+impl error::Error for LiveError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            LiveError::Malformed(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The state of one page connected to a live view
+pub struct LiveSession<S, M, U, R> {
+    state: S,
+    update: U,
+    render: R,
+    rendered: Vec<LiveNode>,
+    _marker: PhantomData<M>,
+}
+
+impl<S, M, D, U, R> LiveSession<S, M, U, R>
+    where D: DomNode<M>, U: Fn(&mut S, M, KeyIter), R: Fn(&S) -> D
+{
+    /// Creates a session for a page starting from `initial_state`
+    pub fn new(initial_state: S, update: U, render: R) -> LiveSession<S, M, U, R> {
+        LiveSession { state: initial_state, update, render, rendered: Vec::new(), _marker: PhantomData }
+    }
+
+    /// The current state
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Renders the page, returning the patches which replace the client's page with it
+    pub fn mount(&mut self) -> Vec<Patch> {
+        self.rendered = LiveNode::of(&(self.render)(&self.state));
+        let mut patches = vec![Patch::Truncate { path: Vec::new(), len: 0 }];
+        patches.extend(self.rendered.iter().map(|node| Patch::Append { path: Vec::new(), node: node.clone() }));
+        patches
+    }
+
+    /// Handles an event forwarded by the client, returning the patches to the page.
+    ///
+    /// Events which no listener handles, for example because the page changed before the event
+    /// arrived, are ignored.
+    pub fn handle_event(&mut self, client_message: &str) -> Result<Vec<Patch>, LiveError> {
+        let message: Value = serde_json::from_str(client_message).map_err(LiveError::Malformed)?;
+        let path = message.get("path").and_then(Value::as_array)
+            .and_then(|path| path.iter().map(|index| index.as_u64().map(|index| index as usize)).collect())
+            .ok_or(LiveError::MissingField("path"))?;
+        let event_type = message.get("event").and_then(Value::as_str)
+            .ok_or(LiveError::MissingField("event"))?;
+        let text = |field| message.get(field).and_then(Value::as_str);
+        let int = |field| message.get(field).and_then(Value::as_i64).unwrap_or(0) as i32;
+        let flag = |field| message.get(field).and_then(Value::as_bool).unwrap_or(false);
+        let event = Event {
+            type_str: Some(event_type),
+            target_value: text("value"),
+            client_x: int("client_x"),
+            client_y: int("client_y"),
+            offset_x: int("offset_x"),
+            offset_y: int("offset_y"),
+            which_keycode: int("which"),
+            shift_key: flag("shift_key"),
+            alt_key: flag("alt_key"),
+            ctrl_key: flag("ctrl_key"),
+            meta_key: flag("meta_key"),
+            detail: text("detail"),
+        };
+
+        let messages = {
+            let rendered = (self.render)(&self.state);
+            let mut acc = EventTargetAcc { path, depth: 0, index: 0, keys: Keys::new(), event, messages: Vec::new() };
+            rendered.process_all::<EventTargetFinder<'_>>(&mut acc).unwrap();
+            acc.messages
+        };
+        for (message, keys) in messages {
+            (self.update)(&mut self.state, message, keys.into_iter());
+        }
+        Ok(self.rerender())
+    }
+
+    /// Updates the state with a message from the server, such as the result of a query,
+    /// returning the patches to the page
+    pub fn dispatch(&mut self, message: M, keys: KeyIter) -> Vec<Patch> {
+        (self.update)(&mut self.state, message, keys);
+        self.rerender()
+    }
+
+    fn rerender(&mut self) -> Vec<Patch> {
+        let rendered = LiveNode::of(&(self.render)(&self.state));
+        let patches = diff(&self.rendered, &rendered);
+        self.rendered = rendered;
+        patches
+    }
+}
+
+/// Script run by live view pages, defining `domaficLive(url, selector)`.
+///
+/// `domaficLive` connects to the WebSocket at `url`, applies the patches it receives to the
+/// children of the element matching `selector`, and forwards events handled by the page's
+/// listeners. Forwarded `submit` events are prevented, so that forms don't navigate.
+pub const CLIENT_SCRIPT: &str = r#"(function() {
+window.domaficLive = function(url, selector) {
+    var root = document.querySelector(selector);
+    var socket = new WebSocket(url);
+    var find = function(path) {
+        var node = root;
+        for (var i = 0; i < path.length; i++) { node = node.childNodes[path[i]]; }
+        return node;
+    };
+    var pathOf = function(node) {
+        var path = [];
+        while (node && node !== root) {
+            path.unshift(Array.prototype.indexOf.call(node.parentNode.childNodes, node));
+            node = node.parentNode;
+        }
+        return node === root ? path : null;
+    };
+    var forward = function(event) {
+        var path = pathOf(event.currentTarget);
+        if (path === null || socket.readyState !== 1) { return; }
+        if (event.type === 'submit') { event.preventDefault(); }
+        var target = event.target || {};
+        var detail = null;
+        if (typeof CustomEvent !== 'undefined' && event instanceof CustomEvent && event.detail != null) {
+            try { detail = JSON.stringify(event.detail); } catch (e) {}
+        }
+        socket.send(JSON.stringify({
+            path: path, event: event.type,
+            value: target.value === undefined ? null : String(target.value),
+            client_x: Math.floor(event.clientX || 0), client_y: Math.floor(event.clientY || 0),
+            offset_x: Math.floor(event.offsetX || 0), offset_y: Math.floor(event.offsetY || 0),
+            which: event.which || event.keyCode || 0,
+            shift_key: !!event.shiftKey, alt_key: !!event.altKey,
+            ctrl_key: !!event.ctrlKey, meta_key: !!event.metaKey,
+            detail: detail
+        }));
+    };
+    var setEvents = function(elem, events) {
+        (elem.__domaficEvents || []).forEach(function(type) { elem.removeEventListener(type, forward); });
+        elem.__domaficEvents = events;
+        events.forEach(function(type) { elem.addEventListener(type, forward); });
+    };
+    var setAttribute = function(elem, key, value) {
+        elem.setAttribute(key, value);
+        if (key === 'value') { elem.value = value; }
+        if (key === 'checked') { elem.checked = value !== 'false'; }
+    };
+    var build = function(node) {
+        if ('text' in node) { return document.createTextNode(node.text); }
+        if ('comment' in node) { return document.createComment(node.comment); }
+        var elem = document.createElement(node.tag);
+        node.attributes.forEach(function(attr) { setAttribute(elem, attr[0], attr[1]); });
+        setEvents(elem, node.events);
+        node.children.forEach(function(child) { elem.appendChild(build(child)); });
+        return elem;
+    };
+    var apply = function(patch) {
+        var node = find(patch.path);
+        if (patch.op === 'replace') { node.parentNode.replaceChild(build(patch.node), node); }
+        else if (patch.op === 'text') { node.nodeValue = patch.text; }
+        else if (patch.op === 'attr') { setAttribute(node, patch.key, patch.value); }
+        else if (patch.op === 'remove_attr') { node.removeAttribute(patch.key); }
+        else if (patch.op === 'events') { setEvents(node, patch.events); }
+        else if (patch.op === 'append') { node.appendChild(build(patch.node)); }
+        else if (patch.op === 'truncate') {
+            while (node.childNodes.length > patch.len) { node.removeChild(node.lastChild); }
+        }
+    };
+    socket.onmessage = function(message) { JSON.parse(message.data).forEach(apply); };
+    return socket;
+};
+})();"#;
+
+/// Processor building `LiveNode`s
+struct LiveNodeBuilder;
+impl<'a, M> DomNodeProcessor<'a, M> for LiveNodeBuilder {
+    type Acc = Vec<LiveNode>;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(acc: &mut Vec<LiveNode>, node: &T) -> Result<(), ()> {
+            acc.push(match node.value() {
+                DomValue::Element { tag } => {
+                    let mut events = Vec::new();
+                    node.listeners().process_all::<EventTypeCollector>(&mut events)?;
+                    let mut children = Vec::new();
+                    node.children().process_all::<LiveNodeBuilder>(&mut children)?;
+                    LiveNode::Element {
+                        tag,
+                        attributes: node.merged_attributes()
+                            .map(|attr| (attr.0, attr.1.as_str().to_string()))
+                            .collect(),
+                        events,
+                        children,
+                    }
+                },
+                DomValue::Comment(text) => LiveNode::Comment(text.to_string()),
+                DomValue::Doctype(_) => LiveNode::Comment(String::new()),
+                text => LiveNode::Text(text.into_text().unwrap()),
+            });
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
+
+/// Listener processor collecting the distinct event types handled by a node's listeners
+struct EventTypeCollector;
+impl<'a, M> ListenerProcessor<'a, M> for EventTypeCollector {
+    type Acc = Vec<&'static str>;
+    type Error = ();
+
+    fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn add_listener<M, L: Listener<M>>(events: &mut Vec<&'static str>, listener: &L) -> Result<(), ()> {
+            let event_type = listener.event_type_handled();
+            if !events.contains(&event_type) {
+                events.push(event_type);
+            }
+            Ok(())
+        }
+        add_listener::<M, L>
+    }
+}
+
+struct EventTargetAcc<'e, M> {
+    // Position of the targeted node
+    path: Vec<usize>,
+    // Number of the path's indices matched by the current node's ancestors
+    depth: usize,
+    // Index of the current node among its siblings
+    index: usize,
+    // Keys of the current node's ancestors
+    keys: Keys,
+    event: Event<'e>,
+    messages: Vec<(M, Keys)>,
+}
+
+/// Processor finding the node at a path and calling its listeners for an event
+struct EventTargetFinder<'e>(PhantomData<Event<'e>>);
+impl<'a, 'e, M> DomNodeProcessor<'a, M> for EventTargetFinder<'e> {
+    type Acc = EventTargetAcc<'e, M>;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<'e, M, T: DomNode<M>>(acc: &mut EventTargetAcc<'e, M>, node: &T) -> Result<(), ()> {
+            let index = acc.index;
+            acc.index += 1;
+            if acc.path.get(acc.depth) != Some(&index) {
+                return Ok(());
+            }
+            let keys = match node.key() {
+                Some(key) => acc.keys.push(key),
+                None => acc.keys,
+            };
+            if acc.depth + 1 == acc.path.len() {
+                let mut listeners = (acc.event, keys, Vec::new());
+                node.listeners().process_all::<ListenerCaller<'e>>(&mut listeners)?;
+                acc.messages.extend(listeners.2.into_iter().map(|message| (message, keys)));
+                return Ok(());
+            }
+            let (parent_keys, parent_index) = (acc.keys, acc.index);
+            acc.keys = keys;
+            acc.depth += 1;
+            acc.index = 0;
+            node.children().process_all::<EventTargetFinder<'e>>(acc)?;
+            acc.depth -= 1;
+            acc.index = parent_index;
+            acc.keys = parent_keys;
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
+
+/// Listener processor calling the listeners for an event's type
+struct ListenerCaller<'e>(PhantomData<Event<'e>>);
+impl<'a, 'e, M> ListenerProcessor<'a, M> for ListenerCaller<'e> {
+    type Acc = (Event<'e>, Keys, Vec<M>);
+    type Error = ();
+
+    fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
+        fn call<'e, M, L: Listener<M>>(acc: &mut (Event<'e>, Keys, Vec<M>), listener: &L) -> Result<(), ()> {
+            if acc.0.type_str == Some(listener.event_type_handled()) {
+                acc.2.push(listener.handle_event(acc.0));
+            }
+            Ok(())
+        }
+        call::<M, L>
+    }
+}
@@ -0,0 +1,118 @@
+//! Enable with the `logging` feature.
+//!
+//! When compiled for emscripten, log records are written to the browser console using the
+//! console method matching their level (`console.error`, `console.warn`, etc.). Otherwise,
+//! records are written to stderr.
+//!
+//! Example:
+//!
+//! ```rust
+//! #[macro_use]
+//! extern crate log;
+//! extern crate domafic;
+//!
+//! use log::LevelFilter;
+//!
+//! fn main() {
+//!     domafic::logging::init(LevelFilter::Info).unwrap();
+//!     info!("Logging to the console");
+//! }
+//! ```
+
+extern crate log;
+use self::log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use std::panic;
+
+/// A `log::Log` implementation which writes to the browser console on emscripten and to
+/// stderr otherwise.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ConsoleLogger {
+    level: LevelFilter,
+}
+
+impl ConsoleLogger {
+    /// Creates a `ConsoleLogger` which writes records at or above `level`
+    pub fn new(level: LevelFilter) -> ConsoleLogger {
+        ConsoleLogger { level }
+    }
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            write_console(record.level(), &format!("[{}] {}", record.target(), record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs a `ConsoleLogger` as the global logger and installs the panic hook from
+/// `install_panic_hook`.
+///
+/// Returns an error if a global logger has already been set.
+pub fn init(level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_boxed_logger(Box::new(ConsoleLogger::new(level)))?;
+    log::set_max_level(level);
+    install_panic_hook();
+    Ok(())
+}
+
+/// Replaces the current panic hook with one that writes the panic message and its location
+/// to the console as an error.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = payload.downcast_ref::<&str>().cloned()
+            .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+            .unwrap_or("Box<Any>");
+
+        let text = match info.location() {
+            Some(location) => format!(
+                "panicked at '{}', {}:{}:{}",
+                message, location.file(), location.line(), location.column()),
+            None => format!("panicked at '{}'", message),
+        };
+        write_console(Level::Error, &text);
+    }));
+}
+
+#[cfg(target_os = "emscripten")]
+fn write_console(level: Level, text: &str) {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int};
+
+    extern "C" {
+        fn emscripten_asm_const_int(s: *const c_char, ...) -> c_int;
+    }
+
+    const JS: &'static [u8] = b"\
+        var text = UTF8ToString($1);\
+        switch ($0) {\
+            case 1: console.error(text); break;\
+            case 2: console.warn(text); break;\
+            case 3: console.info(text); break;\
+            default: console.log(text); break;\
+        }\
+    \0";
+
+    // Interior nul bytes can't be passed through to JS, so replace them
+    let text_cstring = CString::new(text.replace('\0', "\\0")).unwrap();
+    unsafe {
+        emscripten_asm_const_int(
+            &JS[0] as *const _ as *const c_char,
+            level as c_int,
+            text_cstring.as_ptr() as c_int,
+        );
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+fn write_console(level: Level, text: &str) {
+    eprintln!("{:<5} {}", level, text);
+}
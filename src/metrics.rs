@@ -0,0 +1,108 @@
+//! Size and depth of rendered trees, for keeping pages within a budget.
+//!
+//! Large pages are slow to write, send, parse and update, and they usually grow a few nodes at
+//! a time, so nothing flags the render which made one too large. `measure` counts the nodes,
+//! attributes, listeners and text of a tree, along with how deeply its elements are nested,
+//! and `Metrics::exceeds` compares the counts with a budget, such as in a test which fails when
+//! a page grows past 5000 nodes.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::listener::on;
+//! use domafic::metrics::{measure, Metrics};
+//! use domafic::tags::{button, div, p};
+//!
+//! let page = div((p("Hello"), button((on("click", |_| ()), "Go"))));
+//!
+//! let metrics = measure(&page);
+//! assert_eq!(metrics, Metrics { nodes: 5, max_depth: 3, attributes: 0, listeners: 1, text_bytes: 7 });
+//!
+//! let budget = Metrics { nodes: 4, ..Metrics::unlimited() };
+//! assert_eq!(metrics.exceeds(&budget), vec!["nodes"]);
+//! ```
+
+use {DomNode, DomNodes};
+use devtools::ListenerCounter;
+use processors::{DomNodeProcessor, Listeners};
+
+/// Counts describing the size of a tree
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Metrics {
+    /// Number of nodes, including text nodes and comments
+    pub nodes: usize,
+    /// Number of nodes from the root of the tree to its most deeply nested node, which is `1`
+    /// for a tree of one node and `0` for an empty tree
+    pub max_depth: usize,
+    /// Number of attributes, counting those merged into one, such as repeated `class`
+    /// attributes, once
+    pub attributes: usize,
+    /// Number of listeners
+    pub listeners: usize,
+    /// Length in bytes of the text of text nodes, before it's escaped
+    pub text_bytes: usize,
+}
+
+impl Metrics {
+    /// A budget which no tree exceeds, to be used as the base of a budget which limits only
+    /// some counts
+    pub fn unlimited() -> Metrics {
+        Metrics {
+            nodes: usize::MAX,
+            max_depth: usize::MAX,
+            attributes: usize::MAX,
+            listeners: usize::MAX,
+            text_bytes: usize::MAX,
+        }
+    }
+
+    /// Names the counts which are higher than those of `budget`, in the order of the fields
+    pub fn exceeds(&self, budget: &Metrics) -> Vec<&'static str> {
+        let counts = [
+            ("nodes", self.nodes, budget.nodes),
+            ("max_depth", self.max_depth, budget.max_depth),
+            ("attributes", self.attributes, budget.attributes),
+            ("listeners", self.listeners, budget.listeners),
+            ("text_bytes", self.text_bytes, budget.text_bytes),
+        ];
+        counts.iter().filter(|count| count.1 > count.2).map(|count| count.0).collect()
+    }
+}
+
+/// Measures `nodes` and their descendants
+pub fn measure<M, T: DomNodes<M>>(nodes: &T) -> Metrics {
+    let mut acc = MeasurerAcc { metrics: Metrics::default(), depth: 0 };
+    nodes.process_all::<Measurer>(&mut acc).unwrap();
+    acc.metrics
+}
+
+struct MeasurerAcc {
+    metrics: Metrics,
+    // Depth of the parent of the current node
+    depth: usize,
+}
+
+/// Processor adding each node to the counts
+struct Measurer;
+impl<'a, M> DomNodeProcessor<'a, M> for Measurer {
+    type Acc = MeasurerAcc;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(acc: &mut MeasurerAcc, node: &T) -> Result<(), ()> {
+            let metrics = &mut acc.metrics;
+            metrics.nodes += 1;
+            metrics.max_depth = metrics.max_depth.max(acc.depth + 1);
+            metrics.attributes += node.merged_attributes().count();
+            metrics.text_bytes += node.value().text().map_or(0, str::len);
+            node.listeners().process_all::<ListenerCounter>(&mut metrics.listeners)?;
+
+            acc.depth += 1;
+            node.children().process_all::<Measurer>(acc)?;
+            acc.depth -= 1;
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
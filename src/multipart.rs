@@ -0,0 +1,136 @@
+//! Encoding forms with file uploads as `multipart/form-data`.
+//!
+//! A `Multipart` holds the fields of a form, in order, each either a text value or a file with
+//! its name and content type. `Multipart::to_bytes` encodes them, separated by a boundary
+//! which doesn't occur in any of the parts, and `Multipart::content_type` gives the matching
+//! `Content-Type` header. When rendering client-side, `JsIo::http_multipart` sends a form as
+//! the body of a request.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::multipart::Multipart;
+//!
+//! let form = Multipart::new()
+//!     .field("caption", "Site visit")
+//!     .file("photo", "visit.png", "image/png", vec![0x89, b'P', b'N', b'G']);
+//!
+//! let boundary = form.boundary();
+//! assert_eq!(form.content_type(), format!("multipart/form-data; boundary={}", boundary));
+//! assert_eq!(form.to_bytes(), [
+//!     format!("--{}\r\n", boundary).as_bytes(),
+//!     b"Content-Disposition: form-data; name=\"caption\"\r\n\r\nSite visit\r\n",
+//!     format!("--{}\r\n", boundary).as_bytes(),
+//!     b"Content-Disposition: form-data; name=\"photo\"; filename=\"visit.png\"\r\n",
+//!     b"Content-Type: image/png\r\n\r\n\x89PNG\r\n",
+//!     format!("--{}--\r\n", boundary).as_bytes(),
+//! ].concat());
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// One field of a `Multipart` form
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Part {
+    /// Name of the field
+    pub name: String,
+    /// Name of the uploaded file, or `None` for a text field
+    pub filename: Option<String>,
+    /// Content type of the uploaded file, such as `image/png`
+    pub content_type: Option<String>,
+    /// Contents of the field
+    pub data: Vec<u8>,
+}
+
+/// A form encoded as `multipart/form-data`
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Multipart {
+    /// Fields of the form, in the order they're encoded
+    pub parts: Vec<Part>,
+}
+
+impl Multipart {
+    /// An empty form
+    pub fn new() -> Multipart {
+        Multipart::default()
+    }
+
+    /// Adds a text field
+    pub fn field<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Multipart {
+        self.parts.push(Part {
+            name: name.into(),
+            filename: None,
+            content_type: None,
+            data: value.into().into_bytes(),
+        });
+        self
+    }
+
+    /// Adds a file named `filename`, of the type `content_type`, with the contents `data`
+    pub fn file<N, F, C>(mut self, name: N, filename: F, content_type: C, data: Vec<u8>) -> Multipart
+        where N: Into<String>, F: Into<String>, C: Into<String>
+    {
+        self.parts.push(Part {
+            name: name.into(),
+            filename: Some(filename.into()),
+            content_type: Some(content_type.into()),
+            data,
+        });
+        self
+    }
+
+    /// The boundary separating the parts.
+    ///
+    /// The boundary is derived from the parts, so it's the same each time the same form is
+    /// encoded, and is extended until no part contains it.
+    pub fn boundary(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        let mut boundary = format!("domafic-{:016x}", hasher.finish());
+        while self.parts.iter().any(|part| contains(&part.data, boundary.as_bytes())) {
+            boundary.push('-');
+        }
+        boundary
+    }
+
+    /// The value of the `Content-Type` header for the encoded form
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary())
+    }
+
+    /// Encodes the form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let boundary = self.boundary();
+        let mut bytes = Vec::new();
+        for part in &self.parts {
+            bytes.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            bytes.extend_from_slice(b"Content-Disposition: form-data; name=\"");
+            bytes.extend_from_slice(escape(&part.name).as_bytes());
+            bytes.push(b'"');
+            if let Some(ref filename) = part.filename {
+                bytes.extend_from_slice(b"; filename=\"");
+                bytes.extend_from_slice(escape(filename).as_bytes());
+                bytes.push(b'"');
+            }
+            bytes.extend_from_slice(b"\r\n");
+            if let Some(ref content_type) = part.content_type {
+                bytes.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+            }
+            bytes.extend_from_slice(b"\r\n");
+            bytes.extend_from_slice(&part.data);
+            bytes.extend_from_slice(b"\r\n");
+        }
+        bytes.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        bytes
+    }
+}
+
+/// Escapes a field or file name as browsers do when submitting forms
+fn escape(name: &str) -> String {
+    name.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
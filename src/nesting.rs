@@ -0,0 +1,232 @@
+//! Checks for nesting of elements which browsers change while parsing HTML.
+//!
+//! When parsing a page, browsers silently "fix" elements nested in ways the HTML standard
+//! forbids: a `div` inside a `p` closes the paragraph, a `tr` directly inside a `table` is
+//! wrapped in a `tbody`, and a `span` inside a `tr` is moved out in front of the table. A page
+//! rendered to HTML with such nesting is parsed into a different tree than the `DomNode` it was
+//! rendered from, which breaks CSS selectors and scripts relying on the tree's structure.
+//!
+//! `check` lists the nodes which would be moved. `Document::validate` includes them, and in
+//! debug builds with the `logging` feature, `Document::write` logs them as warnings.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::nesting::{check, Problem};
+//! use domafic::tags::{div, p, table, td, tr};
+//! use std::marker::PhantomData;
+//!
+//! let page = div((
+//!     p(div("Closes the paragraph")),
+//!     table(tr(td(("Needs a tbody", PhantomData::<()>)))),
+//! ));
+//!
+//! let violations = check(&page);
+//! assert_eq!(violations.len(), 2);
+//! assert_eq!(violations[0].tag, "div");
+//! assert_eq!(violations[0].problem, Problem::ClosesParagraph);
+//! assert_eq!(violations[1].to_string(),
+//!     "`tr` at 0.1.0 inside `table` must be inside one of `thead`, `tbody`, `tfoot`");
+//! ```
+
+use {DomNode, DomNodes, DomValue};
+use processors::DomNodeProcessor;
+use xml_writer::{has_children, VOID_ELEMENTS};
+
+use std::fmt;
+
+/// Elements which close an open `p` element
+pub const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "details", "dialog", "div", "dl", "fieldset",
+    "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header",
+    "hgroup", "hr", "main", "menu", "nav", "ol", "p", "pre", "section", "table", "ul",
+];
+
+/// Elements which stop an open `p` element from being closed by their children
+const PARAGRAPH_SCOPE: &[&str] = &["button", "caption", "object", "table", "td", "th"];
+
+/// Elements which can only appear inside the given parents
+const PARENTS: &[(&str, &[&str])] = &[
+    ("li", &["ul", "ol", "menu"]),
+    ("dt", &["dl", "div"]),
+    ("dd", &["dl", "div"]),
+    ("caption", &["table"]),
+    ("colgroup", &["table"]),
+    ("thead", &["table"]),
+    ("tbody", &["table"]),
+    ("tfoot", &["table"]),
+    ("tr", &["thead", "tbody", "tfoot"]),
+    ("td", &["tr"]),
+    ("th", &["tr"]),
+    ("col", &["colgroup"]),
+    ("optgroup", &["select"]),
+    ("option", &["select", "datalist", "optgroup"]),
+];
+
+/// Elements which can only contain the given elements, and no text other than whitespace
+const CONTENTS: &[(&str, &[&str])] = &[
+    ("table", &["caption", "colgroup", "thead", "tbody", "tfoot", "tr", "script", "template", "style"]),
+    ("thead", &["tr", "script", "template"]),
+    ("tbody", &["tr", "script", "template"]),
+    ("tfoot", &["tr", "script", "template"]),
+    ("tr", &["td", "th", "script", "template"]),
+    ("colgroup", &["col", "template"]),
+    ("select", &["option", "optgroup", "hr", "script", "template"]),
+];
+
+/// Elements which can't contain another element of the same kind
+const UNNESTABLE: &[&str] = &["a", "button", "form"];
+
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// The way in which a node is nested incorrectly
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Problem {
+    /// One of `BLOCK_ELEMENTS` inside a `p`, which closes the paragraph
+    ClosesParagraph,
+    /// An element outside of the parents it must appear in, which are given
+    Misplaced(&'static [&'static str]),
+    /// A node which its parent can't contain, such as text inside a `tr`
+    Disallowed,
+    /// An element inside another of the same kind, such as a `form` inside a `form`, or a
+    /// heading directly inside another heading
+    Nested,
+    /// Children of a void element, such as `img`
+    VoidWithChildren,
+}
+
+/// A node which a browser would move when parsing its HTML
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Violation {
+    /// Position of the node, as the indices of it and its ancestors among their siblings
+    pub path: Vec<usize>,
+    /// The node's tag, or `#text` for a text node
+    pub tag: &'static str,
+    /// The tag of the node's parent, if it has one
+    pub parent: Option<&'static str>,
+    /// The way in which the node is nested incorrectly
+    pub problem: Problem,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|index| index.to_string()).collect();
+        write!(f, "`{}` at {}", self.tag, path.join("."))?;
+        if let Some(parent) = self.parent {
+            write!(f, " inside `{}`", parent)?;
+        }
+        match self.problem {
+            Problem::ClosesParagraph => write!(f, " closes the enclosing `p`"),
+            Problem::Misplaced(parents) => {
+                write!(f, " must be inside one of ")?;
+                for (i, parent) in parents.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "`{}`", parent)?;
+                }
+                Ok(())
+            },
+            Problem::Disallowed => write!(f, " is moved or dropped by the parser"),
+            Problem::Nested => write!(f, " can't be nested inside another `{}`", self.tag),
+            Problem::VoidWithChildren => write!(f, " is a void element and can't have children"),
+        }
+    }
+}
+
+/// Lists the nodes in `nodes` which a browser would move when parsing their HTML, in the order
+/// they appear
+pub fn check<M, T: DomNodes<M>>(nodes: &T) -> Vec<Violation> {
+    let mut acc = NestingCheckerAcc { ancestors: Vec::new(), path: vec![0], violations: Vec::new() };
+    nodes.process_all::<NestingChecker>(&mut acc).unwrap();
+    acc.violations
+}
+
+fn lookup(table: &[(&str, &'static [&'static str])], tag: &str) -> Option<&'static [&'static str]> {
+    table.iter().find(|entry| entry.0 == tag).map(|entry| entry.1)
+}
+
+struct NestingCheckerAcc {
+    // Tags of the current node's ancestors, outermost first
+    ancestors: Vec<&'static str>,
+    // Position of the current node
+    path: Vec<usize>,
+    violations: Vec<Violation>,
+}
+
+impl NestingCheckerAcc {
+    fn problem(&self, tag: &'static str, is_text: bool, text: &str) -> Option<Problem> {
+        let parent = self.ancestors.last().cloned();
+        if is_text {
+            let disallowed = parent.and_then(|parent| lookup(CONTENTS, parent)).is_some();
+            return if disallowed && !text.trim().is_empty() { Some(Problem::Disallowed) } else { None };
+        }
+        if let Some(parents) = lookup(PARENTS, tag) {
+            if !parent.is_some_and(|parent| parents.contains(&parent)) {
+                return Some(Problem::Misplaced(parents));
+            }
+        }
+        if let Some(contents) = parent.and_then(|parent| lookup(CONTENTS, parent)) {
+            if !contents.contains(&tag) {
+                return Some(Problem::Disallowed);
+            }
+        }
+        if BLOCK_ELEMENTS.contains(&tag) {
+            let open_paragraph = self.ancestors.iter().rev()
+                .take_while(|ancestor| !PARAGRAPH_SCOPE.contains(ancestor))
+                .any(|&ancestor| ancestor == "p");
+            if open_paragraph {
+                return Some(Problem::ClosesParagraph);
+            }
+        }
+        let nested = UNNESTABLE.contains(&tag) && self.ancestors.contains(&tag) ||
+            HEADINGS.contains(&tag) && parent.is_some_and(|parent| HEADINGS.contains(&parent));
+        if nested {
+            return Some(Problem::Nested);
+        }
+        None
+    }
+}
+
+/// Processor checking each node against its ancestors
+struct NestingChecker;
+impl<'a, M> DomNodeProcessor<'a, M> for NestingChecker {
+    type Acc = NestingCheckerAcc;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(acc: &mut NestingCheckerAcc, node: &T) -> Result<(), ()> {
+            let value = node.value();
+            let (tag, is_text) = match value {
+                DomValue::Element { tag } => (tag, false),
+                DomValue::Comment(_) | DomValue::Doctype(_) => {
+                    *acc.path.last_mut().unwrap() += 1;
+                    return Ok(());
+                },
+                _ => ("#text", true),
+            };
+            let problem = acc.problem(tag, is_text, value.text().unwrap_or(""));
+            let problem = problem.or_else(|| {
+                let void = VOID_ELEMENTS.contains(&tag) && has_children::<M, _>(node.children());
+                if void { Some(Problem::VoidWithChildren) } else { None }
+            });
+            if let Some(problem) = problem {
+                acc.violations.push(Violation {
+                    path: acc.path.clone(),
+                    tag,
+                    parent: acc.ancestors.last().cloned(),
+                    problem,
+                });
+            }
+
+            if !is_text {
+                acc.ancestors.push(tag);
+                acc.path.push(0);
+                node.children().process_all::<NestingChecker>(acc)?;
+                acc.path.pop();
+                acc.ancestors.pop();
+            }
+            *acc.path.last_mut().unwrap() += 1;
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
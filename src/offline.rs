@@ -0,0 +1,288 @@
+//! Queueing HTTP requests which fail while the device is offline, and replaying them once it
+//! reconnects.
+//!
+//! Apps used in the field lose their connection often, and a change the user saved while
+//! offline shouldn't be lost. A `RetryQueue` in the application state issues requests as
+//! `Effect`s, each with a `RetryPolicy`. When a request fails with `HttpError::NetworkError`
+//! and its policy allows another attempt, it's kept rather than reported: it's retried after a
+//! backoff of `RETRY_DELAY_MILLIS`, doubling with each attempt, or, if the device is offline,
+//! as soon as it reconnects. Requests sent while offline wait for the connection without being
+//! attempted. Each request's `on_response` produces its outcome message once it gets a
+//! response, or once it runs out of attempts.
+//!
+//! The queue's own messages wrap an `Attempt`, which the update passes back to
+//! `RetryQueue::handle`. The subscriptions from `RetryQueue::subscriptions` report the
+//! browser's `online` and `offline` events, which are passed to `RetryQueue::set_online`.
+//!
+//! Timeouts aren't retried, since the server may have received the request. Queued requests
+//! are held in memory, so they're lost if the page is closed before they're sent.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::effects::{Executor, HttpError, MockExecutor, Request, Response};
+//! use domafic::offline::{Attempt, RetryPolicy, RetryQueue};
+//!
+//! enum Msg {
+//!     Save(String),
+//!     Saved(Result<Response, HttpError>),
+//!     Connection(bool),
+//!     Retry(Attempt),
+//! }
+//!
+//! struct State { queue: RetryQueue<Msg>, saved: usize }
+//!
+//! fn update(state: &mut State, msg: Msg, executor: &dyn Executor<Msg>) {
+//!     match msg {
+//!         Msg::Save(note) => {
+//!             let request = Request::post("/notes", note);
+//!             state.queue.send(executor, request, RetryPolicy::WhenOnline { max_attempts: 5 }, Msg::Saved);
+//!         },
+//!         Msg::Saved(result) => if result.is_ok() { state.saved += 1 },
+//!         Msg::Connection(online) => state.queue.set_online(executor, online),
+//!         Msg::Retry(attempt) => if let Some(outcome) = state.queue.handle(executor, attempt) {
+//!             update(state, outcome, executor);
+//!         },
+//!     }
+//! }
+//!
+//! let executor = MockExecutor::new();
+//! let mut state = State { queue: RetryQueue::new(true, Msg::Retry), saved: 0 };
+//!
+//! // The connection drops while the note is being saved, so it waits to be sent again
+//! update(&mut state, Msg::Save("Site visit".to_string()), &executor);
+//! update(&mut state, Msg::Connection(false), &executor);
+//! let msg = executor.respond(Err(HttpError::NetworkError));
+//! update(&mut state, msg, &executor);
+//! assert_eq!(state.queue.queued(), 1);
+//! assert!(executor.is_empty());
+//!
+//! // Once the device reconnects, the note is sent again and saved
+//! update(&mut state, Msg::Connection(true), &executor);
+//! let msg = executor.respond(Ok(Response::new(201, "")));
+//! update(&mut state, msg, &executor);
+//! assert_eq!((state.saved, state.queue.queued()), (1, 0));
+//! ```
+
+use effects::{Effect, Executor, HttpError, Request, Response};
+use subscriptions::{on_offline, on_online, Subscription};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// Delay before the first retry of a request which failed while the device was online.
+///
+/// The delay doubles with each further attempt, up to `MAX_RETRY_DELAY_MILLIS`.
+pub const RETRY_DELAY_MILLIS: u32 = 1_000;
+
+/// Longest delay between retries of a request while the device is online
+pub const MAX_RETRY_DELAY_MILLIS: u32 = 30_000;
+
+/// Whether a request which fails with a network error is attempted again
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum RetryPolicy {
+    /// Report the failure immediately
+    Never,
+    /// Retry the request, waiting for the device to reconnect if it's offline, until it has
+    /// been attempted `max_attempts` times in total
+    WhenOnline {
+        /// Number of attempts, including the first, before the failure is reported
+        max_attempts: u32,
+    },
+}
+
+impl RetryPolicy {
+    /// The policy for requests with `method`: requests which change data on the server, such
+    /// as "POST" or "DELETE", are retried up to five times, and other requests aren't retried,
+    /// since their responses are usually only useful right away
+    pub fn for_method(method: &str) -> RetryPolicy {
+        match method {
+            "POST" | "PUT" | "PATCH" | "DELETE" => RetryPolicy::WhenOnline { max_attempts: 5 },
+            _ => RetryPolicy::Never,
+        }
+    }
+
+    fn allows(&self, attempts: u32) -> bool {
+        match *self {
+            RetryPolicy::Never => false,
+            RetryPolicy::WhenOnline { max_attempts } => attempts < max_attempts,
+        }
+    }
+}
+
+/// Identifier of a request issued through a `RetryQueue`
+#[derive(Debug, Copy, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct RequestId(u32);
+
+/// Message produced by a `RetryQueue`: the result of one attempt at a request, or the end of
+/// its backoff. Pass it back to `RetryQueue::handle`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Attempt {
+    id: RequestId,
+    result: Option<Result<Response, HttpError>>,
+}
+
+impl Attempt {
+    /// The request this attempt belongs to
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+}
+
+struct Entry<M> {
+    request: Request,
+    policy: RetryPolicy,
+    attempts: u32,
+    waiting: bool,
+    on_response: Box<dyn Fn(Result<Response, HttpError>) -> M>,
+}
+
+/// HTTP requests issued through the queue which haven't produced their outcome yet
+pub struct RetryQueue<M> {
+    online: bool,
+    next_id: u32,
+    wrap: Rc<dyn Fn(Attempt) -> M>,
+    entries: BTreeMap<RequestId, Entry<M>>,
+}
+
+impl<M: 'static> RetryQueue<M> {
+    /// Creates an empty queue for a device which is `online`, such as according to
+    /// `web_render::is_online`. `wrap` converts the queue's `Attempt`s to messages.
+    pub fn new<W: Fn(Attempt) -> M + 'static>(online: bool, wrap: W) -> RetryQueue<M> {
+        RetryQueue { online, next_id: 0, wrap: Rc::new(wrap), entries: BTreeMap::new() }
+    }
+
+    /// Issues `request` with `executor`, or queues it if the device is offline and `policy`
+    /// allows retries. `on_response` produces the outcome message.
+    pub fn send<F>(
+        &mut self,
+        executor: &dyn Executor<M>,
+        request: Request,
+        policy: RetryPolicy,
+        on_response: F,
+    ) -> RequestId
+        where F: Fn(Result<Response, HttpError>) -> M + 'static
+    {
+        let id = RequestId(self.next_id);
+        self.next_id += 1;
+        let waiting = !self.online && policy.allows(0);
+        self.entries.insert(id, Entry {
+            request,
+            policy,
+            attempts: 0,
+            waiting,
+            on_response: Box::new(on_response),
+        });
+        if !waiting {
+            self.attempt(executor, id);
+        }
+        id
+    }
+
+    /// Handles a message produced by the queue, returning the outcome message of the request
+    /// if it's finished
+    pub fn handle(&mut self, executor: &dyn Executor<M>, attempt: Attempt) -> Option<M> {
+        let Attempt { id, result } = attempt;
+        let result = match result {
+            Some(result) => result,
+            None => {
+                // A backoff has ended. The request may have been sent already on reconnecting.
+                if self.online && self.entries.get(&id).is_some_and(|entry| entry.waiting) {
+                    self.attempt(executor, id);
+                }
+                return None;
+            },
+        };
+
+        let retry = match (self.entries.get(&id), &result) {
+            (Some(entry), &Err(HttpError::NetworkError)) => entry.policy.allows(entry.attempts),
+            (Some(_), _) => false,
+            (None, _) => return None,
+        };
+        if !retry {
+            return self.entries.remove(&id).map(|entry| (entry.on_response)(result));
+        }
+
+        let entry = self.entries.get_mut(&id).unwrap();
+        entry.waiting = true;
+        if self.online {
+            let delay = RETRY_DELAY_MILLIS.saturating_mul(1 << (entry.attempts - 1).min(16))
+                .min(MAX_RETRY_DELAY_MILLIS);
+            executor.execute(Effect::delay(delay, (self.wrap)(Attempt { id, result: None })));
+        }
+        None
+    }
+
+    /// Records whether the device is online, sending every waiting request once it reconnects
+    pub fn set_online(&mut self, executor: &dyn Executor<M>, online: bool) {
+        let reconnected = online && !self.online;
+        self.online = online;
+        if reconnected {
+            let waiting: Vec<RequestId> = self.entries.iter()
+                .filter(|&(_, entry)| entry.waiting)
+                .map(|(&id, _)| id)
+                .collect();
+            for id in waiting {
+                self.attempt(executor, id);
+            }
+        }
+    }
+
+    /// Subscriptions to the device going online or offline, which produce `f(true)` or
+    /// `f(false)` respectively
+    pub fn subscriptions<F>(f: F) -> Vec<Subscription<M>>
+        where F: Fn(bool) -> M + Clone + 'static
+    {
+        let g = f.clone();
+        vec![on_online(move || f(true)), on_offline(move || g(false))]
+    }
+
+    fn attempt(&mut self, executor: &dyn Executor<M>, id: RequestId) {
+        let entry = self.entries.get_mut(&id).unwrap();
+        entry.attempts += 1;
+        entry.waiting = false;
+        let wrap = self.wrap.clone();
+        executor.execute(Effect::http(entry.request.clone(), move |result| {
+            wrap(Attempt { id, result: Some(result) })
+        }));
+    }
+}
+
+impl<M> RetryQueue<M> {
+    /// Whether the device is online, as last reported to the queue
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    /// The number of requests waiting to be sent again
+    pub fn queued(&self) -> usize {
+        self.entries.values().filter(|entry| entry.waiting).count()
+    }
+
+    /// The number of requests which haven't produced their outcome yet, including those which
+    /// are waiting
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether every request has produced its outcome
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of times the request `id` has been attempted, or `None` if it has finished
+    pub fn attempts(&self, id: RequestId) -> Option<u32> {
+        self.entries.get(&id).map(|entry| entry.attempts)
+    }
+}
+
+impl<M> fmt::Debug for RetryQueue<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let requests: Vec<(&RequestId, &Request)> = self.entries.iter()
+            .map(|(id, entry)| (id, &entry.request))
+            .collect();
+        f.debug_struct("RetryQueue").field("online", &self.online).field("requests", &requests)
+            .finish()
+    }
+}
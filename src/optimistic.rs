@@ -0,0 +1,231 @@
+//! An optimistic update applies the expected result of a command (such as an HTTP request)
+//! to the state immediately, rather than waiting for the command to complete. If the command
+//! fails, the update has to be undone.
+//!
+//! To do this bookkeeping, keep a `Pending` in the application state and implement
+//! `Optimistic` for the state. Each optimistic update registers a `Rollback` and a failure
+//! message with `Pending::begin`, which returns a `CommandId`. Once the command completes, the
+//! message it produces should report the outcome for that id through `ReportsCommand`.
+//! `settle` (or, when rendering client-side, the `with_rollback` updater) then discards the
+//! rollback on success or applies it on failure.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::optimistic::*;
+//!
+//! struct State {
+//!     todos: Vec<String>,
+//!     error: Option<&'static str>,
+//!     pending: Pending<State, Msg>,
+//! }
+//!
+//! impl Optimistic<Msg> for State {
+//!     fn pending(&mut self) -> &mut Pending<State, Msg> { &mut self.pending }
+//! }
+//!
+//! enum Msg {
+//!     Saved(CommandId, bool),
+//!     SaveFailed,
+//! }
+//!
+//! impl ReportsCommand for Msg {
+//!     fn command_result(&self) -> Option<CommandResult> {
+//!         match *self {
+//!             Msg::Saved(id, true) => Some(CommandResult::Succeeded(id)),
+//!             Msg::Saved(id, false) => Some(CommandResult::Failed(id)),
+//!             _ => None,
+//!         }
+//!     }
+//! }
+//!
+//! let mut state = State { todos: Vec::new(), error: None, pending: Pending::new() };
+//!
+//! // Add a todo before the server has confirmed that it was saved
+//! let rollback = Rollback::snapshot(|state: &mut State| &mut state.todos, state.todos.clone());
+//! let id = state.pending.begin(rollback, Msg::SaveFailed);
+//! state.todos.push("Write docs".to_string());
+//!
+//! // The server reports an error, so the todo is removed again
+//! let failure_msg = settle(&mut state, &Msg::Saved(id, false));
+//! assert!(state.todos.is_empty());
+//! assert!(match failure_msg { Some(Msg::SaveFailed) => true, _ => false });
+//! ```
+
+use std::fmt;
+
+/// Identifier of an in-flight command with an associated `Rollback`
+#[derive(Debug, Copy, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct CommandId(u32);
+
+/// Operation which undoes an optimistic update to state of type `S`
+pub struct Rollback<S>(Box<dyn FnOnce(&mut S)>);
+
+impl<S: 'static> Rollback<S> {
+    /// Creates a `Rollback` which undoes an update by calling `f`
+    pub fn new<F: FnOnce(&mut S) + 'static>(f: F) -> Rollback<S> {
+        Rollback(Box::new(f))
+    }
+
+    /// Creates a `Rollback` which resets the part of the state selected by `field` to
+    /// the snapshot `value`
+    pub fn snapshot<T: 'static>(field: fn(&mut S) -> &mut T, value: T) -> Rollback<S> {
+        Rollback::new(move |state| *field(state) = value)
+    }
+}
+
+impl<S> Rollback<S> {
+    /// Undoes the update
+    pub fn apply(self, state: &mut S) {
+        (self.0)(state)
+    }
+}
+
+impl<S> fmt::Debug for Rollback<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Rollback")
+    }
+}
+
+/// Set of in-flight commands which may require their optimistic updates to be rolled back
+#[derive(Debug)]
+pub struct Pending<S, M> {
+    next_id: u32,
+    entries: Vec<(CommandId, Rollback<S>, M)>,
+}
+
+impl<S, M> Default for Pending<S, M> {
+    fn default() -> Pending<S, M> {
+        Pending { next_id: 0, entries: Vec::new() }
+    }
+}
+
+impl<S, M> Pending<S, M> {
+    /// Creates an empty `Pending`
+    pub fn new() -> Pending<S, M> {
+        Pending::default()
+    }
+
+    /// Records a command, returning its id.
+    ///
+    /// If the command fails, `rollback` is applied and `on_failure` is dispatched.
+    pub fn begin(&mut self, rollback: Rollback<S>, on_failure: M) -> CommandId {
+        let id = CommandId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.entries.push((id, rollback, on_failure));
+        id
+    }
+
+    /// Marks the command as successful, discarding its rollback.
+    ///
+    /// Returns `false` if the command was not pending.
+    pub fn succeed(&mut self, id: CommandId) -> bool {
+        self.take(id).is_some()
+    }
+
+    /// Marks the command as failed, returning its rollback and failure message.
+    ///
+    /// Returns `None` if the command was not pending.
+    pub fn fail(&mut self, id: CommandId) -> Option<(Rollback<S>, M)> {
+        self.take(id)
+    }
+
+    /// Returns whether the command is still pending
+    pub fn contains(&self, id: CommandId) -> bool {
+        self.entries.iter().any(|entry| entry.0 == id)
+    }
+
+    /// Returns the number of pending commands
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no pending commands
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn take(&mut self, id: CommandId) -> Option<(Rollback<S>, M)> {
+        self.entries.iter().position(|entry| entry.0 == id).map(|index| {
+            let (_, rollback, on_failure) = self.entries.remove(index);
+            (rollback, on_failure)
+        })
+    }
+}
+
+/// Application state which tracks optimistic updates
+pub trait Optimistic<M>: Sized {
+    /// Returns the state's set of pending commands
+    fn pending(&mut self) -> &mut Pending<Self, M>;
+}
+
+/// Outcome of a command
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum CommandResult {
+    /// The command succeeded
+    Succeeded(CommandId),
+    /// The command failed
+    Failed(CommandId),
+}
+
+/// Messages which may report the outcome of a command
+pub trait ReportsCommand {
+    /// Returns the outcome reported by this message, if any
+    fn command_result(&self) -> Option<CommandResult>;
+}
+
+/// Settles the command whose outcome is reported by `msg`, if any.
+///
+/// On failure, the command's rollback is applied to `state` and its failure message is
+/// returned so that it can be dispatched.
+pub fn settle<S, M>(state: &mut S, msg: &M) -> Option<M>
+    where S: Optimistic<M>, M: ReportsCommand
+{
+    match msg.command_result() {
+        Some(CommandResult::Succeeded(id)) => {
+            state.pending().succeed(id);
+            None
+        }
+        Some(CommandResult::Failed(id)) => {
+            state.pending().fail(id).map(|(rollback, on_failure)| {
+                rollback.apply(state);
+                on_failure
+            })
+        }
+        None => None,
+    }
+}
+
+/// Wraps `updater` so that commands are settled automatically.
+///
+/// When a message reports a failed command, the command's rollback is applied before the
+/// message is passed to `updater`, after which the command's failure message is dispatched.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub fn with_rollback<U>(updater: U) -> WithRollback<U> {
+    WithRollback(updater)
+}
+
+/// An `Updater` which settles commands reported by messages before updating.
+///
+/// Created by `with_rollback`.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub struct WithRollback<U>(U);
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{settle, Optimistic, ReportsCommand, WithRollback};
+    use keys::{KeyIter, Keys};
+    use web_render::{JsIo, Updater};
+
+    impl<S, M, U> Updater<S, M> for WithRollback<U>
+        where S: Optimistic<M>, M: ReportsCommand, U: Updater<S, M>
+    {
+        fn update(&self, state: &mut S, msg: M, keys: KeyIter, js_io: &dyn JsIo<M>) {
+            let failure_msg = settle(state, &msg);
+            self.0.update(state, msg, keys, js_io);
+            if let Some(failure_msg) = failure_msg {
+                self.0.update(state, failure_msg, Keys::new().into_iter(), js_io);
+            }
+        }
+    }
+}
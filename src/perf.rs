@@ -0,0 +1,123 @@
+//! Render timing statistics, for finding the state shapes or lists which make an application
+//! janky.
+//!
+//! A `PerfMonitor` keeps exponential moving averages of how long each update took to render
+//! and to patch into the DOM, along with the number of frames the slow ones dropped. When
+//! rendering client-side, `web_render::perf_monitor` times every update and reports the
+//! statistics in an overlay or to the console, and can be switched on and off while the
+//! application runs.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::perf::PerfMonitor;
+//!
+//! let mut monitor = PerfMonitor::new();
+//! monitor.record(2.0, 1.0);
+//! monitor.record(30.0, 10.0);
+//!
+//! let stats = monitor.stats();
+//! assert_eq!(stats.updates, 2);
+//! // 40ms is over two frames at 60 frames per second
+//! assert_eq!(stats.dropped_frames, 2);
+//! assert_eq!(stats.slowest_millis, 40.0);
+//! assert!(stats.render_millis > 2.0 && stats.render_millis < 30.0);
+//! ```
+
+use std::fmt;
+
+/// Milliseconds available to draw each frame at 60 frames per second
+pub const FRAME_BUDGET_MILLIS: f64 = 1000.0 / 60.0;
+
+/// Weight of the latest update in the moving averages kept by `PerfMonitor::new`
+pub const DEFAULT_SMOOTHING: f64 = 0.1;
+
+/// Timing statistics of the updates recorded by a `PerfMonitor`
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PerfStats {
+    /// Number of updates recorded
+    pub updates: u64,
+    /// Moving average of the milliseconds spent rendering the `DomNode`
+    pub render_millis: f64,
+    /// Moving average of the milliseconds spent patching the DOM
+    pub patch_millis: f64,
+    /// Milliseconds taken by the slowest update, rendering and patching
+    pub slowest_millis: f64,
+    /// Number of frames which couldn't be drawn because an update was still being rendered or
+    /// patched
+    pub dropped_frames: u64,
+}
+
+impl PerfStats {
+    /// The frame rate, up to 60 frames per second, which updates taking the average time to
+    /// render and patch could sustain
+    pub fn sustainable_fps(&self) -> f64 {
+        1000.0 / (self.render_millis + self.patch_millis).max(FRAME_BUDGET_MILLIS)
+    }
+}
+
+impl fmt::Display for PerfStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "render {:.1}ms, patch {:.1}ms, {:.0} fps, slowest {:.1}ms, {} dropped frames in {} updates",
+            self.render_millis, self.patch_millis, self.sustainable_fps(), self.slowest_millis,
+            self.dropped_frames, self.updates)
+    }
+}
+
+/// Keeps moving averages of the time taken to render and patch each update
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PerfMonitor {
+    smoothing: f64,
+    stats: PerfStats,
+}
+
+impl PerfMonitor {
+    /// Creates a monitor weighting each update by `DEFAULT_SMOOTHING`
+    pub fn new() -> PerfMonitor {
+        PerfMonitor::with_smoothing(DEFAULT_SMOOTHING)
+    }
+
+    /// Creates a monitor weighting the latest update by `smoothing`, between 0 and 1, in its
+    /// moving averages. Higher values follow changes faster, but are noisier.
+    pub fn with_smoothing(smoothing: f64) -> PerfMonitor {
+        assert!(smoothing > 0.0 && smoothing <= 1.0, "smoothing must be in (0, 1]");
+        PerfMonitor { smoothing, stats: PerfStats::default() }
+    }
+
+    /// Records an update which took `render_millis` to render and `patch_millis` to patch
+    /// into the DOM, returning the number of frames it dropped
+    pub fn record(&mut self, render_millis: f64, patch_millis: f64) -> u64 {
+        let smoothing = self.smoothing;
+        let average = |previous: f64, latest: f64| previous + smoothing * (latest - previous);
+        let stats = &mut self.stats;
+        if stats.updates == 0 {
+            stats.render_millis = render_millis;
+            stats.patch_millis = patch_millis;
+        } else {
+            stats.render_millis = average(stats.render_millis, render_millis);
+            stats.patch_millis = average(stats.patch_millis, patch_millis);
+        }
+        let total = render_millis + patch_millis;
+        let dropped = (total / FRAME_BUDGET_MILLIS) as u64;
+        stats.updates += 1;
+        stats.slowest_millis = stats.slowest_millis.max(total);
+        stats.dropped_frames += dropped;
+        dropped
+    }
+
+    /// The statistics of the updates recorded so far
+    pub fn stats(&self) -> PerfStats {
+        self.stats
+    }
+
+    /// Forgets every recorded update
+    pub fn reset(&mut self) {
+        self.stats = PerfStats::default();
+    }
+}
+
+impl Default for PerfMonitor {
+    fn default() -> PerfMonitor {
+        PerfMonitor::new()
+    }
+}
@@ -0,0 +1,225 @@
+//! Enable with the `persist` feature.
+//!
+//! A `Persist` describes where and how application state is saved. State is stored as JSON
+//! alongside a schema version. When state saved by an older version of the application is
+//! restored, it is passed through each registered migration in turn until it matches the
+//! current version.
+//!
+//! Example:
+//!
+//! ```rust
+//! extern crate domafic;
+//! extern crate serde_json;
+//!
+//! use domafic::persist::Persist;
+//! use domafic::storage::{MemoryStorage, Storage};
+//! use serde_json::Value;
+//!
+//! fn main() {
+//!     // Version 1 stored a list of todo strings, version 2 stores (todo, completed) pairs
+//!     let persist = Persist::<Vec<(String, bool)>>::new("todos", 2)
+//!         .with_migration(1, |todos| match todos {
+//!             Value::Array(todos) => Value::Array(
+//!                 todos.into_iter().map(|todo| Value::Array(vec![todo, Value::Bool(false)]))
+//!                     .collect()
+//!             ),
+//!             other => other,
+//!         });
+//!
+//!     let storage = MemoryStorage::new();
+//!     storage.set("todos", r#"{"version":1,"state":["Write docs"]}"#);
+//!
+//!     let todos = persist.restore(&storage).unwrap_or_else(|_| Vec::new());
+//!     assert_eq!(todos, vec![("Write docs".to_string(), false)]);
+//! }
+//! ```
+//!
+//! When rendering client-side, `Persist::updater` wraps an `Updater` so that the state is saved
+//! to `localStorage` after every update.
+
+extern crate serde;
+extern crate serde_json;
+
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+use self::serde_json::Value;
+
+use storage::Storage;
+
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A function transforming persisted state from one schema version to the next
+pub type Migration = Box<dyn Fn(Value) -> Value>;
+
+/// Default delay before state is written to storage by `Persisted` updaters
+pub const DEFAULT_DEBOUNCE_MILLIS: u32 = 250;
+
+/// Description of how to persist state of type `S`.
+pub struct Persist<S> {
+    key: String,
+    version: u32,
+    migrations: Vec<(u32, Migration)>,
+    debounce_millis: u32,
+    _marker: PhantomData<S>,
+}
+
+/// Error returned when persisted state could not be restored
+#[derive(Debug)]
+pub enum RestoreError {
+    /// No state was stored under the key
+    Missing,
+    /// The stored state could not be decoded
+    Malformed(serde_json::Error),
+    /// The stored state came from a newer schema version than the current one
+    UnknownVersion(u32),
+    /// No migration was registered from the given schema version
+    MissingMigration(u32),
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RestoreError::Missing => write!(f, "no persisted state was found"),
+            RestoreError::Malformed(ref err) => write!(f, "persisted state was malformed: {}", err),
+            RestoreError::UnknownVersion(version) =>
+                write!(f, "persisted state has unknown schema version {}", version),
+            RestoreError::MissingMigration(version) =>
+                write!(f, "no migration from schema version {}", version),
+        }
+    }
+}
+
+impl error::Error for RestoreError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            RestoreError::Malformed(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl<S> Persist<S> where S: Serialize + DeserializeOwned {
+    /// Creates a `Persist` which stores state under `key` with schema version `version`
+    pub fn new(key: &str, version: u32) -> Persist<S> {
+        Persist {
+            key: key.to_string(),
+            version,
+            migrations: Vec::new(),
+            debounce_millis: DEFAULT_DEBOUNCE_MILLIS,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Registers a migration transforming state saved with schema version `from_version`
+    /// into state for schema version `from_version + 1`.
+    pub fn with_migration<F>(mut self, from_version: u32, migration: F) -> Persist<S>
+        where F: Fn(Value) -> Value + 'static
+    {
+        self.migrations.push((from_version, Box::new(migration)));
+        self
+    }
+
+    /// Sets the delay used by `Persisted` updaters before writing state to storage
+    pub fn with_debounce(mut self, millis: u32) -> Persist<S> {
+        self.debounce_millis = millis;
+        self
+    }
+
+    /// Returns the storage key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the current schema version
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Encodes `state` along with the current schema version
+    pub fn encode(&self, state: &S) -> Result<String, serde_json::Error> {
+        let mut envelope = serde_json::Map::new();
+        envelope.insert("version".to_string(), Value::from(self.version));
+        envelope.insert("state".to_string(), serde_json::to_value(state)?);
+        serde_json::to_string(&Value::Object(envelope))
+    }
+
+    /// Decodes state produced by `encode`, migrating it to the current schema version
+    pub fn decode(&self, encoded: &str) -> Result<S, RestoreError> {
+        let mut envelope: Value = serde_json::from_str(encoded).map_err(RestoreError::Malformed)?;
+        let malformed = || RestoreError::Malformed(
+            <serde_json::Error as serde::de::Error>::custom("expected version and state fields"));
+
+        let mut version = envelope.get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(malformed)? as u32;
+        let mut state = envelope.get_mut("state").map(Value::take).ok_or_else(malformed)?;
+
+        if version > self.version {
+            return Err(RestoreError::UnknownVersion(version));
+        }
+
+        while version < self.version {
+            let migration = self.migrations.iter()
+                .find(|migration| migration.0 == version)
+                .map(|migration| &migration.1)
+                .ok_or(RestoreError::MissingMigration(version))?;
+            state = migration(state);
+            version += 1;
+        }
+
+        serde_json::from_value(state).map_err(RestoreError::Malformed)
+    }
+
+    /// Saves `state` to `storage`
+    pub fn save<T: Storage>(&self, storage: &T, state: &S) -> Result<(), serde_json::Error> {
+        storage.set(&self.key, &self.encode(state)?);
+        Ok(())
+    }
+
+    /// Restores state from `storage`, migrating it to the current schema version
+    pub fn restore<T: Storage>(&self, storage: &T) -> Result<S, RestoreError> {
+        let encoded = storage.get(&self.key).ok_or(RestoreError::Missing)?;
+        self.decode(&encoded)
+    }
+
+    /// Wraps `updater` so that the state is saved to `localStorage` after each update.
+    ///
+    /// Writes are debounced (see `with_debounce`) so that bursts of messages only result in a
+    /// single write.
+    #[cfg(all(feature = "web_render", target_os = "emscripten"))]
+    pub fn updater<U>(self, updater: U) -> Persisted<S, U> {
+        Persisted { persist: self, updater }
+    }
+}
+
+/// An `Updater` which saves the state to `localStorage` after each update.
+///
+/// Created by `Persist::updater`.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub struct Persisted<S, U> {
+    persist: Persist<S>,
+    updater: U,
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{Persisted, serde};
+    use super::serde::de::DeserializeOwned;
+    use keys::KeyIter;
+    use web_render::{JsIo, Updater, WebStorage};
+
+    impl<S, M, U> Updater<S, M> for Persisted<S, U>
+        where S: serde::Serialize + DeserializeOwned, U: Updater<S, M>
+    {
+        fn update(&self, state: &mut S, msg: M, keys: KeyIter, js_io: &dyn JsIo<M>) {
+            self.updater.update(state, msg, keys, js_io);
+            match self.persist.encode(state) {
+                Ok(encoded) => WebStorage::Local.set_debounced(
+                    &self.persist.key, &encoded, self.persist.debounce_millis),
+                Err(err) => panic!("Failed to encode persisted state: {}", err),
+            }
+        }
+    }
+}
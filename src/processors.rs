@@ -16,6 +16,105 @@ pub trait DomNodeProcessor<'a, Message> {
     /// Returns a folding function capable of processing elements of type `T: DomNode`.
     fn get_processor<T: DomNode<Message>>()
         -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error>;
+
+    /// Returns whether processing should stop, such as once a processor has found the node it
+    /// was looking for, or a writer has written as much as it may.
+    ///
+    /// Collections of `DomNode`s check this before each of their nodes, and skip the rest of
+    /// their nodes once it returns `true`. Processors decide for themselves which children to
+    /// process, so a processor can skip a node's children by not processing them. The default
+    /// never stops.
+    fn is_stopped(_acc: &Self::Acc) -> bool {
+        false
+    }
+}
+
+/// What to do after a `Visitor` has visited a node
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Flow {
+    /// Go on to visit the node's children, then its later siblings
+    Continue,
+    /// Go on to visit the node's later siblings, without visiting its children
+    SkipChildren,
+    /// Visit no more nodes
+    Stop,
+}
+
+/// Visits each node of a tree, in document order, deciding whether to go on after each one.
+///
+/// Unlike a `DomNodeProcessor`, which has to process the children of each node itself, a
+/// `Visitor` only looks at one node at a time, and `visit` walks the tree for it.
+pub trait Visitor<Message> {
+    /// Visits `node`, returning which nodes to visit next
+    fn visit<T: DomNode<Message>>(&mut self, node: &T) -> Flow;
+}
+
+/// Walks `nodes` and their descendants with `visitor`, stopping early if it returns
+/// `Flow::Stop`, and returns the visitor.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::{DomNode, DomValue};
+/// use domafic::processors::{visit, Flow, Visitor};
+/// use domafic::tags::{div, nav, p};
+/// use std::marker::PhantomData;
+///
+/// // Finds the first text mentioning `word`, outside of navigation
+/// struct FindText { word: &'static str, found: Option<String> }
+/// impl<M> Visitor<M> for FindText {
+///     fn visit<T: DomNode<M>>(&mut self, node: &T) -> Flow {
+///         match node.value() {
+///             DomValue::Element { tag: "nav" } => Flow::SkipChildren,
+///             DomValue::Text(text) if text.contains(self.word) => {
+///                 self.found = Some(text.to_string());
+///                 Flow::Stop
+///             },
+///             _ => Flow::Continue,
+///         }
+///     }
+/// }
+///
+/// let page = div((nav("Sale menu"), p("First sale"), p("Second sale"), PhantomData::<()>));
+/// let search = visit(&page, FindText { word: "sale", found: None });
+/// assert_eq!(search.found.as_ref().map(String::as_str), Some("First sale"));
+/// ```
+pub fn visit<M, N, V>(nodes: &N, visitor: V) -> V
+    where N: DomNodes<M> + ?Sized, V: Visitor<M>
+{
+    let mut acc = Visiting { visitor, stopped: false };
+    // Visiting never fails
+    let _ = nodes.process_all::<VisitProcessor<V>>(&mut acc);
+    acc.visitor
+}
+
+struct Visiting<V> {
+    visitor: V,
+    stopped: bool,
+}
+
+struct VisitProcessor<V>(PhantomData<V>);
+impl<'a, M, V: Visitor<M>> DomNodeProcessor<'a, M> for VisitProcessor<V> {
+    type Acc = Visiting<V>;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+        fn visit_node<M, V: Visitor<M>, T: DomNode<M>>(acc: &mut Visiting<V>, node: &T) -> Result<(), ()> {
+            match acc.visitor.visit(node) {
+                Flow::Continue => node.children().process_all::<VisitProcessor<V>>(acc),
+                Flow::SkipChildren => Ok(()),
+                Flow::Stop => {
+                    acc.stopped = true;
+                    Ok(())
+                }
+            }
+        }
+        visit_node::<M, V, T>
+    }
+
+    fn is_stopped(acc: &Self::Acc) -> bool {
+        acc.stopped
+    }
 }
 
 /// Collection of `DomNode`s with a common message type
@@ -92,6 +191,9 @@ impl<Message, L: Listeners<Message>> Listeners<Message> for Option<L> {
 impl<Message, T: DomNodes<Message>> DomNodes<Message> for [T] {
     fn process_all<'a, P: DomNodeProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
         for x in self {
+            if P::is_stopped(acc) {
+                break;
+            }
             x.process_all::<P>(acc)?;
         }
         Ok(())
@@ -111,6 +213,9 @@ impl<Message, T: Listeners<Message>> Listeners<Message> for [T] {
 impl<Message, T: DomNodes<Message>> DomNodes<Message> for Vec<T> {
     fn process_all<'a, P: DomNodeProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
         for x in self {
+            if P::is_stopped(acc) {
+                break;
+            }
             x.process_all::<P>(acc)?;
         }
         Ok(())
@@ -127,11 +232,68 @@ impl<Message, T: Listeners<Message>> Listeners<Message> for Vec<T> {
     }
 }
 
+impl<Message, T: DomNodes<Message>> DomNodes<Message> for &[T] {
+    fn process_all<'a, P: DomNodeProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        (**self).process_all::<P>(acc)
+    }
+}
+
+impl<Message, T: Listeners<Message>> Listeners<Message> for &[T] {
+    fn process_all<'a, P: ListenerProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        (**self).process_all::<P>(acc)
+    }
+}
+
+/// The values of the map, in the order of their keys
+#[cfg(any(feature = "use_std", test))]
+impl<Message, K, T: DomNodes<Message>> DomNodes<Message> for ::std::collections::BTreeMap<K, T> {
+    fn process_all<'a, P: DomNodeProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        for x in self.values() {
+            if P::is_stopped(acc) {
+                break;
+            }
+            x.process_all::<P>(acc)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl<Message, T: DomNodes<Message>> DomNodes<Message> for Box<[T]> {
+    fn process_all<'a, P: DomNodeProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        (**self).process_all::<P>(acc)
+    }
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl<Message, T: Listeners<Message>> Listeners<Message> for Box<[T]> {
+    fn process_all<'a, P: ListenerProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        (**self).process_all::<P>(acc)
+    }
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl<Message, T: DomNodes<Message> + Clone> DomNodes<Message> for ::std::borrow::Cow<'_, [T]> {
+    fn process_all<'a, P: DomNodeProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        (**self).process_all::<P>(acc)
+    }
+}
+
+#[cfg(any(feature = "use_std", test))]
+impl<Message, T: Listeners<Message> + Clone> Listeners<Message> for ::std::borrow::Cow<'_, [T]> {
+    fn process_all<'a, P: ListenerProcessor<'a, Message>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        (**self).process_all::<P>(acc)
+    }
+}
+
 macro_rules! array_impls {
     ($($len:expr,)*) => { $(
         impl<M, T: DomNodes<M>> DomNodes<M> for [T; $len] {
             fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
                 for x in self {
+                    if P::is_stopped(acc) {
+                        break;
+                    }
                     x.process_all::<P>(acc)?;
                 }
                 Ok(())
@@ -186,6 +348,9 @@ macro_rules! tuple_impls {
                     where P: DomNodeProcessor<'a, M> {
                 &self.$idx.process_all::<P>(acc)?;
                 $(
+                    if P::is_stopped(acc) {
+                        return Ok(());
+                    }
                     &self.$nidx.process_all::<P>(acc)?;
                 )*
                 Ok(())
@@ -208,7 +373,30 @@ macro_rules! tuple_impls {
     }
 }
 
+// `M` and `P` are skipped, as they name the message and processor types
 tuple_impls!(
+    (31 => AH),
+    (30 => AG),
+    (29 => AF),
+    (28 => AE),
+    (27 => AD),
+    (26 => AC),
+    (25 => AB),
+    (24 => AA),
+    (23 => Z),
+    (22 => Y),
+    (21 => X),
+    (20 => W),
+    (19 => V),
+    (18 => U),
+    (17 => T),
+    (16 => S),
+    (15 => R),
+    (14 => Q),
+    (13 => O),
+    (12 => N),
+    (11 => L),
+    (10 => K),
     (9 => J),
     (8 => I),
     (7 => H),
@@ -0,0 +1,246 @@
+//! Limiting how often messages from high-frequency events are handled.
+//!
+//! Events such as `scroll`, `mousemove` or `resize` can fire far more often than an expensive
+//! update can keep up with. A `RateLimiter` sorts messages into classes with a classifier, and
+//! lets at most one message of each class through per interval, as given by its `Limit`.
+//! Messages which arrive before the interval has passed are either dropped, or coalesced so
+//! that only the latest of them is handled once the interval ends. Messages which the
+//! classifier doesn't limit are always handled.
+//!
+//! When rendering client-side, `RateLimiter::updater` wraps an `Updater` so that messages are
+//! limited before they reach it, and coalesced messages are delivered, with the keys of the
+//! component they came from, when their interval ends.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::KeyIter;
+//! use domafic::rate_limit::{Admission, Limit, RateLimiter};
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Msg { Scrolled(u32), Moved(i32), Clicked }
+//!
+//! let mut limiter = RateLimiter::new(|msg: &Msg| match *msg {
+//!     Msg::Scrolled(_) => Some(Limit::coalesce("scroll", 100)),
+//!     Msg::Moved(_) => Some(Limit::drop("move", 50)),
+//!     Msg::Clicked => None,
+//! });
+//!
+//! // The first scroll is handled, and later ones wait for the end of the interval
+//! assert!(limiter.admit(0, Msg::Scrolled(10), KeyIter::empty()).is_handled());
+//! assert_eq!(limiter.admit(20, Msg::Scrolled(20), KeyIter::empty()),
+//!     Admission::Held { class: "scroll", flush_after_millis: Some(80) });
+//! assert_eq!(limiter.admit(40, Msg::Scrolled(30), KeyIter::empty()),
+//!     Admission::Held { class: "scroll", flush_after_millis: None });
+//! match limiter.flush(100, "scroll") {
+//!     Some((Msg::Scrolled(offset), _)) => assert_eq!(offset, 30),
+//!     _ => unreachable!(),
+//! }
+//!
+//! // Excess movements are dropped, and clicks are never limited
+//! assert!(limiter.admit(0, Msg::Moved(1), KeyIter::empty()).is_handled());
+//! assert_eq!(limiter.admit(10, Msg::Moved(2), KeyIter::empty()), Admission::Dropped);
+//! assert!(limiter.admit(10, Msg::Clicked, KeyIter::empty()).is_handled());
+//! ```
+
+use keys::KeyIter;
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+use std::rc::Rc;
+
+/// What happens to a message which arrives before the interval of its class has passed
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Excess {
+    /// The message is never handled
+    Drop,
+    /// The message replaces any other waiting message of its class, and is handled once the
+    /// interval ends
+    Coalesce,
+}
+
+/// How often messages of one class are handled
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Limit {
+    /// Name of the class, which messages limited together share
+    pub class: &'static str,
+    /// Shortest time between two messages of the class being handled
+    pub interval_millis: u32,
+    /// What happens to messages which arrive sooner
+    pub excess: Excess,
+}
+
+impl Limit {
+    /// Handles at most one message of `class` every `interval_millis`, dropping the rest
+    pub fn drop(class: &'static str, interval_millis: u32) -> Limit {
+        Limit { class, interval_millis, excess: Excess::Drop }
+    }
+
+    /// Handles at most one message of `class` every `interval_millis`, handling only the
+    /// latest of the rest once the interval ends
+    pub fn coalesce(class: &'static str, interval_millis: u32) -> Limit {
+        Limit { class, interval_millis, excess: Excess::Coalesce }
+    }
+}
+
+/// What a `RateLimiter` decided to do with a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Admission<M> {
+    /// Handle the message now, with the keys it was delivered with
+    Handle(M, KeyIter),
+    /// The message was dropped
+    Dropped,
+    /// The message is waiting for the interval of `class` to end. If `flush_after_millis` is
+    /// given, the caller should `flush` the class after that many milliseconds. Otherwise a
+    /// flush is already due.
+    Held {
+        /// The class of the message
+        class: &'static str,
+        /// Time until the interval ends, if no other message of the class was waiting
+        flush_after_millis: Option<u32>,
+    },
+}
+
+impl<M> Admission<M> {
+    /// Whether the message should be handled now
+    pub fn is_handled(&self) -> bool {
+        matches!(*self, Admission::Handle(..))
+    }
+}
+
+type Classifier<M> = Box<dyn Fn(&M) -> Option<Limit>>;
+
+struct Window<M> {
+    started: u64,
+    waiting: Option<(M, KeyIter)>,
+}
+
+/// Limits how often messages of each class are handled
+pub struct RateLimiter<M> {
+    classify: Classifier<M>,
+    windows: HashMap<&'static str, Window<M>>,
+}
+
+impl<M> RateLimiter<M> {
+    /// Creates a limiter which limits the messages `classify` gives a `Limit` for
+    pub fn new<F: Fn(&M) -> Option<Limit> + 'static>(classify: F) -> RateLimiter<M> {
+        RateLimiter { classify: Box::new(classify), windows: HashMap::new() }
+    }
+
+    /// Decides what to do with `msg`, delivered with `keys` at `now_millis`
+    pub fn admit(&mut self, now_millis: u64, msg: M, keys: KeyIter) -> Admission<M> {
+        let limit = match (self.classify)(&msg) {
+            Some(limit) => limit,
+            None => return Admission::Handle(msg, keys),
+        };
+        if let Some(window) = self.windows.get_mut(limit.class) {
+            let ends = window.started + u64::from(limit.interval_millis);
+            // A message arriving after the interval, but before a late flush, still waits for
+            // the flush so that messages are handled in order
+            if window.waiting.is_some() || (now_millis < ends && limit.excess == Excess::Coalesce) {
+                let flush_after_millis = if window.waiting.is_none() {
+                    Some((ends - now_millis) as u32)
+                } else {
+                    None
+                };
+                window.waiting = Some((msg, keys));
+                return Admission::Held { class: limit.class, flush_after_millis };
+            }
+            if now_millis < ends {
+                return Admission::Dropped;
+            }
+        }
+        self.windows.insert(limit.class, Window { started: now_millis, waiting: None });
+        Admission::Handle(msg, keys)
+    }
+
+    /// Takes the message of `class` which is waiting for its interval to end, starting the
+    /// next interval at `now_millis`
+    pub fn flush(&mut self, now_millis: u64, class: &str) -> Option<(M, KeyIter)> {
+        let window = self.windows.get_mut(class)?;
+        let waiting = window.waiting.take()?;
+        window.started = now_millis;
+        Some(waiting)
+    }
+
+    /// Whether a message of `class` is waiting for its interval to end
+    pub fn is_waiting(&self, class: &str) -> bool {
+        self.windows.get(class).is_some_and(|window| window.waiting.is_some())
+    }
+
+    /// Wraps `updater` so that messages are limited before they're passed to it
+    #[cfg(all(feature = "web_render", target_os = "emscripten"))]
+    pub fn updater<U>(self, updater: U) -> RateLimited<M, U> {
+        RateLimited {
+            limiter: Rc::new(RefCell::new(self)),
+            flushed: Rc::new(RefCell::new(None)),
+            updater,
+        }
+    }
+}
+
+impl<M> fmt::Debug for RateLimiter<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let waiting: Vec<&&str> = self.windows.iter()
+            .filter(|&(_, window)| window.waiting.is_some())
+            .map(|(class, _)| class)
+            .collect();
+        f.debug_struct("RateLimiter").field("waiting", &waiting).finish()
+    }
+}
+
+/// An `Updater` which limits messages before handling them.
+///
+/// Created by `RateLimiter::updater`.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub struct RateLimited<M, U> {
+    limiter: Rc<RefCell<RateLimiter<M>>>,
+    // Keys of a coalesced message which is being delivered, so that it isn't limited again
+    flushed: Rc<RefCell<Option<KeyIter>>>,
+    updater: U,
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{Admission, RateLimited};
+    use keys::KeyIter;
+    use web_render::{JsIo, Updater};
+
+    extern "C" {
+        fn emscripten_get_now() -> f64;
+    }
+
+    fn now_millis() -> u64 {
+        unsafe { emscripten_get_now() as u64 }
+    }
+
+    impl<S, M: 'static, U> Updater<S, M> for RateLimited<M, U>
+        where U: Updater<S, M>
+    {
+        fn update(&self, state: &mut S, msg: M, keys: KeyIter, js_io: &dyn JsIo<M>) {
+            if let Some(keys) = self.flushed.borrow_mut().take() {
+                self.updater.update(state, msg, keys, js_io);
+                return;
+            }
+            let admission = self.limiter.borrow_mut().admit(now_millis(), msg, keys);
+            match admission {
+                Admission::Handle(msg, keys) => self.updater.update(state, msg, keys, js_io),
+                Admission::Held { class, flush_after_millis: Some(millis) } => {
+                    let limiter = self.limiter.clone();
+                    let flushed = self.flushed.clone();
+                    // The message is taken just before it's delivered back to `update`
+                    js_io.delay(millis, Box::new(move || {
+                        let (msg, keys) = limiter.borrow_mut().flush(now_millis(), class)
+                            .expect("coalesced message was already flushed");
+                        *flushed.borrow_mut() = Some(keys);
+                        msg
+                    }));
+                },
+                Admission::Held { .. } | Admission::Dropped => {},
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Re-rendering similar pages to HTML by reusing the unchanged parts of previous renders.
+//!
+//! A `RenderCache` keeps the HTML of the last page rendered for each layout key. Subtrees with
+//! a props hash, such as those wrapped with `DomNode::with_should_update` and `StaticNode`s,
+//! are identified by their position in the tree and the hash: when a subtree is rendered again
+//! at the same position with the same props hash, its HTML is copied from the previous render
+//! instead of being written again.
+//! This suits long-running servers rendering many variations of a shared layout, such as pages
+//! personalized with the visitor's name.
+//!
+//! The `should_update` functions themselves aren't called: a subtree is reused exactly when its
+//! props hash is unchanged. A props hash must cover all of its node's output, so wrappers which
+//! add to it, such as `WithAttributes`, fold what they add into the hash, and nodes without
+//! props, such as `uncontrolled` regions, are written again each time.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::render_cache::RenderCache;
+//! use domafic::tags::{div, header, ul, li};
+//! use std::marker::PhantomData;
+//!
+//! let articles = vec!["First", "Second"];
+//! let page = |user: &str| div((
+//!     header(format!("Welcome, {}", user)),
+//!     ul(articles.iter().map(|title| li(*title)).collect::<Vec<_>>())
+//!         .with_should_update(&articles, |old, new| old != new),
+//!     PhantomData::<()>,
+//! ));
+//!
+//! let mut cache = RenderCache::new();
+//! cache.render("home", &page("Ada"));
+//! let html = cache.render("home", &page("Grace")).to_vec();
+//!
+//! assert_eq!(html, page("Grace").to_string().into_bytes());
+//! assert_eq!(cache.reused("home"), 1);
+//! ```
+
+use {DomNode, DomNodes, DomValue};
+use processors::DomNodeProcessor;
+use xml_writer::{has_children, write_comment, write_escaped, write_html_attribute, VOID_ELEMENTS};
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// The HTML of the last page rendered for each layout
+#[derive(Debug, Clone, Default)]
+pub struct RenderCache {
+    layouts: HashMap<String, CachedRender>,
+}
+
+/// A subtree of a previous render which can be reused
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+struct Region {
+    props_hash: u64,
+    bytes: Range<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CachedRender {
+    html: Vec<u8>,
+    // Regions keyed by the position of their node, as the indices of it and its ancestors
+    // among their siblings
+    regions: BTreeMap<Vec<usize>, Region>,
+    reused: usize,
+}
+
+impl RenderCache {
+    /// Creates an empty cache
+    pub fn new() -> RenderCache {
+        RenderCache::default()
+    }
+
+    /// Renders `node` to HTML, reusing the output of the last node rendered with `layout_key`
+    /// for subtrees whose props hash is unchanged
+    pub fn render<M, T: DomNode<M>>(&mut self, layout_key: &str, node: &T) -> &[u8] {
+        let previous = self.layouts.remove(layout_key).unwrap_or_default();
+        let mut acc = CachingWriterAcc {
+            previous,
+            next: CachedRender::default(),
+            path: vec![0],
+        };
+        node.process_all::<CachingWriter>(&mut acc).expect("writing to a Vec can't fail");
+        self.layouts.insert(layout_key.to_string(), acc.next);
+        &self.layouts[layout_key].html
+    }
+
+    /// The number of subtrees copied from the previous render in the last render of
+    /// `layout_key`
+    pub fn reused(&self, layout_key: &str) -> usize {
+        self.layouts.get(layout_key).map_or(0, |layout| layout.reused)
+    }
+
+    /// Forgets the last render of `layout_key`
+    pub fn remove(&mut self, layout_key: &str) {
+        self.layouts.remove(layout_key);
+    }
+
+    /// Forgets the renders of all layouts
+    pub fn clear(&mut self) {
+        self.layouts.clear();
+    }
+}
+
+struct CachingWriterAcc {
+    previous: CachedRender,
+    next: CachedRender,
+    // Position of the current node
+    path: Vec<usize>,
+}
+
+impl CachingWriterAcc {
+    /// Copies the region at the current position from the previous render, along with the
+    /// regions inside of it, if its props hash is `props_hash`
+    fn reuse(&mut self, props_hash: u64) -> bool {
+        let bytes = match self.previous.regions.get(&self.path) {
+            Some(region) if region.props_hash == props_hash => region.bytes.clone(),
+            _ => return false,
+        };
+        let start = self.next.html.len();
+        self.next.html.extend_from_slice(&self.previous.html[bytes.clone()]);
+        for (path, region) in self.previous.regions.range(self.path.clone()..) {
+            if !path.starts_with(&self.path) { break; }
+            let offset = |index: usize| index - bytes.start + start;
+            self.next.regions.insert(path.clone(), Region {
+                props_hash: region.props_hash,
+                bytes: offset(region.bytes.start)..offset(region.bytes.end),
+            });
+        }
+        self.next.reused += 1;
+        true
+    }
+}
+
+/// Processor writing HTML identical to `HtmlWriter`, while recording and reusing regions
+struct CachingWriter;
+impl<'a, M> DomNodeProcessor<'a, M> for CachingWriter {
+    type Acc = CachingWriterAcc;
+    type Error = io::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, T: DomNode<M>>(acc: &mut CachingWriterAcc, node: &T) -> Result<(), io::Error> {
+            let props_hash = node.props_hash();
+            if !props_hash.is_some_and(|props_hash| acc.reuse(props_hash)) {
+                let start = acc.next.html.len();
+                write_node(acc, node)?;
+                if let Some(props_hash) = props_hash {
+                    let region = Region { props_hash, bytes: start..acc.next.html.len() };
+                    acc.next.regions.insert(acc.path.clone(), region);
+                }
+            }
+            *acc.path.last_mut().unwrap() += 1;
+            Ok(())
+        }
+        add_node::<M, T>
+    }
+}
+
+fn write_node<M, T: DomNode<M>>(acc: &mut CachingWriterAcc, node: &T) -> Result<(), io::Error> {
+    if let Some(precompiled) = node.precompiled() {
+        return acc.next.html.write_all(precompiled.html().as_bytes());
+    }
+    let tag = match node.value() {
+        DomValue::Element { tag } => tag,
+        DomValue::Text(text) => return write_escaped(&mut acc.next.html, text),
+        DomValue::OwnedText(text) => return write_escaped(&mut acc.next.html, &text),
+        DomValue::Comment(text) => return write_comment(&mut acc.next.html, text),
+        DomValue::Doctype(name) => return write!(acc.next.html, "<!DOCTYPE {}>", name),
+    };
+    write!(acc.next.html, "<{}", tag)?;
+    for attr in node.merged_attributes() {
+        write_html_attribute(&mut acc.next.html, attr.0, &attr.1)?;
+    }
+    write!(acc.next.html, ">")?;
+    if VOID_ELEMENTS.contains(&tag) && !has_children::<M, _>(node.children()) {
+        return Ok(());
+    }
+
+    acc.path.push(0);
+    let result = node.children().process_all::<CachingWriter>(acc);
+    acc.path.pop();
+    result?;
+    write!(acc.next.html, "</{}>", tag)
+}
@@ -0,0 +1,267 @@
+//! Enable with the `replay` feature.
+//!
+//! A `Recorder` keeps every message delivered to the application, serialized as JSON along
+//! with the keys of the component it came from. The recording can be exported with `to_json`,
+//! for example when a user reports a bug, and the session reconstructed later by `replay`,
+//! which passes each message to the update function in turn, starting from the initial state.
+//! Updates are deterministic as long as their effects go through an `effects::Executor`, so
+//! that a `MockExecutor` can stand in for the browser during the replay.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::KeyIter;
+//! use domafic::replay::{replay, Recorder, Replay};
+//!
+//! fn update(total: &mut i64, msg: i64, _keys: KeyIter) {
+//!     *total += msg;
+//! }
+//!
+//! // Record a session
+//! let recorder = Recorder::new();
+//! let mut total = 0;
+//! for msg in vec![5, -2, 10] {
+//!     recorder.record(&msg, KeyIter::empty()).unwrap();
+//!     update(&mut total, msg, KeyIter::empty());
+//! }
+//! let log = recorder.to_json();
+//!
+//! // Reproduce it from the log
+//! let messages = Recorder::from_json(&log).unwrap().messages();
+//! assert_eq!(replay(&messages, update, 0).unwrap(), total);
+//!
+//! // Or inspect the state after each message
+//! let mut steps = Replay::new(&messages, 0);
+//! steps.step(update).unwrap().unwrap();
+//! assert_eq!(*steps.state(), 5);
+//! ```
+//!
+//! When rendering client-side, `Recorder::updater` wraps an `Updater` so that every message
+//! is recorded before it's handled.
+
+extern crate serde;
+extern crate serde_json;
+
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+use self::serde_json::Value;
+
+use keys::{KeyIter, Keys};
+
+use std::cell::RefCell;
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+
+/// A message delivered to the application, serialized as JSON
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedMessage {
+    /// Position of the message in the session, starting at 0
+    pub sequence: u64,
+    /// Keys of the component the message came from, from the outermost inwards
+    pub keys: Vec<usize>,
+    /// The serialized message
+    pub message: Value,
+}
+
+impl RecordedMessage {
+    /// The keys of the component the message came from
+    pub fn key_iter(&self) -> KeyIter {
+        self.keys.iter().fold(Keys::new(), |keys, &key| keys.push(key as u32)).into_iter()
+    }
+}
+
+/// Error returned when a recording could not be replayed
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The recording could not be decoded
+    Malformed(serde_json::Error),
+    /// The message with the given sequence number could not be decoded as a message
+    Message(u64, serde_json::Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Malformed(ref err) => write!(f, "recording was malformed: {}", err),
+            ReplayError::Message(sequence, ref err) =>
+                write!(f, "message {} could not be decoded: {}", sequence, err),
+        }
+    }
+}
+
+impl error::Error for ReplayError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ReplayError::Malformed(ref err) | ReplayError::Message(_, ref err) => Some(err),
+        }
+    }
+}
+
+/// A shared recording of the messages delivered to an application.
+///
+/// Clones of a `Recorder` add to the same recording.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder(Rc<RefCell<Vec<RecordedMessage>>>);
+
+impl Recorder {
+    /// Creates an empty recording
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    /// Appends `msg`, sent by the component identified by `keys`, to the recording
+    pub fn record<M: Serialize>(&self, msg: &M, keys: KeyIter) -> Result<(), serde_json::Error> {
+        let message = serde_json::to_value(msg)?;
+        let mut messages = self.0.borrow_mut();
+        let sequence = messages.len() as u64;
+        messages.push(RecordedMessage { sequence, keys: keys.collect(), message });
+        Ok(())
+    }
+
+    /// The recorded messages, in the order they were delivered
+    pub fn messages(&self) -> Vec<RecordedMessage> {
+        self.0.borrow().clone()
+    }
+
+    /// The number of recorded messages
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Whether no messages have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// Removes all recorded messages
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear()
+    }
+
+    /// Encodes the recording as a JSON array of `{"sequence", "keys", "message"}` objects
+    pub fn to_json(&self) -> String {
+        let messages = self.0.borrow().iter().map(|recorded| {
+            let mut object = serde_json::Map::new();
+            object.insert("sequence".to_string(), Value::from(recorded.sequence));
+            object.insert("keys".to_string(), Value::from(recorded.keys.clone()));
+            object.insert("message".to_string(), recorded.message.clone());
+            Value::Object(object)
+        }).collect();
+        Value::Array(messages).to_string()
+    }
+
+    /// Decodes a recording produced by `to_json`
+    pub fn from_json(json: &str) -> Result<Recorder, ReplayError> {
+        let malformed = || ReplayError::Malformed(
+            <serde_json::Error as serde::de::Error>::custom("expected an array of recorded messages"));
+
+        let value: Value = serde_json::from_str(json).map_err(ReplayError::Malformed)?;
+        let entries = match value {
+            Value::Array(entries) => entries,
+            _ => return Err(malformed()),
+        };
+        let mut messages = Vec::with_capacity(entries.len());
+        for mut entry in entries {
+            let sequence = entry.get("sequence").and_then(Value::as_u64).ok_or_else(malformed)?;
+            let keys = entry.get("keys").and_then(Value::as_array).ok_or_else(malformed)?
+                .iter()
+                .map(|key| key.as_u64().map(|key| key as usize))
+                .collect::<Option<Vec<usize>>>()
+                .ok_or_else(malformed)?;
+            let message = entry.get_mut("message").map(Value::take).ok_or_else(malformed)?;
+            messages.push(RecordedMessage { sequence, keys, message });
+        }
+        Ok(Recorder(Rc::new(RefCell::new(messages))))
+    }
+
+    /// Wraps `updater` so that every message is recorded before it's passed to `updater`
+    #[cfg(all(feature = "web_render", target_os = "emscripten"))]
+    pub fn updater<U>(&self, updater: U) -> Recorded<U> {
+        Recorded { recorder: self.clone(), updater }
+    }
+}
+
+/// Reconstructs the state reached by passing each of `messages` to `update`, starting from
+/// `initial_state`
+pub fn replay<S, M, F>(messages: &[RecordedMessage], mut update: F, initial_state: S) -> Result<S, ReplayError>
+    where M: DeserializeOwned, F: FnMut(&mut S, M, KeyIter)
+{
+    let mut replay = Replay::new(messages, initial_state);
+    while let Some(result) = replay.step(&mut update) {
+        result?;
+    }
+    Ok(replay.into_state())
+}
+
+/// A replay of recorded messages which can be advanced one message at a time
+pub struct Replay<'a, S> {
+    messages: &'a [RecordedMessage],
+    position: usize,
+    state: S,
+}
+
+impl<'a, S> Replay<'a, S> {
+    /// Starts a replay of `messages` from `initial_state`
+    pub fn new(messages: &'a [RecordedMessage], initial_state: S) -> Replay<'a, S> {
+        Replay { messages, position: 0, state: initial_state }
+    }
+
+    /// Passes the next message to `update`, returning `None` once every message has been
+    /// replayed
+    pub fn step<M, F>(&mut self, mut update: F) -> Option<Result<(), ReplayError>>
+        where M: DeserializeOwned, F: FnMut(&mut S, M, KeyIter)
+    {
+        let recorded = self.messages.get(self.position)?;
+        self.position += 1;
+        Some(match serde_json::from_value(recorded.message.clone()) {
+            Ok(msg) => {
+                update(&mut self.state, msg, recorded.key_iter());
+                Ok(())
+            },
+            Err(err) => Err(ReplayError::Message(recorded.sequence, err)),
+        })
+    }
+
+    /// The number of messages replayed so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The state reached by the messages replayed so far
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Ends the replay, returning the state reached
+    pub fn into_state(self) -> S {
+        self.state
+    }
+}
+
+/// An `Updater` which records every message before handling it.
+///
+/// Created by `Recorder::updater`.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub struct Recorded<U> {
+    recorder: Recorder,
+    updater: U,
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+mod web {
+    use super::{Recorded, serde};
+    use keys::KeyIter;
+    use web_render::{JsIo, Updater};
+
+    impl<S, M, U> Updater<S, M> for Recorded<U>
+        where M: serde::Serialize, U: Updater<S, M>
+    {
+        fn update(&self, state: &mut S, msg: M, keys: KeyIter, js_io: &dyn JsIo<M>) {
+            if let Err(err) = self.recorder.record(&msg, keys.clone()) {
+                panic!("Failed to record message: {}", err);
+            }
+            self.updater.update(state, msg, keys, js_io);
+        }
+    }
+}
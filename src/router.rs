@@ -0,0 +1,905 @@
+//! Typed routes, and links which navigate between them without reloading the page.
+//!
+//! A `Route` is usually an enum with a variant for each page of the application, which can be
+//! converted to and from the path of its URL. `link` renders an `<a href>` to a route, so that
+//! pages rendered on the server link to each other and can be crawled. When rendering
+//! client-side, clicking a `link` doesn't load a new page: the URL is updated through the
+//! History API, and the route is delivered to the application as a message converted with
+//! `From`. Clicks which open the link somewhere else, with a modifier key or a button other
+//! than the primary one, are left to the browser.
+//!
+//! Routes with parameters are declared with `routes!`, which generates an enum with a variant
+//! for each route pattern, such as `"/users/{id}?tab={tab}"`, along with its `Route`
+//! implementation. Each parameter is a typed field of its variant, parsed from and written to
+//! the URL through `RouteParam`, so neither matching nor building URLs handles strings.
+//!
+//! `resolve` matches a path to a `RouteOutcome`: the route, a redirect to another route, or
+//! `NotFound`. Routes redirect by returning their target from `Route::redirect`, which
+//! `routes!` declares with a second `=>`. Server-side, `RouteOutcome::status_code` and
+//! `RouteOutcome::location` give the HTTP response for the outcome, so that the same routes
+//! answer with 301s and 404s. Client-side, `current_route` resolves the page's URL, replacing it
+//! with the target of a redirect, and links to a redirected route go straight to its target.
+//!
+//! `nav_link` marks links to the current route and its ancestors as active, and `breadcrumbs`
+//! lists links to each of them.
+//!
+//! Large applications can nest routes rather than declaring every page in one enum: a route
+//! whose pattern ends in a rest parameter, such as `/orgs/{org}/{page..}`, matches the rest of
+//! the path to a child route enum. The parent renders the chrome its children share, and
+//! `outlet` marks where the child route is rendered within it.
+//!
+//! Routes implementing `RouteData` name the URL their page loads its data from, so that a
+//! `prefetch_link` can load it when the user hovers over the link or scrolls it into view,
+//! before they click it. The prefetched response is stored in the `JsIo` HTTP cache, where the
+//! page's own request finds it.
+//!
+//! Routes implementing `RouteTitle` give each page a title, which may be computed from the
+//! application's state. Server-side, `Document::for_route` puts it in the page's `<title>`.
+//! Client-side, wrapping the renderer with `with_title` updates `document.title` whenever the
+//! application navigates, so that both render modes title a page the same way.
+//!
+//! Pass `on_route_change` to `web_render::run_with_subscriptions` to also be told when the user
+//! moves through the history with the back and forward buttons.
+//!
+//! Example:
+//!
+//! ```rust
+//! #[macro_use]
+//! extern crate domafic;
+//!
+//! use domafic::router::{link, resolve, Route, RouteOutcome};
+//!
+//! routes! {
+//!     #[derive(Debug, Clone, PartialEq)]
+//!     pub enum Page {
+//!         Home => "/",
+//!         Post { id: u32, comments: Option<bool> } => "/posts/{id}?comments={comments}",
+//!         OldPost { id: u32 } => "/blog/{id}" => Page::Post { id: *id, comments: None },
+//!     }
+//! }
+//!
+//! enum Msg { Navigate(Page) }
+//!
+//! impl From<Page> for Msg {
+//!     fn from(page: Page) -> Msg { Msg::Navigate(page) }
+//! }
+//!
+//! fn main() {
+//!     let first_post = link::<Msg, _, _>(Page::Post { id: 1, comments: None }, "First post");
+//!     assert_eq!(first_post.to_string(), "<a href=\"/posts/1\" data-domafic-link=\"\">First post</a>");
+//!
+//!     assert_eq!(Page::from_path("/posts/1?comments=true"), Some(Page::Post { id: 1, comments: Some(true) }));
+//!     assert_eq!(Page::from_path("/posts/first"), None);
+//!
+//!     let old = resolve::<Page>("/blog/1");
+//!     assert_eq!(old, RouteOutcome::Redirect(Page::Post { id: 1, comments: None }));
+//!     assert_eq!((old.status_code(), old.location()), (301, Some("/posts/1".to_string())));
+//!     assert_eq!(resolve::<Page>("/about").status_code(), 404);
+//! }
+//! ```
+
+use {DomNodes, Event, KeyValue, Listener, Listeners};
+use AttributeValue::{OwnedStr, Str};
+use processors::{DomNodeProcessor, EmptyListeners, ListenerProcessor};
+use subscriptions::{on_pop_state, Subscription};
+use tags::{a, attributes, li, Tag};
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+use web_render::{location_path, replace_location, set_title, Renderer};
+
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// Attribute marking the links whose clicks are handled by the application
+pub const LINK_ATTRIBUTE: &str = "data-domafic-link";
+
+/// Attribute holding the URL a `prefetch_link` prefetches
+pub const PREFETCH_ATTRIBUTE: &str = "data-domafic-prefetch";
+
+/// Attribute holding when a `prefetch_link` prefetches, `hover` or `visible`
+pub const PREFETCH_ON_ATTRIBUTE: &str = "data-domafic-prefetch-on";
+
+/// Milliseconds for which prefetched data is fresh. Routes' data is loaded with
+/// `JsIo::http_cached` and `CachePolicy::MaxAge` of at most this long to use prefetched
+/// responses, and a URL isn't prefetched again while its response is fresh.
+pub const PREFETCH_MAX_AGE_MILLIS: u32 = 30_000;
+
+/// Class of a `nav_link` to the current route or one of its ancestors
+pub const ACTIVE_CLASS: &str = "active";
+
+/// A page of the application, identified by the path of its URL
+pub trait Route: Sized {
+    /// The path of the route's URL, such as `/posts/1`, which may include a query string
+    fn path(&self) -> String;
+
+    /// The route identified by `path`, or `None` if no route matches it
+    fn from_path(path: &str) -> Option<Self>;
+
+    /// The route which this route has moved to, if any, such as the new path of a renamed page
+    fn redirect(&self) -> Option<Self> { None }
+}
+
+/// A route whose page loads data over HTTP, which links to the route can prefetch
+pub trait RouteData: Route {
+    /// The URL of the data the route's page loads with a "GET" request, if it loads any
+    fn data_url(&self) -> Option<String>;
+}
+
+/// When a `prefetch_link` prefetches the data of its route
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Prefetch {
+    /// When the pointer moves over the link, or it's focused with the keyboard
+    Hover,
+    /// When the link is first scrolled into view
+    Visible,
+}
+
+impl Prefetch {
+    /// The value of `PREFETCH_ON_ATTRIBUTE`
+    fn as_str(self) -> &'static str {
+        match self {
+            Prefetch::Hover => "hover",
+            Prefetch::Visible => "visible",
+        }
+    }
+}
+
+/// A route whose page has a title, which may be computed from the application's state, such as
+/// the name of the post the page shows.
+///
+/// Routes with a fixed title can ignore `state`.
+pub trait RouteTitle<S>: Route {
+    /// The title of the route's page
+    fn title(&self, state: &S) -> String;
+}
+
+/// Most redirects followed from one route before `resolve` gives up on a redirect loop
+pub const MAX_REDIRECTS: usize = 16;
+
+/// The result of matching a path to a route
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum RouteOutcome<R> {
+    /// The path matched this route
+    Matched(R),
+    /// The path matched a route which redirects to this one, after following every redirect
+    Redirect(R),
+    /// No route matched this path
+    NotFound(String),
+}
+
+impl<R: Route> RouteOutcome<R> {
+    /// The route to render, which is `None` for `NotFound`
+    pub fn route(&self) -> Option<&R> {
+        match *self {
+            RouteOutcome::Matched(ref route) | RouteOutcome::Redirect(ref route) => Some(route),
+            RouteOutcome::NotFound(_) => None,
+        }
+    }
+
+    /// The HTTP status of a response to the path: 200, 301 for a redirect, or 404
+    pub fn status_code(&self) -> u16 {
+        match *self {
+            RouteOutcome::Matched(_) => 200,
+            RouteOutcome::Redirect(_) => 301,
+            RouteOutcome::NotFound(_) => 404,
+        }
+    }
+
+    /// The `Location` header of a redirect, which is the path of its target
+    pub fn location(&self) -> Option<String> {
+        match *self {
+            RouteOutcome::Redirect(ref target) => Some(target.path()),
+            _ => None,
+        }
+    }
+}
+
+/// Follows the redirects from `route`, returning the last route, which doesn't redirect, or
+/// the route reached after `MAX_REDIRECTS` as an error, which is usually a redirect loop
+fn try_follow_redirects<R: Route>(route: R) -> Result<R, R> {
+    let mut route = route;
+    for _ in 0..MAX_REDIRECTS {
+        match route.redirect() {
+            Some(target) => route = target,
+            None => return Ok(route),
+        }
+    }
+    Err(route)
+}
+
+/// Follows the redirects from `route`, returning the last route, which doesn't redirect.
+///
+/// Panics if there are more than `MAX_REDIRECTS`, which is usually a redirect loop.
+fn follow_redirects<R: Route>(route: R) -> R {
+    try_follow_redirects(route).unwrap_or_else(|route|
+        panic!("route `{}` redirects more than {} times", route.path(), MAX_REDIRECTS))
+}
+
+/// The route a link to `route` goes to: the target of its redirects, or `route` itself if
+/// they loop, so that rendering a view never panics
+fn link_target<R: Route + Clone>(route: R) -> R {
+    try_follow_redirects(route.clone()).unwrap_or(route)
+}
+
+/// Matches `path`, which may include a query string, to a route, following any redirects.
+///
+/// Panics if a route redirects more than `MAX_REDIRECTS` times.
+pub fn resolve<R: Route>(path: &str) -> RouteOutcome<R> {
+    match R::from_path(path) {
+        Some(route) => match route.redirect() {
+            Some(target) => RouteOutcome::Redirect(follow_redirects(target)),
+            None => RouteOutcome::Matched(route),
+        },
+        None => RouteOutcome::NotFound(path.to_string()),
+    }
+}
+
+/// Resolves the page's URL, and replaces it with the path of the target if it redirects,
+/// without adding an entry to the history
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub fn current_route<R: Route>() -> RouteOutcome<R> {
+    let outcome = resolve(&location_path());
+    if let Some(location) = outcome.location() {
+        replace_location(&location);
+    }
+    outcome
+}
+
+// Subscriptions are only handled client-side
+#[cfg(not(all(feature = "web_render", target_os = "emscripten")))]
+fn current_route<R: Route>() -> RouteOutcome<R> {
+    unreachable!("subscriptions are only handled when rendering client-side")
+}
+
+/// A value of a route parameter, which can be parsed from and written to a URL
+pub trait RouteParam: Sized {
+    /// Parses the value of a parameter, after it's been decoded from the URL
+    fn from_param(value: &str) -> Option<Self>;
+
+    /// The value of the parameter, which is encoded when it's written to the URL
+    fn to_param(&self) -> String;
+
+    /// The value of a query parameter which is missing from the URL, or `None` if the route
+    /// doesn't match without it
+    fn missing() -> Option<Self> { None }
+
+    /// Whether the parameter is left out of the query string
+    fn is_missing(&self) -> bool { false }
+}
+
+macro_rules! impl_route_params {
+    ($($ty:ty),*) => { $(
+        impl RouteParam for $ty {
+            fn from_param(value: &str) -> Option<$ty> { <$ty as FromStr>::from_str(value).ok() }
+            fn to_param(&self) -> String { self.to_string() }
+        }
+    )* }
+}
+
+impl_route_params!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, bool, char, String);
+
+/// An optional query parameter, which is `None` when it's missing from the URL
+impl<T: RouteParam> RouteParam for Option<T> {
+    fn from_param(value: &str) -> Option<Option<T>> { T::from_param(value).map(Some) }
+    fn to_param(&self) -> String { self.as_ref().map_or(String::new(), T::to_param) }
+    fn missing() -> Option<Option<T>> { Some(None) }
+    fn is_missing(&self) -> bool { self.is_none() }
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+enum Segment {
+    Literal(&'static str),
+    Param(&'static str),
+    // Matches the rest of the path, along with the query string
+    Rest(&'static str),
+}
+
+/// A pattern matching the paths of a route, such as `/users/{id}?tab={tab}`.
+///
+/// Segments of the path in braces are parameters, which match any non-empty segment. The query
+/// string lists parameters as `key={name}`, which may appear in any order, and other keys in
+/// the URL's query string are ignored.
+///
+/// The last segment may instead be a rest parameter, such as `/orgs/{org}/{page..}`, whose
+/// value is the remainder of the path, starting with `/`, followed by the whole query string.
+/// Its value is usually a child route, which is matched against the rest of the path and sees
+/// the query parameters of its parent.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct RoutePattern {
+    segments: Vec<Segment>,
+    // (key, parameter name) of each query parameter
+    query: Vec<(&'static str, &'static str)>,
+}
+
+fn parameter_name(segment: &'static str) -> Option<&'static str> {
+    if segment.starts_with('{') && segment.ends_with('}') && segment.len() > 2 {
+        Some(segment[1..segment.len() - 1].trim())
+    } else {
+        None
+    }
+}
+
+/// Splits `path` into its segments, ignoring a trailing slash
+fn segments(path: &str) -> Vec<&str> {
+    let path = path.trim_start_matches('/').trim_end_matches('/');
+    if path.is_empty() { Vec::new() } else { path.split('/').collect() }
+}
+
+impl RoutePattern {
+    /// Parses `pattern`.
+    ///
+    /// Panics if a query parameter isn't of the form `key={name}`, or a rest parameter isn't the
+    /// last segment of the path.
+    pub fn new(pattern: &'static str) -> RoutePattern {
+        let (path, query) = match pattern.find('?') {
+            Some(index) => (&pattern[..index], &pattern[index + 1..]),
+            None => (pattern, ""),
+        };
+        let segments: Vec<Segment> = segments(path).into_iter()
+            .map(|segment| match parameter_name(segment) {
+                Some(name) if name.ends_with("..") => Segment::Rest(name[..name.len() - 2].trim()),
+                Some(name) => Segment::Param(name),
+                None => Segment::Literal(segment),
+            })
+            .collect();
+        let rest = segments.iter().position(|segment| matches!(*segment, Segment::Rest(_)));
+        if rest.is_some_and(|rest| rest + 1 != segments.len()) {
+            panic!("the rest parameter of route `{}` must be its last segment", pattern);
+        }
+        let query = query.split('&').filter(|pair| !pair.is_empty()).map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap();
+            match parts.next().and_then(parameter_name) {
+                Some(name) => (key, name),
+                None => panic!("query parameter `{}` of route `{}` must look like `key={{name}}`", pair, pattern),
+            }
+        }).collect();
+        RoutePattern { segments, query }
+    }
+
+    /// The parameters of `path`, which may include a query string, if it matches the pattern
+    pub fn matches(&self, path: &str) -> Option<Params> {
+        let path = path.split('#').next().unwrap();
+        let (path, query) = match path.find('?') {
+            Some(index) => (&path[..index], &path[index + 1..]),
+            None => (path, ""),
+        };
+
+        let path_segments = segments(path);
+        let mut values = Vec::new();
+        let fixed = match self.segments.last() {
+            Some(&Segment::Rest(name)) => {
+                let fixed = self.segments.len() - 1;
+                if path_segments.len() < fixed {
+                    return None;
+                }
+                // The child matches its own segments, so they're left encoded
+                let mut rest = format!("/{}", path_segments[fixed..].join("/"));
+                if !query.is_empty() {
+                    rest.push('?');
+                    rest.push_str(query);
+                }
+                values.push((name, rest));
+                fixed
+            },
+            _ if path_segments.len() != self.segments.len() => return None,
+            _ => self.segments.len(),
+        };
+        for (segment, value) in self.segments[..fixed].iter().zip(path_segments) {
+            let value = decode(value, false)?;
+            match *segment {
+                Segment::Literal(literal) => if literal != value { return None; },
+                Segment::Param(name) => {
+                    if value.is_empty() { return None; }
+                    values.push((name, value));
+                },
+                Segment::Rest(_) => unreachable!(),
+            }
+        }
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let mut parts = pair.splitn(2, '=');
+            let key = decode(parts.next().unwrap(), true)?;
+            let value = decode(parts.next().unwrap_or(""), true)?;
+            let param = self.query.iter().find(|param| param.0 == key);
+            // The first value given for a key is used
+            if let Some(&(_, name)) = param {
+                if !values.iter().any(|value| value.0 == name) {
+                    values.push((name, value));
+                }
+            }
+        }
+        Some(Params { values })
+    }
+
+    /// Builds a path matching the pattern from the value of each parameter, leaving out the
+    /// query parameters whose value is `None`.
+    ///
+    /// Panics if a parameter of the path has no value.
+    pub fn to_path(&self, values: &[(&str, Option<String>)]) -> String {
+        let value = |name: &str| values.iter().find(|value| value.0 == name).and_then(|value| value.1.as_ref());
+        let mut path = String::new();
+        let mut child_query = None;
+        for segment in &self.segments {
+            let name = match *segment {
+                Segment::Literal(literal) => {
+                    path.push('/');
+                    path.push_str(literal);
+                    continue;
+                },
+                Segment::Param(name) | Segment::Rest(name) => name,
+            };
+            let value = match value(name) {
+                Some(value) => value,
+                None => panic!("no value was given for route parameter `{}`", name),
+            };
+            if let Segment::Rest(_) = *segment {
+                let (child_path, query) = match value.find('?') {
+                    Some(index) => (&value[..index], Some(&value[index + 1..])),
+                    None => (&value[..], None),
+                };
+                path.push_str(child_path.trim_end_matches('/'));
+                child_query = query.filter(|query| !query.is_empty());
+            } else {
+                path.push('/');
+                encode(&mut path, value);
+            }
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        let mut separator = '?';
+        if let Some(query) = child_query {
+            path.push(separator);
+            path.push_str(query);
+            separator = '&';
+        }
+        for &(key, name) in &self.query {
+            if let Some(value) = value(name) {
+                path.push(separator);
+                encode(&mut path, key);
+                path.push('=');
+                encode(&mut path, value);
+                separator = '&';
+            }
+        }
+        path
+    }
+}
+
+/// The parameters of a path matched by a `RoutePattern`
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Params {
+    values: Vec<(&'static str, String)>,
+}
+
+impl Params {
+    /// The decoded value of the parameter `name`, or `None` if it was missing
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.iter().find(|value| value.0 == name).map(|value| &*value.1)
+    }
+
+    /// Parses the value of the parameter `name`, returning `None` if it's malformed, or if it
+    /// was missing and `T` has no value for missing parameters
+    pub fn parse<T: RouteParam>(&self, name: &str) -> Option<T> {
+        match self.get(name) {
+            Some(value) => T::from_param(value),
+            None => T::missing(),
+        }
+    }
+}
+
+/// Writes `value` to `out`, escaping every character except those which are unreserved in
+/// URLs
+fn encode(out: &mut String, value: &str) {
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+}
+
+/// Decodes the escapes in `value`, and `+` as a space in query strings, returning `None` if an
+/// escape is malformed or the result isn't UTF-8
+fn decode(value: &str, query: bool) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value.get(i + 1..i + 3)?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+                continue;
+            },
+            b'+' if query => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+// Used by `routes!`
+#[doc(hidden)]
+pub fn param_value<T: RouteParam>(value: &T) -> Option<String> {
+    if value.is_missing() { None } else { Some(value.to_param()) }
+}
+
+/// Declares an enum with a variant for each of the given routes, and implements `Route` for it.
+///
+/// Each variant is followed by the `RoutePattern` of its paths. A variant's fields are the
+/// parameters of its pattern, named the same as the field, with types implementing
+/// `RouteParam`. Paths are matched against the patterns in order, and a path whose parameters
+/// can't be parsed matches none of them. Each pattern is parsed once per thread, when it's
+/// first used. Attributes, such as doc comments and derives, are passed through to the enum and
+/// its variants.
+///
+/// A route which redirects is followed by a second `=>` and its target, an expression in which
+/// the variant's fields are bound by reference.
+///
+/// The enum also implements `RouteParam`, so that it can be the type of a rest parameter,
+/// nesting its routes inside another route.
+///
+/// See the `router` module for an example.
+#[macro_export]
+macro_rules! routes {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident $({ $($field:ident: $ty:ty),* $(,)* })? => $pattern:expr
+                $(=> $target:expr)?
+            ),* $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $(
+                $(#[$variant_attr])*
+                $variant $({ $($field: $ty),* })?,
+            )*
+        }
+
+        impl $crate::router::Route for $name {
+            fn path(&self) -> ::std::string::String {
+                match *self {
+                    $(
+                        $name::$variant $({ $(ref $field),* })? => {
+                            // Parsed once, on the first use of the pattern
+                            thread_local!(static PATTERN: $crate::router::RoutePattern =
+                                $crate::router::RoutePattern::new($pattern));
+                            PATTERN.with(|pattern| pattern.to_path(&[
+                                $($((stringify!($field), $crate::router::param_value($field)),)*)?
+                            ]))
+                        },
+                    )*
+                }
+            }
+
+            fn from_path(path: &str) -> ::std::option::Option<$name> {
+                $({
+                    thread_local!(static PATTERN: $crate::router::RoutePattern =
+                        $crate::router::RoutePattern::new($pattern));
+                    if let Some(params) = PATTERN.with(|pattern| pattern.matches(path)) {
+                        #[allow(unused_variables)]
+                        fn parse(params: &$crate::router::Params) -> ::std::option::Option<$name> {
+                            Some($name::$variant $({
+                                $($field: params.parse::<$ty>(stringify!($field))?),*
+                            })?)
+                        }
+                        if let Some(route) = parse(&params) {
+                            return Some(route);
+                        }
+                    }
+                })*
+                None
+            }
+
+            #[allow(unused_variables)]
+            fn redirect(&self) -> ::std::option::Option<$name> {
+                match *self {
+                    $(
+                        $name::$variant $({ $(ref $field),* })? => None $(.or(Some($target)))?,
+                    )*
+                }
+            }
+        }
+
+        // Nests the routes in the rest parameter of a parent route
+        impl $crate::router::RouteParam for $name {
+            fn from_param(value: &str) -> ::std::option::Option<$name> {
+                $crate::router::Route::from_path(value)
+            }
+
+            fn to_param(&self) -> ::std::string::String {
+                $crate::router::Route::path(self)
+            }
+        }
+    };
+}
+
+/// A listener producing the message for a link's route when the link is clicked
+pub struct LinkListener<M, R> {
+    route: R,
+    _marker: PhantomData<M>,
+}
+
+impl<M: From<R>, R: Clone> Listeners<M> for LinkListener<M, R> {
+    fn process_all<'a, P: ListenerProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M: From<R>, R: Clone> Listener<M> for LinkListener<M, R> {
+    fn event_type_handled(&self) -> &'static str {
+        "click"
+    }
+    fn handle_event(&self, _event: Event) -> M {
+        M::from(self.route.clone())
+    }
+}
+
+/// An `<a>` element linking to `route`, containing `children`.
+///
+/// When rendering client-side, a click with the primary button and no modifier keys pushes the
+/// route's path onto the history and delivers `M::from(route)` to the application instead of
+/// loading the page. Other clicks, such as ones opening the link in a new tab, never reach the
+/// application. A link to a route which redirects links to its target instead, unless its
+/// redirects loop, in which case it links to the route itself.
+pub fn link<M, R, C>(route: R, children: C) -> Tag<M, C, [KeyValue; 2], LinkListener<M, R>>
+    where M: From<R>, R: Route + Clone, C: DomNodes<M>
+{
+    let route = link_target(route);
+    let href = ("href", OwnedStr(route.path()));
+    a((
+        attributes([href, (LINK_ATTRIBUTE, Str(""))]),
+        LinkListener { route, _marker: PhantomData },
+        children,
+    ))
+}
+
+/// A `link` to `route` which prefetches the route's `RouteData::data_url`, if it has one, when
+/// the user hovers over the link or it scrolls into view, as chosen by `prefetch`.
+///
+/// When rendering client-side, the response is stored in the HTTP cache used by
+/// `JsIo::http_cached`, so the page's own request with `CachePolicy::MaxAge` is answered
+/// without waiting for the network. A URL is only prefetched once while its response is fresh,
+/// however many links name it, and isn't prefetched while the page's own request is in flight.
+/// Failed prefetches are ignored.
+pub fn prefetch_link<M, R, C>(route: R, prefetch: Prefetch, children: C) -> NavLink<M, R, C>
+    where M: From<R>, R: RouteData + Clone, C: DomNodes<M>
+{
+    let route = link_target(route);
+    let mut link_attributes = vec![("href", OwnedStr(route.path())), (LINK_ATTRIBUTE, Str(""))];
+    if let Some(url) = route.data_url() {
+        link_attributes.push((PREFETCH_ATTRIBUTE, OwnedStr(url)));
+        link_attributes.push((PREFETCH_ON_ATTRIBUTE, Str(prefetch.as_str())));
+    }
+    a((
+        attributes(link_attributes),
+        LinkListener { route, _marker: PhantomData },
+        children,
+    ))
+}
+
+/// The path of `route` without its query string or a trailing slash
+fn path_without_query<R: Route>(route: &R) -> String {
+    let path = route.path();
+    let path = path.split('?').next().unwrap().trim_end_matches('/');
+    if path.is_empty() { "/".to_string() } else { path.to_string() }
+}
+
+/// Whether `current` is `route` or is below it in the hierarchy of paths, such as
+/// `/users/ada/settings` below `/users/ada`, ignoring query strings.
+///
+/// The root route `/` is only active when it's the current route, since every route is below
+/// it.
+pub fn is_active<R: Route>(route: &R, current: &R) -> bool {
+    let path = path_without_query(route);
+    let current = path_without_query(current);
+    current == path || path != "/" && current.starts_with(&path) && current[path.len()..].starts_with('/')
+}
+
+/// A link created by `nav_link` or `prefetch_link`
+pub type NavLink<M, R, C> = Tag<M, C, Vec<KeyValue>, LinkListener<M, R>>;
+
+/// A list item created by `breadcrumbs`
+pub type Breadcrumb<M, R, C> = Tag<M, NavLink<M, R, C>, [KeyValue; 0], EmptyListeners>;
+
+/// A `link` to `route`, which has the class `ACTIVE_CLASS` when `is_active(&route, current)`,
+/// and `aria-current="page"` when it links to the current page.
+///
+/// Other classes given with `DomNode::with_attributes` are merged with the active class.
+pub fn nav_link<M, R, C>(route: R, current: &R, children: C) -> NavLink<M, R, C>
+    where M: From<R>, R: Route + Clone, C: DomNodes<M>
+{
+    let route = link_target(route);
+    let mut link_attributes = vec![("href", OwnedStr(route.path())), (LINK_ATTRIBUTE, Str(""))];
+    if path_without_query(&route) == path_without_query(current) {
+        link_attributes.push(("aria-current", Str("page")));
+    }
+    if is_active(&route, current) {
+        link_attributes.push(("class", Str(ACTIVE_CLASS)));
+    }
+    a((
+        attributes(link_attributes),
+        LinkListener { route, _marker: PhantomData },
+        children,
+    ))
+}
+
+/// The routes matched by each ancestor of `route`'s path, from the root down, followed by
+/// `route` itself.
+///
+/// Ancestor paths which match no route, or a route which redirects, are skipped.
+pub fn ancestors<R: Route + Clone>(route: &R) -> Vec<R> {
+    let path = path_without_query(route);
+    let mut ancestors: Vec<R> = path.match_indices('/')
+        .map(|(index, _)| if index == 0 { "/" } else { &path[..index] })
+        .filter(|&ancestor| ancestor != path)
+        .filter_map(R::from_path)
+        .filter(|ancestor| ancestor.redirect().is_none())
+        .collect();
+    ancestors.push(route.clone());
+    ancestors
+}
+
+/// A list item containing a `nav_link` for each of the `ancestors` of `current`, labelled by
+/// `label`, so that the last one is marked as the current page.
+///
+/// These are usually placed in an `ol` inside a `<nav aria-label="Breadcrumb">`.
+///
+/// Example:
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate domafic;
+///
+/// use domafic::router::{breadcrumbs, Route};
+/// use domafic::tags::ol;
+///
+/// routes! {
+///     #[derive(Debug, Clone, PartialEq)]
+///     pub enum Page {
+///         Home => "/",
+///         Docs => "/docs",
+///         Section { name: String } => "/docs/{name}",
+///     }
+/// }
+///
+/// struct Navigate(Page);
+///
+/// impl From<Page> for Navigate {
+///     fn from(page: Page) -> Navigate { Navigate(page) }
+/// }
+///
+/// fn main() {
+///     let current = Page::Section { name: "routing".to_string() };
+///     let trail = ol(breadcrumbs::<Navigate, _, _, _>(&current, |page| match *page {
+///         Page::Home => "Home".to_string(),
+///         Page::Docs => "Docs".to_string(),
+///         Page::Section { ref name } => name.clone(),
+///     }));
+///     assert_eq!(trail.to_string(), "<ol>\
+///         <li><a href=\"/\" data-domafic-link=\"\">Home</a></li>\
+///         <li><a href=\"/docs\" data-domafic-link=\"\" class=\"active\">Docs</a></li>\
+///         <li><a href=\"/docs/routing\" data-domafic-link=\"\" aria-current=\"page\" class=\"active\">routing</a></li>\
+///         </ol>");
+/// }
+/// ```
+pub fn breadcrumbs<M, R, C, F>(current: &R, label: F) -> Vec<Breadcrumb<M, R, C>>
+    where M: From<R>, R: Route + Clone, C: DomNodes<M>, F: Fn(&R) -> C
+{
+    ancestors(current).into_iter().map(|route| {
+        let children = label(&route);
+        li(nav_link(route, current, children))
+    }).collect()
+}
+
+/// The place in a parent route's shared chrome where its child route is rendered.
+///
+/// A layout which takes an `Outlet`, rather than any `DomNodes`, shows in its signature which
+/// part of the page changes with the child route.
+pub struct Outlet<M, N> {
+    node: N,
+    _marker: PhantomData<M>,
+}
+
+impl<M, N: DomNodes<M>> DomNodes<M> for Outlet<M, N> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        self.node.process_all::<P>(acc)
+    }
+}
+
+/// Renders the child route `route` with `render`, to be placed in the chrome of its parent.
+///
+/// Example:
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate domafic;
+///
+/// use domafic::{DomNode, DomNodes};
+/// use domafic::router::{outlet, Outlet, Route};
+/// use domafic::tags::{div, h1, p};
+///
+/// routes! {
+///     pub enum OrgPage {
+///         Overview => "/",
+///         Repo { name: String } => "/repos/{name}",
+///     }
+/// }
+///
+/// routes! {
+///     pub enum Page {
+///         Org { org: String, page: OrgPage } => "/orgs/{org}/{page..}",
+///     }
+/// }
+///
+/// // Shared by every page of an organization
+/// fn chrome<N: DomNodes<()>>(org: &str, content: Outlet<(), N>) -> impl DomNode<()> {
+///     div((h1(org.to_string()), content))
+/// }
+///
+/// fn main() {
+///     let page = Page::from_path("/orgs/rust/repos/domafic").unwrap();
+///     let Page::Org { ref org, ref page } = page;
+///     let rendered = chrome(org, outlet(page, |page| match *page {
+///         OrgPage::Overview => p(format!("About {}", org)),
+///         OrgPage::Repo { ref name } => p(format!("{}/{}", org, name)),
+///     }));
+///     assert_eq!(rendered.displayable().to_string(), "<div><h1>rust</h1><p>rust/domafic</p></div>");
+/// }
+/// ```
+pub fn outlet<M, R, N, F>(route: &R, render: F) -> Outlet<M, N>
+    where R: Route, N: DomNodes<M>, F: FnOnce(&R) -> N
+{
+    Outlet { node: render(route), _marker: PhantomData }
+}
+
+/// Subscribes to the user moving through the session history, such as with the back and
+/// forward buttons.
+///
+/// `f` receives the outcome of resolving the page's new URL, as by `current_route`.
+pub fn on_route_change<M, R, F>(f: F) -> Subscription<M>
+    where R: Route, F: Fn(RouteOutcome<R>) -> M + 'static
+{
+    on_pop_state(move || f(current_route()))
+}
+
+/// A renderer which sets `document.title` to the title of the current route after rendering,
+/// as returned by `with_title`
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub struct TitledRenderer<R, G, Rn> {
+    route: G,
+    renderer: Rn,
+    _marker: PhantomData<R>,
+}
+
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+impl<S, M, R, G, Rn> Renderer<S, M> for TitledRenderer<R, G, Rn>
+    where R: RouteTitle<S>, G: Fn(&S) -> &R, Rn: Renderer<S, M>
+{
+    type Rendered<'a> = Rn::Rendered<'a> where S: 'a;
+
+    fn render<'a>(&self, state: &'a S) -> Self::Rendered<'a> {
+        let rendered = self.renderer.render(state);
+        set_title(&(self.route)(state).title(state));
+        rendered
+    }
+}
+
+/// Wraps `renderer` so that `document.title` follows the title of the route returned by
+/// `route`, which is usually a field of the state.
+///
+/// The title is updated after every render, so it changes along with the page, whether the
+/// application navigated through a `link`, `on_route_change` or an update of its own.
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub fn with_title<S, R, G, Rn>(route: G, renderer: Rn) -> TitledRenderer<R, G, Rn>
+    where R: RouteTitle<S>, G: Fn(&S) -> &R
+{
+    TitledRenderer { route, renderer, _marker: PhantomData }
+}
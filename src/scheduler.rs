@@ -0,0 +1,173 @@
+//! Scheduling the runtime's callbacks: re-renders, timeouts and animation frames.
+//!
+//! The web runtime doesn't call the browser's timers directly, but goes through a `Scheduler`,
+//! which `JsIo::scheduler` exposes to updates. When rendering client-side, this is the
+//! `EmscriptenScheduler`, which uses `setTimeout`, `requestAnimationFrame` and emscripten's main
+//! loop. A `TestScheduler` runs the same callbacks natively against a virtual clock, so code
+//! which waits for timeouts or frames can be tested without a browser, and other backends can
+//! drive the runtime by implementing `Scheduler`.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::scheduler::{Scheduler, TestScheduler};
+//! use std::cell::RefCell;
+//! use std::rc::Rc;
+//!
+//! let scheduler = TestScheduler::new();
+//! let log = Rc::new(RefCell::new(Vec::new()));
+//!
+//! let timeout_log = log.clone();
+//! scheduler.set_timeout(100, Box::new(move || timeout_log.borrow_mut().push("timeout")));
+//! for _ in 0..3 {
+//!     let render_log = log.clone();
+//!     scheduler.schedule_render(Box::new(move || render_log.borrow_mut().push("render")));
+//! }
+//!
+//! // Re-renders scheduled before a frame are coalesced into one
+//! assert_eq!(scheduler.run_frame(), 1);
+//! scheduler.advance(99);
+//! assert_eq!(*log.borrow(), vec!["render"]);
+//! scheduler.advance(1);
+//! assert_eq!(*log.borrow(), vec!["render", "timeout"]);
+//! ```
+
+use std::cell::{Cell, RefCell};
+
+/// Schedules callbacks on the runtime's event loop
+pub trait Scheduler {
+    /// Calls `render` before the next frame is drawn. A render scheduled while another is
+    /// waiting replaces it, so that several updates in a row are only drawn once.
+    fn schedule_render(&self, render: Box<dyn FnOnce()>);
+
+    /// Calls `callback` once `millis` milliseconds have passed
+    fn set_timeout(&self, millis: u32, callback: Box<dyn FnOnce()>);
+
+    /// Calls `callback` before the next frame is drawn
+    fn request_frame(&self, callback: Box<dyn FnOnce()>);
+
+    /// Hands control to the event loop, which calls scheduled callbacks as they become due.
+    ///
+    /// The emscripten loop never returns. Other schedulers may return once no callbacks are
+    /// left.
+    fn run_main_loop(&self);
+}
+
+type Callback = Box<dyn FnOnce()>;
+
+struct Timeout {
+    due: u64,
+    // Orders timeouts which are due at the same time by when they were set
+    sequence: u64,
+    callback: Callback,
+}
+
+/// A `Scheduler` which runs callbacks natively against a virtual clock, for tests.
+///
+/// Nothing runs until the test calls `advance`, `run_frame` or `run_main_loop`. Callbacks may
+/// schedule further callbacks through a shared reference to the scheduler, such as an `Rc`.
+#[derive(Default)]
+pub struct TestScheduler {
+    now: Cell<u64>,
+    sequence: Cell<u64>,
+    timeouts: RefCell<Vec<Timeout>>,
+    // `None` marks the position of the scheduled render among the frame callbacks
+    frames: RefCell<Vec<Option<Callback>>>,
+    render: RefCell<Option<Callback>>,
+}
+
+impl TestScheduler {
+    /// Creates a scheduler with no callbacks, at time 0
+    pub fn new() -> TestScheduler {
+        TestScheduler::default()
+    }
+
+    /// Milliseconds passed on the virtual clock
+    pub fn now(&self) -> u64 {
+        self.now.get()
+    }
+
+    /// The number of timeouts which haven't been called
+    pub fn pending_timeouts(&self) -> usize {
+        self.timeouts.borrow().len()
+    }
+
+    /// The number of callbacks, including any scheduled render, waiting for the next frame
+    pub fn pending_frames(&self) -> usize {
+        self.frames.borrow().len()
+    }
+
+    /// Whether a render is waiting for the next frame
+    pub fn is_render_pending(&self) -> bool {
+        self.render.borrow().is_some()
+    }
+
+    /// Moves the clock forward by `millis` milliseconds, calling the timeouts which become due
+    /// in the order they're due, including timeouts set by those callbacks
+    pub fn advance(&self, millis: u64) {
+        let until = self.now.get() + millis;
+        while let Some(timeout) = self.next_timeout(until) {
+            self.now.set(timeout.due);
+            (timeout.callback)();
+        }
+        self.now.set(until);
+    }
+
+    /// Draws a frame, calling the callbacks requested before it in the order they were
+    /// requested, and returning how many were called.
+    ///
+    /// Callbacks requested while the frame is drawn wait for the next one.
+    pub fn run_frame(&self) -> usize {
+        let frames = ::std::mem::take(&mut *self.frames.borrow_mut());
+        let count = frames.len();
+        for callback in frames {
+            let callback = callback.or_else(|| self.render.borrow_mut().take());
+            if let Some(callback) = callback {
+                callback();
+            }
+        }
+        count
+    }
+
+    fn next_timeout(&self, until: u64) -> Option<Timeout> {
+        let mut timeouts = self.timeouts.borrow_mut();
+        let index = timeouts.iter().enumerate()
+            .filter(|&(_, timeout)| timeout.due <= until)
+            .min_by_key(|&(_, timeout)| (timeout.due, timeout.sequence))
+            .map(|(index, _)| index)?;
+        Some(timeouts.remove(index))
+    }
+}
+
+impl Scheduler for TestScheduler {
+    fn schedule_render(&self, render: Box<dyn FnOnce()>) {
+        if self.render.borrow_mut().replace(render).is_none() {
+            self.frames.borrow_mut().push(None);
+        }
+    }
+
+    fn set_timeout(&self, millis: u32, callback: Box<dyn FnOnce()>) {
+        let sequence = self.sequence.get();
+        self.sequence.set(sequence + 1);
+        let due = self.now.get() + u64::from(millis);
+        self.timeouts.borrow_mut().push(Timeout { due, sequence, callback });
+    }
+
+    fn request_frame(&self, callback: Box<dyn FnOnce()>) {
+        self.frames.borrow_mut().push(Some(callback));
+    }
+
+    /// Draws frames and advances the clock to each timeout until no callbacks are left
+    fn run_main_loop(&self) {
+        loop {
+            let next_due = self.timeouts.borrow().iter().map(|timeout| timeout.due).min();
+            if self.pending_frames() > 0 {
+                self.run_frame();
+            } else if let Some(due) = next_due {
+                self.advance(due - self.now.get());
+            } else {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Service worker lifecycle messages, and a prompt to reload once a new version is available.
+//!
+//! A progressive web app is served from its service worker's caches, so a deployment reaches
+//! users only once the new worker has installed and taken control of the page. When rendering
+//! client-side, `JsIo::register_service_worker` registers the worker script and delivers a
+//! `ServiceWorkerEvent` as each step happens. A new version waits until every page using the
+//! old one has closed, unless the user chooses to update, which `JsIo::activate_update` does by
+//! posting `SKIP_WAITING_MESSAGE` to the waiting worker and reloading the page once it has
+//! taken control. The worker script should skip waiting when it receives the message:
+//!
+//! ```js
+//! self.addEventListener('message', function(event) {
+//!     if (event.data === 'domafic:skip-waiting') { self.skipWaiting(); }
+//! });
+//! ```
+//!
+//! A `ReloadPrompt` in the application's state follows the events, and renders a prompt while
+//! an update is waiting.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::service_worker::{ReloadPrompt, ServiceWorkerEvent};
+//!
+//! #[derive(Clone)]
+//! enum Msg { Reload, Dismiss }
+//!
+//! let mut prompt = ReloadPrompt::new();
+//! prompt.observe(&ServiceWorkerEvent::Installed);
+//! assert!(prompt.render("A new version is available", "Reload", "Later", Msg::Reload, Msg::Dismiss)
+//!     .is_none());
+//!
+//! prompt.observe(&ServiceWorkerEvent::Updated);
+//! let banner = prompt.render("A new version is available", "Reload", "Later", Msg::Reload, Msg::Dismiss);
+//! assert_eq!(banner.unwrap().displayable().to_string(), "\
+//!     <div class=\"domafic-reload-prompt\" role=\"status\">A new version is available\
+//!     <button>Reload</button><button>Later</button></div>");
+//!
+//! // In an update, when rendering client-side:
+//! // Msg::Reload => js_io.activate_update(),
+//! // Msg::Dismiss => state.prompt.dismiss(),
+//! prompt.dismiss();
+//! assert!(!prompt.is_shown());
+//! ```
+
+use DomNode;
+use AttributeValue::Str;
+use listener::on;
+use tags::{attributes, button, div};
+
+/// Message posted to a waiting service worker by `JsIo::activate_update`, asking it to call
+/// `skipWaiting` and take control of the page
+pub const SKIP_WAITING_MESSAGE: &str = "domafic:skip-waiting";
+
+/// A step in the lifecycle of the page's service worker
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum ServiceWorkerEvent {
+    /// The worker script was registered. Its installation may still be in progress.
+    Registered,
+    /// A worker was installed for a page which had none, so the app now works offline
+    Installed,
+    /// A new version of the worker was installed, and is waiting to replace the one which
+    /// controls the page
+    Updated,
+    /// A different worker took control of the page, such as after an update was activated
+    ControllerChange,
+    /// Registration failed, or service workers aren't supported, with the browser's message.
+    /// No further events are delivered.
+    Failed(String),
+}
+
+impl ServiceWorkerEvent {
+    /// The event named by the runtime's JavaScript
+    #[doc(hidden)]
+    pub fn from_js(name: &str) -> Option<ServiceWorkerEvent> {
+        match name {
+            "registered" => Some(ServiceWorkerEvent::Registered),
+            "installed" => Some(ServiceWorkerEvent::Installed),
+            "updated" => Some(ServiceWorkerEvent::Updated),
+            "controllerchange" => Some(ServiceWorkerEvent::ControllerChange),
+            _ => None,
+        }
+    }
+}
+
+/// Whether to show a prompt to reload the page into a new version of the app
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub struct ReloadPrompt {
+    update_waiting: bool,
+    dismissed: bool,
+}
+
+impl ReloadPrompt {
+    /// A prompt which isn't shown until an update is waiting
+    pub fn new() -> ReloadPrompt {
+        ReloadPrompt::default()
+    }
+
+    /// Follows a lifecycle event, showing the prompt when a new version is waiting, even if
+    /// an earlier prompt was dismissed, and hiding it once a new version has taken control
+    pub fn observe(&mut self, event: &ServiceWorkerEvent) {
+        match *event {
+            ServiceWorkerEvent::Updated => {
+                self.update_waiting = true;
+                self.dismissed = false;
+            },
+            ServiceWorkerEvent::ControllerChange => self.update_waiting = false,
+            _ => {},
+        }
+    }
+
+    /// Hides the prompt until the next update
+    pub fn dismiss(&mut self) {
+        self.dismissed = true;
+    }
+
+    /// Whether an update is waiting and the prompt hasn't been dismissed
+    pub fn is_shown(&self) -> bool {
+        self.update_waiting && !self.dismissed
+    }
+
+    /// Renders the prompt while it's shown: `message`, followed by a button labelled
+    /// `reload_label` which delivers `on_reload`, and one labelled `dismiss_label` which
+    /// delivers `on_dismiss`.
+    ///
+    /// The prompt is a `div` of class "domafic-reload-prompt", with the "status" role so that
+    /// screen readers announce it.
+    pub fn render<M: Clone>(
+        &self,
+        message: &'static str,
+        reload_label: &'static str,
+        dismiss_label: &'static str,
+        on_reload: M,
+        on_dismiss: M,
+    ) -> Option<impl DomNode<M>> {
+        if !self.is_shown() {
+            return None;
+        }
+        Some(div((
+            attributes([("class", Str("domafic-reload-prompt")), ("role", Str("status"))]),
+            (
+                message,
+                button((on("click", move |_| on_reload.clone()), reload_label)),
+                button((on("click", move |_| on_dismiss.clone()), dismiss_label)),
+            ),
+        )))
+    }
+}
@@ -0,0 +1,331 @@
+//! Keyboard shortcuts, mapping key combinations to messages in global or named scopes.
+//!
+//! `Shortcuts` are built by the subscriber passed to `web_render::run_with_subscriptions`, so
+//! that they follow the state: each binding belongs to the global scope or to a named scope,
+//! such as the component which handles it, and a scope can be disabled while it shouldn't
+//! react, such as the editor's shortcuts while a modal is open. `Shortcuts::subscription`
+//! turns them into a subscription to the window's key presses, which prevents the browser's
+//! default action for the combinations it handles.
+//!
+//! A binding in an enabled named scope takes precedence over a global binding of the same
+//! combination. Two enabled bindings of a combination in the same scope, or in two named
+//! scopes, are a conflict, which `Shortcuts::conflicts` lists, and which panics in debug
+//! builds when the subscription is created.
+//!
+//! While the user is typing into a text field, only combinations with "ctrl", "alt" or "meta",
+//! and the escape key, are handled, so that shortcuts don't take the keys being typed.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::shortcuts::{KeyCombo, Scope, Shortcuts};
+//!
+//! #[derive(Debug, Clone, PartialEq)]
+//! enum Msg { Save, Help, CloseModal, Bold }
+//!
+//! fn shortcuts(modal_open: bool) -> Shortcuts<Msg> {
+//!     Shortcuts::new()
+//!         .global("ctrl+s", Msg::Save)
+//!         .global("?", Msg::Help)
+//!         .scoped("editor", "ctrl+b", Msg::Bold)
+//!         .scoped("modal", "escape", Msg::CloseModal)
+//!         .enabled(Scope::Named("editor"), !modal_open)
+//!         .enabled(Scope::Named("modal"), modal_open)
+//! }
+//!
+//! let bold = KeyCombo::parse("Ctrl+B").unwrap();
+//! assert_eq!(bold.to_string(), "Ctrl+B");
+//! assert_eq!(shortcuts(false).find(bold, false), Some(&Msg::Bold));
+//! assert_eq!(shortcuts(true).find(bold, false), None);
+//!
+//! // Typing a "?" into a text field doesn't open the help
+//! let question_mark = KeyCombo::parse("?").unwrap();
+//! assert_eq!(shortcuts(false).find(question_mark, false), Some(&Msg::Help));
+//! assert_eq!(shortcuts(false).find(question_mark, true), None);
+//! assert!(shortcuts(true).conflicts().is_empty());
+//! ```
+
+use subscriptions::{on_key_down, KeyDown, Subscription};
+
+use std::fmt;
+
+/// Keycodes of keys with names, other than letters, digits and function keys
+const NAMED_KEYS: [(&str, u32); 22] = [
+    ("backspace", 8),
+    ("tab", 9),
+    ("enter", 13),
+    ("escape", 27),
+    ("space", 32),
+    ("pageup", 33),
+    ("pagedown", 34),
+    ("end", 35),
+    ("home", 36),
+    ("left", 37),
+    ("up", 38),
+    ("right", 39),
+    ("down", 40),
+    ("insert", 45),
+    ("delete", 46),
+    (";", 186),
+    ("=", 187),
+    (",", 188),
+    ("-", 189),
+    (".", 190),
+    ("/", 191),
+    ("`", 192),
+];
+
+/// Keycode of the escape key, which is handled while the user is typing
+const ESCAPE: u32 = 27;
+
+/// A key pressed along with a set of modifier keys
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct KeyCombo {
+    /// Keycode of the key, as in `Event::which_keycode`
+    pub key_code: u32,
+    /// Whether "shift" is held
+    pub shift: bool,
+    /// Whether "alt" is held
+    pub alt: bool,
+    /// Whether "ctrl" is held
+    pub ctrl: bool,
+    /// Whether "meta" is held
+    pub meta: bool,
+}
+
+impl KeyCombo {
+    /// The key with `key_code`, without modifiers
+    pub fn new(key_code: u32) -> KeyCombo {
+        KeyCombo { key_code, shift: false, alt: false, ctrl: false, meta: false }
+    }
+
+    /// Parses a combination such as `"ctrl+shift+k"`, `"escape"` or `"?"`, ignoring case.
+    ///
+    /// Modifiers, which are `ctrl`, `shift`, `alt` and `meta`, are separated by `+` and followed
+    /// by a letter, a digit, a function key such as `f2`, punctuation such as `/`, or a named
+    /// key such as `enter`, `space` or `up`. `?` is `shift+/`. Returns `None` for anything else.
+    pub fn parse(combo: &str) -> Option<KeyCombo> {
+        let combo = combo.to_lowercase();
+        let mut parts: Vec<&str> = combo.split('+').map(str::trim).collect();
+        let key = parts.pop()?;
+        let mut parsed = if key == "?" {
+            KeyCombo { shift: true, ..KeyCombo::new(191) }
+        } else {
+            KeyCombo::new(key_code(key)?)
+        };
+        for modifier in parts {
+            let held = match modifier {
+                "shift" => &mut parsed.shift,
+                "alt" => &mut parsed.alt,
+                "ctrl" => &mut parsed.ctrl,
+                "meta" => &mut parsed.meta,
+                _ => return None,
+            };
+            *held = true;
+        }
+        Some(parsed)
+    }
+
+    /// The combination of a key press
+    pub fn of(key: &KeyDown) -> KeyCombo {
+        KeyCombo {
+            key_code: key.key_code,
+            shift: key.shift_key,
+            alt: key.alt_key,
+            ctrl: key.ctrl_key,
+            meta: key.meta_key,
+        }
+    }
+
+    /// Whether the combination is handled while the user is typing into a text field
+    fn handled_while_editing(&self) -> bool {
+        self.ctrl || self.alt || self.meta || self.key_code == ESCAPE
+    }
+}
+
+/// The keycode of a key name which isn't a modifier, as written in a combination
+fn key_code(key: &str) -> Option<u32> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ 'a'..='z'), None) => return Some(c as u32 - 'a' as u32 + 65),
+        (Some(c @ '0'..='9'), None) => return Some(c as u32),
+        _ => {},
+    }
+    if let Some(Ok(number @ 1..=12)) = key.strip_prefix('f').map(str::parse::<u32>) {
+        return Some(111 + number);
+    }
+    if key == "esc" {
+        return Some(ESCAPE);
+    }
+    NAMED_KEYS.iter().find(|named| named.0 == key).map(|named| named.1)
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let modifiers = [(self.ctrl, "Ctrl+"), (self.alt, "Alt+"), (self.shift, "Shift+"), (self.meta, "Meta+")];
+        for &(held, name) in &modifiers {
+            if held {
+                write!(f, "{}", name)?;
+            }
+        }
+        match self.key_code {
+            code @ 65..=90 | code @ 48..=57 => write!(f, "{}", (code as u8) as char),
+            code @ 112..=123 => write!(f, "F{}", code - 111),
+            code => match NAMED_KEYS.iter().find(|named| named.1 == code) {
+                Some(&(name, _)) => {
+                    let mut chars = name.chars();
+                    let first = chars.next().unwrap().to_uppercase();
+                    write!(f, "{}{}", first, chars.as_str())
+                },
+                None => write!(f, "Key{}", code),
+            },
+        }
+    }
+}
+
+/// Where a shortcut applies
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Scope {
+    /// Everywhere on the page
+    Global,
+    /// Within a named part of the application, such as a component
+    Named(&'static str),
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Scope::Global => write!(f, "the global scope"),
+            Scope::Named(name) => write!(f, "scope `{}`", name),
+        }
+    }
+}
+
+/// Two enabled shortcuts for the same key combination, so that neither takes precedence
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Conflict {
+    /// The combination bound twice
+    pub combo: KeyCombo,
+    /// Scope of the first binding
+    pub first: Scope,
+    /// Scope of the later binding
+    pub second: Scope,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.first == self.second {
+            write!(f, "{} is bound twice in {}", self.combo, self.first)
+        } else {
+            write!(f, "{} is bound in both {} and {}", self.combo, self.first, self.second)
+        }
+    }
+}
+
+struct Binding<M> {
+    scope: Scope,
+    combo: KeyCombo,
+    message: M,
+}
+
+/// Key combinations mapped to messages, in global and named scopes
+pub struct Shortcuts<M> {
+    bindings: Vec<Binding<M>>,
+    disabled: Vec<Scope>,
+}
+
+impl<M> Shortcuts<M> {
+    /// No shortcuts, with every scope enabled
+    pub fn new() -> Shortcuts<M> {
+        Shortcuts { bindings: Vec::new(), disabled: Vec::new() }
+    }
+
+    /// Binds `combo`, as parsed by `KeyCombo::parse`, to `message` in `scope`.
+    ///
+    /// Panics if `combo` isn't a valid combination.
+    pub fn bind(mut self, scope: Scope, combo: &str, message: M) -> Shortcuts<M> {
+        let parsed = KeyCombo::parse(combo)
+            .unwrap_or_else(|| panic!("invalid key combination `{}`", combo));
+        self.bindings.push(Binding { scope, combo: parsed, message });
+        self
+    }
+
+    /// Binds `combo` to `message` everywhere on the page
+    pub fn global(self, combo: &str, message: M) -> Shortcuts<M> {
+        self.bind(Scope::Global, combo, message)
+    }
+
+    /// Binds `combo` to `message` in the scope named `scope`
+    pub fn scoped(self, scope: &'static str, combo: &str, message: M) -> Shortcuts<M> {
+        self.bind(Scope::Named(scope), combo, message)
+    }
+
+    /// Enables or disables the shortcuts in `scope`. Every scope starts enabled.
+    pub fn enabled(mut self, scope: Scope, enabled: bool) -> Shortcuts<M> {
+        self.disabled.retain(|&disabled| disabled != scope);
+        if !enabled {
+            self.disabled.push(scope);
+        }
+        self
+    }
+
+    /// Whether the shortcuts in `scope` are enabled
+    pub fn is_enabled(&self, scope: Scope) -> bool {
+        !self.disabled.contains(&scope)
+    }
+
+    /// Lists each pair of enabled bindings of the same combination which conflict: those in
+    /// the same scope, and those in two named scopes
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let enabled: Vec<&Binding<M>> = self.bindings.iter()
+            .filter(|binding| self.is_enabled(binding.scope))
+            .collect();
+        let mut conflicts = Vec::new();
+        for (index, first) in enabled.iter().enumerate() {
+            for second in &enabled[index + 1..] {
+                let shadowed = first.scope != second.scope &&
+                    (first.scope == Scope::Global || second.scope == Scope::Global);
+                if first.combo == second.combo && !shadowed {
+                    conflicts.push(Conflict { combo: first.combo, first: first.scope, second: second.scope });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// The message of the shortcut for `combo`, pressed while typing into a text field if
+    /// `editing`, or `None` if no enabled shortcut handles it
+    pub fn find(&self, combo: KeyCombo, editing: bool) -> Option<&M> {
+        if editing && !combo.handled_while_editing() {
+            return None;
+        }
+        let bound: Vec<&Binding<M>> = self.bindings.iter()
+            .filter(|binding| binding.combo == combo && self.is_enabled(binding.scope))
+            .collect();
+        // Named scopes take precedence over the global scope
+        bound.iter().find(|binding| binding.scope != Scope::Global).or_else(|| bound.first())
+            .map(|binding| &binding.message)
+    }
+}
+
+impl<M: Clone + 'static> Shortcuts<M> {
+    /// Subscribes to the window's key presses, delivering the message of the shortcut for each
+    /// combination pressed and preventing the browser's default action for it.
+    ///
+    /// In debug builds, panics if any shortcuts conflict.
+    pub fn subscription(self) -> Subscription<M> {
+        #[cfg(debug_assertions)]
+        {
+            let conflicts = self.conflicts();
+            let descriptions: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+            assert!(conflicts.is_empty(), "conflicting shortcuts: {}", descriptions.join("; "));
+        }
+        on_key_down(move |key| self.find(KeyCombo::of(&key), key.editing).cloned())
+    }
+}
+
+impl<M> Default for Shortcuts<M> {
+    fn default() -> Shortcuts<M> {
+        Shortcuts::new()
+    }
+}
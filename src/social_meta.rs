@@ -0,0 +1,210 @@
+//! Open Graph and Twitter card metadata for link previews.
+//!
+//! A `SocialMeta` describes how a page should appear when shared. It's converted into the
+//! `og:` and `twitter:` meta tags expected by crawlers, and checked for the fields they
+//! require, when added to a `Document` with `Document::social_meta`.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::document::Document;
+//! use domafic::social_meta::{Card, Error, SocialMeta};
+//!
+//! let meta = SocialMeta::new("Hello, world", "My first post")
+//!     .url("https://example.com/posts/hello-world")
+//!     .image("https://example.com/images/hello.png")
+//!     .card(Card::SummaryLargeImage);
+//! let document = Document::new("Hello, world").social_meta(&meta).unwrap();
+//! assert_eq!(document.head.len(), 6);
+//!
+//! // Crawlers can't load images from relative URLs
+//! let relative = meta.clone().image("/images/hello.png");
+//! assert_eq!(Document::new("Hello").social_meta(&relative), Err(Error::RelativeUrl("og:image")));
+//! ```
+
+use document::{Document, HeadElement};
+
+use std::error;
+use std::fmt;
+
+/// The layout of a Twitter card
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Card {
+    /// A small square image beside the title and description
+    Summary,
+    /// A large image above the title and description
+    SummaryLargeImage,
+}
+
+impl Card {
+    /// The value of the `twitter:card` meta tag
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Card::Summary => "summary",
+            Card::SummaryLargeImage => "summary_large_image",
+        }
+    }
+}
+
+/// A problem with a `SocialMeta` which would prevent a correct link preview
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Error {
+    /// A required field was empty, given as the name of its meta tag
+    Missing(&'static str),
+    /// A URL which must be absolute was relative, given as the name of its meta tag
+    RelativeUrl(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Missing(tag) => write!(f, "social metadata is missing `{}`", tag),
+            Error::RelativeUrl(tag) => write!(f, "social metadata `{}` must be an absolute URL", tag),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Metadata describing how a page appears when shared
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct SocialMeta {
+    /// Title of the preview
+    pub title: String,
+    /// Description shown under the title
+    pub description: String,
+    /// Absolute URL of the preview image
+    pub image: Option<String>,
+    /// Text describing the image, for screen readers
+    pub image_alt: Option<String>,
+    /// Canonical absolute URL of the page
+    pub url: Option<String>,
+    /// Name of the site the page belongs to
+    pub site_name: Option<String>,
+    /// Open Graph type of the page, such as `website` or `article`
+    pub kind: String,
+    /// Layout of the Twitter card
+    pub card: Card,
+    /// Twitter handle of the site, such as `@example`
+    pub twitter_site: Option<String>,
+}
+
+impl SocialMeta {
+    /// Creates metadata for a `website` with a summary card and no image
+    pub fn new<T: Into<String>, D: Into<String>>(title: T, description: D) -> SocialMeta {
+        SocialMeta {
+            title: title.into(),
+            description: description.into(),
+            image: None,
+            image_alt: None,
+            url: None,
+            site_name: None,
+            kind: "website".to_string(),
+            card: Card::Summary,
+            twitter_site: None,
+        }
+    }
+
+    /// Sets the absolute URL of the preview image
+    pub fn image<I: Into<String>>(mut self, image: I) -> SocialMeta {
+        self.image = Some(image.into());
+        self
+    }
+
+    /// Sets the text describing the image
+    pub fn image_alt<A: Into<String>>(mut self, image_alt: A) -> SocialMeta {
+        self.image_alt = Some(image_alt.into());
+        self
+    }
+
+    /// Sets the canonical absolute URL of the page
+    pub fn url<U: Into<String>>(mut self, url: U) -> SocialMeta {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Sets the name of the site
+    pub fn site_name<S: Into<String>>(mut self, site_name: S) -> SocialMeta {
+        self.site_name = Some(site_name.into());
+        self
+    }
+
+    /// Sets the Open Graph type of the page
+    pub fn kind<K: Into<String>>(mut self, kind: K) -> SocialMeta {
+        self.kind = kind.into();
+        self
+    }
+
+    /// Sets the layout of the Twitter card
+    pub fn card(mut self, card: Card) -> SocialMeta {
+        self.card = card;
+        self
+    }
+
+    /// Sets the Twitter handle of the site
+    pub fn twitter_site<S: Into<String>>(mut self, twitter_site: S) -> SocialMeta {
+        self.twitter_site = Some(twitter_site.into());
+        self
+    }
+
+    /// Checks that the fields required for a preview are present and that URLs are absolute
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.title.trim().is_empty() { return Err(Error::Missing("og:title")); }
+        if self.description.trim().is_empty() { return Err(Error::Missing("og:description")); }
+        if self.kind.trim().is_empty() { return Err(Error::Missing("og:type")); }
+        if self.card == Card::SummaryLargeImage && self.image.is_none() {
+            return Err(Error::Missing("og:image"));
+        }
+        if self.image.iter().any(|image| !is_absolute(image)) {
+            return Err(Error::RelativeUrl("og:image"));
+        }
+        if self.url.iter().any(|url| !is_absolute(url)) {
+            return Err(Error::RelativeUrl("og:url"));
+        }
+        Ok(())
+    }
+
+    /// Returns the meta tags for the metadata, after validating it
+    pub fn head_elements(&self) -> Result<Vec<HeadElement>, Error> {
+        self.validate()?;
+
+        let mut elements = Vec::new();
+        {
+            let mut property = |property: &str, content: &str| elements.push(HeadElement::Property {
+                property: property.to_string(),
+                content: content.to_string(),
+            });
+            property("og:title", &self.title);
+            property("og:description", &self.description);
+            property("og:type", &self.kind);
+            if let Some(ref url) = self.url { property("og:url", url); }
+            if let Some(ref site_name) = self.site_name { property("og:site_name", site_name); }
+            if let Some(ref image) = self.image {
+                property("og:image", image);
+                if let Some(ref image_alt) = self.image_alt { property("og:image:alt", image_alt); }
+            }
+        }
+        {
+            // Twitter falls back to the `og:` properties for the title, description and image
+            let mut meta = |name: &str, content: &str| elements.push(HeadElement::Meta {
+                name: name.to_string(),
+                content: content.to_string(),
+            });
+            meta("twitter:card", self.card.as_str());
+            if let Some(ref twitter_site) = self.twitter_site { meta("twitter:site", twitter_site); }
+        }
+        Ok(elements)
+    }
+}
+
+fn is_absolute(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+impl Document {
+    /// Adds the meta tags for `meta`, returning an error if it's missing required fields
+    pub fn social_meta(mut self, meta: &SocialMeta) -> Result<Document, Error> {
+        self.head.extend(meta.head_elements()?);
+        Ok(self)
+    }
+}
@@ -0,0 +1,98 @@
+//! Spring physics, for values which move as if they were attached to a spring.
+//!
+//! An eased animation has a fixed duration and always starts from rest. A spring instead pulls
+//! its value towards a target, with a force given by its `stiffness`, slowed by its `damping`,
+//! so a value released with a velocity, such as a dragged card, keeps its momentum and settles
+//! naturally. Changing the target while a spring is moving continues from its current value and
+//! velocity.
+//!
+//! A `Spring` is solved step by step with `Spring::step`. To animate a value in the UI, issue
+//! `Effect::spring`, which steps the spring on each animation frame and delivers its value until
+//! it comes to rest.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::spring::{spring, SpringState};
+//!
+//! let wobbly = spring(180.0, 12.0);
+//! let mut state = SpringState { value: 0.0, velocity: 0.0 };
+//! let mut overshot = false;
+//! while !wobbly.is_at_rest(&state, 100.0) {
+//!     state = wobbly.step(state, 100.0, 16.0);
+//!     overshot |= state.value > 100.0;
+//! }
+//! assert!(overshot);
+//! assert!((state.value - 100.0).abs() < wobbly.precision);
+//! ```
+
+/// Longest step of the solver. Longer steps are split, so that stiff springs stay stable.
+const STEP_MILLIS: f64 = 1.0;
+
+/// The parameters of a spring
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Spring {
+    /// Strength of the force pulling the value towards the target, per unit of distance
+    pub stiffness: f64,
+    /// Strength of the friction slowing the value down, per unit of velocity
+    pub damping: f64,
+    /// Mass of the moving value. Heavier values respond more slowly to the same force.
+    pub mass: f64,
+    /// Largest distance from the target, and largest velocity per second, at which the value
+    /// is considered at rest
+    pub precision: f64,
+}
+
+/// Creates a spring with a mass of 1, at rest within 0.01 of its target.
+///
+/// A `stiffness` of 170 and `damping` of 26 settles quickly without overshooting, while lower
+/// damping makes the value bounce around the target before settling.
+///
+/// Panics unless `stiffness` and `damping` are positive, since an undamped spring never comes
+/// to rest.
+pub fn spring(stiffness: f64, damping: f64) -> Spring {
+    assert!(stiffness > 0.0 && damping > 0.0, "springs must have positive stiffness and damping");
+    Spring { stiffness, damping, mass: 1.0, precision: 0.01 }
+}
+
+/// The position and velocity of a value moved by a `Spring`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpringState {
+    /// The current value
+    pub value: f64,
+    /// The rate at which the value is changing, per second
+    pub velocity: f64,
+}
+
+impl Spring {
+    /// Replaces the spring's mass. Panics unless `mass` is positive.
+    pub fn with_mass(mut self, mass: f64) -> Spring {
+        assert!(mass > 0.0, "springs must have a positive mass");
+        self.mass = mass;
+        self
+    }
+
+    /// Replaces the distance and velocity at which the value is considered at rest
+    pub fn with_precision(mut self, precision: f64) -> Spring {
+        self.precision = precision;
+        self
+    }
+
+    /// Moves `state` towards `target` for `millis` milliseconds
+    pub fn step(&self, mut state: SpringState, target: f64, millis: f64) -> SpringState {
+        let mut remaining = millis;
+        while remaining > 0.0 {
+            let seconds = remaining.min(STEP_MILLIS) / 1000.0;
+            let force = -self.stiffness * (state.value - target) - self.damping * state.velocity;
+            state.velocity += force / self.mass * seconds;
+            state.value += state.velocity * seconds;
+            remaining -= STEP_MILLIS;
+        }
+        state
+    }
+
+    /// Whether `state` has settled at `target`
+    pub fn is_at_rest(&self, state: &SpringState, target: f64) -> bool {
+        (state.value - target).abs() < self.precision && state.velocity.abs() < self.precision
+    }
+}
@@ -0,0 +1,294 @@
+//! Generating a static site by rendering each route to a file.
+//!
+//! A `Generator` renders every route's page with the application's renderer and writes it to
+//! an output directory as a complete `Document`. Pages are written to `index.html` files so
+//! that `/posts/hello-world` is served from `posts/hello-world/index.html`. Paths with a file
+//! extension, such as `/404.html`, are written as-is.
+//!
+//! Files listed in `AssetManifest`s are copied into the output directory alongside the pages,
+//! as is the `WebManifest` of an installable app.
+//! Once every page has been written, the `href` and `src` attributes of each page are checked,
+//! and internal links which don't lead to a generated page or a copied asset are reported.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::AttributeValue::Str;
+//! use domafic::document::Document;
+//! use domafic::ssg::Generator;
+//! use domafic::tags::{a, attributes, h1, div};
+//! use std::marker::PhantomData;
+//!
+//! enum Route { Home, About }
+//!
+//! let out_dir = std::env::temp_dir().join("domafic-ssg-example");
+//! let report = Generator::new(&out_dir).generate(
+//!     &[Route::Home, Route::About],
+//!     |route| match *route { Route::Home => "/", Route::About => "/about" }.to_string(),
+//!     |route| match *route { Route::Home => "Home", Route::About => "About" },
+//!     |title: &&str| (Document::new(*title), div((
+//!         h1(*title),
+//!         a((attributes([("href", Str("/about"))]), "About")),
+//!         a((attributes([("href", Str("/contact#form"))]), "Contact")),
+//!         PhantomData::<()>,
+//!     ))),
+//! ).unwrap();
+//!
+//! assert!(out_dir.join("about/index.html").is_file());
+//! assert_eq!(report.pages.len(), 2);
+//!
+//! // Both pages link to `/contact`, which wasn't generated
+//! assert_eq!(report.broken_links.len(), 2);
+//! assert_eq!(report.broken_links[0].page, "/");
+//! assert_eq!(report.broken_links[0].href, "/contact#form");
+//! ```
+
+use {DomNode, DomNodes};
+use css;
+use document::Document;
+use processors::DomNodeProcessor;
+use sri::Integrity;
+use web_manifest::WebManifest;
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A directory of files to copy into the generated site
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct AssetManifest {
+    root: PathBuf,
+    files: Vec<String>,
+}
+
+impl AssetManifest {
+    /// Creates an empty manifest of files in the directory `root`
+    pub fn new<P: Into<PathBuf>>(root: P) -> AssetManifest {
+        AssetManifest { root: root.into(), files: Vec::new() }
+    }
+
+    /// Creates a manifest of every file in the directory `root`, including those in
+    /// subdirectories
+    pub fn scan<P: Into<PathBuf>>(root: P) -> io::Result<AssetManifest> {
+        let mut manifest = AssetManifest::new(root);
+        let root = manifest.root.clone();
+        scan_dir(&root, "", &mut manifest.files)?;
+        manifest.files.sort();
+        Ok(manifest)
+    }
+
+    /// Adds a file, given as a `/`-separated path relative to the manifest's root, such as
+    /// `images/logo.png`
+    pub fn with<F: Into<String>>(mut self, file: F) -> AssetManifest {
+        self.files.push(file.into());
+        self
+    }
+
+    /// The directory containing the files
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The files to copy, relative to the manifest's root
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Computes the subresource integrity hash of `file`, relative to the manifest's root, for
+    /// use with `Document::script_with_integrity` or `Document::stylesheet_with_integrity`
+    pub fn integrity(&self, file: &str) -> io::Result<Integrity> {
+        Integrity::of_file(self.root.join(file))
+    }
+}
+
+fn scan_dir(dir: &Path, prefix: &str, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let relative = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        if entry.file_type()?.is_dir() {
+            scan_dir(&entry.path(), &relative, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// An internal link which doesn't lead to a generated page or asset
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct BrokenLink {
+    /// Path of the page containing the link
+    pub page: String,
+    /// The link, as written in the page
+    pub href: String,
+}
+
+/// The result of generating a site
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Report {
+    /// Files written for each page, in the order of the routes
+    pub pages: Vec<PathBuf>,
+    /// Asset files copied into the output directory
+    pub assets: Vec<PathBuf>,
+    /// Internal links which don't lead to a generated page or asset, in the order they appear
+    pub broken_links: Vec<BrokenLink>,
+}
+
+/// Renders routes to files in an output directory
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Generator {
+    out_dir: PathBuf,
+    assets: Vec<AssetManifest>,
+    web_manifest: Option<(String, WebManifest)>,
+}
+
+impl Generator {
+    /// Creates a generator which writes to the directory `out_dir`, creating it if necessary
+    pub fn new<P: Into<PathBuf>>(out_dir: P) -> Generator {
+        Generator { out_dir: out_dir.into(), assets: Vec::new(), web_manifest: None }
+    }
+
+    /// Adds assets to copy into the output directory
+    pub fn with_assets(mut self, manifest: AssetManifest) -> Generator {
+        self.assets.push(manifest);
+        self
+    }
+
+    /// Writes `manifest` to the file served at the URL path `href`, such as
+    /// `/manifest.webmanifest`, and adds it to every page's `Document` as by
+    /// `Document::web_manifest`.
+    ///
+    /// Generating the site fails with `io::ErrorKind::InvalidData` if browsers couldn't
+    /// install the app the manifest describes.
+    pub fn with_web_manifest<H: Into<String>>(mut self, href: H, manifest: WebManifest) -> Generator {
+        self.web_manifest = Some((href.into(), manifest));
+        self
+    }
+
+    /// Renders each of `routes` to a file, copies the assets, and checks internal links.
+    ///
+    /// `path` gives the URL path of each route, such as `/posts/hello-world`, and `state`
+    /// produces the application state to render it with. `render` returns the page's
+    /// `Document` along with the node to use as its body.
+    ///
+    /// CSS collected by the `css` module is cleared before rendering each page, so that each
+    /// page only includes the CSS it uses.
+    pub fn generate<R, S, M, T, P, St, Rn>(
+        &self,
+        routes: &[R],
+        path: P,
+        state: St,
+        render: Rn,
+    ) -> io::Result<Report>
+        where T: DomNode<M>, P: Fn(&R) -> String, St: Fn(&R) -> S, Rn: Fn(&S) -> (Document, T)
+    {
+        let mut report = Report::default();
+        let mut targets = HashSet::new();
+        let mut page_links = Vec::new();
+
+        for manifest in &self.assets {
+            for file in &manifest.files {
+                let destination = self.out_dir.join(file);
+                if let Some(parent) = destination.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(manifest.root.join(file), &destination)?;
+                targets.insert(normalize(file));
+                report.assets.push(destination);
+            }
+        }
+
+        let mut manifest_elements = Vec::new();
+        if let Some((ref href, ref manifest)) = self.web_manifest {
+            manifest_elements = manifest.head_elements(href)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let destination = self.out_dir.join(href.trim_start_matches('/'));
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&destination, manifest.to_json())?;
+            targets.insert(normalize(href));
+            report.assets.push(destination);
+        }
+
+        for route in routes {
+            let page_path = path(route);
+            css::clear();
+            let (mut document, body) = render(&state(route));
+            document.head.extend(manifest_elements.iter().cloned());
+
+            let file = self.out_dir.join(output_file(&page_path));
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut html = Vec::new();
+            document.write(&mut html, &body)?;
+            fs::write(&file, html)?;
+
+            targets.insert(normalize(&page_path));
+            page_links.push((page_path, links(&body)));
+            report.pages.push(file);
+        }
+        css::clear();
+
+        for (page, hrefs) in page_links {
+            for href in hrefs {
+                if is_internal(&href) && !targets.contains(&normalize(&href)) {
+                    report.broken_links.push(BrokenLink { page: page.clone(), href });
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Returns the values of all `href` and `src` attributes in `node` and its descendants
+pub fn links<M, T: DomNode<M>>(node: &T) -> Vec<String> {
+    let mut links = Vec::new();
+    node.process_all::<LinkCollector>(&mut links).unwrap();
+    links
+}
+
+struct LinkCollector;
+impl<'a, M> DomNodeProcessor<'a, M> for LinkCollector {
+    type Acc = Vec<String>;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn collect<M, T: DomNode<M>>(links: &mut Vec<String>, node: &T) -> Result<(), ()> {
+            links.extend(node.attributes()
+                .filter(|attr| attr.0 == "href" || attr.0 == "src")
+                .map(|attr| attr.1.as_str().to_string()));
+            node.children().process_all::<LinkCollector>(links)
+        }
+        collect::<M, T>
+    }
+}
+
+/// The file a page is written to, relative to the output directory
+fn output_file(path: &str) -> PathBuf {
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        PathBuf::from("index.html")
+    } else if Path::new(path).extension().is_some() {
+        PathBuf::from(path)
+    } else {
+        Path::new(path).join("index.html")
+    }
+}
+
+/// Whether `href` is a path on the same site, rather than an absolute or relative URL
+fn is_internal(href: &str) -> bool {
+    href.starts_with('/') && !href.starts_with("//")
+}
+
+/// Reduces a path or link to the form used to compare them, such as `posts/hello-world`
+fn normalize(href: &str) -> String {
+    let end = href.find(&['#', '?'][..]).unwrap_or(href.len());
+    let path = href[..end].trim_matches('/');
+    let path = if path == "index.html" { "" } else { path.trim_end_matches("/index.html") };
+    path.to_string()
+}
@@ -0,0 +1,128 @@
+//! Subtrees precompiled to HTML, for parts of a page which never change.
+//!
+//! A `StaticNode` writes its node as HTML once, when it's created. Writing it with an
+//! `HtmlWriter` afterwards copies the precompiled HTML instead of visiting every node, so a
+//! `StaticNode` built once and cloned into each response, such as a site's header and footer,
+//! costs a copy of its bytes to render. The web renderer creates its elements from a template
+//! built from the same HTML, cloning the template whenever the subtree is rendered again, and
+//! never updates them.
+//!
+//! Other processors, and writers of other dialects, visit the node's tree as usual.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::static_node::StaticNode;
+//! use domafic::tags::{div, footer, header, main};
+//! use std::marker::PhantomData;
+//!
+//! let chrome = StaticNode::new(header(("My site", PhantomData::<()>)));
+//!
+//! let page = |body: &'static str| div((chrome.clone(), main(body), footer(())));
+//!
+//! assert_eq!(chrome.html(), "<header>My site</header>");
+//! assert_eq!(
+//!     page("Hello").to_string(),
+//!     "<div><header>My site</header><main>Hello</main><footer></footer></div>"
+//! );
+//! ```
+
+use {DomNode, DomNodes, DomValue, KeyValue};
+use processors::{DomNodeProcessor, EmptyListeners};
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// The HTML of a `StaticNode`
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Precompiled {
+    html: String,
+    hash: u64,
+}
+
+impl Precompiled {
+    /// The node's HTML, with its text and attributes escaped
+    pub fn html(&self) -> &str {
+        &self.html
+    }
+
+    /// A hash of the HTML, identifying the template the web renderer creates from it
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// A `DomNode` along with its precompiled HTML.
+///
+/// The listeners of the original node and its descendants are ignored, so that the web renderer
+/// can build its elements from the precompiled HTML. Listeners can be added to the `StaticNode`
+/// itself with `with_listeners`.
+pub struct StaticNode<M, T: DomNode<M>>(Arc<(T, Precompiled)>, PhantomData<M>);
+
+impl<M, T: DomNode<M>> StaticNode<M, T> {
+    /// Precompiles `node` to HTML
+    pub fn new(node: T) -> StaticNode<M, T> {
+        let mut html = Vec::new();
+        node.write_html(&mut html).expect("writing to a Vec can't fail");
+        let html = String::from_utf8(html).expect("HTML is written as UTF-8");
+        let mut hasher = DefaultHasher::new();
+        html.hash(&mut hasher);
+        let hash = hasher.finish();
+        StaticNode(Arc::new((node, Precompiled { html, hash })), PhantomData)
+    }
+
+    /// The node's precompiled HTML
+    pub fn html(&self) -> &str {
+        &(self.0).1.html
+    }
+}
+
+impl<M, T: DomNode<M>> Clone for StaticNode<M, T> {
+    fn clone(&self) -> StaticNode<M, T> {
+        StaticNode(self.0.clone(), PhantomData)
+    }
+}
+
+impl<M, T: DomNode<M>> DomNodes<M> for StaticNode<M, T> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+static EMPTY_LISTN_REF: &EmptyListeners = &EmptyListeners;
+
+impl<M, T: DomNode<M>> DomNode<M> for StaticNode<M, T> {
+    type Children = T::Children;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { (self.0).0.key() }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        (self.0).0.get_attribute(index)
+    }
+    fn children(&self) -> &Self::Children {
+        (self.0).0.children()
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        ((self.0).0.children(), EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { (self.0).0.value() }
+    fn props_hash(&self) -> Option<u64> { Some((self.0).1.hash) }
+    fn should_update(&self, _old_props_hash: u64) -> bool { false }
+    fn precompiled(&self) -> Option<&Precompiled> { Some(&(self.0).1) }
+}
+
+impl<M, T: DomNode<M>> fmt::Display for StaticNode<M, T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.displayable().fmt(formatter)
+    }
+}
@@ -0,0 +1,42 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Key-value string storage.
+///
+/// When rendering client-side, `web_render::WebStorage` provides access to the browser's
+/// `localStorage` and `sessionStorage`. `MemoryStorage` can be used server-side or in tests.
+pub trait Storage {
+    /// Returns the value stored under `key`, if any
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Stores `value` under `key`, replacing any previous value
+    fn set(&self, key: &str, value: &str);
+
+    /// Removes the value stored under `key`, if any
+    fn remove(&self, key: &str);
+}
+
+/// `Storage` backed by an in-memory map
+#[derive(Debug, Default)]
+pub struct MemoryStorage(RefCell<HashMap<String, String>>);
+
+impl MemoryStorage {
+    /// Creates an empty `MemoryStorage`
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.borrow().get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        self.0.borrow_mut().insert(key.to_string(), value.to_string());
+    }
+
+    fn remove(&self, key: &str) {
+        self.0.borrow_mut().remove(key);
+    }
+}
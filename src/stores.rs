@@ -0,0 +1,187 @@
+//! Splitting application state into independent stores, each updated by its own messages.
+//!
+//! A `Store` is a piece of state along with the messages which update it. `combine_stores!`
+//! declares a state struct with one field per store, which is itself a `Store` of
+//! `AnyMessage`s: each message is passed to the store whose `Message` type it holds, so stores
+//! don't have to share a single message enum or update function. The render function receives
+//! the combined state, and listeners produce `AnyMessage`s with `AnyMessage::new`.
+//!
+//! `Store`s are generic over the type passed to their updates for running effects. When
+//! rendering client-side, this is the `JsIo`, and `update_stores` is an `Updater` for any
+//! combined state.
+//!
+//! Example:
+//!
+//! ```rust
+//! #[macro_use]
+//! extern crate domafic;
+//!
+//! use domafic::KeyIter;
+//! use domafic::stores::{AnyMessage, Store};
+//!
+//! #[derive(Default)]
+//! struct Counter(i32);
+//! struct Increment;
+//!
+//! impl<Io: ?Sized> Store<Io> for Counter {
+//!     type Message = Increment;
+//!     fn update(&mut self, _msg: Increment, _keys: KeyIter, _io: &Io) { self.0 += 1; }
+//! }
+//!
+//! #[derive(Default)]
+//! struct Todos(Vec<String>);
+//! struct AddTodo(String);
+//!
+//! impl<Io: ?Sized> Store<Io> for Todos {
+//!     type Message = AddTodo;
+//!     fn update(&mut self, msg: AddTodo, _keys: KeyIter, _io: &Io) { self.0.push(msg.0); }
+//! }
+//!
+//! combine_stores! {
+//!     /// State of the whole app
+//!     #[derive(Default)]
+//!     pub struct App {
+//!         pub counter: Counter,
+//!         pub todos: Todos,
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let mut app = App::default();
+//!     app.route(AnyMessage::new(Increment), KeyIter::empty(), &()).unwrap();
+//!     app.route(AnyMessage::new(AddTodo("Write docs".to_string())), KeyIter::empty(), &()).unwrap();
+//!
+//!     assert_eq!(app.counter.0, 1);
+//!     assert_eq!(app.todos.0, vec!["Write docs".to_string()]);
+//!     assert!(app.route(AnyMessage::new("unknown"), KeyIter::empty(), &()).is_err());
+//! }
+//! ```
+
+use keys::KeyIter;
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+use web_render::JsIo;
+
+use std::any::Any;
+use std::fmt;
+
+/// A piece of application state along with the messages which update it
+pub trait Store<Io: ?Sized> {
+    /// Type of messages handled by the store. Each store in a combined state must have a
+    /// different message type.
+    type Message: 'static;
+
+    /// Updates the store based on a message, running effects through `io`
+    fn update(&mut self, msg: Self::Message, keys: KeyIter, io: &Io);
+}
+
+/// A message of any type, which is routed to the store handling messages of that type
+pub struct AnyMessage {
+    message: Box<dyn Any>,
+    type_name: &'static str,
+}
+
+impl AnyMessage {
+    /// Wraps `message` to be routed to the store whose `Message` type is `T`
+    pub fn new<T: 'static>(message: T) -> AnyMessage {
+        AnyMessage { message: Box::new(message), type_name: ::std::any::type_name::<T>() }
+    }
+
+    /// Whether the wrapped message is of type `T`
+    pub fn is<T: 'static>(&self) -> bool {
+        self.message.is::<T>()
+    }
+
+    /// Unwraps the message if it's of type `T`, or returns it unchanged otherwise
+    pub fn downcast<T: 'static>(self) -> Result<T, AnyMessage> {
+        let type_name = self.type_name;
+        match self.message.downcast::<T>() {
+            Ok(message) => Ok(*message),
+            Err(message) => Err(AnyMessage { message, type_name }),
+        }
+    }
+
+    /// The name of the wrapped message's type
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl fmt::Debug for AnyMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AnyMessage({})", self.type_name)
+    }
+}
+
+/// Declares a struct with a field for each of the given stores, which routes each
+/// `AnyMessage` to the store handling its type.
+///
+/// The struct gets a `route` method, which returns messages that no store handles, and
+/// implements `Store` with `AnyMessage`s as its messages, panicking on messages no store
+/// handles. Attributes, such as doc comments and derives, are passed through to the struct.
+///
+/// See the `stores` module for an example.
+#[macro_export]
+macro_rules! combine_stores {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident: $store:ty),* $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $store,
+            )*
+        }
+
+        impl $name {
+            /// Passes `msg` to the store handling its type, or returns it if no store does
+            #[allow(dead_code)]
+            pub fn route<Io: ?Sized>(
+                &mut self,
+                msg: $crate::stores::AnyMessage,
+                keys: $crate::KeyIter,
+                io: &Io,
+            ) -> ::std::result::Result<(), $crate::stores::AnyMessage>
+                where $($store: $crate::stores::Store<Io>),*
+            {
+                $(
+                    let msg = match msg.downcast::<<$store as $crate::stores::Store<Io>>::Message>() {
+                        Ok(msg) => {
+                            $crate::stores::Store::<Io>::update(&mut self.$field, msg, keys, io);
+                            return Ok(());
+                        },
+                        Err(msg) => msg,
+                    };
+                )*
+                Err(msg)
+            }
+        }
+
+        impl<Io: ?Sized> $crate::stores::Store<Io> for $name
+            where $($store: $crate::stores::Store<Io>),*
+        {
+            type Message = $crate::stores::AnyMessage;
+
+            fn update(&mut self, msg: $crate::stores::AnyMessage, keys: $crate::KeyIter, io: &Io) {
+                if let Err(msg) = self.route(msg, keys, io) {
+                    panic!("No store in `{}` handles messages of type `{}`",
+                        stringify!($name), msg.type_name());
+                }
+            }
+        }
+    };
+}
+
+/// An `Updater` for a state declared with `combine_stores!`, passing each message to the store
+/// handling its type.
+///
+/// Example: `run("body", update_stores, render, App::default())`
+#[cfg(all(feature = "web_render", target_os = "emscripten"))]
+pub fn update_stores<S>(state: &mut S, msg: AnyMessage, keys: KeyIter, js_io: &dyn JsIo<AnyMessage>)
+    where S: for<'a> Store<dyn JsIo<AnyMessage> + 'a, Message = AnyMessage>
+{
+    state.update(msg, keys, js_io)
+}
@@ -0,0 +1,106 @@
+//! Typed inline styles.
+//!
+//! `style` builds a `style` attribute from a list of declarations, whose values are anything
+//! which converts to a `stylesheet::Value`: keywords, `theme::Length`s, `theme::Color`s and
+//! unitless numbers. Declarations of the same type can be given as an array or a `Vec`, and
+//! declarations of different types as a tuple. The attribute is passed to a tag like those
+//! from `tags::attributes`, and a tag given several `style` attributes, such as one from a
+//! component and one from its caller, renders them merged into one, both to HTML and in the
+//! browser.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::styles::style;
+//! use domafic::tags::div;
+//! use domafic::theme::{Color, Length::Px};
+//! use std::marker::PhantomData;
+//!
+//! let node = div((
+//!     style((("color", "red"), ("width", Px(30.0)))),
+//!     style([("background", Color::Rgb(255, 255, 255))]),
+//!     PhantomData::<()>,
+//! ));
+//! assert_eq!(node.displayable().to_string(),
+//!     "<div style=\"color: red; width: 30px; background: #ffffff\"></div>");
+//! ```
+
+use {AttributeValue, KeyValue};
+use stylesheet::Value;
+use tags::{attributes, Attrs};
+
+use std::fmt::Write;
+
+/// A list of CSS declarations, each a property and its value
+pub trait Declarations {
+    /// Appends the declarations to `css`, separated by `; `
+    fn write_css(self, css: &mut String);
+}
+
+fn write_declaration(css: &mut String, property: &str, value: Value) {
+    if !css.is_empty() {
+        css.push_str("; ");
+    }
+    // Writing to a `String` can't fail
+    let _ = write!(css, "{}: {}", property, value);
+}
+
+impl<V: Into<Value>> Declarations for Vec<(&'static str, V)> {
+    fn write_css(self, css: &mut String) {
+        for (property, value) in self {
+            write_declaration(css, property, value.into());
+        }
+    }
+}
+
+macro_rules! array_impls {
+    ($($len:expr,)*) => { $(
+        impl<V: Into<Value>> Declarations for [(&'static str, V); $len] {
+            fn write_css(self, css: &mut String) {
+                for (property, value) in self {
+                    write_declaration(css, property, value.into());
+                }
+            }
+        }
+    )* }
+}
+
+array_impls!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,);
+
+macro_rules! tuple_impls {
+    ($(($($typ:ident . $idx:tt),+))*) => { $(
+        impl<$($typ: Into<Value>),+> Declarations for ($((&'static str, $typ),)+) {
+            fn write_css(self, css: &mut String) {
+                $( write_declaration(css, (self.$idx).0, (self.$idx).1.into()); )+
+            }
+        }
+    )* }
+}
+
+tuple_impls!(
+    (A.0)
+    (A.0, B.1)
+    (A.0, B.1, C.2)
+    (A.0, B.1, C.2, D.3)
+    (A.0, B.1, C.2, D.3, E.4)
+    (A.0, B.1, C.2, D.3, E.4, F.5)
+    (A.0, B.1, C.2, D.3, E.4, F.5, G.6)
+    (A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7)
+    (A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7, I.8)
+    (A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7, I.8, J.9)
+    (A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7, I.8, J.9, K.10)
+    (A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7, I.8, J.9, K.10, L.11)
+);
+
+/// The declarations as CSS text, as written in a `style` attribute
+pub fn to_css<D: Declarations>(declarations: D) -> String {
+    let mut css = String::new();
+    declarations.write_css(&mut css);
+    css
+}
+
+/// A `style` attribute setting `declarations`, to be passed to a tag
+pub fn style<D: Declarations>(declarations: D) -> Attrs<[KeyValue; 1]> {
+    attributes([("style", AttributeValue::OwnedStr(to_css(declarations)))])
+}
@@ -0,0 +1,251 @@
+//! A typed builder for CSS stylesheets.
+//!
+//! Stylesheets are built from typed selectors and declarations, whose values use the
+//! `Length` and `Color` types from the `theme` module. A `Stylesheet` can be built at runtime with
+//! its builder methods, or declared as a `const` from static slices. Either way it's rendered
+//! to CSS text through its `Display` implementation.
+//!
+//! `Selector::Scope` refers to the styled node when a stylesheet is passed to `scoped`, which
+//! collects it through the same mechanism as `css::styled`.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::stylesheet::{Declaration, Rule, Selector, Stylesheet, Value};
+//! use domafic::theme::{Color, Length};
+//! use std::borrow::Cow;
+//!
+//! // Declared at compile time
+//! const CARD: Rule = Rule {
+//!     selectors: Cow::Borrowed(&[Selector::Scope]),
+//!     declarations: Cow::Borrowed(&[
+//!         Declaration("padding", Value::Lengths(&[Length::Rem(1.0), Length::Rem(2.0)])),
+//!         Declaration("color", Value::Color(Color::Rgb(17, 17, 17))),
+//!     ]),
+//! };
+//!
+//! // Built at runtime
+//! let sheet = Stylesheet::new()
+//!     .with_rule(CARD)
+//!     .with_media("(max-width: 600px)", vec![
+//!         Rule::new(vec![Selector::Child(&Selector::Scope, &Selector::Tag("p"))])
+//!             .with("margin", Length::Px(0.0))
+//!             .with("display", "none"),
+//!     ]);
+//!
+//! assert_eq!(
+//!     sheet.to_string(),
+//!     "& { padding: 1rem 2rem; color: #111111; }\n\
+//!      @media (max-width: 600px) {\n& > p { margin: 0px; display: none; }\n}"
+//! );
+//! ```
+
+use DomNode;
+use css::{styled, Styled};
+use theme::{Color, Length};
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// A CSS selector
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Selector {
+    /// The node a scoped stylesheet is applied to, written as `&`
+    Scope,
+    /// Any element, written as `*`
+    Any,
+    /// Elements with the given tag name
+    Tag(&'static str),
+    /// Elements with the given class
+    Class(&'static str),
+    /// The element with the given id
+    Id(&'static str),
+    /// Elements matching an attribute selector, such as `Attribute("disabled")` or
+    /// `Attribute("type=\"text\"")`
+    Attribute(&'static str),
+    /// Elements matching a selector in the given pseudo-class state, such as `hover`
+    PseudoClass(&'static Selector, &'static str),
+    /// Elements matching both selectors
+    And(&'static Selector, &'static Selector),
+    /// Elements matching the second selector inside elements matching the first
+    Descendant(&'static Selector, &'static Selector),
+    /// Elements matching the second selector which are children of elements matching the first
+    Child(&'static Selector, &'static Selector),
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Selector::Scope => f.write_str("&"),
+            Selector::Any => f.write_str("*"),
+            Selector::Tag(tag) => f.write_str(tag),
+            Selector::Class(class) => write!(f, ".{}", class),
+            Selector::Id(id) => write!(f, "#{}", id),
+            Selector::Attribute(attribute) => write!(f, "[{}]", attribute),
+            Selector::PseudoClass(selector, pseudo_class) => write!(f, "{}:{}", selector, pseudo_class),
+            Selector::And(first, second) => write!(f, "{}{}", first, second),
+            Selector::Descendant(ancestor, descendant) => write!(f, "{} {}", ancestor, descendant),
+            Selector::Child(parent, child) => write!(f, "{} > {}", parent, child),
+        }
+    }
+}
+
+/// The value of a CSS property
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Value {
+    /// A single length
+    Length(Length),
+    /// Several lengths separated by spaces, as used by properties such as `margin`
+    Lengths(&'static [Length]),
+    /// A color
+    Color(Color),
+    /// A unitless number, as used by properties such as `opacity`
+    Number(f32),
+    /// A keyword or any other CSS value, such as `"none"` or `"var(--color-text)"`
+    Keyword(&'static str),
+}
+
+impl From<Length> for Value {
+    fn from(length: Length) -> Value { Value::Length(length) }
+}
+
+impl From<Color> for Value {
+    fn from(color: Color) -> Value { Value::Color(color) }
+}
+
+impl From<f32> for Value {
+    fn from(number: f32) -> Value { Value::Number(number) }
+}
+
+impl From<&'static str> for Value {
+    fn from(keyword: &'static str) -> Value { Value::Keyword(keyword) }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Length(length) => length.fmt(f),
+            Value::Lengths(lengths) => {
+                for (i, length) in lengths.iter().enumerate() {
+                    if i > 0 { f.write_str(" ")?; }
+                    length.fmt(f)?;
+                }
+                Ok(())
+            },
+            Value::Color(color) => color.fmt(f),
+            Value::Number(number) => number.fmt(f),
+            Value::Keyword(keyword) => f.write_str(keyword),
+        }
+    }
+}
+
+/// A CSS declaration of a property and its value
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Declaration(pub &'static str, pub Value);
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {};", self.0, self.1)
+    }
+}
+
+/// A CSS rule, applying declarations to the elements matching any of its selectors
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// Selectors for the elements the rule applies to
+    pub selectors: Cow<'static, [Selector]>,
+    /// Declarations applied to the matching elements
+    pub declarations: Cow<'static, [Declaration]>,
+}
+
+impl Rule {
+    /// Creates a rule with no declarations
+    pub fn new(selectors: Vec<Selector>) -> Rule {
+        Rule { selectors: Cow::Owned(selectors), declarations: Cow::Owned(Vec::new()) }
+    }
+
+    /// Adds a declaration
+    pub fn with<V: Into<Value>>(mut self, property: &'static str, value: V) -> Rule {
+        self.declarations.to_mut().push(Declaration(property, value.into()));
+        self
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, selector) in self.selectors.iter().enumerate() {
+            if i > 0 { f.write_str(", ")?; }
+            selector.fmt(f)?;
+        }
+        f.write_str(" {")?;
+        for declaration in self.declarations.iter() {
+            write!(f, " {}", declaration)?;
+        }
+        f.write_str(" }")
+    }
+}
+
+/// A rule or media query in a `Stylesheet`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    /// A rule
+    Rule(Rule),
+    /// Rules which only apply when a media query, such as `"(max-width: 600px)"`, matches
+    Media(&'static str, Cow<'static, [Rule]>),
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Item::Rule(ref rule) => rule.fmt(f),
+            Item::Media(query, ref rules) => {
+                writeln!(f, "@media {} {{", query)?;
+                for rule in rules.iter() {
+                    writeln!(f, "{}", rule)?;
+                }
+                f.write_str("}")
+            },
+        }
+    }
+}
+
+/// A list of CSS rules and media queries
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stylesheet {
+    /// The items of the stylesheet, in order
+    pub items: Cow<'static, [Item]>,
+}
+
+impl Stylesheet {
+    /// Creates an empty stylesheet
+    pub fn new() -> Stylesheet {
+        Stylesheet::default()
+    }
+
+    /// Adds a rule
+    pub fn with_rule(mut self, rule: Rule) -> Stylesheet {
+        self.items.to_mut().push(Item::Rule(rule));
+        self
+    }
+
+    /// Adds rules which only apply when `query` matches
+    pub fn with_media(mut self, query: &'static str, rules: Vec<Rule>) -> Stylesheet {
+        self.items.to_mut().push(Item::Media(query, Cow::Owned(rules)));
+        self
+    }
+}
+
+impl fmt::Display for Stylesheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 { f.write_str("\n")?; }
+            item.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Scopes `stylesheet` to `node`, as `css::styled` does for CSS text
+pub fn scoped<M, T: DomNode<M>>(stylesheet: &Stylesheet, node: T) -> Styled<M, T> {
+    styled(&stylesheet.to_string(), node)
+}
@@ -0,0 +1,668 @@
+//! Subscriptions deliver messages for events which don't originate from an element in the
+//! rendered `DomNode` tree, such as the page being hidden or unloaded, the window being resized,
+//! or timers.
+//!
+//! When rendering client-side, pass a subscriber function to `web_render::run_with_subscriptions`.
+//! The subscriber is called with the current state after every update, and the browser
+//! listeners backing its subscriptions are added or removed to match the list it returns.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::subscriptions::{every, on_before_unload, on_visibility_change, Subscription};
+//!
+//! struct State { paused: bool, unsaved_changes: bool }
+//! enum Msg { Hidden(bool), Tick(f64), Unloading }
+//!
+//! fn subscriptions(state: &State) -> Vec<Subscription<Msg>> {
+//!     let mut subscriptions = vec![
+//!         on_visibility_change(Msg::Hidden),
+//!         // Ask the user to confirm leaving the page while there are unsaved changes
+//!         on_before_unload(state.unsaved_changes, || Msg::Unloading),
+//!     ];
+//!     // The clock stops while paused, since the timer is only kept while it's returned
+//!     if !state.paused {
+//!         subscriptions.push(every(1000, Msg::Tick));
+//!     }
+//!     subscriptions
+//! }
+//! # fn main() {
+//! #     let state = State { paused: false, unsaved_changes: true };
+//! #     assert_eq!(subscriptions(&state).len(), 3);
+//! # }
+//! ```
+
+use ElementRef;
+
+use std::rc::Rc;
+
+/// The event that a `Subscription` listens for
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum SubscriptionSource {
+    /// The document's `visibilitychange` event
+    VisibilityChange,
+    /// The window's `pagehide` event
+    PageHide,
+    /// The document's `freeze` event, fired when the browser suspends a background page
+    Freeze,
+    /// The document's `resume` event, fired when a frozen page is resumed
+    Resume,
+    /// The window's `beforeunload` event
+    BeforeUnload {
+        /// Whether to ask the user to confirm that they want to leave the page
+        prompt: bool,
+    },
+    /// The document's `fullscreenchange` event
+    FullscreenChange,
+    /// The document's `pointerlockchange` event
+    PointerLockChange,
+    /// The window's `deviceorientation` event
+    DeviceOrientation,
+    /// The window's `devicemotion` event
+    DeviceMotion,
+    /// The `change` event of the `MediaQueryList` for a CSS media query
+    MediaQuery(&'static str),
+    /// The window's `popstate` event
+    PopState,
+    /// The window's `keydown` event
+    KeyDown,
+    /// The window's `online` event
+    Online,
+    /// The window's `offline` event
+    Offline,
+    /// The window's `resize` event
+    Resize,
+    /// A timer which fires every given number of milliseconds
+    Interval(u32),
+    /// The browser's animation frames, which are requested for as long as the subscription is
+    /// kept
+    AnimationFrame,
+    /// An event of the given type on the elements the application is mounted under
+    RootEvent(&'static str),
+    /// An event of the given type on the page's `<body>`
+    BodyEvent(&'static str),
+    /// A `click` on the document outside of the element marked with the ref
+    ClickOutside(ElementRef),
+}
+
+impl SubscriptionSource {
+    /// The name of the DOM event, or of the kind of timer for `Interval` and `AnimationFrame`
+    pub fn event_type(&self) -> &'static str {
+        match *self {
+            SubscriptionSource::VisibilityChange => "visibilitychange",
+            SubscriptionSource::PageHide => "pagehide",
+            SubscriptionSource::Freeze => "freeze",
+            SubscriptionSource::Resume => "resume",
+            SubscriptionSource::BeforeUnload { .. } => "beforeunload",
+            SubscriptionSource::FullscreenChange => "fullscreenchange",
+            SubscriptionSource::PointerLockChange => "pointerlockchange",
+            SubscriptionSource::DeviceOrientation => "deviceorientation",
+            SubscriptionSource::DeviceMotion => "devicemotion",
+            SubscriptionSource::MediaQuery(_) => "change",
+            SubscriptionSource::PopState => "popstate",
+            SubscriptionSource::KeyDown => "keydown",
+            SubscriptionSource::Online => "online",
+            SubscriptionSource::Offline => "offline",
+            SubscriptionSource::Resize => "resize",
+            SubscriptionSource::Interval(_) => "interval",
+            SubscriptionSource::AnimationFrame => "animationframe",
+            SubscriptionSource::RootEvent(event_type) => event_type,
+            SubscriptionSource::BodyEvent(event_type) => event_type,
+            SubscriptionSource::ClickOutside(_) => "click",
+        }
+    }
+
+    /// Whether the event is listened for relative to an element, such as the elements the
+    /// application is mounted under or the page's `<body>`, rather than being one of the
+    /// document's or window's own events
+    pub fn is_element_event(&self) -> bool {
+        matches!(*self,
+            SubscriptionSource::RootEvent(_) |
+            SubscriptionSource::BodyEvent(_) |
+            SubscriptionSource::ClickOutside(_))
+    }
+}
+
+/// Produces a message, if the event calls for one, from numbers describing the event, such as
+/// whether the page is hidden
+type Handler<Message> = Box<dyn Fn(&[f64]) -> Option<Message>>;
+
+/// A subscription to an event outside of the rendered `DomNode` tree
+pub struct Subscription<Message> {
+    source: SubscriptionSource,
+    handler: Handler<Message>,
+}
+
+impl<Message> Subscription<Message> {
+    /// The event this subscription listens for
+    pub fn source(&self) -> SubscriptionSource {
+        self.source
+    }
+
+    // Only called by the client-side runtime
+    #[cfg_attr(not(all(feature = "web_render", target_os = "emscripten")), allow(dead_code))]
+    pub(crate) fn handle(&self, detail: &[f64]) -> Option<Message> {
+        (self.handler)(detail)
+    }
+}
+
+/// Reads a boolean flag passed from JavaScript as a number
+fn flag(detail: &[f64], index: usize) -> bool {
+    detail.get(index).cloned().unwrap_or(0.0) != 0.0
+}
+
+/// Reads a number passed from JavaScript, which is `NaN` if the value was missing
+fn number(detail: &[f64], index: usize) -> Option<f64> {
+    detail.get(index).cloned().filter(|value| !value.is_nan())
+}
+
+/// Subscribes to changes in the page's visibility.
+///
+/// `f` receives `true` if the page has become hidden, such as when the user switches tabs,
+/// and `false` if it has become visible again.
+pub fn on_visibility_change<M, F>(f: F) -> Subscription<M>
+    where F: Fn(bool) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::VisibilityChange,
+        handler: Box::new(move |detail| Some(f(flag(detail, 0)))),
+    }
+}
+
+/// Subscribes to the page being hidden as the user navigates away from it.
+///
+/// `f` receives `true` if the page may be kept in the browser's back-forward cache, in which
+/// case it may be shown again without being reloaded.
+pub fn on_page_hide<M, F>(f: F) -> Subscription<M>
+    where F: Fn(bool) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::PageHide,
+        handler: Box::new(move |detail| Some(f(flag(detail, 0)))),
+    }
+}
+
+/// Subscribes to the browser freezing the page to save resources.
+///
+/// No timers or network callbacks run while the page is frozen, so this is the last chance
+/// to save state before the page may be discarded.
+pub fn on_freeze<M, F>(f: F) -> Subscription<M>
+    where F: Fn() -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::Freeze,
+        handler: Box::new(move |_| Some(f())),
+    }
+}
+
+/// Subscribes to the browser resuming a frozen page
+pub fn on_resume<M, F>(f: F) -> Subscription<M>
+    where F: Fn() -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::Resume,
+        handler: Box::new(move |_| Some(f())),
+    }
+}
+
+/// Subscribes to the page being about to unload.
+///
+/// If `prompt` is `true`, the browser asks the user to confirm that they want to leave the
+/// page. The message produced by `f` is handled before the prompt is shown, and the
+/// subscription's `prompt` value after that update is the one used.
+pub fn on_before_unload<M, F>(prompt: bool, f: F) -> Subscription<M>
+    where F: Fn() -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::BeforeUnload { prompt },
+        handler: Box::new(move |_| Some(f())),
+    }
+}
+
+/// Subscribes to the page entering or leaving fullscreen mode.
+///
+/// `f` receives `true` if an element is now being shown fullscreen.
+pub fn on_fullscreen_change<M, F>(f: F) -> Subscription<M>
+    where F: Fn(bool) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::FullscreenChange,
+        handler: Box::new(move |detail| Some(f(flag(detail, 0)))),
+    }
+}
+
+/// Subscribes to the pointer being locked to, or released from, an element.
+///
+/// `f` receives `true` if the pointer is now locked.
+pub fn on_pointer_lock_change<M, F>(f: F) -> Subscription<M>
+    where F: Fn(bool) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::PointerLockChange,
+        handler: Box::new(move |detail| Some(f(flag(detail, 0)))),
+    }
+}
+
+/// Subscribes to the user moving through the session history, such as with the back and
+/// forward buttons.
+///
+/// The page's new URL can be read from `web_render::location_path`, or matched to a route by
+/// using `router::on_route_change` instead.
+pub fn on_pop_state<M, F>(f: F) -> Subscription<M>
+    where F: Fn() -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::PopState,
+        handler: Box::new(move |_| Some(f())),
+    }
+}
+
+/// Subscribes to the device regaining its network connection.
+///
+/// Browsers report being online when connected to any network, which may not reach the
+/// internet. The `offline` module retries HTTP requests on top of this subscription.
+pub fn on_online<M, F>(f: F) -> Subscription<M>
+    where F: Fn() -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::Online,
+        handler: Box::new(move |_| Some(f())),
+    }
+}
+
+/// Subscribes to the device losing its network connection
+pub fn on_offline<M, F>(f: F) -> Subscription<M>
+    where F: Fn() -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::Offline,
+        handler: Box::new(move |_| Some(f())),
+    }
+}
+
+/// The size of the browser's viewport, in CSS pixels
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Viewport {
+    /// Width of the viewport, including any vertical scrollbar
+    pub width: u32,
+    /// Height of the viewport, including any horizontal scrollbar
+    pub height: u32,
+}
+
+/// Subscribes to the browser's window being resized.
+///
+/// `f` receives the new size of the viewport. Resizing by dragging the window fires many
+/// events, which the `rate_limit` module can coalesce.
+pub fn on_resize<M, F>(f: F) -> Subscription<M>
+    where F: Fn(Viewport) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::Resize,
+        handler: Box::new(move |detail| Some(f(Viewport {
+            width: number(detail, 0).unwrap_or(0.0) as u32,
+            height: number(detail, 1).unwrap_or(0.0) as u32,
+        }))),
+    }
+}
+
+/// Subscribes to a timer which fires every `interval_millis` milliseconds.
+///
+/// `f` receives the current time, in milliseconds since the Unix epoch. The timer starts when
+/// the subscription is first returned, and is kept running across updates for as long as the
+/// subscriber returns a subscription with the same interval, so that returning it after each
+/// update doesn't restart it. Browsers fire timers less often in background tabs.
+pub fn every<M, F>(interval_millis: u32, f: F) -> Subscription<M>
+    where F: Fn(f64) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::Interval(interval_millis),
+        handler: Box::new(move |detail| Some(f(number(detail, 0).unwrap_or(0.0)))),
+    }
+}
+
+/// Subscribes to the browser's animation frames, for animations which are driven by the
+/// application's state.
+///
+/// `f` receives the time since the previous frame, or since the subscription started for the
+/// first frame, in milliseconds. Each frame's message is handled before the browser paints,
+/// and frames stop once the subscriber no longer returns the subscription, so it should only
+/// be returned while something is moving. Browsers pause animation frames in background tabs.
+pub fn on_animation_frame<M, F>(f: F) -> Subscription<M>
+    where F: Fn(f64) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::AnimationFrame,
+        handler: Box::new(move |detail| Some(f(number(detail, 0).unwrap_or(0.0)))),
+    }
+}
+
+/// An event on an element which the application doesn't render, delivered by a subscription
+/// from `on_root_event` or `on_body_event`
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct ElementEvent {
+    /// Whether the event was dispatched on the element itself, rather than on one of its
+    /// descendants
+    pub on_element: bool,
+    /// Whether the event's target is inside the elements the application is mounted under
+    pub inside_app: bool,
+}
+
+impl ElementEvent {
+    fn from_detail(detail: &[f64]) -> ElementEvent {
+        ElementEvent { on_element: flag(detail, 0), inside_app: flag(detail, 1) }
+    }
+}
+
+/// Subscribes to events of `event_type`, such as `"click"`, on the elements the application is
+/// mounted under, including events which bubble up from the rendered elements.
+///
+/// `f` decides whether an event produces a message. Messages are delivered with an empty
+/// `KeyIter`.
+pub fn on_root_event<M, F>(event_type: &'static str, f: F) -> Subscription<M>
+    where F: Fn(ElementEvent) -> Option<M> + 'static
+{
+    Subscription {
+        source: SubscriptionSource::RootEvent(event_type),
+        handler: Box::new(move |detail| f(ElementEvent::from_detail(detail))),
+    }
+}
+
+/// Subscribes to events of `event_type` on the page's `<body>`, such as clicks outside of a
+/// menu, which should close it:
+///
+/// ```rust
+/// use domafic::subscriptions::on_body_event;
+///
+/// enum Msg { CloseMenu }
+///
+/// let _close = on_body_event("click", |event| if event.inside_app {
+///     None
+/// } else {
+///     Some(Msg::CloseMenu)
+/// });
+/// ```
+///
+/// `f` decides whether an event produces a message. Messages are delivered with an empty
+/// `KeyIter`.
+pub fn on_body_event<M, F>(event_type: &'static str, f: F) -> Subscription<M>
+    where F: Fn(ElementEvent) -> Option<M> + 'static
+{
+    Subscription {
+        source: SubscriptionSource::BodyEvent(event_type),
+        handler: Box::new(move |detail| f(ElementEvent::from_detail(detail))),
+    }
+}
+
+/// Subscribes to clicks anywhere on the page outside of the element marked with `element`, such
+/// as to close a dropdown or a modal.
+///
+/// Clicks are checked before they reach the application's own listeners, so a click inside the
+/// element which causes it to be rendered differently isn't mistaken for one outside of it. A
+/// subscription only sees clicks after the one which created it, so that the click which opens
+/// a dropdown doesn't also close it. No messages are produced while no element is marked with
+/// the ref.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::ElementRef;
+/// use domafic::subscriptions::{on_click_outside, Subscription};
+///
+/// const MENU: ElementRef = ElementRef("menu");
+///
+/// struct State { menu_open: bool }
+/// enum Msg { CloseMenu }
+///
+/// fn subscriptions(state: &State) -> Vec<Subscription<Msg>> {
+///     if state.menu_open { vec![on_click_outside(MENU, || Msg::CloseMenu)] } else { vec![] }
+/// }
+/// # fn main() {
+/// #     assert_eq!(subscriptions(&State { menu_open: true }).len(), 1);
+/// # }
+/// ```
+pub fn on_click_outside<M, F>(element: ElementRef, f: F) -> Subscription<M>
+    where F: Fn() -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::ClickOutside(element),
+        handler: Box::new(move |_| Some(f())),
+    }
+}
+
+/// A key pressed while the page has focus
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct KeyDown {
+    /// Keycode of the key, as in `Event::which_keycode`
+    pub key_code: u32,
+    /// Whether the "shift" key was held
+    pub shift_key: bool,
+    /// Whether the "alt" key was held
+    pub alt_key: bool,
+    /// Whether the "ctrl" key was held
+    pub ctrl_key: bool,
+    /// Whether the "meta" key was held
+    pub meta_key: bool,
+    /// Whether the key was pressed while typing into a text field or another editable element
+    pub editing: bool,
+    /// Whether the key is being held down, so that the press repeats
+    pub repeat: bool,
+}
+
+/// Subscribes to keys pressed anywhere on the page, such as for keyboard shortcuts.
+///
+/// `f` decides whether a key press produces a message. The browser's default action for the
+/// key, such as saving the page for Ctrl+S, is prevented for the presses which do, and left
+/// alone for the others. The `shortcuts` module maps key combinations to messages on top of
+/// this subscription.
+pub fn on_key_down<M, F>(f: F) -> Subscription<M>
+    where F: Fn(KeyDown) -> Option<M> + 'static
+{
+    Subscription {
+        source: SubscriptionSource::KeyDown,
+        handler: Box::new(move |detail| f(KeyDown {
+            key_code: number(detail, 0).unwrap_or(0.0) as u32,
+            shift_key: flag(detail, 1),
+            alt_key: flag(detail, 2),
+            ctrl_key: flag(detail, 3),
+            meta_key: flag(detail, 4),
+            editing: flag(detail, 5),
+            repeat: flag(detail, 6),
+        })),
+    }
+}
+
+/// The physical orientation of the device, in degrees.
+///
+/// Angles are `None` if the device can't measure them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DeviceOrientation {
+    /// Rotation around the z axis, from 0 to 360
+    pub alpha: Option<f64>,
+    /// Rotation around the x axis (tilting forwards and backwards), from -180 to 180
+    pub beta: Option<f64>,
+    /// Rotation around the y axis (tilting left and right), from -90 to 90
+    pub gamma: Option<f64>,
+    /// Whether `alpha` is relative to the Earth's coordinate frame rather than an arbitrary
+    /// starting orientation
+    pub absolute: bool,
+}
+
+/// Acceleration of the device along each axis, in meters per second squared
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Acceleration {
+    /// Acceleration along the x axis
+    pub x: f64,
+    /// Acceleration along the y axis
+    pub y: f64,
+    /// Acceleration along the z axis
+    pub z: f64,
+}
+
+/// Rate of rotation of the device around each axis, in degrees per second
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RotationRate {
+    /// Rotation rate around the z axis
+    pub alpha: f64,
+    /// Rotation rate around the x axis
+    pub beta: f64,
+    /// Rotation rate around the y axis
+    pub gamma: f64,
+}
+
+/// Motion of the device.
+///
+/// Measurements are `None` if the device can't provide them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DeviceMotion {
+    /// Acceleration excluding the effect of gravity
+    pub acceleration: Option<Acceleration>,
+    /// Acceleration including the effect of gravity
+    pub acceleration_including_gravity: Option<Acceleration>,
+    /// Rate of rotation
+    pub rotation_rate: Option<RotationRate>,
+    /// Interval at which the device is sampled, in milliseconds
+    pub interval_millis: f64,
+}
+
+/// Subscribes to changes in the physical orientation of the device.
+///
+/// Some browsers, such as Safari on iOS, require the user's permission before reporting
+/// orientation. Request it with `JsIo::request_motion_permission` in response to a user action.
+pub fn on_device_orientation<M, F>(f: F) -> Subscription<M>
+    where F: Fn(DeviceOrientation) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::DeviceOrientation,
+        handler: Box::new(move |detail| Some(f(DeviceOrientation {
+            alpha: number(detail, 0),
+            beta: number(detail, 1),
+            gamma: number(detail, 2),
+            absolute: flag(detail, 3),
+        }))),
+    }
+}
+
+/// Subscribes to reports of the device's motion, which arrive at a regular interval.
+///
+/// Some browsers, such as Safari on iOS, require the user's permission before reporting
+/// motion. Request it with `JsIo::request_motion_permission` in response to a user action.
+pub fn on_device_motion<M, F>(f: F) -> Subscription<M>
+    where F: Fn(DeviceMotion) -> M + 'static
+{
+    fn acceleration(detail: &[f64], start: usize) -> Option<Acceleration> {
+        match (number(detail, start), number(detail, start + 1), number(detail, start + 2)) {
+            (Some(x), Some(y), Some(z)) => Some(Acceleration { x, y, z }),
+            _ => None,
+        }
+    }
+
+    Subscription {
+        source: SubscriptionSource::DeviceMotion,
+        handler: Box::new(move |detail| Some(f(DeviceMotion {
+            acceleration: acceleration(detail, 0),
+            acceleration_including_gravity: acceleration(detail, 3),
+            rotation_rate: match (number(detail, 6), number(detail, 7), number(detail, 8)) {
+                (Some(alpha), Some(beta), Some(gamma)) => Some(RotationRate { alpha, beta, gamma }),
+                _ => None,
+            },
+            interval_millis: number(detail, 9).unwrap_or(0.0),
+        }))),
+    }
+}
+
+/// Subscribes to whether a CSS media query, such as `"(max-width: 600px)"`, matches.
+///
+/// `f` receives the query's result when the subscription starts, and again whenever the
+/// result changes.
+pub fn media_query<M, F>(query: &'static str, f: F) -> Subscription<M>
+    where F: Fn(bool) -> M + 'static
+{
+    Subscription {
+        source: SubscriptionSource::MediaQuery(query),
+        handler: Box::new(move |detail| Some(f(flag(detail, 0)))),
+    }
+}
+
+/// A set of viewport classes, such as "phone" or "desktop", each selected by a media query.
+///
+/// Store `Breakpoints` in the application state, subscribe to its media queries with
+/// `subscriptions`, pass the resulting messages to `set_matches`, and branch on the current
+/// class while rendering using `responsive`.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::subscriptions::{responsive, Breakpoints};
+///
+/// #[derive(Debug, Copy, Clone, PartialEq)]
+/// enum Viewport { Phone, Tablet, Desktop }
+///
+/// let mut breakpoints = Breakpoints::new(Viewport::Desktop)
+///     .with("(max-width: 600px)", Viewport::Phone)
+///     .with("(max-width: 1024px)", Viewport::Tablet);
+///
+/// // Delivered by the subscriptions from `breakpoints.subscriptions(...)`
+/// breakpoints.set_matches("(max-width: 1024px)", true);
+///
+/// let columns = responsive(&breakpoints, |viewport| match viewport {
+///     Viewport::Phone => 1,
+///     Viewport::Tablet => 2,
+///     Viewport::Desktop => 4,
+/// });
+/// assert_eq!(columns, 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Breakpoints<C> {
+    breakpoints: Vec<(&'static str, C, bool)>,
+    default: C,
+}
+
+impl<C: Copy> Breakpoints<C> {
+    /// Creates a set of breakpoints whose class is `default` when no media query matches
+    pub fn new(default: C) -> Breakpoints<C> {
+        Breakpoints { breakpoints: Vec::new(), default }
+    }
+
+    /// Adds a class which is selected when `query` matches.
+    ///
+    /// Classes added earlier take priority when several queries match.
+    pub fn with(mut self, query: &'static str, class: C) -> Breakpoints<C> {
+        self.breakpoints.push((query, class, false));
+        self
+    }
+
+    /// Records the latest result of `query`, as delivered by a subscription from
+    /// `subscriptions`
+    pub fn set_matches(&mut self, query: &str, matches: bool) {
+        for breakpoint in &mut self.breakpoints {
+            if breakpoint.0 == query { breakpoint.2 = matches; }
+        }
+    }
+
+    /// Returns the class of the first breakpoint whose query matches, or the default class if
+    /// none do
+    pub fn current(&self) -> C {
+        self.breakpoints.iter()
+            .find(|breakpoint| breakpoint.2)
+            .map_or(self.default, |breakpoint| breakpoint.1)
+    }
+
+    /// Subscribes to each breakpoint's media query, producing messages with `f`, which
+    /// receives the query and whether it matches
+    pub fn subscriptions<M, F>(&self, f: F) -> Vec<Subscription<M>>
+        where F: Fn(&'static str, bool) -> M + 'static
+    {
+        let f = Rc::new(f);
+        self.breakpoints.iter().map(|&(query, _, _)| {
+            let f = f.clone();
+            media_query(query, move |matches| f(query, matches))
+        }).collect()
+    }
+}
+
+/// Renders using the current class of `breakpoints`
+pub fn responsive<C, R, F>(breakpoints: &Breakpoints<C>, render: F) -> R
+    where C: Copy, F: FnOnce(C) -> R
+{
+    render(breakpoints.current())
+}
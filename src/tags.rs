@@ -12,6 +12,9 @@ use processors::{DomNodeProcessor, EmptyListeners};
 
 use opt_std::marker::PhantomData;
 
+#[cfg(feature = "strict_attrs")]
+pub mod strict;
+
 /// Properties used to create a `Tag` `DomNode`.
 ///
 /// This is primarily used as an input (via `Into<TagProperties>`) for the various tag functions.
@@ -19,7 +22,25 @@ use opt_std::marker::PhantomData;
 /// specifying all the fields for `TagProperties` by simply calling the tag function with the
 /// appropriate combination of listeners, attributes, and children.
 ///
-/// Note that multiple listeners or multiple children must be grouped into a single tuple.
+/// Note that multiple listeners or multiple children must be grouped into a single tuple, and
+/// that properties are given in the order attributes, listeners, children. Two groups of
+/// attributes can be given, such as attributes shared between several tags followed by those of
+/// one tag, which are merged in order. Attributes can't follow listeners or children, since
+/// other crates could implement `Listeners` or `DomNodes` for `Attrs` and make the order
+/// ambiguous, but they can be added to a finished node with `DomNode::with_attributes`.
+///
+/// Example:
+///
+/// ```rust
+/// use domafic::DomNode;
+/// use domafic::tags::{attributes, button};
+/// use domafic::AttributeValue::Str;
+/// use std::marker::PhantomData;
+///
+/// let shared = attributes([("class", Str("primary"))]);
+/// let save = button((shared, attributes([("id", Str("save"))]), ("Save", PhantomData::<()>)));
+/// assert_eq!(save.to_string(), "<button class=\"primary\" id=\"save\">Save</button>");
+/// ```
 pub struct TagProperties<
     Message,
     Children: DomNodes<Message>,
@@ -164,6 +185,78 @@ impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, L: Listeners<M>>
     }
 }
 
+// (attributes, attributes)
+#[cfg(any(feature = "use_std", test))]
+impl<M, A: AsRef<[KeyValue]>, B: AsRef<[KeyValue]>>
+    From<(Attrs<A>, Attrs<B>)> for TagProperties<M, (), Vec<KeyValue>, EmptyListeners>
+{
+    fn from(props: (Attrs<A>, Attrs<B>)) -> TagProperties<M, (), Vec<KeyValue>, EmptyListeners> {
+        TagProperties {
+            children: (),
+            key: None,
+            attributes: merge(props.0, props.1),
+            listeners: EmptyListeners,
+            msg_marker: PhantomData,
+        }
+    }
+}
+
+// (attributes, attributes, children)
+#[cfg(any(feature = "use_std", test))]
+impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, B: AsRef<[KeyValue]>>
+    From<(Attrs<A>, Attrs<B>, C)> for TagProperties<M, C, Vec<KeyValue>, EmptyListeners>
+{
+    fn from(props: (Attrs<A>, Attrs<B>, C)) -> TagProperties<M, C, Vec<KeyValue>, EmptyListeners> {
+        TagProperties {
+            children: props.2,
+            key: None,
+            attributes: merge(props.0, props.1),
+            listeners: EmptyListeners,
+            msg_marker: PhantomData,
+        }
+    }
+}
+
+// (attributes, attributes, listeners)
+#[cfg(any(feature = "use_std", test))]
+impl<M, A: AsRef<[KeyValue]>, B: AsRef<[KeyValue]>, L: Listeners<M>>
+    From<(Attrs<A>, Attrs<B>, L)> for TagProperties<M, (), Vec<KeyValue>, L>
+{
+    fn from(props: (Attrs<A>, Attrs<B>, L)) -> TagProperties<M, (), Vec<KeyValue>, L> {
+        TagProperties {
+            children: (),
+            key: None,
+            attributes: merge(props.0, props.1),
+            listeners: props.2,
+            msg_marker: PhantomData,
+        }
+    }
+}
+
+// (attributes, attributes, listeners, children)
+#[cfg(any(feature = "use_std", test))]
+impl<M, C: DomNodes<M>, A: AsRef<[KeyValue]>, B: AsRef<[KeyValue]>, L: Listeners<M>>
+    From<(Attrs<A>, Attrs<B>, L, C)> for TagProperties<M, C, Vec<KeyValue>, L>
+{
+    fn from(props: (Attrs<A>, Attrs<B>, L, C)) -> TagProperties<M, C, Vec<KeyValue>, L> {
+        TagProperties {
+            children: props.3,
+            key: None,
+            attributes: merge(props.0, props.1),
+            listeners: props.2,
+            msg_marker: PhantomData,
+        }
+    }
+}
+
+/// Concatenates two groups of attributes, in order
+#[cfg(any(feature = "use_std", test))]
+fn merge<A: AsRef<[KeyValue]>, B: AsRef<[KeyValue]>>(first: Attrs<A>, second: Attrs<B>) -> Vec<KeyValue> {
+    let mut attributes = first.0.as_ref().to_vec();
+    attributes.extend_from_slice(second.0.as_ref());
+    attributes
+}
+
 /// A tag element, such as `div` or `span`.
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub struct Tag<
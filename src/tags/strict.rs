@@ -0,0 +1,362 @@
+//! Tags with typed attribute setters, checking attribute names at compile time.
+//!
+//! Each function in this module creates a `StrictTag` for the element of the same name in
+//! `tags`. A `StrictTag` has a setter for each global attribute, such as `id` and `class`, and
+//! for each attribute the HTML standard defines for its element, so misspelled attributes and
+//! attributes set on the wrong element fail to compile. Boolean attributes, such as `disabled`,
+//! are only written when set to `true`. Void elements, such as `img`, have no `children` method.
+//!
+//! Setters are named after their attributes, with `-` replaced by `_` and with a trailing `_`
+//! for Rust keywords, as in `type_` and `for_`. `data-*` and `aria-*` attributes are set with
+//! `data` and `aria`. The functions in `tags` remain available for attributes this module
+//! doesn't know about.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNode;
+//! use domafic::tags::strict::{a, img};
+//!
+//! let logo = a::<()>()
+//!     .href("/")
+//!     .class("logo")
+//!     .children(img().src("/logo.png").alt("Home").width("64"));
+//!
+//! assert_eq!(
+//!     logo.to_string(),
+//!     "<a href=\"/\" class=\"logo\"><img src=\"/logo.png\" alt=\"Home\" width=\"64\"></a>"
+//! );
+//! ```
+//!
+//! Attributes which the element doesn't have are rejected:
+//!
+//! ```compile_fail
+//! use domafic::tags::strict::img;
+//!
+//! let image = img::<()>().href("/");
+//! ```
+
+use {AttributeValue, DomNode, DomNodes, DomValue, KeyValue, Listeners};
+use processors::{DomNodeProcessor, EmptyListeners};
+use super::Tag;
+
+use std::fmt;
+use std::marker::PhantomData;
+
+/// An element of the HTML standard
+pub trait Element {
+    /// The element's tag name
+    const TAG: &'static str;
+}
+
+/// An element which can have children, unlike void elements such as `img`
+pub trait Container: Element {}
+
+/// A tag whose attributes are set by typed setters, checked against its element `E`
+pub struct StrictTag<M, E: Element, C: DomNodes<M>, L: Listeners<M>> {
+    tag: Tag<M, C, Vec<KeyValue>, L>,
+    element: PhantomData<E>,
+}
+
+impl<M, E: Element, C: DomNodes<M>, L: Listeners<M>> StrictTag<M, E, C, L> {
+    fn attribute<V: Into<AttributeValue>>(mut self, key: &'static str, value: V) -> Self {
+        self.tag.attributes.push((key, value.into()));
+        self
+    }
+
+    fn flag(self, key: &'static str, set: bool) -> Self {
+        if set { self.attribute(key, "") } else { self }
+    }
+
+    /// Sets a `data-*` attribute. `key` includes the `data-` prefix.
+    pub fn data<V: Into<AttributeValue>>(self, key: &'static str, value: V) -> Self {
+        debug_assert!(key.starts_with("data-"), "{} is not a data attribute", key);
+        self.attribute(key, value)
+    }
+
+    /// Sets an `aria-*` attribute. `key` includes the `aria-` prefix.
+    pub fn aria<V: Into<AttributeValue>>(self, key: &'static str, value: V) -> Self {
+        debug_assert!(key.starts_with("aria-"), "{} is not an ARIA attribute", key);
+        self.attribute(key, value)
+    }
+
+    /// Replaces the tag's listeners
+    pub fn listeners<L2: Listeners<M>>(self, listeners: L2) -> StrictTag<M, E, C, L2> {
+        let Tag { tagname, children, key, attributes, msg_marker, .. } = self.tag;
+        StrictTag {
+            tag: Tag { tagname, children, key, attributes, listeners, msg_marker },
+            element: PhantomData,
+        }
+    }
+}
+
+impl<M, E: Container, C: DomNodes<M>, L: Listeners<M>> StrictTag<M, E, C, L> {
+    /// Replaces the tag's children
+    pub fn children<C2: DomNodes<M>>(self, children: C2) -> StrictTag<M, E, C2, L> {
+        let Tag { tagname, key, attributes, listeners, msg_marker, .. } = self.tag;
+        StrictTag {
+            tag: Tag { tagname, children, key, attributes, listeners, msg_marker },
+            element: PhantomData,
+        }
+    }
+}
+
+impl<M, E: Element, C: DomNodes<M>, L: Listeners<M>> DomNodes<M> for StrictTag<M, E, C, L> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M, E: Element, C: DomNodes<M>, L: Listeners<M>> DomNode<M> for StrictTag<M, E, C, L> {
+    type Children = C;
+    type Listeners = L;
+    type WithoutListeners = StrictTag<M, E, C, EmptyListeners>;
+    fn key(&self) -> Option<u32> { self.tag.key() }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.tag.get_attribute(index)
+    }
+    fn children(&self) -> &Self::Children {
+        self.tag.children()
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        self.tag.listeners()
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        self.tag.children_and_listeners()
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        let (tag, listeners) = self.tag.split_listeners();
+        (StrictTag { tag, element: PhantomData }, listeners)
+    }
+    fn value(&self) -> DomValue<'_> { self.tag.value() }
+}
+
+impl<M, E: Element, C: DomNodes<M>, L: Listeners<M>> fmt::Display for StrictTag<M, E, C, L> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt::Display::fmt(&self.tag, formatter)
+    }
+}
+
+macro_rules! attribute_name {
+    ($setter:ident) => { stringify!($setter) };
+    ($setter:ident $name:expr) => { $name };
+}
+
+macro_rules! attribute_setters {
+    ($($setter:ident $(= $name:expr)?),*; $($flag:ident $(= $flag_name:expr)?),*) => {
+        $(
+            #[doc = concat!("Sets the `", attribute_name!($setter $($name)?), "` attribute")]
+            pub fn $setter<V: Into<AttributeValue>>(self, value: V) -> Self {
+                self.attribute(attribute_name!($setter $($name)?), value)
+            }
+        )*
+        $(
+            #[doc = concat!("Sets the boolean `", attribute_name!($flag $($flag_name)?),
+                "` attribute, which is left out when `set` is `false`")]
+            pub fn $flag(self, set: bool) -> Self {
+                self.flag(attribute_name!($flag $($flag_name)?), set)
+            }
+        )*
+    }
+}
+
+impl<M, E: Element, C: DomNodes<M>, L: Listeners<M>> StrictTag<M, E, C, L> {
+    attribute_setters!(
+        accesskey, autocapitalize, class, contenteditable, dir, draggable, enterkeyhint,
+        id, inputmode, is, lang, nonce, role, slot, spellcheck, style, tabindex, title, translate;
+        autofocus, hidden, inert
+    );
+}
+
+macro_rules! strict_tags {
+    ($(
+        $tagname:ident => $element:ident $(: $container:ident)? {
+            $($setter:ident $(= $name:expr)?),*; $($flag:ident $(= $flag_name:expr)?),*
+        }
+    )*) => {
+        /// Types identifying the elements of `StrictTag`s
+        pub mod elements {
+            $(
+                #[doc = concat!("The `", stringify!($tagname), "` element")]
+                pub enum $element {}
+            )*
+        }
+
+        $(
+            impl Element for elements::$element {
+                const TAG: &'static str = stringify!($tagname);
+            }
+
+            $(impl $container for elements::$element {})?
+
+            impl<M, C: DomNodes<M>, L: Listeners<M>> StrictTag<M, elements::$element, C, L> {
+                attribute_setters!($($setter $(= $name)?),*; $($flag $(= $flag_name)?),*);
+            }
+
+            #[doc = concat!("Creates a `", stringify!($tagname),
+                "` tag with no attributes, listeners or children")]
+            pub fn $tagname<M>() -> StrictTag<M, elements::$element, (), EmptyListeners> {
+                StrictTag {
+                    tag: Tag {
+                        tagname: stringify!($tagname),
+                        children: (),
+                        key: None,
+                        attributes: Vec::new(),
+                        listeners: EmptyListeners,
+                        msg_marker: PhantomData,
+                    },
+                    element: PhantomData,
+                }
+            }
+        )*
+    }
+}
+
+// Elements marked `Container` can have children
+strict_tags!(
+    a => A: Container {
+        href, target, download, ping, rel, hreflang, type_ = "type", referrerpolicy;
+    }
+    abbr => Abbr: Container { ; }
+    address => Address: Container { ; }
+    area => Area {
+        alt, coords, shape, href, target, download, ping, rel, referrerpolicy;
+    }
+    article => Article: Container { ; }
+    aside => Aside: Container { ; }
+    audio => Audio: Container {
+        src, crossorigin, preload;
+        autoplay, controls, loop_ = "loop", muted
+    }
+    b => B: Container { ; }
+    base => Base { href, target; }
+    bdi => Bdi: Container { ; }
+    bdo => Bdo: Container { ; }
+    blockquote => Blockquote: Container { cite; }
+    body => Body: Container { ; }
+    br => Br { ; }
+    button => Button: Container {
+        form, formaction, formenctype, formmethod, formtarget, name, popovertarget,
+        popovertargetaction, type_ = "type", value;
+        disabled, formnovalidate
+    }
+    canvas => Canvas: Container { width, height; }
+    caption => Caption: Container { ; }
+    cite => Cite: Container { ; }
+    code => Code: Container { ; }
+    col => Col { span; }
+    colgroup => Colgroup: Container { span; }
+    datalist => Datalist: Container { ; }
+    dd => Dd: Container { ; }
+    del => Del: Container { cite, datetime; }
+    details => Details: Container { name; open }
+    dfn => Dfn: Container { ; }
+    dialog => Dialog: Container { ; open }
+    div => Div: Container { ; }
+    dl => Dl: Container { ; }
+    dt => Dt: Container { ; }
+    em => Em: Container { ; }
+    embed => Embed { src, type_ = "type", width, height; }
+    fieldset => Fieldset: Container { form, name; disabled }
+    figcaption => Figcaption: Container { ; }
+    figure => Figure: Container { ; }
+    footer => Footer: Container { ; }
+    form => Form: Container {
+        accept_charset = "accept-charset", action, autocomplete, enctype, method, name, rel,
+        target;
+        novalidate
+    }
+    h1 => H1: Container { ; }
+    h2 => H2: Container { ; }
+    h3 => H3: Container { ; }
+    h4 => H4: Container { ; }
+    h5 => H5: Container { ; }
+    h6 => H6: Container { ; }
+    head => Head: Container { ; }
+    header => Header: Container { ; }
+    hr => Hr { ; }
+    i => I: Container { ; }
+    iframe => Iframe: Container {
+        src, srcdoc, name, sandbox, allow, width, height, referrerpolicy, loading;
+        allowfullscreen
+    }
+    img => Img {
+        alt, src, srcset, sizes, crossorigin, usemap, referrerpolicy, decoding, loading,
+        fetchpriority, width, height;
+        ismap
+    }
+    input => Input {
+        accept, alt, autocomplete, dirname, form, formaction, formenctype, formmethod,
+        formtarget, height, list, max, maxlength, min, minlength, name, pattern, placeholder,
+        popovertarget, popovertargetaction, size, src, step, type_ = "type", value, width;
+        checked, disabled, formnovalidate, multiple, readonly, required
+    }
+    ins => Ins: Container { cite, datetime; }
+    kbd => Kbd: Container { ; }
+    label => Label: Container { for_ = "for"; }
+    legend => Legend: Container { ; }
+    li => Li: Container { value; }
+    link => Link {
+        href, crossorigin, rel, as_ = "as", media, integrity, hreflang, type_ = "type",
+        referrerpolicy, sizes, imagesrcset, imagesizes, color, fetchpriority;
+        disabled
+    }
+    main => Main: Container { ; }
+    map => Map: Container { name; }
+    mark => Mark: Container { ; }
+    menu => Menu: Container { ; }
+    meta => Meta { name, http_equiv = "http-equiv", content, charset, media; }
+    meter => Meter: Container { value, min, max, low, high, optimum; }
+    nav => Nav: Container { ; }
+    noscript => Noscript: Container { ; }
+    object => Object: Container { data_ = "data", type_ = "type", name, form, width, height; }
+    ol => Ol: Container { start, type_ = "type"; reversed }
+    optgroup => Optgroup: Container { label; disabled }
+    option => Option: Container { label, value; disabled, selected }
+    output => Output: Container { for_ = "for", form, name; }
+    p => P: Container { ; }
+    pre => Pre: Container { ; }
+    progress => Progress: Container { value, max; }
+    q => Q: Container { cite; }
+    rp => Rp: Container { ; }
+    rt => Rt: Container { ; }
+    ruby => Ruby: Container { ; }
+    s => S: Container { ; }
+    samp => Samp: Container { ; }
+    script => Script: Container {
+        src, type_ = "type", crossorigin, integrity, referrerpolicy, fetchpriority;
+        nomodule, async_ = "async", defer
+    }
+    section => Section: Container { ; }
+    select => Select: Container { autocomplete, form, name, size; disabled, multiple, required }
+    small => Small: Container { ; }
+    source => Source { type_ = "type", media, src, srcset, sizes, width, height; }
+    span => Span: Container { ; }
+    strong => Strong: Container { ; }
+    style => Style: Container { media; }
+    sub => Sub: Container { ; }
+    summary => Summary: Container { ; }
+    sup => Sup: Container { ; }
+    table => Table: Container { ; }
+    tbody => Tbody: Container { ; }
+    td => Td: Container { colspan, rowspan, headers; }
+    textarea => Textarea: Container {
+        autocomplete, cols, dirname, form, maxlength, minlength, name, placeholder, rows, wrap;
+        disabled, readonly, required
+    }
+    tfoot => Tfoot: Container { ; }
+    th => Th: Container { colspan, rowspan, headers, scope, abbr; }
+    thead => Thead: Container { ; }
+    time => Time: Container { datetime; }
+    title => Title: Container { ; }
+    tr => Tr: Container { ; }
+    track => Track { kind, src, srclang, label; default }
+    u => U: Container { ; }
+    ul => Ul: Container { ; }
+    var => Var: Container { ; }
+    video => Video: Container {
+        src, crossorigin, poster, preload, width, height;
+        autoplay, playsinline, loop_ = "loop", muted, controls
+    }
+    wbr => Wbr { ; }
+);
@@ -0,0 +1,323 @@
+//! Generators of arbitrary `DomNode` trees for property-based testing with `quickcheck`.
+//!
+//! `TestNode` is a tree of tags, attributes, text and keys built at runtime, which implements
+//! `quickcheck::Arbitrary` so that tests can be run against many generated trees, with failing
+//! trees shrunk to a minimal example. The invariants in this module can be checked against
+//! generated trees, and `TestNode`s can be passed to processors outside of the crate to test
+//! them in the same way.
+//!
+//! Example:
+//!
+//! ```rust
+//! extern crate domafic;
+//! extern crate quickcheck;
+//!
+//! use domafic::testing::{html_round_trips, TestNode};
+//! use quickcheck::QuickCheck;
+//!
+//! fn round_trips(node: TestNode) -> bool {
+//!     html_round_trips(&node)
+//! }
+//!
+//! fn main() {
+//!     QuickCheck::new().tests(50).quickcheck(round_trips as fn(TestNode) -> bool);
+//! }
+//! ```
+
+extern crate marksman_escape;
+extern crate quickcheck;
+
+use self::marksman_escape::Unescape;
+use self::quickcheck::{Arbitrary, Gen};
+
+use {AttributeValue, DomNode, DomNodes, DomValue, KeyValue};
+use devtools::{NodeSnapshot, SnapshotValue};
+use processors::{DomNodeProcessor, EmptyListeners};
+use xml_writer::VOID_ELEMENTS;
+
+/// Tags of generated elements
+pub const TAGS: &[&str] = &[
+    "div", "span", "p", "a", "ul", "li", "button", "section", "em", "br", "img", "input",
+];
+
+/// Keys of generated attributes
+pub const ATTRIBUTES: &[&str] = &["id", "class", "title", "href", "data-value", "aria-label"];
+
+/// Deepest nesting of generated elements
+const MAX_DEPTH: usize = 4;
+
+/// The contents of a `TestNode`
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum TestValue {
+    /// An element with the given tag
+    Tag(&'static str),
+    /// A text node
+    Text(String),
+}
+
+/// A `DomNode` built at runtime, which can be generated by `quickcheck`
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct TestNode {
+    /// The node's tag or text
+    pub value: TestValue,
+    /// The node's key
+    pub key: Option<u32>,
+    /// The node's attributes, which are empty for text nodes
+    pub attributes: Vec<KeyValue>,
+    /// The node's children, which are empty for text and void elements
+    pub children: Vec<TestNode>,
+}
+
+impl TestNode {
+    /// Creates an element with no attributes or children
+    pub fn tag(tag: &'static str) -> TestNode {
+        TestNode { value: TestValue::Tag(tag), key: None, attributes: Vec::new(), children: Vec::new() }
+    }
+
+    /// Creates a text node
+    pub fn text<S: Into<String>>(text: S) -> TestNode {
+        TestNode {
+            value: TestValue::Text(text.into()),
+            key: None,
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn arbitrary_at_depth(g: &mut Gen, depth: usize) -> TestNode {
+        if depth == MAX_DEPTH || u8::arbitrary(g) % 3 == 0 {
+            return TestNode::text(String::arbitrary(g));
+        }
+        let tag = *g.choose(TAGS).unwrap();
+        let mut node = TestNode::tag(tag);
+        if bool::arbitrary(g) {
+            node.key = Some(u32::arbitrary(g) % 8);
+        }
+        for _ in 0..usize::arbitrary(g) % 3 {
+            let key = *g.choose(ATTRIBUTES).unwrap();
+            node.attributes.push((key, AttributeValue::OwnedStr(String::arbitrary(g))));
+        }
+        if !VOID_ELEMENTS.contains(&tag) {
+            let max_children = g.size().min(MAX_DEPTH - depth + 1);
+            for _ in 0..usize::arbitrary(g) % (max_children + 1) {
+                node.children.push(TestNode::arbitrary_at_depth(g, depth + 1));
+            }
+        }
+        node
+    }
+}
+
+impl Arbitrary for TestNode {
+    fn arbitrary(g: &mut Gen) -> TestNode {
+        TestNode::arbitrary_at_depth(g, 0)
+    }
+
+    /// Shrinks to each child, then removes children, attributes and the key, then shrinks the
+    /// text or the children
+    fn shrink(&self) -> Box<dyn Iterator<Item = TestNode>> {
+        let mut smaller = self.children.clone();
+        for i in 0..self.children.len() {
+            let mut node = self.clone();
+            node.children.remove(i);
+            smaller.push(node);
+        }
+        for i in 0..self.attributes.len() {
+            let mut node = self.clone();
+            node.attributes.remove(i);
+            smaller.push(node);
+        }
+        if self.key.is_some() {
+            smaller.push(TestNode { key: None, ..self.clone() });
+        }
+        if let TestValue::Text(ref text) = self.value {
+            smaller.extend(text.shrink().map(TestNode::text));
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            for shrunk in child.shrink() {
+                let mut node = self.clone();
+                node.children[i] = shrunk;
+                smaller.push(node);
+            }
+        }
+        Box::new(smaller.into_iter())
+    }
+}
+
+impl<M> DomNodes<M> for TestNode {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+static EMPTY_LISTN_REF: &EmptyListeners = &EmptyListeners;
+
+impl<M> DomNode<M> for TestNode {
+    type Children = Vec<TestNode>;
+    type Listeners = EmptyListeners;
+    type WithoutListeners = TestNode;
+    fn key(&self) -> Option<u32> { self.key }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.attributes.get(index)
+    }
+    fn children(&self) -> &Self::Children {
+        &self.children
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (&self.children, EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> {
+        match self.value {
+            TestValue::Tag(tag) => DomValue::Element { tag },
+            TestValue::Text(ref text) => DomValue::Text(text),
+        }
+    }
+}
+
+/// Reduces `nodes` to the tree a browser would parse from their HTML: keys are removed,
+/// repeated `class` attributes are merged, adjacent text nodes are merged and empty text nodes
+/// are removed
+pub fn normalize(nodes: &[TestNode]) -> Vec<TestNode> {
+    let mut normalized: Vec<TestNode> = Vec::new();
+    for node in nodes {
+        match node.value {
+            TestValue::Text(ref text) if text.is_empty() => {},
+            TestValue::Text(ref text) => {
+                if let Some(&mut TestNode { value: TestValue::Text(ref mut last), .. }) =
+                    normalized.last_mut()
+                {
+                    last.push_str(text);
+                    continue;
+                }
+                normalized.push(TestNode::text(text.clone()));
+            },
+            TestValue::Tag(tag) => normalized.push(TestNode {
+                value: TestValue::Tag(tag),
+                key: None,
+                attributes: DomNode::<()>::merged_attributes(node)
+                    .map(|attr| (attr.0, AttributeValue::OwnedStr(attr.1.as_str().to_string())))
+                    .collect(),
+                children: normalize(&node.children),
+            }),
+        }
+    }
+    normalized
+}
+
+/// Parses HTML written by `DomNode::write_html` from a tree of `TestNode`s.
+///
+/// Returns `None` if the HTML is malformed or contains a tag or attribute key which isn't in
+/// `TAGS` or `ATTRIBUTES`.
+pub fn parse_html(html: &str) -> Option<Vec<TestNode>> {
+    let mut parser = Parser { html: html.as_bytes(), position: 0 };
+    let nodes = parser.nodes()?;
+    if parser.position == parser.html.len() { Some(nodes) } else { None }
+}
+
+/// Checks that writing `node` as HTML and parsing it gives the normalized tree
+pub fn html_round_trips(node: &TestNode) -> bool {
+    let mut html = Vec::new();
+    DomNode::<()>::write_html(node, &mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    parse_html(&html) == Some(normalize(::std::slice::from_ref(node)))
+}
+
+/// Checks that the devtools snapshot of `node` has the same structure, and that each node's
+/// key stack holds the keys of its ancestors and itself
+pub fn snapshot_matches(node: &TestNode) -> bool {
+    fn matches(node: &TestNode, snapshot: &NodeSnapshot, mut keys: Vec<u32>) -> bool {
+        keys.extend(node.key);
+        let value = match node.value {
+            TestValue::Tag(tag) => SnapshotValue::Tag(tag),
+            TestValue::Text(ref text) => SnapshotValue::Text(text.clone()),
+        };
+        snapshot.value == value &&
+            snapshot.key == node.key &&
+            snapshot.keys == keys &&
+            snapshot.listeners == 0 &&
+            DomNode::<()>::merged_attributes(node).count() == snapshot.attributes.len() &&
+            DomNode::<()>::merged_attributes(node).zip(&snapshot.attributes)
+                .all(|(attr, snapshot)| *attr == *snapshot) &&
+            snapshot.children.len() == node.children.len() &&
+            node.children.iter().zip(&snapshot.children)
+                .all(|(child, snapshot)| matches(child, snapshot, keys.clone()))
+    }
+    let snapshot = NodeSnapshot::of::<(), _>(node);
+    snapshot.len() == 1 && matches(node, &snapshot[0], Vec::new())
+}
+
+struct Parser<'a> {
+    html: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.html[self.position..].starts_with(prefix.as_bytes())
+    }
+
+    fn expect(&mut self, prefix: &str) -> Option<()> {
+        if !self.starts_with(prefix) { return None; }
+        self.position += prefix.len();
+        Some(())
+    }
+
+    /// Reads up to the next occurrence of `end`, unescaping entities
+    fn until(&mut self, end: u8) -> Option<String> {
+        let length = self.html[self.position..].iter().position(|&byte| byte == end)
+            .unwrap_or(self.html.len() - self.position);
+        let raw = &self.html[self.position..(self.position + length)];
+        self.position += length;
+        String::from_utf8(Unescape::new(raw.iter().cloned()).collect()).ok()
+    }
+
+    fn name(&mut self) -> &'a str {
+        let length = self.html[self.position..].iter()
+            .position(|byte| !(byte.is_ascii_alphanumeric() || *byte == b'-'))
+            .unwrap_or(self.html.len() - self.position);
+        let name = &self.html[self.position..(self.position + length)];
+        self.position += length;
+        ::std::str::from_utf8(name).unwrap()
+    }
+
+    /// Parses nodes up to the end of the input or a closing tag
+    fn nodes(&mut self) -> Option<Vec<TestNode>> {
+        let mut nodes = Vec::new();
+        while self.position < self.html.len() && !self.starts_with("</") {
+            if self.starts_with("<") {
+                nodes.push(self.element()?);
+            } else {
+                nodes.push(TestNode::text(self.until(b'<')?));
+            }
+        }
+        Some(nodes)
+    }
+
+    fn element(&mut self) -> Option<TestNode> {
+        self.expect("<")?;
+        let name = self.name();
+        let tag = *TAGS.iter().find(|&&tag| tag == name)?;
+        let mut node = TestNode::tag(tag);
+        while self.expect(" ").is_some() {
+            let key = self.name();
+            let key = *ATTRIBUTES.iter().find(|&&attribute| attribute == key)?;
+            self.expect("=\"")?;
+            let value = self.until(b'"')?;
+            self.expect("\"")?;
+            node.attributes.push((key, AttributeValue::OwnedStr(value)));
+        }
+        self.expect(">")?;
+        if !VOID_ELEMENTS.contains(&tag) {
+            node.children = self.nodes()?;
+            self.expect("</")?;
+            self.expect(tag)?;
+            self.expect(">")?;
+        }
+        Some(node)
+    }
+}
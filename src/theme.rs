@@ -0,0 +1,193 @@
+//! A `Theme` describes an application's colors and spacing.
+//!
+//! `provide` renders part of the application with a theme: the theme's values are set as CSS
+//! custom properties on a wrapping element, and the theme is available to render functions
+//! through `current`. Stylesheets and inline styles refer to the values with `color_var` and
+//! `spacing_var`, so switching themes at runtime only updates the wrapping element's
+//! attributes, and none of the elements inside it.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::{DomNode, KeyValue};
+//! use domafic::AttributeValue::OwnedStr;
+//! use domafic::tags::{attributes, div};
+//! use domafic::theme::{self, color_var, Color, Length, Theme};
+//! use std::marker::PhantomData;
+//!
+//! let light = Theme::new("light")
+//!     .with_color("background", Color::Rgb(255, 255, 255))
+//!     .with_color("text", Color::Rgb(17, 17, 17))
+//!     .with_spacing("gutter", Length::Rem(1.0));
+//! let dark = light.clone()
+//!     .renamed("dark")
+//!     .with_color("background", Color::Rgb(17, 17, 17))
+//!     .with_color("text", Color::Rgb(238, 238, 238));
+//!
+//! let render = |dark_mode: bool| theme::provide(if dark_mode { &dark } else { &light }, || {
+//!     let style: [KeyValue; 1] =
+//!         [("style", OwnedStr(format!("color: {}", color_var("text"))))];
+//!     div((attributes(style), PhantomData::<()>))
+//! });
+//!
+//! assert_eq!(
+//!     render(true).to_string(),
+//!     "<div data-theme=\"dark\" style=\"--color-background: #111111; --color-text: #eeeeee; \
+//!         --spacing-gutter: 1rem;\"><div style=\"color: var(--color-text)\"></div></div>"
+//! );
+//! ```
+//!
+//! Combine with `subscriptions::media_query("(prefers-color-scheme: dark)", ...)` to follow the
+//! user's dark mode preference.
+
+use {AttributeValue, DomNode, KeyValue};
+use context;
+use processors::EmptyListeners;
+use tags::{attributes, div, Tag};
+
+use std::fmt;
+use std::rc::Rc;
+
+/// A CSS color
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Color {
+    /// An opaque color with red, green and blue components
+    Rgb(u8, u8, u8),
+    /// A color with red, green and blue components and an opacity from 0 to 1
+    Rgba(u8, u8, u8, f32),
+    /// A named CSS color or other CSS color value, such as `"rebeccapurple"`
+    Css(&'static str),
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            Color::Rgba(r, g, b, a) => write!(f, "rgba({}, {}, {}, {})", r, g, b, a),
+            Color::Css(value) => f.write_str(value),
+        }
+    }
+}
+
+/// A CSS length
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Length {
+    /// Pixels
+    Px(f32),
+    /// Multiples of the root element's font size
+    Rem(f32),
+    /// Multiples of the element's font size
+    Em(f32),
+    /// Percentage of the containing element's size
+    Percent(f32),
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Length::Px(value) => write!(f, "{}px", value),
+            Length::Rem(value) => write!(f, "{}rem", value),
+            Length::Em(value) => write!(f, "{}em", value),
+            Length::Percent(value) => write!(f, "{}%", value),
+        }
+    }
+}
+
+/// Named colors and spacing values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    name: &'static str,
+    colors: Vec<(&'static str, Color)>,
+    spacing: Vec<(&'static str, Length)>,
+}
+
+impl Theme {
+    /// Creates a theme with no colors or spacing values.
+    ///
+    /// The name is set as the `data-theme` attribute of the element wrapping themed content.
+    pub fn new(name: &'static str) -> Theme {
+        Theme { name, colors: Vec::new(), spacing: Vec::new() }
+    }
+
+    /// Changes the name of the theme
+    pub fn renamed(mut self, name: &'static str) -> Theme {
+        self.name = name;
+        self
+    }
+
+    /// Sets the color called `name`, replacing any existing color with that name
+    pub fn with_color(mut self, name: &'static str, color: Color) -> Theme {
+        set(&mut self.colors, name, color);
+        self
+    }
+
+    /// Sets the spacing value called `name`, replacing any existing value with that name
+    pub fn with_spacing(mut self, name: &'static str, length: Length) -> Theme {
+        set(&mut self.spacing, name, length);
+        self
+    }
+
+    /// The name of the theme
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the color called `name`
+    pub fn color(&self, name: &str) -> Option<Color> {
+        self.colors.iter().find(|color| color.0 == name).map(|color| color.1)
+    }
+
+    /// Returns the spacing value called `name`
+    pub fn spacing(&self, name: &str) -> Option<Length> {
+        self.spacing.iter().find(|spacing| spacing.0 == name).map(|spacing| spacing.1)
+    }
+
+    /// Returns the CSS custom property declarations for the theme's values, such as
+    /// `--color-text: #111111;`
+    pub fn css_variables(&self) -> String {
+        let colors = self.colors.iter()
+            .map(|&(name, color)| format!("--color-{}: {};", name, color));
+        let spacing = self.spacing.iter()
+            .map(|&(name, length)| format!("--spacing-{}: {};", name, length));
+        colors.chain(spacing).collect::<Vec<_>>().join(" ")
+    }
+}
+
+fn set<T>(values: &mut Vec<(&'static str, T)>, name: &'static str, value: T) {
+    match values.iter_mut().find(|existing| existing.0 == name) {
+        Some(existing) => existing.1 = value,
+        None => values.push((name, value)),
+    }
+}
+
+/// Returns a CSS expression referring to the current theme's color called `name`
+pub fn color_var(name: &str) -> String {
+    format!("var(--color-{})", name)
+}
+
+/// Returns a CSS expression referring to the current theme's spacing value called `name`
+pub fn spacing_var(name: &str) -> String {
+    format!("var(--spacing-{})", name)
+}
+
+/// A `div` carrying a theme's CSS custom properties, wrapping the themed content
+pub type Themed<M, D> = Tag<M, D, [KeyValue; 2], EmptyListeners>;
+
+/// Renders `f` with `theme` available from `current`, wrapping the result in a `div` which
+/// sets the theme's values as CSS custom properties.
+pub fn provide<M, D, F>(theme: &Theme, f: F) -> Themed<M, D>
+    where D: DomNode<M>, F: FnOnce() -> D
+{
+    let theme = Rc::new(theme.clone());
+    let attrs = [
+        ("data-theme", AttributeValue::Str(theme.name)),
+        ("style", AttributeValue::OwnedStr(theme.css_variables())),
+    ];
+    let content = context::provide_rc(theme, f);
+    div((attributes(attrs), content))
+}
+
+/// Returns the theme provided by the innermost enclosing call to `provide`
+pub fn current() -> Option<Rc<Theme>> {
+    context::get::<Theme>()
+}
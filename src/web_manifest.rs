@@ -0,0 +1,308 @@
+//! Web app manifests, which let browsers install a site as an app.
+//!
+//! A `WebManifest` describes how the installed app appears: its name, icons, colors and
+//! display mode. `WebManifest::to_json` encodes it as the `manifest.webmanifest` file
+//! browsers expect, and `Document::web_manifest` links to the file and adds the matching meta
+//! tags. When generating a static site, `ssg::Generator::with_web_manifest` writes the file
+//! alongside the pages.
+//!
+//! Browsers only offer to install an app whose manifest has a name, a start URL, and icons
+//! 192 and 512 pixels square, which `WebManifest::validate` checks. Installing also requires a
+//! service worker, as described in the `service_worker` module.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::document::Document;
+//! use domafic::web_manifest::{Display, Error, Icon, WebManifest};
+//!
+//! let manifest = WebManifest::new("Field Notes")
+//!     .short_name("Notes")
+//!     .display(Display::Standalone)
+//!     .theme_color("#336699")
+//!     .icon(Icon::new("/icons/192.png", "192x192").mime("image/png"));
+//! assert_eq!(manifest.validate(), Err(Error::MissingIcon("512x512")));
+//!
+//! let manifest = manifest.icon(Icon::new("/icons/512.png", "512x512").mime("image/png"));
+//! assert_eq!(manifest.to_json(), "{\"name\":\"Field Notes\",\"short_name\":\"Notes\",\
+//!     \"start_url\":\"/\",\"display\":\"standalone\",\"theme_color\":\"#336699\",\
+//!     \"icons\":[{\"src\":\"/icons/192.png\",\"sizes\":\"192x192\",\"type\":\"image/png\"},\
+//!     {\"src\":\"/icons/512.png\",\"sizes\":\"512x512\",\"type\":\"image/png\"}]}");
+//!
+//! let document = Document::new("Field Notes").web_manifest("/manifest.webmanifest", &manifest)
+//!     .unwrap();
+//! assert_eq!(document.head.len(), 4);
+//! ```
+
+use devtools::write_string;
+use document::{Document, HeadElement};
+
+use std::error;
+use std::fmt;
+
+/// How the installed app is displayed
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Display {
+    /// The whole screen, without any browser UI
+    Fullscreen,
+    /// A window of its own, like a native app
+    Standalone,
+    /// A window of its own with minimal navigation controls
+    MinimalUi,
+    /// A regular browser tab
+    Browser,
+}
+
+impl Display {
+    /// The value of the manifest's `display` member
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Display::Fullscreen => "fullscreen",
+            Display::Standalone => "standalone",
+            Display::MinimalUi => "minimal-ui",
+            Display::Browser => "browser",
+        }
+    }
+}
+
+/// An icon for the installed app
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Icon {
+    /// URL of the image
+    pub src: String,
+    /// Sizes of the image, such as `192x192`, separated by spaces
+    pub sizes: String,
+    /// MIME type of the image, such as `image/png`
+    pub mime: Option<String>,
+    /// Purposes of the image, such as `maskable` for an icon which may be cropped to a circle
+    pub purpose: Option<String>,
+}
+
+impl Icon {
+    /// An icon at `src` of the given sizes
+    pub fn new<S: Into<String>, Z: Into<String>>(src: S, sizes: Z) -> Icon {
+        Icon { src: src.into(), sizes: sizes.into(), mime: None, purpose: None }
+    }
+
+    /// Sets the MIME type of the image
+    pub fn mime<M: Into<String>>(mut self, mime: M) -> Icon {
+        self.mime = Some(mime.into());
+        self
+    }
+
+    /// Sets the purposes of the image
+    pub fn purpose<P: Into<String>>(mut self, purpose: P) -> Icon {
+        self.purpose = Some(purpose.into());
+        self
+    }
+
+    fn is_maskable_only(&self) -> bool {
+        self.purpose.as_ref().is_some_and(|purpose| purpose.split_whitespace().all(|p| p == "maskable"))
+    }
+}
+
+/// A problem with a `WebManifest` which would prevent browsers from installing the app
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Error {
+    /// A required member was empty, given as its name in the manifest
+    Missing(&'static str),
+    /// No icon has the given size
+    MissingIcon(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Missing(member) => write!(f, "web app manifest is missing `{}`", member),
+            Error::MissingIcon(size) => write!(f, "web app manifest has no {} icon", size),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Description of an installable web app
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct WebManifest {
+    /// Name of the app, shown when it's installed and on its splash screen
+    pub name: String,
+    /// Shorter name, shown where there isn't room for the full name
+    pub short_name: Option<String>,
+    /// Description of what the app does
+    pub description: Option<String>,
+    /// URL loaded when the installed app is opened
+    pub start_url: String,
+    /// URLs which are part of the app, as a path prefix such as `/app/`
+    pub scope: Option<String>,
+    /// How the installed app is displayed
+    pub display: Option<Display>,
+    /// Color of the window's title bar, also set for the page with a `theme-color` meta tag
+    pub theme_color: Option<String>,
+    /// Color of the splash screen shown while the app loads
+    pub background_color: Option<String>,
+    /// Icons for the app, in any order
+    pub icons: Vec<Icon>,
+}
+
+impl WebManifest {
+    /// Creates a manifest for an app named `name`, started at `/`, with no icons
+    pub fn new<N: Into<String>>(name: N) -> WebManifest {
+        WebManifest {
+            name: name.into(),
+            short_name: None,
+            description: None,
+            start_url: "/".to_string(),
+            scope: None,
+            display: None,
+            theme_color: None,
+            background_color: None,
+            icons: Vec::new(),
+        }
+    }
+
+    /// Sets the shorter name of the app
+    pub fn short_name<S: Into<String>>(mut self, short_name: S) -> WebManifest {
+        self.short_name = Some(short_name.into());
+        self
+    }
+
+    /// Sets the description of the app
+    pub fn description<D: Into<String>>(mut self, description: D) -> WebManifest {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets the URL loaded when the app is opened
+    pub fn start_url<U: Into<String>>(mut self, start_url: U) -> WebManifest {
+        self.start_url = start_url.into();
+        self
+    }
+
+    /// Sets the URLs which are part of the app
+    pub fn scope<S: Into<String>>(mut self, scope: S) -> WebManifest {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Sets how the installed app is displayed
+    pub fn display(mut self, display: Display) -> WebManifest {
+        self.display = Some(display);
+        self
+    }
+
+    /// Sets the color of the window's title bar
+    pub fn theme_color<C: Into<String>>(mut self, theme_color: C) -> WebManifest {
+        self.theme_color = Some(theme_color.into());
+        self
+    }
+
+    /// Sets the color of the splash screen
+    pub fn background_color<C: Into<String>>(mut self, background_color: C) -> WebManifest {
+        self.background_color = Some(background_color.into());
+        self
+    }
+
+    /// Adds an icon
+    pub fn icon(mut self, icon: Icon) -> WebManifest {
+        self.icons.push(icon);
+        self
+    }
+
+    /// Checks that the manifest has the members browsers require to install the app
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.name.trim().is_empty() { return Err(Error::Missing("name")); }
+        if self.start_url.trim().is_empty() { return Err(Error::Missing("start_url")); }
+        for &size in &["192x192", "512x512"] {
+            if !self.icons.iter().any(|icon| icon.sizes.split_whitespace().any(|s| s == size)) {
+                return Err(Error::MissingIcon(size));
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the manifest as JSON, omitting members which aren't set
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        {
+            let mut member = |name: &str, value: &str| {
+                if json.len() > 1 { json.push(','); }
+                write_string(&mut json, name);
+                json.push(':');
+                write_string(&mut json, value);
+            };
+            member("name", &self.name);
+            if let Some(ref short_name) = self.short_name { member("short_name", short_name); }
+            if let Some(ref description) = self.description { member("description", description); }
+            member("start_url", &self.start_url);
+            if let Some(ref scope) = self.scope { member("scope", scope); }
+            if let Some(display) = self.display { member("display", display.as_str()); }
+            if let Some(ref color) = self.theme_color { member("theme_color", color); }
+            if let Some(ref color) = self.background_color { member("background_color", color); }
+        }
+        if !self.icons.is_empty() {
+            json.push_str(",\"icons\":[");
+            for (i, icon) in self.icons.iter().enumerate() {
+                if i > 0 { json.push(','); }
+                json.push_str("{\"src\":");
+                write_string(&mut json, &icon.src);
+                json.push_str(",\"sizes\":");
+                write_string(&mut json, &icon.sizes);
+                if let Some(ref mime) = icon.mime {
+                    json.push_str(",\"type\":");
+                    write_string(&mut json, mime);
+                }
+                if let Some(ref purpose) = icon.purpose {
+                    json.push_str(",\"purpose\":");
+                    write_string(&mut json, purpose);
+                }
+                json.push('}');
+            }
+            json.push(']');
+        }
+        json.push('}');
+        json
+    }
+
+    /// Returns the `<link rel="manifest">` to the manifest at `href` and the matching meta
+    /// tags, after validating it.
+    ///
+    /// Safari doesn't read icons or names from the manifest, so the first icon which isn't
+    /// only `maskable` is also linked as the `apple-touch-icon`, and the short name, or else the
+    /// name, is given as the `apple-mobile-web-app-title`.
+    pub fn head_elements(&self, href: &str) -> Result<Vec<HeadElement>, Error> {
+        self.validate()?;
+
+        let mut elements = vec![HeadElement::Link {
+            rel: "manifest".to_string(),
+            href: href.to_string(),
+            integrity: None,
+        }];
+        if let Some(ref theme_color) = self.theme_color {
+            elements.push(HeadElement::Meta {
+                name: "theme-color".to_string(),
+                content: theme_color.clone(),
+            });
+        }
+        elements.push(HeadElement::Meta {
+            name: "apple-mobile-web-app-title".to_string(),
+            content: self.short_name.as_ref().unwrap_or(&self.name).clone(),
+        });
+        if let Some(icon) = self.icons.iter().find(|icon| !icon.is_maskable_only()) {
+            elements.push(HeadElement::Link {
+                rel: "apple-touch-icon".to_string(),
+                href: icon.src.clone(),
+                integrity: None,
+            });
+        }
+        Ok(elements)
+    }
+}
+
+impl Document {
+    /// Links to `manifest`, served at `href`, and adds its meta tags, returning an error if
+    /// browsers couldn't install the app it describes
+    pub fn web_manifest(mut self, href: &str, manifest: &WebManifest) -> Result<Document, Error> {
+        self.head.extend(manifest.head_elements(href)?);
+        Ok(self)
+    }
+}
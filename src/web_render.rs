@@ -24,105 +24,804 @@ impl<F, S, M> Updater<S, M> for F
 }
 
 /// `Renderer`s convert the current state to the current UI `DomNode`.
+///
+/// The rendered `DomNode` may borrow from the state, so that text and attributes can refer
+/// to `String`s in the state rather than copies of them. Every function from `&State` to a
+/// `DomNode` is a `Renderer`, including those whose node borrows from their argument, such as
+/// `fn render(state: &State) -> impl DomNode<Msg> + '_`. Rust can't infer that a closure's
+/// result borrows from its argument, so those are written as `fn` items.
 pub trait Renderer<State, Message> {
 
-    // Note: this should really be Rendered<'a>: DomNode + 'a
-    // to allow for references to bits of state, but this is
-    // impossible without ATCs
-    /// Type of the rendered `DomNode`
-    type Rendered: DomNode<Message>;
+    /// Type of the `DomNode` rendered from a state borrowed for `'a`
+    type Rendered<'a>: DomNode<Message> where State: 'a;
 
     /// Renders a `DomNode` given the current application state
-    fn render(&self, &State) -> Self::Rendered;
+    fn render<'a>(&self, state: &'a State) -> Self::Rendered<'a>;
+}
+
+/// A function rendering a `DomNode` which may borrow from the state for `'a`, through which
+/// functions implement `Renderer`
+pub trait RenderFn<'a, State: 'a, Message>: Fn(&'a State) -> <Self as RenderFn<'a, State, Message>>::Output {
+    /// Type of the rendered `DomNode`
+    type Output: DomNode<Message>;
 }
 
-impl<F, S, R, M> Renderer<S, M> for F where F: Fn(&S) -> R, R: DomNode<M> {
-    type Rendered = R;
-    fn render(&self, state: &S) -> Self::Rendered {
+impl<'a, S: 'a, M, F, D> RenderFn<'a, S, M> for F where F: Fn(&'a S) -> D, D: DomNode<M> {
+    type Output = D;
+}
+
+impl<F, S, M> Renderer<S, M> for F where F: for<'a> RenderFn<'a, S, M> {
+    type Rendered<'a> = <F as RenderFn<'a, S, M>>::Output where S: 'a;
+    fn render<'a>(&self, state: &'a S) -> Self::Rendered<'a> {
         (self)(state)
     }
 }
 
-pub use self::private::{run, JsIo, HttpRequest, HttpResponse, HttpResult};
+pub use self::private::{
+    hydrate, mount, mount_when_ready, mount_with_subscriptions, run, run_multi, run_when_ready, run_with_subscriptions, try_mount, try_mount_multi, try_hydrate_with_subscriptions, try_mount_with_subscriptions, use_trusted_types, AppHandle, JsIo, CachePolicy, CommandHandle, DispatchTarget, element_text, EmscriptenScheduler, is_online, location_path, perf_monitor, replace_location, set_title, PerfDisplay, HttpError, HttpRequest, HttpResponse, HttpResult,
+    MotionPermission, NotificationOptions, NotificationPermission, PoolStats, WebStorage
+};
 
 mod private {
 
     extern crate libc;
 
     use super::{Updater, Renderer};
-    use {DomNode, DomValue, Event, KeyValue, Listener};
+    use {DomNode, DomValue, ElementRef, Event, KeyValue, Listener};
+    use processors::EmptyListeners;
+    use tags::{div, Tag};
+    pub use effects::HttpError;
+    use effects::{Animation, Effect, Executor, SpringAnimation, Progress, Response, StorageArea, PROGRESS_INTERVAL_MILLIS};
+    #[cfg(debug_assertions)]
+    use AttributeValue;
+    use busy::Busy;
+    use context;
+    use css;
+    use error::Error;
+    #[cfg(debug_assertions)]
+    use devtools::{self, MessageRecord, MessageRing, NodeSnapshot};
+    #[cfg(debug_assertions)]
+    use ids;
+    use html_writer::{subtree_html, Subtree};
+    #[cfg(feature = "indexed_db")]
+    use indexed_db::{parse_batch, IdbBatch, IdbError, IdbStore};
     use keys::Keys;
+    use multipart::Multipart;
+    use perf::PerfMonitor;
+    use router::PREFETCH_MAX_AGE_MILLIS;
+    use scheduler::Scheduler;
+    use service_worker::{ServiceWorkerEvent, SKIP_WAITING_MESSAGE};
+    use storage::Storage;
+    use subscriptions::{Subscription, SubscriptionSource};
+    use widget::{MountedWidget, WidgetNode};
     use processors::{DomNodes, Listeners, DomNodeProcessor, ListenerProcessor};
+    use static_node::Precompiled;
 
     // This module as a whole is "use_std"-only, so these don't need to be cfg'd
+    use std::any::Any;
+    use std::cell::{Cell, RefCell};
+    use std::collections::{HashSet, VecDeque};
     use std::ffi::{CString, CStr};
+    use std::fmt;
     use std::marker::PhantomData;
-    use std::{mem, ptr, str};
+    use std::rc::{Rc, Weak};
+    use std::{mem, slice, str};
+
+    /// Routes the DOM writes which Trusted Types restrict through a policy named `policy_name`.
+    ///
+    /// Call this before `run` on pages whose Content Security Policy includes
+    /// `require-trusted-types-for 'script'`, and allow the policy with a
+    /// `trusted-types <policy_name>` directive. Domafic never parses HTML itself, so the policy
+    /// is only used when an application sets a property such as `innerHTML`, `srcdoc`, a
+    /// `script`'s `src`, or an `on...` handler as an attribute. Those values are passed through
+    /// unchanged, so they must not come from untrusted input.
+    ///
+    /// This does nothing in browsers which don't support Trusted Types.
+    pub fn use_trusted_types(policy_name: &str) {
+        const JS: &'static [u8] = b"\
+            if ('undefined' === typeof trustedTypes) { return; }\
+            var pass = function(value) { return value; };\
+            __domafic_tt_policy = trustedTypes.createPolicy(UTF8ToString($0), {\
+                createHTML: pass,\
+                createScript: pass,\
+                createScriptURL: pass\
+            });\
+        \0";
+        let policy_name_cstring = CString::new(policy_name).unwrap();
+        unsafe {
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                policy_name_cstring.as_ptr() as libc::c_int
+            );
+        }
+    }
 
     /// Runs the application (`updater`, `renderer`, `initial_state`) on the webpage under the element
     /// specified by `element_selector`.
-    pub fn run<D, M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S) -> !
+    ///
+    /// Panics with an `Error::InvalidSelector` if the browser can't parse `element_selector`,
+    /// or an `Error::MissingElement` if no element matches it. Scripts loaded in the page's
+    /// `<head>` run before the element exists, so they should use `run_when_ready` instead.
+    pub fn run<M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S) -> !
         where
-        D: DomNode<M>,
         M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M, Rendered=D>
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static
     {
-        unsafe {
-            // Get initial DomNode
-            let rendered = renderer.render(&initial_state);
-
-            // Initialize the browser system
-            let document = web_init();
-            let root_node_element =
-                document.element_from_selector(element_selector)
-                    .expect(&format!(
-                        "Target element of `run` was not found: {}", element_selector));
-
-            root_node_element.remove_all_children();
-
-            // Lives forever on the stack, referenced and mutated in callbacks
-            let mut app_system = (
-                rendered,
-                updater,
-                renderer,
-                initial_state,
-                VDomNode {
-                    value: VNodeValue::Tag("N/A - root"),
-                    keys: Keys::new(),
-                    web_element: root_node_element,
-                    attributes: Vec::new(),
-                    listeners: Vec::new(),
-                    children: Vec::new(),
-                }
-            );
-            let app_system_mut_ptr = (&mut app_system) as *mut (D, U, R, S, VDomNode<M>);
+        run_with_subscriptions(element_selector, updater, renderer, |_: &S| Vec::new(), initial_state)
+    }
 
-            // Draw initial DomNode to browser
-            let mut node_index = 0;
-            let mut input = WebWriterAcc {
-                system_ptr: app_system_mut_ptr,
-                document: document,
+    /// Runs the application like `run`, additionally listening for the events described by
+    /// `subscriber`.
+    ///
+    /// `subscriber` is called with the current state after every update, and the application
+    /// listens for exactly the subscriptions it returns.
+    pub fn run_with_subscriptions<M, U, R, F, S>(
+        element_selector: &str,
+        updater: U,
+        renderer: R,
+        subscriber: F,
+        initial_state: S,
+    ) -> !
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static,
+        F: Fn(&S) -> Vec<Subscription<M>> + 'static,
+    {
+        // The application is never unmounted, so its handle isn't needed
+        let _ = mount_with_subscriptions(element_selector, updater, renderer, subscriber, initial_state);
+        EmscriptenScheduler.run_main_loop();
+        panic!("Emscripten main loop should never return")
+    }
+
+    /// Draws the application (`updater`, `renderer`, `initial_state`) on the webpage under the
+    /// element specified by `element_selector`, returning a handle which removes it again.
+    ///
+    /// Unlike `run`, this returns once the application is drawn, so that several applications
+    /// can be mounted on one page. Emscripten's runtime must be kept alive for them to handle
+    /// events, by calling `EmscriptenScheduler.run_main_loop()` once every application is
+    /// mounted, or by building with `-s NO_EXIT_RUNTIME=1`.
+    pub fn mount<M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S) -> AppHandle
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static
+    {
+        mount_with_subscriptions(element_selector, updater, renderer, |_: &S| Vec::new(), initial_state)
+    }
+
+    /// Mounts the application like `mount`, additionally listening for the events described by
+    /// `subscriber` like `run_with_subscriptions`.
+    pub fn mount_with_subscriptions<M, U, R, F, S>(
+        element_selector: &str,
+        updater: U,
+        renderer: R,
+        subscriber: F,
+        initial_state: S,
+    ) -> AppHandle
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static,
+        F: Fn(&S) -> Vec<Subscription<M>> + 'static,
+    {
+        try_mount_with_subscriptions(element_selector, updater, renderer, subscriber, initial_state)
+            .unwrap_or_else(|err| panic!("Failed to mount the application: {}", err))
+    }
+
+    /// Mounts the application like `mount`, returning an error rather than panicking if no
+    /// element matches `element_selector`, or the page couldn't be drawn
+    pub fn try_mount<M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S)
+        -> Result<AppHandle, Error>
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static
+    {
+        try_mount_with_subscriptions(element_selector, updater, renderer, |_: &S| Vec::new(), initial_state)
+    }
+
+    /// Mounts the application like `mount_with_subscriptions`, returning an error rather than
+    /// panicking like `try_mount`
+    pub fn try_mount_with_subscriptions<M, U, R, F, S>(
+        element_selector: &str,
+        updater: U,
+        renderer: R,
+        subscriber: F,
+        initial_state: S,
+    ) -> Result<AppHandle, Error>
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static,
+        F: Fn(&S) -> Vec<Subscription<M>> + 'static,
+    {
+        unsafe { mount_system(&[element_selector], false, false, updater, Borrowed(renderer), subscriber, initial_state) }
+    }
+
+    /// Runs the application like `run`, on a page whose contents under the element matching
+    /// `element_selector` were written from the same initial state by
+    /// `html_writer::write_hydratable`.
+    ///
+    /// Rather than rebuilding the page, the runtime adopts the elements already on it and
+    /// attaches their listeners, so the page keeps its scroll position and any half-typed text,
+    /// and doesn't flash while the application starts. If the page doesn't match the rendered
+    /// node, such as when the server rendered from a different state, the elements are
+    /// replaced as by `run`, with a warning in debug builds.
+    pub fn hydrate<M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S) -> !
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static
+    {
+        // The application is never unmounted, so its handle isn't needed
+        let _ = try_hydrate_with_subscriptions(element_selector, updater, renderer, |_: &S| Vec::new(), initial_state)
+            .unwrap_or_else(|err| panic!("Failed to mount the application: {}", err));
+        EmscriptenScheduler.run_main_loop();
+        panic!("Emscripten main loop should never return")
+    }
+
+    /// Mounts the application on a server-rendered page like `hydrate`, listening for the events
+    /// described by `subscriber` like `run_with_subscriptions`, and returning an error rather
+    /// than panicking like `try_mount`
+    pub fn try_hydrate_with_subscriptions<M, U, R, F, S>(
+        element_selector: &str,
+        updater: U,
+        renderer: R,
+        subscriber: F,
+        initial_state: S,
+    ) -> Result<AppHandle, Error>
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static,
+        F: Fn(&S) -> Vec<Subscription<M>> + 'static,
+    {
+        unsafe { mount_system(&[element_selector], false, true, updater, Borrowed(renderer), subscriber, initial_state) }
+    }
+
+    /// Runs an application which controls several disjoint regions of the page, such as a
+    /// header, a sidebar and a modal root, with one state and updater.
+    ///
+    /// Each region is a pair of a CSS selector and a function rendering the contents of the
+    /// element it matches. After every update, every region is rendered again from the new
+    /// state, and patched separately from the others.
+    pub fn run_multi<D, M, U, F, S>(regions: &[(&str, F)], updater: U, initial_state: S) -> !
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        F: Fn(&S) -> D + Clone + 'static,
+        S: 'static,
+    {
+        // The application is never unmounted, so its handle isn't needed
+        let _ = try_mount_multi(regions, updater, initial_state)
+            .unwrap_or_else(|err| panic!("Failed to mount the application: {}", err));
+        EmscriptenScheduler.run_main_loop();
+        panic!("Emscripten main loop should never return")
+    }
+
+    /// Mounts an application controlling several regions of the page like `run_multi`,
+    /// returning an error rather than panicking if a selector doesn't match an element
+    pub fn try_mount_multi<D, M, U, F, S>(regions: &[(&str, F)], updater: U, initial_state: S)
+        -> Result<AppHandle, Error>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        F: Fn(&S) -> D + Clone + 'static,
+        S: 'static,
+    {
+        let selectors: Vec<&str> = regions.iter().map(|&(selector, _)| selector).collect();
+        let renderer = Regions(regions.iter().map(|&(_, ref render)| render.clone()).collect());
+        unsafe { mount_system(&selectors, true, false, updater, Borrowed(renderer), |_: &S| Vec::new(), initial_state) }
+    }
+
+    /// Renders each region of an application started with `run_multi` as a child of one node,
+    /// whose children are drawn under their own roots
+    struct Regions<F>(Vec<F>);
+
+    impl<S, M, D, F> Renderer<S, M> for Regions<F> where F: Fn(&S) -> D, D: DomNode<M> {
+        type Rendered<'a> = Tag<M, Vec<D>, [KeyValue; 0], EmptyListeners> where S: 'a;
+        fn render<'a>(&self, state: &'a S) -> Self::Rendered<'a> {
+            div(self.0.iter().map(|render| render(state)).collect::<Vec<_>>())
+        }
+    }
+
+    /// A `Renderer` whose `DomNode` is stored alongside the state it borrows from
+    trait StaticRenderer<S, M> {
+        type Rendered: DomNode<M>;
+        fn render(&self, state: &S) -> Self::Rendered;
+    }
+
+    /// Renders with an application's `Renderer`, giving the node the type it has when
+    /// borrowing the state for `'static`.
+    ///
+    /// That holds while the node is alive: events aren't delivered to its listeners during an
+    /// update, it's dropped and replaced by the next render before the state is read again,
+    /// and it's dropped before the state when the application is unmounted. Applications can't
+    /// reach the adaptor, so nodes rendered by it can't be kept elsewhere.
+    struct Borrowed<R>(R);
+
+    impl<S: 'static, M, R: Renderer<S, M>> StaticRenderer<S, M> for Borrowed<R> {
+        type Rendered = R::Rendered<'static>;
+        fn render(&self, state: &S) -> Self::Rendered {
+            self.0.render(unsafe { &*(state as *const S) })
+        }
+    }
+
+    /// Mounts an application under the elements matching `element_selectors`. If `regions` is
+    /// set, each child of the rendered node is drawn under the root at the same position.
+    /// Otherwise there is a single root, under which the rendered node is drawn, or whose
+    /// server-rendered contents are adopted if `hydrate` is set.
+    unsafe fn mount_system<D, M, U, R, F, S>(
+        element_selectors: &[&str],
+        regions: bool,
+        hydrate: bool,
+        updater: U,
+        renderer: R,
+        subscriber: F,
+        initial_state: S,
+    ) -> Result<AppHandle, Error>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+        F: Fn(&S) -> Vec<Subscription<M>> + 'static,
+    {
+        // Get initial DomNode
+        let rendered = renderer.render(&initial_state);
+
+        // Initialize the browser system
+        let document = web_init();
+        let mut vdom_roots = Vec::new();
+        for element_selector in element_selectors {
+            let root_node_element = document.element_from_selector(element_selector)?;
+            vdom_roots.push(VDomNode {
+                value: VNodeValue::Tag("N/A - root"),
                 keys: Keys::new(),
-                parent_element: &(*app_system_mut_ptr).4.web_element,
-                node_level: &mut (*app_system_mut_ptr).4.children,
-                node_index: &mut node_index,
+                web_element: root_node_element,
+                attributes: Vec::new(),
+                listeners: Vec::new(),
+                children: Vec::new(),
+                props_hash: None,
+                widget: None,
+                #[cfg(debug_assertions)]
+                path: String::new(),
+                #[cfg(debug_assertions)]
+                recreated_listeners: 0,
+            });
+        }
+
+        for root in &vdom_roots {
+            if !hydrate {
+                root.web_element.remove_all_children();
+            }
+
+            #[cfg(debug_assertions)]
+            install_crash_overlay(&root.web_element);
+        }
+
+        // Borrowed by callbacks until the application is unmounted
+        let app = next_app_id();
+        let system = Rc::new(RefCell::new(AppSystem {
+            id: app,
+            rendered,
+            updater,
+            renderer,
+            state: initial_state,
+            vdom_roots,
+            regions,
+            subscriptions: Subscriptions {
+                subscriber: Box::new(subscriber),
+                active: Vec::new(),
+            },
+        }));
+        register_app(app, system.clone());
+        defer_events(app);
+
+        // Draw initial DomNode to browser. Messages sent meanwhile are queued, since the system
+        // is borrowed.
+        let drawn = {
+            let mut system = system.borrow_mut();
+            let system = &mut *system;
+            let drawn = if hydrate {
+                match hydrate_rendered(system) {
+                    Err(Error::HydrationMismatch(node)) => {
+                        #[cfg(debug_assertions)]
+                        warn_hydration_mismatch(&node);
+                        let _ = node;
+                        let root = &mut system.vdom_roots[0];
+                        for child in &mut root.children {
+                            detach_listeners(child);
+                        }
+                        root.children.clear();
+                        root.web_element.remove_all_children();
+                        write_rendered(system, document)
+                    },
+                    drawn => drawn,
+                }
+            } else {
+                write_rendered(system, document)
             };
+            if drawn.is_ok() {
+                mount_widgets();
+
+                #[cfg(debug_assertions)]
+                {
+                    publish_to_devtools(&system.rendered);
+                    warn_duplicate_ids(&system.rendered);
+                }
+
+                inject_scoped_css();
+                watch_prefetch_links();
+                sync_focus_traps();
+                sync_interaction_classes();
+                sync_sentinels();
+                sync_subscriptions(system);
+            }
+            drawn
+        };
+        end_render(app);
+        if let Err(err) = drawn {
+            // Remove whatever was drawn before the failure
+            unmount_system::<D, M, U, R, S>(app);
+            return Err(err);
+        }
+
+        Ok(AppHandle {
+            app,
+            unmount_system: unmount_system::<D, M, U, R, S>,
+        })
+    }
+
+    /// Runs the application like `run`, once the page has been parsed.
+    ///
+    /// Unlike `run`, this works from scripts loaded in the page's `<head>`, which run before
+    /// the element matching `element_selector` exists.
+    pub fn run_when_ready<M, U, R, S>(element_selector: &str, updater: U, renderer: R, initial_state: S) -> !
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static,
+    {
+        mount_when_ready(element_selector, updater, renderer, initial_state, |result| {
+            // The application is never unmounted, so its handle isn't needed
+            if let Err(err) = result {
+                panic!("Failed to mount the application: {}", err);
+            }
+        });
+        EmscriptenScheduler.run_main_loop();
+        panic!("Emscripten main loop should never return")
+    }
+
+    /// Mounts the application like `try_mount` once the page has been parsed, passing the
+    /// result to `on_mounted`.
+    ///
+    /// If the page has already been parsed, the application is mounted, and `on_mounted` is
+    /// called, before this returns.
+    pub fn mount_when_ready<M, U, R, S, F>(
+        element_selector: &str,
+        updater: U,
+        renderer: R,
+        initial_state: S,
+        on_mounted: F,
+    )
+        where
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: Renderer<S, M> + 'static,
+        S: 'static,
+        F: FnOnce(Result<AppHandle, Error>) + 'static,
+    {
+        let element_selector = element_selector.to_string();
+        let mount = Box::new(move || {
+            on_mounted(try_mount(&element_selector, updater, renderer, initial_state))
+        });
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var fn_ptr = $0, callback_ptr = $1;\
+                var ready = function() { Runtime.dynCall('vi', fn_ptr, [callback_ptr]); };\
+                if (document.readyState === 'loading') {\
+                    document.addEventListener('DOMContentLoaded', ready, { once: true });\
+                } else {\
+                    ready();\
+                }\
+            \0";
+
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                run_scheduled as *const libc::c_void,
+                scheduled_callback(mount),
+            );
+        }
+    }
+
+    /// A handle to an application started with `mount`, which can remove it from the page.
+    ///
+    /// Dropping the handle leaves the application running.
+    #[derive(Debug)]
+    pub struct AppHandle {
+        app: AppId,
+        unmount_system: unsafe fn(AppId),
+    }
+
+    impl AppHandle {
+        /// Removes the application from the page.
+        ///
+        /// Event listeners and subscriptions are removed, the elements' entries in the pool of
+        /// JavaScript objects are deleted, the root element is emptied, and the application's
+        /// state is dropped. Timers and HTTP requests which complete afterwards are ignored.
+        ///
+        /// Panics if called from within the application's own update or render.
+        pub fn unmount(self) {
+            unsafe { (self.unmount_system)(self.app) }
+        }
+    }
+
+    unsafe fn unmount_system<D, M, U, R, S>(app: AppId)
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let system = match mounted_system::<D, M, U, R, S>(app) {
+            Some(system) => system,
+            None => return,
+        };
+        {
+            let mut system = system.try_borrow_mut()
+                .expect("An application can't be unmounted while it's updating or rendering");
+
+            // Stop listening for subscribed events
+            system.subscriptions.subscriber = Box::new(|_: &S| Vec::new());
+            sync_subscriptions(&mut system);
+
+            const JS: &'static [u8] = b"\
+                delete __domafic_rendering[$0];\
+                delete __domafic_deferred_events[$0];\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char, app);
+
+            for root in &mut system.vdom_roots {
+                detach_listeners(root);
+                root.web_element.remove_all_children();
+            }
+        }
+        unregister_app(app);
+
+        // Dropping the VDOM deletes the pool entries of its elements and listeners, and
+        // destroys its widgets
+        drop(system);
+    }
+
+    /// The state of a mounted application, which callbacks from JavaScript reach through the
+    /// registry of mounted applications.
+    ///
+    /// The registry holds it in a `RefCell`, which is borrowed mutably while the application is
+    /// updating or rendering, so messages which arrive meanwhile fail to borrow it and are
+    /// queued instead.
+    struct AppSystem<D, M: 'static, U, R, S> {
+        id: AppId,
+        rendered: D,
+        updater: U,
+        renderer: R,
+        state: S,
+        // One root for each element the application was mounted under
+        vdom_roots: VDOMLevel<M>,
+        // Whether each child of the rendered node is drawn under the root at the same
+        // position, for applications started with `run_multi`
+        regions: bool,
+        subscriptions: Subscriptions<S, M>,
+    }
+
+    /// Identifies a mounted application to callbacks from JavaScript.
+    ///
+    /// Ids aren't reused, so callbacks which arrive after their application was unmounted are
+    /// dropped, rather than reaching another application allocated in its place.
+    type AppId = libc::c_int;
+
+    struct MountedApp {
+        id: AppId,
+        // The application's `RefCell<AppSystem>`
+        system: Rc<dyn Any>,
+        // Deliveries of messages which arrived while the application was busy
+        queued: VecDeque<Box<dyn FnOnce()>>,
+        // Commands issued through the `JsIo` which haven't finished
+        in_flight: Busy,
+    }
+
+    thread_local! {
+        static MOUNTED_APPS: RefCell<Vec<MountedApp>> = RefCell::new(Vec::new());
+        static NEXT_APP_ID: Cell<AppId> = Cell::new(0);
+    }
+
+    /// Allocates the id of an application which is about to be mounted
+    fn next_app_id() -> AppId {
+        NEXT_APP_ID.with(|next| {
+            let id = next.get();
+            next.set(id.wrapping_add(1));
+            id
+        })
+    }
+
+    /// Adds `system` to the registry of mounted applications as `app`
+    fn register_app<D, M, U, R, S>(app: AppId, system: Rc<RefCell<AppSystem<D, M, U, R, S>>>)
+        where
+        D: 'static,
+        M: 'static,
+        U: 'static,
+        R: 'static,
+        S: 'static,
+    {
+        MOUNTED_APPS.with(|apps| apps.borrow_mut().push(MountedApp {
+            id: app,
+            system,
+            queued: VecDeque::new(),
+            in_flight: Busy::new(),
+        }));
+    }
+
+    /// Removes `app` from the registry
+    fn unregister_app(app: AppId) {
+        let removed = MOUNTED_APPS.with(|apps| {
+            let mut apps = apps.borrow_mut();
+            let index = apps.iter().position(|mounted| mounted.id == app)?;
+            Some(apps.remove(index))
+        });
+        // Messages queued for the application are dropped outside of the registry's borrow
+        drop(removed);
+    }
+
+    /// The system of `app`, or `None` if it isn't mounted
+    fn mounted_system<D, M, U, R, S>(app: AppId) -> Option<Rc<RefCell<AppSystem<D, M, U, R, S>>>>
+        where
+        D: 'static,
+        M: 'static,
+        U: 'static,
+        R: 'static,
+        S: 'static,
+    {
+        let system = MOUNTED_APPS.with(|apps| apps.borrow().iter().find(|mounted| mounted.id == app)
+            .map(|mounted| mounted.system.clone()))?;
+        // Callbacks are instantiated with the types of the application which created them
+        Some(system.downcast().expect("callback reached an application of another type"))
+    }
+
+    /// Changes the commands `app` has in flight with `change`, if it's mounted
+    fn update_in_flight<F: FnOnce(&mut Busy)>(app: AppId, change: F) {
+        MOUNTED_APPS.with(|apps| {
+            if let Some(mounted) = apps.borrow_mut().iter_mut().find(|mounted| mounted.id == app) {
+                change(&mut mounted.in_flight);
+            }
+        });
+    }
+
+    /// Counts a command as in flight for `app` until it's dropped
+    struct InFlight {
+        app: AppId,
+        tag: Option<String>,
+    }
+
+    impl InFlight {
+        fn start(app: AppId, tag: Option<&str>) -> InFlight {
+            update_in_flight(app, |busy| busy.start(tag));
+            InFlight { app, tag: tag.map(|tag| tag.to_string()) }
+        }
+    }
+
+    impl Drop for InFlight {
+        fn drop(&mut self) {
+            update_in_flight(self.app, |busy| busy.finish(self.tag.as_deref()));
+        }
+    }
+
+    /// Whether `app` is still mounted
+    fn is_mounted(app: AppId) -> bool {
+        MOUNTED_APPS.with(|apps| apps.borrow().iter().any(|mounted| mounted.id == app))
+    }
+
+    /// The commands `app` has in flight, which are provided to its renderer as context
+    fn in_flight(app: AppId) -> Busy {
+        MOUNTED_APPS.with(|apps| apps.borrow().iter().find(|mounted| mounted.id == app)
+            .map(|mounted| mounted.in_flight.clone()).unwrap_or_default())
+    }
+
+    /// Runs `deliver` once `app` has finished its current update
+    fn queue_for_app(app: AppId, deliver: Box<dyn FnOnce()>) {
+        MOUNTED_APPS.with(|apps| {
+            if let Some(mounted) = apps.borrow_mut().iter_mut().find(|mounted| mounted.id == app) {
+                mounted.queued.push_back(deliver);
+            }
+        });
+    }
+
+    /// Queues the events fired at the listeners of `app` until `end_render`.
+    ///
+    /// The rendered `DomNode` may borrow from the state being updated, and its listeners may
+    /// be dropped along with it by the next render, so events fired at them during an update
+    /// or render, such as a `blur` fired by removing a focused element, are queued in
+    /// JavaScript and dispatched once the render has finished to the listeners which are still
+    /// attached.
+    fn defer_events(app: AppId) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                __domafic_rendering[$0] = true;\
+                __domafic_deferred_events[$0] = __domafic_deferred_events[$0] || [];\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char, app);
+        }
+    }
 
-            (*app_system_mut_ptr).0.process_all::<WebWriter<D, M, U, R, S>>(&mut input).unwrap();
+    /// Delivers the messages queued during the update and render of `app` once the render has
+    /// finished and released its system, and then dispatches the events queued during the
+    /// render to the listeners which are still attached
+    fn end_render(app: AppId) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                __domafic_rendering[$0] = false;\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char, app);
+        }
+        deliver_queued(app);
+        unsafe {
+            // A listener's callback is still attached if it's still in the pool, since dropping
+            // its `WebElement` deletes it
+            const JS: &'static [u8] = b"\
+                var events = __domafic_deferred_events[$0] || [];\
+                __domafic_deferred_events[$0] = [];\
+                events.forEach(function(deferred) {\
+                    var callback = deferred[0];\
+                    if (__domafic_pool[callback.__domafic_index] === callback) { callback(deferred[1]); }\
+                });\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char, app);
+        }
+    }
 
-            run_main_web_loop()
+    /// Delivers the messages queued while `app` was busy, in the order they arrived
+    fn deliver_queued(app: AppId) {
+        loop {
+            let next = MOUNTED_APPS.with(|apps| {
+                let mut apps = apps.borrow_mut();
+                let mounted = apps.iter_mut().find(|mounted| mounted.id == app)?;
+                mounted.queued.pop_front()
+            });
+            match next {
+                Some(deliver) => deliver(),
+                None => return,
+            }
         }
     }
 
-    struct JsIoImpl<D, M, U, R, S>
+    /// The `JsIo` given to an application's updater, through which it can also read the
+    /// application's last render
+    struct JsIoImpl<'a, D: 'a, M, U, R, S>
         where
-        D: DomNode<M>,
+        D: DomNode<M> + 'static,
         M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M>
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M> + 'static,
+        S: 'static,
     {
-        app_system: *mut (D, U, R, S, VDomNode<M>)
+        app: AppId,
+        rendered: &'a D,
+        vdom_roots: &'a VDOMLevel<M>,
+        system: PhantomData<(U, R, S)>,
     }
 
     /// A single HTTP request
@@ -156,11 +855,19 @@ mod private {
         pub body: &'a str,
     }
 
-    /// HTTP request error indicating either a network connection error or a timeout
-    #[derive(Debug, Copy, Clone)]
-    pub enum HttpError {
-        NetworkError,
-        Timeout,
+    /// Determines how a request issued with `JsIo::http_cached` uses the HTTP cache.
+    ///
+    /// Cached responses are keyed by method and URL. Only successful (2xx) responses are cached.
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub enum CachePolicy {
+        /// Share the response of an identical in-flight request, if there is one, but don't
+        /// use or populate the cache
+        Dedupe,
+        /// Use a cached response if it is younger than the given number of milliseconds.
+        /// Otherwise, share an identical in-flight request or issue a new one.
+        MaxAge(u32),
+        /// Always issue a new request, replacing the cached response once it arrives
+        Reload,
     }
 
     /// Handler for an HTTP response
@@ -177,462 +884,3557 @@ mod private {
         }
     }
 
-    /// JavaScript IO interface
+    /// JavaScript IO interface.
+    ///
+    /// The `JsIo` is also an `effects::Executor`, so updates can issue HTTP requests, storage
+    /// writes and timers as `Effect`s which can be recorded in native tests.
     pub trait JsIo<Message> {
-        /// Issue an asynchronous HTTP request
+        /// Issue an asynchronous HTTP request.
+        ///
+        /// Like the other HTTP requests and delays, it returns a handle which cancels it when
+        /// passed to `cancel`, such as when a newer search supersedes it.
         fn http<'b> (
             &self,
             http_request: HttpRequest<'b>,
             handler: Box<HttpResponseHandler<Message=Message>>,
-        );
-    }
+        ) -> CommandHandle;
 
-    impl<D, M, U, R, S> JsIo<M> for JsIoImpl<D, M, U, R, S>
-        where
-        D: DomNode<M>,
-        M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M, Rendered=D>
-    {
-        fn http<'b> (
+        /// Issue an asynchronous HTTP request which may be served from, or deduplicated by,
+        /// the HTTP cache according to `cache_policy`.
+        ///
+        /// Only "GET" and "HEAD" requests are cached. Other requests bypass the cache, and remove
+        /// any cached responses for their URL.
+        fn http_cached<'b> (
             &self,
             http_request: HttpRequest<'b>,
-            handler: Box<HttpResponseHandler<Message=M>>,
-        ) {
-            JsIoImpl::http(self, http_request, handler)
-        }
-    }
+            cache_policy: CachePolicy,
+            handler: Box<dyn HttpResponseHandler<Message=Message>>,
+        ) -> CommandHandle;
 
-    impl<D, M, U, R, S> JsIoImpl<D, M, U, R, S>
-        where
-        D: DomNode<M>,
-        M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M, Rendered=D>
-    {
-        fn http<'b> (
+        /// Issue an asynchronous HTTP request which is counted under `tag`, as well as in the
+        /// total, by `busy::current` until its response has been handled
+        fn http_tagged<'b> (
             &self,
+            tag: &str,
             http_request: HttpRequest<'b>,
-            handler: Box<HttpResponseHandler<Message=M>>,
-        ) {
-            unsafe {
-                let HttpRequest { method, headers, url, body, timeout_millis } = http_request;
-                let method_cstring = CString::new(method).unwrap();
-                let url_cstring = CString::new(url).unwrap();
-                let body_cstring = CString::new(body).unwrap();
+            handler: Box<dyn HttpResponseHandler<Message=Message>>,
+        ) -> CommandHandle;
 
-                let header_key_cstrings: Vec<CString> =
-                headers.iter().map(|header| CString::new(header.0).unwrap()).collect();
+        /// Issue an asynchronous HTTP request, delivering the message produced by `on_progress`
+        /// as its body is sent and its response received.
+        ///
+        /// Progress is delivered at most once every `effects::PROGRESS_INTERVAL_MILLIS`, and a
+        /// last time, marked `done`, just before the response. The request bypasses the HTTP
+        /// cache.
+        fn http_with_progress<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            on_progress: Box<dyn Fn(Progress) -> Message>,
+            handler: Box<dyn HttpResponseHandler<Message=Message>>,
+        ) -> CommandHandle;
 
-                let header_key_pointers: Vec<libc::c_int> =
-                header_key_cstrings.iter().map(|cstring|
-                    cstring.as_ptr() as libc::c_int).collect();
+        /// Issue an asynchronous HTTP request whose body is `form`, encoded as
+        /// `multipart/form-data`, in place of the request's `body`, delivering progress as by
+        /// `http_with_progress` if `on_progress` is given.
+        ///
+        /// The request's `Content-Type` header is replaced with the one for the form.
+        fn http_multipart<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            form: &Multipart,
+            on_progress: Option<Box<dyn Fn(Progress) -> Message>>,
+            handler: Box<dyn HttpResponseHandler<Message=Message>>,
+        ) -> CommandHandle;
 
-                let header_value_cstrings: Vec<CString> =
-                headers.iter().map(|header| CString::new(header.1).unwrap()).collect();
+        /// Removes all cached responses whose URLs begin with `url_prefix`.
+        ///
+        /// Pass an empty string to clear the whole HTTP cache.
+        fn clear_http_cache(&self, url_prefix: &str);
 
-                let header_value_pointers: Vec<libc::c_int> =
-                header_value_cstrings.iter().map(|cstring|
-                    cstring.as_ptr() as libc::c_int).collect();
+        /// Returns the current size and occupancy of the JavaScript element pool
+        fn pool_stats(&self) -> PoolStats;
 
-                let handler_ptr = Box::into_raw(handler);
+        /// Trims freed entries from the end of the element pool, returning the number of
+        /// entries removed
+        fn compact_pool(&self) -> usize;
 
-                let (handler_data_ptr, handler_vtable_ptr):
-                    (*const libc::c_void, *const libc::c_void) =
-                    mem::transmute(handler_ptr);
+        /// Checks the element pool against the virtual DOM, returning the indices of any
+        /// pool entries which are still occupied but are no longer referenced.
+        ///
+        /// Unreferenced entries are also logged to the browser console. They usually indicate
+        /// elements or listener callbacks leaked by a panic during rendering.
+        #[cfg(debug_assertions)]
+        fn validate_pool(&self) -> Vec<usize>;
 
-                const JS: &'static [u8] = b"\
-                    var handler_fn_ptr = $0;\
-                    var app_system = $1;\
-                    var method = UTF8ToString($2);\
-                    var url = UTF8ToString($3);\
-                    var body = UTF8ToString($4);\
-                    var header_len = $5;\
-                    var header_key_ptr = $6;\
-                    var header_value_ptr = $7;\
-                    var timeout = $8;\
-                    var handler_data_ptr = $9;\
-                    var handler_vtable_ptr = $10;\
-                    var xhr = new XMLHttpRequest();\
-                    var error_fn = function(error_sig) { return function() {\
-                        Runtime.dynCall('viiiiiiii', handler_fn_ptr, [error_sig, app_system, handler_data_ptr, handler_vtable_ptr, 0, 0, 0, 0]);\
-                    } };\
-                    xhr.addEventListener('timeout', error_fn(1));\
-                    xhr.addEventListener('error', error_fn(2));\
-                    xhr.addEventListener('load', function() {\
-                        var stack = Runtime.stackSave();\
-                        var status_code = xhr.status;\
-                        var status_text = allocate(\
-                            intArrayFromString(xhr.statusText), 'i8', ALLOC_STACK\
-                        );\
-                        var response_headers = allocate(\
-                            intArrayFromString(xhr.getAllResponseHeaders()), 'i8', ALLOC_STACK\
-                        );\
-                        var response_body =\
-                            allocate(intArrayFromString(xhr.responseText), 'i8', ALLOC_STACK);\
-                        Runtime.dynCall('viiiiiiii', handler_fn_ptr, [0, app_system, handler_data_ptr, handler_vtable_ptr, status_code, status_text, response_body, response_headers]);\
-                        Runtime.stackRestore(stack);\
-                    });\
-                    try { xhr.open(method, url, true); } catch (e) { error_fn(3); return; }\
-                    for (var i = 0; i < header_len; i++) {\
+        /// Shows a browser notification, requesting permission from the user first if they
+        /// haven't yet granted or denied it.
+        ///
+        /// The resulting permission is delivered as the message produced by `on_permission`.
+        /// The notification is only shown if the permission is `Granted`.
+        fn notify(
+            &self,
+            title: &str,
+            options: NotificationOptions,
+            on_permission: Box<dyn Fn(NotificationPermission) -> Message>,
+        );
+
+        /// Returns whether the user has allowed the page to show notifications
+        fn notification_permission(&self) -> NotificationPermission;
+
+        /// Asks the user for permission to show notifications without showing one, delivering
+        /// the result as the message produced by `on_permission`.
+        fn request_notification_permission(
+            &self,
+            on_permission: Box<dyn Fn(NotificationPermission) -> Message>,
+        );
+
+        /// Shows an alert dialog, delivering the message produced by `on_close` once the user
+        /// dismisses it.
+        ///
+        /// Browser dialogs block the page, so they're shown after the current update
+        /// has finished rendering.
+        fn alert(&self, text: &str, on_close: Box<dyn Fn() -> Message>);
+
+        /// Shows a confirmation dialog, delivering whether the user accepted it as the message
+        /// produced by `on_close`.
+        ///
+        /// Browser dialogs block the page, so they're shown after the current update
+        /// has finished rendering.
+        fn confirm(&self, text: &str, on_close: Box<dyn Fn(bool) -> Message>);
+
+        /// Shows a dialog asking the user to enter some text, initially `default_text`.
+        ///
+        /// The entered text, or `None` if the dialog was cancelled, is delivered as the message
+        /// produced by `on_close`. Browser dialogs block the page, so they're shown after the
+        /// current update has finished rendering.
+        fn prompt(
+            &self,
+            text: &str,
+            default_text: &str,
+            on_close: Box<dyn Fn(Option<&str>) -> Message>,
+        );
+
+        /// Asks the browser to show the element marked with `element` fullscreen.
+        ///
+        /// Browsers only allow this in response to a user action such as a click. Subscribe
+        /// with `subscriptions::on_fullscreen_change` to find out when fullscreen mode is
+        /// entered or left. Failures are logged to the browser console.
+        fn request_fullscreen(&self, element: ElementRef);
+
+        /// Leaves fullscreen mode, if the page is in it
+        fn exit_fullscreen(&self);
+
+        /// Asks the browser to lock the pointer to the element marked with `element`, hiding
+        /// the cursor and reporting only relative mouse movement.
+        ///
+        /// Browsers only allow this in response to a user action such as a click. Subscribe
+        /// with `subscriptions::on_pointer_lock_change` to find out when the pointer is locked
+        /// or released. Failures are logged to the browser console.
+        fn request_pointer_lock(&self, element: ElementRef);
+
+        /// Releases the pointer, if it's locked
+        fn exit_pointer_lock(&self);
+
+        /// Writes the part of the rendered page identified by `subtree` to a string of HTML, as
+        /// by `html_writer::subtree_html`, or returns `None` if the page has no such node.
+        ///
+        /// The page is the one rendered before the current update.
+        fn export_html(&self, subtree: Subtree) -> Option<String>;
+
+        /// Offers `bytes` to the user as a file named `filename`, of the MIME type `mime` such as
+        /// "text/csv", as if they had followed a download link.
+        ///
+        /// The bytes are copied into a `Blob`, whose object URL is revoked once the download
+        /// has started.
+        fn download(&self, filename: &str, mime: &str, bytes: &[u8]);
+
+        /// Moves keyboard focus to the element marked with `element`.
+        ///
+        /// Focus is moved after the current update has finished rendering, so that the element
+        /// may be one which the update adds. Missing elements are logged to the browser console.
+        fn focus(&self, element: ElementRef);
+
+        /// Asks the user for permission to report device orientation and motion, delivering
+        /// the result as the message produced by `on_permission`.
+        ///
+        /// Only some browsers, such as Safari on iOS, require permission, and they only allow
+        /// requesting it in response to a user action such as a click. Other browsers report
+        /// `MotionPermission::Granted` immediately.
+        fn request_motion_permission(&self, on_permission: Box<dyn Fn(MotionPermission) -> Message>);
+
+        /// Dispatches a `CustomEvent` named `event_name` to `target`, so that scripts on the
+        /// page outside of the app can react to its changes.
+        ///
+        /// `detail_json` is parsed as JSON and set as the event's `detail`. The event bubbles,
+        /// and is dispatched after the current update has finished rendering, so that listeners
+        /// see the rendered page. Missing targets and invalid JSON are logged to the browser
+        /// console.
+        fn dispatch_event(&self, target: DispatchTarget, event_name: &str, detail_json: Option<&str>);
+
+        /// Reads the JSON of the value stored under `key` in `store`, delivering the message
+        /// produced by `on_value` from it, or from `None` if there is none.
+        ///
+        /// `indexed_db::get` decodes the value with serde.
+        #[cfg(feature = "indexed_db")]
+        fn idb_get(
+            &self,
+            store: IdbStore,
+            key: &str,
+            on_value: Box<dyn Fn(Result<Option<String>, IdbError>) -> Message>,
+        );
+
+        /// Stores the value encoded by `value_json` under `key` in `store`, and delivers the
+        /// message produced by `on_written` once the write has been committed.
+        ///
+        /// Invalid JSON is reported as `IdbError::Malformed`.
+        #[cfg(feature = "indexed_db")]
+        fn idb_put(
+            &self,
+            store: IdbStore,
+            key: &str,
+            value_json: &str,
+            on_written: Box<dyn Fn(Result<(), IdbError>) -> Message>,
+        );
+
+        /// Removes the value stored under `key` in `store`, if any, and delivers the message
+        /// produced by `on_deleted` once the removal has been committed
+        #[cfg(feature = "indexed_db")]
+        fn idb_delete(
+            &self,
+            store: IdbStore,
+            key: &str,
+            on_deleted: Box<dyn Fn(Result<(), IdbError>) -> Message>,
+        );
+
+        /// Reads every entry of `store` with a cursor, in the order of their keys, delivering
+        /// the message produced by `on_batch` for each batch of up to `batch_size` entries.
+        ///
+        /// Values are delivered as JSON. The last batch is `done`, and is delivered even if it's
+        /// empty. Nothing further is delivered after an error.
+        #[cfg(feature = "indexed_db")]
+        fn idb_scan(
+            &self,
+            store: IdbStore,
+            batch_size: u32,
+            on_batch: Box<dyn Fn(Result<IdbBatch<String>, IdbError>) -> Message>,
+        );
+
+        /// Registers the service worker script at `url`, delivering the message produced by
+        /// `on_event` for each step of its lifecycle.
+        ///
+        /// Registering again with the same `url`, such as on every page load, is harmless. A
+        /// `ServiceWorkerEvent::Failed` is delivered if registration fails, or if the browser
+        /// doesn't support service workers.
+        fn register_service_worker(
+            &self,
+            url: &str,
+            on_event: Box<dyn Fn(ServiceWorkerEvent) -> Message>,
+        );
+
+        /// Asks the browser to check whether the registered service worker script has changed,
+        /// which delivers `ServiceWorkerEvent::Updated` once a new version has installed.
+        ///
+        /// Browsers also check on navigations, but a long-lived page may want to check
+        /// periodically.
+        fn check_for_update(&self);
+
+        /// Posts `service_worker::SKIP_WAITING_MESSAGE` to the waiting service worker, and
+        /// reloads the page once it has taken control, so that the page runs the new version.
+        ///
+        /// Does nothing but log to the browser console if no update is waiting.
+        fn activate_update(&self);
+
+        /// Delivers the message produced by `on_timeout` once `millis` milliseconds have passed
+        fn delay(&self, millis: u32, on_timeout: Box<dyn Fn() -> Message>) -> CommandHandle;
+
+        /// Cancels an HTTP request or delay, so that it never delivers a message.
+        ///
+        /// A request which no other request has joined through the HTTP cache is aborted.
+        /// Cancelling a command which has already delivered its message does nothing.
+        fn cancel(&self, handle: CommandHandle);
+
+        /// The `Executor` running `Effect`s with this `JsIo`, for passing to updates written
+        /// against `effects::Executor`
+        fn executor(&self) -> &dyn Executor<Message>;
+
+        /// The `Scheduler` running the application's timeouts and animation frames
+        fn scheduler(&self) -> &dyn Scheduler;
+    }
+
+    /// The target of an event dispatched with `JsIo::dispatch_event`
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub enum DispatchTarget<'a> {
+        /// The element marked with the given ref
+        Ref(ElementRef),
+        /// The first element matching a CSS selector, such as "#legacy-app"
+        Selector(&'a str),
+        /// The page's `document`
+        Document,
+        /// The page's `window`
+        Window,
+    }
+
+    /// Identifies an HTTP request or delay issued through the `JsIo`, so that it can be
+    /// cancelled by `JsIo::cancel`
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub struct CommandHandle(u32);
+
+    impl CommandHandle {
+        fn next() -> CommandHandle {
+            NEXT_COMMAND.with(|next| {
+                let handle = CommandHandle(next.get());
+                next.set(next.get().wrapping_add(1));
+                handle
+            })
+        }
+    }
+
+    thread_local! {
+        static NEXT_COMMAND: Cell<u32> = Cell::new(0);
+        // Delays which haven't been delivered or cancelled
+        static PENDING_DELAYS: RefCell<HashSet<u32>> = RefCell::new(HashSet::new());
+    }
+
+    impl<'a> From<ElementRef> for DispatchTarget<'a> {
+        fn from(element: ElementRef) -> DispatchTarget<'a> {
+            DispatchTarget::Ref(element)
+        }
+    }
+
+    impl<'a> From<&'a str> for DispatchTarget<'a> {
+        fn from(selector: &'a str) -> DispatchTarget<'a> {
+            DispatchTarget::Selector(selector)
+        }
+    }
+
+    /// Whether the user has allowed the page to receive device orientation and motion events
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub enum MotionPermission {
+        /// Orientation and motion events will be delivered
+        Granted,
+        /// The user has refused to allow orientation and motion events
+        Denied,
+    }
+
+    /// Options for a notification shown with `JsIo::notify`
+    #[derive(Debug, Copy, Clone, Default)]
+    pub struct NotificationOptions<'a> {
+        /// Text shown below the notification's title
+        pub body: &'a str,
+        /// Optional URL of an icon to show with the notification
+        pub icon: Option<&'a str>,
+        /// Optional tag identifying the notification. Showing a notification replaces any
+        /// existing notification with the same tag.
+        pub tag: Option<&'a str>,
+    }
+
+    /// Whether the user has allowed the page to show notifications
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub enum NotificationPermission {
+        /// Notifications may be shown
+        Granted,
+        /// The user has refused to allow notifications
+        Denied,
+        /// The user hasn't yet been asked, or dismissed the request without choosing
+        Undecided,
+        /// The browser doesn't support notifications
+        Unsupported,
+    }
+
+    impl NotificationPermission {
+        fn from_js(permission: libc::c_int) -> NotificationPermission {
+            match permission {
+                0 => NotificationPermission::Granted,
+                1 => NotificationPermission::Denied,
+                2 => NotificationPermission::Undecided,
+                _ => NotificationPermission::Unsupported,
+            }
+        }
+    }
+
+    /// Size and occupancy of the JavaScript pool used to hold DOM elements and listener
+    /// callbacks referenced from Rust
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub struct PoolStats {
+        /// Total number of slots in the pool, including freed slots
+        pub size: usize,
+        /// Number of slots currently holding an element or callback
+        pub occupied: usize,
+        /// Number of freed slots available for reuse
+        pub free: usize,
+    }
+
+    impl<'a, D, M, U, R, S> JsIo<M> for JsIoImpl<'a, D, M, U, R, S>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        fn http<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            handler: Box<HttpResponseHandler<Message=M>>,
+        ) -> CommandHandle {
+            JsIoImpl::http(self, http_request, None, None, None, None, handler)
+        }
+
+        fn http_cached<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            cache_policy: CachePolicy,
+            handler: Box<dyn HttpResponseHandler<Message=M>>,
+        ) -> CommandHandle {
+            JsIoImpl::http(self, http_request, Some(cache_policy), None, None, None, handler)
+        }
+
+        fn http_tagged<'b> (
+            &self,
+            tag: &str,
+            http_request: HttpRequest<'b>,
+            handler: Box<dyn HttpResponseHandler<Message=M>>,
+        ) -> CommandHandle {
+            JsIoImpl::http(self, http_request, None, Some(tag), None, None, handler)
+        }
+
+        fn http_with_progress<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            on_progress: Box<dyn Fn(Progress) -> M>,
+            handler: Box<dyn HttpResponseHandler<Message=M>>,
+        ) -> CommandHandle {
+            JsIoImpl::http(self, http_request, None, None, None, Some(progress_handler(on_progress)), handler)
+        }
+
+        fn http_multipart<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            form: &Multipart,
+            on_progress: Option<Box<dyn Fn(Progress) -> M>>,
+            handler: Box<dyn HttpResponseHandler<Message=M>>,
+        ) -> CommandHandle {
+            let content_type = form.content_type();
+            let mut headers: Vec<(&str, &str)> = http_request.headers.iter()
+                .filter(|header| !header.0.eq_ignore_ascii_case("content-type"))
+                .cloned()
+                .collect();
+            headers.push(("Content-Type", &content_type));
+            let http_request = HttpRequest { headers: &headers, body: "", ..http_request };
+            let on_progress = on_progress.map(progress_handler);
+            JsIoImpl::http(self, http_request, None, None, Some(&form.to_bytes()), on_progress, handler)
+        }
+
+        fn clear_http_cache(&self, url_prefix: &str) {
+            JsIoImpl::clear_http_cache(self, url_prefix)
+        }
+
+        fn pool_stats(&self) -> PoolStats {
+            pool_stats()
+        }
+
+        fn compact_pool(&self) -> usize {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var pool = __domafic_pool;\
+                    var trimmed = 0;\
+                    while (pool.length > 0 && pool[pool.length - 1] === undefined) {\
+                        pool.pop();\
+                        trimmed++;\
+                    }\
+                    __domafic_pool_free = __domafic_pool_free.filter(function(index, i, free) {\
+                        return index < pool.length && free.indexOf(index) === i;\
+                    });\
+                    return trimmed;\
+                \0";
+
+                emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char) as usize
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        fn validate_pool(&self) -> Vec<usize> {
+            let mut referenced = Vec::new();
+            unsafe {
+                for root in self.vdom_roots {
+                    collect_pool_indices(root, &mut referenced);
+                }
+            }
+
+            let mut unreferenced: Vec<libc::c_int> = vec![0; pool_stats().size];
+            let count = unsafe {
+                const JS: &'static [u8] = b"\
+                    var referenced = {};\
+                    for (var i = 0; i < $1; i++) { referenced[getValue($0 + (i * 4), 'i32')] = true; }\
+                    var count = 0;\
+                    for (var j = 0; j < __domafic_pool.length; j++) {\
+                        if (__domafic_pool[j] === undefined || referenced[j]) { continue; }\
+                        console.warn('domafic: pool entry ' + j + ' is not referenced by the VDOM',\
+                            __domafic_pool[j]);\
+                        if (count < $3) { setValue($2 + (count * 4), j, 'i32'); }\
+                        count++;\
+                    }\
+                    return count;\
+                \0";
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    referenced.as_ptr() as *const _ as *const libc::c_char,
+                    referenced.len() as libc::c_int,
+                    unreferenced.as_mut_ptr() as *const _ as *const libc::c_char,
+                    unreferenced.len() as libc::c_int,
+                ) as usize
+            };
+
+            unreferenced.truncate(count);
+            unreferenced.into_iter().map(|index| index as usize).collect()
+        }
+
+        fn notify(
+            &self,
+            title: &str,
+            options: NotificationOptions,
+            on_permission: Box<dyn Fn(NotificationPermission) -> M>,
+        ) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var title = UTF8ToString($3);\
+                    var options = { body: UTF8ToString($4) };\
+                    if ($5) { options.icon = UTF8ToString($5); }\
+                    if ($6) { options.tag = UTF8ToString($6); }\
+                    var deliver = function(permission) {\
+                        if (permission == 0) {\
+                            try { new Notification(title, options); }\
+                            catch (e) { console.error('domafic: failed to show notification', e); }\
+                        }\
+                        __domafic_deliver($0, $1, $2, permission, null);\
+                    };\
+                    var permission = __domafic_notification_permission();\
+                    if (permission != 2) {\
+                        setTimeout(function() { deliver(permission); }, 0);\
+                    } else {\
+                        __domafic_request_notification_permission(deliver);\
+                    }\
+                \0";
+
+                let title_cstring = CString::new(title).unwrap();
+                let body_cstring = CString::new(options.body).unwrap();
+                let icon_cstring = options.icon.map(|icon| CString::new(icon).unwrap());
+                let tag_cstring = options.tag.map(|tag| CString::new(tag).unwrap());
+                let handler_ptr = deferred_handler(
+                    Box::new(move |permission, _| {
+                        on_permission(NotificationPermission::from_js(permission))
+                    }));
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle_deferred::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    handler_ptr,
+                    title_cstring.as_ptr() as libc::c_int,
+                    body_cstring.as_ptr() as libc::c_int,
+                    icon_cstring.as_ref().map_or(0, |icon| icon.as_ptr() as libc::c_int),
+                    tag_cstring.as_ref().map_or(0, |tag| tag.as_ptr() as libc::c_int),
+                );
+            }
+        }
+
+        fn notification_permission(&self) -> NotificationPermission {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    return __domafic_notification_permission();\
+                \0";
+
+                NotificationPermission::from_js(
+                    emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char))
+            }
+        }
+
+        fn request_notification_permission(
+            &self,
+            on_permission: Box<dyn Fn(NotificationPermission) -> M>,
+        ) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var deliver = function(permission) {\
+                        __domafic_deliver($0, $1, $2, permission, null);\
+                    };\
+                    var permission = __domafic_notification_permission();\
+                    if (permission != 2) {\
+                        setTimeout(function() { deliver(permission); }, 0);\
+                    } else {\
+                        __domafic_request_notification_permission(deliver);\
+                    }\
+                \0";
+
+                let handler_ptr = deferred_handler(
+                    Box::new(move |permission, _| {
+                        on_permission(NotificationPermission::from_js(permission))
+                    }));
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle_deferred::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    handler_ptr,
+                );
+            }
+        }
+
+        fn alert(&self, text: &str, on_close: Box<dyn Fn() -> M>) {
+            self.dialog(0, text, "", Box::new(move |_, _| on_close()))
+        }
+
+        fn confirm(&self, text: &str, on_close: Box<dyn Fn(bool) -> M>) {
+            self.dialog(1, text, "", Box::new(move |accepted, _| on_close(accepted == 1)))
+        }
+
+        fn prompt(
+            &self,
+            text: &str,
+            default_text: &str,
+            on_close: Box<dyn Fn(Option<&str>) -> M>,
+        ) {
+            self.dialog(2, text, default_text, Box::new(move |_, entered| on_close(entered)))
+        }
+
+        fn request_fullscreen(&self, element: ElementRef) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = __domafic_find_ref(UTF8ToString($0));\
+                    if (!elem) { return; }\
+                    var request = elem.requestFullscreen || elem.webkitRequestFullscreen;\
+                    if (!request) { console.error('domafic: fullscreen is not supported'); return; }\
+                    var result = request.call(elem);\
+                    if (result && result.catch) {\
+                        result.catch(function(e) {\
+                            console.error('domafic: failed to enter fullscreen', e);\
+                        });\
+                    }\
+                \0";
+                let name_cstring = CString::new(element.name()).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    name_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        fn exit_fullscreen(&self) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    if (!(document.fullscreenElement || document.webkitFullscreenElement)) {\
+                        return;\
+                    }\
+                    var exit = document.exitFullscreen || document.webkitExitFullscreen;\
+                    var result = exit.call(document);\
+                    if (result && result.catch) { result.catch(function() {}); }\
+                \0";
+                emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+            }
+        }
+
+        fn request_pointer_lock(&self, element: ElementRef) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = __domafic_find_ref(UTF8ToString($0));\
+                    if (!elem) { return; }\
+                    if (!elem.requestPointerLock) {\
+                        console.error('domafic: pointer lock is not supported');\
+                        return;\
+                    }\
+                    var result = elem.requestPointerLock();\
+                    if (result && result.catch) {\
+                        result.catch(function(e) {\
+                            console.error('domafic: failed to lock pointer', e);\
+                        });\
+                    }\
+                \0";
+                let name_cstring = CString::new(element.name()).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    name_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        fn exit_pointer_lock(&self) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    if (document.pointerLockElement && document.exitPointerLock) {\
+                        document.exitPointerLock();\
+                    }\
+                \0";
+                emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+            }
+        }
+
+        fn export_html(&self, subtree: Subtree) -> Option<String> {
+            unsafe { subtree_html(self.rendered, subtree) }
+        }
+
+        fn download(&self, filename: &str, mime: &str, bytes: &[u8]) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var blob = new Blob([HEAPU8.slice($2, $2 + $3)], { type: UTF8ToString($1) });\
+                    var url = URL.createObjectURL(blob);\
+                    var link = document.createElement('a');\
+                    link.href = url;\
+                    link.download = UTF8ToString($0);\
+                    link.style.display = 'none';\
+                    document.body.appendChild(link);\
+                    link.click();\
+                    document.body.removeChild(link);\
+                    setTimeout(function() { URL.revokeObjectURL(url); }, 0);\
+                \0";
+                let filename_cstring = CString::new(filename).unwrap();
+                let mime_cstring = CString::new(mime).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    filename_cstring.as_ptr() as libc::c_int,
+                    mime_cstring.as_ptr() as libc::c_int,
+                    bytes.as_ptr() as libc::c_int,
+                    bytes.len() as libc::c_int,
+                );
+            }
+        }
+
+        fn focus(&self, element: ElementRef) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var name = UTF8ToString($0);\
+                    setTimeout(function() {\
+                        var elem = __domafic_find_ref(name);\
+                        if (!elem) { console.error('domafic: no element to focus: ' + name); return; }\
+                        elem.focus();\
+                    }, 0);\
+                \0";
+                let name_cstring = CString::new(element.name()).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    name_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        fn request_motion_permission(&self, on_permission: Box<dyn Fn(MotionPermission) -> M>) {
+            unsafe {
+                // Orientation and motion permissions are requested separately, and both must be
+                // granted. Browsers without `requestPermission` don't require permission.
+                const JS: &'static [u8] = b"\
+                    var request = function(api) {\
+                        if ('undefined' === typeof api || !api.requestPermission) {\
+                            return Promise.resolve('granted');\
+                        }\
+                        return api.requestPermission();\
+                    };\
+                    var deliver = function(granted) {\
+                        __domafic_deliver($0, $1, $2, granted ? 1 : 0, null);\
+                    };\
+                    var orientation = 'undefined' === typeof DeviceOrientationEvent ?\
+                        undefined : DeviceOrientationEvent;\
+                    var motion = 'undefined' === typeof DeviceMotionEvent ?\
+                        undefined : DeviceMotionEvent;\
+                    Promise.all([request(orientation), request(motion)]).then(function(results) {\
+                        deliver(results[0] === 'granted' && results[1] === 'granted');\
+                    }, function(e) {\
+                        console.error('domafic: failed to request motion permission', e);\
+                        deliver(false);\
+                    });\
+                \0";
+
+                let handler_ptr = deferred_handler(
+                    Box::new(move |granted, _| on_permission(
+                        if granted == 1 { MotionPermission::Granted } else { MotionPermission::Denied }
+                    )));
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle_deferred::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    handler_ptr,
+                );
+            }
+        }
+
+        fn dispatch_event(&self, target: DispatchTarget, event_name: &str, detail_json: Option<&str>) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var kind = $0;\
+                    var name = UTF8ToString($1);\
+                    var eventName = UTF8ToString($2);\
+                    var detail = null;\
+                    if ($3) {\
+                        try { detail = JSON.parse(UTF8ToString($3)); }\
+                        catch (e) { console.error('domafic: invalid detail for event ' + eventName, e); return; }\
+                    }\
+                    setTimeout(function() {\
+                        var target;\
+                        if (kind == 0) { target = __domafic_find_ref(name); }\
+                        else if (kind == 1) { target = document.querySelector(name); }\
+                        else if (kind == 2) { target = document; }\
+                        else { target = window; }\
+                        if (!target) {\
+                            console.error('domafic: no target for event ' + eventName + ': ' + name);\
+                            return;\
+                        }\
+                        target.dispatchEvent(new CustomEvent(eventName, { bubbles: true, detail: detail }));\
+                    }, 0);\
+                \0";
+
+                let (kind, name) = match target {
+                    DispatchTarget::Ref(element) => (0, element.name()),
+                    DispatchTarget::Selector(selector) => (1, selector),
+                    DispatchTarget::Document => (2, ""),
+                    DispatchTarget::Window => (3, ""),
+                };
+                let name_cstring = CString::new(name).unwrap();
+                let event_name_cstring = CString::new(event_name).unwrap();
+                let detail_cstring = detail_json.map(|detail| CString::new(detail).unwrap());
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    kind as libc::c_int,
+                    name_cstring.as_ptr() as libc::c_int,
+                    event_name_cstring.as_ptr() as libc::c_int,
+                    detail_cstring.as_ref().map_or(0, |detail| detail.as_ptr() as libc::c_int),
+                );
+            }
+        }
+
+        #[cfg(feature = "indexed_db")]
+        fn idb_get(
+            &self,
+            store: IdbStore,
+            key: &str,
+            on_value: Box<dyn Fn(Result<Option<String>, IdbError>) -> M>,
+        ) {
+            const JS: &'static [u8] = b"\
+                var deliver = function(code, text) { __domafic_deliver($0, $1, $2, code, text); };\
+                var key = UTF8ToString($5);\
+                __domafic_idb_store(UTF8ToString($3), UTF8ToString($4), 'readonly', function(code, store) {\
+                    if (code) { deliver(code, store); return; }\
+                    var request = store.get(key);\
+                    request.onsuccess = function() {\
+                        if (request.result === undefined) { deliver(1, null); }\
+                        else { deliver(0, JSON.stringify(request.result)); }\
+                    };\
+                    request.onerror = function() { deliver(2, String(request.error)); };\
+                });\
+            \0";
+
+            self.idb_request(JS, store, key, None, Box::new(move |code, text| on_value(match code {
+                0 => Ok(Some(text.unwrap_or("null").to_string())),
+                1 => Ok(None),
+                _ => Err(idb_error(code, text)),
+            })));
+        }
+
+        #[cfg(feature = "indexed_db")]
+        fn idb_put(
+            &self,
+            store: IdbStore,
+            key: &str,
+            value_json: &str,
+            on_written: Box<dyn Fn(Result<(), IdbError>) -> M>,
+        ) {
+            const JS: &'static [u8] = b"\
+                var deliver = function(code, text) { __domafic_deliver($0, $1, $2, code, text); };\
+                var key = UTF8ToString($5);\
+                var value;\
+                try { value = JSON.parse(UTF8ToString($6)); }\
+                catch (e) { setTimeout(function() { deliver(4, String(e)); }, 0); return; }\
+                __domafic_idb_store(UTF8ToString($3), UTF8ToString($4), 'readwrite', function(code, store) {\
+                    if (code) { deliver(code, store); return; }\
+                    var transaction = store.transaction;\
+                    transaction.oncomplete = function() { deliver(0, null); };\
+                    transaction.onabort = function() { deliver(2, String(transaction.error)); };\
+                    try { store.put(value, key); }\
+                    catch (e) {\
+                        transaction.oncomplete = null;\
+                        transaction.onabort = null;\
+                        transaction.abort();\
+                        deliver(2, String(e));\
+                    }\
+                });\
+            \0";
+
+            self.idb_request(JS, store, key, Some(value_json), Box::new(move |code, text| {
+                on_written(if code == 0 { Ok(()) } else { Err(idb_error(code, text)) })
+            }));
+        }
+
+        #[cfg(feature = "indexed_db")]
+        fn idb_delete(
+            &self,
+            store: IdbStore,
+            key: &str,
+            on_deleted: Box<dyn Fn(Result<(), IdbError>) -> M>,
+        ) {
+            const JS: &'static [u8] = b"\
+                var deliver = function(code, text) { __domafic_deliver($0, $1, $2, code, text); };\
+                var key = UTF8ToString($5);\
+                __domafic_idb_store(UTF8ToString($3), UTF8ToString($4), 'readwrite', function(code, store) {\
+                    if (code) { deliver(code, store); return; }\
+                    var transaction = store.transaction;\
+                    transaction.oncomplete = function() { deliver(0, null); };\
+                    transaction.onabort = function() { deliver(2, String(transaction.error)); };\
+                    store.delete(key);\
+                });\
+            \0";
+
+            self.idb_request(JS, store, key, None, Box::new(move |code, text| {
+                on_deleted(if code == 0 { Ok(()) } else { Err(idb_error(code, text)) })
+            }));
+        }
+
+        #[cfg(feature = "indexed_db")]
+        fn idb_scan(
+            &self,
+            store: IdbStore,
+            batch_size: u32,
+            on_batch: Box<dyn Fn(Result<IdbBatch<String>, IdbError>) -> M>,
+        ) {
+            unsafe {
+                // Batches which aren't the last are delivered with code 0, which keeps the
+                // handler alive for the next one.
+                const JS: &'static [u8] = b"\
+                    var deliver = function(code, text) { __domafic_deliver($0, $1, $2, code, text); };\
+                    var size = Math.max($5, 1);\
+                    __domafic_idb_store(UTF8ToString($3), UTF8ToString($4), 'readonly', function(code, store) {\
+                        if (code) { deliver(code, store); return; }\
+                        var batch = [];\
+                        var request = store.openCursor();\
+                        request.onsuccess = function() {\
+                            var cursor = request.result;\
+                            if (!cursor) { deliver(5, JSON.stringify(batch)); return; }\
+                            batch.push([String(cursor.key), cursor.value]);\
+                            if (batch.length >= size) { deliver(0, JSON.stringify(batch)); batch = []; }\
+                            cursor.continue();\
+                        };\
+                        request.onerror = function() { deliver(2, String(request.error)); };\
+                    });\
+                \0";
+
+                let handler_ptr = deferred_handler(
+                    Box::new(move |code, text| on_batch(match code {
+                        0 | 5 => parse_batch(text.unwrap_or("[]"), code == 5),
+                        _ => Err(idb_error(code, text)),
+                    })));
+
+                let database_cstring = CString::new(store.database).unwrap();
+                let name_cstring = CString::new(store.name).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle_repeated::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    handler_ptr,
+                    database_cstring.as_ptr() as libc::c_int,
+                    name_cstring.as_ptr() as libc::c_int,
+                    batch_size.min(libc::c_int::max_value() as u32) as libc::c_int,
+                );
+            }
+        }
+
+        fn register_service_worker(
+            &self,
+            url: &str,
+            on_event: Box<dyn Fn(ServiceWorkerEvent) -> M>,
+        ) {
+            unsafe {
+                // Lifecycle events are delivered with code 0 and named by their text, which
+                // keeps the handler alive for the next one. A failure is the last event.
+                const JS: &'static [u8] = b"\
+                    var deliver = function(code, text) { __domafic_deliver($0, $1, $2, code, text); };\
+                    if (!('serviceWorker' in navigator)) {\
+                        setTimeout(function() { deliver(1, 'service workers are not supported'); }, 0);\
+                        return;\
+                    }\
+                    var container = navigator.serviceWorker;\
+                    var watched = null;\
+                    var watch = function(worker) {\
+                        if (!worker || worker === watched) { return; }\
+                        watched = worker;\
+                        worker.addEventListener('statechange', function() {\
+                            if (worker.state !== 'installed') { return; }\
+                            deliver(0, container.controller ? 'updated' : 'installed');\
+                        });\
+                    };\
+                    container.register(UTF8ToString($3)).then(function(registration) {\
+                        window.__domafic_sw_registration = registration;\
+                        deliver(0, 'registered');\
+                        if (registration.waiting && container.controller) { deliver(0, 'updated'); }\
+                        watch(registration.installing);\
+                        registration.addEventListener('updatefound', function() {\
+                            watch(registration.installing);\
+                        });\
+                        container.addEventListener('controllerchange', function() {\
+                            if (window.__domafic_sw_reload) { window.location.reload(); return; }\
+                            deliver(0, 'controllerchange');\
+                        });\
+                    }, function(e) { deliver(1, String(e)); });\
+                \0";
+
+                let url_cstring = CString::new(url).unwrap();
+                let handler_ptr = deferred_handler(
+                    Box::new(move |code, text| on_event(
+                        match (code, text.and_then(ServiceWorkerEvent::from_js)) {
+                            (0, Some(event)) => event,
+                            _ => ServiceWorkerEvent::Failed(text.unwrap_or("").to_string()),
+                        }
+                    )));
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle_repeated::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    handler_ptr,
+                    url_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        fn check_for_update(&self) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var registration = window.__domafic_sw_registration;\
+                    if (!registration) { console.error('domafic: no service worker is registered'); return; }\
+                    registration.update().catch(function(e) {\
+                        console.error('domafic: failed to check for a service worker update', e);\
+                    });\
+                \0";
+                emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+            }
+        }
+
+        fn activate_update(&self) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var registration = window.__domafic_sw_registration;\
+                    if (!registration || !registration.waiting) {\
+                        console.error('domafic: no service worker update is waiting');\
+                        return;\
+                    }\
+                    window.__domafic_sw_reload = true;\
+                    registration.waiting.postMessage(UTF8ToString($0));\
+                \0";
+                let message_cstring = CString::new(SKIP_WAITING_MESSAGE).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    message_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        fn delay(&self, millis: u32, on_timeout: Box<dyn Fn() -> M>) -> CommandHandle {
+            let handle = CommandHandle::next();
+            PENDING_DELAYS.with(|pending| pending.borrow_mut().insert(handle.0));
+            let deliver = deliver_later(
+                update_system::<D, M, U, R, S>, self.app, on_timeout);
+            EmscriptenScheduler.set_timeout(millis, Box::new(move || {
+                if PENDING_DELAYS.with(|pending| pending.borrow_mut().remove(&handle.0)) {
+                    deliver();
+                }
+            }));
+            handle
+        }
+
+        fn cancel(&self, handle: CommandHandle) {
+            PENDING_DELAYS.with(|pending| pending.borrow_mut().remove(&handle.0));
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    if ('undefined' === typeof __domafic_commands) { return; }\
+                    var cancel = __domafic_commands[$0];\
+                    if (cancel) { delete __domafic_commands[$0]; cancel(); }\
+                \0";
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle.0 as libc::c_int,
+                );
+            }
+        }
+
+        fn executor(&self) -> &dyn Executor<M> {
+            self
+        }
+
+        fn scheduler(&self) -> &dyn Scheduler {
+            &EmscriptenScheduler
+        }
+    }
+
+    impl<'a, D, M, U, R, S> Executor<M> for JsIoImpl<'a, D, M, U, R, S>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        fn execute(&self, effect: Effect<M>) {
+            match effect {
+                Effect::Http { request, on_response } => {
+                    let headers: Vec<(&str, &str)> = request.headers.iter()
+                        .map(|header| (header.0.as_str(), header.1.as_str()))
+                        .collect();
+                    let http_request = HttpRequest {
+                        method: &request.method,
+                        headers: &headers,
+                        url: &request.url,
+                        body: &request.body,
+                        timeout_millis: request.timeout_millis,
+                    };
+                    JsIoImpl::http(self, http_request, None, None, None, None, Box::new(move |result: HttpResult| {
+                        on_response(result.map(|response| Response {
+                            status_code: response.status_code,
+                            status_text: response.status_text.to_string(),
+                            headers: response.headers.iter()
+                                .map(|header| (header.0.to_string(), header.1.to_string()))
+                                .collect(),
+                            body: response.body.to_string(),
+                        }))
+                    }));
+                },
+                Effect::SetStorage { area, key, value } => WebStorage::from(area).set(&key, &value),
+                Effect::RemoveStorage { area, key } => WebStorage::from(area).remove(&key),
+                Effect::Delay { millis, message } => {
+                    // The timer's callback is only called once
+                    let message = Cell::new(Some(message));
+                    self.delay(millis, Box::new(move || message.take().unwrap()));
+                },
+                Effect::Animate { animation } => {
+                    let started = unsafe { emscripten_get_now() };
+                    animate_frames(update_system::<D, M, U, R, S>, self.app, animation, started);
+                },
+                Effect::Spring { animation } => {
+                    let started = unsafe { emscripten_get_now() };
+                    spring_frames(update_system::<D, M, U, R, S>, self.app, animation, started);
+                },
+            }
+        }
+    }
+
+    /// Delivers the value of `animation` on the next frame, and on each frame after that until
+    /// it ends or the application is unmounted
+    fn animate_frames<M: 'static>(
+        deliver: unsafe fn(AppId, M, Keys),
+        app: AppId,
+        animation: Animation<M>,
+        started: f64,
+    ) {
+        EmscriptenScheduler.request_frame(Box::new(move || {
+            if !is_mounted(app) { return; }
+            let elapsed = unsafe { emscripten_get_now() } - started;
+            let message = animation.message_at(elapsed);
+            if !animation.is_done_at(elapsed) {
+                animate_frames(deliver, app, animation, started);
+            }
+            unsafe { deliver(app, message, Keys::new()) }
+        }));
+    }
+
+    /// Longest time a spring is moved between two frames, so that it doesn't jump when frames
+    /// are delayed, such as while the page is in the background
+    const MAX_SPRING_FRAME_MILLIS: f64 = 64.0;
+
+    /// Moves the spring of `animation` on the next frame, delivering its value, and on each
+    /// frame after that until it comes to rest or the application is unmounted
+    fn spring_frames<M: 'static>(
+        deliver: unsafe fn(AppId, M, Keys),
+        app: AppId,
+        mut animation: SpringAnimation<M>,
+        last_frame: f64,
+    ) {
+        EmscriptenScheduler.request_frame(Box::new(move || {
+            if !is_mounted(app) { return; }
+            let now = unsafe { emscripten_get_now() };
+            let message = animation.advance((now - last_frame).min(MAX_SPRING_FRAME_MILLIS));
+            if !animation.is_done() {
+                spring_frames(deliver, app, animation, now);
+            }
+            unsafe { deliver(app, message, Keys::new()) }
+        }));
+    }
+
+    /// A callback delivering the message produced by `on_timeout` to the application.
+    ///
+    /// Only generic over the message type, so that the callback is `'static` without requiring
+    /// the application's other types to be.
+    fn deliver_later<M: 'static>(
+        deliver: unsafe fn(AppId, M, Keys),
+        app: AppId,
+        on_timeout: Box<dyn Fn() -> M>,
+    ) -> Box<dyn FnOnce()> {
+        Box::new(move || unsafe { deliver(app, on_timeout(), Keys::new()) })
+    }
+
+    /// Handler for a message produced asynchronously by JavaScript, such as the result of a
+    /// dialog. It receives an integer and an optional string from JavaScript.
+    type DeferredHandler<M> = Box<dyn Fn(libc::c_int, Option<&str>) -> M>;
+
+    /// Boxes a `DeferredHandler` into a thin pointer which can be passed to JavaScript.
+    ///
+    /// The handler is freed when `handle_deferred` is called with the pointer, so every
+    /// handler created this way must be called exactly once, or by `handle_repeated` until
+    /// it's called with its final value.
+    fn deferred_handler<M>(handler: DeferredHandler<M>) -> libc::c_int {
+        Box::into_raw(Box::new(handler)) as libc::c_int
+    }
+
+    unsafe extern "C" fn handle_deferred<D, M, U, R, S>(
+        app: AppId,
+        handler_ptr: *mut DeferredHandler<M>,
+        value: libc::c_int,
+        text_ptr: *const libc::c_char,
+    )
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let handler = Box::from_raw(handler_ptr);
+
+        let text = if (text_ptr as usize) != 0 {
+            str::from_utf8(CStr::from_ptr(text_ptr).to_bytes()).ok()
+        } else {
+            None
+        };
+
+        let message = handler(value, text);
+        update_system::<D, M, U, R, S>(app, message, Keys::new());
+    }
+
+    /// Calls a `DeferredHandler` created with `deferred_handler` for one of a series of
+    /// values, such as the batches of an IndexedDB scan, freeing it once `value` is nonzero.
+    unsafe extern "C" fn handle_repeated<D, M, U, R, S>(
+        app: AppId,
+        handler_ptr: *mut DeferredHandler<M>,
+        value: libc::c_int,
+        text_ptr: *const libc::c_char,
+    )
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let text = if (text_ptr as usize) != 0 {
+            str::from_utf8(CStr::from_ptr(text_ptr).to_bytes()).ok()
+        } else {
+            None
+        };
+
+        let message = (*handler_ptr)(value, text);
+        if value != 0 {
+            drop(Box::from_raw(handler_ptr));
+        }
+        update_system::<D, M, U, R, S>(app, message, Keys::new());
+    }
+
+    /// Frees a `DeferredHandler` without calling it, such as when its command is cancelled
+    unsafe extern "C" fn drop_deferred<M>(handler_ptr: *mut DeferredHandler<M>) {
+        drop(Box::from_raw(handler_ptr));
+    }
+
+    /// Handler for the progress of an HTTP request, which is freed along with the final progress
+    fn progress_handler<M: 'static>(on_progress: Box<dyn Fn(Progress) -> M>) -> DeferredHandler<M> {
+        Box::new(move |done, text| on_progress(
+            text.and_then(|text| Progress::from_js(text, done != 0)).unwrap()
+        ))
+    }
+
+    /// The error for a failed IndexedDB request: code 3 if IndexedDB isn't available, 4 if
+    /// a value wasn't valid JSON, and otherwise a failure with the browser's message
+    #[cfg(feature = "indexed_db")]
+    fn idb_error(code: libc::c_int, text: Option<&str>) -> IdbError {
+        let message = text.unwrap_or("").to_string();
+        match code {
+            3 => IdbError::Unsupported,
+            4 => IdbError::Malformed(message),
+            _ => IdbError::Failed(message),
+        }
+    }
+
+    fn pool_stats() -> PoolStats {
+        let mut stats: [libc::c_int; 3] = [0; 3];
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var occupied = 0;\
+                for (var i = 0; i < __domafic_pool.length; i++) {\
+                    if (__domafic_pool[i] !== undefined) { occupied++; }\
+                }\
+                setValue($0, __domafic_pool.length, 'i32');\
+                setValue($0 + 4, occupied, 'i32');\
+                setValue($0 + 8, __domafic_pool_free.length, 'i32');\
+            \0";
+
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                stats.as_mut_ptr() as *const _ as *const libc::c_char,
+            );
+        }
+        PoolStats {
+            size: stats[0] as usize,
+            occupied: stats[1] as usize,
+            free: stats[2] as usize,
+        }
+    }
+
+    /// Collects the pool indices of every element and listener callback held by the VDOM
+    #[cfg(debug_assertions)]
+    fn collect_pool_indices<M>(vnode: &VDomNode<M>, indices: &mut Vec<libc::c_int>) {
+        indices.push(vnode.web_element.0);
+        for &(ref callback, _, _, _) in &vnode.listeners {
+            indices.push(callback.0);
+        }
+        for child in &vnode.children {
+            collect_pool_indices(child, indices);
+        }
+    }
+
+    impl<'a, D, M, U, R, S> JsIoImpl<'a, D, M, U, R, S>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        fn http<'b> (
+            &self,
+            http_request: HttpRequest<'b>,
+            cache_policy: Option<CachePolicy>,
+            tag: Option<&str>,
+            body_bytes: Option<&[u8]>,
+            progress: Option<DeferredHandler<M>>,
+            handler: Box<HttpResponseHandler<Message=M>>,
+        ) -> CommandHandle {
+            // Counted as in flight until the handler is freed, once the response has been
+            // handled or the request cancelled, so that the render which follows is no longer
+            // busy with the request
+            let in_flight = InFlight::start(self.app, tag);
+            let handler: Box<dyn HttpResponseHandler<Message=M>> = Box::new(move |result: HttpResult| {
+                let _ = &in_flight;
+                handler.handle(result)
+            });
+            let handle = CommandHandle::next();
+            unsafe {
+                let HttpRequest { method, headers, url, body, timeout_millis } = http_request;
+                let method_cstring = CString::new(method).unwrap();
+                let url_cstring = CString::new(url).unwrap();
+                let body_cstring = CString::new(body).unwrap();
+
+                let header_key_cstrings: Vec<CString> =
+                headers.iter().map(|header| CString::new(header.0).unwrap()).collect();
+
+                let header_key_pointers: Vec<libc::c_int> =
+                header_key_cstrings.iter().map(|cstring|
+                    cstring.as_ptr() as libc::c_int).collect();
+
+                let header_value_cstrings: Vec<CString> =
+                headers.iter().map(|header| CString::new(header.1).unwrap()).collect();
+
+                let header_value_pointers: Vec<libc::c_int> =
+                header_value_cstrings.iter().map(|cstring|
+                    cstring.as_ptr() as libc::c_int).collect();
+
+                let handler_ptr = Box::into_raw(Box::new(handler)) as libc::c_int;
+                let progress_ptr = progress.map_or(0, deferred_handler);
+
+                let (cache_mode, max_age_millis) = match cache_policy {
+                    None => (0, 0),
+                    Some(CachePolicy::Dedupe) => (1, 0),
+                    Some(CachePolicy::MaxAge(millis)) => (2, millis),
+                    Some(CachePolicy::Reload) => (3, 0),
+                };
+
+                // Cached responses and requests which join an in-flight request can't be delivered
+                // synchronously, since we're still in the middle of an update. They don't report
+                // progress, so their progress handler is freed straight away.
+                const JS: &'static [u8] = b"\
+                    var handler_fn_ptr = $0;\
+                    var app_system = $1;\
+                    var method = UTF8ToString($2);\
+                    var url = UTF8ToString($3);\
+                    var body = UTF8ToString($4);\
+                    var header_len = $5;\
+                    var header_key_ptr = $6;\
+                    var header_value_ptr = $7;\
+                    var timeout = $8;\
+                    var handler = [$9, $18];\
+                    var cache_mode = $10;\
+                    var max_age = $11;\
+                    if ($13 >= 0) { body = HEAPU8.slice($12, $12 + $13); }\
+                    var progress = $15;\
+                    if ('undefined' === typeof __domafic_http_cache) {\
+                        __domafic_http_cache = {};\
+                        __domafic_http_inflight = {};\
+                    }\
+                    if ('undefined' === typeof __domafic_commands) { __domafic_commands = {}; }\
+                    var drop_handler = function(handler) {\
+                        Runtime.dynCall('viiiiiii', handler_fn_ptr, [3, app_system, handler[0], 0, 0, 0, 0]);\
+                    };\
+                    var drop_progress = function() {\
+                        if (progress) { Runtime.dynCall('vi', $17, [progress]); }\
+                    };\
+                    var deliver = function(handlers, response) {\
+                        for (var i = 0; i < handlers.length; i++) {\
+                            delete __domafic_commands[handlers[i][1]];\
+                            var stack = Runtime.stackSave();\
+                            var status_text = allocate(\
+                                intArrayFromString(response.status_text), 'i8', ALLOC_STACK\
+                            );\
+                            var response_headers = allocate(\
+                                intArrayFromString(response.headers), 'i8', ALLOC_STACK\
+                            );\
+                            var response_body =\
+                                allocate(intArrayFromString(response.body), 'i8', ALLOC_STACK);\
+                            Runtime.dynCall('viiiiiii', handler_fn_ptr, [response.sig, app_system, handlers[i][0], response.status, status_text, response_body, response_headers]);\
+                            Runtime.stackRestore(stack);\
+                        }\
+                    };\
+                    var failure = function(sig) {\
+                        return { sig: sig, status: 0, status_text: '', body: '', headers: '' };\
+                    };\
+                    if (method !== 'GET' && method !== 'HEAD') {\
+                        delete __domafic_http_cache['GET ' + url];\
+                        delete __domafic_http_cache['HEAD ' + url];\
+                    }\
+                    var cacheable = cache_mode != 0 && (method === 'GET' || method === 'HEAD');\
+                    var key = method + ' ' + url;\
+                    if (cacheable && cache_mode == 2) {\
+                        var cached = __domafic_http_cache[key];\
+                        if (cached && (Date.now() - cached.time) < max_age) {\
+                            drop_progress();\
+                            var timer = setTimeout(function() { deliver([handler], cached.response); }, 0);\
+                            __domafic_commands[handler[1]] = function() {\
+                                clearTimeout(timer);\
+                                drop_handler(handler);\
+                            };\
+                            return;\
+                        }\
+                    }\
+                    if (cacheable && cache_mode != 3 && __domafic_http_inflight[key]) {\
+                        drop_progress();\
+                        var joined = __domafic_http_inflight[key];\
+                        joined.push(handler);\
+                        __domafic_commands[handler[1]] = function() {\
+                            var index = joined.indexOf(handler);\
+                            if (index >= 0) { joined.splice(index, 1); drop_handler(handler); }\
+                        };\
+                        return;\
+                    }\
+                    var handlers = [handler];\
+                    if (cacheable) { __domafic_http_inflight[key] = handlers; }\
+                    var last_progress = { upload: false, loaded: 0, total: -1, time: 0 };\
+                    var cancelled = false;\
+                    var report = function(final) {\
+                        if (!progress || cancelled) { return; }\
+                        var text = (last_progress.upload ? '1 ' : '0 ') + last_progress.loaded + ' ' +\
+                            last_progress.total;\
+                        __domafic_deliver($14, app_system, progress, final ? 1 : 0, text);\
+                    };\
+                    var on_progress = function(upload) {\
+                        return function(event) {\
+                            last_progress.upload = upload;\
+                            last_progress.loaded = event.loaded;\
+                            last_progress.total = event.lengthComputable ? event.total : -1;\
+                            var now = Date.now();\
+                            if (now - last_progress.time < $16) { return; }\
+                            last_progress.time = now;\
+                            report(false);\
+                        };\
+                    };\
+                    var finish = function(response) {\
+                        if (cancelled) { return; }\
+                        report(true);\
+                        if (cacheable && __domafic_http_inflight[key] === handlers) {\
+                            delete __domafic_http_inflight[key];\
+                        }\
+                        if (cacheable && cache_mode != 1 && response.sig == 0 &&\
+                            response.status >= 200 && response.status < 300) {\
+                            __domafic_http_cache[key] = { time: Date.now(), response: response };\
+                        }\
+                        deliver(handlers, response);\
+                    };\
+                    var xhr = new XMLHttpRequest();\
+                    __domafic_commands[handler[1]] = function() {\
+                        var index = handlers.indexOf(handler);\
+                        if (index < 0) { return; }\
+                        handlers.splice(index, 1);\
+                        drop_handler(handler);\
+                        if (handlers.length > 0) { return; }\
+                        cancelled = true;\
+                        if (cacheable && __domafic_http_inflight[key] === handlers) {\
+                            delete __domafic_http_inflight[key];\
+                        }\
+                        drop_progress();\
+                        xhr.abort();\
+                    };\
+                    if (progress) {\
+                        xhr.upload.addEventListener('progress', on_progress(true));\
+                        xhr.addEventListener('progress', on_progress(false));\
+                    }\
+                    xhr.addEventListener('timeout', function() { finish(failure(1)); });\
+                    xhr.addEventListener('error', function() { finish(failure(2)); });\
+                    xhr.addEventListener('load', function() {\
+                        finish({\
+                            sig: 0,\
+                            status: xhr.status,\
+                            status_text: xhr.statusText,\
+                            body: xhr.responseText,\
+                            headers: xhr.getAllResponseHeaders()\
+                        });\
+                    });\
+                    try { xhr.open(method, url, true); } catch (e) {\
+                        setTimeout(function() { finish(failure(2)); }, 0);\
+                        return;\
+                    }\
+                    for (var i = 0; i < header_len; i++) {\
                         var header_key = UTF8ToString(getValue(header_key_ptr + (i * 4), '*'));\
                         var header_value = UTF8ToString(getValue(header_value_ptr + (i * 4), '*'));\
                         xhr.setRequestHeader(header_key, header_value);\
                     }\
-                    xhr.responseType = 'text';\
-                    if (timeout != 0) { xhr.timeout = timeout; }\
-                    xhr.send(body);\
+                    xhr.responseType = 'text';\
+                    if (timeout != 0) { xhr.timeout = timeout; }\
+                    xhr.send(body);\
+                \0";
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle_http_result::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    method_cstring.as_ptr() as libc::c_int,
+                    url_cstring.as_ptr() as libc::c_int,
+                    body_cstring.as_ptr() as libc::c_int,
+                    header_key_pointers.len() as libc::c_int,
+                    header_key_pointers.as_ptr() as *const _ as *const libc::c_char,
+                    header_value_pointers.as_ptr() as *const _ as *const libc::c_char,
+                    timeout_millis.unwrap_or(0) as libc::c_int,
+                    handler_ptr,
+                    cache_mode as libc::c_int,
+                    max_age_millis as libc::c_int,
+                    body_bytes.map_or(0, |bytes| bytes.as_ptr() as libc::c_int),
+                    body_bytes.map_or(-1, |bytes| bytes.len() as libc::c_int),
+                    handle_repeated::<D, M, U, R, S> as *const libc::c_void,
+                    progress_ptr,
+                    PROGRESS_INTERVAL_MILLIS as libc::c_int,
+                    drop_deferred::<M> as *const libc::c_void,
+                    handle.0 as libc::c_int,
+                );
+            }
+            handle
+        }
+
+        /// Shows an alert (`kind` 0), confirm (1) or prompt (2) dialog once the current update
+        /// has finished, passing the result to `handler`.
+        fn dialog(
+            &self,
+            kind: libc::c_int,
+            text: &str,
+            default_text: &str,
+            handler: DeferredHandler<M>,
+        ) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var kind = $3;\
+                    var text = UTF8ToString($4);\
+                    var default_text = UTF8ToString($5);\
+                    setTimeout(function() {\
+                        if (kind == 0) {\
+                            window.alert(text);\
+                            __domafic_deliver($0, $1, $2, 0, null);\
+                        } else if (kind == 1) {\
+                            __domafic_deliver($0, $1, $2, window.confirm(text) ? 1 : 0, null);\
+                        } else {\
+                            __domafic_deliver($0, $1, $2, 0, window.prompt(text, default_text));\
+                        }\
+                    }, 0);\
+                \0";
+
+                let text_cstring = CString::new(text).unwrap();
+                let default_text_cstring = CString::new(default_text).unwrap();
+                let handler_ptr = deferred_handler(handler);
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    handle_deferred::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    handler_ptr,
+                    kind,
+                    text_cstring.as_ptr() as libc::c_int,
+                    default_text_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        /// Runs the JavaScript `js` of an IndexedDB request for `key` in `store`, passing the
+        /// result to `handler`.
+        ///
+        /// `js` receives the handler as `$0` to `$2`, the database and store names as `$3` and
+        /// `$4`, the key as `$5`, and the value's JSON, if any, as `$6`.
+        #[cfg(feature = "indexed_db")]
+        fn idb_request(
+            &self,
+            js: &'static [u8],
+            store: IdbStore,
+            key: &str,
+            value_json: Option<&str>,
+            handler: DeferredHandler<M>,
+        ) {
+            let database_cstring = CString::new(store.database).unwrap();
+            let name_cstring = CString::new(store.name).unwrap();
+            let key_cstring = CString::new(key).unwrap();
+            let value_cstring = value_json.map(|value| CString::new(value).unwrap());
+            let handler_ptr = deferred_handler(handler);
+
+            unsafe {
+                emscripten_asm_const_int(
+                    &js[0] as *const _ as *const libc::c_char,
+                    handle_deferred::<D, M, U, R, S> as *const libc::c_void,
+                    self.app,
+                    handler_ptr,
+                    database_cstring.as_ptr() as libc::c_int,
+                    name_cstring.as_ptr() as libc::c_int,
+                    key_cstring.as_ptr() as libc::c_int,
+                    value_cstring.as_ref().map_or(0, |value| value.as_ptr() as libc::c_int),
+                );
+            }
+        }
+
+        fn clear_http_cache(&self, url_prefix: &str) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    if ('undefined' === typeof __domafic_http_cache) { return; }\
+                    var prefix = UTF8ToString($0);\
+                    for (var key in __domafic_http_cache) {\
+                        var url = key.substring(key.indexOf(' ') + 1);\
+                        if (url.lastIndexOf(prefix, 0) === 0) { delete __domafic_http_cache[key]; }\
+                    }\
+                \0";
+                let prefix_cstring = CString::new(url_prefix).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    prefix_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+    }
+
+    unsafe extern fn handle_http_result<D, M, U, R, S>
+    (
+        error_sig: libc::c_int,
+        app: AppId,
+        handler_ptr: *mut Box<HttpResponseHandler<Message=M>>,
+        status_code: u16,
+        status_text: *const libc::c_char,
+        body: *const libc::c_char,
+        headers_ptr: *const libc::c_char
+    )
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let handler = Box::from_raw(handler_ptr);
+        if error_sig == 3 {
+            // The request was cancelled, so the handler is only freed
+            return;
+        }
+
+        let status_text = str::from_utf8(CStr::from_ptr(status_text).to_bytes()).unwrap();
+
+        let headers;
+        let response_result = match error_sig {
+            0 => {
+                let headers_str = str::from_utf8(CStr::from_ptr(headers_ptr).to_bytes()).unwrap();
+                headers = headers_str.split("\r\n").flat_map(|header| {
+                    header.find(':').map(|split_index| {
+                        let (key, value) = header.split_at(split_index);
+                        (key.trim(), value[1..].trim())
+                    })
+                }).collect::<Vec<_>>();
+
+                Ok(HttpResponse {
+                    status_code: status_code,
+                    status_text: status_text,
+                    headers: &headers,
+                    body: str::from_utf8(CStr::from_ptr(body).to_bytes()).unwrap(),
+                })
+            },
+
+            1 => Err(HttpError::Timeout),
+
+            2 => Err(HttpError::NetworkError),
+
+            _ => unreachable!(),
+        };
+
+        let message = handler.handle(response_result);
+        drop(handler);
+
+        update_system::<D, M, U, R, S>(app, message, Keys::new());
+    }
+
+    /// The browser's `localStorage` or `sessionStorage`.
+    ///
+    /// Storage access errors (such as exceeding the storage quota or storage being disabled)
+    /// are logged to the console, and reads return `None`.
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub enum WebStorage {
+        /// `window.localStorage`, which persists across browser sessions
+        Local,
+        /// `window.sessionStorage`, which is cleared when the page session ends
+        Session,
+    }
+
+    impl From<StorageArea> for WebStorage {
+        fn from(area: StorageArea) -> WebStorage {
+            match area {
+                StorageArea::Local => WebStorage::Local,
+                StorageArea::Session => WebStorage::Session,
+            }
+        }
+    }
+
+    impl WebStorage {
+        fn index(&self) -> libc::c_int {
+            match *self {
+                WebStorage::Local => 0,
+                WebStorage::Session => 1,
+            }
+        }
+
+        /// Stores `value` under `key` after `millis` milliseconds have passed without another
+        /// call to `set_debounced` for the same key.
+        ///
+        /// This is useful for saving data which changes frequently, as writes to storage are
+        /// synchronous and can be slow.
+        pub fn set_debounced(&self, key: &str, value: &str, millis: u32) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    if ('undefined' === typeof __domafic_storage_timers) {\
+                        __domafic_storage_timers = {};\
+                    }\
+                    var storage = $0 == 0 ? 'localStorage' : 'sessionStorage';\
+                    var key = UTF8ToString($1);\
+                    var value = UTF8ToString($2);\
+                    var timer_key = storage + ':' + key;\
+                    clearTimeout(__domafic_storage_timers[timer_key]);\
+                    __domafic_storage_timers[timer_key] = setTimeout(function() {\
+                        delete __domafic_storage_timers[timer_key];\
+                        try { window[storage].setItem(key, value); }\
+                        catch (e) { console.error('domafic: failed to write to ' + storage, e); }\
+                    }, $3);\
+                \0";
+                let key_cstring = CString::new(key).unwrap();
+                let value_cstring = CString::new(value).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.index(),
+                    key_cstring.as_ptr() as libc::c_int,
+                    value_cstring.as_ptr() as libc::c_int,
+                    millis as libc::c_int,
+                );
+            }
+        }
+    }
+
+    impl Storage for WebStorage {
+        fn get(&self, key: &str) -> Option<String> {
+            let key_cstring = CString::new(key).unwrap();
+
+            // First find the encoded length of the value, then copy it into a Rust buffer
+            let len = unsafe {
+                const JS: &'static [u8] = b"\
+                    var storage = $0 == 0 ? 'localStorage' : 'sessionStorage';\
+                    var value;\
+                    try { value = window[storage].getItem(UTF8ToString($1)); }\
+                    catch (e) { console.error('domafic: failed to read from ' + storage, e); }\
+                    if (value === null || value === undefined) { return -1; }\
+                    return lengthBytesUTF8(value);\
+                \0";
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.index(),
+                    key_cstring.as_ptr() as libc::c_int,
+                )
+            };
+            if len < 0 { return None; }
+
+            let mut buffer: Vec<u8> = vec![0; len as usize + 1];
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var storage = $0 == 0 ? 'localStorage' : 'sessionStorage';\
+                    var value = window[storage].getItem(UTF8ToString($1)) || '';\
+                    stringToUTF8(value, $2, $3);\
+                \0";
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.index(),
+                    key_cstring.as_ptr() as libc::c_int,
+                    buffer.as_mut_ptr() as libc::c_int,
+                    buffer.len() as libc::c_int,
+                );
+            }
+            buffer.pop(); // Remove the nul terminator
+            String::from_utf8(buffer).ok()
+        }
+
+        fn set(&self, key: &str, value: &str) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var storage = $0 == 0 ? 'localStorage' : 'sessionStorage';\
+                    try { window[storage].setItem(UTF8ToString($1), UTF8ToString($2)); }\
+                    catch (e) { console.error('domafic: failed to write to ' + storage, e); }\
+                \0";
+                let key_cstring = CString::new(key).unwrap();
+                let value_cstring = CString::new(value).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.index(),
+                    key_cstring.as_ptr() as libc::c_int,
+                    value_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        fn remove(&self, key: &str) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var storage = $0 == 0 ? 'localStorage' : 'sessionStorage';\
+                    try { window[storage].removeItem(UTF8ToString($1)); }\
+                    catch (e) { console.error('domafic: failed to write to ' + storage, e); }\
+                \0";
+                let key_cstring = CString::new(key).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.index(),
+                    key_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+    }
+
+    extern "C" {
+        fn emscripten_asm_const_int(s: *const libc::c_char, ...) -> libc::c_int;
+        fn emscripten_get_now() -> f64;
+        fn emscripten_pause_main_loop();
+        fn emscripten_set_main_loop(m: extern fn(), fps: libc::c_int, infinite: libc::c_int);
+    }
+
+    type JsElementId = libc::c_int;
+
+    #[derive(Debug)]
+    struct WebElement(JsElementId);
+
+    #[derive(Debug, Copy, Clone)]
+    struct WebDocument(()); // Contains private () so that it can't be created externally
+
+    fn web_init() -> WebDocument {
+        const JS: &'static [u8] = b"\
+            if('undefined'===typeof __domafic_pool){\
+                console.log('Intializing __domafic_pool');\
+                __domafic_pool=[];\
+                __domafic_pool_free=[];\
+            }\
+            if ('undefined' === typeof __domafic_rendering) {\
+                __domafic_rendering = {};\
+                __domafic_deferred_events = {};\
+            }\
+            if ('undefined' === typeof __domafic_tt_policy) { __domafic_tt_policy = null; }\
+            __domafic_set_property = function(elem, key, value) {\
+                var policy = __domafic_tt_policy;\
+                var script = elem.tagName === 'SCRIPT';\
+                if (policy) {\
+                    var html = key === 'innerHTML' || key === 'outerHTML' || key === 'srcdoc';\
+                    if (html) {\
+                        value = policy.createHTML(value === null ? '' : value);\
+                    } else if (script && key === 'src') {\
+                        value = policy.createScriptURL(value === null ? '' : value);\
+                    } else if (key.lastIndexOf('on', 0) === 0 ||\
+                            (script && (key === 'text' || key === 'textContent' || key === 'innerText'))) {\
+                        value = value === null ? null : policy.createScript(value);\
+                    }\
+                }\
+                if (key.lastIndexOf('data-', 0) === 0 || key.lastIndexOf('aria-', 0) === 0) {\
+                    if (elem.nodeType !== 1) { return; }\
+                    if (value === null) { elem.removeAttribute(key); } else { elem.setAttribute(key, value); }\
+                    return;\
+                }\
+                elem[key] = value;\
+            };\
+            __domafic_deliver = function(fn_ptr, app_system, handler, value, text) {\
+                var stack = Runtime.stackSave();\
+                var text_ptr = (text === null || text === undefined) ? 0 :\
+                    allocate(intArrayFromString(text), 'i8', ALLOC_STACK);\
+                Runtime.dynCall('viiii', fn_ptr, [app_system, handler, value, text_ptr]);\
+                Runtime.stackRestore(stack);\
+            };\
+            __domafic_idb_databases = {};\
+            __domafic_idb_store = function(database, name, mode, callback) {\
+                var withDatabase = function(db) {\
+                    try { callback(0, db.transaction(name, mode).objectStore(name)); }\
+                    catch (e) { callback(2, String(e)); }\
+                };\
+                var open = function(version) {\
+                    var request;\
+                    try { request = version ? indexedDB.open(database, version) : indexedDB.open(database); }\
+                    catch (e) { callback(3, null); return; }\
+                    request.onupgradeneeded = function() {\
+                        if (!request.result.objectStoreNames.contains(name)) {\
+                            request.result.createObjectStore(name);\
+                        }\
+                    };\
+                    request.onsuccess = function() {\
+                        var db = request.result;\
+                        if (!db.objectStoreNames.contains(name)) {\
+                            db.close();\
+                            open(db.version + 1);\
+                            return;\
+                        }\
+                        db.onversionchange = function() {\
+                            db.close();\
+                            delete __domafic_idb_databases[database];\
+                        };\
+                        __domafic_idb_databases[database] = db;\
+                        withDatabase(db);\
+                    };\
+                    request.onerror = function() { callback(2, String(request.error)); };\
+                };\
+                var cached = __domafic_idb_databases[database];\
+                if (cached && cached.objectStoreNames.contains(name)) { withDatabase(cached); return; }\
+                if (cached) { cached.close(); delete __domafic_idb_databases[database]; }\
+                if ('undefined' === typeof indexedDB || !indexedDB) {\
+                    setTimeout(function() { callback(3, null); }, 0);\
+                    return;\
+                }\
+                open(0);\
+            };\
+            __domafic_find_ref = function(name) {\
+                var elems = document.querySelectorAll('[data-domafic-ref]');\
+                for (var i = 0; i < elems.length; i++) {\
+                    if (elems[i].getAttribute('data-domafic-ref') === name) { return elems[i]; }\
+                }\
+                console.error('domafic: no element is marked with ref ' + name);\
+                return null;\
+            };\
+            __domafic_notification_permission = function() {\
+                if ('undefined' === typeof Notification) { return 3; }\
+                if (Notification.permission === 'granted') { return 0; }\
+                if (Notification.permission === 'denied') { return 1; }\
+                return 2;\
+            };\
+            __domafic_request_notification_permission = function(callback) {\
+                var called = false;\
+                var done = function() {\
+                    if (called) { return; }\
+                    called = true;\
+                    callback(__domafic_notification_permission());\
+                };\
+                var promise = Notification.requestPermission(done);\
+                if (promise && promise.then) { promise.then(done, done); }\
+            };\
+        \0";
+
+        unsafe {
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+        }
+
+        WebDocument(())
+    }
+
+    /// Maximum number of stack frames shown by the crash overlay
+    #[cfg(debug_assertions)]
+    const CRASH_OVERLAY_STACK_FRAMES: libc::c_int = 12;
+
+    /// Installs a panic hook which, after running the previously installed hook, draws an
+    /// overlay describing the panic on top of the application's root element.
+    ///
+    /// A panic in an updater or renderer leaves the application's state and virtual DOM in an
+    /// unknown condition, so rather than attempting to reuse them, the overlay's reset button
+    /// reloads the page, which restarts the application from its initial state.
+    #[cfg(debug_assertions)]
+    fn install_crash_overlay(root_element: &WebElement) {
+        use std::panic;
+
+        let root_id = root_element.0;
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous_hook(info);
+
+            let payload = info.payload();
+            let message = payload.downcast_ref::<&str>().cloned()
+                .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+                .unwrap_or("Box<Any>");
+            let location = info.location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+                .unwrap_or_else(String::new);
+
+            // Interior nul bytes can't be passed through to JS, so replace them
+            let message_cstring = CString::new(message.replace('\0', "\\0")).unwrap();
+            let location_cstring = CString::new(location.replace('\0', "\\0")).unwrap();
+
+            const JS: &'static [u8] = b"\
+                var root = __domafic_pool[$0];\
+                if (!root) { return; }\
+                var stack = (new Error()).stack || '';\
+                var frames = stack.split('\\n').slice(0, $3);\
+                var overlay = document.createElement('div');\
+                overlay.setAttribute('style', 'position:fixed;top:0;left:0;right:0;bottom:0;\
+                    z-index:2147483647;overflow:auto;padding:2em;background:rgba(40,0,0,0.92);\
+                    color:#fff;font-family:monospace;');\
+                var heading = document.createElement('h2');\
+                heading.textContent = 'The application panicked';\
+                overlay.appendChild(heading);\
+                var message = document.createElement('pre');\
+                message.setAttribute('style', 'white-space:pre-wrap;font-size:1.2em;');\
+                message.textContent = UTF8ToString($1);\
+                overlay.appendChild(message);\
+                var location = document.createElement('p');\
+                location.textContent = UTF8ToString($2);\
+                overlay.appendChild(location);\
+                var trace = document.createElement('pre');\
+                trace.setAttribute('style', 'opacity:0.7;');\
+                trace.textContent = frames.join('\\n');\
+                overlay.appendChild(trace);\
+                var reset = document.createElement('button');\
+                reset.textContent = 'Reset state';\
+                reset.addEventListener('click', function() { window.location.reload(); });\
+                overlay.appendChild(reset);\
+                root.appendChild(overlay);\
+            \0";
+
+            unsafe {
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    root_id,
+                    message_cstring.as_ptr() as libc::c_int,
+                    location_cstring.as_ptr() as libc::c_int,
+                    CRASH_OVERLAY_STACK_FRAMES,
+                );
+            }
+        }));
+    }
+
+    extern fn pause_main_web_loop() {
+        unsafe { emscripten_pause_main_loop(); }
+    }
+
+    /// The `Scheduler` used when rendering client-side, which runs callbacks with the browser's
+    /// `setTimeout` and `requestAnimationFrame`
+    #[derive(Debug, Copy, Clone, Default)]
+    pub struct EmscriptenScheduler;
+
+    thread_local! {
+        static PENDING_RENDER: RefCell<Option<Box<dyn FnOnce()>>> = RefCell::new(None);
+    }
+
+    /// Splits a scheduled callback into a pointer which can be passed to JavaScript.
+    ///
+    /// The callback is freed when `run_scheduled` is called with the pointer.
+    fn scheduled_callback(callback: Box<dyn FnOnce()>) -> libc::c_int {
+        Box::into_raw(Box::new(callback)) as libc::c_int
+    }
+
+    unsafe extern "C" fn run_scheduled(callback_ptr: *mut Box<dyn FnOnce()>) {
+        let callback = Box::from_raw(callback_ptr);
+        callback()
+    }
+
+    impl Scheduler for EmscriptenScheduler {
+        fn schedule_render(&self, render: Box<dyn FnOnce()>) {
+            let was_pending = PENDING_RENDER.with(|pending| pending.borrow_mut().replace(render).is_some());
+            if !was_pending {
+                self.request_frame(Box::new(|| {
+                    if let Some(render) = PENDING_RENDER.with(|pending| pending.borrow_mut().take()) {
+                        render();
+                    }
+                }));
+            }
+        }
+
+        fn set_timeout(&self, millis: u32, callback: Box<dyn FnOnce()>) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var fn_ptr = $0, callback_ptr = $1;\
+                    setTimeout(function() { Runtime.dynCall('vi', fn_ptr, [callback_ptr]); }, $2);\
+                \0";
+
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    run_scheduled as *const libc::c_void,
+                    scheduled_callback(callback),
+                    millis as libc::c_int,
+                );
+            }
+        }
+
+        fn request_frame(&self, callback: Box<dyn FnOnce()>) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var fn_ptr = $0, callback_ptr = $1;\
+                    var frame = function() { Runtime.dynCall('vi', fn_ptr, [callback_ptr]); };\
+                    if (window.requestAnimationFrame) {\
+                        window.requestAnimationFrame(frame);\
+                    } else {\
+                        setTimeout(frame, 16);\
+                    }\
                 \0";
 
-                emscripten_asm_const_int(
-                    &JS[0] as *const _ as *const libc::c_char,
-                    handle_http_result::<D, M, U, R, S> as *const libc::c_void,
-                    self.app_system as *const libc::c_void,
-                    method_cstring.as_ptr() as libc::c_int,
-                    url_cstring.as_ptr() as libc::c_int,
-                    body_cstring.as_ptr() as libc::c_int,
-                    header_key_pointers.len() as libc::c_int,
-                    header_key_pointers.as_ptr() as *const _ as *const libc::c_char,
-                    header_value_pointers.as_ptr() as *const _ as *const libc::c_char,
-                    timeout_millis.unwrap_or(0) as libc::c_int,
-                    handler_data_ptr,
-                    handler_vtable_ptr,
-                );
-            }
-        }
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    run_scheduled as *const libc::c_void,
+                    scheduled_callback(callback),
+                );
+            }
+        }
+
+        /// Starts emscripten's main loop, which never returns
+        fn run_main_loop(&self) {
+            unsafe { emscripten_set_main_loop(pause_main_web_loop, 0, 1); }
+        }
+    }
+
+    impl WebDocument {
+        fn element_from_selector(&self, selector: &str) -> Result<WebElement, Error> {
+            let id = {
+                unsafe {
+                    const JS: &'static [u8] = b"\
+                        var elem;\
+                        try { elem = document.querySelector(UTF8ToString($0)); } catch (e) { return -3; }\
+                        if (!elem) {return -1;}\
+                        var index = __domafic_pool_free.pop();\
+                        if (index !== undefined) { __domafic_pool[index] = elem; return index; }\
+                        if (__domafic_pool.length >= 2147483647) { return -2; }\
+                        return __domafic_pool.push(elem) - 1;\
+                    \0";
+                    let selector_cstring = CString::new(selector).unwrap();
+                    emscripten_asm_const_int(
+                        &JS[0] as *const _ as *const libc::c_char,
+                        selector_cstring.as_ptr() as libc::c_int
+                    )
+                }
+            };
+            match id {
+                -1 => Err(Error::MissingElement(selector.to_string())),
+                -3 => Err(Error::InvalidSelector(selector.to_string())),
+                id => pooled(id, "querySelector"),
+            }
+        }
+
+        fn create_element(&self, tagname: &str) -> Result<WebElement, Error> {
+            let id = {
+                unsafe {
+                    const JS: &'static [u8] = b"\
+                        var elem = document.createElement(UTF8ToString($0));\
+                        if (!elem) {return -1;}\
+                        var index = __domafic_pool_free.pop();\
+                        if (index !== undefined) { __domafic_pool[index] = elem; return index; }\
+                        if (__domafic_pool.length >= 2147483647) { return -2; }\
+                        return __domafic_pool.push(elem) - 1;\
+                    \0";
+                    let tagname_cstring = CString::new(tagname).unwrap();
+                    emscripten_asm_const_int(
+                        &JS[0] as *const _ as *const libc::c_char,
+                        tagname_cstring.as_ptr() as libc::c_int
+                    )
+                }
+            };
+            pooled(id, "createElement")
+        }
+
+        fn create_text_node(&self, text: &str) -> Result<WebElement, Error> {
+            let id = {
+                unsafe {
+                    const JS: &'static [u8] = b"\
+                        var text = document.createTextNode(UTF8ToString($0));\
+                        if (!text) {return -1;}\
+                        var elem = document.createElement('span');\
+                        elem.appendChild(text);\
+                        var index = __domafic_pool_free.pop();\
+                        if (index !== undefined) { __domafic_pool[index] = elem; return index; }\
+                        if (__domafic_pool.length >= 2147483647) { return -2; }\
+                        return __domafic_pool.push(elem) - 1;\
+                    \0";
+                    let text_cstring = CString::new(text).unwrap();
+                    emscripten_asm_const_int(
+                        &JS[0] as *const _ as *const libc::c_char,
+                        text_cstring.as_ptr() as libc::c_int
+                    )
+                }
+            };
+            pooled(id, "createTextNode")
+        }
+
+        fn create_comment(&self, text: &str) -> Result<WebElement, Error> {
+            let id = {
+                unsafe {
+                    const JS: &'static [u8] = b"\
+                        var comment = document.createComment(UTF8ToString($0));\
+                        if (!comment) {return -1;}\
+                        var index = __domafic_pool_free.pop();\
+                        if (index !== undefined) { __domafic_pool[index] = comment; return index; }\
+                        if (__domafic_pool.length >= 2147483647) { return -2; }\
+                        return __domafic_pool.push(comment) - 1;\
+                    \0";
+                    let text_cstring = CString::new(text).unwrap();
+                    emscripten_asm_const_int(
+                        &JS[0] as *const _ as *const libc::c_char,
+                        text_cstring.as_ptr() as libc::c_int
+                    )
+                }
+            };
+            pooled(id, "createComment")
+        }
+
+        /// Creates the elements of a `StaticNode` by cloning a template of its HTML, which is
+        /// parsed the first time the node is created
+        fn create_from_template(&self, precompiled: &Precompiled) -> Result<WebElement, Error> {
+            let id = {
+                unsafe {
+                    const JS: &'static [u8] = b"\
+                        if ('undefined' === typeof __domafic_templates) {\
+                            __domafic_templates = new Map();\
+                        }\
+                        var key = $1 + ':' + $2;\
+                        var template = __domafic_templates.get(key);\
+                        if (!template) {\
+                            template = document.createElement('template');\
+                            var html = UTF8ToString($0);\
+                            template.innerHTML = __domafic_tt_policy ?\
+                                __domafic_tt_policy.createHTML(html) : html;\
+                            __domafic_templates.set(key, template);\
+                        }\
+                        var elem = template.content.firstChild.cloneNode(true);\
+                        if (elem.nodeType !== Node.ELEMENT_NODE) {\
+                            var span = document.createElement('span');\
+                            span.appendChild(elem);\
+                            elem = span;\
+                        }\
+                        var index = __domafic_pool_free.pop();\
+                        if (index !== undefined) { __domafic_pool[index] = elem; return index; }\
+                        if (__domafic_pool.length >= 2147483647) { return -2; }\
+                        return __domafic_pool.push(elem) - 1;\
+                    \0";
+                    let html_cstring = CString::new(precompiled.html()).unwrap();
+                    emscripten_asm_const_int(
+                        &JS[0] as *const _ as *const libc::c_char,
+                        html_cstring.as_ptr() as libc::c_int,
+                        (precompiled.hash() >> 32) as libc::c_int,
+                        precompiled.hash() as libc::c_int,
+                    )
+                }
+            };
+            pooled(id, "cloneNode")
+        }
+    }
+
+    /// The pooled element at index `id`, or the error a JavaScript snippet which pools a new
+    /// object reported: -1 if `operation` failed, or -2 if the pool is full
+    fn pooled(id: libc::c_int, operation: &'static str) -> Result<WebElement, Error> {
+        match id {
+            -2 => Err(Error::PoolExhausted),
+            id if id < 0 => Err(Error::Ffi(operation)),
+            id => Ok(WebElement(id)),
+        }
+    }
+
+    unsafe extern fn handle_listener<D, M, U, R, S>(
+        handler_ptr: *const ListenerHandler<M>,
+        app: AppId,
+
+        type_str_ptr: *const libc::c_char,
+        target_value_ptr: *const libc::c_char,
+        client_x: libc::c_int,
+        client_y: libc::c_int,
+        offset_x: libc::c_int,
+        offset_y: libc::c_int,
+        which_keycode: libc::c_int,
+        shift_key: libc::c_int,
+        alt_key: libc::c_int,
+        ctrl_key: libc::c_int,
+        meta_key: libc::c_int,
+        detail_ptr: *const libc::c_char,
+
+        keys_size: libc::c_uint,
+        key_1: libc::c_uint,
+        key_2: libc::c_uint,
+        key_3: libc::c_uint,
+        key_4: libc::c_uint,
+        key_5: libc::c_uint,
+        key_6: libc::c_uint,
+        key_7: libc::c_uint,
+        key_8: libc::c_uint,
+        key_9: libc::c_uint,
+        key_10: libc::c_uint,
+        key_11: libc::c_uint,
+        key_12: libc::c_uint,
+        key_13: libc::c_uint,
+        key_14: libc::c_uint,
+        key_15: libc::c_uint,
+        key_16: libc::c_uint,
+        key_17: libc::c_uint,
+        key_18: libc::c_uint,
+        key_19: libc::c_uint,
+        key_20: libc::c_uint,
+        key_21: libc::c_uint,
+        key_22: libc::c_uint,
+        key_23: libc::c_uint,
+        key_24: libc::c_uint,
+        key_25: libc::c_uint,
+        key_26: libc::c_uint,
+        key_27: libc::c_uint,
+        key_28: libc::c_uint,
+        key_29: libc::c_uint,
+        key_30: libc::c_uint,
+        key_31: libc::c_uint,
+        key_32: libc::c_uint,
+    )
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+
+    {
+        let system = match mounted_system::<D, M, U, R, S>(app) {
+            Some(system) => system,
+            None => return,
+        };
+
+        let type_str = if (type_str_ptr as usize) != 0 {
+            str::from_utf8(CStr::from_ptr(type_str_ptr).to_bytes()).ok()
+        } else {
+            None
+        };
+        let target_value = if (target_value_ptr as usize) != 0 {
+            str::from_utf8(CStr::from_ptr(target_value_ptr).to_bytes()).ok()
+        } else {
+            None
+        };
+        let detail = if (detail_ptr as usize) != 0 {
+            str::from_utf8(CStr::from_ptr(detail_ptr).to_bytes()).ok()
+        } else {
+            None
+        };
+        let event = Event {
+            type_str: type_str,
+            target_value: target_value,
+            client_x: client_x as i32,
+            client_y: client_y as i32,
+            offset_x: offset_x as i32,
+            offset_y: offset_y as i32,
+            which_keycode: which_keycode as i32,
+            shift_key: shift_key == 1,
+            alt_key: alt_key == 1,
+            ctrl_key: ctrl_key == 1,
+            meta_key: meta_key == 1,
+            detail: detail,
+        };
+
+        let keys = Keys {
+            size: keys_size,
+            stack: [
+                key_1,
+                key_2,
+                key_3,
+                key_4,
+                key_5,
+                key_6,
+                key_7,
+                key_8,
+                key_9,
+                key_10,
+                key_11,
+                key_12,
+                key_13,
+                key_14,
+                key_15,
+                key_16,
+                key_17,
+                key_18,
+                key_19,
+                key_20,
+                key_21,
+                key_22,
+                key_23,
+                key_24,
+                key_25,
+                key_26,
+                key_27,
+                key_28,
+                key_29,
+                key_30,
+                key_31,
+                key_32,
+            ]
+        };
+
+        // The listener belongs to the rendered node, so it's only called while the system can
+        // be borrowed. Events fired during an update or render are queued in JavaScript until
+        // the render has finished, as described in `defer_events`.
+        let message = match system.try_borrow() {
+            Ok(_) => ((*handler_ptr).0)(event),
+            Err(_) => return,
+        };
+        update_system::<D, M, U, R, S>(app, message, keys);
     }
 
-    unsafe extern fn handle_http_result<D, M, U, R, S>
+    unsafe fn update_system<D, M, U, R, S>
     (
-        error_sig: libc::c_int,
-        system_c_ptr: *mut libc::c_void,
-        handler_data_ptr: *const libc::c_void,
-        handler_vtable_ptr: *const libc::c_void,
-        status_code: u16,
-        status_text: *const libc::c_char,
-        body: *const libc::c_char,
-        headers_ptr: *const libc::c_char
+        app: AppId,
+        message: M,
+        keys: Keys
     )
         where
-        D: DomNode<M>,
+        D: DomNode<M> + 'static,
         M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M, Rendered=D>,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
     {
-        let handler_ptr: *mut HttpResponseHandler<Message=M> =
-            mem::transmute((handler_data_ptr, handler_vtable_ptr));
-        let handler = Box::from_raw(handler_ptr);
+        let system = match mounted_system::<D, M, U, R, S>(app) {
+            Some(system) => system,
+            // The application was unmounted before the message arrived
+            None => return,
+        };
+        {
+            let mut system = match system.try_borrow_mut() {
+                Ok(system) => system,
+                Err(_) => {
+                    // A message sent while the application is updating or rendering, such as by
+                    // an event which the update fired synchronously, is delivered once the
+                    // update has been rendered
+                    let deliver: unsafe fn(AppId, M, Keys) = update_system::<D, M, U, R, S>;
+                    queue_for_app(app, Box::new(move || deliver(app, message, keys)));
+                    return;
+                },
+            };
+            let system = &mut *system;
+            defer_events(app);
 
-        let status_text = str::from_utf8(CStr::from_ptr(status_text).to_bytes()).unwrap();
+            #[cfg(debug_assertions)]
+            record_message::<M>(keys);
 
-        let headers;
-        let response_result = match error_sig {
-            0 => {
-                let headers_str = str::from_utf8(CStr::from_ptr(headers_ptr).to_bytes()).unwrap();
-                headers = headers_str.split("\r\n").flat_map(|header| {
-                    header.find(':').map(|split_index| {
-                        let (key, value) = header.split_at(split_index);
-                        (key.trim(), value[1..].trim())
-                    })
-                }).collect::<Vec<_>>();
+            // Update state
+            let io = JsIoImpl::<D, M, U, R, S> {
+                app,
+                rendered: &system.rendered,
+                vdom_roots: &system.vdom_roots,
+                system: PhantomData,
+            };
+            system.updater.update(&mut system.state, message, keys.into_iter(), &io);
 
-                Ok(HttpResponse {
-                    status_code: status_code,
-                    status_text: status_text,
-                    headers: &headers,
-                    body: str::from_utf8(CStr::from_ptr(body).to_bytes()).unwrap(),
-                })
-            },
+            // Render new DomNode, replacing the last one in place
+            let render_started = perf_timestamp();
+            system.rendered = context::provide(in_flight(app), || system.renderer.render(&system.state));
+            let patch_started = perf_timestamp();
 
-            1 => Err(HttpError::Timeout),
+            // Write new DomNode to root element. There's no caller to return an error to, so it's
+            // reported like a panic in the update or render.
+            if let Err(err) = write_rendered(system, WebDocument(())) {
+                panic!("Failed to patch the page: {}", err);
+            }
+            mount_widgets();
+            if let (Some(render_started), Some(patch_started)) = (render_started, patch_started) {
+                record_perf(patch_started - render_started, emscripten_get_now() - patch_started);
+            }
 
-            2 => Err(HttpError::NetworkError),
+            #[cfg(debug_assertions)]
+            {
+                publish_to_devtools(&system.rendered);
+                warn_duplicate_ids(&system.rendered);
+            }
 
-            _ => unreachable!(),
+            inject_scoped_css();
+            watch_prefetch_links();
+            sync_focus_traps();
+            sync_interaction_classes();
+            sync_sentinels();
+            sync_subscriptions(system);
+        }
+        end_render(app);
+    }
+
+    /// Patches the roots of the application to match its rendered node
+    unsafe fn write_rendered<D, M, U, R, S>(system: &mut AppSystem<D, M, U, R, S>, document: WebDocument)
+        -> Result<(), Error>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let app = system.id;
+        let rendered = &system.rendered;
+        let vdom_roots = &mut system.vdom_roots;
+        if system.regions {
+            let mut input = RegionWriterAcc { app, document, roots: vdom_roots, index: 0 };
+            rendered.children().process_all::<RegionWriter<D, M, U, R, S>>(&mut input)
+        } else {
+            let vdom_root = &mut vdom_roots[0];
+            let mut node_index = 0;
+            let mut input = WebWriterAcc {
+                app: app,
+                document: document,
+                keys: Keys::new(),
+                parent_element: &vdom_root.web_element,
+                node_level: &mut vdom_root.children,
+                node_index: &mut node_index,
+                listeners_only: false,
+                #[cfg(debug_assertions)]
+                path: String::new(),
+            };
+            rendered.process_all::<WebWriter<D, M, U, R, S>>(&mut input)
+        }
+    }
+
+    /// Adopts the server-rendered contents of the application's root as the elements of its
+    /// rendered node, failing with `Error::HydrationMismatch` if they don't match
+    unsafe fn hydrate_rendered<D, M, U, R, S>(system: &mut AppSystem<D, M, U, R, S>) -> Result<(), Error>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let rendered = &system.rendered;
+        let vdom_root = &mut system.vdom_roots[0];
+        let mut next_id = 0;
+        {
+            let mut input = HydrationAcc {
+                app: system.id,
+                keys: Keys::new(),
+                parent_element: &vdom_root.web_element,
+                node_level: &mut vdom_root.children,
+                next_id: &mut next_id,
+                #[cfg(debug_assertions)]
+                path: String::new(),
+            };
+            rendered.process_all::<Hydrator<D, M, U, R, S>>(&mut input)?;
+        }
+        if vdom_root.web_element.child_count() != vdom_root.children.len() {
+            return Err(Error::HydrationMismatch("end of the application".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Warns that the page being hydrated didn't match the rendered node, so it was rebuilt
+    #[cfg(debug_assertions)]
+    fn warn_hydration_mismatch(node: &str) {
+        const JS: &'static [u8] = b"\
+            console.warn('domafic: the server-rendered page was replaced, since it has no ' +\
+                UTF8ToString($0) + ' where the application rendered one. The page should be ' +\
+                'written by html_writer::write_hydratable from the same initial state.');\
+        \0";
+        let node_cstring = CString::new(node).unwrap();
+        unsafe {
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                node_cstring.as_ptr() as libc::c_int,
+            );
+        }
+    }
+
+    /// Where `perf_monitor` reports the timing statistics of updates
+    #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+    pub enum PerfDisplay {
+        /// An overlay in the corner of the page, refreshed after every update, which turns
+        /// red when an update drops frames
+        Overlay,
+        /// The browser console, which is warned whenever an update drops frames
+        Console,
+    }
+
+    thread_local! {
+        static PERF_MONITOR: RefCell<Option<(PerfMonitor, PerfDisplay)>> = RefCell::new(None);
+    }
+
+    /// The path and query string of the page's URL, such as `/posts/1?sort=new`
+    pub fn location_path() -> String {
+        // First find the encoded length of the path, then copy it into a Rust buffer
+        let len = unsafe {
+            const JS: &'static [u8] = b"\
+                return lengthBytesUTF8(window.location.pathname + window.location.search);\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char)
         };
 
-        let message = handler.handle(response_result);
+        let mut buffer: Vec<u8> = vec![0; len as usize + 1];
+        unsafe {
+            const JS: &'static [u8] = b"\
+                stringToUTF8(window.location.pathname + window.location.search, $0, $1);\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                buffer.as_mut_ptr() as libc::c_int,
+                buffer.len() as libc::c_int,
+            );
+        }
+        buffer.pop(); // Remove the nul terminator
+        String::from_utf8(buffer).unwrap()
+    }
 
-        update_system::<D, M, U, R, S>(system_c_ptr, message, Keys::new());
+    /// The text of the element with the id `id`, such as data embedded in a server-rendered
+    /// page as a `<script type="application/json">`, or `None` if there is no such element
+    pub fn element_text(id: &str) -> Option<String> {
+        let id_cstring = CString::new(id).unwrap();
+        // First find the encoded length of the text, then copy it into a Rust buffer
+        let len = unsafe {
+            const JS: &'static [u8] = b"\
+                var element = document.getElementById(UTF8ToString($0));\
+                return element ? lengthBytesUTF8(element.textContent) : -1;\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char, id_cstring.as_ptr())
+        };
+        if len < 0 {
+            return None;
+        }
+
+        let mut buffer: Vec<u8> = vec![0; len as usize + 1];
+        unsafe {
+            const JS: &'static [u8] = b"\
+                stringToUTF8(document.getElementById(UTF8ToString($0)).textContent, $1, $2);\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                id_cstring.as_ptr(),
+                buffer.as_mut_ptr() as libc::c_int,
+                buffer.len() as libc::c_int,
+            );
+        }
+        buffer.pop(); // Remove the nul terminator
+        Some(String::from_utf8(buffer).unwrap())
     }
 
-    extern "C" {
-        fn emscripten_asm_const_int(s: *const libc::c_char, ...) -> libc::c_int;
-        fn emscripten_pause_main_loop();
-        fn emscripten_set_main_loop(m: extern fn(), fps: libc::c_int, infinite: libc::c_int);
+    /// Whether the browser reports having a network connection, for initializing state which
+    /// `subscriptions::on_online` and `on_offline` keep up to date
+    pub fn is_online() -> bool {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                return navigator.onLine === false ? 0 : 1;\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char) != 0
+        }
     }
 
-    type JsElementId = libc::c_int;
+    /// Replaces the page's URL with `path`, without loading the page or adding an entry to the
+    /// history
+    pub fn replace_location(path: &str) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                window.history.replaceState(window.history.state, '', UTF8ToString($0));\
+            \0";
+            let path_cstring = CString::new(path).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                path_cstring.as_ptr() as libc::c_int,
+            );
+        }
+    }
 
-    #[derive(Debug)]
-    struct WebElement(JsElementId);
+    /// Sets the title of the page, leaving `document.title` alone if it's already `title`
+    pub fn set_title(title: &str) {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var title = UTF8ToString($0);\
+                if (document.title !== title) { document.title = title; }\
+            \0";
+            let title_cstring = CString::new(title).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                title_cstring.as_ptr() as libc::c_int,
+            );
+        }
+    }
 
-    #[derive(Debug, Copy, Clone)]
-    struct WebDocument(()); // Contains private () so that it can't be created externally
+    /// Times how long every update takes to render and to patch the DOM, reporting the
+    /// statistics to `display`, or stops timing updates and removes the overlay if `display`
+    /// is `None`.
+    ///
+    /// This may be called at any time, such as from an update handling a keyboard shortcut.
+    /// The statistics restart whenever the monitor is switched on.
+    pub fn perf_monitor(display: Option<PerfDisplay>) {
+        PERF_MONITOR.with(|monitor| *monitor.borrow_mut() = display.map(|display| (PerfMonitor::new(), display)));
+        if display != Some(PerfDisplay::Overlay) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var overlay = document.getElementById('__domafic_perf');\
+                    if (overlay) { overlay.parentNode.removeChild(overlay); }\
+                \0";
+                emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+            }
+        }
+    }
 
-    fn web_init() -> WebDocument {
+    /// The current time in milliseconds, if updates are being timed
+    fn perf_timestamp() -> Option<f64> {
+        let timing = PERF_MONITOR.with(|monitor| monitor.borrow().is_some());
+        if timing { Some(unsafe { emscripten_get_now() }) } else { None }
+    }
+
+    /// Records the timing of an update and reports the statistics
+    fn record_perf(render_millis: f64, patch_millis: f64) {
+        let report = PERF_MONITOR.with(|monitor| {
+            monitor.borrow_mut().as_mut().map(|&mut (ref mut monitor, display)| {
+                let dropped = monitor.record(render_millis, patch_millis);
+                (display, dropped, monitor.stats())
+            })
+        });
+        let (display, dropped, stats) = match report {
+            Some(report) => report,
+            None => return,
+        };
+        if display == PerfDisplay::Console && dropped == 0 {
+            return;
+        }
+
+        let stats_cstring = CString::new(stats.to_string()).unwrap();
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var text = UTF8ToString($1);\
+                if (!$0) {\
+                    console.warn('domafic: an update dropped ' + $2 + ' frames (' + text + ')');\
+                    return;\
+                }\
+                var overlay = document.getElementById('__domafic_perf');\
+                if (!overlay) {\
+                    overlay = document.createElement('div');\
+                    overlay.id = '__domafic_perf';\
+                    overlay.setAttribute('style', 'position:fixed;right:0;bottom:0;z-index:2147483646;\
+                        padding:0.3em 0.6em;color:#fff;font:12px monospace;pointer-events:none;');\
+                    document.body.appendChild(overlay);\
+                }\
+                overlay.style.background = $2 ? 'rgba(160,0,0,0.85)' : 'rgba(0,0,0,0.7)';\
+                overlay.textContent = text;\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                (display == PerfDisplay::Overlay) as libc::c_int,
+                stats_cstring.as_ptr() as libc::c_int,
+                dropped as libc::c_int,
+            );
+        }
+    }
+
+    /// Number of delivered messages kept for devtools extensions
+    #[cfg(debug_assertions)]
+    const DEVTOOLS_MESSAGES: usize = 50;
+
+    #[cfg(debug_assertions)]
+    thread_local! {
+        static DEVTOOLS_LOG: ::std::cell::RefCell<(u64, MessageRing<MessageRecord>)> =
+            ::std::cell::RefCell::new((0, MessageRing::new(DEVTOOLS_MESSAGES)));
+    }
+
+    /// Records a message about to be delivered to the updater for devtools extensions
+    #[cfg(debug_assertions)]
+    fn record_message<M>(keys: Keys) {
+        DEVTOOLS_LOG.with(|log| {
+            let mut log = log.borrow_mut();
+            let sequence = log.0;
+            log.0 += 1;
+            log.1.push(MessageRecord {
+                sequence,
+                message_type: ::std::any::type_name::<M>(),
+                keys: keys.into_iter().map(|key| key as u32).collect(),
+            });
+        });
+    }
+
+    /// Sends a description of the rendered application to a devtools extension, if one has
+    /// installed a hook, as described in the `devtools` module
+    #[cfg(debug_assertions)]
+    fn publish_to_devtools<M, D: DomNode<M>>(rendered: &D) {
+        unsafe {
+            const HAS_HOOK: &'static [u8] = b"\
+                return ('undefined' !== typeof __DOMAFIC_DEVTOOLS_HOOK__) ? 1 : 0;\
+            \0";
+            if emscripten_asm_const_int(&HAS_HOOK[0] as *const _ as *const libc::c_char) == 0 {
+                return;
+            }
+
+            let json = DEVTOOLS_LOG.with(|log| devtools::to_json(&NodeSnapshot::of(rendered), &log.borrow().1));
+            const JS: &'static [u8] = b"\
+                var payload = UTF8ToString($0);\
+                if (__DOMAFIC_DEVTOOLS_HOOK__.update) { __DOMAFIC_DEVTOOLS_HOOK__.update(payload); }\
+                window.postMessage({ source: 'domafic-devtools', payload: payload }, '*');\
+            \0";
+            let json_cstring = CString::new(json).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                json_cstring.as_ptr() as libc::c_int,
+            );
+        }
+    }
+
+    /// Warns in the console about each id given to more than one element of `rendered`, as
+    /// described in the `ids` module, along with both elements
+    #[cfg(debug_assertions)]
+    fn warn_duplicate_ids<M, D: DomNode<M>>(rendered: &D) {
         const JS: &'static [u8] = b"\
-            if('undefined'===typeof __domafic_pool){\
-                console.log('Intializing __domafic_pool');\
-                __domafic_pool=[];\
-                __domafic_pool_free=[];\
-            }\
+            var element = function(path) {\
+                return document.querySelector('[data-domafic-path=\\'' + UTF8ToString(path) + '\\']');\
+            };\
+            console.warn('domafic: ' + UTF8ToString($0) + '. Labels, links and getElementById ' +\
+                'only find the first element with an id.', element($1), element($2));\
         \0";
+        for duplicate in ids::check(rendered) {
+            let join = |path: &[usize]| {
+                let path: Vec<String> = path.iter().map(|index| index.to_string()).collect();
+                CString::new(path.join(".")).unwrap()
+            };
+            let description_cstring = CString::new(duplicate.to_string()).unwrap();
+            let first_cstring = join(&duplicate.first);
+            let second_cstring = join(&duplicate.second);
+            unsafe {
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    description_cstring.as_ptr() as libc::c_int,
+                    first_cstring.as_ptr() as libc::c_int,
+                    second_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+    }
+
+    /// Prefetches the data of `router::prefetch_link`s into the HTTP cache used by
+    /// `JsIo::http_cached`: on hover or focus through listeners on the document, which are
+    /// added once, and on scrolling into view through an `IntersectionObserver` watching the
+    /// links rendered since the last call
+    fn watch_prefetch_links() {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var max_age = $0;\
+                if ('undefined' === typeof __domafic_prefetched) {\
+                    __domafic_prefetched = {};\
+                    __domafic_prefetch = function(link) {\
+                        var url = link.getAttribute('data-domafic-prefetch');\
+                        if (!url) { return; }\
+                        if ('undefined' === typeof __domafic_http_cache) {\
+                            __domafic_http_cache = {};\
+                            __domafic_http_inflight = {};\
+                        }\
+                        var key = 'GET ' + url;\
+                        var now = Date.now();\
+                        var cached = __domafic_http_cache[key];\
+                        if ((now - (__domafic_prefetched[url] || -Infinity)) < max_age ||\
+                            (cached && (now - cached.time) < max_age) || __domafic_http_inflight[key]) {\
+                            return;\
+                        }\
+                        delete __domafic_prefetched[url];\
+                        __domafic_prefetched[url] = now;\
+                        var urls = Object.keys(__domafic_prefetched);\
+                        if (urls.length > 64) { delete __domafic_prefetched[urls[0]]; }\
+                        var xhr = new XMLHttpRequest();\
+                        xhr.addEventListener('load', function() {\
+                            var cached = __domafic_http_cache[key];\
+                            if (xhr.status >= 200 && xhr.status < 300 && !(cached && cached.time > now)) {\
+                                __domafic_http_cache[key] = { time: Date.now(), response: {\
+                                    sig: 0,\
+                                    status: xhr.status,\
+                                    status_text: xhr.statusText,\
+                                    body: xhr.responseText,\
+                                    headers: xhr.getAllResponseHeaders()\
+                                } };\
+                            }\
+                        });\
+                        try { xhr.open('GET', url, true); } catch (e) { return; }\
+                        xhr.responseType = 'text';\
+                        xhr.send();\
+                    };\
+                    var on_hover = function(event) {\
+                        var link = event.target && event.target.closest &&\
+                            event.target.closest('[data-domafic-prefetch-on=hover]');\
+                        if (link) { __domafic_prefetch(link); }\
+                    };\
+                    document.addEventListener('mouseover', on_hover, true);\
+                    document.addEventListener('focusin', on_hover, true);\
+                    if ('undefined' !== typeof IntersectionObserver) {\
+                        __domafic_prefetch_observer = new IntersectionObserver(function(entries, observer) {\
+                            for (var i = 0; i < entries.length; i++) {\
+                                if (entries[i].isIntersecting) {\
+                                    observer.unobserve(entries[i].target);\
+                                    __domafic_prefetch(entries[i].target);\
+                                }\
+                            }\
+                        });\
+                    }\
+                }\
+                if ('undefined' === typeof __domafic_prefetch_observer) { return; }\
+                var links = document.querySelectorAll('[data-domafic-prefetch-on=visible]');\
+                for (var i = 0; i < links.length; i++) {\
+                    if (!links[i].__domafic_prefetch_observed) {\
+                        links[i].__domafic_prefetch_observed = true;\
+                        __domafic_prefetch_observer.observe(links[i]);\
+                    }\
+                }\
+            \0";
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                PREFETCH_MAX_AGE_MILLIS as libc::c_int,
+            );
+        }
+    }
+
+    /// Starts confining focus to the `focus::focus_trap`s added since the last render, and
+    /// returns focus from the ones which were removed to the elements focused before them.
+    ///
+    /// Tab presses and focus changes are handled by listeners on the document, which are added
+    /// once and only act on the innermost trap.
+    fn sync_focus_traps() {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                if ('undefined' === typeof __domafic_focus_traps) {\
+                    __domafic_focus_traps = [];\
+                    var focusable = function(trap) {\
+                        var candidates = trap.querySelectorAll('a[href], area[href], button, input, ' +\
+                            'select, textarea, iframe, [tabindex], [contenteditable]');\
+                        return Array.prototype.filter.call(candidates, function(elem) {\
+                            return !elem.disabled && elem.tabIndex >= 0 && elem.getClientRects().length > 0;\
+                        });\
+                    };\
+                    __domafic_focus_into = function(trap) {\
+                        var target = trap.querySelector('[autofocus]') || focusable(trap)[0] || trap;\
+                        target.focus();\
+                    };\
+                    var innermost = function() {\
+                        return __domafic_focus_traps[__domafic_focus_traps.length - 1];\
+                    };\
+                    document.addEventListener('keydown', function(event) {\
+                        var trap = innermost();\
+                        if (!trap || (event.keyCode || event.which) != 9) { return; }\
+                        var elems = focusable(trap.element);\
+                        var active = document.activeElement;\
+                        var outside = !trap.element.contains(active);\
+                        if (elems.length == 0) { event.preventDefault(); trap.element.focus(); return; }\
+                        var first = elems[0];\
+                        var last = elems[elems.length - 1];\
+                        if (event.shiftKey && (outside || active === first || active === trap.element)) {\
+                            event.preventDefault();\
+                            last.focus();\
+                        } else if (!event.shiftKey && (outside || active === last)) {\
+                            event.preventDefault();\
+                            first.focus();\
+                        }\
+                    }, true);\
+                    document.addEventListener('focusin', function(event) {\
+                        var trap = innermost();\
+                        if (trap && !trap.element.contains(event.target)) { __domafic_focus_into(trap.element); }\
+                    }, true);\
+                }\
+                var traps = __domafic_focus_traps;\
+                for (var i = traps.length - 1; i >= 0; i--) {\
+                    if (document.contains(traps[i].element)) { continue; }\
+                    var removed = traps.splice(i, 1)[0];\
+                    if (removed.restore && document.contains(removed.restore)) { removed.restore.focus(); }\
+                }\
+                var elements = document.querySelectorAll('[data-domafic-focus-trap]');\
+                for (var i = 0; i < elements.length; i++) {\
+                    var tracked = traps.some(function(trap) { return trap.element === elements[i]; });\
+                    if (tracked) { continue; }\
+                    var restore = document.activeElement;\
+                    traps.push({ element: elements[i], restore: restore });\
+                    if (!elements[i].contains(restore)) { __domafic_focus_into(elements[i]); }\
+                }\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+        }
+    }
 
+    /// Applies the classes given by `with_hover_class` and `with_focus_within_class` to the
+    /// elements which are hovered or contain focus, including elements which were just added
+    /// or patched
+    fn sync_interaction_classes() {
         unsafe {
+            const JS: &'static [u8] = b"\
+                if ('undefined' === typeof __domafic_sync_interaction) {\
+                    var apply = function(attribute, active) {\
+                        var elements = document.querySelectorAll('[' + attribute + ']');\
+                        for (var i = 0; i < elements.length; i++) {\
+                            var on = active(elements[i]);\
+                            elements[i].getAttribute(attribute).split(/\\s+/).forEach(function(name) {\
+                                if (name) { elements[i].classList.toggle(name, on); }\
+                            });\
+                        }\
+                    };\
+                    __domafic_sync_interaction = function() {\
+                        apply('data-domafic-hover-class', function(elem) { return elem.matches(':hover'); });\
+                        apply('data-domafic-focus-within-class', function(elem) {\
+                            return elem.contains(document.activeElement);\
+                        });\
+                    };\
+                    ['mouseover', 'mouseout', 'focusin', 'focusout'].forEach(function(type) {\
+                        document.addEventListener(type, function() { __domafic_sync_interaction(); }, true);\
+                    });\
+                }\
+                __domafic_sync_interaction();\
+            \0";
             emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
         }
+    }
 
-        WebDocument(())
+    /// Watches the sentinels rendered by `infinite_scroll::infinite_scroll`, dispatching
+    /// `domafic:visible` on those which are armed when they come into view, or when they're
+    /// armed again while they're still in view. Without `IntersectionObserver`, sentinels are
+    /// treated as always being in view.
+    fn sync_sentinels() {
+        unsafe {
+            const JS: &'static [u8] = b"\
+                if ('undefined' === typeof __domafic_sync_sentinels) {\
+                    var sentinels = [];\
+                    var armed = function(elem) {\
+                        return elem.getAttribute('data-domafic-sentinel') === 'true';\
+                    };\
+                    var dispatch = function(elem) {\
+                        if (elem.isConnected && armed(elem)) {\
+                            elem.dispatchEvent(new CustomEvent('domafic:visible'));\
+                        }\
+                    };\
+                    var observer = 'undefined' === typeof IntersectionObserver ? null :\
+                        new IntersectionObserver(function(entries) {\
+                            entries.forEach(function(entry) {\
+                                entry.target.__domafic_visible = entry.isIntersecting;\
+                                if (entry.isIntersecting) { dispatch(entry.target); }\
+                            });\
+                        }, { rootMargin: '200px' });\
+                    __domafic_sync_sentinels = function() {\
+                        sentinels = sentinels.filter(function(elem) {\
+                            if (elem.isConnected) { return true; }\
+                            if (observer) { observer.unobserve(elem); }\
+                            return false;\
+                        });\
+                        var elements = document.querySelectorAll('[data-domafic-sentinel]');\
+                        for (var i = 0; i < elements.length; i++) {\
+                            var elem = elements[i];\
+                            var now_armed = armed(elem);\
+                            if (sentinels.indexOf(elem) < 0) {\
+                                sentinels.push(elem);\
+                                if (observer) {\
+                                    observer.observe(elem);\
+                                } else {\
+                                    elem.__domafic_visible = true;\
+                                    if (now_armed) { setTimeout(dispatch.bind(null, elem), 0); }\
+                                }\
+                            } else if (now_armed && !elem.__domafic_armed && elem.__domafic_visible) {\
+                                setTimeout(dispatch.bind(null, elem), 0);\
+                            }\
+                            elem.__domafic_armed = now_armed;\
+                        }\
+                    };\
+                }\
+                __domafic_sync_sentinels();\
+            \0";
+            emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char);
+        }
     }
 
-    extern fn pause_main_web_loop() {
-        unsafe { emscripten_pause_main_loop(); }
+    /// Appends CSS collected by `css::styled` since the last render to a `<style>` element in
+    /// `document.head`
+    fn inject_scoped_css() {
+        let css = match css::take_uninjected() {
+            Some(css) => css,
+            None => return,
+        };
+        unsafe {
+            const JS: &'static [u8] = b"\
+                var style = document.head.querySelector('style[data-domafic-css]');\
+                if (!style) {\
+                    style = document.createElement('style');\
+                    style.setAttribute('data-domafic-css', '');\
+                    document.head.appendChild(style);\
+                }\
+                style.appendChild(document.createTextNode('\\n' + UTF8ToString($0)));\
+            \0";
+            let css_cstring = CString::new(css).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                css_cstring.as_ptr() as libc::c_int,
+            );
+        }
     }
 
-    fn run_main_web_loop() -> ! {
-        unsafe { emscripten_set_main_loop(pause_main_web_loop, 0, 1); }
-        panic!("Emscripten main loop should never return")
+    /// The application's subscriber and the subscriptions it returned after the latest update
+    struct Subscriptions<S, M> {
+        subscriber: Box<dyn Fn(&S) -> Vec<Subscription<M>>>,
+        active: Vec<Subscription<M>>,
     }
 
-    impl WebDocument {
-        fn element_from_selector(&self, selector: &str) -> Option<WebElement> {
-            let id = {
-                unsafe {
-                    const JS: &'static [u8] = b"\
-                        var elem = document.querySelector(UTF8ToString($0));\
-                        if (!elem) {return -1;}\
-                        var index = __domafic_pool_free.pop();\
-                        if (index) { __domafic_pool[index] = elem; return index; }\
-                        return __domafic_pool.push(elem) - 1;\
-                    \0";
-                    let selector_cstring = CString::new(selector).unwrap();
-                    emscripten_asm_const_int(
-                        &JS[0] as *const _ as *const libc::c_char,
-                        selector_cstring.as_ptr() as libc::c_int
-                    )
+    /// Event types which may be subscribed to, indexed by the values passed to
+    /// `handle_subscription`, and whether they're fired on the window rather than the document
+    const SUBSCRIPTION_EVENT_TYPES: [(&'static str, bool); 14] = [
+        ("visibilitychange", false),
+        ("pagehide", true),
+        ("freeze", false),
+        ("resume", false),
+        ("beforeunload", true),
+        ("fullscreenchange", false),
+        ("pointerlockchange", false),
+        ("deviceorientation", true),
+        ("devicemotion", true),
+        ("popstate", true),
+        ("keydown", true),
+        ("online", true),
+        ("offline", true),
+        ("resize", true),
+    ];
+
+    /// Calls the subscriber with the current state, then adds or removes browser listeners so
+    /// that exactly the returned event types are listened for.
+    unsafe fn sync_subscriptions<D, M, U, R, S>(
+        system: &mut AppSystem<D, M, U, R, S>
+    )
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let app = system.id;
+        let subscriptions = &mut system.subscriptions;
+        subscriptions.active = (subscriptions.subscriber)(&system.state);
+
+        for (index, &(event_type, on_window)) in SUBSCRIPTION_EVENT_TYPES.iter().enumerate() {
+            let mut subscribed = false;
+            let mut prompt = false;
+            for subscription in &subscriptions.active {
+                let source = subscription.source();
+                if source.event_type() == event_type && !source.is_element_event() {
+                    subscribed = true;
+                    if let SubscriptionSource::BeforeUnload { prompt: true } = source {
+                        prompt = true;
+                    }
                 }
-            };
-            if id < 0 { None } else { Some(WebElement(id)) }
+            }
+
+            // `beforeunload` listeners request a confirmation prompt by cancelling the event.
+            // The prompt flag is read after the listener's message has been handled, so it
+            // reflects the latest state. Key presses which produced a message are cancelled,
+            // so that shortcuts replace the browser's default action for their keys.
+            const JS: &'static [u8] = b"\
+                if ('undefined' === typeof __domafic_subscriptions) {\
+                    __domafic_subscriptions = {};\
+                }\
+                var index = $0;\
+                var fn_ptr = $3;\
+                var app_system = $4;\
+                var type = UTF8ToString($5);\
+                var target = $6 ? window : document;\
+                var existing = __domafic_subscriptions[index];\
+                if (!$1) {\
+                    if (existing) {\
+                        existing.prompt = false;\
+                        target.removeEventListener(type, existing.listener);\
+                        delete __domafic_subscriptions[index];\
+                    }\
+                    return;\
+                }\
+                if (existing) { existing.prompt = $2; return; }\
+                var subscription = { prompt: $2 };\
+                subscription.listener = function(event) {\
+                    var num = function(value) {\
+                        return (value === null || value === undefined) ? NaN : value;\
+                    };\
+                    var detail = [];\
+                    if (type == 'visibilitychange') { detail = [document.hidden ? 1 : 0]; }\
+                    if (type == 'pagehide') { detail = [event.persisted ? 1 : 0]; }\
+                    if (type == 'fullscreenchange') { detail = [document.fullscreenElement ? 1 : 0]; }\
+                    if (type == 'pointerlockchange') { detail = [document.pointerLockElement ? 1 : 0]; }\
+                    if (type == 'resize') { detail = [window.innerWidth, window.innerHeight]; }\
+                    if (type == 'deviceorientation') {\
+                        detail = [num(event.alpha), num(event.beta), num(event.gamma),\
+                            event.absolute ? 1 : 0];\
+                    }\
+                    if (type == 'devicemotion') {\
+                        var a = event.acceleration || {};\
+                        var g = event.accelerationIncludingGravity || {};\
+                        var r = event.rotationRate || {};\
+                        detail = [num(a.x), num(a.y), num(a.z), num(g.x), num(g.y), num(g.z),\
+                            num(r.alpha), num(r.beta), num(r.gamma), num(event.interval)];\
+                    }\
+                    if (type == 'keydown') {\
+                        var target = event.target || {};\
+                        var editing = target.isContentEditable ||\
+                            /^(INPUT|TEXTAREA|SELECT)$/.test(target.tagName || '');\
+                        detail = [event.keyCode || event.which || 0, event.shiftKey ? 1 : 0,\
+                            event.altKey ? 1 : 0, event.ctrlKey ? 1 : 0, event.metaKey ? 1 : 0,\
+                            editing ? 1 : 0, event.repeat ? 1 : 0];\
+                    }\
+                    var stack = Runtime.stackSave();\
+                    var detail_ptr = Runtime.stackAlloc(detail.length * 8);\
+                    for (var i = 0; i < detail.length; i++) {\
+                        setValue(detail_ptr + (i * 8), detail[i], 'double');\
+                    }\
+                    var handled = Runtime.dynCall('iiiii', fn_ptr, [app_system, index, detail_ptr, detail.length]);\
+                    Runtime.stackRestore(stack);\
+                    if (type == 'keydown' && handled) { event.preventDefault(); }\
+                    if (type == 'beforeunload' && subscription.prompt) {\
+                        event.preventDefault();\
+                        event.returnValue = '';\
+                        return '';\
+                    }\
+                };\
+                target.addEventListener(type, subscription.listener);\
+                __domafic_subscriptions[index] = subscription;\
+            \0";
+
+            let event_type_cstring = CString::new(event_type).unwrap();
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                index as libc::c_int,
+                subscribed as libc::c_int,
+                prompt as libc::c_int,
+                handle_subscription::<D, M, U, R, S> as *const libc::c_void,
+                app,
+                event_type_cstring.as_ptr() as libc::c_int,
+                on_window as libc::c_int,
+            );
         }
 
-        fn create_element(&self, tagname: &str) -> Option<WebElement> {
-            let id = {
-                unsafe {
-                    const JS: &'static [u8] = b"\
-                        var elem = document.createElement(UTF8ToString($0));\
-                        if (!elem) {return -1;}\
-                        var index = __domafic_pool_free.pop();\
-                        if (index) { __domafic_pool[index] = elem; return index; }\
-                        return __domafic_pool.push(elem) - 1;\
-                    \0";
-                    let tagname_cstring = CString::new(tagname).unwrap();
-                    emscripten_asm_const_int(
-                        &JS[0] as *const _ as *const libc::c_char,
-                        tagname_cstring.as_ptr() as libc::c_int
-                    )
-                }
+        // Media query subscriptions are keyed by their query rather than an event type
+        let mut queries: Vec<&str> = Vec::new();
+        for subscription in &subscriptions.active {
+            if let SubscriptionSource::MediaQuery(query) = subscription.source() {
+                if !queries.contains(&query) { queries.push(query); }
+            }
+        }
+
+        // New queries report their initial result once the current update has finished
+        const JS: &'static [u8] = b"\
+            if ('undefined' === typeof __domafic_media_queries) {\
+                __domafic_media_queries = {};\
+            }\
+            var fn_ptr = $1;\
+            var app_system = $2;\
+            var wanted = {};\
+            UTF8ToString($0).split('\\n').forEach(function(query) {\
+                if (query) { wanted[query] = true; }\
+            });\
+            for (var query in __domafic_media_queries) {\
+                if (wanted[query]) { continue; }\
+                var existing = __domafic_media_queries[query];\
+                existing.list.removeListener(existing.listener);\
+                delete __domafic_media_queries[query];\
+            }\
+            Object.keys(wanted).forEach(function(query) {\
+                if (__domafic_media_queries[query]) { return; }\
+                var list = window.matchMedia(query);\
+                var listener = function() {\
+                    if (__domafic_media_queries[query] === undefined) { return; }\
+                    var stack = Runtime.stackSave();\
+                    var query_ptr = allocate(intArrayFromString(query), 'i8', ALLOC_STACK);\
+                    Runtime.dynCall('viii', fn_ptr, [app_system, query_ptr, list.matches ? 1 : 0]);\
+                    Runtime.stackRestore(stack);\
+                };\
+                list.addListener(listener);\
+                __domafic_media_queries[query] = { list: list, listener: listener };\
+                setTimeout(listener, 0);\
+            });\
+        \0";
+
+        let queries_cstring = CString::new(queries.join("\n")).unwrap();
+        emscripten_asm_const_int(
+            &JS[0] as *const _ as *const libc::c_char,
+            queries_cstring.as_ptr() as libc::c_int,
+            handle_media_query::<D, M, U, R, S> as *const libc::c_void,
+            app,
+        );
+
+        // Element events are keyed by the application, since each has its own roots, and by
+        // whether they're on the roots, on the body, or outside of a ref. Clicks outside of a
+        // ref are checked while capturing, before a listener can remove the clicked element,
+        // and only once the click which subscribed has finished.
+        let mut element_events: Vec<String> = Vec::new();
+        for subscription in &subscriptions.active {
+            let key = match subscription.source() {
+                SubscriptionSource::RootEvent(event_type) => format!("root {}", event_type),
+                SubscriptionSource::BodyEvent(event_type) => format!("body {}", event_type),
+                SubscriptionSource::ClickOutside(element) => format!("outside {}", element.name()),
+                _ => continue,
             };
-            if id < 0 { None } else { Some(WebElement(id)) }
+            if !element_events.contains(&key) { element_events.push(key); }
         }
+        let roots: Vec<String> = system.vdom_roots.iter()
+            .map(|root| root.web_element.0.to_string())
+            .collect();
 
-        fn create_text_node(&self, text: &str) -> Option<WebElement> {
-            let id = {
-                unsafe {
-                    const JS: &'static [u8] = b"\
-                        var text = document.createTextNode(UTF8ToString($0));\
-                        if (!text) {return -1;}\
-                        var elem = document.createElement('span');\
-                        elem.appendChild(text);\
-                        var index = __domafic_pool_free.pop();\
-                        if (index) { __domafic_pool[index] = elem; return index; }\
-                        return __domafic_pool.push(elem) - 1;\
-                    \0";
-                    let text_cstring = CString::new(text).unwrap();
-                    emscripten_asm_const_int(
-                        &JS[0] as *const _ as *const libc::c_char,
-                        text_cstring.as_ptr() as libc::c_int
-                    )
-                }
+        const ELEMENT_JS: &'static [u8] = b"\
+            if ('undefined' === typeof __domafic_element_events) {\
+                __domafic_element_events = {};\
+            }\
+            var fn_ptr = $2;\
+            var app_system = $3;\
+            var roots = UTF8ToString($1).split(',').map(function(id) {\
+                return __domafic_pool[parseInt(id, 10)];\
+            });\
+            var active = __domafic_element_events[app_system] || {};\
+            var wanted = {};\
+            UTF8ToString($0).split('\\n').forEach(function(key) {\
+                if (key) { wanted[key] = true; }\
+            });\
+            for (var key in active) {\
+                if (wanted[key]) { continue; }\
+                active[key].targets.forEach(function(target) {\
+                    target.removeEventListener(active[key].type, active[key].listener, active[key].capture);\
+                });\
+                delete active[key];\
+            }\
+            Object.keys(wanted).forEach(function(key) {\
+                if (active[key]) { return; }\
+                var kind = key.slice(0, key.indexOf(' '));\
+                var name = key.slice(kind.length + 1);\
+                var outside = kind === 'outside';\
+                var type = outside ? 'click' : name;\
+                var targets = outside ? [] : kind === 'root' ? roots : [document.body];\
+                var listener = function(event) {\
+                    if (outside) {\
+                        var elem = __domafic_find_ref(name);\
+                        if (!elem || elem.contains(event.target)) { return; }\
+                    }\
+                    var inside = roots.some(function(root) { return root.contains(event.target); });\
+                    var stack = Runtime.stackSave();\
+                    var key_ptr = allocate(intArrayFromString(key), 'i8', ALLOC_STACK);\
+                    Runtime.dynCall('viiii', fn_ptr, [app_system, key_ptr,\
+                        event.target === event.currentTarget ? 1 : 0, inside ? 1 : 0]);\
+                    Runtime.stackRestore(stack);\
+                };\
+                var entry = { type: type, targets: targets, listener: listener, capture: outside };\
+                targets.forEach(function(target) { target.addEventListener(type, listener); });\
+                if (outside) {\
+                    setTimeout(function() {\
+                        if (active[key] !== entry) { return; }\
+                        entry.targets = [document];\
+                        document.addEventListener('click', listener, true);\
+                    }, 0);\
+                }\
+                active[key] = entry;\
+            });\
+            __domafic_element_events[app_system] = active;\
+        \0";
+
+        let element_events_cstring = CString::new(element_events.join("\n")).unwrap();
+        let roots_cstring = CString::new(roots.join(",")).unwrap();
+        emscripten_asm_const_int(
+            &ELEMENT_JS[0] as *const _ as *const libc::c_char,
+            element_events_cstring.as_ptr() as libc::c_int,
+            roots_cstring.as_ptr() as libc::c_int,
+            handle_element_event::<D, M, U, R, S> as *const libc::c_void,
+            app,
+        );
+
+        // Timers are keyed by the application, and by their interval or `frame` for animation
+        // frames. Frames measure the time since the previous frame, and the next one is only
+        // requested once the current one's message has been handled, while it's still wanted.
+        let mut timers: Vec<String> = Vec::new();
+        for subscription in &subscriptions.active {
+            let key = match subscription.source() {
+                SubscriptionSource::Interval(millis) => format!("interval {}", millis),
+                SubscriptionSource::AnimationFrame => "frame".to_string(),
+                _ => continue,
             };
-            if id < 0 { None } else { Some(WebElement(id)) }
+            if !timers.contains(&key) { timers.push(key); }
         }
-    }
 
-    unsafe extern fn handle_listener<D, M, U, R, S>(
-        listener_data_c_ptr: *const libc::c_void,
-        listener_vtable_c_ptr: *const libc::c_void,
-        system_c_ptr: *mut libc::c_void,
+        const TIMER_JS: &'static [u8] = b"\
+            if ('undefined' === typeof __domafic_timers) {\
+                __domafic_timers = {};\
+            }\
+            var fn_ptr = $1;\
+            var app_system = $2;\
+            var active = __domafic_timers[app_system] || {};\
+            var wanted = {};\
+            UTF8ToString($0).split('\\n').forEach(function(key) {\
+                if (key) { wanted[key] = true; }\
+            });\
+            for (var key in active) {\
+                if (wanted[key]) { continue; }\
+                if (active[key].interval) { clearInterval(active[key].interval); }\
+                if (active[key].frame) { cancelAnimationFrame(active[key].frame); }\
+                delete active[key];\
+            }\
+            var fire = function(key, value) {\
+                var stack = Runtime.stackSave();\
+                var key_ptr = allocate(intArrayFromString(key), 'i8', ALLOC_STACK);\
+                var value_ptr = Runtime.stackAlloc(8);\
+                setValue(value_ptr, value, 'double');\
+                Runtime.dynCall('viii', fn_ptr, [app_system, key_ptr, value_ptr]);\
+                Runtime.stackRestore(stack);\
+            };\
+            Object.keys(wanted).forEach(function(key) {\
+                if (active[key]) { return; }\
+                var entry = {};\
+                if (key === 'frame') {\
+                    var last = performance.now();\
+                    var frame = function(now) {\
+                        var elapsed = Math.max(0, now - last);\
+                        last = now;\
+                        fire(key, elapsed);\
+                        if (active[key] === entry) { entry.frame = requestAnimationFrame(frame); }\
+                    };\
+                    entry.frame = requestAnimationFrame(frame);\
+                } else {\
+                    var millis = parseInt(key.slice(key.indexOf(' ') + 1), 10);\
+                    entry.interval = setInterval(function() { fire(key, Date.now()); }, millis);\
+                }\
+                active[key] = entry;\
+            });\
+            __domafic_timers[app_system] = active;\
+        \0";
 
-        type_str_ptr: *const libc::c_char,
-        target_value_ptr: *const libc::c_char,
-        client_x: libc::c_int,
-        client_y: libc::c_int,
-        offset_x: libc::c_int,
-        offset_y: libc::c_int,
-        which_keycode: libc::c_int,
-        shift_key: libc::c_int,
-        alt_key: libc::c_int,
-        ctrl_key: libc::c_int,
-        meta_key: libc::c_int,
+        let timers_cstring = CString::new(timers.join("\n")).unwrap();
+        emscripten_asm_const_int(
+            &TIMER_JS[0] as *const _ as *const libc::c_char,
+            timers_cstring.as_ptr() as libc::c_int,
+            handle_timer::<D, M, U, R, S> as *const libc::c_void,
+            app,
+        );
+    }
 
-        keys_size: libc::c_uint,
-        key_1: libc::c_uint,
-        key_2: libc::c_uint,
-        key_3: libc::c_uint,
-        key_4: libc::c_uint,
-        key_5: libc::c_uint,
-        key_6: libc::c_uint,
-        key_7: libc::c_uint,
-        key_8: libc::c_uint,
-        key_9: libc::c_uint,
-        key_10: libc::c_uint,
-        key_11: libc::c_uint,
-        key_12: libc::c_uint,
-        key_13: libc::c_uint,
-        key_14: libc::c_uint,
-        key_15: libc::c_uint,
-        key_16: libc::c_uint,
-        key_17: libc::c_uint,
-        key_18: libc::c_uint,
-        key_19: libc::c_uint,
-        key_20: libc::c_uint,
-        key_21: libc::c_uint,
-        key_22: libc::c_uint,
-        key_23: libc::c_uint,
-        key_24: libc::c_uint,
-        key_25: libc::c_uint,
-        key_26: libc::c_uint,
-        key_27: libc::c_uint,
-        key_28: libc::c_uint,
-        key_29: libc::c_uint,
-        key_30: libc::c_uint,
-        key_31: libc::c_uint,
-        key_32: libc::c_uint,
+    unsafe extern "C" fn handle_timer<D, M, U, R, S>(
+        app: AppId,
+        key_ptr: *const libc::c_char,
+        value_ptr: *const f64,
     )
         where
-        (D, U, R, S): Sized,
-        D: DomNode<M>,
+        D: DomNode<M> + 'static,
         M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M, Rendered=D>
-
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
     {
-        let listener_ref: &mut Listener<M> =
-            mem::transmute((listener_data_c_ptr, listener_vtable_c_ptr));
+        let key = CStr::from_ptr(key_ptr).to_string_lossy().into_owned();
+        let detail = [*value_ptr];
 
-        let type_str = if (type_str_ptr as usize) != 0 {
-            str::from_utf8(CStr::from_ptr(type_str_ptr).to_bytes()).ok()
-        } else {
-            None
-        };
-        let target_value = if (target_value_ptr as usize) != 0 {
-            str::from_utf8(CStr::from_ptr(target_value_ptr).to_bytes()).ok()
-        } else {
-            None
-        };
-        let event = Event {
-            type_str: type_str,
-            target_value: target_value,
-            client_x: client_x as i32,
-            client_y: client_y as i32,
-            offset_x: offset_x as i32,
-            offset_y: offset_y as i32,
-            which_keycode: which_keycode as i32,
-            shift_key: shift_key == 1,
-            alt_key: alt_key == 1,
-            ctrl_key: ctrl_key == 1,
-            meta_key: meta_key == 1,
-        };
+        deliver_subscribed::<D, M, U, R, S, _>(app, move |active| active.iter()
+            .filter(|subscription| match subscription.source() {
+                SubscriptionSource::Interval(millis) => key == format!("interval {}", millis),
+                SubscriptionSource::AnimationFrame => key == "frame",
+                _ => false,
+            })
+            .filter_map(|subscription| subscription.handle(&detail))
+            .collect());
+    }
 
-        let keys = Keys {
-            size: keys_size,
-            stack: [
-                key_1,
-                key_2,
-                key_3,
-                key_4,
-                key_5,
-                key_6,
-                key_7,
-                key_8,
-                key_9,
-                key_10,
-                key_11,
-                key_12,
-                key_13,
-                key_14,
-                key_15,
-                key_16,
-                key_17,
-                key_18,
-                key_19,
-                key_20,
-                key_21,
-                key_22,
-                key_23,
-                key_24,
-                key_25,
-                key_26,
-                key_27,
-                key_28,
-                key_29,
-                key_30,
-                key_31,
-                key_32,
-            ]
-        };
+    unsafe extern "C" fn handle_element_event<D, M, U, R, S>(
+        app: AppId,
+        key_ptr: *const libc::c_char,
+        on_element: libc::c_int,
+        inside_app: libc::c_int,
+    )
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let key = CStr::from_ptr(key_ptr).to_string_lossy().into_owned();
+        let detail = [on_element as f64, inside_app as f64];
 
-        let message = listener_ref.handle_event(event);
-        update_system(system_c_ptr, message, keys);
+        deliver_subscribed::<D, M, U, R, S, _>(app, move |active| active.iter()
+            .filter(|subscription| match subscription.source() {
+                SubscriptionSource::RootEvent(event_type) => key == format!("root {}", event_type),
+                SubscriptionSource::BodyEvent(event_type) => key == format!("body {}", event_type),
+                SubscriptionSource::ClickOutside(element) => key == format!("outside {}", element.name()),
+                _ => false,
+            })
+            .filter_map(|subscription| subscription.handle(&detail))
+            .collect());
     }
 
-    unsafe fn update_system<D, M, U, R, S>
-    (
-        system_c_ptr: *mut libc::c_void,
-        message: M,
-        keys: Keys
+    unsafe extern "C" fn handle_media_query<D, M, U, R, S>(
+        app: AppId,
+        query_ptr: *const libc::c_char,
+        matches: libc::c_int,
     )
         where
-        (D, U, R, S): Sized,
-        D: DomNode<M>,
+        D: DomNode<M> + 'static,
         M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M, Rendered=D>,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
     {
+        let query = CStr::from_ptr(query_ptr).to_bytes().to_vec();
+        let detail = [matches as f64];
 
-        let system_ptr: *mut (D, U, R, S, VDomNode<M>) = mem::transmute(system_c_ptr);
-        let system_ref: &mut (D, U, R, S, VDomNode<M>) = system_ptr.as_mut().unwrap();
+        deliver_subscribed::<D, M, U, R, S, _>(app, move |active| active.iter()
+            .filter(|subscription| match subscription.source() {
+                SubscriptionSource::MediaQuery(subscribed) => subscribed.as_bytes() == &query[..],
+                _ => false,
+            })
+            .filter_map(|subscription| subscription.handle(&detail))
+            .collect());
+    }
 
-        let (
-            ref mut rendered,
-            ref mut updater,
-            ref mut renderer,
-            ref mut state,
-            ref mut vdom_root,
-        ) = *system_ref;
+    unsafe extern "C" fn handle_subscription<D, M, U, R, S>(
+        app: AppId,
+        index: libc::c_int,
+        detail_ptr: *const f64,
+        detail_len: libc::c_int,
+    ) -> libc::c_int
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        let event_type = SUBSCRIPTION_EVENT_TYPES[index as usize].0;
+        let detail = slice::from_raw_parts(detail_ptr, detail_len as usize).to_vec();
 
-        // Update state
-        updater.update(state, message, keys.into_iter(), &JsIoImpl { app_system: system_ptr });
+        // JavaScript cancels key presses which delivered a message
+        deliver_subscribed::<D, M, U, R, S, _>(app, move |active| active.iter()
+            .filter(|subscription| {
+                let source = subscription.source();
+                source.event_type() == event_type && !source.is_element_event()
+            })
+            .filter_map(|subscription| subscription.handle(&detail))
+            .collect()) as libc::c_int
+    }
 
-        // Render new DomNode
-        // TODO: fix unsafety due to possible `panic` in `render`
-        ptr::drop_in_place(rendered);
-        ptr::write(rendered, renderer.render(state));
+    /// Delivers the messages which the active subscriptions of `app` produce for an event,
+    /// returning how many there were.
+    ///
+    /// All of the messages are collected first, since each update replaces the subscriptions.
+    /// If the application is updating or rendering, the event is handled once it has finished,
+    /// and none are counted.
+    unsafe fn deliver_subscribed<D, M, U, R, S, F>(app: AppId, messages_for: F) -> usize
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+        F: Fn(&[Subscription<M>]) -> Vec<M> + 'static,
+    {
+        let system = match mounted_system::<D, M, U, R, S>(app) {
+            Some(system) => system,
+            None => return 0,
+        };
+        let messages = match system.try_borrow() {
+            Ok(system) => messages_for(&system.subscriptions.active),
+            Err(_) => {
+                let deliver: unsafe fn(AppId, F) -> usize = deliver_subscribed::<D, M, U, R, S, F>;
+                queue_for_app(app, Box::new(move || { deliver(app, messages_for); }));
+                return 0;
+            },
+        };
 
-        // Write new DomNode to root element
-        {
-            let mut node_index = 0;
-            let mut input = WebWriterAcc {
-                system_ptr: system_ptr,
-                document: WebDocument(()),
-                keys: Keys::new(),
-                parent_element: &vdom_root.web_element,
-                node_level: &mut vdom_root.children,
-                node_index: &mut node_index,
-            };
-            rendered.process_all::<WebWriter<D, M, U, R, S>>(&mut input).unwrap();
+        let delivered = messages.len();
+        for message in messages {
+            update_system::<D, M, U, R, S>(app, message, Keys::new());
         }
+        delivered
     }
 
     impl WebElement {
@@ -706,32 +4508,59 @@ mod private {
             if err < 0 { panic!("Attempted to move child DOM element out of bounds") }
         }
 
-        /// Requires that `listener_ptr` and `system_ptr` are valid and that
-        /// `root_node_id` is a valid `WebElement` id throughout the duration of
-        /// time that it is possible for this callback to be triggered.
-        /// Returns an element that is a reference to the created function
+        /// Requires that `listener_ptr` is valid whenever the callback can be triggered outside
+        /// of an update or render of `app`. The rendered node is replaced in
+        /// place, so a listener stays at the same address until it's removed, or re-added if it
+        /// moved.
+        ///
+        /// Returns an element that is a reference to the created function, and the handler it
+        /// calls, which must be kept until the element has been dropped. The function does
+        /// nothing once the element's pool entry has been deleted.
         unsafe fn set_listener<D, M, U, R, S>(
             &self,
             event_name: &str,
             listener_ptr: *const Listener<M>,
-            system_ptr: *mut (D, U, R, S, VDomNode<M>),
+            app: AppId,
             keys: Keys,
-        ) -> WebElement
+        ) -> (WebElement, Box<ListenerHandler<M>>)
             where
-            (D, U, R, S): Sized, // Make sure *mut (D, U, R, S) is a thin ptr
-            D: DomNode<M>,
+            D: DomNode<M> + 'static,
             M: 'static,
-            U: Updater<S, M>,
-            R: Renderer<S, M, Rendered=D>
+            U: Updater<S, M> + 'static,
+            R: StaticRenderer<S, M, Rendered=D> + 'static,
+            S: 'static,
         {
             unsafe {
                 const JS: &'static [u8] = b"\
                     var callback = function(event) {\
-                        var stack = Runtime.stackSave();\
+                        if (__domafic_pool[callback.__domafic_index] !== callback) { return; }\
                         event = event || window.event;\
+                        var link = event.currentTarget;\
+                        if (event.type == 'click' && link && link.hasAttribute &&\
+                                link.hasAttribute('data-domafic-link')) {\
+                            var elsewhere = event.button || event.shiftKey || event.altKey ||\
+                                event.ctrlKey || event.metaKey || event.defaultPrevented ||\
+                                (link.target && link.target != '_self');\
+                            if (elsewhere) { return; }\
+                            event.preventDefault();\
+                            if (link.href != window.location.href) {\
+                                window.history.pushState(null, '', link.href);\
+                            }\
+                        }\
+                        if (__domafic_rendering[$4]) {\
+                            __domafic_deferred_events[$4].push([callback, event]);\
+                            return;\
+                        }\
+                        var stack = Runtime.stackSave();\
                         var typeStr = event.type ? allocate(intArrayFromString(event.type), 'i8', ALLOC_STACK) : 0;\
                         var targetValue = (event.target && event.target.value) ? allocate(intArrayFromString(event.target.value), 'i8', ALLOC_STACK) : 0;\
-                        Runtime.dynCall('viiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiii', $2, [$3, $4, $5,\
+                        var detail = null;\
+                        if (typeof CustomEvent !== 'undefined' && event instanceof CustomEvent &&\
+                                event.detail !== null && event.detail !== undefined) {\
+                            try { detail = JSON.stringify(event.detail); } catch (e) {}\
+                        }\
+                        var detailJson = (typeof detail === 'string') ? allocate(intArrayFromString(detail), 'i8', ALLOC_STACK) : 0;\
+                        Runtime.dynCall('viiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiiii', $2, [$3, $4,\
                         typeStr,\
                         targetValue,\
                         Math.floor(event.clientX || 0), Math.floor(event.clientY || 0),\
@@ -741,8 +4570,9 @@ mod private {
                         event.altKey ? 1 : 0,\
                         event.ctrlKey ? 1 : 0,\
                         event.metaKey ? 1 : 0,\
-                        $6, $7,\
-                        $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38,\
+                        detailJson,\
+                        $5, $6,\
+                        $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37,\
                         ]);\
                         Runtime.stackRestore(stack);\
                     };\
@@ -752,24 +4582,29 @@ mod private {
                         false\
                     );\
                     var index = __domafic_pool_free.pop();\
-                    if (index) { __domafic_pool[index] = callback; return index; }\
-                    return __domafic_pool.push(callback) - 1;\
+                    if (index !== undefined) {\
+                        __domafic_pool[index] = callback;\
+                    } else {\
+                        index = __domafic_pool.push(callback) - 1;\
+                    }\
+                    callback.__domafic_index = index;\
+                    return index;\
                 \0";
 
                 let event_name_cstring = CString::new(event_name).unwrap();
                 let Keys { size: k_size, stack: k } = keys;
-                let (listener_data_c_ptr, listener_vtable_c_ptr):
-                    (*const libc::c_void, *const libc::c_void) =
-                    mem::transmute(listener_ptr);
+                let handler = Box::new(ListenerHandler(Box::new(move |event| (*listener_ptr).handle_event(event))));
 
-                WebElement(emscripten_asm_const_int(
+                // $0: element, $1: event name, $2: `handle_listener`, $3: handler, $4: app,
+                // $5: key count, $6 to $37: keys. `handle_listener` takes the handler, the app,
+                // 12 event fields and the 33 key arguments, hence the 47 'i's.
+                let element = WebElement(emscripten_asm_const_int(
                     &JS[0] as *const _ as *const libc::c_char,
                     self.0,
                     event_name_cstring.as_ptr() as libc::c_int,
                     handle_listener::<D, M, U, R, S> as *const libc::c_void,
-                    listener_data_c_ptr,
-                    listener_vtable_c_ptr,
-                    system_ptr as *const libc::c_void,
+                    &*handler as *const ListenerHandler<M> as libc::c_int,
+                    app,
                     k_size,
                     k[0],
                     k[1],
@@ -803,7 +4638,8 @@ mod private {
                     k[29],
                     k[30],
                     k[31]
-                ))
+                ));
+                (element, handler)
             }
         }
 
@@ -821,39 +4657,192 @@ mod private {
                     listener.0,
                 );
             }
-        }
-
-        fn remove_all_children(&self) {
+        }
+
+        fn remove_all_children(&self) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = __domafic_pool[$0];\
+                    while (elem.hasChildNodes()) { elem.removeChild(elem.lastChild); }\
+                \0";
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.0,
+                );
+            }
+        }
+
+        /// Number of child nodes of the element
+        fn child_count(&self) -> usize {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    return __domafic_pool[$0].childNodes.length;\
+                \0";
+                emscripten_asm_const_int(&JS[0] as *const _ as *const libc::c_char, self.0) as usize
+            }
+        }
+
+        /// Adopts the child at `index` as the node `value`, or returns `None` if the child isn't
+        /// that node. An element with listeners must also have the `HYDRATION_ID_ATTRIBUTE`
+        /// `hydration_id`. Adjacent text nodes are written to HTML as one, so a text node which
+        /// only starts with the node's text is split, and empty text nodes, which aren't in the
+        /// HTML at all, are created.
+        fn hydrate_child(&self, index: usize, value: &VNodeValue, hydration_id: Option<usize>)
+            -> Result<Option<WebElement>, Error>
+        {
+            let (kind, text) = match *value {
+                VNodeValue::Tag(tag) => (0, tag),
+                VNodeValue::Text(ref text) => (1, text.as_str()),
+                VNodeValue::Comment(_) => (2, ""),
+                VNodeValue::Static(_) => (3, ""),
+            };
+            let id = unsafe {
+                const JS: &'static [u8] = b"\
+                    var parent = __domafic_pool[$0];\
+                    var child = parent.childNodes[$1];\
+                    var kind = $2;\
+                    var value = UTF8ToString($3);\
+                    if (kind === 0) {\
+                        if (!child || child.nodeType !== 1 ||\
+                            child.tagName.toLowerCase() !== value.toLowerCase()) { return -1; }\
+                        if ($4 >= 0 && child.getAttribute('data-domafic-id') !== String($4)) { return -1; }\
+                    } else if (kind === 1) {\
+                        var is_text = child && child.nodeType === 3;\
+                        if (is_text && child.data !== value && value && child.data.indexOf(value) === 0) {\
+                            child.splitText(value.length);\
+                        } else if (!is_text || child.data !== value) {\
+                            if (value) { return -1; }\
+                            child = document.createTextNode('');\
+                            parent.insertBefore(child, parent.childNodes[$1] || null);\
+                        }\
+                    } else if (kind === 2) {\
+                        if (!child || child.nodeType !== 8) { return -1; }\
+                    } else if (!child || child.nodeType !== 1) {\
+                        return -1;\
+                    }\
+                    var index = __domafic_pool_free.pop();\
+                    if (index !== undefined) { __domafic_pool[index] = child; return index; }\
+                    if (__domafic_pool.length >= 2147483647) { return -2; }\
+                    return __domafic_pool.push(child) - 1;\
+                \0";
+                let text_cstring = CString::new(text).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.0,
+                    index as libc::c_int,
+                    kind,
+                    text_cstring.as_ptr() as libc::c_int,
+                    hydration_id.map_or(-1, |id| id as libc::c_int),
+                )
+            };
+            if id == -1 {
+                return Ok(None);
+            }
+            pooled(id, "hydrate").map(Some)
+        }
+
+        #[allow(dead_code)]
+        fn remove_self(&self) {
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    var elem = __domafic_pool[$0];\
+                    if (elem.parentNode) { elem.parentNode.removeChild(elem); }\
+                \0";
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.0,
+                );
+            }
+        }
+
+        /// Removes `key_value`, which was set with `set_attribute`
+        fn remove_attribute(&self, key_value: &KeyValue) {
+            if key_value.1.as_bool_attribute(key_value.0).is_some() {
+                return self.set_attribute(&(key_value.0, AttributeValue::Bool(false)));
+            }
+            unsafe {
+                const JS: &'static [u8] = b"\
+                    __domafic_set_property(__domafic_pool[$0], UTF8ToString($1), null);\
+                \0";
+                let key_cstring = CString::new(key_value.0).unwrap();
+                emscripten_asm_const_int(
+                    &JS[0] as *const _ as *const libc::c_char,
+                    self.0,
+                    key_cstring.as_ptr() as libc::c_int,
+                );
+            }
+        }
+
+        fn set_attribute(&self, key_value: &KeyValue) {
+            if let Some(set) = key_value.1.as_bool_attribute(key_value.0) {
+                // Boolean attributes are set by being present, whatever their text, and not all of
+                // them have a property of the same name, such as `readonly`
+                unsafe {
+                    const JS: &'static [u8] = b"\
+                        var elem = __domafic_pool[$0];\
+                        var key = UTF8ToString($1);\
+                        if ($2) { elem.setAttribute(key, ''); } else { elem.removeAttribute(key); }\
+                    \0";
+                    let key_cstring = CString::new(key_value.0).unwrap();
+                    emscripten_asm_const_int(
+                        &JS[0] as *const _ as *const libc::c_char,
+                        self.0,
+                        key_cstring.as_ptr() as libc::c_int,
+                        set as libc::c_int
+                    );
+                }
+                return;
+            }
             unsafe {
                 const JS: &'static [u8] = b"\
-                    var elem = __domafic_pool[$0];\
-                    while (elem.hasChildNodes()) { elem.removeChild(elem.lastChild); }\
+                    __domafic_set_property(__domafic_pool[$0], UTF8ToString($1), UTF8ToString($2));\
                 \0";
+                let key_cstring = CString::new(key_value.0).unwrap();
+                let value_str = key_value.1.as_str();
+                let value_cstring = CString::new(value_str).unwrap();
                 emscripten_asm_const_int(
                     &JS[0] as *const _ as *const libc::c_char,
                     self.0,
+                    key_cstring.as_ptr() as libc::c_int,
+                    value_cstring.as_ptr() as libc::c_int
                 );
             }
         }
+    }
 
-        #[allow(dead_code)]
-        fn remove_self(&self) {
+    impl WebElement {
+        /// Sets a property the user can change, such as the `value` of an `input`, if the
+        /// element's live value differs from it. The value is left alone when it's already
+        /// right, so that the caret doesn't move while the user is typing.
+        fn sync_property(&self, key_value: &KeyValue) {
             unsafe {
                 const JS: &'static [u8] = b"\
                     var elem = __domafic_pool[$0];\
-                    if (elem.parentNode) { elem.parentNode.removeChild(elem); }\
+                    var key = UTF8ToString($1);\
+                    var value = UTF8ToString($2);\
+                    if (key !== 'value') { value = value !== 'false'; }\
+                    if (elem[key] !== value) { elem[key] = value; }\
                 \0";
+                let key_cstring = CString::new(key_value.0).unwrap();
+                let value_cstring = CString::new(key_value.1.as_str()).unwrap();
                 emscripten_asm_const_int(
                     &JS[0] as *const _ as *const libc::c_char,
                     self.0,
+                    key_cstring.as_ptr() as libc::c_int,
+                    value_cstring.as_ptr() as libc::c_int
                 );
             }
         }
 
-        fn remove_attribute(&self, key: &str) {
+        /// Resets a property set with `sync_property` once its attribute is no longer rendered:
+        /// `value` to `''`, and `checked` and `selected` to `false`
+        fn reset_property(&self, key: &str) {
             unsafe {
                 const JS: &'static [u8] = b"\
-                    __domafic_pool[$0][UTF8ToString($1)] = null;\
+                    var elem = __domafic_pool[$0];\
+                    var key = UTF8ToString($1);\
+                    var value = key === 'value' ? '' : false;\
+                    if (elem[key] !== value) { elem[key] = value; }\
                 \0";
                 let key_cstring = CString::new(key).unwrap();
                 emscripten_asm_const_int(
@@ -863,21 +4852,31 @@ mod private {
                 );
             }
         }
+    }
 
-        fn set_attribute(&self, key_value: &KeyValue) {
-            unsafe {
-                const JS: &'static [u8] = b"\
-                    __domafic_pool[$0][UTF8ToString($1)] = UTF8ToString($2);\
-                \0";
-                let key_cstring = CString::new(key_value.0).unwrap();
-                let value_str = key_value.1.as_str();
-                let value_cstring = CString::new(value_str).unwrap();
-                emscripten_asm_const_int(
-                    &JS[0] as *const _ as *const libc::c_char,
-                    self.0,
-                    key_cstring.as_ptr() as libc::c_int,
-                    value_cstring.as_ptr() as libc::c_int
-                );
+    /// Whether `key` names a property of a `tag` element which the user can change, and which
+    /// the element's attribute stops controlling once they have.
+    ///
+    /// These are set as properties, after the element's children, so that a `select` has its
+    /// options when its `value` is set, and on every update, even when the rendered value is
+    /// the same as before, so that the element shows the value from the application's state
+    /// rather than what the user last entered.
+    fn is_controlled_property(tag: &str, key: &str) -> bool {
+        match key {
+            "value" => tag == "input" || tag == "textarea" || tag == "select",
+            "checked" => tag == "input",
+            "selected" => tag == "option",
+            _ => false,
+        }
+    }
+
+    /// Sets the controlled properties of `node` on `element`
+    fn sync_controlled_properties<M, T: DomNode<M>>(element: &WebElement, node: &T) {
+        if let DomValue::Element { tag } = node.value() {
+            for attr in node.merged_attributes() {
+                if is_controlled_property(tag, attr.0) {
+                    element.sync_property(&attr);
+                }
             }
         }
     }
@@ -897,10 +4896,57 @@ mod private {
         }
     }
 
+    /// A `JsWidget` managing an element, which is destroyed along with the element's `VDomNode`
+    struct WidgetSlot(Rc<RefCell<Option<Box<dyn MountedWidget>>>>);
+
+    thread_local! {
+        // Widgets whose elements were created by the current render, to be created once the
+        // render has added the elements to the page
+        static PENDING_WIDGETS: RefCell<Vec<(Weak<RefCell<Option<Box<dyn MountedWidget>>>>,
+            Box<dyn FnOnce() -> Box<dyn MountedWidget>>)>> = RefCell::new(Vec::new());
+    }
+
+    impl WidgetSlot {
+        fn mount_later(mount: Box<dyn FnOnce() -> Box<dyn MountedWidget>>) -> WidgetSlot {
+            let slot = Rc::new(RefCell::new(None));
+            PENDING_WIDGETS.with(|pending| pending.borrow_mut().push((Rc::downgrade(&slot), mount)));
+            WidgetSlot(slot)
+        }
+    }
+
+    impl Drop for WidgetSlot {
+        fn drop(&mut self) {
+            if let Some(mut widget) = self.0.borrow_mut().take() {
+                widget.destroy();
+            }
+        }
+    }
+
+    impl fmt::Debug for WidgetSlot {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "WidgetSlot")
+        }
+    }
+
+    /// Creates the widgets whose elements were added to the page by the last render
+    fn mount_widgets() {
+        let pending = PENDING_WIDGETS.with(|pending| mem::replace(&mut *pending.borrow_mut(), Vec::new()));
+        for (slot, mount) in pending {
+            // Elements created and removed within the same render never get a widget
+            if let Some(slot) = slot.upgrade() {
+                *slot.borrow_mut() = Some(mount());
+            }
+        }
+    }
+
     #[derive(Debug, Clone, Eq, PartialEq)]
     enum VNodeValue {
         Text(String),
         Tag(&'static str),
+        // A comment, or an empty comment standing in for a doctype
+        Comment(String),
+        // A `StaticNode`, identified by the hash of its HTML
+        Static(u64),
     }
     #[derive(Debug)]
     struct VDomNode<Message: 'static> {
@@ -908,48 +4954,212 @@ mod private {
         keys: Keys,
         web_element: WebElement,
         attributes: Vec<KeyValue>,
-        listeners: Vec<(WebElement, *const Listener<Message>, &'static str)>,
+        // The callback added to the element, the listener it calls, the event type, and the
+        // handler passed to JavaScript, which is freed after the callback is deleted
+        listeners: Vec<(WebElement, *const Listener<Message>, &'static str, Box<ListenerHandler<Message>>)>,
         children: VDOMLevel<Message>,
+        // Hash of the props given to `with_should_update` when the node was last rendered
+        props_hash: Option<u64>,
+        // The widget managing the element, if it was rendered from a `widget::Widget`
+        widget: Option<WidgetSlot>,
+        // Position of the element in the tree, e.g. "0.2.1", as last stamped on the element
+        #[cfg(debug_assertions)]
+        path: String,
+        // Number of consecutive renders which replaced the element's listeners with new ones
+        #[cfg(debug_assertions)]
+        recreated_listeners: u32,
+    }
+    type VDOMLevel<Message> = Vec<VDomNode<Message>>;
+
+    /// Calls a listener of the rendered node. It's boxed when the listener is attached, so that
+    /// JavaScript can hold a thin pointer to it.
+    struct ListenerHandler<Message>(Box<dyn Fn(Event) -> Message>);
+
+    impl<M> fmt::Debug for ListenerHandler<M> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "ListenerHandler")
+        }
     }
-    type VDOMLevel<Message: 'static> = Vec<VDomNode<Message>>;
 
     struct WebWriter<'a, 'n, D, M, U, R, S>(
         PhantomData<(&'a (), &'n (), D, M, U, R, S)>
     );
-    struct WebWriterAcc<'n, D, M, U, R, S> where M: 'static {
-        system_ptr: *mut (D, U, R, S, VDomNode<M>),
+    struct WebWriterAcc<'n, M> where M: 'static {
+        app: AppId,
         keys: Keys,
         document: WebDocument,
         parent_element: &'n WebElement,
         node_level: &'n mut VDOMLevel<M>,
         node_index: &'n mut usize,
+        // Whether an ancestor was skipped by `should_update`, so that only listeners are updated
+        listeners_only: bool,
+        // Position of `parent_element` in the tree
+        #[cfg(debug_assertions)]
+        path: String,
+    }
+
+    /// Stamps an element with the position of the node it was rendered from, so that it can be
+    /// correlated with render code when inspected in devtools
+    #[cfg(debug_assertions)]
+    fn stamp_path(element: &WebElement, path: &str) {
+        element.set_attribute(&("data-domafic-path", AttributeValue::OwnedStr(path.to_string())));
+    }
+
+    /// Number of consecutive renders replacing an element's listeners before a warning is logged
+    #[cfg(debug_assertions)]
+    const RECREATED_LISTENERS_WARNING: u32 = 3;
+
+    /// Warns that the listeners of the element at `path` are replaced on every render.
+    ///
+    /// Listeners are matched to the previous render's listeners by address, so listeners stored
+    /// in a new allocation on each render, such as in a `Vec` or `Box` built by the renderer,
+    /// are removed from the DOM and added again every time.
+    #[cfg(debug_assertions)]
+    fn warn_recreated_listeners(path: &str) {
+        const JS: &'static [u8] = b"\
+            var path = UTF8ToString($0);\
+            console.warn('domafic: the listeners of the element at ' + path + ' have been ' +\
+                'recreated on every render. Listeners stored in new allocations each render, ' +\
+                'such as in a Vec or Box, are removed from the DOM and added again each time.',\
+                document.querySelector('[data-domafic-path=\\'' + path + '\\']'));\
+        \0";
+        let path_cstring = CString::new(path).unwrap();
+        unsafe {
+            emscripten_asm_const_int(
+                &JS[0] as *const _ as *const libc::c_char,
+                path_cstring.as_ptr() as libc::c_int,
+            );
+        }
+    }
+
+    /// Stamps an element with the key stack of the node it was rendered from, such as "3/7"
+    #[cfg(debug_assertions)]
+    fn stamp_keys(element: &WebElement, keys: Keys) {
+        if keys.size == 0 { return; }
+        let keys: Vec<String> = keys.into_iter().map(|key| key.to_string()).collect();
+        element.set_attribute(&("data-domafic-key", AttributeValue::OwnedStr(keys.join("/"))));
+    }
+
+    /// Passes a widget node's props to the widget created for its element, replacing the widget
+    /// if the node has a different type of widget
+    fn update_widget(slot: &mut Option<WidgetSlot>, widget: Option<&dyn WidgetNode>) {
+        let updated = match (slot.as_ref(), widget) {
+            (Some(slot), Some(widget)) => match *slot.0.borrow_mut() {
+                Some(ref mut mounted) => widget.update(&mut **mounted),
+                // Not created yet, so it will be created with the latest props
+                None => true,
+            },
+            (None, None) => true,
+            _ => false,
+        };
+        if !updated {
+            *slot = widget.map(|widget| WidgetSlot::mount_later(widget.mount()));
+        }
+    }
+
+    /// Removes the listeners of `vnode` and its descendants, for elements in a skipped subtree
+    /// which no longer correspond to a node
+    fn detach_listeners<M>(vnode: &mut VDomNode<M>) {
+        for (element, _, event_type, _) in vnode.listeners.drain(..) {
+            vnode.web_element.remove_listener(event_type, &element);
+        }
+        for child in &mut vnode.children {
+            detach_listeners(child);
+        }
+    }
+
+    /// Replaces the listeners of `vnode` which aren't in `listeners`, leaving those which are
+    /// unchanged attached to the element
+    unsafe fn update_listeners<D, M, U, R, S>(
+        vnode: &mut VDomNode<M>,
+        listeners: Vec<*const dyn Listener<M>>,
+        app: AppId,
+        keys: Keys,
+    )
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        // Remove excess listeners
+        {
+            let mut i = 0;
+            while i < vnode.listeners.len() {
+                let do_remove = {
+                    let ref listener = vnode.listeners[i];
+                    let (ref old_element, ref old_ptr, ref old_str, _) = *listener;
+
+                    if !listeners.iter().any(|listener|
+                        *old_ptr as *const () == *listener as *const () &&
+                        *old_str == unsafe{ (**listener).event_type_handled() }
+                    ) {
+                        vnode.web_element.remove_listener(old_str, &old_element);
+                        true
+                    } else {
+                        i += 1;
+                        false
+                    }
+                };
+
+                if do_remove {
+                    vnode.listeners.remove(i);
+                }
+            }
+        }
+
+        // Add new listeners
+        for listener in listeners {
+            unsafe {
+                let event_type = (*listener).event_type_handled();
+                if !vnode.listeners.iter().any(|x|
+                        x.1 as *const () == listener as *const () &&
+                        x.2 == event_type
+                    ) {
+                    let (element, handler) = vnode.web_element.set_listener::<D, M, U, R, S>(
+                        event_type,
+                        listener,
+                        app,
+                        keys
+                    );
+                    vnode.listeners.push((element, listener, event_type, handler));
+                }
+            }
+        }
     }
 
     impl<'a, 'n, D, M, U, R, S> DomNodeProcessor<'a, M> for WebWriter<'a, 'n, D, M, U, R, S>
         where
-        D: DomNode<M>,
+        D: DomNode<M> + 'static,
         M: 'static,
-        U: Updater<S, M>,
-        R: Renderer<S, M, Rendered=D>
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
     {
-        type Acc = WebWriterAcc<'n, D, M, U, R, S>;
-        type Error = ();
+        type Acc = WebWriterAcc<'n, M>;
+        type Error = Error;
 
         fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
             fn add_node<'a, 'n, T, D, M, U, R, S>(
-                acc: &mut WebWriterAcc<'n, D, M, U, R, S>,
-                node: &'a T) -> Result<(), ()>
+                acc: &mut WebWriterAcc<'n, M>,
+                node: &'a T) -> Result<(), Error>
                 where
                 T: DomNode<M>,
-                D: DomNode<M>,
+                D: DomNode<M> + 'static,
                 M: 'static,
-                U: Updater<S, M>,
-                R: Renderer<S, M, Rendered=D>
+                U: Updater<S, M> + 'static,
+                R: StaticRenderer<S, M, Rendered=D> + 'static,
+                S: 'static,
             {
 
-                let vnode_value = match node.value() {
-                    DomValue::Element { tag } => VNodeValue::Tag(tag),
-                    DomValue::Text(text) => VNodeValue::Text(text.to_string()),
+                let precompiled = node.precompiled();
+                let vnode_value = match (precompiled, node.value()) {
+                    (Some(precompiled), _) => VNodeValue::Static(precompiled.hash()),
+                    (None, DomValue::Element { tag }) => VNodeValue::Tag(tag),
+                    (None, DomValue::Comment(text)) => VNodeValue::Comment(text.to_string()),
+                    (None, DomValue::Doctype(_)) => VNodeValue::Comment(String::new()),
+                    (None, text) => VNodeValue::Text(text.into_text().unwrap()),
                 };
 
                 let keys = if let Some(new_key) = node.key() {
@@ -964,6 +5174,44 @@ mod private {
                     listeners
                 };
 
+                #[cfg(debug_assertions)]
+                let path = if acc.path.is_empty() {
+                    acc.node_index.to_string()
+                } else {
+                    format!("{}.{}", acc.path, acc.node_index)
+                };
+
+                if acc.listeners_only {
+                    // An ancestor's subtree was skipped, so the nodes have the same structure as
+                    // when they were last updated and match the vnodes at the same positions
+                    if let Some(vnode) = acc.node_level.get_mut(*acc.node_index) {
+                        vnode.props_hash = node.props_hash();
+                        unsafe {
+                            update_listeners::<D, M, U, R, S>(vnode, listeners, acc.app, keys);
+                        }
+                        let mut child_node_index = 0;
+                        {
+                            let mut new_acc = WebWriterAcc {
+                                app: acc.app,
+                                keys: keys,
+                                document: acc.document,
+                                parent_element: &vnode.web_element,
+                                node_level: &mut vnode.children,
+                                node_index: &mut child_node_index,
+                                listeners_only: true,
+                                #[cfg(debug_assertions)]
+                                path: path.clone(),
+                            };
+                            node.children().process_all::<WebWriter<D, M, U, R, S>>(&mut new_acc)?;
+                        }
+                        for unmatched in vnode.children.iter_mut().skip(child_node_index) {
+                            detach_listeners(unmatched);
+                        }
+                    }
+                    *acc.node_index += 1;
+                    return Ok(());
+                }
+
                 let vnode_match_opt_index = {
                     let mut vnode_match_opt_index = None;
                     let mut trial_index = *acc.node_index;
@@ -988,100 +5236,123 @@ mod private {
                     // Add new listeners, unify attributes, unify children
 
                     {
-                        let mut vnode = &mut acc.node_level[vnode_index];
+                        let vnode = &mut acc.node_level[vnode_index];
 
-                        // Remove excess listeners
+                        #[cfg(debug_assertions)]
                         {
-                            let mut i = 0;
-                            while i < vnode.listeners.len() {
-                                let do_remove = {
-                                    let ref listener = vnode.listeners[i];
-                                    let (ref old_element, ref old_ptr, ref old_str) = *listener;
-
-                                    if !listeners.iter().any(|listener|
-                                        *old_ptr == *listener &&
-                                        *old_str == unsafe{ (**listener).event_type_handled() }
-                                    ) {
-                                        vnode.web_element.remove_listener(old_str, &old_element);
-                                        true
-                                    } else {
-                                        i += 1;
-                                        false
-                                    }
-                                };
+                            if vnode.path != path {
+                                stamp_path(&vnode.web_element, &path);
+                                vnode.path = path.clone();
+                            }
 
-                                if do_remove {
-                                    vnode.listeners.remove(i);
+                            // A listener for the same event in the same position, but at a new
+                            // address, is removed and re-added to the element
+                            let recreated = vnode.listeners.iter().zip(listeners.iter())
+                                .any(|(old, &new)| old.1 as *const () != new as *const () &&
+                                    old.2 == unsafe { (*new).event_type_handled() });
+                            if recreated {
+                                vnode.recreated_listeners += 1;
+                                if vnode.recreated_listeners == RECREATED_LISTENERS_WARNING {
+                                    warn_recreated_listeners(&path);
                                 }
+                            } else {
+                                vnode.recreated_listeners = 0;
                             }
                         }
 
-                        // Add new listeners
-                        for listener in listeners {
-                            unsafe {
-                                let event_type = (*listener).event_type_handled();
-                                if !vnode.listeners.iter().any(|x|
-                                        x.1 == listener &&
-                                        x.2 == event_type
-                                    ) {
-                                    let element = vnode.web_element.set_listener(
-                                        event_type,
-                                        listener,
-                                        acc.system_ptr,
-                                        keys
-                                    );
-                                    vnode.listeners.push((element, listener, event_type));
-                                }
-                            }
+                        // Skip the rest of the subtree if its props haven't changed
+                        let skip = match (vnode.props_hash, node.props_hash()) {
+                            (Some(old_props_hash), Some(_)) => !node.should_update(old_props_hash),
+                            (None, Some(_)) => false,
+                            // Nodes without props, such as uncontrolled regions, may still decline
+                            (_, None) => !node.should_update(0),
+                        };
+                        vnode.props_hash = node.props_hash();
+
+                        if !skip {
+                            update_widget(&mut vnode.widget, node.widget());
                         }
 
-                        // Remove excess attributes
-                        {
-                            let mut i = 0;
-                            while i < vnode.attributes.len() {
-                                let do_remove = {
-                                    let ref old_attribute = vnode.attributes[i];
-                                    if !node.attributes().any(|attr| *attr == *old_attribute) {
-                                        vnode.web_element.remove_attribute(old_attribute.0);
-                                        true
-                                    } else {
-                                        false
-                                    }
-                                };
+                        unsafe {
+                            update_listeners::<D, M, U, R, S>(vnode, listeners, acc.app, keys);
+                        }
 
-                                if do_remove {
-                                    vnode.attributes.remove(i);
-                                } else {
-                                    i += 1;
-                                }
+                        if !skip {
+                            // Remove attributes which are no longer set. Attributes are
+                            // matched by name, so that one whose value changed is updated in
+                            // place rather than removed and added again, which would reset
+                            // transitions on it and, for some attributes, the focus. A controlled
+                            // property is reset instead, since the user may have changed it
+                            // since its attribute was last set.
+                            {
+                                let web_element = &vnode.web_element;
+                                vnode.attributes.retain(|old_attribute| {
+                                    let kept = node.merged_attributes().any(|attr| attr.0 == old_attribute.0);
+                                    if !kept {
+                                        match vnode_value {
+                                            VNodeValue::Tag(tag) if is_controlled_property(tag, old_attribute.0) =>
+                                                web_element.reset_property(old_attribute.0),
+                                            _ => web_element.remove_attribute(old_attribute),
+                                        }
+                                    }
+                                    kept
+                                });
                             }
-                        }
 
-                        // Add new attributes
-                        for new_attribute in node.attributes() {
-                            if !vnode.attributes.contains(new_attribute) {
-                                vnode.web_element.set_attribute(new_attribute);
-                                vnode.attributes.push(new_attribute.clone());
+                            // Add new attributes, and update those whose value changed.
+                            // Controlled properties are set after the children.
+                            for new_attribute in node.merged_attributes() {
+                                let controlled = match vnode_value {
+                                    VNodeValue::Tag(tag) => is_controlled_property(tag, new_attribute.0),
+                                    _ => false,
+                                };
+                                match vnode.attributes.iter().position(|old| old.0 == new_attribute.0) {
+                                    Some(i) if controlled => vnode.attributes[i] = new_attribute.into_owned(),
+                                    Some(i) if vnode.attributes[i] == *new_attribute => {},
+                                    Some(i) => {
+                                        vnode.web_element.set_attribute(&new_attribute);
+                                        vnode.attributes[i] = new_attribute.into_owned();
+                                    },
+                                    None => {
+                                        if !controlled {
+                                            vnode.web_element.set_attribute(&new_attribute);
+                                        }
+                                        vnode.attributes.push(new_attribute.into_owned());
+                                    },
+                                }
                             }
                         }
 
-                        // To the children!
+                        // To the children! Those of a `StaticNode` don't have vnodes
                         let mut child_node_index = 0;
-                        {
+                        if precompiled.is_none() {
                             let mut new_acc = WebWriterAcc {
-                                system_ptr: acc.system_ptr,
+                                app: acc.app,
                                 keys: keys,
                                 document: acc.document,
                                 parent_element: &vnode.web_element,
                                 node_level: &mut vnode.children,
                                 node_index: &mut child_node_index,
+                                listeners_only: skip,
+                                #[cfg(debug_assertions)]
+                                path: path.clone(),
                             };
                             node.children().process_all::<WebWriter<D, M, U, R, S>>(&mut new_acc)?;
                         }
-                        // Remove DOM elements left over from the last render that weren't repurposed
-                        while child_node_index < vnode.children.len() {
-                            let unused_dom_element = vnode.children.pop().unwrap();
-                            unused_dom_element.web_element.remove_self();
+                        if skip {
+                            // Leave elements without a node in place, but stop them sending
+                            // messages from listeners of the previous render
+                            for unmatched in vnode.children.iter_mut().skip(child_node_index) {
+                                detach_listeners(unmatched);
+                            }
+                        } else {
+                            // Remove DOM elements left over from the last render that weren't
+                            // repurposed
+                            while child_node_index < vnode.children.len() {
+                                let unused_dom_element = vnode.children.pop().unwrap();
+                                unused_dom_element.web_element.remove_self();
+                            }
+                            sync_controlled_properties(&vnode.web_element, node);
                         }
                     }
 
@@ -1094,31 +5365,45 @@ mod private {
                 } else {
                     // Construct as a new element
 
-                    let html_element = match node.value() {
-                        DomValue::Element { tag } => {
-                            acc.document.create_element(tag).unwrap()},
-                        DomValue::Text(text) =>
-                            acc.document.create_text_node(text).unwrap(),
+                    let html_element = match vnode_value {
+                        VNodeValue::Static(_) =>
+                            acc.document.create_from_template(precompiled.unwrap())?,
+                        VNodeValue::Tag(tag) => acc.document.create_element(tag)?,
+                        VNodeValue::Text(ref text) => acc.document.create_text_node(text)?,
+                        VNodeValue::Comment(ref text) => acc.document.create_comment(text)?,
                     };
 
                     let mut listeners_with_metadata = Vec::new();
                     for listener in listeners {
                         unsafe {
                             let event_type = (*listener).event_type_handled();
-                            let element = html_element.set_listener(
+                            let (element, handler) = html_element.set_listener::<D, M, U, R, S>(
                                 event_type,
                                 listener,
-                                acc.system_ptr,
+                                acc.app,
                                 keys
                             );
-                            listeners_with_metadata.push((element, listener, event_type));
+                            listeners_with_metadata.push((element, listener, event_type, handler));
                         }
                     }
 
                     let mut vnode_attributes = Vec::new();
-                    for attr in node.attributes() {
-                        html_element.set_attribute(attr);
-                        vnode_attributes.push(attr.clone());
+                    for attr in node.merged_attributes() {
+                        // Elements created from a template already have their attributes
+                        let controlled = match vnode_value {
+                            VNodeValue::Tag(tag) => is_controlled_property(tag, attr.0),
+                            _ => false,
+                        };
+                        if precompiled.is_none() && !controlled {
+                            html_element.set_attribute(&attr);
+                        }
+                        vnode_attributes.push(attr.into_owned());
+                    }
+
+                    #[cfg(debug_assertions)]
+                    {
+                        stamp_keys(&html_element, keys);
+                        stamp_path(&html_element, &path);
                     }
 
                     let mut vnode = VDomNode {
@@ -1128,47 +5413,281 @@ mod private {
                         attributes: vnode_attributes,
                         listeners: listeners_with_metadata,
                         children: Vec::new(),
+                        props_hash: node.props_hash(),
+                        widget: node.widget().map(|widget| WidgetSlot::mount_later(widget.mount())),
+                        #[cfg(debug_assertions)]
+                        path: path.clone(),
+                        #[cfg(debug_assertions)]
+                        recreated_listeners: 0,
+                    };
+
+                    // The children of elements created from a template were created with them
+                    if precompiled.is_none() {
+                        let mut child_node_index = 0;
+                        {
+                            let mut new_acc = WebWriterAcc {
+                                app: acc.app,
+                                keys: keys,
+                                document: acc.document,
+                                parent_element: &vnode.web_element,
+                                node_level: &mut vnode.children,
+                                node_index: &mut child_node_index,
+                                listeners_only: false,
+                                #[cfg(debug_assertions)]
+                                path: path.clone(),
+                            };
+                            node.children().process_all::<WebWriter<D, M, U, R, S>>(&mut new_acc)?;
+                        }
+                        // Remove DOM elements left over from the last render that weren't repurposed
+                        while child_node_index < vnode.children.len() {
+                            let unused_dom_element = vnode.children.pop().unwrap();
+                            unused_dom_element.web_element.remove_self();
+                        }
+                        sync_controlled_properties(&vnode.web_element, node);
+                    }
+
+                    acc.parent_element.insert(*acc.node_index, &vnode.web_element);
+                    acc.node_level.insert(*acc.node_index, vnode);
+                }
+
+                *acc.node_index += 1;
+                Ok(())
+            }
+
+            add_node::<T, D, M, U, R, S>
+        }
+    }
+
+    /// Writes each node under the root at the same position, for applications started with
+    /// `run_multi`
+    struct RegionWriter<'a, 'n, D, M, U, R, S>(
+        PhantomData<(&'a (), &'n (), D, M, U, R, S)>
+    );
+    struct RegionWriterAcc<'n, M> where M: 'static {
+        app: AppId,
+        document: WebDocument,
+        roots: &'n mut VDOMLevel<M>,
+        // Position of the next region
+        index: usize,
+    }
+
+    impl<'a, 'n, D, M, U, R, S> DomNodeProcessor<'a, M> for RegionWriter<'a, 'n, D, M, U, R, S>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        type Acc = RegionWriterAcc<'n, M>;
+        type Error = Error;
+
+        fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+            fn add_region<'a, 'n, T, D, M, U, R, S>(
+                acc: &mut RegionWriterAcc<'n, M>,
+                node: &'a T) -> Result<(), Error>
+                where
+                T: DomNode<M>,
+                D: DomNode<M> + 'static,
+                M: 'static,
+                U: Updater<S, M> + 'static,
+                R: StaticRenderer<S, M, Rendered=D> + 'static,
+                S: 'static,
+            {
+                let root = &mut acc.roots[acc.index];
+                let mut node_index = 0;
+                {
+                    let mut input = WebWriterAcc {
+                        app: acc.app,
+                        document: acc.document,
+                        keys: Keys::new(),
+                        parent_element: &root.web_element,
+                        node_level: &mut root.children,
+                        node_index: &mut node_index,
+                        listeners_only: false,
+                        #[cfg(debug_assertions)]
+                        path: acc.index.to_string(),
                     };
+                    node.process_all::<WebWriter<D, M, U, R, S>>(&mut input)?;
+                }
+                // Remove the region's previous contents if they weren't repurposed
+                while node_index < root.children.len() {
+                    let unused_dom_element = root.children.pop().unwrap();
+                    unused_dom_element.web_element.remove_self();
+                }
+                acc.index += 1;
+                Ok(())
+            }
+
+            add_region::<T, D, M, U, R, S>
+        }
+    }
+
+    /// Adopts the elements of a server-rendered page as the elements of a rendered node, for
+    /// applications started with `hydrate`
+    struct Hydrator<'a, 'n, D, M, U, R, S>(
+        PhantomData<(&'a (), &'n (), D, M, U, R, S)>
+    );
+    struct HydrationAcc<'n, M> where M: 'static {
+        app: AppId,
+        keys: Keys,
+        parent_element: &'n WebElement,
+        node_level: &'n mut VDOMLevel<M>,
+        // The `HYDRATION_ID_ATTRIBUTE` of the next element with listeners
+        next_id: &'n mut usize,
+        // Position of `parent_element` in the tree
+        #[cfg(debug_assertions)]
+        path: String,
+    }
+
+    /// Describes a node for `Error::HydrationMismatch`
+    fn describe_vnode(value: &VNodeValue) -> String {
+        match *value {
+            VNodeValue::Tag(tag) => format!("<{}>", tag),
+            VNodeValue::Text(ref text) => format!("text {:?}", text),
+            VNodeValue::Comment(_) => "comment".to_string(),
+            VNodeValue::Static(_) => "static node".to_string(),
+        }
+    }
+
+    impl<'a, 'n, D, M, U, R, S> DomNodeProcessor<'a, M> for Hydrator<'a, 'n, D, M, U, R, S>
+        where
+        D: DomNode<M> + 'static,
+        M: 'static,
+        U: Updater<S, M> + 'static,
+        R: StaticRenderer<S, M, Rendered=D> + 'static,
+        S: 'static,
+    {
+        type Acc = HydrationAcc<'n, M>;
+        type Error = Error;
+
+        fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &'a T) -> Result<(), Self::Error> {
+            fn add_node<'a, 'n, T, D, M, U, R, S>(
+                acc: &mut HydrationAcc<'n, M>,
+                node: &'a T) -> Result<(), Error>
+                where
+                T: DomNode<M>,
+                D: DomNode<M> + 'static,
+                M: 'static,
+                U: Updater<S, M> + 'static,
+                R: StaticRenderer<S, M, Rendered=D> + 'static,
+                S: 'static,
+            {
+                let precompiled = node.precompiled();
+                let vnode_value = match (precompiled, node.value()) {
+                    (Some(precompiled), _) => VNodeValue::Static(precompiled.hash()),
+                    (None, DomValue::Element { tag }) => VNodeValue::Tag(tag),
+                    (None, DomValue::Comment(text)) => VNodeValue::Comment(text.to_string()),
+                    (None, DomValue::Doctype(_)) => VNodeValue::Comment(String::new()),
+                    (None, text) => VNodeValue::Text(text.into_text().unwrap()),
+                };
+
+                let keys = if let Some(new_key) = node.key() {
+                    acc.keys.push(new_key)
+                } else {
+                    acc.keys
+                };
+
+                let listeners = {
+                    let mut listeners = Vec::new();
+                    node.listeners().process_all::<ListenersToVec<M>>(&mut listeners)?;
+                    listeners
+                };
+
+                // Numbered in the same order as by `write_hydratable`
+                let hydration_id = match vnode_value {
+                    VNodeValue::Tag(_) if !listeners.is_empty() => {
+                        *acc.next_id += 1;
+                        Some(*acc.next_id - 1)
+                    },
+                    _ => None,
+                };
 
-                    let mut child_node_index = 0;
+                let node_index = acc.node_level.len();
+                let html_element = acc.parent_element.hydrate_child(node_index, &vnode_value, hydration_id)?
+                    .ok_or_else(|| Error::HydrationMismatch(describe_vnode(&vnode_value)))?;
+
+                #[cfg(debug_assertions)]
+                let path = if acc.path.is_empty() {
+                    node_index.to_string()
+                } else {
+                    format!("{}.{}", acc.path, node_index)
+                };
+
+                let mut listeners_with_metadata = Vec::new();
+                for listener in listeners {
+                    unsafe {
+                        let event_type = (*listener).event_type_handled();
+                        let (element, handler) = html_element.set_listener::<D, M, U, R, S>(
+                            event_type,
+                            listener,
+                            acc.app,
+                            keys
+                        );
+                        listeners_with_metadata.push((element, listener, event_type, handler));
+                    }
+                }
+
+                #[cfg(debug_assertions)]
+                {
+                    stamp_keys(&html_element, keys);
+                    stamp_path(&html_element, &path);
+                }
+
+                let mut vnode = VDomNode {
+                    value: vnode_value,
+                    keys: keys,
+                    web_element: html_element,
+                    attributes: node.merged_attributes().map(|attr| attr.into_owned()).collect(),
+                    listeners: listeners_with_metadata,
+                    children: Vec::new(),
+                    props_hash: node.props_hash(),
+                    widget: node.widget().map(|widget| WidgetSlot::mount_later(widget.mount())),
+                    #[cfg(debug_assertions)]
+                    path: path.clone(),
+                    #[cfg(debug_assertions)]
+                    recreated_listeners: 0,
+                };
+
+                // The children of a `StaticNode` don't have vnodes
+                if precompiled.is_none() {
                     {
-                        let mut new_acc = WebWriterAcc {
-                            system_ptr: acc.system_ptr,
+                        let mut new_acc = HydrationAcc {
+                            app: acc.app,
                             keys: keys,
-                            document: acc.document,
                             parent_element: &vnode.web_element,
                             node_level: &mut vnode.children,
-                            node_index: &mut child_node_index,
+                            next_id: &mut *acc.next_id,
+                            #[cfg(debug_assertions)]
+                            path: path,
                         };
-                        node.children().process_all::<WebWriter<D, M, U, R, S>>(&mut new_acc)?;
+                        node.children().process_all::<Hydrator<D, M, U, R, S>>(&mut new_acc)?;
                     }
-                    // Remove DOM elements left over from the last render that weren't repurposed
-                    while child_node_index < vnode.children.len() {
-                        let unused_dom_element = vnode.children.pop().unwrap();
-                        unused_dom_element.web_element.remove_self();
+                    if vnode.web_element.child_count() != vnode.children.len() {
+                        // Keep the vnode, so that its listeners are removed with the others
+                        acc.node_level.push(vnode);
+                        return Err(Error::HydrationMismatch(format!("end of {}",
+                            describe_vnode(&acc.node_level[node_index].value))));
                     }
-
-                    acc.parent_element.insert(*acc.node_index, &vnode.web_element);
-                    acc.node_level.insert(*acc.node_index, vnode);
                 }
 
-                *acc.node_index += 1;
+                acc.node_level.push(vnode);
                 Ok(())
             }
 
-            add_node
+            add_node::<T, D, M, U, R, S>
         }
     }
 
     struct ListenersToVec<Message: 'static>(PhantomData<Message>);
     impl<'a, M: 'static> ListenerProcessor<'a, M> for ListenersToVec<M> {
         type Acc = Vec<*const Listener<M>>;
-        type Error = ();
+        type Error = Error;
 
         fn get_processor<L: Listener<M>>() -> fn(&mut Self::Acc, &'a L) -> Result<(), Self::Error> {
             fn add_listener_to_vec<M, L: Listener<M>>(
                 vec: &mut Vec<*const Listener<M>>,
-                listener: &L) -> Result<(), ()>
+                listener: &L) -> Result<(), Error>
             {
                 vec.push(
                     // Extend the lifetime of the listener (we know it's valid until at least the
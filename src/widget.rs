@@ -0,0 +1,187 @@
+//! Adapters for embedding widgets from JS libraries, such as maps or code editors.
+//!
+//! A `JsWidget` wraps a library's widget so that it can be used like any other `DomNode`.
+//! `widget` creates a node whose element the web renderer creates once and then leaves to the
+//! widget. After the render which creates the element, the widget is initialized with the
+//! element's `ElementRef`. Whenever the node is rendered with props which hash differently, the
+//! widget is updated with the new props, and once the element is removed the widget is
+//! destroyed.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::{DomNode, ElementRef};
+//! use domafic::widget::{widget, JsWidget};
+//!
+//! #[derive(Hash, Clone)]
+//! struct MapProps { lat: i32, lng: i32, zoom: u8 }
+//!
+//! struct Map { element: ElementRef }
+//!
+//! impl JsWidget for Map {
+//!     type Props = MapProps;
+//!
+//!     fn init(element: ElementRef, props: &MapProps) -> Map {
+//!         // Create the map in `__domafic_find_ref(element.name())` with JS ...
+//!         let _ = props;
+//!         Map { element }
+//!     }
+//!
+//!     fn update(&mut self, props: &MapProps) {
+//!         // ... move it to `props` ...
+//!         let _ = props;
+//!     }
+//!
+//!     fn destroy(&mut self) {
+//!         // ... and remove it
+//!     }
+//! }
+//!
+//! const MAP: ElementRef = ElementRef("map");
+//!
+//! let node = widget::<(), Map>(MAP, MapProps { lat: 51, lng: 0, zoom: 9 });
+//! assert_eq!(node.to_string(), "<div data-domafic-ref=\"map\"></div>");
+//! ```
+
+use {DomNode, DomNodes, DomValue, ElementRef, KeyValue};
+use processors::{DomNodeProcessor, EmptyListeners};
+
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// A widget from a JS library which manages the contents of an element.
+pub trait JsWidget: Sized + 'static {
+    /// The values the widget is rendered from
+    type Props: Hash + Clone + 'static;
+
+    /// Creates the widget in `element`, which has just been added to the page
+    fn init(element: ElementRef, props: &Self::Props) -> Self;
+
+    /// Updates the widget after it's rendered with props which hash differently from those
+    /// it was last given
+    fn update(&mut self, props: &Self::Props);
+
+    /// Removes the widget, after its element has been removed from the page
+    fn destroy(&mut self);
+}
+
+/// A `JsWidget` created by the web renderer
+pub trait MountedWidget {
+    /// Removes the widget
+    fn destroy(&mut self);
+
+    /// The widget, which can be downcast to its `JsWidget` type
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<W: JsWidget> MountedWidget for W {
+    fn destroy(&mut self) {
+        JsWidget::destroy(self)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A node whose element is managed by a `JsWidget`, as seen by the web renderer
+pub trait WidgetNode {
+    /// Returns a function creating the node's widget, to be called once its element has been
+    /// added to the page
+    fn mount(&self) -> Box<dyn FnOnce() -> Box<dyn MountedWidget>>;
+
+    /// Passes the node's props to `widget`. Returns `false` without updating `widget` if it was
+    /// created by a node with a different type of widget.
+    fn update(&self, widget: &mut dyn MountedWidget) -> bool;
+}
+
+/// Creates a node whose element is managed by a `W` widget with `props`.
+///
+/// The node is an empty `div` marked with `element`, which is passed to the widget when it's
+/// created. Each widget on the page should have its own `ElementRef`.
+pub fn widget<M, W: JsWidget>(element: ElementRef, props: W::Props) -> Widget<M, W> {
+    let mut hasher = DefaultHasher::new();
+    props.hash(&mut hasher);
+    Widget {
+        element,
+        attributes: [element.attribute()],
+        props,
+        props_hash: hasher.finish(),
+        _marker: PhantomData,
+    }
+}
+
+/// A `DomNode` whose element is managed by a `JsWidget`
+pub struct Widget<M, W: JsWidget> {
+    element: ElementRef,
+    attributes: [KeyValue; 1],
+    props: W::Props,
+    props_hash: u64,
+    _marker: PhantomData<(M, W)>,
+}
+
+impl<M, W: JsWidget> Widget<M, W> {
+    /// The props the widget is rendered with
+    pub fn props(&self) -> &W::Props {
+        &self.props
+    }
+}
+
+impl<M, W: JsWidget> WidgetNode for Widget<M, W> {
+    fn mount(&self) -> Box<dyn FnOnce() -> Box<dyn MountedWidget>> {
+        let element = self.element;
+        let props = self.props.clone();
+        Box::new(move || Box::new(W::init(element, &props)) as Box<dyn MountedWidget>)
+    }
+
+    fn update(&self, widget: &mut dyn MountedWidget) -> bool {
+        match widget.as_any_mut().downcast_mut::<W>() {
+            Some(widget) => { widget.update(&self.props); true },
+            None => false,
+        }
+    }
+}
+
+static EMPTY_NODES_REF: &() = &();
+static EMPTY_LISTN_REF: &EmptyListeners = &EmptyListeners;
+
+impl<M, W: JsWidget> DomNodes<M> for Widget<M, W> {
+    fn process_all<'a, P: DomNodeProcessor<'a, M>>(&'a self, acc: &mut P::Acc) -> Result<(), P::Error> {
+        P::get_processor()(acc, self)
+    }
+}
+
+impl<M, W: JsWidget> DomNode<M> for Widget<M, W> {
+    type Children = ();
+    type Listeners = EmptyListeners;
+    type WithoutListeners = Self;
+    fn key(&self) -> Option<u32> { None }
+    fn get_attribute(&self, index: usize) -> Option<&KeyValue> {
+        self.attributes.get(index)
+    }
+    fn children(&self) -> &Self::Children {
+        EMPTY_NODES_REF
+    }
+    fn listeners(&self) -> &Self::Listeners {
+        EMPTY_LISTN_REF
+    }
+    fn children_and_listeners(&self) -> (&Self::Children, &Self::Listeners) {
+        (EMPTY_NODES_REF, EMPTY_LISTN_REF)
+    }
+    fn split_listeners(self) -> (Self::WithoutListeners, Self::Listeners) {
+        (self, EmptyListeners)
+    }
+    fn value(&self) -> DomValue<'_> { DomValue::Element { tag: "div" } }
+    fn props_hash(&self) -> Option<u64> { Some(self.props_hash) }
+    fn should_update(&self, old_props_hash: u64) -> bool { old_props_hash != self.props_hash }
+    fn widget(&self) -> Option<&dyn WidgetNode> { Some(self) }
+}
+
+impl<M, W: JsWidget> fmt::Display for Widget<M, W> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.displayable().fmt(formatter)
+    }
+}
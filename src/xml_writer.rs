@@ -0,0 +1,373 @@
+//! Types, traits and functions for writing a `DomNode` to HTML, XHTML or XML.
+//!
+//! `XmlWriter` is parameterized by a `Dialect`, which decides how elements without children
+//! are written. `HtmlWriter` is an `XmlWriter` using the `Html` dialect.
+//!
+//! `XmlDocument` writes a complete XML document: the XML declaration, any processing
+//! instructions, and a root element with namespace declarations.
+//!
+//! Example:
+//!
+//! ```rust
+//! use domafic::DomNodes;
+//! use domafic::tags::{br, div, p};
+//! use domafic::xml_writer::{Xhtml, XmlDocument, XmlWriter};
+//! use std::marker::PhantomData;
+//!
+//! let page = div((p("Hello"), br(PhantomData::<()>)));
+//!
+//! let mut html = Vec::new();
+//! page.process_all::<XmlWriter<_, Xhtml>>(&mut html).unwrap();
+//! assert_eq!(String::from_utf8(html).unwrap(), "<div><p>Hello</p><br/></div>");
+//!
+//! let mut xml = Vec::new();
+//! XmlDocument::new()
+//!     .processing_instruction("xml-stylesheet", "type=\"text/css\" href=\"/feed.css\"")
+//!     .namespace(None, "http://www.w3.org/1999/xhtml")
+//!     .write::<_, _, _, Xhtml>(&mut xml, &page)
+//!     .unwrap();
+//! assert_eq!(String::from_utf8(xml).unwrap(), "\
+//!     <?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+//!     <?xml-stylesheet type=\"text/css\" href=\"/feed.css\"?>\
+//!     <div xmlns=\"http://www.w3.org/1999/xhtml\"><p>Hello</p><br/></div>");
+//! ```
+
+extern crate marksman_escape;
+use self::marksman_escape::Escape;
+
+use {AttributeValue, DomNode, DomNodes, DomValue};
+use processors::DomNodeProcessor;
+
+// This module as a whole is "use_std"-only, so these don't need to be cfg'd
+use std::borrow::Cow;
+use std::io;
+use std::marker::PhantomData;
+
+/// How an element without children is written
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum EmptyElement {
+    /// With an opening and closing tag: `<div></div>`
+    Closed,
+    /// With a self-closing tag: `<br/>`
+    SelfClosed,
+    /// With only an opening tag, as for HTML void elements: `<br>`
+    Void,
+}
+
+/// The syntax rules used by an `XmlWriter`
+pub trait Dialect {
+    /// Returns how an element with the tag `tag` and no children is written
+    fn empty_element(tag: &str) -> EmptyElement;
+
+    /// Whether attributes are written in the canonical order described by `Canonical`, rather
+    /// than in the order they were given
+    fn canonical_attributes() -> bool { false }
+
+    /// Whether the HTML of `StaticNode`s, which is precompiled with `Html`, can be copied as-is
+    fn copies_precompiled() -> bool { false }
+
+    /// Whether a boolean attribute which is set is written by its name alone, as HTML allows,
+    /// rather than with its name as its value: `disabled="disabled"`
+    fn minimizes_boolean_attributes() -> bool { false }
+}
+
+/// Elements which can't have children in HTML
+pub const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "keygen", "link", "meta",
+    "param", "source", "track", "wbr",
+];
+
+/// HTML syntax: void elements are written without closing tags
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Html;
+impl Dialect for Html {
+    fn empty_element(tag: &str) -> EmptyElement {
+        if VOID_ELEMENTS.contains(&tag) { EmptyElement::Void } else { EmptyElement::Closed }
+    }
+
+    fn copies_precompiled() -> bool { true }
+
+    fn minimizes_boolean_attributes() -> bool { true }
+}
+
+/// XHTML syntax, as used by AMP pages and feeds embedding HTML: void elements self-close and
+/// other elements are always closed, so the output is also valid HTML
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Xhtml;
+impl Dialect for Xhtml {
+    fn empty_element(tag: &str) -> EmptyElement {
+        if VOID_ELEMENTS.contains(&tag) { EmptyElement::SelfClosed } else { EmptyElement::Closed }
+    }
+}
+
+/// XML syntax: all elements without children self-close
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Xml;
+impl Dialect for Xml {
+    fn empty_element(_tag: &str) -> EmptyElement {
+        EmptyElement::SelfClosed
+    }
+}
+
+/// The syntax of `D`, with attributes sorted by name and the classes within `class`
+/// attributes sorted and deduplicated.
+///
+/// Pages written this way repeat the same byte sequences wherever the same elements appear,
+/// regardless of the order their attributes were given in, so they compress better with gzip
+/// or deflate.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Canonical<D: Dialect>(PhantomData<D>);
+impl<D: Dialect> Dialect for Canonical<D> {
+    fn empty_element(tag: &str) -> EmptyElement {
+        D::empty_element(tag)
+    }
+
+    fn canonical_attributes() -> bool { true }
+
+    fn minimizes_boolean_attributes() -> bool { D::minimizes_boolean_attributes() }
+}
+
+/// Type to use for processing a `DomNode` tree and writing it using the syntax of `D`.
+///
+/// This type should not ever need to be instantiated. Instead, simply
+/// name the type in calls to `DomNodes::process_all::<XmlWriter<...>>(...)`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct XmlWriter<W: io::Write, D: Dialect>(PhantomData<(W, D)>);
+impl<'a, M, W: io::Write, D: Dialect> DomNodeProcessor<'a, M> for XmlWriter<W, D> {
+    type Acc = W;
+    type Error = io::Error;
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn add_node<M, W, D, T>(w: &mut W, node: &T) -> Result<(), io::Error>
+                where W: io::Write, D: Dialect, T: DomNode<M> {
+            if let Some(precompiled) = node.precompiled().filter(|_| D::copies_precompiled()) {
+                return w.write_all(precompiled.html().as_bytes());
+            }
+            match node.value() {
+                DomValue::Element { tag } => write_element::<M, W, D, T>(w, tag, node, &[]),
+                DomValue::Text(text) => write_escaped(w, text),
+                DomValue::OwnedText(text) => write_escaped(w, &text),
+                DomValue::Comment(text) => write_comment(w, text),
+                DomValue::Doctype(name) => write!(w, "<!DOCTYPE {}>", name),
+            }
+        }
+        add_node::<M, W, D, T>
+    }
+}
+
+/// Writes an element, adding `extra_attributes` after the node's own attributes
+fn write_element<M, W, D, T>(
+    w: &mut W,
+    tag: &str,
+    node: &T,
+    extra_attributes: &[(String, String)],
+) -> Result<(), io::Error>
+    where W: io::Write, D: Dialect, T: DomNode<M>
+{
+    write!(w, "<{}", tag)?;
+    if D::canonical_attributes() {
+        let merged: Vec<_> = node.merged_attributes().collect();
+        let attributes = merged.iter()
+            .filter_map(|attr| html_attribute(attr.0, &attr.1).map(|value| (attr.0, value)))
+            .chain(extra_attributes.iter().map(|attr| (attr.0.as_str(), Some(attr.1.as_str()))));
+        for (key, value) in canonical_attributes(attributes) {
+            write_dialect_attribute::<W, D>(w, key, value.as_deref())?;
+        }
+    } else {
+        for attr in node.merged_attributes() {
+            if let Some(value) = html_attribute(attr.0, &attr.1) {
+                write_dialect_attribute::<W, D>(w, attr.0, value)?;
+            }
+        }
+        for (key, value) in extra_attributes {
+            write_attribute(w, key, value)?;
+        }
+    }
+
+    if !has_children::<M, _>(node.children()) {
+        match D::empty_element(tag) {
+            EmptyElement::Closed => {},
+            EmptyElement::SelfClosed => return write!(w, "/>"),
+            EmptyElement::Void => return write!(w, ">"),
+        }
+    }
+
+    write!(w, ">")?;
+    node.children().process_all::<XmlWriter<W, D>>(w)?;
+    write!(w, "</{}>", tag)
+}
+
+/// Sorts attributes, given as from `html_attribute`, by name, and sorts and deduplicates the
+/// classes of `class` attributes
+pub(crate) fn canonical_attributes<'a, I>(attributes: I) -> Vec<(&'a str, Option<Cow<'a, str>>)>
+    where I: Iterator<Item = (&'a str, Option<&'a str>)>
+{
+    let mut attributes: Vec<(&str, Option<Cow<str>>)> = attributes
+        .map(|(key, value)| match value {
+            Some(value) if key == "class" => {
+                let mut classes: Vec<&str> = value.split_whitespace().collect();
+                classes.sort_unstable();
+                classes.dedup();
+                (key, Some(Cow::Owned(classes.join(" "))))
+            },
+            value => (key, value.map(Cow::Borrowed)),
+        })
+        .collect();
+    // Stable, so that repeated attributes keep their relative order
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    attributes
+}
+
+/// How an attribute is written in HTML: `None` if it's left out, as a boolean attribute which
+/// isn't set is, and otherwise its text, which is `None` for a boolean attribute which is set
+pub(crate) fn html_attribute<'v>(key: &str, value: &'v AttributeValue) -> Option<Option<&'v str>> {
+    match value.as_bool_attribute(key) {
+        Some(false) => None,
+        Some(true) => Some(None),
+        None => Some(Some(value.as_str())),
+    }
+}
+
+/// Writes an attribute as given by `html_attribute`, with `D`'s syntax for boolean attributes
+fn write_dialect_attribute<W, D>(w: &mut W, key: &str, value: Option<&str>) -> Result<(), io::Error>
+    where W: io::Write, D: Dialect
+{
+    match value {
+        Some(value) => write_attribute(w, key, value),
+        None if D::minimizes_boolean_attributes() => write!(w, " {}", key),
+        None => write_attribute(w, key, key),
+    }
+}
+
+/// Writes an attribute as HTML, leaving out boolean attributes which aren't set and writing
+/// those which are by name alone
+pub(crate) fn write_html_attribute<W: io::Write>(w: &mut W, key: &str, value: &AttributeValue) -> Result<(), io::Error> {
+    write_dialect_attribute::<W, Html>(w, key, match html_attribute(key, value) {
+        Some(value) => value,
+        None => return Ok(()),
+    })
+}
+
+pub(crate) fn write_attribute<W: io::Write>(w: &mut W, key: &str, value: &str) -> Result<(), io::Error> {
+    write!(w, " {}=\"", key)?;
+    write_escaped_attribute(w, value)?;
+    write!(w, "\"")
+}
+
+/// Writes `value` for use inside of a double-quoted attribute.
+///
+/// Only the characters which are special inside of a quoted attribute are escaped, so that
+/// values such as inline styles stay readable.
+pub(crate) fn write_escaped_attribute<W: io::Write>(w: &mut W, value: &str) -> Result<(), io::Error> {
+    let mut rest = value;
+    while let Some(index) = rest.find(&['&', '<', '"'][..]) {
+        w.write_all(&rest.as_bytes()[..index])?;
+        w.write_all(match rest.as_bytes()[index] {
+            b'&' => b"&amp;",
+            b'<' => b"&lt;",
+            _ => b"&quot;",
+        })?;
+        rest = &rest[index + 1..];
+    }
+    w.write_all(rest.as_bytes())
+}
+
+/// Writes `text` as a comment, adding spaces where it would otherwise end the comment early or
+/// be invalid in XML: between adjacent `-`s, before a leading `>` or `->` and after a trailing `-`
+pub(crate) fn write_comment<W: io::Write>(w: &mut W, text: &str) -> Result<(), io::Error> {
+    write!(w, "<!--")?;
+    if text.starts_with('>') || text.starts_with("->") {
+        write!(w, " ")?;
+    }
+    let mut previous = None;
+    for c in text.chars() {
+        if c == '-' && previous == Some('-') {
+            write!(w, " ")?;
+        }
+        write!(w, "{}", c)?;
+        previous = Some(c);
+    }
+    if previous == Some('-') {
+        write!(w, " ")?;
+    }
+    write!(w, "-->")
+}
+
+/// Writes `text` with characters that are special in HTML and XML escaped
+pub(crate) fn write_escaped<W: io::Write>(w: &mut W, text: &str) -> Result<(), io::Error> {
+    let escaped: Vec<u8> = Escape::new(text.bytes()).collect();
+    w.write_all(&escaped)
+}
+
+/// Whether `nodes` contains any nodes
+pub(crate) fn has_children<M, T: DomNodes<M>>(nodes: &T) -> bool {
+    let mut has_children = false;
+    nodes.process_all::<HasChildren>(&mut has_children).unwrap();
+    has_children
+}
+
+/// Processor which records whether there are any nodes
+struct HasChildren;
+impl<'a, M> DomNodeProcessor<'a, M> for HasChildren {
+    type Acc = bool;
+    type Error = ();
+
+    fn get_processor<T: DomNode<M>>() -> fn(&mut Self::Acc, &T) -> Result<(), Self::Error> {
+        fn has_child<T>(acc: &mut bool, _node: &T) -> Result<(), ()> {
+            *acc = true;
+            Ok(())
+        }
+        has_child::<T>
+    }
+}
+
+/// Builder for writing a `DomNode` as the root element of an XML document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XmlDocument {
+    processing_instructions: Vec<(String, String)>,
+    namespaces: Vec<(String, String)>,
+}
+
+impl XmlDocument {
+    /// Creates a document with only the XML declaration
+    pub fn new() -> XmlDocument {
+        XmlDocument::default()
+    }
+
+    /// Adds a processing instruction, such as `<?xml-stylesheet href="style.css"?>`, after
+    /// the XML declaration
+    pub fn processing_instruction(mut self, target: &str, data: &str) -> XmlDocument {
+        self.processing_instructions.push((target.to_string(), data.to_string()));
+        self
+    }
+
+    /// Declares a namespace on the root element. If `prefix` is `None`, `uri` becomes the
+    /// default namespace.
+    pub fn namespace(mut self, prefix: Option<&str>, uri: &str) -> XmlDocument {
+        let key = match prefix {
+            Some(prefix) => format!("xmlns:{}", prefix),
+            None => "xmlns".to_string(),
+        };
+        self.namespaces.push((key, uri.to_string()));
+        self
+    }
+
+    /// Writes the document with `root` as its root element, using the syntax of `D`.
+    ///
+    /// If `root` is a text node, only its text is written after the prolog.
+    pub fn write<W, M, T, D>(&self, w: &mut W, root: &T) -> Result<(), io::Error>
+        where W: io::Write, T: DomNode<M>, D: Dialect
+    {
+        write!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        for (target, data) in &self.processing_instructions {
+            write!(w, "<?{} {}?>", target, data)?;
+        }
+        match root.value() {
+            DomValue::Element { tag } => write_element::<M, W, D, T>(w, tag, root, &self.namespaces),
+            DomValue::Text(text) => write_escaped(w, text),
+            DomValue::OwnedText(text) => write_escaped(w, &text),
+            DomValue::Comment(text) => write_comment(w, text),
+            DomValue::Doctype(name) => write!(w, "<!DOCTYPE {}>", name),
+        }
+    }
+}